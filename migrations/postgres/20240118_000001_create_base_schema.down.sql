@@ -0,0 +1,4 @@
+DROP TABLE item_modifiers;
+DROP TABLE collected_items;
+DROP TABLE modifiers;
+DROP TABLE base_items;
@@ -0,0 +1,51 @@
+-- Postgres counterpart of migrations/sqlite/20240118_000001_create_base_schema.sql.
+-- Timestamps stay TEXT in SQLite's 'YYYY-MM-DD HH:MM:SS' format so both backends sort
+-- and export them the same way.
+CREATE TABLE base_items (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    category TEXT NOT NULL,                    -- Stores ItemCategory as string
+    stat_requirements TEXT NOT NULL,           -- Stores StatRequirements as JSON
+    implicit_modifiers TEXT NOT NULL,          -- Stores Vec<String> as JSON
+    base_level BIGINT NOT NULL,
+    tags TEXT NOT NULL,                        -- Stores Vec<String> as JSON
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE modifiers (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    tier BIGINT,
+    modifier_values TEXT NOT NULL,             -- Stores Vec<f64> as JSON
+    is_crafted BOOLEAN NOT NULL DEFAULT FALSE,
+    stat_requirements TEXT,                    -- Stores Option<ModifierStatRequirements> as JSON
+    attribute_scaling TEXT,                    -- Stores Option<HashMap<CoreAttribute, f64>> as JSON
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE collected_items (
+    id BIGSERIAL PRIMARY KEY,
+    trade_id TEXT UNIQUE NOT NULL,             -- Maps to Item.id
+    base_item_id BIGINT NOT NULL REFERENCES base_items(id),
+    name TEXT,
+    price_amount DOUBLE PRECISION,
+    price_currency TEXT,
+    stats TEXT NOT NULL,                       -- Stores HashMap<String, f64> as JSON
+    corrupted BOOLEAN NOT NULL DEFAULT FALSE,
+    stat_requirements TEXT NOT NULL,           -- Stores StatRequirements as JSON
+    attribute_values TEXT NOT NULL,            -- Stores HashMap<CoreAttribute, u32> as JSON
+    collected_at TEXT NOT NULL
+);
+
+CREATE TABLE item_modifiers (
+    item_id BIGINT NOT NULL REFERENCES collected_items(id),
+    modifier_id BIGINT NOT NULL REFERENCES modifiers(id),
+    modifier_values TEXT NOT NULL,             -- Stores Vec<f64> as JSON
+    PRIMARY KEY (item_id, modifier_id)
+);
+
+CREATE INDEX idx_collected_items_collected_at ON collected_items(collected_at);
+CREATE INDEX idx_base_items_name ON base_items(name);
+CREATE INDEX idx_modifiers_name ON modifiers(name);
+CREATE INDEX idx_collected_items_trade_id ON collected_items(trade_id);
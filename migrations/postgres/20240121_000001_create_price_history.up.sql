@@ -0,0 +1,14 @@
+-- Daily price aggregates per modifier or base type, for tracking prices over a league
+CREATE TABLE price_history (
+    id BIGSERIAL PRIMARY KEY,
+    day TEXT NOT NULL,                         -- UTC date, YYYY-MM-DD
+    subject_kind TEXT NOT NULL,                -- 'modifier' or 'base'
+    subject TEXT NOT NULL,                     -- Modifier name or base type name
+    median DOUBLE PRECISION NOT NULL,          -- Prices in the rates file's base currency
+    p25 DOUBLE PRECISION NOT NULL,
+    p75 DOUBLE PRECISION NOT NULL,
+    volume BIGINT NOT NULL,                    -- Listings the aggregate was taken over
+    UNIQUE (day, subject_kind, subject)
+);
+
+CREATE INDEX idx_price_history_subject ON price_history(subject_kind, subject, day);
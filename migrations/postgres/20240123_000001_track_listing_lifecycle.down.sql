@@ -0,0 +1,3 @@
+DROP INDEX idx_collected_items_delisted_at;
+ALTER TABLE collected_items DROP COLUMN delisted_at;
+DROP TABLE collection_runs;
@@ -0,0 +1,26 @@
+-- Back to modifiers unique by display text. Rows sharing a text are merged into the oldest
+-- one; a listing that had several of them keeps the values of the first.
+DROP TABLE modifier_stat_hashes;
+
+CREATE TEMPORARY TABLE modifier_merges AS
+SELECT id AS old_id, MIN(id) OVER (PARTITION BY name) AS new_id FROM modifiers;
+
+DELETE FROM item_modifiers im
+USING modifier_merges mm
+WHERE im.modifier_id = mm.old_id
+  AND EXISTS (
+      SELECT 1 FROM item_modifiers other
+      JOIN modifier_merges om ON om.old_id = other.modifier_id
+      WHERE other.item_id = im.item_id
+        AND om.new_id = mm.new_id
+        AND other.modifier_id < im.modifier_id
+  );
+UPDATE item_modifiers im SET modifier_id = mm.new_id
+FROM modifier_merges mm
+WHERE im.modifier_id = mm.old_id AND mm.old_id <> mm.new_id;
+DELETE FROM modifiers WHERE id IN (SELECT old_id FROM modifier_merges WHERE old_id <> new_id);
+DROP TABLE modifier_merges;
+
+ALTER TABLE modifiers DROP CONSTRAINT modifiers_name_stat_key_key;
+ALTER TABLE modifiers DROP COLUMN stat_key;
+ALTER TABLE modifiers ADD CONSTRAINT modifiers_name_key UNIQUE (name);
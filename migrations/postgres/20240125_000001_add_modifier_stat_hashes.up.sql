@@ -0,0 +1,13 @@
+-- Postgres counterpart of migrations/sqlite/20240125_000001_add_modifier_stat_hashes.sql.
+-- Modifiers are keyed by text plus the stat hashes behind it instead of text alone.
+ALTER TABLE modifiers ADD COLUMN stat_key TEXT NOT NULL DEFAULT '';
+ALTER TABLE modifiers DROP CONSTRAINT modifiers_name_key;
+ALTER TABLE modifiers ADD CONSTRAINT modifiers_name_stat_key_key UNIQUE (name, stat_key);
+
+CREATE TABLE modifier_stat_hashes (
+    modifier_id BIGINT NOT NULL REFERENCES modifiers(id),
+    stat_hash TEXT NOT NULL,
+    PRIMARY KEY (modifier_id, stat_hash)
+);
+
+CREATE INDEX idx_modifier_stat_hashes_hash ON modifier_stat_hashes(stat_hash);
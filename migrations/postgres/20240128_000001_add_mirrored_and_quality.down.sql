@@ -0,0 +1,2 @@
+ALTER TABLE collected_items DROP COLUMN quality;
+ALTER TABLE collected_items DROP COLUMN mirrored;
@@ -0,0 +1 @@
+ALTER TABLE base_items DROP COLUMN item_class;
@@ -0,0 +1,3 @@
+-- The class of a base within its category, e.g. 'Helmet' or 'Ring', as ItemClass names
+-- it. NULL where neither the base's name nor a listing of it has told it yet.
+ALTER TABLE base_items ADD COLUMN item_class TEXT;
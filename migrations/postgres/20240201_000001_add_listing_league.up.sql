@@ -0,0 +1,3 @@
+-- The trade league a listing was collected from, e.g. "Standard"; NULL for listings
+-- collected before leagues were recorded
+ALTER TABLE collected_items ADD COLUMN league TEXT;
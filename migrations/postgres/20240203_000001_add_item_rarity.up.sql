@@ -0,0 +1,3 @@
+-- Normal, Magic, Rare or Unique, as the listing resolved it; NULL for listings collected
+-- before rarity was recorded, which read back as Normal
+ALTER TABLE collected_items ADD COLUMN rarity TEXT;
@@ -0,0 +1,2 @@
+-- Listing price converted to the common base currency; NULL when no rate was known
+ALTER TABLE collected_items ADD COLUMN price_normalized REAL;
@@ -0,0 +1 @@
+ALTER TABLE collected_items DROP COLUMN last_seen_at;
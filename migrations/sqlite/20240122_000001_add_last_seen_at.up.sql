@@ -0,0 +1,4 @@
+-- When a listing was last returned by a search; collected_at stays the first sighting.
+-- trade_id is already UNIQUE, so a listing seen again updates its row instead of adding one.
+ALTER TABLE collected_items ADD COLUMN last_seen_at TEXT NOT NULL DEFAULT '';
+UPDATE collected_items SET last_seen_at = collected_at;
@@ -0,0 +1,4 @@
+-- SQLite refuses to drop an indexed column, so the index goes first
+DROP INDEX idx_collected_items_delisted_at;
+ALTER TABLE collected_items DROP COLUMN delisted_at;
+DROP TABLE collection_runs;
@@ -0,0 +1,11 @@
+-- Start of each collection run, so listings missing from the latest runs can be found
+CREATE TABLE collection_runs (
+    id INTEGER PRIMARY KEY,
+    started_at TEXT NOT NULL                   -- SQLite preferred datetime format
+);
+
+-- When a listing was judged gone from the trade site; NULL while it is still listed.
+-- collected_at is when it was first seen.
+ALTER TABLE collected_items ADD COLUMN delisted_at TEXT;
+
+CREATE INDEX idx_collected_items_delisted_at ON collected_items(delisted_at);
@@ -0,0 +1,4 @@
+-- The listing exactly as fetched from the trade API, so fields the models don't map yet
+-- (sockets, influences, notes, ...) survive and `db reprocess` can convert it again.
+-- NULL for listings stored before this column existed.
+ALTER TABLE collected_items ADD COLUMN raw_json TEXT;
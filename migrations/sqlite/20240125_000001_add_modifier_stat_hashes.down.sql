@@ -0,0 +1,43 @@
+-- Back to modifiers unique by display text. Rows sharing a text are merged into the oldest
+-- one; a listing that had several of them keeps the values of the first.
+DROP TABLE modifier_stat_hashes;
+
+CREATE TABLE modifiers_old (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    tier INTEGER,
+    modifier_values TEXT NOT NULL,             -- Stores Vec<f64> as JSON
+    is_crafted BOOLEAN NOT NULL DEFAULT FALSE,
+    stat_requirements TEXT,                    -- Stores Option<ModifierStatRequirements> as JSON
+    attribute_scaling TEXT,                    -- Stores Option<HashMap<CoreAttribute, f64>> as JSON
+    created_at TEXT NOT NULL                   -- SQLite preferred datetime format
+);
+
+INSERT INTO modifiers_old (
+    id, name, tier, modifier_values, is_crafted, stat_requirements, attribute_scaling, created_at
+)
+SELECT id, name, tier, modifier_values, is_crafted, stat_requirements, attribute_scaling, created_at
+FROM modifiers
+WHERE id IN (SELECT MIN(id) FROM modifiers GROUP BY name);
+
+CREATE TABLE item_modifiers_old (
+    item_id INTEGER NOT NULL,
+    modifier_id INTEGER NOT NULL,
+    modifier_values TEXT NOT NULL,             -- Stores Vec<f64> as JSON
+    PRIMARY KEY (item_id, modifier_id),
+    FOREIGN KEY (item_id) REFERENCES collected_items(id),
+    FOREIGN KEY (modifier_id) REFERENCES modifiers_old(id)
+);
+
+INSERT OR IGNORE INTO item_modifiers_old (item_id, modifier_id, modifier_values)
+SELECT im.item_id, (SELECT MIN(m2.id) FROM modifiers m2 WHERE m2.name = m.name), im.modifier_values
+FROM item_modifiers im
+JOIN modifiers m ON m.id = im.modifier_id
+ORDER BY im.modifier_id;
+
+DROP TABLE item_modifiers;
+DROP TABLE modifiers;
+ALTER TABLE modifiers_old RENAME TO modifiers;
+ALTER TABLE item_modifiers_old RENAME TO item_modifiers;
+
+CREATE INDEX idx_modifiers_name ON modifiers(name);
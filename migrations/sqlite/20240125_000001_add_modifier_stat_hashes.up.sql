@@ -0,0 +1,53 @@
+-- Modifiers were unique by display text, which merges different stats that happen to read
+-- the same. They are now keyed by text plus the stat hashes behind it. Rows stored before
+-- hashes were recorded keep an empty key until a listing with the same text fills it in,
+-- e.g. during `db reprocess`.
+--
+-- SQLite can't drop a UNIQUE constraint, so both modifier tables are rebuilt. item_modifiers
+-- is copied first and pointed at the new table so dropping the old one breaks no references.
+CREATE TABLE modifiers_new (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    stat_key TEXT NOT NULL DEFAULT '',         -- Sorted stat hashes joined with '|'; '' when unknown
+    tier INTEGER,
+    modifier_values TEXT NOT NULL,             -- Stores Vec<f64> as JSON
+    is_crafted BOOLEAN NOT NULL DEFAULT FALSE,
+    stat_requirements TEXT,                    -- Stores Option<ModifierStatRequirements> as JSON
+    attribute_scaling TEXT,                    -- Stores Option<HashMap<CoreAttribute, f64>> as JSON
+    created_at TEXT NOT NULL,                  -- SQLite preferred datetime format
+    UNIQUE (name, stat_key)
+);
+
+INSERT INTO modifiers_new (
+    id, name, tier, modifier_values, is_crafted, stat_requirements, attribute_scaling, created_at
+)
+SELECT id, name, tier, modifier_values, is_crafted, stat_requirements, attribute_scaling, created_at
+FROM modifiers;
+
+CREATE TABLE item_modifiers_new (
+    item_id INTEGER NOT NULL,
+    modifier_id INTEGER NOT NULL,
+    modifier_values TEXT NOT NULL,             -- Stores Vec<f64> as JSON
+    PRIMARY KEY (item_id, modifier_id),
+    FOREIGN KEY (item_id) REFERENCES collected_items(id),
+    FOREIGN KEY (modifier_id) REFERENCES modifiers_new(id)
+);
+
+INSERT INTO item_modifiers_new (item_id, modifier_id, modifier_values)
+SELECT item_id, modifier_id, modifier_values FROM item_modifiers;
+
+DROP TABLE item_modifiers;
+DROP TABLE modifiers;
+ALTER TABLE modifiers_new RENAME TO modifiers;
+ALTER TABLE item_modifiers_new RENAME TO item_modifiers;
+
+CREATE INDEX idx_modifiers_name ON modifiers(name);
+
+CREATE TABLE modifier_stat_hashes (
+    modifier_id INTEGER NOT NULL,
+    stat_hash TEXT NOT NULL,                   -- e.g. explicit.stat_3299347043
+    PRIMARY KEY (modifier_id, stat_hash),
+    FOREIGN KEY (modifier_id) REFERENCES modifiers(id)
+);
+
+CREATE INDEX idx_modifier_stat_hashes_hash ON modifier_stat_hashes(stat_hash);
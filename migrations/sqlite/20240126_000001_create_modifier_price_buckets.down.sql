@@ -0,0 +1 @@
+DROP TABLE modifier_price_buckets;
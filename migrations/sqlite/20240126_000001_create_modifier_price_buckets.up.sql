@@ -0,0 +1,15 @@
+-- Daily listing prices per modifier and range of its first value, rebuilt from the stored
+-- listings by `db refresh-aggregates` so reports don't have to scan every listing
+CREATE TABLE modifier_price_buckets (
+    modifier TEXT NOT NULL,                    -- Modifier template, e.g. '+# to maximum Life'
+    day TEXT NOT NULL,                         -- UTC date the listings were last seen, YYYY-MM-DD
+    bucket INTEGER NOT NULL,                   -- Index of the value range, 0 for the lowest
+    value_min REAL NOT NULL,                   -- Value range the bucket covers
+    value_max REAL NOT NULL,
+    listings INTEGER NOT NULL,
+    median REAL NOT NULL,                      -- Normalized prices
+    p25 REAL NOT NULL,
+    p75 REAL NOT NULL,
+    refreshed_at TEXT NOT NULL,                -- SQLite preferred datetime format
+    PRIMARY KEY (modifier, day, bucket)
+);
@@ -0,0 +1,4 @@
+-- Socket count and what fills them (runes, soul cores), as JSON, e.g.
+-- {"count":2,"contents":["Desert Rune"]}. NULL for items without sockets and for listings
+-- stored before this column existed; `db reprocess` fills it in from raw_json.
+ALTER TABLE collected_items ADD COLUMN sockets TEXT;
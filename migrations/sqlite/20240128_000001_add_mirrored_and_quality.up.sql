@@ -0,0 +1,5 @@
+-- Mirrored (the trade API's "duplicated") listings and the item's quality. Listings stored
+-- before these columns existed read as not mirrored with no quality until `db reprocess`
+-- fills them in from raw_json.
+ALTER TABLE collected_items ADD COLUMN mirrored BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE collected_items ADD COLUMN quality INTEGER;
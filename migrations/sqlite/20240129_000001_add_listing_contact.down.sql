@@ -0,0 +1,5 @@
+ALTER TABLE collected_items DROP COLUMN indexed_at;
+ALTER TABLE collected_items DROP COLUMN stash_y;
+ALTER TABLE collected_items DROP COLUMN stash_x;
+ALTER TABLE collected_items DROP COLUMN stash_name;
+ALTER TABLE collected_items DROP COLUMN whisper;
@@ -0,0 +1,8 @@
+-- How to reach the seller: the whisper the trade site offers, the stash tab and cell the
+-- item sits in, and when the site indexed the listing ('YYYY-MM-DD HH:MM:SS', UTC). NULL
+-- for listings stored before these columns existed until `db reprocess` fills them in.
+ALTER TABLE collected_items ADD COLUMN whisper TEXT;
+ALTER TABLE collected_items ADD COLUMN stash_name TEXT;
+ALTER TABLE collected_items ADD COLUMN stash_x INTEGER;
+ALTER TABLE collected_items ADD COLUMN stash_y INTEGER;
+ALTER TABLE collected_items ADD COLUMN indexed_at TEXT;
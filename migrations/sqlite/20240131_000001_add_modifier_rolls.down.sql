@@ -0,0 +1 @@
+ALTER TABLE item_modifiers DROP COLUMN modifier_rolls;
@@ -0,0 +1,3 @@
+-- JSON array of what each magnitude of a listing's modifier rolled and the range it
+-- could roll in, e.g. [{"current":45,"min":40,"max":49}]. NULL where the range isn't known.
+ALTER TABLE item_modifiers ADD COLUMN modifier_rolls TEXT;
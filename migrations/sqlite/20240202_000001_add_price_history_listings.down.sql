@@ -0,0 +1 @@
+ALTER TABLE price_history DROP COLUMN listings;
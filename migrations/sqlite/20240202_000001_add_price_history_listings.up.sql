@@ -0,0 +1,5 @@
+-- Listings of the subject seen that day, priced in a known currency or not, so supply can
+-- be told apart from the prices; volume only counts the listings the prices were taken
+-- over. Existing days get their volume, the closest they have.
+ALTER TABLE price_history ADD COLUMN listings INTEGER NOT NULL DEFAULT 0;
+UPDATE price_history SET listings = volume;
@@ -0,0 +1,96 @@
+use serde::Serialize;
+use crate::data::affix_data_loader::{AffixDatabase, AffixIssue};
+use crate::data::item_base_data_loader::BaseDataLoader;
+use crate::models::CleanedItem;
+use super::pipeline::ItemAnalyzer;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FlaggedListing {
+    pub id: String,
+    pub name: String,
+    pub base_type: String,
+    pub item_level: u32,
+    pub issues: Vec<AffixIssue>,
+}
+
+// Checks listings against the affix database, flagging affixes their item class or level
+// can't roll and crafted mods. Every listing's affixes are also learned, so a run over
+// collected data can extend the database.
+pub struct AffixAnalyzer {
+    bases: BaseDataLoader,
+    database: AffixDatabase,
+    learned: AffixDatabase,
+    checked: usize,
+    // Listings whose base type isn't a known base, so their class is unknown
+    unknown_bases: usize,
+    flagged: Vec<FlaggedListing>,
+}
+
+impl AffixAnalyzer {
+    pub fn new(bases: BaseDataLoader) -> Self {
+        Self {
+            bases,
+            database: AffixDatabase::default(),
+            learned: AffixDatabase::default(),
+            checked: 0,
+            unknown_bases: 0,
+            flagged: Vec::new(),
+        }
+    }
+
+    pub fn set_database(&mut self, database: AffixDatabase) {
+        self.database = database;
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        let Some(category) = self.bases.get_base(&item.base_type).map(|base| base.category) else {
+            self.unknown_bases += 1;
+            return;
+        };
+        self.checked += 1;
+        self.learned.observe(category, item);
+
+        let issues = self.database.check(category, item);
+        if !issues.is_empty() {
+            self.flagged.push(FlaggedListing {
+                id: item.id.clone(),
+                name: item.name.clone(),
+                base_type: item.base_type.clone(),
+                item_level: item.item_level,
+                issues,
+            });
+        }
+    }
+
+    // Affixes seen on the processed listings
+    pub fn learned(&self) -> &AffixDatabase {
+        &self.learned
+    }
+
+    pub fn into_learned(self) -> AffixDatabase {
+        self.learned
+    }
+
+    pub fn flagged(&self) -> &[FlaggedListing] {
+        &self.flagged
+    }
+}
+
+impl ItemAnalyzer for AffixAnalyzer {
+    fn name(&self) -> &str {
+        "affixes"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!({
+            "listings_checked": self.checked,
+            "unknown_bases": self.unknown_bases,
+            "flagged_listings": self.flagged.len(),
+            "flagged": self.flagged,
+        })
+    }
+}
@@ -0,0 +1,345 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use crate::data::currency_converter::CurrencyConverter;
+use crate::models::item::modifier_numbers;
+use crate::models::{modifier_template, percentile, CleanedItem, CoreAttribute, ItemRarity, LinearFit};
+use super::pipeline::ItemAnalyzer;
+
+// Listings a modifier needs alongside the attribute to be reported
+pub const DEFAULT_STACKING_MIN_LISTINGS: usize = 5;
+
+// Bases reported, highest attribute totals first
+pub const DEFAULT_STACKING_BASES: usize = 10;
+
+// Width of the attribute total brackets listings are priced in
+const PRICE_BRACKET_WIDTH: f64 = 10.0;
+
+fn attribute_name(attribute: &CoreAttribute) -> &'static str {
+    match attribute {
+        CoreAttribute::Strength => "Strength",
+        CoreAttribute::Dexterity => "Dexterity",
+        CoreAttribute::Intelligence => "Intelligence",
+    }
+}
+
+// How much of `attribute` a modifier line grants, e.g. 20 for "+20 to Strength" or
+// "+20 to Strength and Dexterity", 8 for "+8 to all Attributes"
+pub fn granted_attribute(text: &str, attribute: &CoreAttribute) -> Option<f64> {
+    let (value, stat) = text.strip_prefix('+')?.split_once(" to ")?;
+    let value: f64 = value.parse().ok()?;
+    let name = attribute_name(attribute);
+    let grants = stat == "all Attributes" || stat.split(" and ").any(|part| part == name);
+    grants.then_some(value)
+}
+
+// A modifier's availability and magnitude on listings granting the attribute against the rest
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StackingModifier {
+    pub modifier: String,
+    // Listings granting the attribute that carry the modifier
+    pub listings: usize,
+    // Share of the listings granting the attribute, and of the others, that carry it
+    pub share_stacking: f64,
+    pub share_other: f64,
+    // share_stacking over share_other; None when no other listing carries it
+    pub lift: Option<f64>,
+    // The modifier's value against the attribute total, on listings with both
+    pub value_fit: Option<LinearFit>,
+    pub median_price: Option<f64>,
+}
+
+// How much of the attribute a base type reaches
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StackingBase {
+    pub base_type: String,
+    pub listings: usize,
+    pub max_attribute: f64,
+    pub median_attribute: f64,
+    pub median_price: Option<f64>,
+}
+
+// Prices of the listings whose attribute total falls in [min_attribute, min_attribute + 10)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StackingPriceBracket {
+    pub min_attribute: f64,
+    pub listings: usize,
+    pub median_price: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StackingReport {
+    pub attribute: CoreAttribute,
+    pub listings: usize,
+    // Listings with a modifier granting the attribute
+    pub stacking_listings: usize,
+    pub unconverted_listings: usize,
+    pub median_price_stacking: Option<f64>,
+    pub median_price_other: Option<f64>,
+    // Median of each stacking listing's price over its attribute total
+    pub price_per_point: Option<f64>,
+    // Most attribute-specific first
+    pub modifiers: Vec<StackingModifier>,
+    pub bases: Vec<StackingBase>,
+    pub price_brackets: Vec<StackingPriceBracket>,
+}
+
+#[derive(Default)]
+struct ModifierTally {
+    stacking: usize,
+    other: usize,
+    // (attribute total, modifier value) on stacking listings
+    points: Vec<(f64, f64)>,
+    prices: Vec<f64>,
+}
+
+#[derive(Default)]
+struct BaseTally {
+    totals: Vec<f64>,
+    prices: Vec<f64>,
+}
+
+fn median(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    Some(percentile(&sorted, 0.5))
+}
+
+// What StatAnalyzer's attribute statistics mean for a build stacking one attribute: the
+// modifiers found with it, the bases reaching the highest totals and what listings cost
+// per point. Uniques are left out, as their modifiers are fixed.
+pub struct AttributeStackAnalyzer {
+    attribute: CoreAttribute,
+    min_listings: usize,
+    top_bases: usize,
+    converter: Option<CurrencyConverter>,
+    unconverted_listings: usize,
+    listings: usize,
+    stacking_listings: usize,
+    modifiers: HashMap<String, ModifierTally>,
+    bases: HashMap<String, BaseTally>,
+    // (attribute total, price) of stacking listings
+    stacking_prices: Vec<(f64, f64)>,
+    other_prices: Vec<f64>,
+}
+
+impl AttributeStackAnalyzer {
+    pub fn new(attribute: CoreAttribute) -> Self {
+        Self {
+            attribute,
+            min_listings: DEFAULT_STACKING_MIN_LISTINGS,
+            top_bases: DEFAULT_STACKING_BASES,
+            converter: None,
+            unconverted_listings: 0,
+            listings: 0,
+            stacking_listings: 0,
+            modifiers: HashMap::new(),
+            bases: HashMap::new(),
+            stacking_prices: Vec::new(),
+            other_prices: Vec::new(),
+        }
+    }
+
+    pub fn with_min_listings(mut self, min_listings: usize) -> Self {
+        self.min_listings = min_listings;
+        self
+    }
+
+    pub fn with_top_bases(mut self, top_bases: usize) -> Self {
+        self.top_bases = top_bases;
+        self
+    }
+
+    pub fn set_currency_converter(&mut self, converter: CurrencyConverter) {
+        self.converter = Some(converter);
+    }
+
+    fn price(&mut self, item: &CleanedItem) -> Option<f64> {
+        let price = item.price.as_ref()?;
+        match &self.converter {
+            Some(converter) => {
+                let amount = converter.convert(price.amount, &price.currency);
+                if amount.is_none() {
+                    self.unconverted_listings += 1;
+                }
+                amount
+            }
+            None => Some(price.amount),
+        }
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        if item.rarity == ItemRarity::Unique {
+            return;
+        }
+        self.listings += 1;
+        let price = self.price(item);
+
+        let total: f64 = item.explicit_mods.iter().filter_map(|text| granted_attribute(text, &self.attribute)).sum();
+        let others = item.explicit_mods.iter().filter(|text| granted_attribute(text, &self.attribute).is_none());
+        if total <= 0.0 {
+            for text in others {
+                self.modifiers.entry(modifier_template(text)).or_default().other += 1;
+            }
+            self.other_prices.extend(price);
+            return;
+        }
+
+        self.stacking_listings += 1;
+        for text in others {
+            let tally = self.modifiers.entry(modifier_template(text)).or_default();
+            tally.stacking += 1;
+            if let Some(&value) = modifier_numbers(text).first() {
+                tally.points.push((total, value));
+            }
+            tally.prices.extend(price);
+        }
+        let base = self.bases.entry(item.base_type.clone()).or_default();
+        base.totals.push(total);
+        base.prices.extend(price);
+        if let Some(price) = price {
+            self.stacking_prices.push((total, price));
+        }
+    }
+
+    pub fn stacking_report(&self) -> StackingReport {
+        let share = |count: usize, of: usize| if of == 0 { 0.0 } else { count as f64 / of as f64 };
+        let other_listings = self.listings - self.stacking_listings;
+        let mut modifiers: Vec<StackingModifier> = self
+            .modifiers
+            .iter()
+            .filter(|(_, tally)| tally.stacking >= self.min_listings.max(1))
+            .map(|(modifier, tally)| {
+                let share_stacking = share(tally.stacking, self.stacking_listings);
+                let share_other = share(tally.other, other_listings);
+                StackingModifier {
+                    modifier: modifier.clone(),
+                    listings: tally.stacking,
+                    share_stacking,
+                    share_other,
+                    lift: (share_other > 0.0).then(|| share_stacking / share_other),
+                    value_fit: LinearFit::fit(&tally.points),
+                    median_price: median(&tally.prices),
+                }
+            })
+            .collect();
+        // Modifiers only ever seen with the attribute come first
+        modifiers.sort_by(|a, b| {
+            b.lift.unwrap_or(f64::INFINITY)
+                .total_cmp(&a.lift.unwrap_or(f64::INFINITY))
+                .then(b.listings.cmp(&a.listings))
+                .then(a.modifier.cmp(&b.modifier))
+        });
+
+        let mut bases: Vec<StackingBase> = self
+            .bases
+            .iter()
+            .map(|(base_type, tally)| StackingBase {
+                base_type: base_type.clone(),
+                listings: tally.totals.len(),
+                max_attribute: tally.totals.iter().copied().fold(0.0, f64::max),
+                median_attribute: median(&tally.totals).unwrap_or_default(),
+                median_price: median(&tally.prices),
+            })
+            .collect();
+        bases.sort_by(|a, b| b.max_attribute.total_cmp(&a.max_attribute).then(a.base_type.cmp(&b.base_type)));
+        bases.truncate(self.top_bases);
+
+        let mut brackets: BTreeMap<u64, Vec<f64>> = BTreeMap::new();
+        for &(total, price) in &self.stacking_prices {
+            brackets.entry((total / PRICE_BRACKET_WIDTH).floor() as u64).or_default().push(price);
+        }
+        let per_point: Vec<f64> = self.stacking_prices.iter().map(|(total, price)| price / total).collect();
+        let stacking_prices: Vec<f64> = self.stacking_prices.iter().map(|(_, price)| *price).collect();
+
+        StackingReport {
+            attribute: self.attribute.clone(),
+            listings: self.listings,
+            stacking_listings: self.stacking_listings,
+            unconverted_listings: self.unconverted_listings,
+            median_price_stacking: median(&stacking_prices),
+            median_price_other: median(&self.other_prices),
+            price_per_point: median(&per_point),
+            modifiers,
+            bases,
+            price_brackets: brackets
+                .into_iter()
+                .map(|(bracket, prices)| StackingPriceBracket {
+                    min_attribute: bracket as f64 * PRICE_BRACKET_WIDTH,
+                    listings: prices.len(),
+                    median_price: median(&prices).unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl ItemAnalyzer for AttributeStackAnalyzer {
+    fn name(&self) -> &str {
+        "stacking"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::to_value(self.stacking_report()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+
+    #[test]
+    fn test_granted_attribute() {
+        let strength = CoreAttribute::Strength;
+        assert_eq!(granted_attribute("+20 to Strength", &strength), Some(20.0));
+        assert_eq!(granted_attribute("+12 to Strength and Intelligence", &strength), Some(12.0));
+        assert_eq!(granted_attribute("+8 to all Attributes", &strength), Some(8.0));
+        assert_eq!(granted_attribute("+20 to Dexterity", &strength), None);
+        assert_eq!(granted_attribute("+80 to maximum Life", &strength), None);
+    }
+
+    #[test]
+    fn test_stacking_report() {
+        let mut analyzer = AttributeStackAnalyzer::new(CoreAttribute::Strength).with_min_listings(2);
+        let items = [
+            ("Plate Vest", vec!["+20 to Strength", "+60 to maximum Life"], 10.0),
+            ("Plate Vest", vec!["+30 to Strength", "+8 to all Attributes", "+90 to maximum Life"], 30.0),
+            ("Iron Ring", vec!["+15 to Strength", "+30% to Fire Resistance"], 4.0),
+            ("Iron Ring", vec!["+30% to Fire Resistance"], 2.0),
+            ("Iron Ring", vec!["+30% to Fire Resistance", "+50 to maximum Life"], 1.0),
+        ];
+        for (i, (base_type, mods, price)) in items.iter().enumerate() {
+            let listing = ListingBuilder::new(&format!("listing{}", i), base_type).with_mods(mods).with_price(*price);
+            analyzer.process_cleaned_item(&listing.cleaned());
+        }
+
+        let report = analyzer.stacking_report();
+        assert_eq!((report.listings, report.stacking_listings), (5, 3));
+        assert_eq!(report.median_price_stacking, Some(10.0));
+        assert_eq!(report.median_price_other, Some(1.5));
+
+        // Life comes with Strength twice as often as without, and grows with it
+        let life = &report.modifiers[0];
+        assert_eq!(life.modifier, "+# to maximum Life");
+        assert_eq!(life.listings, 2);
+        assert!((life.lift.unwrap() - (2.0 / 3.0) / 0.5).abs() < 1e-9);
+        assert!(life.value_fit.as_ref().unwrap().slope > 0.0);
+        // Fire resistance shows up on a single stacking listing, below the cutoff
+        assert_eq!(report.modifiers.len(), 1);
+
+        assert_eq!(report.bases[0].base_type, "Plate Vest");
+        assert_eq!(report.bases[0].max_attribute, 38.0);
+        assert_eq!(report.bases[0].median_price, Some(20.0));
+        assert_eq!(
+            report.price_brackets.iter().map(|bracket| (bracket.min_attribute, bracket.listings)).collect::<Vec<_>>(),
+            [(10.0, 1), (20.0, 1), (30.0, 1)]
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use plotters::prelude::*;
+use std::path::{Path, PathBuf};
+use crate::errors::{Result, ScraperError};
+use super::modifier::ModifierAnalyzer;
+use super::stat_analyzer::StatAnalyzer;
+
+// Modifiers charted when no limit is given, most common first
+pub const DEFAULT_CHART_MODIFIERS: usize = 10;
+
+const CHART_SIZE: (u32, u32) = (800, 500);
+const HISTOGRAM_BINS: usize = 20;
+
+fn chart_error(e: impl std::fmt::Display) -> ScraperError {
+    ScraperError::IoError(format!("failed to draw chart: {}", e))
+}
+
+// Axis range padded so points on the edge aren't drawn on the frame
+fn padded_range(values: impl Iterator<Item = f64>) -> Option<(f64, f64)> {
+    let (min, max) = values.fold((f64::MAX, f64::MIN), |(min, max), v| (min.min(v), max.max(v)));
+    if min > max {
+        return None;
+    }
+    let pad = if max > min { (max - min) * 0.05 } else { 1.0 };
+    Some((min - pad, max + pad))
+}
+
+// Even-width bins over the values' range as (start, end, count)
+pub fn histogram_bins(values: &[f64], bins: usize) -> Vec<(f64, f64, u32)> {
+    let (min, max) = match values.iter().fold(None, |range: Option<(f64, f64)>, &v| {
+        Some(range.map_or((v, v), |(min, max)| (min.min(v), max.max(v))))
+    }) {
+        Some(range) => range,
+        None => return Vec::new(),
+    };
+    if min == max || bins == 0 {
+        return vec![(min - 0.5, max + 0.5, values.len() as u32)];
+    }
+
+    let width = (max - min) / bins as f64;
+    let mut counts = vec![0u32; bins];
+    for &value in values {
+        let index = (((value - min) / width) as usize).min(bins - 1);
+        counts[index] += 1;
+    }
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| (min + i as f64 * width, min + (i + 1) as f64 * width, count))
+        .collect()
+}
+
+pub fn histogram_svg(title: &str, x_label: &str, values: &[f64]) -> Result<String> {
+    let bins = histogram_bins(values, HISTOGRAM_BINS);
+    let (x_min, x_max) = match (bins.first(), bins.last()) {
+        (Some(first), Some(last)) => (first.0, last.1),
+        _ => return Err(ScraperError::ValidationError(format!("no values to chart for {}", title))),
+    };
+    let y_max = bins.iter().map(|bin| bin.2).max().unwrap_or(1) + 1;
+
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, CHART_SIZE).into_drawing_area();
+        root.fill(&WHITE).map_err(chart_error)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 20))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(50)
+            .build_cartesian_2d(x_min..x_max, 0u32..y_max)
+            .map_err(chart_error)?;
+        chart.configure_mesh()
+            .x_desc(x_label)
+            .y_desc("listings")
+            .draw()
+            .map_err(chart_error)?;
+        chart.draw_series(bins.iter().map(|&(start, end, count)| {
+            Rectangle::new([(start, 0), (end, count)], BLUE.mix(0.6).filled())
+        }))
+        .map_err(chart_error)?;
+        root.present().map_err(chart_error)?;
+    }
+    Ok(svg)
+}
+
+pub fn scatter_svg(title: &str, x_label: &str, y_label: &str, points: &[(f64, f64)]) -> Result<String> {
+    let (x_range, y_range) = match (
+        padded_range(points.iter().map(|p| p.0)),
+        padded_range(points.iter().map(|p| p.1)),
+    ) {
+        (Some(x), Some(y)) => (x, y),
+        _ => return Err(ScraperError::ValidationError(format!("no points to chart for {}", title))),
+    };
+
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, CHART_SIZE).into_drawing_area();
+        root.fill(&WHITE).map_err(chart_error)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 20))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(60)
+            .build_cartesian_2d(x_range.0..x_range.1, y_range.0..y_range.1)
+            .map_err(chart_error)?;
+        chart.configure_mesh()
+            .x_desc(x_label)
+            .y_desc(y_label)
+            .draw()
+            .map_err(chart_error)?;
+        chart.draw_series(points.iter().map(|&point| Circle::new(point, 3, RED.mix(0.5).filled())))
+            .map_err(chart_error)?;
+        root.present().map_err(chart_error)?;
+    }
+    Ok(svg)
+}
+
+// Something safe to use as a file name, e.g. "+#% to Fire Resistance" -> "to-fire-resistance"
+fn file_stem(name: &str) -> String {
+    let stem: Vec<String> = name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| part.to_ascii_lowercase())
+        .collect();
+    if stem.is_empty() { "unnamed".to_string() } else { stem.join("-") }
+}
+
+// SVG charts for the `modifiers` most common modifiers (a value histogram and a
+// price-vs-value scatter each) and each attribute's requirement distribution
+pub async fn write_charts(
+    dir: &Path,
+    modifier_analyzer: &ModifierAnalyzer,
+    stat_analyzer: &StatAnalyzer,
+    modifiers: usize,
+) -> Result<Vec<PathBuf>> {
+    tokio::fs::create_dir_all(dir).await?;
+    let mut charts: Vec<(PathBuf, String)> = Vec::new();
+
+    let mut stats: Vec<_> = modifier_analyzer.all_stats()
+        .filter(|stats| !stats.price_points.is_empty())
+        .collect();
+    stats.sort_by(|a, b| b.total_occurrences.cmp(&a.total_occurrences).then_with(|| a.name.cmp(&b.name)));
+    for stats in stats.into_iter().take(modifiers) {
+        let stem = file_stem(&stats.name);
+        let values: Vec<f64> = stats.price_points.iter().map(|&(value, _)| value).collect();
+        charts.push((
+            dir.join(format!("values-{}.svg", stem)),
+            histogram_svg(&stats.name, "value", &values)?,
+        ));
+        charts.push((
+            dir.join(format!("price-{}.svg", stem)),
+            scatter_svg(&stats.name, "value", "price", &stats.price_points)?,
+        ));
+    }
+
+    for (attribute, values) in stat_analyzer.requirement_values() {
+        let values: Vec<f64> = values.into_iter().map(f64::from).collect();
+        charts.push((
+            dir.join(format!("requirements-{}.svg", file_stem(&attribute))),
+            histogram_svg(&format!("{} requirement", attribute), "requirement", &values)?,
+        ));
+    }
+
+    let mut written = Vec::new();
+    for (path, svg) in charts {
+        tokio::fs::write(&path, svg).await?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_bins_and_svg() {
+        let bins = histogram_bins(&[0.0, 1.0, 2.0, 9.9, 10.0], 5);
+        let counts: Vec<u32> = bins.iter().map(|bin| bin.2).collect();
+        assert_eq!(counts, vec![2, 1, 0, 0, 2]);
+        assert_eq!(histogram_bins(&[3.0, 3.0], 5), vec![(2.5, 3.5, 2)]);
+
+        let svg = histogram_svg("+#% to Fire Resistance", "value", &[10.0, 20.0, 30.0]).unwrap();
+        assert!(svg.starts_with("<svg") && svg.contains("+#% to Fire Resistance"));
+        assert!(scatter_svg("empty", "value", "price", &[]).is_err());
+        assert_eq!(file_stem("+#% to Fire Resistance"), "to-fire-resistance");
+    }
+}
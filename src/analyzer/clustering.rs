@@ -0,0 +1,247 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::collections::HashMap;
+use crate::models::percentile;
+use super::price_model::PriceObservation;
+
+pub const DEFAULT_CLUSTER_SEED: u64 = 42;
+
+const MAX_ITERATIONS: usize = 100;
+
+// Modifiers named in a cluster's label, at most
+const LABEL_MODIFIERS: usize = 3;
+
+// How common a modifier is inside a cluster
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterModifier {
+    pub modifier: String,
+    // Share of the cluster's listings with the modifier
+    pub prevalence: f64,
+    // Prevalence in the cluster minus prevalence across all listings
+    pub lift: f64,
+}
+
+// A group of listings with a similar modifier profile, i.e. one item archetype
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemCluster {
+    // The defining modifiers joined together, e.g. "+# to maximum Life + +#% to Fire Resistance"
+    pub label: String,
+    pub size: usize,
+    pub average_price: f64,
+    pub median_price: f64,
+    pub modifiers: Vec<ClusterModifier>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterReport {
+    pub k: usize,
+    pub iterations: usize,
+    pub features: usize,
+    // Largest cluster first
+    pub clusters: Vec<ItemCluster>,
+}
+
+// k-means over which modifiers each listing has. Presence rather than rolled values keeps
+// modifiers with big numbers (life, armour) from drowning out the rest, so clusters follow
+// what an item is rather than how well it rolled. Modifiers on fewer than `min_occurrences`
+// listings are ignored. None when there are fewer listings than clusters or no features.
+pub fn cluster_listings(
+    observations: &[PriceObservation],
+    k: usize,
+    min_occurrences: usize,
+    seed: u64,
+) -> Option<ClusterReport> {
+    let mut occurrences: HashMap<&str, usize> = HashMap::new();
+    for observation in observations {
+        for name in observation.modifiers.keys() {
+            *occurrences.entry(name.as_str()).or_default() += 1;
+        }
+    }
+    let mut names: Vec<&str> = occurrences
+        .iter()
+        .filter(|(_, &count)| count >= min_occurrences.max(1))
+        .map(|(&name, _)| name)
+        .collect();
+    names.sort_unstable();
+    if k == 0 || observations.len() < k || names.is_empty() {
+        return None;
+    }
+
+    let rows: Vec<Vec<f64>> = observations
+        .iter()
+        .map(|observation| {
+            names.iter()
+                .map(|name| if observation.modifiers.contains_key(*name) { 1.0 } else { 0.0 })
+                .collect()
+        })
+        .collect();
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut centroids = initial_centroids(&rows, k, &mut rng);
+    let mut assignments = vec![usize::MAX; rows.len()];
+    let mut iterations = 0;
+    while iterations < MAX_ITERATIONS {
+        iterations += 1;
+        let mut changed = false;
+        for (row, assignment) in rows.iter().zip(&mut assignments) {
+            let nearest = nearest_centroid(row, &centroids);
+            changed |= *assignment != nearest;
+            *assignment = nearest;
+        }
+        if !changed {
+            break;
+        }
+
+        // An emptied cluster keeps its old centroid
+        for (index, centroid) in centroids.iter_mut().enumerate() {
+            let members: Vec<&Vec<f64>> = rows.iter()
+                .zip(&assignments)
+                .filter(|(_, &assignment)| assignment == index)
+                .map(|(row, _)| row)
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            for (feature, value) in centroid.iter_mut().enumerate() {
+                *value = members.iter().map(|row| row[feature]).sum::<f64>() / members.len() as f64;
+            }
+        }
+    }
+
+    let overall: Vec<f64> = (0..names.len())
+        .map(|feature| rows.iter().map(|row| row[feature]).sum::<f64>() / rows.len() as f64)
+        .collect();
+    let mut clusters: Vec<ItemCluster> = (0..k)
+        .filter_map(|index| {
+            let members: Vec<usize> = (0..rows.len()).filter(|&i| assignments[i] == index).collect();
+            describe_cluster(&members, &rows, observations, &names, &overall)
+        })
+        .collect();
+    clusters.sort_by_key(|cluster| std::cmp::Reverse(cluster.size));
+
+    Some(ClusterReport {
+        k,
+        iterations,
+        features: names.len(),
+        clusters,
+    })
+}
+
+fn describe_cluster(
+    members: &[usize],
+    rows: &[Vec<f64>],
+    observations: &[PriceObservation],
+    names: &[&str],
+    overall: &[f64],
+) -> Option<ItemCluster> {
+    if members.is_empty() {
+        return None;
+    }
+
+    let mut modifiers: Vec<ClusterModifier> = names
+        .iter()
+        .enumerate()
+        .map(|(feature, name)| {
+            let prevalence = members.iter().map(|&i| rows[i][feature]).sum::<f64>() / members.len() as f64;
+            ClusterModifier {
+                modifier: name.to_string(),
+                prevalence,
+                lift: prevalence - overall[feature],
+            }
+        })
+        .filter(|modifier| modifier.prevalence > 0.0)
+        .collect();
+    modifiers.sort_by(|a, b| b.lift.total_cmp(&a.lift));
+
+    // Named after what most of its listings have and sets it apart from the rest
+    let label_parts: Vec<&str> = modifiers
+        .iter()
+        .filter(|modifier| modifier.prevalence >= 0.5 && modifier.lift > 0.0)
+        .take(LABEL_MODIFIERS)
+        .map(|modifier| modifier.modifier.as_str())
+        .collect();
+    let label = if label_parts.is_empty() { "mixed".to_string() } else { label_parts.join(" + ") };
+
+    let mut prices: Vec<f64> = members.iter().map(|&i| observations[i].price).collect();
+    prices.sort_by(|a, b| a.total_cmp(b));
+
+    Some(ItemCluster {
+        label,
+        size: members.len(),
+        average_price: prices.iter().sum::<f64>() / prices.len() as f64,
+        median_price: percentile(&prices, 0.5),
+        modifiers,
+    })
+}
+
+// k-means++: each further centroid is a row picked with probability proportional to its
+// squared distance from the nearest centroid so far
+fn initial_centroids(rows: &[Vec<f64>], k: usize, rng: &mut StdRng) -> Vec<Vec<f64>> {
+    let mut centroids = vec![rows[rng.gen_range(0..rows.len())].clone()];
+    while centroids.len() < k {
+        let distances: Vec<f64> = rows.iter()
+            .map(|row| centroids.iter().map(|c| squared_distance(row, c)).fold(f64::MAX, f64::min))
+            .collect();
+        let total: f64 = distances.iter().sum();
+        if total == 0.0 {
+            // Fewer distinct rows than clusters; duplicates just end up empty
+            centroids.push(rows[rng.gen_range(0..rows.len())].clone());
+            continue;
+        }
+
+        let mut target = rng.gen_range(0.0..total);
+        let chosen = distances.iter()
+            .position(|&distance| {
+                target -= distance;
+                target < 0.0
+            })
+            .unwrap_or(rows.len() - 1);
+        centroids.push(rows[chosen].clone());
+    }
+    centroids
+}
+
+fn nearest_centroid(row: &[f64], centroids: &[Vec<f64>]) -> usize {
+    centroids.iter()
+        .map(|centroid| squared_distance(row, centroid))
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+fn squared_distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(modifiers: &[&str], price: f64) -> PriceObservation {
+        PriceObservation {
+            modifiers: modifiers.iter().map(|name| (name.to_string(), 1.0)).collect(),
+            price,
+        }
+    }
+
+    #[test]
+    fn test_separates_archetypes() {
+        let mut observations = Vec::new();
+        for i in 0..6 {
+            observations.push(observation(&["life", "fire res"], 10.0 + i as f64));
+            observations.push(observation(&["energy shield", "int"], 50.0 + i as f64));
+        }
+        observations.push(observation(&["life", "fire res", "int"], 12.0));
+
+        let report = cluster_listings(&observations, 2, 2, DEFAULT_CLUSTER_SEED).unwrap();
+        assert_eq!(report.clusters.len(), 2);
+        assert_eq!(report.clusters[0].size, 7);
+        assert_eq!(report.clusters[0].label, "fire res + life");
+        assert_eq!(report.clusters[1].label, "energy shield + int");
+        assert_eq!(report.clusters[1].median_price, 52.5);
+
+        assert!(cluster_listings(&observations[..1], 2, 1, DEFAULT_CLUSTER_SEED).is_none());
+    }
+}
@@ -0,0 +1,417 @@
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::BufRead;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncWriteExt;
+use tracing::warn;
+use crate::errors::{Result, ScraperError};
+use crate::models::ItemResponse;
+use crate::storage::compression::{self, FileWriter};
+use super::collection_plan::CollectionPlan;
+
+// Format of collected data files: the header's fields followed by "items", the array of
+// listings. Version 1 files only had the version; files from before versioning are a bare
+// array of listings and read as version 0. NDJSON files hold the same: the header on the
+// first line, then a listing per line. Either can be compressed, see Compression.
+pub const COLLECTED_DATA_VERSION: u32 = 2;
+
+fn check_version(version: u32) -> std::result::Result<(), String> {
+    if version > COLLECTED_DATA_VERSION {
+        return Err(format!("collected data version {} is newer than the supported {}", version, COLLECTED_DATA_VERSION));
+    }
+    Ok(())
+}
+
+// How a collected data file is laid out, told by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectedDataFormat {
+    // A single JSON document, only valid once the run has finished
+    Json,
+    // A line per listing, flushed as each arrives so a crash loses at most the last one
+    Ndjson,
+}
+
+impl CollectedDataFormat {
+    // .ndjson and .jsonl files are NDJSON, anything else JSON, compressed or not
+    pub fn from_path(path: &Path) -> Self {
+        match compression::content_extension(path) {
+            Some("ndjson" | "jsonl") => CollectedDataFormat::Ndjson,
+            _ => CollectedDataFormat::Json,
+        }
+    }
+}
+
+// What a collected data file holds, written ahead of its listings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectedDataHeader {
+    // The version the file was written in
+    pub version: u32,
+    // Taken from the listings for files written before the header had it
+    pub league: Option<String>,
+    // Unix seconds the collection started; unknown for older files
+    pub collected_at: Option<u64>,
+    // The plan the listings were searched with
+    pub query_plan: Option<CollectionPlan>,
+}
+
+impl CollectedDataHeader {
+    pub fn new(league: Option<String>, query_plan: Option<CollectionPlan>) -> Self {
+        Self {
+            version: COLLECTED_DATA_VERSION,
+            league,
+            collected_at: Some(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()),
+            query_plan,
+        }
+    }
+}
+
+// A whole collected data file
+#[derive(Debug, Clone)]
+pub struct CollectedDataset {
+    pub header: CollectedDataHeader,
+    pub items: Vec<ItemResponse>,
+}
+
+// Writes a collected data file one item at a time, so a collection run never has to hold
+// every listing it fetched
+pub struct CollectedDataWriter {
+    file: FileWriter,
+    format: CollectedDataFormat,
+    written: usize,
+}
+
+impl CollectedDataWriter {
+    // Start a new file in the format its extension names
+    pub async fn create(path: &Path, header: &CollectedDataHeader) -> Result<Self> {
+        let format = CollectedDataFormat::from_path(path);
+        let mut file = compression::create_file(path).await?;
+        let mut opening = serde_json::to_string(header)?;
+        match format {
+            CollectedDataFormat::Json => {
+                // The header object, left open for the items to follow
+                opening.pop();
+                opening.push_str(",\"items\":[");
+            }
+            CollectedDataFormat::Ndjson => opening.push('\n'),
+        }
+        file.write_all(opening.as_bytes()).await?;
+        file.flush().await?;
+        Ok(Self { file, format, written: 0 })
+    }
+
+    // Add to an existing NDJSON file, e.g. when a run resumes, keeping its header. JSON
+    // files and missing or empty ones are started afresh.
+    pub async fn append(path: &Path, header: &CollectedDataHeader) -> Result<Self> {
+        let format = CollectedDataFormat::from_path(path);
+        let existing = tokio::fs::metadata(path).await.map(|metadata| metadata.len()).unwrap_or(0);
+        if format == CollectedDataFormat::Json || existing == 0 {
+            return Self::create(path, header).await;
+        }
+        Ok(Self { file: compression::append_file(path).await?, format, written: 0 })
+    }
+
+    pub async fn write(&mut self, item: &ItemResponse) -> Result<()> {
+        match self.format {
+            CollectedDataFormat::Json => {
+                let separator: &[u8] = if self.written == 0 { b"\n" } else { b",\n" };
+                self.file.write_all(separator).await?;
+                self.file.write_all(&serde_json::to_vec(item)?).await?;
+            }
+            CollectedDataFormat::Ndjson => {
+                let mut line = serde_json::to_vec(item)?;
+                line.push(b'\n');
+                self.file.write_all(&line).await?;
+                self.file.flush().await?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    // Close the file; a JSON one isn't valid until this has run
+    pub async fn finish(mut self) -> Result<usize> {
+        if self.format == CollectedDataFormat::Json {
+            self.file.write_all(b"\n]}\n").await?;
+        }
+        // Also ends a compressed stream
+        self.file.shutdown().await?;
+        Ok(self.written)
+    }
+}
+
+// Hands each listing of an array to the callback as it is parsed
+struct ItemsSeed<'a, F>(&'a mut F);
+
+impl<'de, F: FnMut(ItemResponse)> DeserializeSeed<'de> for ItemsSeed<'_, F> {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> std::result::Result<usize, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: FnMut(ItemResponse)> Visitor<'de> for ItemsSeed<'_, F> {
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of listings")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<usize, A::Error> {
+        let mut count = 0;
+        while let Some(item) = seq.next_element::<ItemResponse>()? {
+            (self.0)(item);
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+// A collected data file of any version, giving its header and how many listings it held
+struct CollectedFileVisitor<F>(F);
+
+impl<'de, F: FnMut(ItemResponse)> Visitor<'de> for CollectedFileVisitor<F> {
+    type Value = (CollectedDataHeader, usize);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a collected data file")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, seq: A) -> std::result::Result<Self::Value, A::Error> {
+        let count = ItemsSeed(&mut self.0).visit_seq(seq)?;
+        Ok((CollectedDataHeader::default(), count))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> std::result::Result<Self::Value, A::Error> {
+        let mut header = CollectedDataHeader::default();
+        let mut version = None;
+        let mut count = None;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "version" => {
+                    let found: u32 = map.next_value()?;
+                    check_version(found).map_err(de::Error::custom)?;
+                    version = Some(found);
+                }
+                "league" => header.league = map.next_value()?,
+                "collected_at" => header.collected_at = map.next_value()?,
+                "query_plan" => header.query_plan = map.next_value()?,
+                "items" => count = Some(map.next_value_seed(ItemsSeed(&mut self.0))?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        header.version = version.ok_or_else(|| de::Error::missing_field("version"))?;
+        Ok((header, count.ok_or_else(|| de::Error::missing_field("items"))?))
+    }
+}
+
+// Hand each listing in a collected data file to `visit` as it is parsed, returning how
+// many there were. Memory use stays at one listing however large the file is.
+pub fn for_each_collected_item(path: &Path, visit: impl FnMut(ItemResponse)) -> Result<usize> {
+    read_collected_file(path, visit).map(|(_, count)| count)
+}
+
+// As for_each_collected_item, also giving the file's header
+pub fn read_collected_file(path: &Path, visit: impl FnMut(ItemResponse)) -> Result<(CollectedDataHeader, usize)> {
+    visit_collected(compression::open_file(path)?, CollectedDataFormat::from_path(path), visit)
+}
+
+// Parse a file of any version and format, bringing an older header up to date with what
+// its listings tell
+fn visit_collected(
+    reader: impl BufRead,
+    format: CollectedDataFormat,
+    mut visit: impl FnMut(ItemResponse),
+) -> Result<(CollectedDataHeader, usize)> {
+    let mut first_league = None;
+    let mut visit = |item: ItemResponse| {
+        if first_league.is_none() {
+            first_league = item.item.league.clone();
+        }
+        visit(item)
+    };
+    let (mut header, count) = match format {
+        CollectedDataFormat::Json => {
+            let mut deserializer = serde_json::Deserializer::from_reader(reader);
+            let read = (&mut deserializer).deserialize_any(CollectedFileVisitor(&mut visit))?;
+            deserializer.end()?;
+            read
+        }
+        CollectedDataFormat::Ndjson => visit_ndjson(reader, &mut visit)?,
+    };
+    if header.league.is_none() {
+        header.league = first_league;
+    }
+    Ok((header, count))
+}
+
+fn visit_ndjson(reader: impl BufRead, mut visit: impl FnMut(ItemResponse)) -> Result<(CollectedDataHeader, usize)> {
+    let mut lines = reader.lines().peekable();
+    let header: CollectedDataHeader = match lines.next() {
+        Some(line) => serde_json::from_str(&line?)?,
+        None => return Err(ScraperError::ParseError("collected data file is empty".to_string())),
+    };
+    check_version(header.version).map_err(ScraperError::ParseError)?;
+
+    let mut count = 0;
+    let mut line_number = 1;
+    while let Some(line) = lines.next() {
+        line_number += 1;
+        let line = match line {
+            Ok(line) => line,
+            // A compressed stream cut off by a crash
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                warn!(line = line_number, error = %e, "collected data ends part-way through");
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(item) => {
+                visit(item);
+                count += 1;
+            }
+            // A run killed mid-write leaves its last line cut short
+            Err(e) if lines.peek().is_none() => {
+                warn!(line = line_number, error = %e, "skipping incomplete last line of collected data");
+            }
+            Err(e) => return Err(ScraperError::ParseError(format!("collected data line {}: {}", line_number, e))),
+        }
+    }
+    Ok((header, count))
+}
+
+// Write listings already in memory in the same format collect streams them in
+pub async fn save_collected_items(path: &Path, header: &CollectedDataHeader, items: &[ItemResponse]) -> Result<()> {
+    let mut writer = CollectedDataWriter::create(path, header).await?;
+    for item in items {
+        writer.write(item).await?;
+    }
+    writer.finish().await?;
+    Ok(())
+}
+
+// Read a whole collected data file; see for_each_collected_item for large ones
+pub async fn load_collected_items(path: &Path) -> Result<CollectedDataset> {
+    let content = compression::read_file(path).await?;
+    let mut items = Vec::new();
+    let (header, _) = visit_collected(content.as_slice(), CollectedDataFormat::from_path(path), |item| items.push(item))?;
+    Ok(CollectedDataset { header, items })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+
+    #[tokio::test]
+    async fn test_streamed_file_round_trip() {
+        let path = std::env::temp_dir().join(format!("poe2-collected-test-{}.json", std::process::id()));
+        let header = CollectedDataHeader::new(Some("Standard".to_string()), Some(CollectionPlan::default()));
+        let mut writer = CollectedDataWriter::create(&path, &header).await.unwrap();
+        for id in ["a", "b", "c"] {
+            writer.write(&ListingBuilder::new(id, "Iron Ring").response()).await.unwrap();
+        }
+        assert_eq!(writer.finish().await.unwrap(), 3);
+
+        // Still a plain JSON document
+        let content: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(content["version"], COLLECTED_DATA_VERSION);
+        assert_eq!(content["items"].as_array().unwrap().len(), 3);
+        assert_eq!(content["league"], "Standard");
+
+        let mut ids = Vec::new();
+        let (read_header, count) = read_collected_file(&path, |item| ids.push(item.id)).unwrap();
+        assert_eq!(read_header, header);
+        assert_eq!(count, 3);
+        assert_eq!(ids, vec!["a", "b", "c"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_save_load_symmetric_and_migrates_older_files() {
+        let path = std::env::temp_dir().join(format!("poe2-collected-save-test-{}.json", std::process::id()));
+        let mut items = ["a", "b"].map(|id| ListingBuilder::new(id, "Iron Ring").response());
+        let header = CollectedDataHeader::new(None, None);
+        save_collected_items(&path, &header, &items).await.unwrap();
+        let loaded = load_collected_items(&path).await.unwrap();
+        assert_eq!(loaded.header, header);
+        assert_eq!(loaded.items.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+
+        // A bare array from before versioning, its league recovered from the listings
+        items[0].item.league = Some("Standard".to_string());
+        std::fs::write(&path, serde_json::to_string(&items).unwrap()).unwrap();
+        let loaded = load_collected_items(&path).await.unwrap();
+        assert_eq!(loaded.items.len(), 2);
+        assert_eq!(loaded.header.version, 0);
+        assert_eq!(loaded.header.league.as_deref(), Some("Standard"));
+        assert!(loaded.header.collected_at.is_none());
+
+        // Version 1, without the metadata
+        std::fs::write(&path, format!(r#"{{"version": 1, "items": {}}}"#, serde_json::to_string(&items).unwrap())).unwrap();
+        let loaded = load_collected_items(&path).await.unwrap();
+        assert_eq!((loaded.header.version, loaded.items.len()), (1, 2));
+
+        std::fs::write(&path, r#"{"items": []}"#).unwrap();
+        assert!(load_collected_items(&path).await.is_err());
+
+        std::fs::write(&path, r#"{"version": 99, "items": []}"#).unwrap();
+        assert!(load_collected_items(&path).await.is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ndjson_appends_and_survives_a_cut_short_line() {
+        let path = std::env::temp_dir().join(format!("poe2-collected-test-{}.ndjson", std::process::id()));
+        let header = CollectedDataHeader::new(Some("Standard".to_string()), None);
+        let mut writer = CollectedDataWriter::create(&path, &header).await.unwrap();
+        writer.write(&ListingBuilder::new("a", "Iron Ring").response()).await.unwrap();
+        writer.finish().await.unwrap();
+
+        // A resumed run carries on in the same file
+        let mut writer = CollectedDataWriter::append(&path, &CollectedDataHeader::new(None, None)).await.unwrap();
+        writer.write(&ListingBuilder::new("b", "Iron Ring").response()).await.unwrap();
+        writer.finish().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        // Then is killed part-way through writing a listing
+        let mut content = std::fs::read_to_string(&path).unwrap();
+        content.push_str(r#"{"id": "c", "item": {"#);
+        std::fs::write(&path, content).unwrap();
+
+        let mut ids = Vec::new();
+        let (read_header, count) = read_collected_file(&path, |item| ids.push(item.id)).unwrap();
+        assert_eq!(read_header, header);
+        assert_eq!(count, 2);
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(load_collected_items(&path).await.unwrap().items.len(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_compressed_files() {
+        for name in ["collected.json.gz", "collected.ndjson.zst"] {
+            let path = std::env::temp_dir().join(format!("poe2-collected-test-{}-{}", std::process::id(), name));
+            let header = CollectedDataHeader::new(None, None);
+            let items = ["a", "b"].map(|id| ListingBuilder::new(id, "Iron Ring").response());
+            save_collected_items(&path, &header, &items).await.unwrap();
+            assert!(serde_json::from_slice::<serde_json::Value>(&std::fs::read(&path).unwrap()).is_err());
+
+            let (_, count) = read_collected_file(&path, |_| {}).unwrap();
+            assert_eq!(count, 2, "{}", name);
+            assert_eq!(load_collected_items(&path).await.unwrap().header, header);
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+use crate::errors::Result;
+use crate::models::CoreAttribute;
+use super::collection_plan::{PlanSort, PriceBand};
+
+pub const DEFAULT_CHECKPOINT_PATH: &str = "data/collection_checkpoint.json";
+
+// Trade category the attribute ranges are searched in
+pub const ATTRIBUTE_CATEGORY: &str = "armour";
+
+fn attribute_category() -> String {
+    ATTRIBUTE_CATEGORY.to_string()
+}
+
+// Bounds on one attribute's requirement
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttributeRange {
+    pub attribute: CoreAttribute,
+    pub min: u32,
+    pub max: u32,
+}
+
+// One query (cell) of a collection plan: a category, optionally narrowed to an attribute
+// requirement range and a price band, searched in one order. Checkpoints from before
+// categories have none and are armour.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeKey {
+    #[serde(default = "attribute_category")]
+    pub category: String,
+    #[serde(flatten)]
+    pub attribute: Option<AttributeRange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub price_band: Option<PriceBand>,
+    // None is the default cheapest-first order
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort: Option<PlanSort>,
+}
+
+impl RangeKey {
+    pub fn attribute(attribute: CoreAttribute, min: u32, max: u32) -> Self {
+        Self {
+            category: attribute_category(),
+            attribute: Some(AttributeRange { attribute, min, max }),
+            price_band: None,
+            sort: None,
+        }
+    }
+
+    pub fn category(category: &str) -> Self {
+        Self {
+            category: category.to_string(),
+            attribute: None,
+            price_band: None,
+            sort: None,
+        }
+    }
+}
+
+// e.g. "Strength 0-50" for the attribute ranges on armour, "jewel price 5-50 indexed_desc"
+impl fmt::Display for RangeKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.attribute.is_none() || self.category != ATTRIBUTE_CATEGORY {
+            parts.push(self.category.clone());
+        }
+        if let Some(range) = &self.attribute {
+            parts.push(format!("{:?} {}-{}", range.attribute, range.min, range.max));
+        }
+        if let Some(band) = &self.price_band {
+            parts.push(band.to_string());
+        }
+        if let Some(sort) = &self.sort {
+            parts.push(sort.as_str().to_string());
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+// A range whose search has run but whose result IDs aren't all fetched yet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingRange {
+    pub range: RangeKey,
+    pub pending_ids: Vec<String>,
+    // Weight of each listing fetched for the range, see ItemResponse::sampling_weight
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling_weight: Option<f64>,
+}
+
+// Progress of a collection run, written after every step so an interrupted run can resume.
+// Fetched items are handed on as they arrive, so only the IDs still to fetch are kept.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CollectionCheckpoint {
+    #[serde(skip)]
+    path: PathBuf,
+    pub completed_ranges: Vec<RangeKey>,
+    pub in_progress: Option<PendingRange>,
+    // Result IDs taken by a range so far, so a listing matching several ranges is only
+    // fetched once per run, resumed or not
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub seen_ids: HashSet<String>,
+    // Result IDs not fetched because the query's previous search returned them too, still
+    // to be marked as seen once the run finishes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unchanged_ids: Vec<String>,
+}
+
+impl CollectionCheckpoint {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ..Self::default()
+        }
+    }
+
+    // Load the checkpoint at `path`, or start a fresh one if there is none
+    pub async fn load_or_new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => {
+                let mut checkpoint: Self = serde_json::from_str(&content)?;
+                checkpoint.path = path;
+                Ok(checkpoint)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new(path)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn is_completed(&self, range: &RangeKey) -> bool {
+        self.completed_ranges.contains(range)
+    }
+
+    // The range if it was interrupted mid-way
+    pub fn pending_for(&self, range: &RangeKey) -> Option<&PendingRange> {
+        self.in_progress.as_ref().filter(|pending| &pending.range == range)
+    }
+
+    // IDs still to fetch for a range that was interrupted mid-way
+    pub fn pending_ids_for(&self, range: &RangeKey) -> Option<&[String]> {
+        self.pending_for(range).map(|pending| pending.pending_ids.as_slice())
+    }
+
+    // Drop IDs an earlier range of the run already took (or repeated within `ids`) and claim
+    // the rest, returning how many were dropped
+    pub fn claim_ids(&mut self, ids: &mut Vec<String>) -> usize {
+        let before = ids.len();
+        ids.retain(|id| self.seen_ids.insert(id.clone()));
+        before - ids.len()
+    }
+
+    pub fn start_range(&mut self, range: RangeKey, ids: Vec<String>, sampling_weight: Option<f64>) {
+        self.in_progress = Some(PendingRange {
+            range,
+            pending_ids: ids,
+            sampling_weight,
+        });
+    }
+
+    // Drop IDs that have been fetched from the pending list
+    pub fn record_fetched(&mut self, requested: &[String]) {
+        if let Some(pending) = &mut self.in_progress {
+            pending.pending_ids.retain(|id| !requested.contains(id));
+        }
+    }
+
+    pub fn complete_range(&mut self, range: RangeKey) {
+        self.in_progress = None;
+        self.completed_ranges.push(range);
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // Write then rename so a crash mid-write can't corrupt the previous checkpoint
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_string(self)?).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+
+    // Remove the checkpoint once a run has finished
+    pub async fn clear(&self) -> Result<()> {
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(min: u32, max: u32) -> RangeKey {
+        RangeKey::attribute(CoreAttribute::Strength, min, max)
+    }
+
+    #[test]
+    fn test_range_key_reads_checkpoints_without_category() {
+        let key: RangeKey = serde_json::from_str(r#"{"attribute":"Strength","min":0,"max":50}"#).unwrap();
+        assert_eq!(key, range(0, 50));
+
+        let sweep: RangeKey = serde_json::from_str(r#"{"category":"weapon.bow"}"#).unwrap();
+        assert_eq!(sweep, RangeKey::category("weapon.bow"));
+        assert_eq!(serde_json::to_string(&sweep).unwrap(), r#"{"category":"weapon.bow"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_round_trip() {
+        let path = std::env::temp_dir().join(format!("poe2-checkpoint-test-{}.json", std::process::id()));
+        let mut checkpoint = CollectionCheckpoint::new(&path);
+
+        checkpoint.complete_range(range(0, 50));
+        checkpoint.start_range(range(51, 100), vec!["a".into(), "b".into(), "c".into()], Some(4.0));
+        checkpoint.record_fetched(&["a".to_string(), "b".to_string()]);
+        checkpoint.save().await.unwrap();
+
+        let loaded = CollectionCheckpoint::load_or_new(&path).await.unwrap();
+        assert!(loaded.is_completed(&range(0, 50)));
+        assert!(!loaded.is_completed(&range(51, 100)));
+        assert_eq!(loaded.pending_ids_for(&range(51, 100)), Some(&["c".to_string()][..]));
+        assert_eq!(loaded.pending_for(&range(51, 100)).unwrap().sampling_weight, Some(4.0));
+        assert!(loaded.pending_ids_for(&range(101, 150)).is_none());
+
+        assert!(loaded.seen_ids.is_empty());
+
+        loaded.clear().await.unwrap();
+        let fresh = CollectionCheckpoint::load_or_new(&path).await.unwrap();
+        assert!(fresh.completed_ranges.is_empty());
+        assert!(fresh.in_progress.is_none());
+    }
+
+    #[test]
+    fn test_claim_ids_drops_ids_taken_earlier() {
+        let mut checkpoint = CollectionCheckpoint::default();
+        let mut first = vec!["a".to_string(), "b".to_string(), "a".to_string()];
+        assert_eq!(checkpoint.claim_ids(&mut first), 1);
+        assert_eq!(first, ["a", "b"]);
+
+        let mut second = vec!["b".to_string(), "c".to_string()];
+        assert_eq!(checkpoint.claim_ids(&mut second), 1);
+        assert_eq!(second, ["c"]);
+
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        let loaded: CollectionCheckpoint = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.seen_ids.len(), 3);
+    }
+}
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use crate::errors::{Result, ScraperError};
+use crate::models::CoreAttribute;
+use super::collection_checkpoint::{AttributeRange, RangeKey, ATTRIBUTE_CATEGORY};
+
+// Listings on one page of trade results, the most a single fetch returns
+pub const SWEEP_PAGE_SIZE: usize = 10;
+
+// Result pages fetched per category in a sweep unless limited otherwise
+pub const DEFAULT_SWEEP_PAGES: usize = 5;
+
+// Trade categories a sweep searches: weapon subtypes, armour slots, jewellery, flasks and jewels
+pub const SWEEP_CATEGORIES: &[&str] = &[
+    "weapon.claw",
+    "weapon.dagger",
+    "weapon.onesword",
+    "weapon.oneaxe",
+    "weapon.onemace",
+    "weapon.spear",
+    "weapon.flail",
+    "weapon.sceptre",
+    "weapon.wand",
+    "weapon.twosword",
+    "weapon.twoaxe",
+    "weapon.twomace",
+    "weapon.warstaff",
+    "weapon.staff",
+    "weapon.talisman",
+    "weapon.bow",
+    "weapon.crossbow",
+    "armour.helmet",
+    "armour.chest",
+    "armour.gloves",
+    "armour.boots",
+    "armour.shield",
+    "armour.buckler",
+    "armour.focus",
+    "armour.quiver",
+    "accessory.ring",
+    "accessory.amulet",
+    "accessory.belt",
+    "flask.life",
+    "flask.mana",
+    "jewel",
+];
+
+// Page limits for a category sweep: every category gets `default_pages` unless listed
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepLimits {
+    pub default_pages: usize,
+    pub category_pages: HashMap<String, usize>,
+}
+
+impl Default for SweepLimits {
+    fn default() -> Self {
+        Self {
+            default_pages: DEFAULT_SWEEP_PAGES,
+            category_pages: HashMap::new(),
+        }
+    }
+}
+
+impl SweepLimits {
+    pub fn pages(&self, category: &str) -> usize {
+        self.category_pages.get(category).copied().unwrap_or(self.default_pages)
+    }
+
+    // Listings fetched at most for a category
+    pub fn max_results(&self, category: &str) -> usize {
+        self.pages(category) * SWEEP_PAGE_SIZE
+    }
+}
+
+// Bounds on the listed price of one price band, in the collection's price currency
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriceBand {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+}
+
+impl fmt::Display for PriceBand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.min, self.max) {
+            (Some(min), Some(max)) => write!(f, "price {}-{}", min, max),
+            (Some(min), None) => write!(f, "price {}+", min),
+            (None, Some(max)) => write!(f, "price <={}", max),
+            (None, None) => f.write_str("any price"),
+        }
+    }
+}
+
+// Order the trade API returns a search's results in; only the first results are fetched,
+// so the order decides which listings a query samples
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanSort {
+    #[default]
+    PriceAsc,
+    PriceDesc,
+    IndexedDesc,
+}
+
+impl PlanSort {
+    // The trade API sort field and direction
+    pub fn field_direction(&self) -> (&'static str, &'static str) {
+        match self {
+            PlanSort::PriceAsc => ("price", "asc"),
+            PlanSort::PriceDesc => ("price", "desc"),
+            PlanSort::IndexedDesc => ("indexed", "desc"),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlanSort::PriceAsc => "price_asc",
+            PlanSort::PriceDesc => "price_desc",
+            PlanSort::IndexedDesc => "indexed_desc",
+        }
+    }
+}
+
+// What a collection run searches: every combination of category, attribute range, price
+// band and sort order is one query ("cell"), of which at most `max_results` listings are
+// fetched. Read from a JSON file (`collect --plan`) or the config's [collect.plan] table;
+// the default is the attribute-stacking armour plan.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectionPlan {
+    // Trade category ids such as "armour", "weapon.bow" or "jewel"
+    pub categories: Vec<String>,
+    // Attributes whose "+# to <Attribute>" modifier is filtered on; empty searches without one
+    pub attributes: Vec<CoreAttribute>,
+    // Bounds on the attribute modifier, as [min, max] pairs
+    pub attribute_ranges: Vec<(u32, u32)>,
+    // Price bands searched separately; empty searches the collection's price range as a whole
+    pub price_bands: Vec<PriceBand>,
+    pub sorts: Vec<PlanSort>,
+    // Listings fetched at most per cell; the trade API returns up to 100 per search
+    pub max_results: Option<usize>,
+    // Per-category overrides of max_results
+    pub category_max_results: HashMap<String, usize>,
+}
+
+impl Default for CollectionPlan {
+    fn default() -> Self {
+        Self {
+            categories: vec![ATTRIBUTE_CATEGORY.to_string()],
+            attributes: vec![CoreAttribute::Strength, CoreAttribute::Dexterity, CoreAttribute::Intelligence],
+            // A good spread of stat requirements, from low to very high
+            attribute_ranges: vec![(0, 50), (51, 100), (101, 150), (151, 200)],
+            price_bands: Vec::new(),
+            sorts: vec![PlanSort::PriceAsc],
+            max_results: None,
+            category_max_results: HashMap::new(),
+        }
+    }
+}
+
+impl CollectionPlan {
+    // Each of SWEEP_CATEGORIES without attribute filters, limited to its pages
+    pub fn sweep(limits: &SweepLimits) -> Self {
+        Self {
+            categories: SWEEP_CATEGORIES.iter().map(|category| category.to_string()).collect(),
+            attributes: Vec::new(),
+            attribute_ranges: Vec::new(),
+            max_results: Some(limits.default_pages * SWEEP_PAGE_SIZE),
+            category_max_results: limits
+                .category_pages
+                .keys()
+                .map(|category| (category.clone(), limits.max_results(category)))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path).await?;
+        let plan: Self = serde_json::from_str(&content)?;
+        plan.validate()?;
+        Ok(plan)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| Err(ScraperError::ValidationError(format!("Invalid collection plan: {}", reason)));
+        if self.categories.is_empty() {
+            return invalid("it has no categories".to_string());
+        }
+        if let Some((min, max)) = self.attribute_ranges.iter().find(|(min, max)| min > max) {
+            return invalid(format!("attribute range [{}, {}] has min above max", min, max));
+        }
+        if let Some(band) = self.price_bands.iter().find(|band| matches!((band.min, band.max), (Some(min), Some(max)) if min > max)) {
+            return invalid(format!("{} has min above max", band));
+        }
+        if self.max_results == Some(0) || self.category_max_results.values().any(|&max| max == 0) {
+            return invalid("max_results must be above 0".to_string());
+        }
+        Ok(())
+    }
+
+    // Listings fetched at most for a cell in the category; None fetches all a search returns
+    pub fn max_results_for(&self, category: &str) -> Option<usize> {
+        self.category_max_results.get(category).copied().or(self.max_results)
+    }
+
+    // Every cell of the plan, in the order they are collected
+    pub fn cells(&self) -> Vec<RangeKey> {
+        let attributes: Vec<Option<AttributeRange>> = if self.attributes.is_empty() || self.attribute_ranges.is_empty() {
+            vec![None]
+        } else {
+            self.attributes
+                .iter()
+                .flat_map(|attribute| {
+                    self.attribute_ranges.iter().map(|&(min, max)| {
+                        Some(AttributeRange { attribute: attribute.clone(), min, max })
+                    })
+                })
+                .collect()
+        };
+        let bands: Vec<Option<PriceBand>> = if self.price_bands.is_empty() {
+            vec![None]
+        } else {
+            self.price_bands.iter().copied().map(Some).collect()
+        };
+        let sorts = if self.sorts.is_empty() { vec![PlanSort::default()] } else { self.sorts.clone() };
+
+        let mut cells = Vec::new();
+        for category in &self.categories {
+            for attribute in &attributes {
+                for band in &bands {
+                    for sort in &sorts {
+                        cells.push(RangeKey {
+                            category: category.clone(),
+                            attribute: attribute.clone(),
+                            price_band: *band,
+                            // The default order is left out so keys match checkpoints from before sorts
+                            sort: (*sort != PlanSort::default()).then_some(*sort),
+                        });
+                    }
+                }
+            }
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_plan_is_attribute_ranges() {
+        let cells = CollectionPlan::default().cells();
+        assert_eq!(cells.len(), 12);
+        assert_eq!(cells[0], RangeKey::attribute(CoreAttribute::Strength, 0, 50));
+        assert_eq!(cells[11], RangeKey::attribute(CoreAttribute::Intelligence, 151, 200));
+    }
+
+    #[test]
+    fn test_plan_cells_cross_every_dimension() {
+        let plan: CollectionPlan = serde_json::from_str(r#"{
+            "categories": ["armour.boots", "jewel"],
+            "attributes": ["Dexterity"],
+            "attribute_ranges": [[0, 100]],
+            "price_bands": [{"max": 5.0}, {"min": 5.0, "max": 50.0}],
+            "sorts": ["price_asc", "indexed_desc"],
+            "max_results": 40,
+            "category_max_results": {"jewel": 10}
+        }"#).unwrap();
+        plan.validate().unwrap();
+
+        let cells = plan.cells();
+        assert_eq!(cells.len(), 8);
+        assert_eq!(cells[1].sort, Some(PlanSort::IndexedDesc));
+        assert_eq!(cells[2].price_band, Some(PriceBand { min: Some(5.0), max: Some(50.0) }));
+        assert_eq!(cells[0].to_string(), "armour.boots Dexterity 0-100 price <=5");
+        assert_eq!(plan.max_results_for("armour.boots"), Some(40));
+        assert_eq!(plan.max_results_for("jewel"), Some(10));
+    }
+
+    #[test]
+    fn test_sweep_plan() {
+        let limits = SweepLimits {
+            default_pages: 2,
+            category_pages: HashMap::from([("jewel".to_string(), 7)]),
+        };
+        let plan = CollectionPlan::sweep(&limits);
+        let cells = plan.cells();
+        assert_eq!(cells.len(), SWEEP_CATEGORIES.len());
+        assert_eq!(cells[0], RangeKey::category("weapon.claw"));
+        assert_eq!(plan.max_results_for("weapon.bow"), Some(20));
+        assert_eq!(plan.max_results_for("jewel"), Some(70));
+    }
+
+    #[test]
+    fn test_invalid_plans() {
+        let backwards: CollectionPlan = serde_json::from_str(r#"{"price_bands": [{"min": 10.0, "max": 1.0}]}"#).unwrap();
+        assert!(backwards.validate().is_err());
+        let empty: CollectionPlan = serde_json::from_str(r#"{"categories": []}"#).unwrap();
+        assert!(empty.validate().is_err());
+        assert!(serde_json::from_str::<CollectionPlan>(r#"{"category": ["jewel"]}"#).is_err());
+    }
+}
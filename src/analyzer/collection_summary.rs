@@ -0,0 +1,136 @@
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use crate::errors::Result;
+use super::collection_checkpoint::RangeKey;
+
+// Per attribute/range bucket counts for one collection run
+#[derive(Debug, Clone, Serialize)]
+pub struct RangeSummary {
+    #[serde(flatten)]
+    pub range: RangeKey,
+    pub items: usize,
+    pub parse_failures: usize,
+    // Result IDs left out because an earlier range of the run already fetched them
+    pub duplicates: usize,
+    // Result IDs left out because the query's previous search returned them too
+    pub unchanged: usize,
+    // Listings each fetched one stands for, see ItemResponse::sampling_weight
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sampling_weight: Option<f64>,
+    // Whether the range was already finished by a previous, resumed run
+    pub skipped: bool,
+}
+
+// What a collection run did, printed at the end of `collect` or written as JSON
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CollectionSummary {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub league: Option<String>,
+    pub items_fetched: usize,
+    pub parse_failures: usize,
+    pub duplicates_skipped: usize,
+    // Share of the result IDs searched that were duplicates
+    pub dedup_rate: f64,
+    // Listings still listed since the previous search that weren't fetched again
+    pub unchanged_skipped: usize,
+    pub db_inserts: usize,
+    pub api_calls: u64,
+    pub duration_secs: f64,
+    // Whether shutdown stopped the run before every range was collected
+    pub interrupted: bool,
+    pub ranges: Vec<RangeSummary>,
+}
+
+impl CollectionSummary {
+    pub fn record_range(&mut self, range: RangeSummary) {
+        self.items_fetched += range.items;
+        self.parse_failures += range.parse_failures;
+        self.duplicates_skipped += range.duplicates;
+        self.unchanged_skipped += range.unchanged;
+        let searched = self.items_fetched + self.parse_failures + self.duplicates_skipped;
+        if searched > 0 {
+            self.dedup_rate = self.duplicates_skipped as f64 / searched as f64;
+        }
+        self.ranges.push(range);
+    }
+
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration_secs = duration.as_secs_f64();
+    }
+
+    pub async fn save_json(&self, path: &Path) -> Result<()> {
+        tokio::fs::write(path, serde_json::to_string_pretty(self)?).await?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for CollectionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.league {
+            Some(league) => writeln!(f, "Collection summary ({})", league)?,
+            None => writeln!(f, "Collection summary")?,
+        }
+        for range in &self.ranges {
+            let label = range.range.to_string();
+            if range.skipped {
+                writeln!(f, "  {:<20} done in an earlier run", label)?;
+            } else {
+                write!(f, "  {:<20} {:>6} items {:>4} failed {:>4} duplicate", label, range.items, range.parse_failures, range.duplicates)?;
+                if range.unchanged > 0 {
+                    write!(f, " {:>4} unchanged", range.unchanged)?;
+                }
+                match range.sampling_weight {
+                    Some(weight) => writeln!(f, "  weight {:.1}", weight)?,
+                    None => writeln!(f)?,
+                }
+            }
+        }
+        writeln!(f, "  Items fetched:  {}", self.items_fetched)?;
+        writeln!(f, "  Parse failures: {}", self.parse_failures)?;
+        writeln!(f, "  Duplicates:     {} ({:.1}%)", self.duplicates_skipped, self.dedup_rate * 100.0)?;
+        if self.unchanged_skipped > 0 {
+            writeln!(f, "  Unchanged:      {}", self.unchanged_skipped)?;
+        }
+        writeln!(f, "  DB inserts:     {}", self.db_inserts)?;
+        writeln!(f, "  API calls:      {}", self.api_calls)?;
+        write!(f, "  Duration:       {:.1}s", self.duration_secs)?;
+        if self.interrupted {
+            write!(f, "\n  Interrupted; the checkpoint is kept for --resume")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CoreAttribute;
+
+    #[test]
+    fn test_record_range_totals() {
+        let mut summary = CollectionSummary::default();
+        for (min, max, items, parse_failures, duplicates, unchanged) in [(0, 50, 12, 1, 0, 3), (51, 100, 8, 0, 4, 0)] {
+            summary.record_range(RangeSummary {
+                range: RangeKey::attribute(CoreAttribute::Strength, min, max),
+                items,
+                parse_failures,
+                duplicates,
+                unchanged,
+                sampling_weight: None,
+                skipped: false,
+            });
+        }
+
+        assert_eq!(summary.items_fetched, 20);
+        assert_eq!(summary.parse_failures, 1);
+        assert_eq!(summary.duplicates_skipped, 4);
+        assert_eq!(summary.dedup_rate, 0.16);
+        assert_eq!(summary.unchanged_skipped, 3);
+
+        let json = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["ranges"][1]["min"], 51);
+        assert_eq!(json["ranges"][1]["attribute"], "Strength");
+    }
+}
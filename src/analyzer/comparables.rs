@@ -0,0 +1,174 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use crate::errors::{Result, ScraperError};
+use crate::models::{modifier_template, percentile, Item};
+use crate::storage::{ProbableSale, Storage, StoredListing};
+
+pub const DEFAULT_COMPARABLES: usize = 10;
+
+// A listing gone within this many hours of first being seen counts as sold
+pub const DEFAULT_SALE_WINDOW_HOURS: f64 = 24.0;
+
+// A stored listing and how closely its modifiers match the item being priced, from 0 to 1
+#[derive(Debug, Clone, Serialize)]
+pub struct Comparable {
+    #[serde(flatten)]
+    pub listing: StoredListing,
+    pub similarity: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceCheck {
+    pub base_type: String,
+    pub category: String,
+    // Listings of the category that were compared
+    pub candidates: usize,
+    pub comparables: Vec<Comparable>,
+    // Median normalized price of the comparables that have one
+    pub median_price: Option<f64>,
+    // Listings of the same base type that were probably sold, most recent first
+    pub probable_sales: Vec<ProbableSale>,
+    // Median normalized price of those sales, i.e. what the base actually goes for
+    pub median_sold_price: Option<f64>,
+}
+
+// Modifier template -> first rolled value
+type ModProfile = HashMap<String, f64>;
+
+fn profile<'a>(modifiers: impl Iterator<Item = (&'a str, &'a [f64])>) -> ModProfile {
+    modifiers
+        .map(|(text, values)| (modifier_template(text), values.first().copied().unwrap_or(0.0)))
+        .collect()
+}
+
+// Share of the two items' combined modifiers they have in common, where a shared modifier
+// counts by how close its rolls are: 1 for equal rolls, 0.5 when one is twice the other
+pub fn similarity(a: &ModProfile, b: &ModProfile) -> f64 {
+    let union = a.len() + b.keys().filter(|name| !a.contains_key(*name)).count();
+    if union == 0 {
+        return 0.0;
+    }
+
+    let shared: f64 = a
+        .iter()
+        .filter_map(|(name, value)| b.get(name).map(|other| (value.abs(), other.abs())))
+        .map(|(x, y)| if x.max(y) == 0.0 { 1.0 } else { x.min(y) / x.max(y) })
+        .sum();
+    shared / union as f64
+}
+
+// The `count` candidates most similar to `item`, best first. Candidates sharing no
+// modifiers are left out, as is the item's own listing if it was stored.
+pub fn rank_comparables(item: &Item, candidates: Vec<StoredListing>, count: usize) -> Vec<Comparable> {
+    let target = profile(item.modifiers.iter().map(|m| (m.name.as_str(), m.values.as_slice())));
+
+    let mut comparables: Vec<Comparable> = candidates
+        .into_iter()
+        .filter(|listing| listing.trade_id != item.id)
+        .map(|listing| {
+            let candidate = profile(listing.modifiers.iter().map(|(text, values)| (text.as_str(), values.as_slice())));
+            Comparable {
+                similarity: similarity(&target, &candidate),
+                listing,
+            }
+        })
+        .filter(|comparable| comparable.similarity > 0.0)
+        .collect();
+
+    comparables.sort_by(|a, b| b.similarity.total_cmp(&a.similarity));
+    comparables.truncate(count);
+    comparables
+}
+
+fn median(mut prices: Vec<f64>) -> Option<f64> {
+    prices.sort_by(|a, b| a.total_cmp(b));
+    (!prices.is_empty()).then(|| percentile(&prices, 0.5))
+}
+
+// Find the stored listings of the item's base category that look most like it, and the
+// listings of its base type that sold within `sold_within_hours`
+pub async fn price_check(
+    db: &impl Storage,
+    item: &Item,
+    count: usize,
+    sold_within_hours: f64,
+) -> Result<PriceCheck> {
+    let base_type = &item.item_type.base_type;
+    let category = db.base_category(base_type).await?.ok_or_else(|| {
+        ScraperError::ValidationError(format!(
+            "Unknown base type '{}'; run `bases update` first",
+            base_type
+        ))
+    })?;
+
+    let candidates = db.listings_in_category(&category).await?;
+    let candidate_count = candidates.len();
+    let comparables = rank_comparables(item, candidates, count);
+
+    let median_price = median(comparables.iter().filter_map(|c| c.listing.price_normalized).collect());
+
+    let probable_sales = db.probable_sales(sold_within_hours, Some(base_type)).await?;
+    let median_sold_price = median(probable_sales.iter().filter_map(|sale| sale.price_normalized).collect());
+
+    Ok(PriceCheck {
+        base_type: base_type.clone(),
+        category,
+        candidates: candidate_count,
+        comparables,
+        median_price,
+        probable_sales,
+        median_sold_price,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Currency, ItemCategory, ItemModifier, ItemRarity, ItemType};
+
+    fn listing(id: &str, modifiers: &[(&str, f64)]) -> StoredListing {
+        StoredListing {
+            trade_id: id.to_string(),
+            base_type: "Iron Ring".to_string(),
+            name: None,
+            price_amount: Some(1.0),
+            price_currency: Some(Currency::Exalted),
+            price_normalized: Some(1.0),
+            modifiers: modifiers.iter().map(|(text, value)| (text.to_string(), vec![*value])).collect(),
+        }
+    }
+
+    #[test]
+    fn test_closest_rolls_rank_first() {
+        let mut item = Item::new(
+            "mine".to_string(),
+            ItemType::new(ItemCategory::Accessory, "Iron Ring".to_string(), ItemRarity::Rare),
+        );
+        for (text, value) in [("+80 to maximum Life", 80.0), ("+30% to Fire Resistance", 30.0)] {
+            item.add_modifier(ItemModifier {
+                name: text.to_string(),
+                tier: None,
+                values: vec![value],
+                rolls: Vec::new(),
+                is_crafted: false,
+                kind: Default::default(),
+                stat_requirements: None,
+                attribute_scaling: None,
+                stat_hashes: Vec::new(),
+            });
+        }
+
+        let candidates = vec![
+            listing("mine", &[("+80 to maximum Life", 80.0), ("+30% to Fire Resistance", 30.0)]),
+            listing("close", &[("+70 to maximum Life", 70.0), ("+30% to Fire Resistance", 30.0)]),
+            listing("partial", &[("+80 to maximum Life", 80.0), ("+20 to Strength", 20.0)]),
+            listing("unrelated", &[("+20 to Strength", 20.0)]),
+        ];
+        let ranked = rank_comparables(&item, candidates, DEFAULT_COMPARABLES);
+
+        let ids: Vec<&str> = ranked.iter().map(|c| c.listing.trade_id.as_str()).collect();
+        assert_eq!(ids, vec!["close", "partial"]);
+        assert!((ranked[0].similarity - (70.0 / 80.0 + 1.0) / 2.0).abs() < 1e-9);
+        assert!((ranked[1].similarity - 1.0 / 3.0).abs() < 1e-9);
+    }
+}
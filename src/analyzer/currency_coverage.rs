@@ -0,0 +1,123 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use crate::data::currency_converter::CurrencyConverter;
+use crate::models::{CleanedItem, Currency};
+use super::pipeline::ItemAnalyzer;
+
+// Listings priced in one currency and whether they could be converted
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CurrencyListings {
+    pub currency: Currency,
+    pub listings: usize,
+    // Has a rate to the base currency, or is the base currency
+    pub converted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoverageReport {
+    pub base: Currency,
+    pub priced_listings: usize,
+    pub converted_listings: usize,
+    // Share of priced listings whose price could be normalized, None with no priced listings
+    pub coverage: Option<f64>,
+    // Listings without a price at all
+    pub unpriced_listings: usize,
+    // Most listed first
+    pub currencies: Vec<CurrencyListings>,
+}
+
+// Counts listings per currency to show how much of the market the exchange rates on disk
+// can normalize, and which currencies or barter items the rest are priced in
+pub struct CurrencyCoverage {
+    converter: CurrencyConverter,
+    listings: BTreeMap<Currency, usize>,
+    unpriced_listings: usize,
+}
+
+impl CurrencyCoverage {
+    pub fn new(converter: CurrencyConverter) -> Self {
+        Self {
+            converter,
+            listings: BTreeMap::new(),
+            unpriced_listings: 0,
+        }
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        match &item.price {
+            Some(price) => *self.listings.entry(price.currency.clone()).or_default() += 1,
+            None => self.unpriced_listings += 1,
+        }
+    }
+
+    pub fn coverage_report(&self) -> CoverageReport {
+        let mut currencies: Vec<CurrencyListings> = self.listings
+            .iter()
+            .map(|(currency, &listings)| CurrencyListings {
+                currency: currency.clone(),
+                listings,
+                converted: self.converter.convert(1.0, currency).is_some(),
+            })
+            .collect();
+        currencies.sort_by_key(|currency| std::cmp::Reverse(currency.listings));
+
+        let priced_listings = currencies.iter().map(|currency| currency.listings).sum();
+        let converted_listings = currencies
+            .iter()
+            .filter(|currency| currency.converted)
+            .map(|currency| currency.listings)
+            .sum();
+        CoverageReport {
+            base: self.converter.base().clone(),
+            priced_listings,
+            converted_listings,
+            coverage: (priced_listings > 0).then(|| converted_listings as f64 / priced_listings as f64),
+            unpriced_listings: self.unpriced_listings,
+            currencies,
+        }
+    }
+}
+
+impl ItemAnalyzer for CurrencyCoverage {
+    fn name(&self) -> &str {
+        "currencies"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!(self.coverage_report())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+
+    #[test]
+    fn test_coverage_counts_unconverted_currencies() {
+        let mut converter = CurrencyConverter::new(Currency::Exalted);
+        converter.set_rate(Currency::Divine, 100.0);
+        let mut coverage = CurrencyCoverage::new(converter);
+        let listings = [
+            ("a", 1.0, "exalted"),
+            ("b", 2.0, "exalted"),
+            ("c", 0.5, "Divine Orbs"),
+            ("d", 3.0, "ancient-rib"),
+        ];
+        for (id, amount, currency) in listings {
+            let item = ListingBuilder::new(id, "Iron Ring").with_price(amount).with_currency(currency).cleaned();
+            coverage.process(&item);
+        }
+
+        let report = coverage.coverage_report();
+        assert_eq!((report.priced_listings, report.converted_listings), (4, 3));
+        assert_eq!(report.coverage, Some(0.75));
+        assert_eq!(report.currencies[0], CurrencyListings { currency: Currency::Exalted, listings: 2, converted: true });
+        let rib = report.currencies.iter().find(|currency| !currency.converted).unwrap();
+        assert_eq!((rib.currency.as_str(), rib.listings), ("ancient-rib", 1));
+    }
+}
@@ -0,0 +1,188 @@
+use serde::Serialize;
+use std::fmt;
+use std::time::Duration;
+use crate::data::stat_catalog_loader::StatCatalogLoader;
+use crate::fetcher::{parse_rules, RangeFilter, RateLimitRule, SearchRequest, MAX_FETCH_IDS};
+use super::collection_checkpoint::RangeKey;
+
+// Result IDs one trade search returns at most
+pub const SEARCH_RESULT_LIMIT: usize = 100;
+
+// Limits the trade API is assumed to have before it has answered with its own
+pub const ASSUMED_SEARCH_RULES: &str = "5:10:60,15:60:300,30:300:1800";
+pub const ASSUMED_FETCH_RULES: &str = "12:4:10,16:12:300";
+
+// One query of a plan as a dry run saw it
+#[derive(Debug, Clone, Serialize)]
+pub struct DryRunQuery {
+    #[serde(flatten)]
+    pub range: RangeKey,
+    // What's wrong with the query as built, found without asking the API
+    pub problems: Vec<String>,
+    // Listings the search matched, when it was sent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u32>,
+    // Result IDs a collection run would fetch for the query
+    pub to_fetch: usize,
+}
+
+// What a collection run of a plan would do, worked out without fetching or writing anything
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DryRunReport {
+    pub league: String,
+    pub queries: Vec<DryRunQuery>,
+    // Whether the searches were sent to count their results
+    pub searched: bool,
+    pub search_calls: usize,
+    pub fetch_calls: usize,
+    pub estimated_duration_secs: f64,
+}
+
+impl DryRunReport {
+    pub fn problems(&self) -> usize {
+        self.queries.iter().map(|query| query.problems.len()).sum()
+    }
+
+    // Count the API calls the queries take and how long the rate limits stretch them over
+    pub fn estimate(&mut self, search_rules: &[RateLimitRule], fetch_rules: &[RateLimitRule]) {
+        self.search_calls = self.queries.len();
+        self.fetch_calls = self.queries.iter().map(|query| query.to_fetch.div_ceil(MAX_FETCH_IDS)).sum();
+        // Search and fetch are limited apart and their waits overlap, so the slower sets the pace
+        let duration = minimum_duration(self.search_calls, search_rules).max(minimum_duration(self.fetch_calls, fetch_rules));
+        self.estimated_duration_secs = duration.as_secs_f64();
+    }
+}
+
+// Rules the server reported for an endpoint, or the assumed ones until it has
+pub fn rules_or_assumed(reported: Vec<RateLimitRule>, assumed: &str) -> Vec<RateLimitRule> {
+    if reported.is_empty() { parse_rules(assumed) } else { reported }
+}
+
+// The least time `requests` take without breaking any of the rules
+pub fn minimum_duration(requests: usize, rules: &[RateLimitRule]) -> Duration {
+    rules
+        .iter()
+        .filter(|rule| rule.max_hits > 0)
+        .map(|rule| rule.period * (requests.saturating_sub(1) / rule.max_hits as usize) as u32)
+        .max()
+        .unwrap_or_default()
+}
+
+// Problems in a built search request that the API would reject or that can't match
+// anything: stat IDs the catalogue doesn't know (when there is one), inverted or negative
+// bounds and more stats than the trade site takes in one query
+pub fn query_problems(request: &SearchRequest, catalog: Option<&StatCatalogLoader>) -> Vec<String> {
+    let mut problems = request.complexity_problems();
+    let query = &request.query;
+    if query.filters.type_filters.filters.category.option.is_empty() {
+        problems.push("no item category".to_string());
+    }
+
+    for stat in query.stats.iter().flat_map(|group| &group.filters) {
+        if catalog.is_some_and(|catalog| catalog.get_stat(&stat.id).is_none()) {
+            problems.push(format!("unknown stat ID {}", stat.id));
+        }
+        if let Some(value) = &stat.value {
+            if let (Some(min), Some(max)) = (value.min, value.max) {
+                if min > max {
+                    problems.push(format!("stat {} has min {} above max {}", stat.id, min, max));
+                }
+            }
+        }
+    }
+
+    if let Some(price) = query.filters.trade_filters.as_ref().and_then(|trade| trade.filters.price.as_ref()) {
+        if price.min.is_some_and(|min| min < 0.0) || price.max.is_some_and(|max| max < 0.0) {
+            problems.push("negative price bound".to_string());
+        }
+        if let (Some(min), Some(max)) = (price.min, price.max) {
+            if min > max {
+                problems.push(format!("price min {} above max {}", min, max));
+            }
+        }
+    }
+
+    let filters = &query.filters;
+    let ranges = [
+        ("item level", filters.misc_filters.as_ref().and_then(|misc| misc.filters.ilvl.as_ref())),
+        ("quality", filters.misc_filters.as_ref().and_then(|misc| misc.filters.quality.as_ref())),
+        ("sockets", filters.socket_filters.as_ref().and_then(|sockets| sockets.filters.sockets.as_ref())),
+        ("rune sockets", filters.equipment_filters.as_ref().and_then(|equipment| equipment.filters.rune_sockets.as_ref())),
+        ("level requirement", filters.req_filters.as_ref().and_then(|req| req.filters.lvl.as_ref())),
+        ("strength requirement", filters.req_filters.as_ref().and_then(|req| req.filters.strength.as_ref())),
+        ("dexterity requirement", filters.req_filters.as_ref().and_then(|req| req.filters.dexterity.as_ref())),
+        ("intelligence requirement", filters.req_filters.as_ref().and_then(|req| req.filters.intelligence.as_ref())),
+        ("waystone tier", filters.map_filters.as_ref().and_then(|map| map.filters.map_tier.as_ref())),
+    ];
+    for (name, range) in ranges {
+        if let Some(RangeFilter { min: Some(min), max: Some(max) }) = range {
+            if min > max {
+                problems.push(format!("{} min {} above max {}", name, min, max));
+            }
+        }
+    }
+    problems
+}
+
+impl fmt::Display for DryRunReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Dry run ({})", self.league)?;
+        for query in &self.queries {
+            let label = query.range.to_string();
+            match query.total {
+                Some(total) => write!(f, "  {:<20} {:>6} matched {:>4} to fetch", label, total, query.to_fetch)?,
+                None => write!(f, "  {:<20} {:>4} to fetch at most", label, query.to_fetch)?,
+            }
+            if query.problems.is_empty() {
+                writeln!(f)?;
+            } else {
+                writeln!(f, "  {}", query.problems.join("; "))?;
+            }
+        }
+        writeln!(f, "  Queries:        {}", self.queries.len())?;
+        writeln!(f, "  Problems:       {}", self.problems())?;
+        writeln!(f, "  Search calls:   {}", self.search_calls)?;
+        writeln!(f, "  Fetch calls:    {}", self.fetch_calls)?;
+        write!(f, "  Est. duration:  {:.0}s", self.estimated_duration_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::QueryBuilder;
+
+    #[test]
+    fn test_query_problems() {
+        let path = std::env::temp_dir().join(format!("poe2-dry-run-stats-{}.json", std::process::id()));
+        std::fs::write(&path, r#"[{"id": "explicit.stat_3299347043", "text": "+# to Strength", "type": "explicit"}]"#).unwrap();
+        let mut catalog = StatCatalogLoader::new();
+        tokio::runtime::Runtime::new().unwrap().block_on(catalog.load_from_file(path.to_str().unwrap())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let sane = QueryBuilder::new().category("armour").stat("explicit.stat_3299347043", 10, 50).price_range(Some(1.0), Some(5.0)).build();
+        assert!(query_problems(&sane, Some(&catalog)).is_empty());
+
+        let broken = QueryBuilder::new().category("armour").stat("explicit.stat_1", 50, 10).price_range(Some(5.0), Some(1.0)).build();
+        assert_eq!(query_problems(&broken, Some(&catalog)), [
+            "unknown stat ID explicit.stat_1",
+            "stat explicit.stat_1 has min 50 above max 10",
+            "price min 5 above max 1",
+        ]);
+        // Without a catalogue only the bounds are checked
+        assert_eq!(query_problems(&broken, None).len(), 2);
+    }
+
+    #[test]
+    fn test_estimate_calls_and_duration() {
+        let query = |to_fetch| DryRunQuery { range: RangeKey::category("jewel"), problems: vec![], total: None, to_fetch };
+        let mut report = DryRunReport { queries: vec![query(100), query(25), query(0)], ..DryRunReport::default() };
+        report.estimate(&parse_rules("5:10:60"), &parse_rules("12:4:10"));
+
+        assert_eq!((report.search_calls, report.fetch_calls), (3, 13));
+        // 13 fetches at 12 per 4s need one more window
+        assert_eq!(report.estimated_duration_secs, 4.0);
+        assert_eq!(minimum_duration(11, &parse_rules("5:10:60,15:60:300")), Duration::from_secs(20));
+        assert_eq!(rules_or_assumed(Vec::new(), ASSUMED_FETCH_RULES).len(), 2);
+    }
+}
@@ -0,0 +1,146 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+use crate::data::currency_converter::CurrencyConverter;
+use crate::models::{percentile, CleanedItem, GemKind};
+use super::pipeline::ItemAnalyzer;
+
+// Prices of one gem at one level and quality, clean or corrupted
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GemPriceReport {
+    pub name: String,
+    pub kind: GemKind,
+    pub level: u32,
+    pub quality: u32,
+    // Corrupted gems can't be changed further, and corruption is how gems go past their
+    // level and quality caps, so they're priced apart from clean ones
+    pub corrupted: bool,
+    pub listings: usize,
+    pub median_price: f64,
+    pub p25: f64,
+    pub p75: f64,
+}
+
+// Name, level, quality and whether it's corrupted
+type GemKey = (String, u32, u32, bool);
+
+// Prices gems by name, level, quality and corruption, which is what sets a gem's price rather than
+// any modifiers
+#[derive(Default)]
+pub struct GemAnalyzer {
+    // Kind and prices of each gem
+    gems: BTreeMap<GemKey, (GemKind, Vec<f64>)>,
+    converter: Option<CurrencyConverter>,
+    unconverted_listings: usize,
+}
+
+impl GemAnalyzer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_currency_converter(&mut self, converter: CurrencyConverter) {
+        self.converter = Some(converter);
+    }
+
+    pub fn unconverted_listings(&self) -> usize {
+        self.unconverted_listings
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        let (Some(gem), Some(price)) = (&item.gem, &item.price) else {
+            return;
+        };
+        let amount = match &self.converter {
+            Some(converter) => match converter.convert(price.amount, &price.currency) {
+                Some(amount) => amount,
+                None => {
+                    self.unconverted_listings += 1;
+                    return;
+                }
+            },
+            None => price.amount,
+        };
+
+        self.gems
+            .entry((item.name.clone(), gem.level, gem.quality, item.corrupted))
+            .or_insert_with(|| (gem.kind, Vec::new()))
+            .1
+            .push(amount);
+    }
+
+    // Every level, quality and corruption combination seen, by gem name then level and
+    // quality, clean before corrupted
+    pub fn reports(&self) -> Vec<GemPriceReport> {
+        self.gems
+            .iter()
+            .map(|((name, level, quality, corrupted), (kind, prices))| {
+                let mut prices = prices.clone();
+                prices.sort_by(|a, b| a.total_cmp(b));
+                GemPriceReport {
+                    name: name.clone(),
+                    kind: *kind,
+                    level: *level,
+                    quality: *quality,
+                    corrupted: *corrupted,
+                    listings: prices.len(),
+                    median_price: percentile(&prices, 0.5),
+                    p25: percentile(&prices, 0.25),
+                    p75: percentile(&prices, 0.75),
+                }
+            })
+            .collect()
+    }
+}
+
+impl ItemAnalyzer for GemAnalyzer {
+    fn name(&self) -> &str {
+        "gems"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!({
+            "unconverted_listings": self.unconverted_listings,
+            "gems": self.reports(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+    use crate::models::GemProperties;
+
+    fn gem(id: usize, level: u32, quality: u32, price: f64) -> CleanedItem {
+        let listing = ListingBuilder::new(&format!("listing{}", id), "Controlled Destruction")
+            .with_rarity("Normal")
+            .with_price(price);
+        CleanedItem { gem: Some(GemProperties { kind: GemKind::Support, level, quality }), ..listing.cleaned() }
+    }
+
+    #[test]
+    fn test_gems_priced_by_level_quality_and_corruption() {
+        let mut analyzer = GemAnalyzer::new();
+        for (i, (level, quality, price)) in [(20, 0, 4.0), (20, 0, 6.0), (20, 20, 30.0), (1, 0, 0.5)].into_iter().enumerate() {
+            analyzer.process_cleaned_item(&gem(i, level, quality, price));
+        }
+        let corrupted = CleanedItem { corrupted: true, ..gem(4, 20, 0, 50.0) };
+        analyzer.process_cleaned_item(&corrupted);
+
+        let combos: Vec<(u32, u32, bool, usize, f64)> = analyzer
+            .reports()
+            .iter()
+            .map(|report| (report.level, report.quality, report.corrupted, report.listings, report.median_price))
+            .collect();
+        assert_eq!(combos, vec![
+            (1, 0, false, 1, 0.5),
+            (20, 0, false, 2, 5.0),
+            (20, 0, true, 1, 50.0),
+            (20, 20, false, 1, 30.0),
+        ]);
+    }
+}
@@ -0,0 +1,308 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::data::currency_converter::CurrencyConverter;
+use crate::models::{modifier_template, CleanedItem, ItemClass, ItemRarity};
+use crate::storage::csv_field;
+use super::pipeline::ItemAnalyzer;
+use super::report_export::escape_html;
+
+// Most common modifiers used as columns when none are named
+pub const DEFAULT_HEATMAP_MODIFIERS: usize = 10;
+
+// Listings a cell needs before its average is shown
+pub const DEFAULT_HEATMAP_MIN_LISTINGS: usize = 3;
+
+// Column of the listings carrying every named modifier at once
+const COMBINED_COLUMN: &str = "all of the above";
+
+// Listings of one base carrying one modifier (or all of them, for the combined column)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeatmapCell {
+    pub listings: usize,
+    pub average_price: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeatmapRow {
+    pub base_type: String,
+    pub listings: usize,
+    // One per column; None below the minimum listings
+    pub cells: Vec<Option<HeatmapCell>>,
+}
+
+// Base types against modifiers, each cell the average price of the base's listings with
+// the modifier, most valuable base first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HeatmapReport {
+    pub class: Option<ItemClass>,
+    pub columns: Vec<String>,
+    pub unconverted_listings: usize,
+    pub rows: Vec<HeatmapRow>,
+}
+
+impl HeatmapReport {
+    pub fn to_csv(&self) -> String {
+        let mut headers = vec!["base_type".to_string(), "listings".to_string()];
+        for column in &self.columns {
+            headers.push(csv_field(column));
+            headers.push(csv_field(&format!("{} listings", column)));
+        }
+        let mut csv = headers.join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            let mut fields = vec![csv_field(&row.base_type), row.listings.to_string()];
+            for cell in &row.cells {
+                match cell {
+                    Some(cell) => {
+                        fields.push(format!("{:.2}", cell.average_price));
+                        fields.push(cell.listings.to_string());
+                    }
+                    None => fields.extend([String::new(), String::new()]),
+                }
+            }
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    // A standalone page with each cell shaded by its price relative to the priciest cell
+    pub fn to_html(&self) -> String {
+        let max = self.rows
+            .iter()
+            .flat_map(|row| row.cells.iter().flatten())
+            .map(|cell| cell.average_price)
+            .fold(0.0, f64::max);
+        let title = match self.class {
+            Some(class) => format!("Modifier value by base type: {}", class),
+            None => "Modifier value by base type".to_string(),
+        };
+
+        let mut html = format!(
+            concat!(
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n",
+                "<style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}",
+                "th,td{{border:1px solid #ccc;padding:4px 8px;text-align:right}}th{{background:#eee}}",
+                "td:first-child{{text-align:left}}small{{color:#555}}</style>\n",
+                "</head>\n<body>\n<h1>{title}</h1>\n<table>\n<thead><tr><th>base type</th>",
+            ),
+            title = escape_html(&title),
+        );
+        for column in &self.columns {
+            html.push_str(&format!("<th>{}</th>", escape_html(column)));
+        }
+        html.push_str("</tr></thead>\n<tbody>\n");
+        for row in &self.rows {
+            html.push_str(&format!("<tr><td>{} <small>({})</small></td>", escape_html(&row.base_type), row.listings));
+            for cell in &row.cells {
+                match cell {
+                    Some(cell) => {
+                        let shade = if max > 0.0 { cell.average_price / max } else { 0.0 };
+                        html.push_str(&format!(
+                            "<td style=\"background:rgba(220,60,30,{:.2})\">{:.2} <small>({})</small></td>",
+                            shade * 0.8,
+                            cell.average_price,
+                            cell.listings,
+                        ));
+                    }
+                    None => html.push_str("<td></td>"),
+                }
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</tbody>\n</table>\n</body>\n</html>\n");
+        html
+    }
+}
+
+#[derive(Default)]
+struct BaseTally {
+    listings: usize,
+    // Prices of the listings carrying each modifier, keyed by template
+    prices: HashMap<String, Vec<f64>>,
+    // Prices of the listings carrying every named modifier
+    combined: Vec<f64>,
+}
+
+// Which base types make the most of a modifier: the average price of each base's listings
+// carrying it. Modifiers are named by text or template, e.g. "#% increased Movement
+// Speed"; without any, the most common ones are used. Uniques are left out, as their
+// modifiers are fixed.
+pub struct ModValueHeatmap {
+    modifiers: Vec<String>,
+    class: Option<ItemClass>,
+    top_modifiers: usize,
+    min_listings: usize,
+    converter: Option<CurrencyConverter>,
+    unconverted_listings: usize,
+    bases: BTreeMap<String, BaseTally>,
+    // Listings carrying each modifier across bases, for picking the most common
+    occurrences: HashMap<String, usize>,
+}
+
+impl ModValueHeatmap {
+    pub fn new(modifiers: Vec<String>) -> Self {
+        Self {
+            modifiers: modifiers.iter().map(|modifier| modifier_template(modifier)).collect(),
+            class: None,
+            top_modifiers: DEFAULT_HEATMAP_MODIFIERS,
+            min_listings: DEFAULT_HEATMAP_MIN_LISTINGS,
+            converter: None,
+            unconverted_listings: 0,
+            bases: BTreeMap::new(),
+            occurrences: HashMap::new(),
+        }
+    }
+
+    // Only count bases of the class, as told by their names
+    pub fn with_class(mut self, class: Option<ItemClass>) -> Self {
+        self.class = class;
+        self
+    }
+
+    pub fn with_top_modifiers(mut self, top_modifiers: usize) -> Self {
+        self.top_modifiers = top_modifiers;
+        self
+    }
+
+    pub fn with_min_listings(mut self, min_listings: usize) -> Self {
+        self.min_listings = min_listings;
+        self
+    }
+
+    pub fn set_currency_converter(&mut self, converter: CurrencyConverter) {
+        self.converter = Some(converter);
+    }
+
+    fn price(&mut self, item: &CleanedItem) -> Option<f64> {
+        let price = item.price.as_ref()?;
+        match &self.converter {
+            Some(converter) => {
+                let amount = converter.convert(price.amount, &price.currency);
+                if amount.is_none() {
+                    self.unconverted_listings += 1;
+                }
+                amount
+            }
+            None => Some(price.amount),
+        }
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        if item.rarity == ItemRarity::Unique {
+            return;
+        }
+        if self.class.is_some() && ItemClass::from_base_name(&item.base_type) != self.class {
+            return;
+        }
+        let Some(price) = self.price(item) else {
+            return;
+        };
+
+        let templates: HashSet<String> = item.explicit_mods.iter().map(|text| modifier_template(text)).collect();
+        let tally = self.bases.entry(item.base_type.clone()).or_default();
+        tally.listings += 1;
+        for template in &templates {
+            tally.prices.entry(template.clone()).or_default().push(price);
+            *self.occurrences.entry(template.clone()).or_default() += 1;
+        }
+        if self.modifiers.len() > 1 && self.modifiers.iter().all(|modifier| templates.contains(modifier)) {
+            tally.combined.push(price);
+        }
+    }
+
+    // The named modifiers, or the most common ones
+    fn columns(&self) -> Vec<String> {
+        if !self.modifiers.is_empty() {
+            return self.modifiers.clone();
+        }
+        let mut common: Vec<(&String, &usize)> = self.occurrences.iter().collect();
+        common.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        common.into_iter().take(self.top_modifiers).map(|(modifier, _)| modifier.clone()).collect()
+    }
+
+    pub fn heatmap_report(&self) -> HeatmapReport {
+        let mut columns = self.columns();
+        let cell = |prices: Option<&Vec<f64>>| {
+            let prices = prices.filter(|prices| prices.len() >= self.min_listings.max(1))?;
+            Some(HeatmapCell {
+                listings: prices.len(),
+                average_price: prices.iter().sum::<f64>() / prices.len() as f64,
+            })
+        };
+
+        let combined = self.modifiers.len() > 1;
+        let mut rows: Vec<HeatmapRow> = self.bases
+            .iter()
+            .map(|(base_type, tally)| {
+                let mut cells: Vec<Option<HeatmapCell>> = columns.iter().map(|column| cell(tally.prices.get(column))).collect();
+                if combined {
+                    cells.push(cell(Some(&tally.combined)));
+                }
+                HeatmapRow { base_type: base_type.clone(), listings: tally.listings, cells }
+            })
+            .filter(|row| row.cells.iter().any(Option::is_some))
+            .collect();
+        // Ranked by the combined column when there is one, otherwise by the best cell
+        let rank = |row: &HeatmapRow| -> f64 {
+            let cells: &[Option<HeatmapCell>] = if combined { &row.cells[row.cells.len() - 1..] } else { &row.cells };
+            cells.iter().flatten().map(|cell| cell.average_price).fold(f64::MIN, f64::max)
+        };
+        rows.sort_by(|a, b| rank(b).total_cmp(&rank(a)).then(a.base_type.cmp(&b.base_type)));
+
+        if combined {
+            columns.push(COMBINED_COLUMN.to_string());
+        }
+        HeatmapReport {
+            class: self.class,
+            columns,
+            unconverted_listings: self.unconverted_listings,
+            rows,
+        }
+    }
+}
+
+impl ItemAnalyzer for ModValueHeatmap {
+    fn name(&self) -> &str {
+        "heatmap"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::to_value(self.heatmap_report()).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+
+    #[test]
+    fn test_heatmap_by_base_and_modifier() {
+        let mut heatmap = ModValueHeatmap::new(vec![
+            "25% increased Movement Speed".to_string(),
+            "+#% to Fire Resistance".to_string(),
+        ])
+        .with_class(Some(ItemClass::Boots))
+        .with_min_listings(1);
+        heatmap.process(&ListingBuilder::new("a", "Leather Boots").with_mods(&["20% increased Movement Speed"]).with_price(10.0).cleaned());
+        heatmap.process(&ListingBuilder::new("b", "Leather Boots").with_mods(&["25% increased Movement Speed", "+30% to Fire Resistance"]).with_price(30.0).cleaned());
+        heatmap.process(&ListingBuilder::new("c", "Velvet Slippers").with_mods(&["15% increased Movement Speed", "+20% to Fire Resistance"]).with_price(50.0).cleaned());
+        heatmap.process(&ListingBuilder::new("d", "Gold Ring").with_mods(&["+20% to Fire Resistance"]).with_price(100.0).cleaned());
+
+        let report = heatmap.heatmap_report();
+        assert_eq!(report.columns, vec!["#% increased Movement Speed", "+#% to Fire Resistance", COMBINED_COLUMN]);
+        assert_eq!(report.rows[0].base_type, "Velvet Slippers");
+        assert_eq!(report.rows[1].cells[0], Some(HeatmapCell { listings: 2, average_price: 20.0 }));
+        assert_eq!(report.rows[1].cells[2], Some(HeatmapCell { listings: 1, average_price: 30.0 }));
+
+        let csv = report.to_csv();
+        assert!(csv.starts_with("base_type,listings,#% increased Movement Speed,#% increased Movement Speed listings,"));
+        assert!(csv.contains("\nLeather Boots,2,20.00,2,30.00,1,30.00,1\n"));
+        assert!(report.to_html().contains("<td style=\"background:rgba(220,60,30,0.80)\">50.00 <small>(1)</small></td>"));
+    }
+}
@@ -0,0 +1,255 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use crate::data::currency_converter::CurrencyConverter;
+use crate::models::{modifier_template, percentile, CleanedItem, JewelRadius};
+use super::pipeline::ItemAnalyzer;
+
+// Modifier combinations reported per jewel base unless configured otherwise
+pub const DEFAULT_JEWEL_COMBINATIONS: usize = 10;
+
+// Listings a combination needs before it is reported
+pub const DEFAULT_JEWEL_MIN_LISTINGS: usize = 3;
+
+// A pair of modifiers seen together on jewels of one base, and what those jewels cost
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModCombination {
+    pub modifiers: Vec<String>,
+    pub listings: usize,
+    pub median_price: f64,
+    pub p25: f64,
+    pub p75: f64,
+}
+
+// Magic and rare jewels of one base, e.g. every non-unique Sapphire
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JewelBaseReport {
+    pub base_type: String,
+    pub listings: usize,
+    pub median_price: f64,
+    // Most listed first
+    pub combinations: Vec<ModCombination>,
+}
+
+// One unique jewel, split by the passives it allocates where those vary
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UniqueJewelReport {
+    pub name: String,
+    pub base_type: String,
+    pub radius: Option<JewelRadius>,
+    pub allocated_passives: Vec<String>,
+    pub listings: usize,
+    pub median_price: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JewelReport {
+    pub unconverted_listings: usize,
+    pub bases: Vec<JewelBaseReport>,
+    pub uniques: Vec<UniqueJewelReport>,
+}
+
+// Name, base type and allocated passives
+type UniqueKey = (String, String, Vec<String>);
+
+// Prices jewels by base and the modifier pairs they carry, and unique jewels by name and
+// allocated passives, since a jewel's worth is in its mods rather than any defences
+#[derive(Default)]
+pub struct JewelAnalyzer {
+    bases: BTreeMap<String, Vec<f64>>,
+    // Prices per base and pair of modifier templates
+    combinations: BTreeMap<(String, Vec<String>), Vec<f64>>,
+    uniques: BTreeMap<UniqueKey, (Option<JewelRadius>, Vec<f64>)>,
+    converter: Option<CurrencyConverter>,
+    unconverted_listings: usize,
+    top_combinations: usize,
+    min_listings: usize,
+}
+
+impl JewelAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            top_combinations: DEFAULT_JEWEL_COMBINATIONS,
+            min_listings: DEFAULT_JEWEL_MIN_LISTINGS,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_top_combinations(mut self, top_combinations: usize) -> Self {
+        self.top_combinations = top_combinations;
+        self
+    }
+
+    pub fn with_min_listings(mut self, min_listings: usize) -> Self {
+        self.min_listings = min_listings;
+        self
+    }
+
+    pub fn set_currency_converter(&mut self, converter: CurrencyConverter) {
+        self.converter = Some(converter);
+    }
+
+    pub fn unconverted_listings(&self) -> usize {
+        self.unconverted_listings
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        let (Some(jewel), Some(price)) = (&item.jewel, &item.price) else {
+            return;
+        };
+        let amount = match &self.converter {
+            Some(converter) => match converter.convert(price.amount, &price.currency) {
+                Some(amount) => amount,
+                None => {
+                    self.unconverted_listings += 1;
+                    return;
+                }
+            },
+            None => price.amount,
+        };
+
+        if jewel.unique {
+            let mut passives = jewel.allocated_passives.clone();
+            passives.sort();
+            self.uniques
+                .entry((item.name.clone(), item.base_type.clone(), passives))
+                .or_insert_with(|| (jewel.radius, Vec::new()))
+                .1
+                .push(amount);
+            return;
+        }
+
+        self.bases.entry(item.base_type.clone()).or_default().push(amount);
+        let templates: Vec<String> = item.explicit_mods
+            .iter()
+            .map(|text| modifier_template(text))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        for (i, first) in templates.iter().enumerate() {
+            for second in &templates[i + 1..] {
+                self.combinations
+                    .entry((item.base_type.clone(), vec![first.clone(), second.clone()]))
+                    .or_default()
+                    .push(amount);
+            }
+        }
+    }
+
+    pub fn jewel_report(&self) -> JewelReport {
+        let mut bases: Vec<JewelBaseReport> = self.bases
+            .iter()
+            .map(|(base_type, prices)| {
+                let mut combinations: Vec<ModCombination> = self.combinations
+                    .range((base_type.clone(), Vec::new())..)
+                    .take_while(|((base, _), _)| base == base_type)
+                    .filter(|(_, prices)| prices.len() >= self.min_listings.max(1))
+                    .map(|((_, modifiers), prices)| {
+                        let prices = sorted(prices);
+                        ModCombination {
+                            modifiers: modifiers.clone(),
+                            listings: prices.len(),
+                            median_price: percentile(&prices, 0.5),
+                            p25: percentile(&prices, 0.25),
+                            p75: percentile(&prices, 0.75),
+                        }
+                    })
+                    .collect();
+                combinations.sort_by(|a, b| b.listings.cmp(&a.listings).then(b.median_price.total_cmp(&a.median_price)));
+                combinations.truncate(self.top_combinations);
+
+                JewelBaseReport {
+                    base_type: base_type.clone(),
+                    listings: prices.len(),
+                    median_price: percentile(&sorted(prices), 0.5),
+                    combinations,
+                }
+            })
+            .collect();
+        bases.sort_by_key(|base| std::cmp::Reverse(base.listings));
+
+        let uniques = self.uniques
+            .iter()
+            .map(|((name, base_type, passives), (radius, prices))| UniqueJewelReport {
+                name: name.clone(),
+                base_type: base_type.clone(),
+                radius: *radius,
+                allocated_passives: passives.clone(),
+                listings: prices.len(),
+                median_price: percentile(&sorted(prices), 0.5),
+            })
+            .collect();
+
+        JewelReport {
+            unconverted_listings: self.unconverted_listings,
+            bases,
+            uniques,
+        }
+    }
+}
+
+fn sorted(prices: &[f64]) -> Vec<f64> {
+    let mut prices = prices.to_vec();
+    prices.sort_by(|a, b| a.total_cmp(b));
+    prices
+}
+
+impl ItemAnalyzer for JewelAnalyzer {
+    fn name(&self) -> &str {
+        "jewels"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!(self.jewel_report())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+
+    fn jewel(id: &str, base: &str, rarity: &str, mods: &[&str], price: f64) -> CleanedItem {
+        ListingBuilder::new(id, base)
+            .with_name(if rarity == "Unique" { "From Nothing" } else { "" })
+            .with_rarity(rarity)
+            .with_mods(mods)
+            .with_property("Radius", "Small")
+            .with_price(price)
+            .cleaned()
+    }
+
+    #[test]
+    fn test_jewels_priced_by_base_combination_and_unique() {
+        let mut analyzer = JewelAnalyzer::new().with_min_listings(2);
+        let life_speed = ["+8 to maximum Life", "4% increased Attack Speed", "+10% to Fire Resistance"];
+        analyzer.process_cleaned_item(&jewel("a", "Ruby", "Rare", &life_speed, 10.0));
+        analyzer.process_cleaned_item(&jewel("b", "Ruby", "Rare", &life_speed[..2], 20.0));
+        analyzer.process_cleaned_item(&jewel("c", "Sapphire", "Magic", &life_speed[..1], 1.0));
+        analyzer.process_cleaned_item(&jewel("d", "Time-Lost Sapphire", "Unique", &["Allocates Heavy Buffer"], 50.0));
+        analyzer.process_cleaned_item(&jewel("e", "Iron Ring", "Rare", &life_speed, 5.0));
+
+        let report = analyzer.jewel_report();
+        assert_eq!(report.bases.len(), 2);
+        let ruby = &report.bases[0];
+        assert_eq!((ruby.base_type.as_str(), ruby.listings, ruby.median_price), ("Ruby", 2, 15.0));
+        assert_eq!(ruby.combinations, vec![ModCombination {
+            modifiers: vec!["#% increased Attack Speed".to_string(), "+# to maximum Life".to_string()],
+            listings: 2,
+            median_price: 15.0,
+            p25: 12.5,
+            p75: 17.5,
+        }]);
+        assert_eq!(report.uniques, vec![UniqueJewelReport {
+            name: "From Nothing".to_string(),
+            base_type: "Time-Lost Sapphire".to_string(),
+            radius: Some(JewelRadius::Small),
+            allocated_passives: vec!["Heavy Buffer".to_string()],
+            listings: 1,
+            median_price: 50.0,
+        }]);
+    }
+}
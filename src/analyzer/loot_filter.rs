@@ -0,0 +1,328 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write;
+use crate::data::affix_data_loader::AffixSlot;
+use crate::data::currency_converter::CurrencyConverter;
+use crate::models::{CleanedItem, ItemRarity};
+use super::pipeline::ItemAnalyzer;
+use super::price_model::{PriceFeature, PriceModel, PriceObservation};
+
+// Listings a base type needs before its prices are modelled
+pub const DEFAULT_FILTER_MIN_LISTINGS: usize = 10;
+
+// Two-modifier rules kept per base, most valuable first, so common bases don't swamp the filter
+const MAX_PAIR_RULES_PER_BASE: usize = 10;
+
+// Rules worth this many times the threshold get the loudest style
+const TOP_TIER_MULTIPLIER: f64 = 10.0;
+
+// A modifier a rule asks for, matched in game by the names of the affixes carrying it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FilterModifier {
+    // Trade stat hashes of the modifier, sorted and joined with '|'
+    pub stat: String,
+    // Every affix name the modifier was listed under on the base, e.g. "Hale" and "Healthy"
+    pub affixes: Vec<String>,
+}
+
+// A base type, optionally with modifiers it has to carry, whose expected value reaches the
+// threshold
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FilterRule {
+    pub base_type: String,
+    // Empty when any drop of the base is worth picking up
+    pub modifiers: Vec<FilterModifier>,
+    // Price model estimate for the base with these modifiers, in the converter's base currency
+    pub expected_value: f64,
+    // Listings of the base the estimate comes from
+    pub listings: usize,
+}
+
+#[derive(Default)]
+struct BaseListings {
+    observations: Vec<PriceObservation>,
+    // Stat key -> affix names seen carrying it
+    affixes: BTreeMap<String, BTreeSet<String>>,
+}
+
+// Turns collected prices into loot filter rules: a price model is fitted per base type on
+// which affixes its listings carry, and the base alone, or with the one or two affixes
+// that lift its expected value over the threshold, is highlighted. Uniques are left out,
+// as they're priced by name rather than by their modifiers.
+pub struct LootFilterAnalyzer {
+    min_value: f64,
+    lambda: f64,
+    min_occurrences: usize,
+    min_listings: usize,
+    converter: Option<CurrencyConverter>,
+    unconverted_listings: usize,
+    bases: BTreeMap<String, BaseListings>,
+}
+
+impl LootFilterAnalyzer {
+    // `min_value` is in the converter's base currency, or as listed without a converter
+    pub fn new(min_value: f64) -> Self {
+        Self {
+            min_value,
+            lambda: 1.0,
+            min_occurrences: 5,
+            min_listings: DEFAULT_FILTER_MIN_LISTINGS,
+            converter: None,
+            unconverted_listings: 0,
+            bases: BTreeMap::new(),
+        }
+    }
+
+    // Ridge penalty and minimum occurrences of the per-base price models
+    pub fn with_price_model(mut self, lambda: f64, min_occurrences: usize) -> Self {
+        self.lambda = lambda;
+        self.min_occurrences = min_occurrences;
+        self
+    }
+
+    pub fn with_min_listings(mut self, min_listings: usize) -> Self {
+        self.min_listings = min_listings;
+        self
+    }
+
+    pub fn set_currency_converter(&mut self, converter: CurrencyConverter) {
+        self.converter = Some(converter);
+    }
+
+    pub fn unconverted_listings(&self) -> usize {
+        self.unconverted_listings
+    }
+
+    fn price(&mut self, item: &CleanedItem) -> Option<f64> {
+        let price = item.price.as_ref()?;
+        match &self.converter {
+            Some(converter) => {
+                let amount = converter.convert(price.amount, &price.currency);
+                if amount.is_none() {
+                    self.unconverted_listings += 1;
+                }
+                amount
+            }
+            None => Some(price.amount),
+        }
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        if item.rarity == ItemRarity::Unique {
+            return;
+        }
+        let Some(price) = self.price(item) else {
+            return;
+        };
+
+        let base = self.bases.entry(item.base_type.clone()).or_default();
+        let mut modifiers = HashMap::new();
+        for modifier in &item.mod_info.explicit {
+            if AffixSlot::from_tier(&modifier.tier).is_none() {
+                continue;
+            }
+            let mut hashes: Vec<&str> = modifier.magnitudes.iter().map(|m| m.hash.as_str()).collect();
+            hashes.sort_unstable();
+            hashes.dedup();
+            let stat = hashes.join("|");
+            if stat.is_empty() {
+                continue;
+            }
+            base.affixes.entry(stat.clone()).or_default().insert(modifier.name.clone());
+            modifiers.insert(stat, 1.0);
+        }
+        base.observations.push(PriceObservation { modifiers, price });
+    }
+
+    // Rules of every base type with enough listings, the most specific first within a
+    // base: a base whose plain drops reach the threshold gets one rule without modifiers,
+    // otherwise each affix and affix pair that lifts it over the threshold gets one
+    pub fn rules(&self) -> Vec<FilterRule> {
+        let mut rules = Vec::new();
+        for (base_type, base) in &self.bases {
+            let listings = base.observations.len();
+            if listings < self.min_listings.max(1) {
+                continue;
+            }
+            let model = PriceModel::fit(&base.observations, PriceFeature::Presence, self.lambda, self.min_occurrences);
+            let rule = |modifiers: Vec<FilterModifier>, expected_value: f64| FilterRule {
+                base_type: base_type.clone(),
+                modifiers,
+                expected_value,
+                listings,
+            };
+
+            let base_value = match &model {
+                Some(model) => model.intercept,
+                None => base.observations.iter().map(|o| o.price).sum::<f64>() / listings as f64,
+            };
+            if base_value >= self.min_value {
+                rules.push(rule(Vec::new(), base_value));
+                continue;
+            }
+            let Some(model) = model else {
+                continue;
+            };
+
+            let valuable: Vec<(FilterModifier, f64)> = model.coefficients
+                .iter()
+                .filter(|c| c.coefficient > 0.0)
+                .filter_map(|c| {
+                    let affixes = base.affixes.get(&c.modifier)?;
+                    Some((FilterModifier { stat: c.modifier.clone(), affixes: affixes.iter().cloned().collect() }, c.coefficient))
+                })
+                .collect();
+            let (singles, rest): (Vec<_>, Vec<_>) = valuable
+                .into_iter()
+                .partition(|(_, coefficient)| base_value + coefficient >= self.min_value);
+
+            let mut pairs = Vec::new();
+            for (i, (first, a)) in rest.iter().enumerate() {
+                for (second, b) in &rest[i + 1..] {
+                    let value = base_value + a + b;
+                    if value >= self.min_value {
+                        pairs.push(rule(vec![first.clone(), second.clone()], value));
+                    }
+                }
+            }
+            pairs.sort_by(|a, b| b.expected_value.total_cmp(&a.expected_value));
+            pairs.truncate(MAX_PAIR_RULES_PER_BASE);
+
+            rules.extend(pairs);
+            rules.extend(singles.into_iter().map(|(modifier, coefficient)| rule(vec![modifier], base_value + coefficient)));
+        }
+        rules
+    }
+
+    // The rules as a loot filter. Filters stop at the first block an item matches, so
+    // rules needing two affixes come before those needing one, and both before plain
+    // base rules; items matching none are shown as the game would anyway.
+    pub fn render(&self, rules: &[FilterRule], currency: &str) -> String {
+        let mut rules: Vec<&FilterRule> = rules.iter().collect();
+        rules.sort_by(|a, b| {
+            b.modifiers.len().cmp(&a.modifiers.len())
+                .then(b.expected_value.total_cmp(&a.expected_value))
+        });
+
+        let mut filter = String::new();
+        let _ = writeln!(filter, "# Generated by rust-scraper from collected trade listings");
+        let _ = writeln!(filter, "# Highlights items expected to be worth at least {:.2} {}", self.min_value, currency);
+        for rule in rules {
+            let _ = writeln!(filter);
+            let modifiers: Vec<&str> = rule.modifiers.iter().map(|m| m.stat.as_str()).collect();
+            let _ = writeln!(
+                filter,
+                "# {} {:.2} {} expected, from {} listings{}",
+                rule.base_type,
+                rule.expected_value,
+                currency,
+                rule.listings,
+                if modifiers.is_empty() { String::new() } else { format!(" ({})", modifiers.join(", ")) },
+            );
+            let _ = writeln!(filter, "Show");
+            let _ = writeln!(filter, "\tBaseType == {}", quoted(&rule.base_type));
+            let _ = writeln!(filter, "\tRarity <= Rare");
+            // Each line has to match, so every modifier is required
+            for modifier in &rule.modifiers {
+                let names: Vec<String> = modifier.affixes.iter().map(|name| quoted(name)).collect();
+                let _ = writeln!(filter, "\tHasExplicitMod {}", names.join(" "));
+            }
+            let style = if rule.expected_value >= self.min_value * TOP_TIER_MULTIPLIER {
+                TOP_STYLE
+            } else {
+                HIGHLIGHT_STYLE
+            };
+            for line in style {
+                let _ = writeln!(filter, "\t{}", line);
+            }
+        }
+        filter
+    }
+}
+
+const TOP_STYLE: [&str; 7] = [
+    "SetFontSize 45",
+    "SetTextColor 255 0 0 255",
+    "SetBorderColor 255 0 0 255",
+    "SetBackgroundColor 255 255 255 255",
+    "PlayAlertSound 6 300",
+    "PlayEffect Red",
+    "MinimapIcon 0 Red Star",
+];
+
+const HIGHLIGHT_STYLE: [&str; 7] = [
+    "SetFontSize 40",
+    "SetTextColor 255 255 255 255",
+    "SetBorderColor 255 207 0 255",
+    "SetBackgroundColor 60 40 0 240",
+    "PlayAlertSound 2 200",
+    "PlayEffect Yellow",
+    "MinimapIcon 1 Yellow Diamond",
+];
+
+// Filter strings can't escape quotes, so any are dropped
+fn quoted(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', ""))
+}
+
+impl ItemAnalyzer for LootFilterAnalyzer {
+    fn name(&self) -> &str {
+        "loot_filter"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!({
+            "min_value": self.min_value,
+            "unconverted_listings": self.unconverted_listings,
+            "rules": self.rules(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+
+    #[test]
+    fn test_rules_for_valuable_bases_and_affix_pairs() {
+        let mut analyzer = LootFilterAnalyzer::new(10.0).with_price_model(0.0, 1).with_min_listings(4);
+        // Rings are worth 1 plus 4 for life and 6 for speed, so only both reach 10
+        let rings = [
+            (vec![], 1.0),
+            (vec![("Hale", "explicit.stat_life")], 5.0),
+            (vec![("Healthy", "explicit.stat_life")], 5.0),
+            (vec![("of Speed", "explicit.stat_speed")], 7.0),
+            (vec![("Hale", "explicit.stat_life"), ("of Speed", "explicit.stat_speed")], 11.0),
+        ];
+        for (i, (mods, price)) in rings.iter().enumerate() {
+            let listing = mods
+                .iter()
+                .fold(ListingBuilder::new(&format!("ring{}", i), "Gold Ring"), |listing, (name, hash)| {
+                    listing.with_affix(name, "P1", hash, 10.0, 20.0)
+                });
+            analyzer.process(&listing.with_price(*price).cleaned());
+        }
+        for i in 0..4 {
+            analyzer.process(&ListingBuilder::new(&format!("amulet{}", i), "Gold Amulet").with_price(120.0).cleaned());
+        }
+        analyzer.process(&ListingBuilder::new("belt", "Heavy Belt").with_price(500.0).cleaned());
+
+        let rules = analyzer.rules();
+        assert_eq!(rules.len(), 2);
+        assert_eq!((rules[0].base_type.as_str(), rules[0].modifiers.len()), ("Gold Amulet", 0));
+        assert_eq!(rules[1].base_type, "Gold Ring");
+        assert_eq!(rules[1].modifiers[1].affixes, vec!["Hale", "Healthy"]);
+        assert!((rules[1].expected_value - 11.0).abs() < 1e-6);
+
+        let filter = analyzer.render(&rules, "exalted");
+        let ring = filter.find("BaseType == \"Gold Ring\"").unwrap();
+        assert!(ring < filter.find("BaseType == \"Gold Amulet\"").unwrap());
+        assert!(filter.contains("\tHasExplicitMod \"of Speed\"\n\tHasExplicitMod \"Hale\" \"Healthy\"\n"));
+        assert!(filter.contains("MinimapIcon 0 Red Star"));
+    }
+}
@@ -1,7 +1,127 @@
 mod modifier;
 pub mod stat_analyzer;
 mod stat_collection;
+mod collection_checkpoint;
+mod search_cache;
+mod collection_plan;
+mod collection_summary;
+mod dry_run;
+mod collected_data;
+mod price_model;
+mod outliers;
+mod snapshot;
+mod trend;
+mod comparables;
+mod clustering;
+mod requirement_model;
+mod report_export;
+mod charts;
+mod pipeline;
+mod unique;
+mod affixes;
+mod gems;
+mod weighting;
+mod attribute_stacking;
+mod loot_filter;
+mod heatmap;
+mod currency_coverage;
+mod jewels;
+mod waystones;
 
-pub use modifier::ModifierAnalyzer;
-pub use stat_analyzer::StatAnalyzer;
-pub use stat_collection::StatCollector;
\ No newline at end of file
+pub use modifier::{ModifierAnalyzer, ModifierAnalyzerSnapshot};
+pub use attribute_stacking::{
+    AttributeStackAnalyzer,
+    StackingBase,
+    StackingModifier,
+    StackingPriceBracket,
+    StackingReport,
+    granted_attribute,
+    DEFAULT_STACKING_BASES,
+    DEFAULT_STACKING_MIN_LISTINGS,
+};
+pub use stat_analyzer::{
+    StatAnalyzer, StatAnalyzerSnapshot, ModifierPair, ValueScaling, DEFAULT_MIN_PAIR_SUPPORT, MIN_SCALING_OBSERVATIONS,
+};
+pub use stat_collection::StatCollector;
+pub use collection_plan::{
+    CollectionPlan, PlanSort, PriceBand, SweepLimits, DEFAULT_SWEEP_PAGES, SWEEP_CATEGORIES, SWEEP_PAGE_SIZE,
+};
+pub use collection_checkpoint::{CollectionCheckpoint, DEFAULT_CHECKPOINT_PATH};
+pub use search_cache::{SearchResultCache, DEFAULT_SEARCH_CACHE_PATH};
+pub use collection_summary::CollectionSummary;
+pub use dry_run::{
+    DryRunQuery,
+    DryRunReport,
+    minimum_duration,
+    query_problems,
+    rules_or_assumed,
+    ASSUMED_FETCH_RULES,
+    ASSUMED_SEARCH_RULES,
+    SEARCH_RESULT_LIMIT,
+};
+pub use collected_data::{
+    CollectedDataFormat,
+    CollectedDataHeader,
+    CollectedDataset,
+    CollectedDataWriter,
+    COLLECTED_DATA_VERSION,
+    for_each_collected_item,
+    read_collected_file,
+    load_collected_items,
+    save_collected_items,
+};
+pub use price_model::{PriceModel, PriceFeature, PriceObservation, ModifierCoefficient};
+pub use outliers::{
+    OutlierFilter,
+    OutlierMethod,
+    OutlierSummary,
+    DEFAULT_IQR_MULTIPLIER,
+    DEFAULT_MAD_THRESHOLD,
+};
+pub use snapshot::{AnalyzerSnapshot, SNAPSHOT_VERSION};
+pub use trend::{TrendAnalyzer, TrendSubject, DailyAggregate, TrendPoint, MarketSignal, format_timestamp, parse_day, parse_timestamp};
+pub use comparables::{
+    Comparable,
+    PriceCheck,
+    price_check,
+    rank_comparables,
+    similarity,
+    DEFAULT_COMPARABLES,
+    DEFAULT_SALE_WINDOW_HOURS,
+};
+pub use clustering::{ClusterReport, ItemCluster, ClusterModifier, cluster_listings, DEFAULT_CLUSTER_SEED};
+pub use requirement_model::{RequirementPrediction, RequirementRange};
+pub use report_export::{ReportFormat, ReportTable, attribute_tables, html_report, write_report};
+pub use charts::{write_charts, histogram_svg, scatter_svg, DEFAULT_CHART_MODIFIERS};
+pub use pipeline::{ItemAnalyzer, AnalysisPipeline};
+pub use unique::{UniqueAnalyzer, UniqueReport, RollPricing, RollBand, ROLL_BANDS};
+pub use affixes::{AffixAnalyzer, FlaggedListing};
+pub use gems::{GemAnalyzer, GemPriceReport};
+pub use jewels::{
+    JewelAnalyzer,
+    JewelBaseReport,
+    JewelReport,
+    ModCombination,
+    UniqueJewelReport,
+    DEFAULT_JEWEL_COMBINATIONS,
+    DEFAULT_JEWEL_MIN_LISTINGS,
+};
+pub use waystones::{
+    WaystoneAnalyzer,
+    WaystoneModifier,
+    WaystoneReport,
+    WaystoneTierReport,
+    DEFAULT_WAYSTONE_MIN_LISTINGS,
+    DEFAULT_WAYSTONE_MODIFIERS,
+};
+pub use currency_coverage::{CurrencyCoverage, CoverageReport, CurrencyListings};
+pub use loot_filter::{LootFilterAnalyzer, FilterRule, FilterModifier, DEFAULT_FILTER_MIN_LISTINGS};
+pub use heatmap::{
+    ModValueHeatmap,
+    HeatmapReport,
+    HeatmapRow,
+    HeatmapCell,
+    DEFAULT_HEATMAP_MODIFIERS,
+    DEFAULT_HEATMAP_MIN_LISTINGS,
+};
+pub use weighting::{WeightedPrices, weighted_mean, weighted_median, effective_sample_size};
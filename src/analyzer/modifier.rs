@@ -1,45 +1,253 @@
 use crate::models::{
+    CleanedItem,
+    CorruptionSplit,
+    ItemRarity,
     ItemResponse,
     ModifierStats,
-    ModInfo
+    ModKind,
+    Price,
+    RollQualityBucket,
 };
-use std::collections::HashMap;
+use crate::models::poe_item::ModBase;
+use crate::data::currency_converter::CurrencyConverter;
+use super::price_model::{PriceFeature, PriceModel, PriceObservation};
+use super::outliers::{OutlierFilter, OutlierSummary};
+use super::clustering::{cluster_listings, ClusterReport};
+use super::weighting::WeightedPrices;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// A listing that passed the currency and price range checks
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ProcessedListing {
+    id: String,
+    raw_price: Price,
+    // Price in the converter's base currency, or as listed without a converter
+    price: f64,
+    #[serde(default)]
+    rarity: ItemRarity,
+    #[serde(default)]
+    corrupted: bool,
+    modifiers: Vec<(ModKind, ModBase)>,
+    // Market listings this one stands for, see ItemResponse::sampling_weight
+    #[serde(default = "unit_weight")]
+    weight: f64,
+    // Outlier filtering is redone each run, so this isn't kept in snapshots
+    #[serde(skip)]
+    excluded: bool,
+}
+
+fn unit_weight() -> f64 {
+    1.0
+}
+
+impl ProcessedListing {
+    // Mean roll quality of the modifiers whose roll ranges are known
+    fn roll_quality(&self) -> Option<f64> {
+        let qualities: Vec<f64> = self.modifiers.iter().filter_map(|(_, m)| m.roll_quality()).collect();
+        if qualities.is_empty() {
+            return None;
+        }
+        Some(qualities.iter().sum::<f64>() / qualities.len() as f64)
+    }
+}
+
+// The listings a ModifierAnalyzer has taken in; its statistics are rebuilt from them on restore
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModifierAnalyzerSnapshot {
+    listings: Vec<ProcessedListing>,
+    unconverted_listings: usize,
+}
+
+impl ModifierAnalyzerSnapshot {
+    pub fn listings(&self) -> usize {
+        self.listings.len()
+    }
+}
 
 pub struct ModifierAnalyzer {
     stats: HashMap<String, ModifierStats>,
+    // Breakpoints that split modifier values into buckets, in ascending order;
+    // empty to split each modifier's observed values evenly
     value_ranges: Vec<f64>,
     min_price: Option<f64>,
     max_price: Option<f64>,
+    // Normalizes listing prices to one currency; without it prices are used as listed
+    converter: Option<CurrencyConverter>,
+    // Listings skipped because their currency had no known rate
+    unconverted_listings: usize,
+    // Kept so outlier filtering and the price regression can revisit every listing
+    listings: Vec<ProcessedListing>,
+    // IDs of the listings above, so a listing seen again in a later run isn't counted twice
+    seen: HashSet<String>,
+    outliers: Option<OutlierSummary>,
+    // Which kinds of mods are analyzed; explicit only unless set
+    mod_kinds: Vec<ModKind>,
+    // Uniques are priced by name rather than by modifiers, see UniqueAnalyzer
+    include_uniques: bool,
+    skipped_uniques: usize,
 }
 
 impl ModifierAnalyzer {
-    pub fn new(value_ranges: Vec<f64>) -> Self {
+    pub fn new(mut value_ranges: Vec<f64>) -> Self {
+        value_ranges.sort_by(|a, b| a.total_cmp(b));
         Self {
             stats: HashMap::new(),
             value_ranges,
             min_price: None,
             max_price: None,
+            converter: None,
+            unconverted_listings: 0,
+            listings: Vec::new(),
+            seen: HashSet::new(),
+            outliers: None,
+            mod_kinds: vec![ModKind::Explicit],
+            include_uniques: false,
+            skipped_uniques: 0,
         }
     }
 
     pub fn process_item(&mut self, item: &ItemResponse) {
+        let modifiers = self.mod_kinds
+            .iter()
+            .flat_map(|&kind| item.item.rolled_mods(kind).into_iter().map(move |m| (kind, m)))
+            .collect();
         // Price is not an Option in the listing
-        let price = &item.listing.price;
-        // The explicit mods are directly a Vec, not an Option
-        for mod_info in &item.item.extended.mods.explicit {
-            self.process_modifier(mod_info, price.amount);
+        let rarity = item.item.item_rarity();
+        let weight = item.sampling_weight.unwrap_or(1.0);
+        self.process_listing(&item.id, &item.listing.price, rarity, item.item.corrupted, weight, modifiers);
+    }
+
+    // Cleaned items without a listing price have nothing to relate their modifiers to
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        let Some(price) = &item.price else {
+            return;
+        };
+        let modifiers = self.mod_kinds
+            .iter()
+            .flat_map(|&kind| item.mod_info.of_kind(kind).iter().map(move |m| (kind, (**m).clone())))
+            .collect();
+        self.process_listing(&item.id, price, item.rarity, item.corrupted, 1.0, modifiers);
+    }
+
+    fn process_listing(&mut self, id: &str, price: &Price, rarity: ItemRarity, corrupted: bool, weight: f64, modifiers: Vec<(ModKind, ModBase)>) {
+        if rarity == ItemRarity::Unique && !self.include_uniques {
+            self.skipped_uniques += 1;
+            return;
+        }
+        let amount = match &self.converter {
+            Some(converter) => match converter.convert(price.amount, &price.currency) {
+                Some(amount) => amount,
+                None => {
+                    self.unconverted_listings += 1;
+                    return;
+                }
+            },
+            None => price.amount,
+        };
+        if self.min_price.is_some_and(|min| amount < min)
+            || self.max_price.is_some_and(|max| amount > max)
+        {
+            return;
+        }
+        if !self.seen.insert(id.to_string()) {
+            return;
+        }
+
+        let listing = ProcessedListing {
+            id: id.to_string(),
+            raw_price: price.clone(),
+            price: amount,
+            rarity,
+            corrupted,
+            modifiers,
+            weight,
+            excluded: false,
+        };
+        self.record_listing(&listing);
+        self.listings.push(listing);
+    }
+
+    fn record_listing(&mut self, listing: &ProcessedListing) {
+        for (kind, mod_info) in &listing.modifiers {
+            self.process_modifier(*kind, mod_info, &listing.raw_price, listing.price);
         }
     }
 
-    fn process_modifier(&mut self, mod_info: &ModInfo, price: f64) {
+    pub fn snapshot(&self) -> ModifierAnalyzerSnapshot {
+        ModifierAnalyzerSnapshot {
+            listings: self.listings.clone(),
+            unconverted_listings: self.unconverted_listings,
+        }
+    }
+
+    // Add the listings of an earlier run's snapshot. Prices stay as they were converted
+    // then, and listings this analyzer already has are skipped.
+    pub fn restore(&mut self, snapshot: ModifierAnalyzerSnapshot) {
+        for listing in snapshot.listings {
+            if self.seen.insert(listing.id.clone()) {
+                self.record_listing(&listing);
+                self.listings.push(listing);
+            }
+        }
+        self.unconverted_listings += snapshot.unconverted_listings;
+    }
+
+    // Flag listings whose price is extreme compared to the rest. When the filter excludes
+    // them, the modifier statistics are rebuilt without those listings and the price model
+    // leaves them out too.
+    pub fn apply_outlier_filter(&mut self, filter: OutlierFilter) -> &OutlierSummary {
+        let prices: Vec<f64> = self.listings.iter().map(|listing| listing.price).collect();
+        let bounds = filter.bounds(&prices);
+
+        let mut summary = OutlierSummary {
+            filter,
+            lower_bound: bounds.map(|(lower, _)| lower),
+            upper_bound: bounds.map(|(_, upper)| upper),
+            below: 0,
+            above: 0,
+            excluded: 0,
+            listings: Vec::new(),
+        };
+        for listing in &mut self.listings {
+            let (below, above) = match bounds {
+                Some((lower, upper)) => (listing.price < lower, listing.price > upper),
+                None => (false, false),
+            };
+            if below || above {
+                summary.below += below as usize;
+                summary.above += above as usize;
+                summary.listings.push(listing.id.clone());
+                listing.excluded = filter.exclude;
+            }
+        }
+
+        if filter.exclude {
+            summary.excluded = summary.listings.len();
+            let listings = std::mem::take(&mut self.listings);
+            self.stats.clear();
+            for listing in listings.iter().filter(|listing| !listing.excluded) {
+                self.record_listing(listing);
+            }
+            self.listings = listings;
+        }
+
+        self.outliers.insert(summary)
+    }
+
+    fn process_modifier(&mut self, kind: ModKind, mod_info: &ModBase, raw_price: &Price, price: f64) {
+        let name = kind.stat_key(&mod_info.name);
         let stats = self.stats
-            .entry(mod_info.name.clone())
-            .or_insert_with(|| ModifierStats::new(mod_info.name.clone()));
+            .entry(name.clone())
+            .or_insert_with(|| ModifierStats::new(name));
 
         // Get the first magnitude value if it exists
-        if let Some(magnitude) = mod_info.magnitudes.first() {
-            if let Ok(value) = magnitude.min.parse::<f64>() {
-                stats.add_data_point(value, price);
+        if let Some(value) = mod_info.rolled_value() {
+            stats.add_data_point(value, price);
+            stats.raw_prices.push((raw_price.amount, raw_price.currency.clone()));
+            stats.update_value_ranges(&self.value_ranges);
+            if let Some(quality) = mod_info.roll_quality() {
+                stats.add_roll_quality(quality);
             }
         }
     }
@@ -48,8 +256,227 @@ impl ModifierAnalyzer {
         self.stats.get(modifier_name)
     }
 
+    // Bounds are in the converter's base currency once one is set
+    pub fn all_stats(&self) -> impl Iterator<Item = &ModifierStats> {
+        self.stats.values()
+    }
+
     pub fn set_price_range(&mut self, min: f64, max: f64) {
         self.min_price = Some(min);
         self.max_price = Some(max);
     }
+
+    pub fn set_currency_converter(&mut self, converter: CurrencyConverter) {
+        self.converter = Some(converter);
+    }
+
+    // Non-explicit mods are reported under a kind-prefixed name, see ModKind::stat_key
+    pub fn set_mod_kinds(&mut self, kinds: Vec<ModKind>) {
+        self.mod_kinds = kinds;
+    }
+
+    pub fn set_include_uniques(&mut self, include_uniques: bool) {
+        self.include_uniques = include_uniques;
+    }
+
+    pub fn unconverted_listings(&self) -> usize {
+        self.unconverted_listings
+    }
+
+    // Listings still in the statistics, as modifier name -> rolled value plus price
+    fn observations(&self) -> Vec<PriceObservation> {
+        self.listings
+            .iter()
+            .filter(|listing| !listing.excluded)
+            .map(|listing| PriceObservation {
+                modifiers: listing.modifiers
+                    .iter()
+                    .map(|(kind, mod_info)| {
+                        (kind.stat_key(&mod_info.name), mod_info.rolled_value().unwrap_or(1.0))
+                    })
+                    .collect(),
+                price: listing.price,
+            })
+            .collect()
+    }
+
+    // Estimate each modifier's contribution to the listing price with a ridge regression
+    pub fn price_model(&self, feature: PriceFeature, lambda: f64, min_occurrences: usize) -> Option<PriceModel> {
+        PriceModel::fit(&self.observations(), feature, lambda, min_occurrences)
+    }
+
+    // Group listings into `k` archetypes by which modifiers they have
+    pub fn clusters(&self, k: usize, min_occurrences: usize, seed: u64) -> Option<ClusterReport> {
+        cluster_listings(&self.observations(), k, min_occurrences, seed)
+    }
+
+    fn listings_by_rarity(&self) -> HashMap<ItemRarity, usize> {
+        let mut counts = HashMap::new();
+        for listing in self.listings.iter().filter(|listing| !listing.excluded) {
+            *counts.entry(listing.rarity).or_default() += 1;
+        }
+        counts
+    }
+
+    // Listing prices of clean and corrupted items, outliers left out when excluded
+    pub fn prices_by_corruption(&self) -> CorruptionSplit {
+        CorruptionSplit::from_prices(self.listings
+            .iter()
+            .filter(|listing| !listing.excluded)
+            .map(|listing| (listing.corrupted, listing.price)))
+    }
+
+    // Listing prices split by how well the listing's modifiers rolled on average, leaving
+    // out listings none of whose roll ranges are known
+    pub fn prices_by_roll_quality(&self) -> Vec<RollQualityBucket> {
+        RollQualityBucket::from_prices(self.listings
+            .iter()
+            .filter(|listing| !listing.excluded)
+            .filter_map(|listing| listing.roll_quality().map(|quality| (quality, listing.price))))
+    }
+
+    // Price estimates weighted by how many market listings each sampled one stands for,
+    // outliers left out when excluded
+    pub fn weighted_prices(&self) -> WeightedPrices {
+        WeightedPrices::from_listings(self.listings
+            .iter()
+            .filter(|listing| !listing.excluded)
+            .map(|listing| {
+                let modifiers = listing.modifiers.iter().map(|(kind, m)| kind.stat_key(&m.name)).collect();
+                (listing.price, listing.weight, modifiers)
+            }))
+    }
+
+    pub fn generate_report(&self, model: Option<&PriceModel>, clusters: Option<&ClusterReport>) -> serde_json::Value {
+        serde_json::json!({
+            "listings_analyzed": self.listings.iter().filter(|listing| !listing.excluded).count(),
+            "unconverted_listings": self.unconverted_listings,
+            "listings_by_rarity": self.listings_by_rarity(),
+            "prices_by_corruption": self.prices_by_corruption(),
+            "prices_by_roll_quality": self.prices_by_roll_quality(),
+            "weighted_prices": self.weighted_prices(),
+            "skipped_uniques": self.skipped_uniques,
+            "outliers": self.outliers,
+            "price_unit": self.converter.as_ref().map(|converter| converter.base()),
+            "modifier_stats": self.stats,
+            "price_model": model,
+            "clusters": clusters,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::{OutlierMethod, DEFAULT_IQR_MULTIPLIER};
+    use crate::models::{Currency, ModifierRoll};
+
+    fn modifier(value: &str) -> ModBase {
+        serde_json::from_value(serde_json::json!({
+            "name": "Sturdy",
+            "tier": "P1",
+            "level": 1,
+            "magnitudes": [{ "hash": "explicit.stat_1", "min": value, "max": value }]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_values_are_bucketed_by_breakpoints() {
+        let mut analyzer = ModifierAnalyzer::new(vec![0.0, 10.0, 20.0]);
+        for value in ["5", "10", "20", "25"] {
+            let price = Price { amount: 1.0, currency: Currency::Exalted };
+            analyzer.process_modifier(ModKind::Explicit, &modifier(value), &price, 1.0);
+        }
+
+        let stats = analyzer.get_stats("Sturdy").unwrap();
+        assert_eq!(stats.total_occurrences, 4);
+        let counts: Vec<u32> = stats.value_ranges.iter().map(|range| range.count).collect();
+        assert_eq!(counts, vec![1, 2]);
+        assert_eq!(stats.value_ranges[1].prices.as_ref().unwrap().p50, 1.0);
+    }
+
+    #[test]
+    fn test_excluded_outliers_leave_the_stats() {
+        let mut analyzer = ModifierAnalyzer::new(Vec::new());
+        let prices = [4.0, 5.0, 5.0, 6.0, 7.0, 8.0, 50000.0];
+        for (i, amount) in prices.into_iter().enumerate() {
+            let listing = ProcessedListing {
+                id: format!("listing{}", i),
+                raw_price: Price { amount, currency: Currency::Exalted },
+                price: amount,
+                rarity: ItemRarity::Rare,
+                corrupted: i % 2 == 1,
+                modifiers: vec![(ModKind::Explicit, modifier("10"))],
+                weight: 1.0,
+                excluded: false,
+            };
+            analyzer.record_listing(&listing);
+            analyzer.listings.push(listing);
+        }
+
+        let filter = OutlierFilter::new(OutlierMethod::Iqr(DEFAULT_IQR_MULTIPLIER), true);
+        let summary = analyzer.apply_outlier_filter(filter);
+        assert_eq!(summary.above, 1);
+        assert_eq!(summary.excluded, 1);
+        assert_eq!(summary.listings, vec!["listing6"]);
+        assert_eq!(analyzer.get_stats("Sturdy").unwrap().total_occurrences, 6);
+
+        let split = analyzer.prices_by_corruption();
+        assert_eq!((split.clean_listings, split.corrupted_listings), (3, 3));
+        assert_eq!(split.clean.unwrap().p50, 5.0);
+        assert_eq!(split.corrupted.unwrap().p50, 6.0);
+    }
+
+    #[test]
+    fn test_snapshot_restores_listings_once() {
+        let mut analyzer = ModifierAnalyzer::new(Vec::new());
+        for (i, value) in ["10", "20", "30"].into_iter().enumerate() {
+            let listing = ProcessedListing {
+                id: format!("listing{}", i),
+                raw_price: Price { amount: 2.0, currency: Currency::Exalted },
+                price: 2.0,
+                rarity: ItemRarity::Rare,
+                corrupted: false,
+                modifiers: vec![(ModKind::Explicit, modifier(value))],
+                weight: 1.0,
+                excluded: false,
+            };
+            analyzer.seen.insert(listing.id.clone());
+            analyzer.record_listing(&listing);
+            analyzer.listings.push(listing);
+        }
+
+        let json = serde_json::to_string(&analyzer.snapshot()).unwrap();
+        let snapshot: ModifierAnalyzerSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot.listings(), 3);
+
+        let mut restored = ModifierAnalyzer::new(Vec::new());
+        restored.restore(snapshot.clone());
+        restored.restore(snapshot);
+        assert_eq!(restored.get_stats("Sturdy").unwrap().total_occurrences, 3);
+        assert_eq!(restored.listings.len(), 3);
+    }
+
+    #[test]
+    fn test_prices_split_by_roll_quality() {
+        let mut analyzer = ModifierAnalyzer::new(Vec::new());
+        for (i, (rolled, amount)) in [(40.0, 2.0), (41.0, 3.0), (46.0, 9.0), (50.0, 30.0)].into_iter().enumerate() {
+            let mut base = modifier("40");
+            base.magnitudes[0].max = "50".to_string();
+            base.rolls = vec![ModifierRoll { current: rolled, min: 40.0, max: 50.0 }];
+            let price = Price { amount, currency: Currency::Exalted };
+            analyzer.process_listing(&format!("listing{}", i), &price, ItemRarity::Rare, false, 1.0, vec![(ModKind::Explicit, base)]);
+        }
+
+        let stats = analyzer.get_stats("Sturdy").unwrap();
+        assert_eq!(stats.rolls_measured, 4);
+        assert!((stats.average_roll_quality.unwrap() - 42.5).abs() < 1e-9);
+        assert_eq!(stats.measures.max, 50.0);
+
+        let buckets = analyzer.prices_by_roll_quality();
+        let counts: Vec<usize> = buckets.iter().map(|bucket| bucket.listings).collect();
+        assert_eq!(counts, vec![2, 1, 0, 1]);
+        assert_eq!(buckets[3].prices.as_ref().unwrap().p50, 30.0);
+    }
 }
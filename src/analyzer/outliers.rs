@@ -0,0 +1,98 @@
+use serde::Serialize;
+use crate::models::percentile;
+
+pub const DEFAULT_IQR_MULTIPLIER: f64 = 1.5;
+pub const DEFAULT_MAD_THRESHOLD: f64 = 3.5;
+
+// Scales the MAD so it estimates the standard deviation of normally distributed data
+const MAD_SCALE: f64 = 0.6745;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "method", content = "threshold")]
+pub enum OutlierMethod {
+    // Outside [Q1 - k * IQR, Q3 + k * IQR]
+    Iqr(f64),
+    // Modified z-score (0.6745 * |x - median| / MAD) above the threshold
+    Mad(f64),
+}
+
+// Decides which listing prices are too extreme to trust. Prices are compared on a log
+// scale since listing prices are heavily right-skewed; a 1 mirror troll listing and a
+// 0.1 regal one are both far out in log terms while ordinary expensive items aren't.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct OutlierFilter {
+    pub method: OutlierMethod,
+    // Drop outliers from the statistics instead of only flagging them
+    pub exclude: bool,
+}
+
+impl OutlierFilter {
+    pub fn new(method: OutlierMethod, exclude: bool) -> Self {
+        Self { method, exclude }
+    }
+
+    // Lowest and highest price that still count as normal, or None when there is too
+    // little spread to judge
+    pub fn bounds(&self, prices: &[f64]) -> Option<(f64, f64)> {
+        let mut logs: Vec<f64> = prices.iter().filter(|&&p| p > 0.0).map(|p| p.ln()).collect();
+        if logs.len() < 4 {
+            return None;
+        }
+        logs.sort_by(|a, b| a.total_cmp(b));
+
+        let (lower, upper) = match self.method {
+            OutlierMethod::Iqr(k) => {
+                let q1 = percentile(&logs, 0.25);
+                let q3 = percentile(&logs, 0.75);
+                let iqr = q3 - q1;
+                (q1 - k * iqr, q3 + k * iqr)
+            }
+            OutlierMethod::Mad(threshold) => {
+                let median = percentile(&logs, 0.5);
+                let mut deviations: Vec<f64> = logs.iter().map(|x| (x - median).abs()).collect();
+                deviations.sort_by(|a, b| a.total_cmp(b));
+                let mad = percentile(&deviations, 0.5);
+                if mad == 0.0 {
+                    return None;
+                }
+                let spread = threshold * mad / MAD_SCALE;
+                (median - spread, median + spread)
+            }
+        };
+
+        Some((lower.exp(), upper.exp()))
+    }
+}
+
+// What the filter found, for the analysis report
+#[derive(Debug, Clone, Serialize)]
+pub struct OutlierSummary {
+    pub filter: OutlierFilter,
+    pub lower_bound: Option<f64>,
+    pub upper_bound: Option<f64>,
+    pub below: usize,
+    pub above: usize,
+    pub excluded: usize,
+    // IDs of the listings outside the bounds
+    pub listings: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_troll_prices_fall_outside_bounds() {
+        let prices = [0.01, 4.0, 5.0, 5.0, 6.0, 7.0, 8.0, 10.0, 12.0, 50000.0];
+
+        for method in [OutlierMethod::Iqr(DEFAULT_IQR_MULTIPLIER), OutlierMethod::Mad(DEFAULT_MAD_THRESHOLD)] {
+            let (lower, upper) = OutlierFilter::new(method, true).bounds(&prices).unwrap();
+            assert!(lower > 0.01 && lower < 4.0, "{:?} lower bound {}", method, lower);
+            assert!(upper > 12.0 && upper < 50000.0, "{:?} upper bound {}", method, upper);
+        }
+
+        let filter = OutlierFilter::new(OutlierMethod::Mad(DEFAULT_MAD_THRESHOLD), false);
+        assert_eq!(filter.bounds(&[5.0, 5.0, 5.0, 5.0, 100.0]), None);
+        assert_eq!(filter.bounds(&[1.0, 2.0]), None);
+    }
+}
@@ -0,0 +1,218 @@
+use futures_util::TryStreamExt;
+use std::collections::HashSet;
+use std::path::Path;
+use tracing::warn;
+use crate::errors::Result;
+use crate::metrics::Metrics;
+use crate::models::{CleanedItem, ItemResponse};
+use crate::storage::{Storage, ITEM_PAGE_SIZE};
+use super::collected_data::{read_collected_file, CollectedDataHeader};
+use super::modifier::ModifierAnalyzer;
+use super::stat_analyzer::StatAnalyzer;
+use super::trend::TrendAnalyzer;
+
+// A pass over collected items. Implement this to run your own analysis alongside the
+// built-in ones without reading the collected data again.
+pub trait ItemAnalyzer {
+    // Key the analyzer's report is filed under in the combined report
+    fn name(&self) -> &str;
+
+    fn process(&mut self, item: &CleanedItem);
+
+    fn report(&self) -> serde_json::Value;
+}
+
+impl ItemAnalyzer for StatAnalyzer {
+    fn name(&self) -> &str {
+        "attributes"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        self.generate_attribute_report()
+    }
+}
+
+impl ItemAnalyzer for ModifierAnalyzer {
+    fn name(&self) -> &str {
+        "modifiers"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    // Without the price model or clusters, which need parameters of their own
+    fn report(&self) -> serde_json::Value {
+        self.generate_report(None, None)
+    }
+}
+
+impl ItemAnalyzer for TrendAnalyzer {
+    fn name(&self) -> &str {
+        "trends"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!({
+            "day": self.day(),
+            "unconverted_listings": self.unconverted_listings(),
+            "aggregates": self.aggregates(),
+        })
+    }
+}
+
+// Feeds every item through each registered analyzer in one pass. Analyzers are borrowed,
+// so callers can keep using them once the pipeline is done.
+#[derive(Default)]
+pub struct AnalysisPipeline<'a> {
+    analyzers: Vec<&'a mut dyn ItemAnalyzer>,
+    // Listing IDs already fed, so a listing collected twice reaches the analyzers once
+    seen: HashSet<String>,
+}
+
+impl<'a> AnalysisPipeline<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, analyzer: &'a mut dyn ItemAnalyzer) {
+        self.analyzers.push(analyzer);
+    }
+
+    pub fn with_analyzer(mut self, analyzer: &'a mut dyn ItemAnalyzer) -> Self {
+        self.register(analyzer);
+        self
+    }
+
+    pub fn process(&mut self, item: &CleanedItem) {
+        if !item.id.is_empty() && !self.seen.insert(item.id.clone()) {
+            return;
+        }
+        for analyzer in &mut self.analyzers {
+            analyzer.process(item);
+        }
+        Metrics::global().record_item_analyzed();
+    }
+
+    pub fn process_response(&mut self, item: &ItemResponse) {
+        self.process(&CleanedItem::from_response(item));
+    }
+
+    // Stream a collected data file through the analyzers, returning how many items it held
+    pub fn run(&mut self, path: &Path) -> Result<usize> {
+        self.run_with_header(path).map(|(_, count)| count)
+    }
+
+    // As run, also giving the file's header: its league, collection time and plan
+    pub fn run_with_header(&mut self, path: &Path) -> Result<(CollectedDataHeader, usize)> {
+        read_collected_file(path, |item| self.process_response(&item))
+    }
+
+    // Stream every stored item through the analyzers, returning how many there were. See
+    // CleanedItem::from_item for what stored items lack compared to collected data.
+    pub async fn run_storage(&mut self, storage: &impl Storage) -> Result<usize> {
+        let mut items = std::pin::pin!(storage.iter_items());
+        let mut count = 0;
+        while let Some(item) = items.try_next().await? {
+            self.process(&CleanedItem::from_item(&item));
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    // Stream every stored listing kept as fetched through the analyzers, returning how many
+    // there were. Unlike run_storage, items keep their affix names and stat hashes; listings
+    // stored without their raw JSON are skipped.
+    pub async fn run_raw_storage(&mut self, storage: &impl Storage) -> Result<usize> {
+        let mut count = 0;
+        let mut after_id = 0;
+        loop {
+            let page = storage.raw_listings_after(after_id, ITEM_PAGE_SIZE).await?;
+            let Some(&(last_id, _)) = page.last() else {
+                break;
+            };
+            after_id = last_id;
+            for (id, raw_json) in page {
+                match serde_json::from_str::<ItemResponse>(&raw_json) {
+                    Ok(item) => {
+                        self.process_response(&item);
+                        count += 1;
+                    }
+                    Err(e) => warn!(id, error = %e, "failed to parse stored listing"),
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    // Each analyzer's report keyed by its name
+    pub fn reports(&self) -> serde_json::Value {
+        self.analyzers
+            .iter()
+            .map(|analyzer| (analyzer.name().to_string(), analyzer.report()))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+
+    // Counts items per base type, standing in for a user-defined analysis
+    #[derive(Default)]
+    struct BaseCounter {
+        counts: std::collections::BTreeMap<String, u32>,
+    }
+
+    impl ItemAnalyzer for BaseCounter {
+        fn name(&self) -> &str {
+            "bases"
+        }
+
+        fn process(&mut self, item: &CleanedItem) {
+            *self.counts.entry(item.base_type.clone()).or_default() += 1;
+        }
+
+        fn report(&self) -> serde_json::Value {
+            serde_json::json!(self.counts)
+        }
+    }
+
+    #[test]
+    fn test_pipeline_feeds_every_analyzer() {
+        let mut stats = StatAnalyzer::new();
+        let mut modifiers = ModifierAnalyzer::new(Vec::new());
+        let mut bases = BaseCounter::default();
+        {
+            let mut pipeline = AnalysisPipeline::new()
+                .with_analyzer(&mut stats)
+                .with_analyzer(&mut modifiers)
+                .with_analyzer(&mut bases);
+            for (id, amount) in [("a", 1.0), ("b", 3.0), ("a", 1.0)] {
+                let listing = ListingBuilder::new(id, "Iron Ring")
+                    .with_mods(&["+10 to Strength"])
+                    .with_affix("of the Brute", "S1", "explicit.stat_1", 10.0, 10.0)
+                    .with_price(amount);
+                pipeline.process_response(&listing.response());
+            }
+
+            let reports = pipeline.reports();
+            assert_eq!(reports["attributes"]["total_items_analyzed"], 2);
+            assert_eq!(reports["modifiers"]["listings_analyzed"], 2);
+            // Repeats don't reach custom analyzers either
+            assert_eq!(reports["bases"]["Iron Ring"], 2);
+        }
+
+        assert_eq!(modifiers.get_stats("of the Brute").unwrap().total_occurrences, 2);
+    }
+}
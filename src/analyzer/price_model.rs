@@ -0,0 +1,200 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+// How a modifier enters the regression
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceFeature {
+    // 1 when the item has the modifier; the coefficient is what having it adds to the price
+    Presence,
+    // The modifier's rolled value; the coefficient is the price of one point of roll
+    Value,
+}
+
+// One listing as seen by the regression: modifier name -> rolled value, and its price
+#[derive(Debug, Clone)]
+pub struct PriceObservation {
+    pub modifiers: HashMap<String, f64>,
+    pub price: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModifierCoefficient {
+    pub modifier: String,
+    pub coefficient: f64,
+    pub occurrences: usize,
+}
+
+// Ridge regression of listing price on modifiers, most valuable modifier first
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceModel {
+    pub feature: PriceFeature,
+    pub lambda: f64,
+    pub observations: usize,
+    pub intercept: f64,
+    pub r_squared: f64,
+    pub coefficients: Vec<ModifierCoefficient>,
+}
+
+impl PriceModel {
+    // Fit price = intercept + sum(coefficient * feature) with an L2 penalty of `lambda`
+    // on the coefficients. Modifiers seen on fewer than `min_occurrences` listings are left
+    // out since their coefficients would be noise.
+    pub fn fit(
+        observations: &[PriceObservation],
+        feature: PriceFeature,
+        lambda: f64,
+        min_occurrences: usize,
+    ) -> Option<Self> {
+        let mut occurrences: HashMap<&str, usize> = HashMap::new();
+        for observation in observations {
+            for name in observation.modifiers.keys() {
+                *occurrences.entry(name.as_str()).or_default() += 1;
+            }
+        }
+        let mut names: Vec<&str> = occurrences
+            .iter()
+            .filter(|(_, &count)| count >= min_occurrences.max(1))
+            .map(|(&name, _)| name)
+            .collect();
+        names.sort_unstable();
+        if observations.is_empty() || names.is_empty() {
+            return None;
+        }
+
+        let rows: Vec<Vec<f64>> = observations
+            .iter()
+            .map(|observation| {
+                names.iter()
+                    .map(|name| match (observation.modifiers.get(*name), feature) {
+                        (None, _) => 0.0,
+                        (Some(_), PriceFeature::Presence) => 1.0,
+                        (Some(value), PriceFeature::Value) => *value,
+                    })
+                    .collect()
+            })
+            .collect();
+        let prices: Vec<f64> = observations.iter().map(|o| o.price).collect();
+
+        // Centre everything so the intercept drops out and isn't penalized
+        let n = rows.len() as f64;
+        let p = names.len();
+        let feature_means: Vec<f64> = (0..p)
+            .map(|j| rows.iter().map(|row| row[j]).sum::<f64>() / n)
+            .collect();
+        let price_mean = prices.iter().sum::<f64>() / n;
+
+        // Normal equations: (XᵀX + λI) β = Xᵀy
+        let mut gram = vec![vec![0.0; p]; p];
+        let mut moment = vec![0.0; p];
+        for (row, price) in rows.iter().zip(&prices) {
+            let centred: Vec<f64> = row.iter().zip(&feature_means).map(|(x, mean)| x - mean).collect();
+            for ((gram_row, moment), x) in gram.iter_mut().zip(&mut moment).zip(&centred) {
+                *moment += x * (price - price_mean);
+                for (cell, other) in gram_row.iter_mut().zip(&centred) {
+                    *cell += x * other;
+                }
+            }
+        }
+        for (j, gram_row) in gram.iter_mut().enumerate() {
+            gram_row[j] += lambda;
+        }
+
+        let betas = solve(gram, moment)?;
+        let intercept = price_mean - betas.iter().zip(&feature_means).map(|(b, m)| b * m).sum::<f64>();
+
+        let (mut residual, mut total) = (0.0, 0.0);
+        for (row, price) in rows.iter().zip(&prices) {
+            let predicted = intercept + row.iter().zip(&betas).map(|(x, b)| x * b).sum::<f64>();
+            residual += (price - predicted).powi(2);
+            total += (price - price_mean).powi(2);
+        }
+        let r_squared = if total > 0.0 { 1.0 - residual / total } else { 0.0 };
+
+        let mut coefficients: Vec<ModifierCoefficient> = names
+            .iter()
+            .zip(&betas)
+            .map(|(name, &coefficient)| ModifierCoefficient {
+                modifier: name.to_string(),
+                coefficient,
+                occurrences: occurrences[name],
+            })
+            .collect();
+        coefficients.sort_by(|a, b| b.coefficient.total_cmp(&a.coefficient));
+
+        Some(Self {
+            feature,
+            lambda,
+            observations: observations.len(),
+            intercept,
+            r_squared,
+            coefficients,
+        })
+    }
+}
+
+// Gaussian elimination with partial pivoting; None if the system is singular
+fn solve(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let n = rhs.len();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+        if matrix[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        rhs.swap(col, pivot);
+
+        let (upper, lower) = matrix.split_at_mut(col + 1);
+        let pivot_row = &upper[col];
+        for (offset, row) in lower.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            for (cell, pivot_cell) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *cell -= factor * pivot_cell;
+            }
+            rhs[col + 1 + offset] -= factor * rhs[col];
+        }
+    }
+
+    let mut solution = vec![0.0; n];
+    for row in (0..n).rev() {
+        let known: f64 = (row + 1..n).map(|k| matrix[row][k] * solution[k]).sum();
+        solution[row] = (rhs[row] - known) / matrix[row][row];
+    }
+    Some(solution)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(modifiers: &[(&str, f64)], price: f64) -> PriceObservation {
+        PriceObservation {
+            modifiers: modifiers.iter().map(|(name, value)| (name.to_string(), *value)).collect(),
+            price,
+        }
+    }
+
+    #[test]
+    fn test_recovers_presence_prices() {
+        // price = 2 + 10 * life + 3 * armour, exactly
+        let observations = vec![
+            observation(&[], 2.0),
+            observation(&[("life", 80.0)], 12.0),
+            observation(&[("armour", 40.0)], 5.0),
+            observation(&[("life", 90.0), ("armour", 35.0)], 15.0),
+            observation(&[("life", 70.0)], 12.0),
+        ];
+
+        let model = PriceModel::fit(&observations, PriceFeature::Presence, 0.0, 1).unwrap();
+        assert_eq!(model.coefficients[0].modifier, "life");
+        assert!((model.coefficients[0].coefficient - 10.0).abs() < 1e-9);
+        assert!((model.coefficients[1].coefficient - 3.0).abs() < 1e-9);
+        assert!((model.intercept - 2.0).abs() < 1e-9);
+        assert!((model.r_squared - 1.0).abs() < 1e-9);
+
+        // Ridge shrinks the coefficients towards zero
+        let ridge = PriceModel::fit(&observations, PriceFeature::Presence, 5.0, 1).unwrap();
+        assert!(ridge.coefficients[0].coefficient < 10.0);
+        assert!(PriceModel::fit(&observations, PriceFeature::Presence, 0.0, 10).is_none());
+    }
+}
@@ -0,0 +1,234 @@
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use crate::errors::Result;
+use crate::storage::csv_field;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    // One file per table
+    Csv,
+    // A single page with every table, sortable by clicking a column header
+    Html,
+}
+
+// One table of the analysis report, already flattened to text cells
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportTable {
+    // File stem for the CSV export
+    pub name: &'static str,
+    pub title: &'static str,
+    pub headers: Vec<&'static str>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl ReportTable {
+    pub fn to_csv(&self) -> String {
+        let mut csv = self.headers.join(",");
+        csv.push('\n');
+        for row in &self.rows {
+            let fields: Vec<String> = row.iter().map(|cell| csv_field(cell)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Correlations, modifier pairs and requirement statistics from the `attributes` part of
+// the analysis report (see StatAnalyzer::generate_attribute_report)
+pub fn attribute_tables(attributes: &Value) -> Vec<ReportTable> {
+    let mut correlations = Vec::new();
+    if let Some(by_attribute) = attributes["attribute_correlations"].as_object() {
+        for (attribute, correlation) in by_attribute {
+            if let Some(modifiers) = correlation["modifier_correlations"].as_object() {
+                for (modifier, strength) in modifiers {
+                    correlations.push(vec![
+                        attribute.clone(),
+                        modifier.clone(),
+                        cell(strength),
+                        cell(&correlation["occurrence_count"]),
+                        cell(&correlation["average_threshold"]),
+                    ]);
+                }
+            }
+        }
+    }
+    correlations.sort();
+
+    let pairs = attributes["modifier_pairs"]
+        .as_array()
+        .map(|pairs| {
+            pairs.iter()
+                .map(|pair| ["first", "second", "count", "support", "lift", "pmi"].iter().map(|key| cell(&pair[*key])).collect())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let statistics = &attributes["requirement_statistics"];
+    let averages = &statistics["average_requirements"];
+    let mut requirements: Vec<Vec<String>> = Vec::new();
+    if let Some(counts) = statistics["single_stat_counts"].as_object() {
+        for (stat, count) in counts {
+            requirements.push(vec![stat.clone(), cell(count), cell(&averages[stat]), String::new()]);
+        }
+    }
+    if let Some(counts) = statistics["dual_stat_counts"].as_object() {
+        for (stats, count) in counts {
+            requirements.push(vec![
+                stats.clone(),
+                cell(count),
+                cell(&averages[format!("{}-1", stats)]),
+                cell(&averages[format!("{}-2", stats)]),
+            ]);
+        }
+    }
+    requirements.sort();
+
+    vec![
+        ReportTable {
+            name: "correlations",
+            title: "Attribute correlations",
+            headers: vec!["attribute", "modifier", "correlation", "attribute_occurrences", "average_threshold"],
+            rows: correlations,
+        },
+        ReportTable {
+            name: "pairs",
+            title: "Modifier pairs",
+            headers: vec!["first", "second", "count", "support", "lift", "pmi"],
+            rows: pairs,
+        },
+        ReportTable {
+            name: "requirements",
+            title: "Requirement statistics",
+            headers: vec!["requirement", "listings", "average", "average_second"],
+            rows: requirements,
+        },
+    ]
+}
+
+pub(super) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Numbers sort numerically and everything else as text; a second click reverses the order
+const SORT_SCRIPT: &str = r#"
+document.querySelectorAll("th").forEach(function (th) {
+  th.addEventListener("click", function () {
+    var table = th.closest("table");
+    var body = table.tBodies[0];
+    var index = Array.prototype.indexOf.call(th.parentNode.children, th);
+    var ascending = th.dataset.order !== "asc";
+    th.dataset.order = ascending ? "asc" : "desc";
+    var rows = Array.prototype.slice.call(body.rows);
+    rows.sort(function (a, b) {
+      var x = a.cells[index].textContent, y = b.cells[index].textContent;
+      var nx = parseFloat(x), ny = parseFloat(y);
+      var order = (!isNaN(nx) && !isNaN(ny)) ? nx - ny : x.localeCompare(y);
+      return ascending ? order : -order;
+    });
+    rows.forEach(function (row) { body.appendChild(row); });
+  });
+});
+"#;
+
+// A standalone page with no external assets, so it can be opened straight from disk
+pub fn html_report(tables: &[ReportTable]) -> String {
+    let mut html = String::from(concat!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Stat analysis report</title>\n",
+        "<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:2em}",
+        "th,td{border:1px solid #ccc;padding:4px 8px;text-align:left}th{cursor:pointer;background:#eee}</style>\n",
+        "</head>\n<body>\n<h1>Stat analysis report</h1>\n",
+    ));
+
+    for table in tables {
+        html.push_str(&format!("<h2>{}</h2>\n<table>\n<thead><tr>", escape_html(table.title)));
+        for header in &table.headers {
+            html.push_str(&format!("<th>{}</th>", escape_html(header)));
+        }
+        html.push_str("</tr></thead>\n<tbody>\n");
+        for row in &table.rows {
+            html.push_str("<tr>");
+            for value in row {
+                html.push_str(&format!("<td>{}</td>", escape_html(value)));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</tbody>\n</table>\n");
+    }
+
+    html.push_str(&format!("<script>{}</script>\n</body>\n</html>\n", SORT_SCRIPT));
+    html
+}
+
+// Write the analysis report into `dir` in the given format, returning the files written
+pub async fn write_report(report: &Value, format: ReportFormat, dir: &Path) -> Result<Vec<PathBuf>> {
+    tokio::fs::create_dir_all(dir).await?;
+
+    let files: Vec<(PathBuf, String)> = match format {
+        ReportFormat::Json => vec![(dir.join("report.json"), serde_json::to_string_pretty(report)?)],
+        ReportFormat::Csv => attribute_tables(&report["attributes"])
+            .iter()
+            .map(|table| (dir.join(format!("{}.csv", table.name)), table.to_csv()))
+            .collect(),
+        ReportFormat::Html => vec![(dir.join("report.html"), html_report(&attribute_tables(&report["attributes"])))],
+    };
+
+    let mut written = Vec::new();
+    for (path, content) in files {
+        tokio::fs::write(&path, content).await?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_from_attribute_report() {
+        let attributes = serde_json::json!({
+            "attribute_correlations": {
+                "[Strength|Str]": {
+                    "attribute": "[Strength|Str]",
+                    "occurrence_count": 2,
+                    "average_threshold": 105.0,
+                    "modifier_correlations": { "of the Lion": 0.5 }
+                }
+            },
+            "modifier_pairs": [
+                { "first": "a, b", "second": "c", "count": 3, "support": 0.3, "lift": 2.0, "pmi": 1.0 }
+            ],
+            "requirement_statistics": {
+                "single_stat_counts": { "[Strength|Str]": 2 },
+                "dual_stat_counts": { "[Dexterity|Dex]-[Strength|Str]": 1 },
+                "average_requirements": {
+                    "[Strength|Str]": 105.0,
+                    "[Dexterity|Dex]-[Strength|Str]-1": 40.0,
+                    "[Dexterity|Dex]-[Strength|Str]-2": 60.0
+                }
+            }
+        });
+
+        let tables = attribute_tables(&attributes);
+        assert_eq!(tables[0].rows, vec![vec!["[Strength|Str]", "of the Lion", "0.5", "2", "105.0"]]);
+        assert_eq!(tables[1].to_csv(), "first,second,count,support,lift,pmi\n\"a, b\",c,3,0.3,2.0,1.0\n");
+        assert_eq!(tables[2].rows[0], vec!["[Dexterity|Dex]-[Strength|Str]", "1", "40.0", "60.0"]);
+
+        let html = html_report(&tables);
+        assert!(html.contains("<td>of the Lion</td>"));
+        assert!(html.contains("<script>"));
+    }
+}
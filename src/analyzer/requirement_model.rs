@@ -0,0 +1,107 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use crate::models::{percentile, CoreAttribute};
+
+// Spread of one attribute's requirement across the listings a modifier was seen on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequirementRange {
+    pub attribute: CoreAttribute,
+    // Listings with the modifier that required this attribute
+    pub observations: usize,
+    // Those listings as a share of all listings with the modifier
+    pub share: f64,
+    pub min: u32,
+    // 10th to 90th percentile, where most listings with the modifier fall
+    pub low: f64,
+    pub median: f64,
+    pub high: f64,
+    pub max: u32,
+}
+
+impl RequirementRange {
+    pub fn from_thresholds(attribute: CoreAttribute, thresholds: &[u32], listings: u32) -> Option<Self> {
+        if thresholds.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f64> = thresholds.iter().map(|&t| t as f64).collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        Some(Self {
+            attribute,
+            observations: thresholds.len(),
+            share: if listings == 0 { 0.0 } else { (thresholds.len() as f64 / listings as f64).min(1.0) },
+            min: *thresholds.iter().min()?,
+            low: percentile(&sorted, 0.1),
+            median: percentile(&sorted, 0.5),
+            high: percentile(&sorted, 0.9),
+            max: *thresholds.iter().max()?,
+        })
+    }
+}
+
+// Which attribute requirements a modifier turns up with, most common attribute first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RequirementPrediction {
+    pub modifier: String,
+    pub listings: u32,
+    pub ranges: Vec<RequirementRange>,
+}
+
+impl RequirementPrediction {
+    // `thresholds` maps requirement names as they appear on listings to the values seen
+    // alongside the modifier; spellings of the same attribute are merged
+    pub fn from_thresholds(modifier: &str, thresholds: &HashMap<String, Vec<u32>>, listings: u32) -> Option<Self> {
+        let mut by_attribute: HashMap<CoreAttribute, Vec<u32>> = HashMap::new();
+        for (name, values) in thresholds {
+            if let Some(attribute) = CoreAttribute::from_requirement_name(name) {
+                by_attribute.entry(attribute).or_default().extend(values);
+            }
+        }
+
+        let mut ranges: Vec<RequirementRange> = by_attribute
+            .into_iter()
+            .filter_map(|(attribute, values)| RequirementRange::from_thresholds(attribute, &values, listings))
+            .collect();
+        if ranges.is_empty() {
+            return None;
+        }
+        ranges.sort_by(|a, b| {
+            b.observations.cmp(&a.observations).then_with(|| a.min.cmp(&b.min))
+        });
+
+        Some(Self {
+            modifier: modifier.to_string(),
+            listings,
+            ranges,
+        })
+    }
+
+    pub fn range(&self, attribute: &CoreAttribute) -> Option<&RequirementRange> {
+        self.ranges.iter().find(|range| &range.attribute == attribute)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merges_requirement_spellings() {
+        let thresholds = HashMap::from([
+            ("[Strength|Str]".to_string(), vec![100, 120, 140]),
+            ("Strength".to_string(), vec![80]),
+            ("[Intelligence|Int]".to_string(), vec![60]),
+            ("Level".to_string(), vec![70]),
+        ]);
+        let prediction = RequirementPrediction::from_thresholds("of the Lion", &thresholds, 5).unwrap();
+
+        assert_eq!(prediction.ranges.len(), 2);
+        let strength = &prediction.ranges[0];
+        assert_eq!(strength.attribute, CoreAttribute::Strength);
+        assert_eq!((strength.observations, strength.min, strength.max), (4, 80, 140));
+        assert!((strength.median - 110.0).abs() < 1e-9);
+        assert!((strength.share - 0.8).abs() < 1e-9);
+        assert_eq!(prediction.range(&CoreAttribute::Intelligence).unwrap().min, 60);
+        assert!(prediction.range(&CoreAttribute::Dexterity).is_none());
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use crate::errors::Result;
+use crate::fetcher::{ResponseCache, SearchRequest};
+
+pub const DEFAULT_SEARCH_CACHE_PATH: &str = "data/search_results.json";
+
+// The result IDs each query returned when last searched, so a repeated poll only fetches
+// listings that are new since. A listing repriced in place keeps its ID, so its stored
+// price is only refreshed once it's fetched again.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchResultCache {
+    #[serde(skip)]
+    path: PathBuf,
+    // Result IDs by query hash
+    queries: HashMap<String, Vec<String>>,
+}
+
+impl SearchResultCache {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ..Self::default()
+        }
+    }
+
+    // Load the cache at `path`, or start an empty one if there is none
+    pub async fn load_or_new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        match tokio::fs::read_to_string(&path).await {
+            Ok(content) => {
+                let mut cache: Self = serde_json::from_str(&content)?;
+                cache.path = path;
+                Ok(cache)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::new(path)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Key of a query searched in a league; the league is part of the search URL, not the body
+    pub fn query_key(league: &str, query: &SearchRequest) -> Result<String> {
+        Ok(ResponseCache::cache_key("POST", league, &serde_json::to_string(query)?))
+    }
+
+    // Split a query's latest result IDs into those its previous search didn't return and
+    // those it did, remembering the latest ones for the next search
+    pub fn diff(&mut self, key: &str, ids: &[String]) -> (Vec<String>, Vec<String>) {
+        let previous: HashSet<&String> = self.queries.get(key).into_iter().flatten().collect();
+        let (unchanged, new) = ids.iter().cloned().partition(|id| previous.contains(id));
+        self.queries.insert(key.to_string(), ids.to_vec());
+        (new, unchanged)
+    }
+
+    pub async fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // Write then rename so a crash mid-write can't corrupt the previous cache
+        let tmp_path = self.path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_string(self)?).await?;
+        tokio::fs::rename(&tmp_path, &self.path).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_diff_against_previous_search() {
+        let path = std::env::temp_dir().join(format!("poe2-search-cache-test-{}.json", std::process::id()));
+        let mut cache = SearchResultCache::new(&path);
+
+        assert_eq!(cache.diff("q", &ids(&["a", "b"])), (ids(&["a", "b"]), vec![]));
+        assert_eq!(cache.diff("q", &ids(&["b", "c"])), (ids(&["c"]), ids(&["b"])));
+        assert_eq!(cache.diff("other", &ids(&["b"])), (ids(&["b"]), vec![]));
+        cache.save().await.unwrap();
+
+        let mut loaded = SearchResultCache::load_or_new(&path).await.unwrap();
+        assert_eq!(loaded.diff("q", &ids(&["a", "c"])), (ids(&["a"]), ids(&["c"])));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
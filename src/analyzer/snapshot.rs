@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::errors::{Result, ScraperError};
+use super::modifier::ModifierAnalyzerSnapshot;
+use super::stat_analyzer::StatAnalyzerSnapshot;
+
+// Bumped whenever the snapshot layout changes incompatibly
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+// Both analyzers' accumulated state, so statistics can keep growing across runs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyzerSnapshot {
+    pub version: u32,
+    // Unix seconds the snapshot was written
+    pub taken_at: u64,
+    pub stats: StatAnalyzerSnapshot,
+    pub modifiers: ModifierAnalyzerSnapshot,
+}
+
+impl AnalyzerSnapshot {
+    pub fn new(stats: StatAnalyzerSnapshot, modifiers: ModifierAnalyzerSnapshot) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            taken_at: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            stats,
+            modifiers,
+        }
+    }
+
+    // None when there is no snapshot at `path` yet
+    pub async fn load(path: &Path) -> Result<Option<Self>> {
+        let content = match tokio::fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let snapshot: Self = serde_json::from_str(&content)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(ScraperError::ValidationError(format!(
+                "analyzer snapshot {} has version {}, expected {}",
+                path.display(),
+                snapshot.version,
+                SNAPSHOT_VERSION
+            )));
+        }
+        Ok(Some(snapshot))
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // Write then rename so a crash mid-write keeps the previous snapshot intact
+        let tmp_path = path.with_extension("json.tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_string(self)?).await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+}
@@ -4,11 +4,14 @@ use serde_json::json;
 use crate::models::{
     ItemResponse,
     CleanedItem,
+    ItemRarity,
+    LinearFit,
+    ModKind,
 };
 use crate::models::poe_item::ModBase;
-use std::ops::Deref;
+use super::requirement_model::RequirementPrediction;
 
-#[derive(Debug, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum StatRequirementType {
     Single(String),
     Dual(String, String),
@@ -28,13 +31,83 @@ pub struct AttributeCorrelation {
     pub modifier_correlations: HashMap<String, f64>,
 }
 
+// Pairs seen together on fewer listings than this are left out of the pair report
+pub const DEFAULT_MIN_PAIR_SUPPORT: u32 = 5;
+
+// Modifier/attribute combinations seen fewer times than this get no value scaling fit
+pub const MIN_SCALING_OBSERVATIONS: usize = 3;
+
+// Whether a modifier rolls higher on items with a higher requirement of an attribute,
+// as a line through (requirement, rolled value) points
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValueScaling {
+    pub modifier: String,
+    pub attribute: String,
+    #[serde(flatten)]
+    pub fit: LinearFit,
+}
+
+// How strongly two modifiers go together, beyond what their popularity alone explains
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModifierPair {
+    pub first: String,
+    pub second: String,
+    // Listings with both modifiers
+    pub count: u32,
+    // Share of all listings with both modifiers
+    pub support: f64,
+    // How much more often the pair appears than if the modifiers were independent;
+    // 1 means no association
+    pub lift: f64,
+    // log2 of the lift, so pairs that avoid each other come out negative
+    pub pmi: f64,
+}
+
 #[derive(Debug)]
 pub struct StatAnalyzer {
     modifier_attribute_occurrences: HashMap<String, HashMap<String, u32>>,
     modifier_thresholds: HashMap<String, HashMap<String, Vec<u32>>>,
     modifier_correlations: HashMap<String, HashMap<String, u32>>,
+    // Listings each modifier appears on, the marginals for pair lift
+    modifier_counts: HashMap<String, u32>,
+    // (attribute requirement, rolled value) per modifier and attribute
+    modifier_value_points: HashMap<String, HashMap<String, Vec<(u32, f64)>>>,
     total_items: u32,
+    // Every listing seen by rarity, including uniques left out of the statistics
+    items_by_rarity: HashMap<ItemRarity, u32>,
     requirement_distributions: HashMap<StatRequirementType, Vec<(u32, u32)>>,
+    // Listing IDs already processed, so a listing seen again in a later run isn't counted twice
+    seen_items: HashSet<String>,
+    // Repeats of an already processed listing skipped by this analyzer
+    duplicate_items: u32,
+    // Which kinds of mods count towards occurrences and correlations
+    mod_kinds: Vec<ModKind>,
+    min_pair_support: u32,
+    // Uniques have fixed modifiers, so by default they'd only skew the correlations
+    include_uniques: bool,
+}
+
+// Accumulated StatAnalyzer state, saved between runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatAnalyzerSnapshot {
+    modifier_attribute_occurrences: HashMap<String, HashMap<String, u32>>,
+    modifier_thresholds: HashMap<String, HashMap<String, Vec<u32>>>,
+    modifier_correlations: HashMap<String, HashMap<String, u32>>,
+    modifier_counts: HashMap<String, u32>,
+    #[serde(default)]
+    modifier_value_points: HashMap<String, HashMap<String, Vec<(u32, f64)>>>,
+    total_items: u32,
+    #[serde(default)]
+    items_by_rarity: HashMap<ItemRarity, u32>,
+    // Pairs rather than a map since JSON object keys have to be strings
+    requirement_distributions: Vec<(StatRequirementType, Vec<(u32, u32)>)>,
+    seen_items: HashSet<String>,
+}
+
+impl StatAnalyzerSnapshot {
+    pub fn total_items(&self) -> u32 {
+        self.total_items
+    }
 }
 
 impl ModInfoLike for ModBase {
@@ -47,7 +120,13 @@ impl ModInfoLike for ModBase {
     }
 
     fn get_value(&self) -> Option<f64> {
-        self.magnitudes.first().and_then(|m| m.min.parse().ok())
+        self.rolled_value()
+    }
+}
+
+impl Default for StatAnalyzer {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -57,182 +136,245 @@ impl StatAnalyzer {
             modifier_attribute_occurrences: HashMap::new(),
             modifier_thresholds: HashMap::new(),
             modifier_correlations: HashMap::new(),
+            modifier_counts: HashMap::new(),
+            modifier_value_points: HashMap::new(),
             total_items: 0,
+            items_by_rarity: HashMap::new(),
             requirement_distributions: HashMap::new(),
+            seen_items: HashSet::new(),
+            duplicate_items: 0,
+            mod_kinds: vec![ModKind::Explicit],
+            min_pair_support: DEFAULT_MIN_PAIR_SUPPORT,
+            include_uniques: false,
         }
     }
 
+    pub fn snapshot(&self) -> StatAnalyzerSnapshot {
+        StatAnalyzerSnapshot {
+            modifier_attribute_occurrences: self.modifier_attribute_occurrences.clone(),
+            modifier_thresholds: self.modifier_thresholds.clone(),
+            modifier_correlations: self.modifier_correlations.clone(),
+            modifier_counts: self.modifier_counts.clone(),
+            modifier_value_points: self.modifier_value_points.clone(),
+            total_items: self.total_items,
+            items_by_rarity: self.items_by_rarity.clone(),
+            requirement_distributions: self.requirement_distributions
+                .iter()
+                .map(|(req_type, values)| (req_type.clone(), values.clone()))
+                .collect(),
+            seen_items: self.seen_items.clone(),
+        }
+    }
+
+    // Add an earlier run's counts to this analyzer's. Restoring into a fresh analyzer
+    // continues exactly where the snapshot left off.
+    pub fn restore(&mut self, snapshot: StatAnalyzerSnapshot) {
+        for (modifier, occurrences) in snapshot.modifier_attribute_occurrences {
+            let counts = self.modifier_attribute_occurrences.entry(modifier).or_default();
+            for (attr, count) in occurrences {
+                *counts.entry(attr).or_default() += count;
+            }
+        }
+        for (modifier, thresholds) in snapshot.modifier_thresholds {
+            let values = self.modifier_thresholds.entry(modifier).or_default();
+            for (attr, thresholds) in thresholds {
+                values.entry(attr).or_default().extend(thresholds);
+            }
+        }
+        for (modifier, correlations) in snapshot.modifier_correlations {
+            let counts = self.modifier_correlations.entry(modifier).or_default();
+            for (other, count) in correlations {
+                *counts.entry(other).or_default() += count;
+            }
+        }
+        for (modifier, count) in snapshot.modifier_counts {
+            *self.modifier_counts.entry(modifier).or_default() += count;
+        }
+        for (modifier, by_attribute) in snapshot.modifier_value_points {
+            let points = self.modifier_value_points.entry(modifier).or_default();
+            for (attr, values) in by_attribute {
+                points.entry(attr).or_default().extend(values);
+            }
+        }
+        for (req_type, values) in snapshot.requirement_distributions {
+            self.requirement_distributions.entry(req_type).or_default().extend(values);
+        }
+        self.total_items += snapshot.total_items;
+        for (rarity, count) in snapshot.items_by_rarity {
+            *self.items_by_rarity.entry(rarity).or_default() += count;
+        }
+        self.seen_items.extend(snapshot.seen_items);
+    }
+
+    // Non-explicit mods are tracked under a kind-prefixed name, see ModKind::stat_key
+    pub fn with_mod_kinds(mut self, kinds: Vec<ModKind>) -> Self {
+        self.mod_kinds = kinds;
+        self
+    }
+
+    pub fn with_min_pair_support(mut self, min_pair_support: u32) -> Self {
+        self.min_pair_support = min_pair_support;
+        self
+    }
+
+    pub fn with_include_uniques(mut self, include_uniques: bool) -> Self {
+        self.include_uniques = include_uniques;
+        self
+    }
+
+    // Counts the listing's rarity and says whether it belongs in the statistics
+    fn admit(&mut self, rarity: ItemRarity) -> bool {
+        *self.items_by_rarity.entry(rarity).or_default() += 1;
+        rarity != ItemRarity::Unique || self.include_uniques
+    }
+
     pub fn process_item(&mut self, item: &ItemResponse) {
+        if !self.seen_items.insert(item.id.clone()) {
+            self.duplicate_items += 1;
+            return;
+        }
+        if !self.admit(item.item.item_rarity()) {
+            return;
+        }
         self.total_items += 1;
 
-        self.process_requirements(item);
-
         // Get stat requirements from the ItemResponse
         let stat_requirements = item.get_stat_requirements();
+        self.process_requirements(&stat_requirements);
         let item_attributes: HashSet<_> = stat_requirements.keys().collect();
 
-        for mod_info in &item.item.extended.mods.explicit {
+        let mods: Vec<(String, Option<f64>)> = item.item.extended.mods
+            .iter_kinds(&self.mod_kinds)
+            .map(|(kind, mod_info)| (kind.stat_key(mod_info.get_name()), mod_info.get_value()))
+            .collect();
+
+        for (name, value) in &mods {
             self.update_modifier_stats(
-                mod_info.deref(),
+                name,
+                *value,
                 &item_attributes,
                 &stat_requirements
             );
         }
 
-        self.update_modifier_correlations(
-            &item.item.extended.mods.explicit
-                .iter()
-                .map(|m| m.deref())
-                .collect::<Vec<_>>()
-        );
+        let mod_names: Vec<String> = mods.into_iter().map(|(name, _)| name).collect();
+
+        self.update_modifier_correlations(&mod_names);
     }
 
     fn update_modifier_stats(
         &mut self,
-        mod_info: &ModBase,  
+        mod_name: &str,
+        value: Option<f64>,
         item_attributes: &HashSet<&String>,
         stat_requirements: &HashMap<String, u32>
     ) {
         let mod_occurrences = self.modifier_attribute_occurrences
-            .entry(mod_info.get_name().to_string())
+            .entry(mod_name.to_string())
             .or_default();
         
         let mod_thresholds = self.modifier_thresholds
-            .entry(mod_info.get_name().to_string())
+            .entry(mod_name.to_string())
             .or_default();
     
         for attr in item_attributes {
             *mod_occurrences.entry((*attr).clone()).or_default() += 1;
             
-            if let Some(&value) = stat_requirements.get(*attr) {
+            if let Some(&threshold) = stat_requirements.get(*attr) {
                 mod_thresholds
                     .entry((*attr).clone())
                     .or_default()
-                    .push(value);
+                    .push(threshold);
+
+                if let Some(value) = value {
+                    self.modifier_value_points
+                        .entry(mod_name.to_string())
+                        .or_default()
+                        .entry((*attr).clone())
+                        .or_default()
+                        .push((threshold, value));
+                }
             }
         }
     }
     
-    fn update_modifier_correlations(&mut self, mods: &[&ModBase]) {
+    fn update_modifier_correlations(&mut self, mods: &[String]) {
+        let distinct: HashSet<&String> = mods.iter().collect();
+        for modifier in distinct {
+            *self.modifier_counts.entry(modifier.clone()).or_default() += 1;
+        }
+
         for (i, mod1) in mods.iter().enumerate() {
             for mod2 in mods.iter().skip(i + 1) {
                 let correlations = self.modifier_correlations
-                    .entry(mod1.get_name().to_string())
+                    .entry(mod1.clone())
                     .or_default();
                 
-                *correlations.entry(mod2.get_name().to_string()).or_default() += 1;
+                *correlations.entry(mod2.clone()).or_default() += 1;
     
                 let reverse_correlations = self.modifier_correlations
-                    .entry(mod2.get_name().to_string())
+                    .entry(mod2.clone())
                     .or_default();
                 
-                *reverse_correlations.entry(mod1.get_name().to_string()).or_default() += 1;
+                *reverse_correlations.entry(mod1.clone()).or_default() += 1;
             }
         }
     }
 
     pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        // Items that aren't listings have no ID to tell repeats apart by
+        if !item.id.is_empty() && !self.seen_items.insert(item.id.clone()) {
+            self.duplicate_items += 1;
+            return;
+        }
+        if !self.admit(item.rarity) {
+            return;
+        }
         self.total_items += 1;
 
-        // Process requirements using cleaned data
-        self.process_cleaned_requirements(item);
-
         // Get stat requirements from cleaned item
         let stat_requirements = item.get_stat_requirements();
+        self.process_requirements(&stat_requirements);
         let item_attributes: HashSet<_> = stat_requirements.keys().collect();
 
-        for mod_info in &item.mod_info.explicit {
+        let mods: Vec<(String, Option<f64>)> = self.mod_kinds
+            .iter()
+            .flat_map(|&kind| {
+                item.mod_info.of_kind(kind).iter().map(move |m| (kind.stat_key(m.get_name()), m.get_value()))
+            })
+            .collect();
+
+        for (name, value) in &mods {
             self.update_modifier_stats(
-                mod_info.deref(),
+                name,
+                *value,
                 &item_attributes,
                 &stat_requirements
             );
         }
 
-        let mod_refs: Vec<&ModBase> = item.mod_info.explicit
-        .iter()
-        .map(|m| m.deref())
-        .collect();
-    self.update_modifier_correlations(&mod_refs);
-    }
+        let mod_names: Vec<String> = mods.into_iter().map(|(name, _)| name).collect();
 
-    fn process_requirements(&mut self, item: &ItemResponse) {
-        let mut item_reqs = Vec::new();
-        
-        // Collect all attribute requirements
-        for req in &item.item.requirements {
-            match req.name.as_str() {
-                "[Dexterity|Dex]" | "[Strength|Str]" | "[Intelligence|Int]" => {
-                    if let Some(value) = req.values.first() {
-                        if let Ok(val) = value.0.parse::<u32>() {
-                            item_reqs.push((req.name.clone(), val));
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-        
-        // Sort requirements for consistent ordering
-        item_reqs.sort_by(|a, b| a.0.cmp(&b.0));
-
-        // Create requirement type and store values
-        match item_reqs.len() {
-            1 => {
-                let req_type = StatRequirementType::Single(item_reqs[0].0.clone());
-                self.requirement_distributions.entry(req_type)
-                    .or_insert_with(Vec::new)
-                    .push((item_reqs[0].1, 0));
-            }
-            2 => {
-                let req_type = StatRequirementType::Dual(
-                    item_reqs[0].0.clone(),
-                    item_reqs[1].0.clone()
-                );
-                self.requirement_distributions.entry(req_type)
-                    .or_insert_with(Vec::new)
-                    .push((item_reqs[0].1, item_reqs[1].1));
-            }
-            _ => {}
-        }
+        self.update_modifier_correlations(&mod_names);
     }
 
-    fn process_cleaned_requirements(&mut self, item: &CleanedItem) {
-        let mut item_reqs = Vec::new();
-        
-        // Collect all attribute requirements from cleaned item
-        for req in &item.requirements {
-            match req.name.as_str() {
-                "[Dexterity|Dex]" | "[Strength|Str]" | "[Intelligence|Int]" => {
-                    if let Some((value, _)) = req.values.first() {
-                        if let Ok(val) = value.parse::<u32>() {
-                            item_reqs.push((req.name.clone(), val));
-                        }
-                    }
-                }
-                _ => {}
-            }
-        }
-        
-        // Sort requirements for consistent ordering (same as original)
-        item_reqs.sort_by(|a, b| a.0.cmp(&b.0));
+    // Count the item's attribute requirements by whether it needs one attribute or two
+    fn process_requirements(&mut self, stat_requirements: &HashMap<String, u32>) {
+        let mut item_reqs: Vec<(&String, u32)> = stat_requirements.iter().map(|(name, &value)| (name, value)).collect();
+        // Sort requirements for consistent ordering
+        item_reqs.sort();
 
-        // Create requirement type and store values (same logic as original)
-        match item_reqs.len() {
-            1 => {
-                let req_type = StatRequirementType::Single(item_reqs[0].0.clone());
-                self.requirement_distributions.entry(req_type)
-                    .or_insert_with(Vec::new)
-                    .push((item_reqs[0].1, 0));
+        match item_reqs.as_slice() {
+            [(name, value)] => {
+                self.requirement_distributions.entry(StatRequirementType::Single((*name).clone()))
+                    .or_default()
+                    .push((*value, 0));
             }
-            2 => {
-                let req_type = StatRequirementType::Dual(
-                    item_reqs[0].0.clone(),
-                    item_reqs[1].0.clone()
-                );
+            [(first, first_value), (second, second_value)] => {
+                let req_type = StatRequirementType::Dual((*first).clone(), (*second).clone());
                 self.requirement_distributions.entry(req_type)
-                    .or_insert_with(Vec::new)
-                    .push((item_reqs[0].1, item_reqs[1].1));
+                    .or_default()
+                    .push((*first_value, *second_value));
             }
             _ => {}
         }
@@ -305,6 +447,100 @@ impl StatAnalyzer {
         common_pairs
     }
 
+    // Modifier pairs seen together on at least `min_support` listings, most associated first.
+    // Unlike the raw co-occurrence share, lift and PMI don't favour pairs of popular modifiers,
+    // so a rare pairing that almost always appears together still ranks highly.
+    pub fn get_modifier_pairs(&self, min_support: u32) -> Vec<ModifierPair> {
+        if self.total_items == 0 {
+            return Vec::new();
+        }
+        let total = self.total_items as f64;
+
+        let mut pairs: Vec<ModifierPair> = self.modifier_correlations
+            .iter()
+            .flat_map(|(first, correlations)| {
+                correlations
+                    .iter()
+                    .filter(move |(second, &count)| first < *second && count >= min_support.max(1))
+                    .map(move |(second, &count)| (first, second, count))
+            })
+            .filter_map(|(first, second, count)| {
+                let first_count = *self.modifier_counts.get(first)?;
+                let second_count = *self.modifier_counts.get(second)?;
+                let lift = count as f64 * total / (first_count as f64 * second_count as f64);
+                Some(ModifierPair {
+                    first: first.clone(),
+                    second: second.clone(),
+                    count,
+                    support: count as f64 / total,
+                    lift,
+                    pmi: lift.log2(),
+                })
+            })
+            .collect();
+
+        pairs.sort_by(|a, b| {
+            b.pmi.total_cmp(&a.pmi)
+                .then_with(|| b.count.cmp(&a.count))
+                .then_with(|| (&a.first, &a.second).cmp(&(&b.first, &b.second)))
+        });
+        pairs
+    }
+
+    // The Str/Dex/Int requirement ranges listings with this modifier were seen at, or None
+    // for a modifier never seen on a listing with attribute requirements
+    pub fn predict_requirements(&self, mod_name: &str) -> Option<RequirementPrediction> {
+        let thresholds = self.modifier_thresholds.get(mod_name)?;
+        let listings = self.modifier_counts.get(mod_name).copied().unwrap_or(0);
+        RequirementPrediction::from_thresholds(mod_name, thresholds, listings)
+    }
+
+    // Per modifier and attribute, a line through rolled value against the attribute
+    // requirement; a positive slope means the modifier rolls higher on bases needing more
+    // of the attribute. Combinations with too few points or a single requirement are skipped.
+    pub fn value_scaling(&self, min_observations: usize) -> Vec<ValueScaling> {
+        let mut scaling: Vec<ValueScaling> = self.modifier_value_points
+            .iter()
+            .flat_map(|(modifier, by_attribute)| {
+                by_attribute.iter().map(move |(attribute, points)| (modifier, attribute, points))
+            })
+            .filter(|(_, _, points)| points.len() >= min_observations.max(2))
+            .filter_map(|(modifier, attribute, points)| {
+                let points: Vec<(f64, f64)> = points.iter().map(|&(t, v)| (t as f64, v)).collect();
+                Some(ValueScaling {
+                    modifier: modifier.clone(),
+                    attribute: attribute.clone(),
+                    fit: LinearFit::fit(&points)?,
+                })
+            })
+            .collect();
+        scaling.sort_by(|a, b| (&a.modifier, &a.attribute).cmp(&(&b.modifier, &b.attribute)));
+        scaling
+    }
+
+    // Every requirement value seen per attribute, whether alone or alongside a second one
+    pub fn requirement_values(&self) -> Vec<(String, Vec<u32>)> {
+        let mut by_attribute: HashMap<&str, Vec<u32>> = HashMap::new();
+        for (req_type, values) in &self.requirement_distributions {
+            match req_type {
+                StatRequirementType::Single(stat) => {
+                    by_attribute.entry(stat).or_default().extend(values.iter().map(|(v, _)| *v));
+                }
+                StatRequirementType::Dual(stat1, stat2) => {
+                    by_attribute.entry(stat1).or_default().extend(values.iter().map(|(v1, _)| *v1));
+                    by_attribute.entry(stat2).or_default().extend(values.iter().map(|(_, v2)| *v2));
+                }
+            }
+        }
+
+        let mut values: Vec<(String, Vec<u32>)> = by_attribute
+            .into_iter()
+            .map(|(stat, values)| (stat.to_string(), values))
+            .collect();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        values
+    }
+
     pub fn get_requirement_statistics(&self) -> serde_json::Value {
         let mut stats = serde_json::json!({
             "single_stat_counts": {},
@@ -340,18 +576,29 @@ impl StatAnalyzer {
     pub fn generate_attribute_report(&self) -> serde_json::Value {
         let correlations = self.analyze_attribute_correlations();
         let common_pairs = self.get_common_modifier_pairs(0.1); // 10% correlation threshold
+        let mut modifiers: Vec<&String> = self.modifier_thresholds.keys().collect();
+        modifiers.sort();
+        let predictions: Vec<RequirementPrediction> = modifiers
+            .into_iter()
+            .filter_map(|modifier| self.predict_requirements(modifier))
+            .collect();
 
         serde_json::json!({
             "total_items_analyzed": self.total_items,
+            "duplicate_items_skipped": self.duplicate_items,
+            "items_by_rarity": self.items_by_rarity,
             "attribute_correlations": correlations,
             "common_modifier_pairs": common_pairs,
+            "modifier_pairs": self.get_modifier_pairs(self.min_pair_support),
             "requirement_statistics": self.get_requirement_statistics(),
+            "requirement_predictions": predictions,
+            "value_scaling": self.value_scaling(MIN_SCALING_OBSERVATIONS),
             "analysis_summary": {
                 "strongest_attribute": correlations.iter()
                     .max_by_key(|(_, c)| c.occurrence_count)
                     .map(|(attr, _)| attr),
-                "most_common_threshold": correlations.iter()
-                    .map(|(_, c)| c.average_threshold.round() as u32)
+                "most_common_threshold": correlations.values()
+                    .map(|c| c.average_threshold.round() as u32)
                     .max()
             }
         })
@@ -361,76 +608,42 @@ impl StatAnalyzer {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::item_type::{ItemType, ItemCategory, ItemRarity};
+    use crate::models::{
+        Currency,
+        ItemData,
+        ExtendedData,
+        ModData,
+        Magnitude,
+        HashData,
+        Requirement,
+        Property,
+        ListingData,
+        Price,
+        Account,
+    };
+    use crate::models::cleaned_item::{ItemProperty, ItemRequirement, ModInfo, ExplicitMod};
 
     #[test]
     fn test_stat_analyzer_basic_functionality() {
         let mut analyzer = StatAnalyzer::new();
-        
-        // Create a test item with some modifiers
-        let mut item = Item::new(
-            "test_item".to_string(),
-            ItemType::new(
-                ItemCategory::Armour,
-                "Test Base".to_string(),
-                ItemRarity::Rare
-            )
-        );
-
-        // Add stat requirements
-        item.stat_requirements.add_requirement(CoreAttribute::Strength, 100);
-        item.attribute_values.insert(CoreAttribute::Strength, 100);
-
-        // Add some modifiers
-        let modifier = ItemModifier {
-            name: "Test Modifier".to_string(),
-            tier: Some(1),
-            values: vec![10.0],
-            is_crafted: false,
-            stat_requirements: None,
-            attribute_scaling: None,
-        };
 
-        item.modifiers.push(modifier);
-
-        // Process the item
-        analyzer.process_item(&item);
+        // Process an item with a Strength requirement and a single modifier, matched by
+        // two overlapping queries
+        analyzer.process_item(&create_test_item_response());
+        analyzer.process_item(&create_test_item_response());
 
         // Verify analysis
         let report = analyzer.generate_attribute_report();
         assert_eq!(report["total_items_analyzed"], 1);
+        assert_eq!(report["duplicate_items_skipped"], 1);
     }
 
-        #[test]
-    fn test_stat_analyzer_basic_functionality() {
-        let mut analyzer = StatAnalyzer::new();
-        
-        let mut item = Item::new(
-            "test_item".to_string(),
-            ItemType::new(
-                ItemCategory::Armour,
-                "Test Base".to_string(),
-                ItemRarity::Rare
-            )
-        );
-
-        item.stat_requirements.add_requirement(CoreAttribute::Strength, 100);
-        item.attribute_values.insert(CoreAttribute::Strength, 100);
-
-        let modifier = ItemModifier {
-            name: "Test Modifier".to_string(),
-            tier: Some(1),
-            values: vec![10.0],
-            is_crafted: false,
-            stat_requirements: None,
-            attribute_scaling: None,
-        };
-
-        item.modifiers.push(modifier);
-        analyzer.process_item(&item);
-
-        let report = analyzer.generate_attribute_report();
-        assert_eq!(report["total_items_analyzed"], 1);
+    fn test_magnitude() -> Magnitude {
+        Magnitude {
+            hash: "explicit.stat_4080418644".to_string(),
+            max: "20".to_string(),
+            min: "17".to_string(),
+        }
     }
 
     // Helper function to create a representative ItemResponse
@@ -438,6 +651,7 @@ mod tests {
         ItemResponse {
             id: "test_id".to_string(),
             item: ItemData {
+                name: "Fate Suit".to_string(),
                 base_type: "Advanced Maraketh Cuirass".to_string(),
                 type_line: "Advanced Maraketh Cuirass".to_string(),
                 explicit_mods: vec![
@@ -445,7 +659,18 @@ mod tests {
                     "+109 to maximum Life".to_string(),
                     "+17 to Strength".to_string(),
                 ],
+                implicit_mods: vec![],
+                enchant_mods: vec![],
+                rune_mods: vec![],
+                crafted_mods: vec![],
                 ilvl: 75,
+                league: None,
+                frame_type: 2,
+                support: None,
+                corrupted: false,
+                duplicated: false,
+                sockets: vec![],
+                socketed_items: vec![],
                 properties: vec![
                     Property {
                         name: "Body Armour".to_string(),
@@ -468,52 +693,67 @@ mod tests {
                 extended: ExtendedData {
                     mods: ModData {
                         explicit: vec![
-                            ExplicitMod {
-                                level: 33,
-                                magnitudes: vec![Magnitude {
-                                    hash: "explicit.stat_4080418644".to_string(),
-                                    max: "20".to_string(),
-                                    min: "17".to_string(),
-                                }],
-                                name: "of the Lion".to_string(),
-                                tier: "R4".to_string(),
-                            }
-                        ]
+                            serde_json::from_value(json!({
+                                "name": "of the Lion",
+                                "tier": "R4",
+                                "magnitudes": [test_magnitude()],
+                            })).unwrap()
+                        ],
+                        ..Default::default()
                     },
                     hashes: HashData {
                         explicit: vec![
-                            ("explicit.stat_4080418644".to_string(), vec![vec![2]])
+                            ("explicit.stat_4080418644".to_string(), vec![2])
                         ],
+                        ..Default::default()
                     }
                 },
-                name: "Fate Suit".to_string(),
                 rarity: "Rare".to_string(),
+                extra: Default::default(),
             },
             listing: ListingData {
                 price: Price {
                     amount: 1.0,
-                    currency: "regal".to_string(),
-                    type_line: "~price".to_string(),
+                    currency: Currency::Regal,
                 },
                 account: Account {
                     name: "TestAccount".to_string(),
                     realm: "poe2".to_string(),
-                }
-            }
+                },
+                whisper: None,
+                stash: None,
+                indexed: None,
+                extra: Default::default(),
+            },
+            sampling_weight: None,
+            extra: Default::default(),
         }
     }
 
     // Helper function to create a cleaned item matching the ItemResponse
     fn create_test_cleaned_item() -> CleanedItem {
+        let explicit: ExplicitMod = serde_json::from_value(json!({
+            "name": "of the Lion",
+            "tier": "R4",
+            "magnitudes": [test_magnitude()],
+            "level": 33,
+        })).unwrap();
+
         CleanedItem {
+            id: "test_id".to_string(),
+            price: None,
             base_type: "Advanced Maraketh Cuirass".to_string(),
             name: "Fate Suit".to_string(),
+            rarity: ItemRarity::Rare,
             explicit_mods: vec![
                 "+54% increased Armour".to_string(),
                 "+109 to maximum Life".to_string(),
                 "+17 to Strength".to_string(),
             ],
             item_level: 75,
+            quality: None,
+            corrupted: false,
+            mirrored: false,
             properties: vec![
                 ItemProperty {
                     name: "Body Armour".to_string(),
@@ -534,24 +774,16 @@ mod tests {
                 }
             ],
             mod_info: ModInfo {
-                explicit: vec![
-                    ExplicitMod {
-                        level: 33,
-                        magnitudes: vec![
-                            Magnitude {
-                                hash: "explicit.stat_4080418644".to_string(),
-                                max: "20".to_string(),
-                                min: "17".to_string(),
-                            }
-                        ],
-                        name: "of the Lion".to_string(),
-                        tier: "R4".to_string(),
-                    }
-                ],
+                explicit: vec![explicit],
+                ..Default::default()
             },
             mod_hashes: HashMap::from_iter(vec![
                 ("explicit.stat_4080418644".to_string(), vec![vec![2]])
             ]),
+            gem: None,
+            jewel: None,
+            waystone: None,
+            sockets: None,
         }
     }
 
@@ -607,4 +839,110 @@ mod tests {
             report_cleaned["attribute_correlations"]
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_non_explicit_mods_are_opt_in() {
+        // Listings without the extra arrays still deserialize
+        let mods: ModData = serde_json::from_value(json!({ "explicit": [] })).unwrap();
+        assert!(mods.implicit.is_empty() && mods.rune.is_empty());
+
+        let mut item = create_test_item_response();
+        item.item.extended.mods.implicit.push(serde_json::from_value(json!({
+            "name": "",
+            "tier": "",
+            "magnitudes": [test_magnitude()],
+        })).unwrap());
+
+        let mut explicit_only = StatAnalyzer::new();
+        explicit_only.process_item(&item);
+        assert_eq!(explicit_only.modifier_attribute_occurrences.len(), 1);
+
+        let mut all_kinds = StatAnalyzer::new().with_mod_kinds(ModKind::ALL.to_vec());
+        all_kinds.process_item(&item);
+        assert!(all_kinds.modifier_attribute_occurrences.contains_key("implicit: "));
+        assert_eq!(all_kinds.modifier_correlations["of the Lion"]["implicit: "], 1);
+    }
+
+    #[test]
+    fn test_value_scaling_with_strength() {
+        let mut analyzer = StatAnalyzer::new();
+        for (i, (requirement, roll)) in [(50, "10"), (100, "15"), (150, "20")].into_iter().enumerate() {
+            let mut item = create_test_item_response();
+            item.id = format!("item{}", i);
+            item.item.requirements[0].values[0].0 = requirement.to_string();
+            item.item.extended.mods.explicit[0] = serde_json::from_value(json!({
+                "name": "of the Lion",
+                "tier": "R4",
+                "magnitudes": [{ "hash": "explicit.stat_4080418644", "min": roll, "max": roll }],
+            })).unwrap();
+            analyzer.process_item(&item);
+        }
+
+        let scaling = analyzer.value_scaling(MIN_SCALING_OBSERVATIONS);
+        assert_eq!(scaling.len(), 1);
+        assert_eq!((scaling[0].modifier.as_str(), scaling[0].attribute.as_str()), ("of the Lion", "[Strength|Str]"));
+        assert!((scaling[0].fit.slope - 0.1).abs() < 1e-9);
+        assert!((scaling[0].fit.intercept - 5.0).abs() < 1e-9);
+        assert!(analyzer.value_scaling(4).is_empty());
+    }
+
+    #[test]
+    fn test_predict_requirements() {
+        let mut analyzer = StatAnalyzer::new();
+        analyzer.process_item(&create_test_item_response());
+
+        let prediction = analyzer.predict_requirements("of the Lion").unwrap();
+        assert_eq!(prediction.listings, 1);
+        let strength = prediction.range(&crate::models::CoreAttribute::Strength).unwrap();
+        assert_eq!((strength.min, strength.max), (105, 105));
+        assert!(analyzer.predict_requirements("of the Fox").is_none());
+    }
+
+    #[test]
+    fn test_modifier_pairs_discount_popular_mods() {
+        let mut analyzer = StatAnalyzer::new();
+        let mods = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        // "life" is on every listing, so it pairs with everything without meaning anything
+        for _ in 0..8 {
+            analyzer.total_items += 1;
+            analyzer.update_modifier_correlations(&mods(&["life", "fire res"]));
+        }
+        for _ in 0..2 {
+            analyzer.total_items += 1;
+            analyzer.update_modifier_correlations(&mods(&["life", "spirit", "minion damage"]));
+        }
+
+        let pairs = analyzer.get_modifier_pairs(2);
+        assert_eq!(pairs.len(), 4);
+        assert_eq!((pairs[0].first.as_str(), pairs[0].second.as_str()), ("minion damage", "spirit"));
+        assert!((pairs[0].lift - 5.0).abs() < 1e-9);
+        assert!((pairs[0].support - 0.2).abs() < 1e-9);
+
+        let life_fire = pairs.iter().find(|p| p.second == "life" && p.first == "fire res").unwrap();
+        assert!((life_fire.support - 0.8).abs() < 1e-9);
+        assert!(life_fire.pmi.abs() < 1e-9);
+
+        // The support cutoff drops the rare pairing
+        assert_eq!(analyzer.get_modifier_pairs(3).len(), 1);
+    }
+
+    #[test]
+    fn test_snapshot_continues_counts() {
+        let item = create_test_item_response();
+        let mut analyzer = StatAnalyzer::new();
+        analyzer.process_item(&item);
+
+        let json = serde_json::to_string(&analyzer.snapshot()).unwrap();
+        let snapshot: StatAnalyzerSnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot.total_items(), 1);
+
+        let mut restored = StatAnalyzer::new();
+        restored.restore(snapshot);
+        // The same listing turning up in the next run is not counted again
+        restored.process_item(&item);
+        let mut report = restored.generate_attribute_report();
+        assert_eq!(report["duplicate_items_skipped"], 1);
+        report["duplicate_items_skipped"] = 0.into();
+        assert_eq!(report, analyzer.generate_attribute_report());
+    }
+}
@@ -1,115 +1,471 @@
-use crate::fetcher::{
-    TradeApiClient, SearchRequest, TradeQuery, StatusFilter, StatFilter,
-    StatFilterValue, StatValue, QueryFilters, TypeFilters, CategoryFilter, 
-    CategoryOption,
-};
+use crate::fetcher::{TradeApiClient, SearchRequest, QueryBuilder, FETCH_ENDPOINT, SEARCH_ENDPOINT};
 use crate::models::{
     CoreAttribute,
-    StatRequirements,
-    Item,
-    ItemModifier,
+    Currency,
     ItemResponse,
-    ModInfo,
 };
-use crate::errors::Result;
+use crate::errors::{Result, ScraperError};
+use crate::data::stat_catalog_loader::StatCatalogLoader;
+use super::collected_data::{load_collected_items, save_collected_items, CollectedDataHeader, CollectedDataset};
+use super::collection_checkpoint::{CollectionCheckpoint, RangeKey};
+use super::collection_plan::{CollectionPlan, SweepLimits};
+use super::collection_summary::{CollectionSummary, RangeSummary};
+use super::dry_run::{query_problems, rules_or_assumed, DryRunQuery, DryRunReport, ASSUMED_FETCH_RULES, ASSUMED_SEARCH_RULES, SEARCH_RESULT_LIMIT};
+use super::search_cache::SearchResultCache;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{info, warn};
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
 use tokio::time::{sleep, Duration};
 
+// Result IDs fetched between checkpoint writes
+const CHECKPOINT_INTERVAL: usize = 20;
+
+// Listings a fetched one stands for when `fetched` of the `total` a query matched were
+// fetched; the total counts listings the API would not return, so it is at least one
+fn sampling_weight(total: u32, fetched: usize) -> Option<f64> {
+    (fetched > 0).then(|| (total as f64 / fetched as f64).max(1.0))
+}
+
 pub struct StatCollector {
     client: TradeApiClient,
-    // Store thresholds as ranges to get a better distribution of items
-    threshold_ranges: Vec<(u32, u32)>,
+    // The categories, attribute ranges, price bands and orders to search
+    plan: CollectionPlan,
     rate_limit_delay: Duration,
+    min_price: Option<f64>,
+    max_price: Option<f64>,
+    price_currency: Option<Currency>,
+    // Bounds on rune sockets, when the query filters on them
+    rune_sockets: Option<(Option<u32>, Option<u32>)>,
+    // Explicit "+# to <Attribute>" stat IDs used to select items per attribute
+    attribute_stat_ids: HashMap<CoreAttribute, String>,
+    checkpoint_path: Option<PathBuf>,
+    resume: bool,
+    search_cache_path: Option<PathBuf>,
+    // Result IDs of the last run that weren't fetched because an earlier search returned them
+    unchanged_ids: Vec<String>,
+    show_progress: bool,
+    cancellation: CancellationToken,
+    summary: CollectionSummary,
 }
 
 impl StatCollector {
     pub fn new(client: TradeApiClient) -> Self {
         Self {
             client,
-            // Define ranges that will give us a good spread of stat requirements
-            threshold_ranges: vec![
-                (0, 50),    // Low requirement items
-                (51, 100),  // Medium requirement items
-                (101, 150), // High requirement items
-                (151, 200), // Very high requirement items
-            ],
+            plan: CollectionPlan::default(),
             rate_limit_delay: Duration::from_millis(100),
+            min_price: None,
+            max_price: None,
+            price_currency: None,
+            rune_sockets: None,
+            attribute_stat_ids: HashMap::from([
+                (CoreAttribute::Strength, "explicit.stat_3299347043".to_string()),
+                (CoreAttribute::Dexterity, "explicit.stat_1284417561".to_string()),
+                (CoreAttribute::Intelligence, "explicit.stat_4220027924".to_string()),
+            ]),
+            checkpoint_path: None,
+            resume: false,
+            search_cache_path: None,
+            unchanged_ids: Vec::new(),
+            show_progress: false,
+            cancellation: CancellationToken::new(),
+            summary: CollectionSummary::default(),
         }
     }
 
-    pub async fn collect_stat_data(&mut self) -> Result<Vec<ItemResponse>> {
-        let mut all_items = Vec::new();
-        
-        // Collect items for each attribute type
-        for attr in [CoreAttribute::Strength, CoreAttribute::Dexterity, CoreAttribute::Intelligence] {
-            for (min, max) in &self.threshold_ranges {
-                // Build query for this attribute range
-                let query = self.build_attribute_query(attr.clone(), *min, *max);
-                
-                // Fetch items and respect rate limiting
-                sleep(self.rate_limit_delay).await;
-                let items = self.client.fetch_items_with_stats(query).await?;
-                
-                println!("Collected {} items for {:?} ({}-{})", 
-                    items.len(), attr, min, max);
-                
-                all_items.extend(items);
+    // Resolve the attribute stat IDs from the trade stats catalogue instead of the built-in ones
+    pub fn with_stat_catalog(mut self, catalog: &StatCatalogLoader) -> Self {
+        for (attr, text) in [
+            (CoreAttribute::Strength, "+# to Strength"),
+            (CoreAttribute::Dexterity, "+# to Dexterity"),
+            (CoreAttribute::Intelligence, "+# to Intelligence"),
+        ] {
+            match catalog.find_stat_id(text, "explicit") {
+                Some(id) => {
+                    self.attribute_stat_ids.insert(attr, id.to_string());
+                }
+                None => warn!(text, "stat catalogue has no explicit stat, keeping built-in ID"),
             }
         }
-        
-        Ok(all_items)
+        self
     }
 
-    fn build_attribute_query(&self, attr: CoreAttribute, min: u32, max: u32) -> SearchRequest {
-        let stat_id = match attr {
-            CoreAttribute::Strength => "explicit.stat_3299347043",
-            CoreAttribute::Dexterity => "explicit.stat_1284417561",
-            CoreAttribute::Intelligence => "explicit.stat_4220027924",
-        };
-    
-        SearchRequest {
-            query: TradeQuery {
-                status: StatusFilter {
-                    option: "online".to_string(),
-                },
-                stats: vec![StatFilter {
-                    r#type: "and".to_string(),
-                    filters: vec![StatFilterValue {
-                        id: stat_id.to_string(),
-                        value: Some(StatValue {
-                            min: Some(min),
-                            max: Some(max),
-                        }),
-                        disabled: false,
-                    }],
-                    disabled: false,
-                }],
-                filters: QueryFilters {
-                    type_filters: TypeFilters {
-                        filters: CategoryFilter {
-                            category: CategoryOption {
-                                option: "armour".to_string(),
-                            },
-                        },
-                    },
-                },
-            },
-            sort: Some(serde_json::json!({
-                "price": "asc"
-            })),
+    // Have the trade API only return listings priced within these bounds
+    pub fn with_price_range(mut self, min: Option<f64>, max: Option<f64>, currency: Option<Currency>) -> Self {
+        self.min_price = min;
+        self.max_price = max;
+        self.price_currency = currency;
+        self
+    }
+
+    // Only collect listings with this many rune sockets
+    pub fn with_rune_sockets(mut self, min: Option<u32>, max: Option<u32>) -> Self {
+        self.rune_sockets = Some((min, max));
+        self
+    }
+
+    // Query these attribute requirement ranges instead of the plan's
+    pub fn with_threshold_ranges(mut self, ranges: Vec<(u32, u32)>) -> Self {
+        self.plan.attribute_ranges = ranges;
+        self
+    }
+
+    // Search each of SWEEP_CATEGORIES for its first pages of listings instead of the
+    // attribute ranges, for broad coverage of the market
+    pub fn with_sweep(self, limits: &SweepLimits) -> Self {
+        self.with_plan(CollectionPlan::sweep(limits))
+    }
+
+    // Search the plan's cells instead of the built-in attribute ranges
+    pub fn with_plan(mut self, plan: CollectionPlan) -> Self {
+        self.plan = plan;
+        self
+    }
+
+    // Write progress to a checkpoint file as the run goes; with `resume` an existing
+    // checkpoint is picked up instead of starting over
+    pub fn with_checkpoint(mut self, path: impl Into<PathBuf>, resume: bool) -> Self {
+        self.checkpoint_path = Some(path.into());
+        self.resume = resume;
+        self
+    }
+
+    // Remember each query's result IDs in a file and only fetch listings its previous search
+    // didn't return; the rest are left in unchanged_ids for the caller to mark as still seen
+    pub fn with_search_cache(mut self, path: impl Into<PathBuf>) -> Self {
+        self.search_cache_path = Some(path.into());
+        self
+    }
+
+    // Draw a progress bar on stderr for each attribute/range bucket
+    pub fn with_progress(mut self, show: bool) -> Self {
+        self.show_progress = show;
+        self
+    }
+
+    // Stop the run once `token` is cancelled: the page being fetched is finished and handed
+    // on, the checkpoint is kept for --resume and the summary is marked interrupted
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.client = self.client.with_cancellation(token.clone());
+        self.cancellation = token;
+        self
+    }
+
+    // Counts from the most recent collect_stat_data run
+    pub fn summary(&self) -> &CollectionSummary {
+        &self.summary
+    }
+
+    // Result IDs the most recent run skipped as unchanged since the previous search
+    pub fn unchanged_ids(&self) -> &[String] {
+        &self.unchanged_ids
+    }
+
+    fn range_progress(&self, range: &RangeKey, len: usize) -> ProgressBar {
+        if !self.show_progress {
+            return ProgressBar::hidden();
+        }
+
+        let bar = ProgressBar::new(len as u64).with_prefix(range.to_string());
+        bar.set_style(
+            ProgressStyle::with_template("{prefix:>20} [{bar:30}] {pos}/{len} {msg}")
+                .expect("progress template is valid")
+                .progress_chars("=> "),
+        );
+        bar
+    }
+
+    async fn open_checkpoint(&self) -> Result<CollectionCheckpoint> {
+        match &self.checkpoint_path {
+            Some(path) if self.resume => {
+                let checkpoint = CollectionCheckpoint::load_or_new(path.clone()).await?;
+                info!(
+                    path = %path.display(),
+                    completed_ranges = checkpoint.completed_ranges.len(),
+                    "resuming collection from checkpoint"
+                );
+                Ok(checkpoint)
+            }
+            Some(path) => Ok(CollectionCheckpoint::new(path.clone())),
+            None => Ok(CollectionCheckpoint::default()),
         }
     }
 
-    // Helper method to save collected data for later analysis
-    pub async fn save_collected_data(&self, items: &[ItemResponse], path: &str) -> Result<()> {
-        let json = serde_json::to_string_pretty(items)?;
-        tokio::fs::write(path, json).await?;
+    async fn save_checkpoint(&self, checkpoint: &CollectionCheckpoint) -> Result<()> {
+        if self.checkpoint_path.is_some() {
+            checkpoint.save().await?;
+        }
         Ok(())
     }
 
-    // Helper method to load previously collected data
-    pub async fn load_collected_data(path: &str) -> Result<Vec<ItemResponse>> {
-        let content = tokio::fs::read_to_string(path).await?;
-        let items = serde_json::from_str(&content)?;
+    // Run the collection and gather every item. Prefer collect_into for long runs, which
+    // hands items on as they arrive instead of holding them all.
+    pub async fn collect_stat_data(&mut self) -> Result<Vec<ItemResponse>> {
+        let (sender, mut receiver) = mpsc::channel(CHECKPOINT_INTERVAL);
+        let gather = async move {
+            let mut items = Vec::new();
+            while let Some(item) = receiver.recv().await {
+                items.push(item);
+            }
+            items
+        };
+
+        let (collected, items) = tokio::join!(self.collect_into(sender), gather);
+        collected?;
         Ok(items)
     }
-}
\ No newline at end of file
+
+    // Run the collection, sending each item to `items` as soon as its page is fetched. The
+    // channel's capacity bounds how far collection runs ahead of the consumer. On --resume
+    // only items fetched after the interruption are sent; earlier ones went out last run.
+    pub async fn collect_into(&mut self, items: mpsc::Sender<ItemResponse>) -> Result<()> {
+        let started = Instant::now();
+        let api_calls_before = self.client.api_calls();
+        self.summary = CollectionSummary {
+            league: Some(self.client.league().to_string()),
+            ..CollectionSummary::default()
+        };
+        let mut checkpoint = self.open_checkpoint().await?;
+        let mut search_cache = match &self.search_cache_path {
+            Some(path) => Some(SearchResultCache::load_or_new(path.clone()).await?),
+            None => None,
+        };
+        self.unchanged_ids.clear();
+
+        for range in self.plan.cells() {
+            if self.cancellation.is_cancelled() {
+                self.summary.interrupted = true;
+                break;
+            }
+            if checkpoint.is_completed(&range) {
+                self.summary.record_range(RangeSummary {
+                    range,
+                    items: 0,
+                    parse_failures: 0,
+                    duplicates: 0,
+                    unchanged: 0,
+                    sampling_weight: None,
+                    skipped: true,
+                });
+                continue;
+            }
+
+            // Either pick up the IDs left from an interrupted run or search afresh
+            let mut duplicates = 0;
+            let mut unchanged = 0;
+            let (ids, weight) = match checkpoint.pending_for(&range) {
+                Some(pending) => (pending.pending_ids.clone(), pending.sampling_weight),
+                None => {
+                    let query = self.build_range_query(&range);
+                    let query_key = SearchResultCache::query_key(self.client.league(), &query)?;
+                    sleep(self.rate_limit_delay).await;
+                    let response = match self.client.search_items(query).await {
+                        Ok(response) => response,
+                        Err(e) if e.is_cancelled() => {
+                            self.summary.interrupted = true;
+                            break;
+                        }
+                        Err(e) => return Err(e),
+                    };
+                    let mut ids = response.get_result_ids().to_vec();
+                    if let Some(max_results) = self.plan.max_results_for(&range.category) {
+                        ids.truncate(max_results);
+                    }
+                    // The weight is the cell's sampling rate, so it's taken before duplicates
+                    // (fetched for an earlier cell) are dropped
+                    let weight = sampling_weight(response.total(), ids.len());
+                    if let Some(cache) = &mut search_cache {
+                        let (new, seen) = cache.diff(&query_key, &ids);
+                        unchanged = seen.len();
+                        checkpoint.unchanged_ids.extend(seen);
+                        ids = new;
+                    }
+                    duplicates = checkpoint.claim_ids(&mut ids);
+                    checkpoint.start_range(range.clone(), ids.clone(), weight);
+                    self.save_checkpoint(&checkpoint).await?;
+                    // Only once the checkpoint holds the new IDs, so an interruption can't lose them
+                    if let Some(cache) = &search_cache {
+                        cache.save().await?;
+                    }
+                    (ids, weight)
+                }
+            };
+
+            let progress = self.range_progress(&range, ids.len());
+            let mut collected = 0;
+            let mut failures = 0;
+            for page in ids.chunks(CHECKPOINT_INTERVAL) {
+                if self.cancellation.is_cancelled() {
+                    self.summary.interrupted = true;
+                    break;
+                }
+                let report = match self.client.fetch_items_lenient(page).await {
+                    Ok(report) => report,
+                    Err(e) if e.is_cancelled() => {
+                        self.summary.interrupted = true;
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                };
+                collected += report.items.len();
+                failures += report.failures.len();
+                for mut item in report.items {
+                    // Rows and reports are told apart by league when several are collected
+                    item.item.league.get_or_insert_with(|| self.client.league().to_string());
+                    item.sampling_weight = weight;
+                    items.send(item).await.map_err(|_| {
+                        ScraperError::IoError("collected item receiver was dropped".to_string())
+                    })?;
+                }
+                checkpoint.record_fetched(page);
+                self.save_checkpoint(&checkpoint).await?;
+
+                progress.inc(page.len() as u64);
+                progress.set_message(format!("{} failed", failures));
+            }
+            progress.finish();
+
+            // An interrupted range stays in progress for --resume to finish
+            let finished = !self.summary.interrupted;
+            if finished {
+                checkpoint.complete_range(range.clone());
+                self.save_checkpoint(&checkpoint).await?;
+            }
+            info!(count = collected, failures, duplicates, unchanged, weight, finished, range = %range, "collected items for range");
+            self.summary.record_range(RangeSummary {
+                range,
+                items: collected,
+                parse_failures: failures,
+                duplicates,
+                unchanged,
+                sampling_weight: weight,
+                skipped: false,
+            });
+            if !finished {
+                break;
+            }
+        }
+
+        if self.summary.interrupted {
+            info!("collection interrupted; the checkpoint is kept for --resume");
+        } else {
+            if self.checkpoint_path.is_some() {
+                checkpoint.clear().await?;
+            }
+            self.unchanged_ids = std::mem::take(&mut checkpoint.unchanged_ids);
+        }
+
+        self.summary.api_calls = self.client.api_calls() - api_calls_before;
+        self.summary.set_duration(started.elapsed());
+        Ok(())
+    }
+
+    // Build every query of the plan and check it against `catalog` without fetching or
+    // writing anything. With `search` the searches alone are sent, to count what each
+    // matches; either way the API calls and time a run would take are estimated.
+    pub async fn dry_run(&mut self, catalog: Option<&StatCatalogLoader>, search: bool) -> Result<DryRunReport> {
+        let mut report = DryRunReport {
+            league: self.client.league().to_string(),
+            searched: search,
+            ..DryRunReport::default()
+        };
+        for range in self.plan.cells() {
+            let query = self.build_range_query(&range);
+            let problems = query_problems(&query, catalog);
+            let max_results = self.plan.max_results_for(&range.category).unwrap_or(SEARCH_RESULT_LIMIT).min(SEARCH_RESULT_LIMIT);
+            let (total, to_fetch) = if search && problems.is_empty() {
+                sleep(self.rate_limit_delay).await;
+                let response = self.client.search_items(query).await?;
+                (Some(response.total()), response.get_result_ids().len().min(max_results))
+            } else {
+                (None, max_results)
+            };
+            report.queries.push(DryRunQuery { range, problems, total, to_fetch });
+        }
+
+        let budget = self.client.rate_budget();
+        report.estimate(
+            &rules_or_assumed(budget.rules(SEARCH_ENDPOINT), ASSUMED_SEARCH_RULES),
+            &rules_or_assumed(budget.rules(FETCH_ENDPOINT), ASSUMED_FETCH_RULES),
+        );
+        Ok(report)
+    }
+
+    fn build_range_query(&self, range: &RangeKey) -> SearchRequest {
+        let (min_price, max_price) = match &range.price_band {
+            Some(band) => (band.min, band.max),
+            None => (self.min_price, self.max_price),
+        };
+        let (field, direction) = range.sort.unwrap_or_default().field_direction();
+        let mut builder = QueryBuilder::new()
+            .online()
+            .category(&range.category)
+            .identified(true)
+            .price_range(min_price, max_price)
+            .sort_by(field, direction);
+        if let Some(attribute) = &range.attribute {
+            let stat_id = self.attribute_stat_ids[&attribute.attribute].as_str();
+            builder = builder.stat(stat_id, attribute.min, attribute.max);
+        }
+
+        if let Some(currency) = &self.price_currency {
+            builder = builder.price_currency(currency);
+        }
+        if let Some((min, max)) = self.rune_sockets {
+            builder = builder.rune_sockets(min, max);
+        }
+
+        builder.build()
+    }
+
+    // Header for a data file of this collector's listings: its league and plan
+    pub fn dataset_header(&self) -> CollectedDataHeader {
+        CollectedDataHeader::new(Some(self.client.league().to_string()), Some(self.plan.clone()))
+    }
+
+    // Save collected listings for later analysis, in the versioned format collect writes
+    pub async fn save_collected_data(&self, items: &[ItemResponse], path: &Path) -> Result<()> {
+        save_collected_items(path, &self.dataset_header(), items).await
+    }
+
+    // Load listings written by save_collected_data or collect, migrating older formats.
+    // They stay ItemResponses; analyzers take them through CleanedItem::from_response.
+    pub async fn load_collected_data(path: &Path) -> Result<CollectedDataset> {
+        load_collected_items(path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling_weight() {
+        assert_eq!(sampling_weight(400, 40), Some(10.0));
+        // Listings delisted between the search and the fetch can leave fewer matches than IDs
+        assert_eq!(sampling_weight(5, 10), Some(1.0));
+        assert_eq!(sampling_weight(0, 0), None);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_run_stops_before_searching() {
+        let transport = std::sync::Arc::new(crate::fetcher::RecordedTransport::new());
+        let client = TradeApiClient::builder("Standard".to_string())
+            .rate_budget(crate::fetcher::RateBudget::new())
+            .transport(transport.clone())
+            .build()
+            .unwrap();
+        let token = CancellationToken::new();
+        let mut collector = StatCollector::new(client).with_cancellation(token.clone());
+        token.cancel();
+
+        assert!(collector.collect_stat_data().await.unwrap().is_empty());
+        assert!(collector.summary().interrupted);
+        assert!(collector.summary().ranges.is_empty());
+        assert!(transport.requests().is_empty());
+    }
+}
@@ -0,0 +1,420 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::data::currency_converter::CurrencyConverter;
+use crate::errors::{Result, ScraperError};
+use crate::models::{percentile, CleanedItem, ItemClass, ItemResponse, ModKind, Price};
+
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+// How far supply has to move, as a fraction, before a price move is put down to it
+const SUPPLY_SHIFT: f64 = 0.2;
+
+// What a price series follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrendSubject {
+    Modifier,
+    Base,
+    // The item class, e.g. "Boots", as the trade site's categories split items
+    Category,
+}
+
+impl TrendSubject {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrendSubject::Modifier => "modifier",
+            TrendSubject::Base => "base",
+            TrendSubject::Category => "category",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "modifier" => Ok(TrendSubject::Modifier),
+            "base" => Ok(TrendSubject::Base),
+            "category" => Ok(TrendSubject::Category),
+            other => Err(ScraperError::ParseError(format!("Unknown trend subject: {}", other))),
+        }
+    }
+}
+
+impl fmt::Display for TrendSubject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// One day's listing prices and supply for a modifier, base type or category
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DailyAggregate {
+    pub day: String,
+    pub kind: TrendSubject,
+    pub subject: String,
+    pub median: f64,
+    pub p25: f64,
+    pub p75: f64,
+    // Listings the prices were taken over
+    pub volume: u32,
+    // Every listing of the subject seen that day, including those in currencies without a
+    // rate, i.e. the supply
+    #[serde(default)]
+    pub listings: u32,
+}
+
+// What a day's price move looks like next to its supply move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarketSignal {
+    // Listings drying up while prices hold or rise
+    Scarce,
+    // Prices falling although listings aren't piling up, so buyers are what's missing
+    WeakDemand,
+}
+
+impl MarketSignal {
+    // From the price and supply changes over the same span, as fractions
+    pub fn classify(price_change: f64, supply_change: f64) -> Option<Self> {
+        if supply_change <= -SUPPLY_SHIFT && price_change >= 0.0 {
+            Some(MarketSignal::Scarce)
+        } else if price_change < 0.0 && supply_change.abs() < SUPPLY_SHIFT {
+            Some(MarketSignal::WeakDemand)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MarketSignal::Scarce => "scarce",
+            MarketSignal::WeakDemand => "weak demand",
+        }
+    }
+}
+
+// A day of a series alongside how its median and listing count moved, as fractions (0.1
+// is +10%)
+#[derive(Debug, Clone, Serialize)]
+pub struct TrendPoint {
+    #[serde(flatten)]
+    pub aggregate: DailyAggregate,
+    pub day_over_day: Option<f64>,
+    pub week_over_week: Option<f64>,
+    pub supply_day_over_day: Option<f64>,
+    pub supply_week_over_week: Option<f64>,
+    // Over the week when there is a week to compare against, otherwise over the day
+    pub signal: Option<MarketSignal>,
+}
+
+// Aggregates a day's listings into per-modifier, per-base and per-category price and
+// supply summaries
+pub struct TrendAnalyzer {
+    day: String,
+    prices: HashMap<(TrendSubject, String), Vec<f64>>,
+    listings: HashMap<(TrendSubject, String), u32>,
+    mod_kinds: Vec<ModKind>,
+    // Prices are only comparable across days once they are in one currency
+    converter: Option<CurrencyConverter>,
+    unconverted_listings: usize,
+}
+
+impl TrendAnalyzer {
+    pub fn new(day: impl Into<String>) -> Self {
+        Self {
+            day: day.into(),
+            prices: HashMap::new(),
+            listings: HashMap::new(),
+            mod_kinds: vec![ModKind::Explicit],
+            converter: None,
+            unconverted_listings: 0,
+        }
+    }
+
+    // Aggregate under today's UTC date
+    pub fn today() -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Self::new(format_day(now / SECS_PER_DAY))
+    }
+
+    pub fn day(&self) -> &str {
+        &self.day
+    }
+
+    pub fn set_mod_kinds(&mut self, kinds: Vec<ModKind>) {
+        self.mod_kinds = kinds;
+    }
+
+    pub fn set_currency_converter(&mut self, converter: CurrencyConverter) {
+        self.converter = Some(converter);
+    }
+
+    pub fn unconverted_listings(&self) -> usize {
+        self.unconverted_listings
+    }
+
+    pub fn process_item(&mut self, item: &ItemResponse) {
+        let modifiers = item.item.extended.mods
+            .iter_kinds(&self.mod_kinds)
+            .map(|(kind, mod_info)| kind.stat_key(&mod_info.name))
+            .collect();
+        let first_property = item.item.properties.first().map(|property| property.name.as_str());
+        let class = item_class(first_property, &item.item.base_type);
+        self.record(&item.item.base_type, class, &item.listing.price, modifiers);
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        let Some(price) = &item.price else {
+            return;
+        };
+        let modifiers = self.mod_kinds
+            .iter()
+            .flat_map(|&kind| item.mod_info.of_kind(kind).iter().map(move |m| kind.stat_key(&m.name)))
+            .collect();
+        let first_property = item.properties.first().map(|property| property.name.as_str());
+        let class = item_class(first_property, &item.base_type);
+        self.record(&item.base_type, class, price, modifiers);
+    }
+
+    fn record(&mut self, base_type: &str, class: Option<ItemClass>, price: &Price, modifiers: Vec<String>) {
+        let subjects: Vec<(TrendSubject, String)> = [(TrendSubject::Base, base_type.to_string())]
+            .into_iter()
+            .chain(class.map(|class| (TrendSubject::Category, class.to_string())))
+            .chain(modifiers.into_iter().map(|modifier| (TrendSubject::Modifier, modifier)))
+            .collect();
+        // Counted before conversion, so listings in unknown currencies still count as supply
+        for subject in &subjects {
+            *self.listings.entry(subject.clone()).or_default() += 1;
+        }
+
+        let amount = match &self.converter {
+            Some(converter) => match converter.convert(price.amount, &price.currency) {
+                Some(amount) => amount,
+                None => {
+                    self.unconverted_listings += 1;
+                    return;
+                }
+            },
+            None => price.amount,
+        };
+        for subject in subjects {
+            self.prices.entry(subject).or_default().push(amount);
+        }
+    }
+
+    pub fn aggregates(&self) -> Vec<DailyAggregate> {
+        let mut aggregates: Vec<DailyAggregate> = self.prices
+            .iter()
+            .map(|(key, prices)| {
+                let (kind, subject) = key;
+                let mut sorted = prices.clone();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                DailyAggregate {
+                    day: self.day.clone(),
+                    kind: *kind,
+                    subject: subject.clone(),
+                    median: percentile(&sorted, 0.5),
+                    p25: percentile(&sorted, 0.25),
+                    p75: percentile(&sorted, 0.75),
+                    volume: sorted.len() as u32,
+                    listings: self.listings.get(key).copied().unwrap_or_default(),
+                }
+            })
+            .collect();
+        aggregates.sort_by(|a, b| (a.kind.as_str(), &a.subject).cmp(&(b.kind.as_str(), &b.subject)));
+        aggregates
+    }
+
+    // Attach day-over-day and week-over-week median and listing count changes to a series.
+    // Days missing from the history leave the matching change empty rather than comparing
+    // against another day.
+    pub fn deltas(history: &[DailyAggregate]) -> Vec<TrendPoint> {
+        let by_day: HashMap<u64, &DailyAggregate> = history
+            .iter()
+            .filter_map(|aggregate| parse_day(&aggregate.day).map(|day| (day, aggregate)))
+            .collect();
+        let change = |day: Option<u64>, back: u64, value: fn(&DailyAggregate) -> f64, current: f64| {
+            let previous = value(by_day.get(&day?.checked_sub(back)?)?);
+            (previous > 0.0).then(|| current / previous - 1.0)
+        };
+        let median: fn(&DailyAggregate) -> f64 = |aggregate| aggregate.median;
+        let listings: fn(&DailyAggregate) -> f64 = |aggregate| f64::from(aggregate.listings);
+
+        history
+            .iter()
+            .map(|aggregate| {
+                let day = parse_day(&aggregate.day);
+                let day_over_day = change(day, 1, median, aggregate.median);
+                let week_over_week = change(day, 7, median, aggregate.median);
+                let supply_day_over_day = change(day, 1, listings, f64::from(aggregate.listings));
+                let supply_week_over_week = change(day, 7, listings, f64::from(aggregate.listings));
+                let signal = match (week_over_week, supply_week_over_week) {
+                    (Some(price), Some(supply)) => MarketSignal::classify(price, supply),
+                    _ => day_over_day
+                        .zip(supply_day_over_day)
+                        .and_then(|(price, supply)| MarketSignal::classify(price, supply)),
+                };
+                TrendPoint {
+                    day_over_day,
+                    week_over_week,
+                    supply_day_over_day,
+                    supply_week_over_week,
+                    signal,
+                    aggregate: aggregate.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+// The class named by an item's first property, as weapons and some armour have, or guessed
+// from its base type
+fn item_class(first_property: Option<&str>, base_type: &str) -> Option<ItemClass> {
+    first_property
+        .and_then(ItemClass::from_name)
+        .or_else(|| ItemClass::from_base_name(base_type))
+}
+
+// Days since 1970-01-01 as YYYY-MM-DD, using the proleptic Gregorian calendar
+fn format_day(days: u64) -> String {
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Seconds since the Unix epoch as a UTC 'YYYY-MM-DD HH:MM:SS', the form timestamps are stored in
+pub fn format_timestamp(secs: u64) -> String {
+    let time = secs % SECS_PER_DAY;
+    format!(
+        "{} {:02}:{:02}:{:02}",
+        format_day(secs / SECS_PER_DAY),
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+// YYYY-MM-DD as days since 1970-01-01
+pub fn parse_day(value: &str) -> Option<u64> {
+    let mut parts = value.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    u64::try_from(era * 146_097 + doe - 719_468).ok()
+}
+
+// A stored 'YYYY-MM-DD HH:MM:SS' as seconds since the Unix epoch
+pub fn parse_timestamp(value: &str) -> Option<u64> {
+    let (day, time) = value.split_once(' ')?;
+    let mut parts = time.splitn(3, ':');
+    let hours: u64 = parts.next()?.parse().ok()?;
+    let minutes: u64 = parts.next()?.parse().ok()?;
+    let seconds: u64 = parts.next()?.parse().ok()?;
+    if hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    Some(parse_day(day)? * SECS_PER_DAY + hours * 3600 + minutes * 60 + seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aggregate(day: &str, median: f64) -> DailyAggregate {
+        supply(day, median, 1)
+    }
+
+    fn supply(day: &str, median: f64, listings: u32) -> DailyAggregate {
+        DailyAggregate {
+            day: day.to_string(),
+            kind: TrendSubject::Modifier,
+            subject: "of the Lion".to_string(),
+            median,
+            p25: median,
+            p75: median,
+            volume: listings,
+            listings,
+        }
+    }
+
+    #[test]
+    fn test_day_round_trip() {
+        assert_eq!(format_day(0), "1970-01-01");
+        assert_eq!(parse_day("2024-02-29").map(format_day).as_deref(), Some("2024-02-29"));
+        assert_eq!(parse_day("2025-01-01").unwrap() - parse_day("2024-12-31").unwrap(), 1);
+        assert_eq!(parse_day("2024-13-01"), None);
+        assert_eq!(format_timestamp(86_400 + 3_723), "1970-01-02 01:02:03");
+        assert_eq!(parse_timestamp("2025-03-04 05:06:07").map(format_timestamp).as_deref(), Some("2025-03-04 05:06:07"));
+        assert_eq!(parse_timestamp("2025-03-04"), None);
+    }
+
+    #[test]
+    fn test_deltas_compare_against_calendar_days() {
+        let history = vec![
+            aggregate("2024-12-25", 10.0),
+            aggregate("2024-12-31", 16.0),
+            aggregate("2025-01-01", 20.0),
+        ];
+        let points = TrendAnalyzer::deltas(&history);
+
+        assert_eq!(points[0].day_over_day, None);
+        // 2024-12-31 has no 2024-12-30 to compare against
+        assert_eq!(points[1].day_over_day, None);
+        assert!((points[2].day_over_day.unwrap() - 0.25).abs() < 1e-9);
+        assert!((points[2].week_over_week.unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_supply_changes_tell_scarcity_from_weak_demand() {
+        let history = vec![
+            supply("2025-01-01", 10.0, 50),
+            supply("2025-01-02", 12.0, 30),
+            supply("2025-01-03", 9.0, 32),
+            supply("2025-01-08", 20.0, 60),
+        ];
+        let points = TrendAnalyzer::deltas(&history);
+
+        assert!((points[1].supply_day_over_day.unwrap() + 0.4).abs() < 1e-9);
+        assert_eq!(points[1].signal, Some(MarketSignal::Scarce));
+        assert_eq!(points[2].signal, Some(MarketSignal::WeakDemand));
+        // Week over week, supply grew along with the price
+        assert!((points[3].supply_week_over_week.unwrap() - 0.2).abs() < 1e-9);
+        assert_eq!(points[3].signal, None);
+    }
+
+    #[test]
+    fn test_aggregates_count_supply_per_category() {
+        let mut trends = TrendAnalyzer::new("2025-01-01");
+        let mut converter = CurrencyConverter::new(crate::models::Currency::Exalted);
+        converter.set_rate(crate::models::Currency::Divine, 100.0);
+        trends.set_currency_converter(converter);
+        let price = |amount, currency: &str| Price { amount, currency: currency.into() };
+        let boots = ItemClass::from_base_name("Velvet Slippers");
+        trends.record("Leather Boots", boots, &price(2.0, "exalted"), vec!["of the Lion".to_string()]);
+        trends.record("Velvet Slippers", boots, &price(1.0, "divine"), Vec::new());
+        trends.record("Velvet Slippers", boots, &price(1.0, "unheard-of-orb"), Vec::new());
+
+        let aggregates = trends.aggregates();
+        let boots = aggregates.iter().find(|a| a.kind == TrendSubject::Category).unwrap();
+        assert_eq!((boots.subject.as_str(), boots.volume, boots.listings), ("Boots", 2, 3));
+        assert_eq!(boots.median, 51.0);
+        assert_eq!(trends.unconverted_listings(), 1);
+    }
+}
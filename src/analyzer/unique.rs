@@ -0,0 +1,316 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use crate::data::currency_converter::CurrencyConverter;
+use crate::data::unique_data_loader::UniqueCatalogue;
+use crate::models::{percentile, CleanedItem, CorruptionSplit, ItemRarity, ModKind};
+use super::pipeline::ItemAnalyzer;
+
+// Listings of one unique are split into this many groups by how well a modifier rolled
+pub const ROLL_BANDS: usize = 4;
+
+// Modifiers are only priced by roll once a unique has this many listings
+const MIN_ROLL_LISTINGS: usize = ROLL_BANDS * 2;
+
+struct UniqueListing {
+    price: f64,
+    corrupted: bool,
+    // Modifier -> first rolled value. Unique modifiers usually have no name, so they're
+    // told apart by stat ID instead.
+    rolls: HashMap<String, f64>,
+}
+
+#[derive(Default)]
+struct UniqueListings {
+    base_type: String,
+    listings: Vec<UniqueListing>,
+}
+
+// Prices of the listings whose roll of a modifier ranks in one band, worst rolls first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RollBand {
+    // Percentile range of the rolls in the band, e.g. 75 to 100 for the best quarter
+    pub from_percentile: u32,
+    pub to_percentile: u32,
+    pub min_roll: f64,
+    pub max_roll: f64,
+    pub listings: usize,
+    pub median_price: f64,
+    // Where min_roll and max_roll sit in the modifier's possible range, 0 being the worst
+    // roll; only with a unique catalogue that knows the range
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_position: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_position: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RollPricing {
+    pub modifier: String,
+    // Lowest and highest value the modifier can roll on this unique, from the catalogue
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub possible_range: Option<(f64, f64)>,
+    pub bands: Vec<RollBand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UniqueReport {
+    pub name: String,
+    pub base_type: String,
+    pub listings: usize,
+    pub median_price: f64,
+    pub p25: f64,
+    pub p75: f64,
+    // Corrupted uniques can carry a corruption implicit or have lost their upside, so
+    // they're priced apart from clean copies too
+    pub by_corruption: CorruptionSplit,
+    pub rolls: Vec<RollPricing>,
+}
+
+// Prices uniques by name. Their modifiers are fixed, so what moves the price is which
+// unique it is and how well its modifiers rolled, not which modifiers it has.
+pub struct UniqueAnalyzer {
+    uniques: HashMap<String, UniqueListings>,
+    mod_kinds: Vec<ModKind>,
+    converter: Option<CurrencyConverter>,
+    unconverted_listings: usize,
+    // Possible mod ranges, to place the rolls seen within them
+    catalogue: Option<UniqueCatalogue>,
+}
+
+impl Default for UniqueAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UniqueAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            uniques: HashMap::new(),
+            mod_kinds: vec![ModKind::Explicit],
+            converter: None,
+            unconverted_listings: 0,
+            catalogue: None,
+        }
+    }
+
+    pub fn set_mod_kinds(&mut self, kinds: Vec<ModKind>) {
+        self.mod_kinds = kinds;
+    }
+
+    pub fn set_currency_converter(&mut self, converter: CurrencyConverter) {
+        self.converter = Some(converter);
+    }
+
+    pub fn set_catalogue(&mut self, catalogue: UniqueCatalogue) {
+        self.catalogue = Some(catalogue);
+    }
+
+    pub fn unconverted_listings(&self) -> usize {
+        self.unconverted_listings
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        if item.rarity != ItemRarity::Unique {
+            return;
+        }
+        let Some(price) = &item.price else {
+            return;
+        };
+        let amount = match &self.converter {
+            Some(converter) => match converter.convert(price.amount, &price.currency) {
+                Some(amount) => amount,
+                None => {
+                    self.unconverted_listings += 1;
+                    return;
+                }
+            },
+            None => price.amount,
+        };
+
+        let rolls = self.mod_kinds
+            .iter()
+            .flat_map(|&kind| item.mod_info.of_kind(kind).iter().map(move |m| (kind, m)))
+            .filter_map(|(kind, m)| {
+                let magnitude = m.magnitudes.first()?;
+                let name = if m.name.is_empty() { &magnitude.hash } else { &m.name };
+                Some((kind.stat_key(name), magnitude.min.parse().ok()?))
+            })
+            .collect();
+        let unique = self.uniques.entry(item.name.clone()).or_default();
+        unique.base_type = item.base_type.clone();
+        unique.listings.push(UniqueListing { price: amount, corrupted: item.corrupted, rolls });
+    }
+
+    pub fn unique_report(&self, name: &str) -> Option<UniqueReport> {
+        let unique = self.uniques.get(name)?;
+        let mut prices: Vec<f64> = unique.listings.iter().map(|listing| listing.price).collect();
+        prices.sort_by(|a, b| a.total_cmp(b));
+
+        let mut modifiers: Vec<&String> = unique.listings
+            .iter()
+            .flat_map(|listing| listing.rolls.keys())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        modifiers.sort();
+
+        Some(UniqueReport {
+            name: name.to_string(),
+            base_type: unique.base_type.clone(),
+            listings: prices.len(),
+            median_price: percentile(&prices, 0.5),
+            p25: percentile(&prices, 0.25),
+            p75: percentile(&prices, 0.75),
+            by_corruption: CorruptionSplit::from_prices(unique.listings.iter().map(|listing| (listing.corrupted, listing.price))),
+            rolls: modifiers
+                .into_iter()
+                .filter_map(|modifier| roll_pricing(modifier, &unique.listings))
+                .map(|pricing| self.place_rolls(name, pricing))
+                .collect(),
+        })
+    }
+
+    fn place_rolls(&self, name: &str, mut pricing: RollPricing) -> RollPricing {
+        let Some(catalogue) = &self.catalogue else {
+            return pricing;
+        };
+        // Keys of mods other than explicit ones are prefixed with their kind
+        let stat = pricing.modifier.rsplit(": ").next().unwrap_or(&pricing.modifier);
+        pricing.possible_range = catalogue.mod_range(name, stat);
+        for band in &mut pricing.bands {
+            band.min_position = catalogue.roll_position(name, stat, band.min_roll);
+            band.max_position = catalogue.roll_position(name, stat, band.max_roll);
+        }
+        pricing
+    }
+
+    // Every unique seen, most listed first
+    pub fn reports(&self) -> Vec<UniqueReport> {
+        let mut reports: Vec<UniqueReport> = self.uniques
+            .keys()
+            .filter_map(|name| self.unique_report(name))
+            .collect();
+        reports.sort_by(|a, b| b.listings.cmp(&a.listings).then_with(|| a.name.cmp(&b.name)));
+        reports
+    }
+}
+
+// None when too few listings have the modifier, or its roll never varies
+fn roll_pricing(modifier: &str, listings: &[UniqueListing]) -> Option<RollPricing> {
+    let mut rolled: Vec<(f64, f64)> = listings
+        .iter()
+        .filter_map(|listing| listing.rolls.get(modifier).map(|&roll| (roll, listing.price)))
+        .collect();
+    if rolled.len() < MIN_ROLL_LISTINGS {
+        return None;
+    }
+    rolled.sort_by(|a, b| a.0.total_cmp(&b.0));
+    if rolled.first()?.0 == rolled.last()?.0 {
+        return None;
+    }
+
+    let bands = (0..ROLL_BANDS)
+        .map(|band| {
+            let start = band * rolled.len() / ROLL_BANDS;
+            let end = (band + 1) * rolled.len() / ROLL_BANDS;
+            let group = &rolled[start..end];
+            let mut prices: Vec<f64> = group.iter().map(|&(_, price)| price).collect();
+            prices.sort_by(|a, b| a.total_cmp(b));
+            RollBand {
+                from_percentile: (band * 100 / ROLL_BANDS) as u32,
+                to_percentile: ((band + 1) * 100 / ROLL_BANDS) as u32,
+                min_roll: group[0].0,
+                max_roll: group[group.len() - 1].0,
+                listings: group.len(),
+                median_price: percentile(&prices, 0.5),
+                min_position: None,
+                max_position: None,
+            }
+        })
+        .collect();
+
+    Some(RollPricing {
+        modifier: modifier.to_string(),
+        possible_range: None,
+        bands,
+    })
+}
+
+impl ItemAnalyzer for UniqueAnalyzer {
+    fn name(&self) -> &str {
+        "uniques"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!({
+            "unconverted_listings": self.unconverted_listings,
+            "uniques": self.reports(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+
+    fn unique(id: usize, name: &str, life: u32, price: f64) -> CleanedItem {
+        ListingBuilder::new(&format!("listing{}", id), "Iron Ring")
+            .with_name(name)
+            .with_rarity("Unique")
+            .with_affix("", "", "explicit.stat_3299347043", life.into(), life.into())
+            .with_price(price)
+            .cleaned()
+    }
+
+    #[test]
+    fn test_uniques_priced_by_name_and_roll() {
+        let mut analyzer = UniqueAnalyzer::new();
+        for i in 0..8 {
+            analyzer.process_cleaned_item(&unique(i, "Ming's Heart", 30 + i as u32, 10.0 + i as f64 * 10.0));
+        }
+        analyzer.process_cleaned_item(&unique(100, "Andvarius", 10, 1.0));
+        let mut rare = unique(101, "Doom Loop", 80, 500.0);
+        rare.rarity = ItemRarity::Rare;
+        analyzer.process_cleaned_item(&rare);
+
+        let reports = analyzer.reports();
+        assert_eq!(reports.len(), 2);
+        let ming = &reports[0];
+        assert_eq!((ming.name.as_str(), ming.listings), ("Ming's Heart", 8));
+        assert_eq!(ming.median_price, 45.0);
+
+        let bands = &ming.rolls[0].bands;
+        assert_eq!(bands.len(), ROLL_BANDS);
+        assert_eq!((bands[0].min_roll, bands[0].max_roll, bands[0].median_price), (30.0, 31.0, 15.0));
+        assert_eq!((bands[3].from_percentile, bands[3].to_percentile, bands[3].median_price), (75, 100, 75.0));
+        // Too few listings to price by roll
+        assert!(reports[1].rolls.is_empty());
+    }
+
+    #[test]
+    fn test_rolls_placed_in_catalogue_range() {
+        let mut catalogue = crate::data::unique_data_loader::UniqueDataLoader::new();
+        let seed = std::env::temp_dir().join(format!("poe2-unique-rolls-test-{}.json", std::process::id()));
+        std::fs::write(&seed, r#"[{"name": "Ming's Heart", "base_type": "Iron Ring", "mods": [
+            {"stat": "explicit.stat_3299347043", "min": 30, "max": 50}
+        ]}]"#).unwrap();
+        tokio::runtime::Runtime::new().unwrap().block_on(catalogue.load_from_file(seed.to_str().unwrap())).unwrap();
+        let _ = std::fs::remove_file(&seed);
+
+        let mut analyzer = UniqueAnalyzer::new();
+        analyzer.set_catalogue(catalogue.catalogue().clone());
+        for i in 0..8 {
+            analyzer.process_cleaned_item(&unique(i, "Ming's Heart", 30 + i as u32, 10.0));
+        }
+
+        let rolls = &analyzer.unique_report("Ming's Heart").unwrap().rolls[0];
+        assert_eq!(rolls.possible_range, Some((30.0, 50.0)));
+        assert_eq!((rolls.bands[0].min_position, rolls.bands[3].max_position), (Some(0.0), Some(0.35)));
+    }
+}
@@ -0,0 +1,225 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use crate::data::currency_converter::CurrencyConverter;
+use crate::models::{modifier_template, percentile, CleanedItem, ItemRarity};
+use super::pipeline::ItemAnalyzer;
+
+// Modifiers reported per tier unless configured otherwise
+pub const DEFAULT_WAYSTONE_MODIFIERS: usize = 10;
+
+// Listings a modifier needs within a tier before it is reported
+pub const DEFAULT_WAYSTONE_MIN_LISTINGS: usize = 3;
+
+// One modifier seen on waystones of a tier, and what those waystones cost
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WaystoneModifier {
+    pub modifier: String,
+    pub listings: usize,
+    pub median_price: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WaystoneTierReport {
+    // None for waystones whose tier couldn't be read
+    pub tier: Option<u32>,
+    pub listings: usize,
+    pub median_price: f64,
+    pub p25: f64,
+    pub p75: f64,
+    // Median price of corrupted or rare waystones, which are the ones already juiced
+    pub rare_median_price: Option<f64>,
+    // Averages over the listings that show the bonus
+    pub average_item_rarity: Option<f64>,
+    pub average_pack_size: Option<f64>,
+    pub average_drop_chance: Option<f64>,
+    // Most listed first
+    pub modifiers: Vec<WaystoneModifier>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WaystoneReport {
+    pub unconverted_listings: usize,
+    // Lowest tier first
+    pub tiers: Vec<WaystoneTierReport>,
+}
+
+#[derive(Default)]
+struct TierPrices {
+    prices: Vec<f64>,
+    rare_prices: Vec<f64>,
+    item_rarity: Vec<u32>,
+    pack_size: Vec<u32>,
+    drop_chance: Vec<u32>,
+    modifiers: BTreeMap<String, Vec<f64>>,
+}
+
+// Prices waystones by tier, since a T15 and a T5 are different markets, along with the
+// bonuses and modifiers that make one waystone of a tier worth more than another
+#[derive(Default)]
+pub struct WaystoneAnalyzer {
+    tiers: BTreeMap<Option<u32>, TierPrices>,
+    converter: Option<CurrencyConverter>,
+    unconverted_listings: usize,
+    top_modifiers: usize,
+    min_listings: usize,
+}
+
+impl WaystoneAnalyzer {
+    pub fn new() -> Self {
+        Self {
+            top_modifiers: DEFAULT_WAYSTONE_MODIFIERS,
+            min_listings: DEFAULT_WAYSTONE_MIN_LISTINGS,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_top_modifiers(mut self, top_modifiers: usize) -> Self {
+        self.top_modifiers = top_modifiers;
+        self
+    }
+
+    pub fn with_min_listings(mut self, min_listings: usize) -> Self {
+        self.min_listings = min_listings;
+        self
+    }
+
+    pub fn set_currency_converter(&mut self, converter: CurrencyConverter) {
+        self.converter = Some(converter);
+    }
+
+    pub fn unconverted_listings(&self) -> usize {
+        self.unconverted_listings
+    }
+
+    pub fn process_cleaned_item(&mut self, item: &CleanedItem) {
+        let (Some(waystone), Some(price)) = (&item.waystone, &item.price) else {
+            return;
+        };
+        let amount = match &self.converter {
+            Some(converter) => match converter.convert(price.amount, &price.currency) {
+                Some(amount) => amount,
+                None => {
+                    self.unconverted_listings += 1;
+                    return;
+                }
+            },
+            None => price.amount,
+        };
+
+        let tier = self.tiers.entry(waystone.tier).or_default();
+        tier.prices.push(amount);
+        if item.corrupted || item.rarity == ItemRarity::Rare {
+            tier.rare_prices.push(amount);
+        }
+        tier.item_rarity.extend(waystone.item_rarity);
+        tier.pack_size.extend(waystone.pack_size);
+        tier.drop_chance.extend(waystone.drop_chance);
+        let templates: BTreeSet<String> = item.explicit_mods.iter().map(|text| modifier_template(text)).collect();
+        for template in templates {
+            tier.modifiers.entry(template).or_default().push(amount);
+        }
+    }
+
+    pub fn waystone_report(&self) -> WaystoneReport {
+        let tiers = self.tiers
+            .iter()
+            .map(|(tier, prices)| {
+                let mut modifiers: Vec<WaystoneModifier> = prices.modifiers
+                    .iter()
+                    .filter(|(_, prices)| prices.len() >= self.min_listings.max(1))
+                    .map(|(modifier, prices)| WaystoneModifier {
+                        modifier: modifier.clone(),
+                        listings: prices.len(),
+                        median_price: percentile(&sorted(prices), 0.5),
+                    })
+                    .collect();
+                modifiers.sort_by(|a, b| b.listings.cmp(&a.listings).then(b.median_price.total_cmp(&a.median_price)));
+                modifiers.truncate(self.top_modifiers);
+
+                let all = sorted(&prices.prices);
+                WaystoneTierReport {
+                    tier: *tier,
+                    listings: all.len(),
+                    median_price: percentile(&all, 0.5),
+                    p25: percentile(&all, 0.25),
+                    p75: percentile(&all, 0.75),
+                    rare_median_price: (!prices.rare_prices.is_empty())
+                        .then(|| percentile(&sorted(&prices.rare_prices), 0.5)),
+                    average_item_rarity: average(&prices.item_rarity),
+                    average_pack_size: average(&prices.pack_size),
+                    average_drop_chance: average(&prices.drop_chance),
+                    modifiers,
+                }
+            })
+            .collect();
+
+        WaystoneReport {
+            unconverted_listings: self.unconverted_listings,
+            tiers,
+        }
+    }
+}
+
+fn sorted(prices: &[f64]) -> Vec<f64> {
+    let mut prices = prices.to_vec();
+    prices.sort_by(|a, b| a.total_cmp(b));
+    prices
+}
+
+fn average(values: &[u32]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().map(|&value| value as f64).sum::<f64>() / values.len() as f64)
+}
+
+impl ItemAnalyzer for WaystoneAnalyzer {
+    fn name(&self) -> &str {
+        "waystones"
+    }
+
+    fn process(&mut self, item: &CleanedItem) {
+        self.process_cleaned_item(item);
+    }
+
+    fn report(&self) -> serde_json::Value {
+        serde_json::json!(self.waystone_report())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::ListingBuilder;
+
+    fn waystone(id: &str, tier: u32, rarity: &str, mods: &[&str], pack_size: u32, price: f64) -> CleanedItem {
+        ListingBuilder::new(id, &format!("Waystone (Tier {})", tier))
+            .with_rarity(rarity)
+            .with_mods(mods)
+            .with_property("Monster Pack Size", &format!("+{}%", pack_size))
+            .with_price(price)
+            .cleaned()
+    }
+
+    #[test]
+    fn test_waystones_priced_by_tier() {
+        let mut analyzer = WaystoneAnalyzer::new().with_min_listings(2);
+        let mods = ["Monsters have 30% increased Attack Speed", "Area contains 2 additional Rare Monsters"];
+        analyzer.process_cleaned_item(&waystone("a", 15, "Rare", &mods, 20, 10.0));
+        analyzer.process_cleaned_item(&waystone("b", 15, "Rare", &mods[..1], 10, 4.0));
+        analyzer.process_cleaned_item(&waystone("c", 15, "Normal", &[], 0, 1.0));
+        analyzer.process_cleaned_item(&waystone("d", 5, "Normal", &[], 0, 0.1));
+
+        let report = analyzer.waystone_report();
+        let tiers: Vec<(Option<u32>, usize, f64)> = report.tiers
+            .iter()
+            .map(|tier| (tier.tier, tier.listings, tier.median_price))
+            .collect();
+        assert_eq!(tiers, vec![(Some(5), 1, 0.1), (Some(15), 3, 4.0)]);
+        let t15 = &report.tiers[1];
+        assert_eq!(t15.rare_median_price, Some(7.0));
+        assert_eq!(t15.average_pack_size, Some(10.0));
+        assert_eq!(t15.modifiers, vec![WaystoneModifier {
+            modifier: "Monsters have #% increased Attack Speed".to_string(),
+            listings: 2,
+            median_price: 7.0,
+        }]);
+    }
+}
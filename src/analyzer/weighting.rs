@@ -0,0 +1,108 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+// Price estimates with each listing weighted by how many market listings it stands for, so
+// strata sampled sparsely (e.g. an expensive price band) count for what they hold. Without
+// sampling weights every listing counts once and the weighted figures equal the plain ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct WeightedPrices {
+    pub listings: usize,
+    // Market listings the sample stands for
+    pub total_weight: f64,
+    pub mean: f64,
+    pub weighted_mean: f64,
+    pub weighted_median: f64,
+    // Kish's effective sample size, (sum w)^2 / sum w^2; well below `listings` means a few
+    // heavily weighted listings carry the estimates
+    pub effective_sample_size: f64,
+    // Weighted mean price of the listings carrying each modifier
+    pub modifier_means: BTreeMap<String, f64>,
+}
+
+// Mean of (value, weight) pairs, None without any weight
+pub fn weighted_mean(points: &[(f64, f64)]) -> Option<f64> {
+    let total: f64 = points.iter().map(|(_, weight)| weight).sum();
+    (total > 0.0).then(|| points.iter().map(|(value, weight)| value * weight).sum::<f64>() / total)
+}
+
+// The value at which half the weight lies on either side
+pub fn weighted_median(points: &[(f64, f64)]) -> Option<f64> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let half = sorted.iter().map(|(_, weight)| weight).sum::<f64>() / 2.0;
+    let mut cumulative = 0.0;
+    for (value, weight) in sorted {
+        cumulative += weight;
+        if cumulative >= half {
+            return Some(value);
+        }
+    }
+    None
+}
+
+pub fn effective_sample_size(weights: impl Iterator<Item = f64> + Clone) -> f64 {
+    let total: f64 = weights.clone().sum();
+    let squares: f64 = weights.map(|weight| weight * weight).sum();
+    if squares > 0.0 { total * total / squares } else { 0.0 }
+}
+
+impl WeightedPrices {
+    // From each listing's price, sampling weight and modifier names
+    pub fn from_listings(listings: impl IntoIterator<Item = (f64, f64, Vec<String>)>) -> Self {
+        let mut points = Vec::new();
+        let mut by_modifier: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::new();
+        for (price, weight, modifiers) in listings {
+            points.push((price, weight));
+            for modifier in modifiers {
+                by_modifier.entry(modifier).or_default().push((price, weight));
+            }
+        }
+        if points.is_empty() {
+            return Self::default();
+        }
+
+        Self {
+            listings: points.len(),
+            total_weight: points.iter().map(|(_, weight)| weight).sum(),
+            mean: points.iter().map(|(price, _)| price).sum::<f64>() / points.len() as f64,
+            weighted_mean: weighted_mean(&points).unwrap_or_default(),
+            weighted_median: weighted_median(&points).unwrap_or_default(),
+            effective_sample_size: effective_sample_size(points.iter().map(|(_, weight)| *weight)),
+            modifier_means: by_modifier
+                .into_iter()
+                .filter_map(|(modifier, points)| weighted_mean(&points).map(|mean| (modifier, mean)))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weights_shift_estimates_towards_sparse_strata() {
+        // Ten cheap listings fetched of ten, two expensive ones standing for forty each
+        let mut listings: Vec<(f64, f64, Vec<String>)> = (0..10).map(|_| (1.0, 1.0, vec!["Life".to_string()])).collect();
+        listings.push((100.0, 40.0, vec!["Life".to_string(), "Spirit".to_string()]));
+        listings.push((100.0, 40.0, vec![]));
+
+        let prices = WeightedPrices::from_listings(listings);
+        assert_eq!(prices.listings, 12);
+        assert_eq!(prices.total_weight, 90.0);
+        assert!((prices.mean - 210.0 / 12.0).abs() < 1e-9);
+        assert!((prices.weighted_mean - 8010.0 / 90.0).abs() < 1e-9);
+        assert_eq!(prices.weighted_median, 100.0);
+        assert!((prices.effective_sample_size - 8100.0 / 3210.0).abs() < 1e-9);
+        assert!((prices.modifier_means["Life"] - 4010.0 / 50.0).abs() < 1e-9);
+        assert_eq!(prices.modifier_means["Spirit"], 100.0);
+    }
+
+    #[test]
+    fn test_unweighted_listings_match_plain_estimates() {
+        let prices = WeightedPrices::from_listings([(1.0, 1.0, vec![]), (3.0, 1.0, vec![])]);
+        assert_eq!(prices.weighted_mean, prices.mean);
+        assert_eq!(prices.effective_sample_size, 2.0);
+        assert_eq!(WeightedPrices::from_listings(std::iter::empty()), WeightedPrices::default());
+    }
+}
@@ -0,0 +1,1435 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+use std::time::Duration;
+use rust_scraper::analyzer::{
+    OutlierFilter,
+    OutlierMethod,
+    PriceFeature,
+    ReportFormat,
+    TrendSubject,
+    DEFAULT_COMPARABLES,
+    DEFAULT_SALE_WINDOW_HOURS,
+    DEFAULT_CLUSTER_SEED,
+    DEFAULT_MIN_PAIR_SUPPORT,
+    DEFAULT_CHART_MODIFIERS,
+    DEFAULT_STACKING_BASES,
+    DEFAULT_STACKING_MIN_LISTINGS,
+    DEFAULT_FILTER_MIN_LISTINGS,
+    DEFAULT_HEATMAP_MODIFIERS,
+    DEFAULT_HEATMAP_MIN_LISTINGS,
+    parse_day,
+    DEFAULT_IQR_MULTIPLIER,
+    DEFAULT_MAD_THRESHOLD,
+};
+use rust_scraper::analyzer::{CollectionPlan, PriceBand, SweepLimits, DEFAULT_SWEEP_PAGES, SWEEP_CATEGORIES};
+use rust_scraper::config::{CollectConfig, ScraperConfig, DEFAULT_CONFIG_PATH};
+use rust_scraper::models::{parse_price_amount, CoreAttribute, Currency, ItemClass, ModKind};
+use rust_scraper::notifier::{ModCriterion, NotifyCriteria, WebhookFormat};
+use rust_scraper::storage::{compression::Compression, Database, DatasetFormat, JournalMode, PoolSettings, Synchronous, DEFAULT_VALUE_BUCKETS};
+
+pub const DEFAULT_LEAGUE: &str = "Standard";
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_FETCH_CONCURRENCY: usize = 2;
+#[cfg(feature = "server")]
+pub const DEFAULT_SERVE_ADDR: &str = "127.0.0.1:8080";
+
+#[derive(Parser, Debug)]
+#[clap(
+    author,
+    version,
+    about,
+    after_help = "Options left off the command line are read from the config file (--config, or scraper.toml when present), then fall back to their built-in defaults."
+)]
+pub struct Cli {
+    #[clap(flatten)]
+    pub api: ApiArgs,
+
+    /// Config file to read settings from; scraper.toml is used when present
+    #[clap(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    #[clap(flatten)]
+    pub database: DatabaseArgs,
+
+    /// More log output; repeat for trace level (-vv)
+    #[clap(short, long, global = true, action = clap::ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Only log warnings and errors
+    #[clap(short, long, global = true)]
+    pub quiet: bool,
+
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Collect listings per attribute requirement range into a JSON file and the database
+    Collect(CollectArgs),
+    /// Run one trade search and store the results
+    Search(SearchArgs),
+    /// Analyse a collected data file
+    Analyze(AnalyzeArgs),
+    /// Shopping report for a build stacking one attribute: the modifiers found with it, the
+    /// bases reaching the highest totals and what listings cost per point
+    Stacking(StackingArgs),
+    /// Matrix of base types against modifiers, each cell the average price of the base's
+    /// listings carrying the modifier, as CSV or a shaded HTML table
+    Heatmap(HeatmapArgs),
+    /// Export collected items from the database
+    Export(ExportArgs),
+    /// Price an item from the most similar listings in the database
+    PriceCheck(PriceCheckArgs),
+    /// Watch a saved trade search for new listings
+    Live(LiveArgs),
+    /// List the active PoE2 leagues
+    Leagues,
+    /// Serve the stored listings and analysis over a REST API
+    #[cfg(feature = "server")]
+    Serve(ServeArgs),
+    /// Collect while a terminal dashboard shows requests, throughput, errors and listings
+    #[cfg(feature = "tui")]
+    Tui(TuiArgs),
+    /// Manage the database: migrations, imports, pruning and reprocessing stored listings
+    #[clap(subcommand)]
+    Db(DbCommand),
+    /// Keep the base item list up to date
+    #[clap(subcommand)]
+    Bases(BasesCommand),
+    /// Build and look up which affixes spawn on each item class
+    #[clap(subcommand)]
+    Affixes(AffixesCommand),
+    /// Refresh exchange rates and check how many listing currencies they cover
+    #[clap(subcommand)]
+    Currency(CurrencyCommand),
+    /// Work with the scraper.toml config file
+    #[clap(subcommand)]
+    Config(ConfigCommand),
+    /// Record and show daily price history
+    #[clap(subcommand)]
+    Trends(TrendsCommand),
+}
+
+// Options for talking to the trade API, shared by every subcommand
+#[derive(Args, Debug)]
+pub struct ApiArgs {
+    /// Trade league to search; collect takes several by repeating it. Without one, the
+    /// config file's league, then Standard.
+    #[clap(short, long = "league", global = true)]
+    pub leagues: Vec<String>,
+
+    /// Collect from every active PoE2 league
+    #[clap(long, global = true, conflicts_with = "leagues")]
+    pub all_leagues: bool,
+
+    /// Don't check --league against the active league list before running
+    #[clap(long, global = true)]
+    pub skip_league_check: bool,
+
+    /// Maximum attempts per API request before giving up. Falls back to [api] max_retries
+    /// in the config file, then the built-in default.
+    #[clap(long, global = true)]
+    pub max_retries: Option<u32>,
+
+    /// HTTP(S) or SOCKS5 proxy for all API requests. Falls back to [api] proxy in the config file.
+    #[clap(long, global = true)]
+    pub proxy: Option<String>,
+
+    /// User-Agent sent to the API; GGG asks tools to include a contact address. Falls back to
+    /// [api] user_agent in the config file, then the built-in one.
+    #[clap(long, global = true)]
+    pub user_agent: Option<String>,
+
+    /// Per-request timeout in seconds. Falls back to [api] timeout_secs in the config file,
+    /// then the built-in default.
+    #[clap(long, global = true)]
+    pub timeout_secs: Option<u64>,
+
+    /// Always hit the API instead of the on-disk response cache
+    #[clap(long, global = true)]
+    pub no_cache: bool,
+
+    /// Write every trade API request and response to this new session directory
+    #[clap(long, global = true, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Answer trade API requests from a session directory written by --record instead of
+    /// the network, e.g. to reproduce a bug report or work on analyzers offline
+    #[clap(long, global = true, value_name = "DIR")]
+    pub replay: Option<PathBuf>,
+
+    /// Item detail batches fetched concurrently. Falls back to [api] fetch_concurrency in the
+    /// config file, then the built-in default.
+    #[clap(long, global = true)]
+    pub fetch_concurrency: Option<usize>,
+
+    /// POESESSID cookie of a logged-in session, for leagues that require auth
+    #[clap(long, global = true)]
+    pub poesessid: Option<String>,
+
+    /// OAuth client ID for the client credentials grant; used over --poesessid when both are set
+    #[clap(long, global = true, requires = "oauth_client_secret")]
+    pub oauth_client_id: Option<String>,
+
+    /// OAuth client secret that goes with --oauth-client-id
+    #[clap(long, global = true, requires = "oauth_client_id")]
+    pub oauth_client_secret: Option<String>,
+}
+
+// Where the database is and how to connect to it, shared by every subcommand. The pool
+// settings can also come from SCRAPER_DB_* environment variables.
+#[derive(Args, Debug)]
+pub struct DatabaseArgs {
+    /// Database to store listings in, e.g. sqlite:poe_items.db or postgres://user@host/poe.
+    /// Falls back to database_url in the config file, then DATABASE_URL, then the built-in default.
+    #[clap(long, global = true)]
+    pub database_url: Option<String>,
+
+    /// Connections kept open to the database. The flag or its environment variable wins,
+    /// then [database] pool_size in the config file, then the built-in default.
+    #[clap(long, global = true, env = "SCRAPER_DB_POOL_SIZE")]
+    pub db_pool_size: Option<u32>,
+
+    /// Milliseconds a SQLite write waits for another connection's lock. The flag or its
+    /// environment variable wins, then [database] busy_timeout_ms in the config file, then
+    /// the built-in default.
+    #[clap(long, global = true, env = "SCRAPER_DB_BUSY_TIMEOUT_MS")]
+    pub db_busy_timeout_ms: Option<u64>,
+
+    /// SQLite journal mode. The flag or its environment variable wins, then [database]
+    /// journal_mode in the config file, then wal.
+    #[clap(long, global = true, value_enum, env = "SCRAPER_DB_JOURNAL_MODE")]
+    pub db_journal_mode: Option<JournalModeArg>,
+
+    /// SQLite synchronous level. The flag or its environment variable wins, then [database]
+    /// synchronous in the config file, then normal.
+    #[clap(long, global = true, value_enum, env = "SCRAPER_DB_SYNCHRONOUS")]
+    pub db_synchronous: Option<SynchronousArg>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum JournalModeArg {
+    Delete,
+    Truncate,
+    Wal,
+}
+
+impl From<JournalModeArg> for JournalMode {
+    fn from(arg: JournalModeArg) -> Self {
+        match arg {
+            JournalModeArg::Delete => JournalMode::Delete,
+            JournalModeArg::Truncate => JournalMode::Truncate,
+            JournalModeArg::Wal => JournalMode::Wal,
+        }
+    }
+}
+
+impl From<JournalMode> for JournalModeArg {
+    fn from(mode: JournalMode) -> Self {
+        match mode {
+            JournalMode::Delete => JournalModeArg::Delete,
+            JournalMode::Truncate => JournalModeArg::Truncate,
+            JournalMode::Wal => JournalModeArg::Wal,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SynchronousArg {
+    Off,
+    Normal,
+    Full,
+}
+
+impl From<SynchronousArg> for Synchronous {
+    fn from(arg: SynchronousArg) -> Self {
+        match arg {
+            SynchronousArg::Off => Synchronous::Off,
+            SynchronousArg::Normal => Synchronous::Normal,
+            SynchronousArg::Full => Synchronous::Full,
+        }
+    }
+}
+
+impl From<Synchronous> for SynchronousArg {
+    fn from(level: Synchronous) -> Self {
+        match level {
+            Synchronous::Off => SynchronousArg::Off,
+            Synchronous::Normal => SynchronousArg::Normal,
+            Synchronous::Full => SynchronousArg::Full,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct PriceArgs {
+    /// Lowest listing price to search for
+    #[clap(short = 'n', long)]
+    pub min_price: Option<f64>,
+
+    /// Highest listing price to search for
+    #[clap(short = 'x', long)]
+    pub max_price: Option<f64>,
+
+    /// Currency the price bounds are expressed in (e.g. exalted, divine, "Orb of Alchemy")
+    #[clap(long)]
+    pub price_currency: Option<Currency>,
+}
+
+// Rune and soul core socket counts to search for
+#[derive(Args, Debug)]
+pub struct SocketArgs {
+    /// Fewest sockets a listing may have
+    #[clap(long)]
+    pub min_sockets: Option<u32>,
+
+    /// Most sockets a listing may have
+    #[clap(long)]
+    pub max_sockets: Option<u32>,
+}
+
+impl SocketArgs {
+    pub fn is_set(&self) -> bool {
+        self.min_sockets.is_some() || self.max_sockets.is_some()
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct CollectArgs {
+    #[clap(flatten)]
+    pub price: PriceArgs,
+
+    #[clap(flatten)]
+    pub sockets: SocketArgs,
+
+    /// Attribute requirement range to query, as MIN-MAX; repeat for several ranges
+    #[clap(long = "stat-range", value_name = "MIN-MAX", value_parser = parse_stat_range)]
+    pub stat_ranges: Vec<(u32, u32)>,
+
+    /// Search every item category for its cheapest listings instead of the attribute ranges
+    #[clap(long)]
+    pub sweep: bool,
+
+    /// Result pages of 10 listings fetched per category when sweeping. Falls back to
+    /// [collect] sweep_pages in the config file, then the built-in default.
+    #[clap(long, requires = "sweep")]
+    pub sweep_pages: Option<usize>,
+
+    /// Pages fetched for one category when sweeping, as CATEGORY=PAGES; repeat for several
+    #[clap(long = "sweep-limit", value_name = "CATEGORY=PAGES", value_parser = parse_sweep_limit, requires = "sweep")]
+    pub sweep_limits: Vec<(String, usize)>,
+
+    /// Price band searched separately from the others, as MIN-MAX in the price currency with
+    /// either end open, e.g. 0-5, 5-50, 50-; repeat to stratify the sample by price
+    #[clap(long = "price-band", value_name = "MIN-MAX", value_parser = parse_price_band)]
+    pub price_bands: Vec<PriceBand>,
+
+    /// Listings fetched at most per query and price band
+    #[clap(long)]
+    pub per_band: Option<usize>,
+
+    /// JSON collection plan of categories, attribute ranges, price bands and sort orders to search
+    #[clap(long, conflicts_with_all = ["sweep", "stat_ranges", "price_bands", "per_band"])]
+    pub plan: Option<PathBuf>,
+
+    /// The config file's [collect.plan], searched unless --plan or --sweep is given
+    #[clap(skip)]
+    pub config_plan: Option<Box<CollectionPlan>>,
+
+    /// Continue an interrupted collection run from its checkpoint
+    #[clap(long)]
+    pub resume: bool,
+
+    /// Where the collected listings are written
+    #[clap(short, long, default_value = "collected_data.json")]
+    pub output: PathBuf,
+
+    /// Keep collecting on an interval until interrupted with Ctrl-C
+    #[clap(long)]
+    pub daemon: bool,
+
+    /// Build and check the plan's queries, then estimate the API calls and time a run would
+    /// take, without fetching listings or writing anything
+    #[clap(long, conflicts_with = "daemon")]
+    pub dry_run: bool,
+
+    /// Also send the dry run's searches (no fetches) to report how many listings each matches
+    #[clap(long, requires = "dry_run")]
+    pub count_results: bool,
+
+    /// Minutes between collection runs in daemon mode
+    #[clap(long, default_value = "30")]
+    pub interval_mins: u64,
+
+    /// Number of per-run output files kept in daemon mode
+    #[clap(long, default_value = "48")]
+    pub keep_runs: usize,
+
+    /// Fetch every listing a daemon run finds, not only those its last search didn't return
+    #[clap(long, requires = "daemon")]
+    pub refetch_all: bool,
+
+    /// Serve Prometheus metrics at /metrics on this address while the daemon runs
+    #[cfg(feature = "server")]
+    #[clap(long, requires = "daemon")]
+    pub metrics_bind: Option<std::net::SocketAddr>,
+
+    /// Mark stored listings delisted once this many runs in a row haven't seen them; 0 never does
+    #[clap(long, default_value = "3")]
+    pub delist_after: u32,
+
+    /// Don't draw per-range progress bars
+    #[clap(long)]
+    pub no_progress: bool,
+
+    /// Also write the end-of-run summary to this file as JSON
+    #[clap(long)]
+    pub summary_json: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    #[clap(flatten)]
+    pub price: PriceArgs,
+
+    #[clap(flatten)]
+    pub sockets: SocketArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct AnalyzeArgs {
+    /// Collected data file produced by `collect`
+    #[clap(short, long, default_value = "collected_data.json")]
+    pub input: PathBuf,
+
+    /// Analyze every listing stored in the database instead of a collected data file
+    #[clap(long, conflicts_with = "input")]
+    pub from_db: bool,
+
+    /// Write the report here instead of stdout
+    #[clap(short, long, conflicts_with = "out")]
+    pub output: Option<PathBuf>,
+
+    /// json prints the report (or writes it with --output); csv writes one file per table
+    /// and html a single page with sortable tables, both into --out
+    #[clap(long, value_enum, default_value = "json")]
+    pub format: ReportFormatArg,
+
+    /// Directory to write the exported report into
+    #[clap(long, value_name = "DIR", required_if_eq_any = [("format", "csv"), ("format", "html")])]
+    pub out: Option<PathBuf>,
+
+    /// Also write SVG charts (value histograms, price-vs-value scatter plots and
+    /// requirement distributions) into this directory
+    #[clap(long, value_name = "DIR")]
+    pub charts: Option<PathBuf>,
+
+    /// Chart this many of the most common modifiers
+    #[clap(long, default_value_t = DEFAULT_CHART_MODIFIERS)]
+    pub chart_modifiers: usize,
+
+    /// Regress price on modifier presence or on rolled values
+    #[clap(long, value_enum, default_value = "presence")]
+    pub price_feature: PriceFeatureArg,
+
+    /// Ridge penalty for the price model; 0 is plain least squares
+    #[clap(long, default_value = "1.0")]
+    pub ridge_lambda: f64,
+
+    /// Leave modifiers seen on fewer listings than this out of the price model
+    #[clap(long, default_value = "5")]
+    pub min_occurrences: usize,
+
+    /// Leave modifier pairs seen together on fewer listings than this out of the pair report
+    #[clap(long, default_value_t = DEFAULT_MIN_PAIR_SUPPORT)]
+    pub min_pair_support: u32,
+
+    /// How to find listings with extreme prices
+    #[clap(long, value_enum, default_value = "none")]
+    pub outliers: OutlierMethodArg,
+
+    /// IQR multiplier or MAD z-score cutoff [default: 1.5 for iqr, 3.5 for mad]
+    #[clap(long)]
+    pub outlier_threshold: Option<f64>,
+
+    /// Only list outliers in the report instead of leaving them out of the statistics
+    #[clap(long)]
+    pub flag_outliers: bool,
+
+    /// Modifier value breakpoints for the per-range price percentiles, e.g. 0,50,100;
+    /// each modifier's values are split into even ranges when left out
+    #[clap(long, value_delimiter = ',')]
+    pub value_breakpoints: Vec<f64>,
+
+    /// Kinds of mods to include in the statistics, e.g. explicit,implicit,rune
+    #[clap(long, value_enum, value_delimiter = ',', default_value = "explicit")]
+    pub mod_kinds: Vec<ModKindArg>,
+
+    /// Continue from the analyzer state saved here by an earlier run, then save the updated
+    /// state back; listings already counted are skipped
+    #[clap(long)]
+    pub snapshot: Option<PathBuf>,
+
+    /// Count uniques in the attribute and modifier statistics too; they are always priced
+    /// per unique name in the report's uniques section
+    #[clap(long)]
+    pub include_uniques: bool,
+
+    /// Group listings into this many modifier archetypes and report each one's prices
+    #[clap(long, value_name = "K")]
+    pub clusters: Option<usize>,
+
+    /// Seed for picking the starting archetypes, so runs are repeatable
+    #[clap(long, default_value_t = DEFAULT_CLUSTER_SEED)]
+    pub cluster_seed: u64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutlierMethodArg {
+    None,
+    Iqr,
+    Mad,
+}
+
+impl AnalyzeArgs {
+    // The outlier filter the flags ask for, if any
+    pub fn outlier_filter(&self) -> Option<OutlierFilter> {
+        let method = match self.outliers {
+            OutlierMethodArg::None => return None,
+            OutlierMethodArg::Iqr => OutlierMethod::Iqr(self.outlier_threshold.unwrap_or(DEFAULT_IQR_MULTIPLIER)),
+            OutlierMethodArg::Mad => OutlierMethod::Mad(self.outlier_threshold.unwrap_or(DEFAULT_MAD_THRESHOLD)),
+        };
+        Some(OutlierFilter::new(method, !self.flag_outliers))
+    }
+
+    pub fn mod_kinds(&self) -> Vec<ModKind> {
+        unique_mod_kinds(&self.mod_kinds)
+    }
+}
+
+fn unique_mod_kinds(args: &[ModKindArg]) -> Vec<ModKind> {
+    let mut kinds: Vec<ModKind> = Vec::new();
+    for &kind in args {
+        if !kinds.contains(&kind.into()) {
+            kinds.push(kind.into());
+        }
+    }
+    kinds
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ModKindArg {
+    Explicit,
+    Implicit,
+    Enchant,
+    Rune,
+    Crafted,
+}
+
+impl From<ModKindArg> for ModKind {
+    fn from(arg: ModKindArg) -> Self {
+        match arg {
+            ModKindArg::Explicit => ModKind::Explicit,
+            ModKindArg::Implicit => ModKind::Implicit,
+            ModKindArg::Enchant => ModKind::Enchant,
+            ModKindArg::Rune => ModKind::Rune,
+            ModKindArg::Crafted => ModKind::Crafted,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct StackingArgs {
+    /// Attribute the build stacks
+    #[clap(value_enum)]
+    pub attribute: AttributeArg,
+
+    /// Collected data file produced by `collect`
+    #[clap(short, long, default_value = "collected_data.json")]
+    pub input: PathBuf,
+
+    /// Analyze every listing stored in the database instead of a collected data file
+    #[clap(long, conflicts_with = "input")]
+    pub from_db: bool,
+
+    /// Listings granting the attribute a modifier has to appear on to be reported
+    #[clap(long, default_value_t = DEFAULT_STACKING_MIN_LISTINGS)]
+    pub min_listings: usize,
+
+    /// Bases listed, highest attribute totals first
+    #[clap(long, default_value_t = DEFAULT_STACKING_BASES)]
+    pub top: usize,
+
+    /// Print the report as JSON
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct HeatmapArgs {
+    /// Modifier to put in a column, by text or template, e.g. "#% increased Movement Speed";
+    /// repeat for more. With several, a last column holds listings carrying all of them.
+    /// Without any, the most common modifiers are used.
+    #[clap(short, long = "modifier")]
+    pub modifiers: Vec<String>,
+
+    /// Only base types of this item class, e.g. boots or rings
+    #[clap(long, value_parser = parse_item_class)]
+    pub class: Option<ItemClass>,
+
+    /// Collected data file produced by `collect`
+    #[clap(short, long, default_value = "collected_data.json")]
+    pub input: PathBuf,
+
+    /// Analyze every listing stored in the database instead of a collected data file
+    #[clap(long, conflicts_with = "input")]
+    pub from_db: bool,
+
+    /// Most common modifiers used as columns when none are given
+    #[clap(long, default_value_t = DEFAULT_HEATMAP_MODIFIERS)]
+    pub top: usize,
+
+    /// Listings a cell needs before its average is shown
+    #[clap(long, default_value_t = DEFAULT_HEATMAP_MIN_LISTINGS)]
+    pub min_listings: usize,
+
+    /// csv for a plain matrix, html for a shaded table; json prints the report
+    #[clap(long, value_enum, default_value = "csv")]
+    pub format: ReportFormatArg,
+
+    /// Write the matrix to this file instead of stdout
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum AttributeArg {
+    #[value(alias = "str")]
+    Strength,
+    #[value(alias = "dex")]
+    Dexterity,
+    #[value(alias = "int")]
+    Intelligence,
+}
+
+impl From<AttributeArg> for CoreAttribute {
+    fn from(arg: AttributeArg) -> Self {
+        match arg {
+            AttributeArg::Strength => CoreAttribute::Strength,
+            AttributeArg::Dexterity => CoreAttribute::Dexterity,
+            AttributeArg::Intelligence => CoreAttribute::Intelligence,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormatArg {
+    Json,
+    Csv,
+    Html,
+}
+
+impl From<ReportFormatArg> for ReportFormat {
+    fn from(arg: ReportFormatArg) -> Self {
+        match arg {
+            ReportFormatArg::Json => ReportFormat::Json,
+            ReportFormatArg::Csv => ReportFormat::Csv,
+            ReportFormatArg::Html => ReportFormat::Html,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum PriceFeatureArg {
+    Presence,
+    Value,
+}
+
+impl From<PriceFeatureArg> for PriceFeature {
+    fn from(arg: PriceFeatureArg) -> Self {
+        match arg {
+            PriceFeatureArg::Presence => PriceFeature::Presence,
+            PriceFeatureArg::Value => PriceFeature::Value,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ExportFormat {
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    Csv,
+    /// Typed columns, only written as a --dataset
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum CompressionArg {
+    Gzip,
+    Zstd,
+}
+
+impl From<CompressionArg> for Compression {
+    fn from(arg: CompressionArg) -> Self {
+        match arg {
+            CompressionArg::Gzip => Compression::Gzip,
+            CompressionArg::Zstd => Compression::Zstd,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+#[clap(args_conflicts_with_subcommands = true)]
+pub struct ExportArgs {
+    #[clap(subcommand)]
+    pub target: Option<ExportTarget>,
+
+    /// File format of the export
+    #[clap(short, long, value_enum, default_value = "json")]
+    pub format: ExportFormat,
+
+    /// Write to this file instead of stdout, compressed if it ends in .gz or .zst
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Write the listing, modifier and listing-modifier tables into this directory,
+    /// partitioned by day, instead of one flat file. json is written as JSON lines.
+    /// parquet files are compressed inside, with gzip only.
+    #[clap(long, conflicts_with = "output")]
+    pub dataset: Option<PathBuf>,
+
+    /// Compress each file of a --dataset export
+    #[clap(long, value_enum, requires = "dataset")]
+    pub compress: Option<CompressionArg>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ExportTarget {
+    /// Write a loot filter highlighting base types, and affixes on them, whose price model
+    /// estimate reaches --min-value
+    Filter(FilterArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct FilterArgs {
+    /// Lowest expected value worth highlighting, e.g. 1ex or 0.5div; a bare number is in the
+    /// currency rates' base currency
+    #[clap(long, value_name = "AMOUNT", value_parser = parse_currency_amount)]
+    pub min_value: (f64, Option<Currency>),
+
+    /// Collected data file produced by `collect`
+    #[clap(short, long, default_value = "collected_data.json")]
+    pub input: PathBuf,
+
+    /// Use every listing stored in the database, as fetched, instead of a collected data file
+    #[clap(long, conflicts_with = "input")]
+    pub from_db: bool,
+
+    /// Write the filter to this file instead of stdout
+    #[clap(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Listings a base type needs before it gets rules
+    #[clap(long, default_value_t = DEFAULT_FILTER_MIN_LISTINGS)]
+    pub min_listings: usize,
+
+    /// Ridge penalty for each base's price model; 0 is plain least squares
+    #[clap(long, default_value = "1.0")]
+    pub ridge_lambda: f64,
+
+    /// Leave affixes seen on fewer of a base's listings than this out of its price model
+    #[clap(long, default_value = "5")]
+    pub min_occurrences: usize,
+}
+
+impl From<ExportFormat> for DatasetFormat {
+    fn from(format: ExportFormat) -> Self {
+        match format {
+            ExportFormat::Json | ExportFormat::Jsonl => DatasetFormat::Jsonl,
+            ExportFormat::Csv => DatasetFormat::Csv,
+            #[cfg(feature = "parquet")]
+            ExportFormat::Parquet => DatasetFormat::Parquet,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct PriceCheckArgs {
+    /// File holding the item as one listing from the trade API fetch endpoint (JSON), or
+    /// as copied in game with Ctrl+C
+    pub input: PathBuf,
+
+    /// Number of comparable listings to show
+    #[clap(short = 'c', long, default_value_t = DEFAULT_COMPARABLES)]
+    pub count: usize,
+
+    /// Count listings of the same base that were delisted within this many hours as sales
+    #[clap(long, default_value_t = DEFAULT_SALE_WINDOW_HOURS)]
+    pub sold_within_hours: f64,
+
+    /// Print the result as JSON instead of a table
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct LiveArgs {
+    /// ID of a saved trade search, as seen in the trade site URL
+    pub search_id: String,
+
+    #[clap(flatten)]
+    pub notify: NotifyArgs,
+}
+
+// Posting matching listings to a webhook
+#[derive(Args, Debug)]
+pub struct NotifyArgs {
+    /// Discord or other webhook to post matching listings to
+    #[clap(long)]
+    pub webhook: Option<String>,
+
+    /// Post a Discord message, or the listing as a JSON object for other receivers
+    #[clap(long, value_enum, default_value = "discord", requires = "webhook")]
+    pub webhook_format: WebhookFormatArg,
+
+    /// Modifier a listing must have, as text, template or stat hash, optionally with a least
+    /// value: "+# to maximum Life>=80". Repeat for several.
+    #[clap(long = "notify-mod", requires = "webhook")]
+    pub modifiers: Vec<ModCriterion>,
+
+    /// Most a listing may cost in exalted to be posted
+    #[clap(long, requires = "webhook")]
+    pub notify_max_price: Option<f64>,
+
+    /// Least seconds between two posts
+    #[clap(long, default_value = "2", requires = "webhook")]
+    pub notify_interval_secs: u64,
+}
+
+impl NotifyArgs {
+    pub fn criteria(&self) -> NotifyCriteria {
+        NotifyCriteria {
+            modifiers: self.modifiers.clone(),
+            max_price: self.notify_max_price,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WebhookFormatArg {
+    Discord,
+    Generic,
+}
+
+impl From<WebhookFormatArg> for WebhookFormat {
+    fn from(arg: WebhookFormatArg) -> Self {
+        match arg {
+            WebhookFormatArg::Discord => WebhookFormat::Discord,
+            WebhookFormatArg::Generic => WebhookFormat::Generic,
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+#[derive(Args, Debug)]
+pub struct TuiArgs {
+    #[clap(flatten)]
+    pub collect: CollectArgs,
+
+    /// Modifier that makes a listing worth showing, as text, template or stat hash,
+    /// optionally with a least value: "+# to maximum Life>=80". Repeat for several.
+    #[clap(long = "watch-mod")]
+    pub modifiers: Vec<ModCriterion>,
+
+    /// Most a listing may cost in exalted to be worth showing
+    #[clap(long)]
+    pub watch_max_price: Option<f64>,
+}
+
+#[cfg(feature = "tui")]
+impl TuiArgs {
+    pub fn criteria(&self) -> NotifyCriteria {
+        NotifyCriteria {
+            modifiers: self.modifiers.clone(),
+            max_price: self.watch_max_price,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Address to listen on
+    #[clap(long, default_value = DEFAULT_SERVE_ADDR)]
+    pub bind: std::net::SocketAddr,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum DbCommand {
+    /// Inspect or change the schema; applies pending migrations when no subcommand is given
+    Migrate(MigrateArgs),
+    /// List listings that were probably sold: delisted soon after they were first seen
+    Sales(SalesArgs),
+    /// Convert the stored raw listings again, refreshing their prices, stats and modifiers
+    Reprocess,
+    /// Delete listings that haven't been seen for a while
+    Prune(PruneArgs),
+    /// Load listings from a dataset written by `export --dataset`, or one of its JSON lines files
+    Import(ImportArgs),
+    /// Rebuild the daily per-modifier price buckets that `trends show --by-value` reads
+    RefreshAggregates(RefreshAggregatesArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct RefreshAggregatesArgs {
+    /// How many even value ranges to split each modifier's rolls into
+    #[clap(long, default_value_t = DEFAULT_VALUE_BUCKETS)]
+    pub value_buckets: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct MigrateArgs {
+    #[clap(subcommand)]
+    pub command: Option<MigrateCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MigrateCommand {
+    /// List every migration as applied, pending, or changed since it was applied
+    Status,
+    /// Create the database if needed and apply pending migrations
+    Run {
+        /// Only print the migrations that would be applied
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Undo applied migrations with their down scripts
+    Revert {
+        /// Revert every migration newer than this version; 0 reverts them all. Defaults to
+        /// reverting only the newest one.
+        #[clap(long)]
+        target: Option<i64>,
+    },
+    /// Revert every migration and apply them again, deleting all stored data
+    Fresh {
+        /// Confirm that the stored data may be deleted
+        #[clap(long)]
+        force: bool,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// A JSON lines or Parquet file, or a directory written by export --dataset
+    pub path: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct SalesArgs {
+    /// Longest a listing can have been up and still count as sold
+    #[clap(long, default_value_t = DEFAULT_SALE_WINDOW_HOURS)]
+    pub within_hours: f64,
+
+    /// Only listings of this base type
+    #[clap(long)]
+    pub base_type: Option<String>,
+
+    /// Print the sales as JSON instead of a table
+    #[clap(long)]
+    pub json: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct PruneArgs {
+    /// Delete listings last seen longer ago than this, e.g. 30d or 12h
+    #[clap(long, value_parser = parse_age)]
+    pub older_than: Duration,
+
+    /// Roll the deleted listings up into daily per-modifier price history first
+    #[clap(long)]
+    pub keep_aggregates: bool,
+
+    /// Leave the freed space in the database file instead of vacuuming
+    #[clap(long)]
+    pub no_vacuum: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum BasesCommand {
+    /// Download the base item list and store it in the cache file and database
+    Update,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AffixesCommand {
+    /// Learn which affixes spawn on each item class from collected listings and add them
+    /// to the affix database
+    Build(AffixBuildArgs),
+    /// Print the affixes that can spawn on a base at an item level
+    Show(AffixShowArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct AffixBuildArgs {
+    /// Collected data file produced by `collect`. Stored listings don't keep affix names
+    /// and tiers, so the database can't be built from them.
+    #[clap(short, long, default_value = "collected_data.json")]
+    pub input: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub struct AffixShowArgs {
+    /// Base type, e.g. "Gold Ring"
+    pub base: String,
+
+    /// Item level of the item
+    #[clap(long, default_value_t = 100)]
+    pub ilvl: u32,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CurrencyCommand {
+    /// Refresh the exchange rates used to normalize prices from the league's currency exchange
+    Update,
+    /// Count listings per currency and how many of them the rates on disk can normalize
+    Coverage(CoverageArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct CoverageArgs {
+    /// Collected data file produced by `collect`
+    #[clap(short, long, default_value = "collected_data.json")]
+    pub input: PathBuf,
+
+    /// Read the listings stored in the database instead of a collected data file
+    #[clap(long)]
+    pub from_db: bool,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Write a commented scraper.toml template
+    Init {
+        /// Where to write the template
+        #[clap(default_value = DEFAULT_CONFIG_PATH)]
+        path: PathBuf,
+
+        /// Overwrite an existing file
+        #[clap(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum TrendsCommand {
+    /// Add a collected data file's prices to the daily price history
+    Record(TrendRecordArgs),
+    /// Print the price history of a modifier or base type with its day and week changes
+    Show(TrendShowArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct TrendRecordArgs {
+    /// Collected data file produced by `collect`
+    #[clap(short, long, default_value = "collected_data.json")]
+    pub input: PathBuf,
+
+    /// Day to record the prices under, as YYYY-MM-DD [default: today, UTC]
+    #[clap(long, value_parser = parse_day_arg)]
+    pub day: Option<String>,
+
+    /// Kinds of mods to track, e.g. explicit,implicit,rune
+    #[clap(long, value_enum, value_delimiter = ',', default_value = "explicit")]
+    pub mod_kinds: Vec<ModKindArg>,
+}
+
+impl TrendRecordArgs {
+    pub fn mod_kinds(&self) -> Vec<ModKind> {
+        unique_mod_kinds(&self.mod_kinds)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct TrendShowArgs {
+    /// Modifier name as it appears in the analyze report
+    #[clap(long, conflicts_with_all = ["base", "category"], required_unless_present_any = ["base", "category"])]
+    pub modifier: Option<String>,
+
+    /// Base type name, e.g. "Advanced Maraketh Cuirass"
+    #[clap(long, conflicts_with = "category")]
+    pub base: Option<String>,
+
+    /// Item class, e.g. boots or "Two Hand Maces"
+    #[clap(long, value_parser = parse_item_class)]
+    pub category: Option<ItemClass>,
+
+    /// Show the modifier's prices per value range from the aggregates built by
+    /// `db refresh-aggregates` instead of its recorded history
+    #[clap(long, requires = "modifier")]
+    pub by_value: bool,
+}
+
+impl TrendShowArgs {
+    pub fn subject(&self) -> (TrendSubject, &str) {
+        match (&self.modifier, &self.base) {
+            (Some(modifier), _) => (TrendSubject::Modifier, modifier),
+            (None, Some(base)) => (TrendSubject::Base, base),
+            (None, None) => match self.category {
+                Some(class) => (TrendSubject::Category, class.as_str()),
+                None => unreachable!("clap requires --modifier, --base or --category"),
+            },
+        }
+    }
+}
+
+fn parse_day_arg(value: &str) -> std::result::Result<String, String> {
+    // Zero-padded so stored days sort in date order
+    parse_day(value)
+        .filter(|_| value.len() == "YYYY-MM-DD".len())
+        .map(|_| value.to_string())
+        .ok_or_else(|| format!("expected a YYYY-MM-DD date, got '{}'", value))
+}
+
+fn parse_age(value: &str) -> std::result::Result<Duration, String> {
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount: u64 = amount.parse().map_err(|_| format!("expected an age like 30d or 12h, got '{}'", value))?;
+    let unit_secs = match unit {
+        "d" => 24 * 60 * 60,
+        "h" => 60 * 60,
+        "m" => 60,
+        _ => return Err(format!("unknown unit '{}' in '{}'; use d, h or m", unit, value)),
+    };
+    Ok(Duration::from_secs(amount * unit_secs))
+}
+
+fn parse_item_class(value: &str) -> std::result::Result<ItemClass, String> {
+    ItemClass::from_name(value).ok_or_else(|| format!("unknown item class '{}'", value))
+}
+
+fn parse_currency_amount(value: &str) -> std::result::Result<(f64, Option<Currency>), String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '/'))
+        .unwrap_or(value.len());
+    let (amount, currency) = value.split_at(split);
+    let amount = parse_price_amount(amount)
+        .ok_or_else(|| format!("expected an amount like 1ex, 0.5div or 1/2div, got '{}'", value))?;
+    let currency = currency.trim();
+    if currency.is_empty() {
+        return Ok((amount, None));
+    }
+    let currency = Currency::from_name(currency);
+    if !currency.is_known() {
+        return Err(format!("unknown currency '{}' in '{}'", currency, value));
+    }
+    Ok((amount, Some(currency)))
+}
+
+fn parse_stat_range(value: &str) -> std::result::Result<(u32, u32), String> {
+    let (min, max) = value
+        .split_once('-')
+        .ok_or_else(|| format!("expected MIN-MAX, got '{}'", value))?;
+    let min: u32 = min.trim().parse().map_err(|e| format!("invalid minimum: {}", e))?;
+    let max: u32 = max.trim().parse().map_err(|e| format!("invalid maximum: {}", e))?;
+    if min > max {
+        return Err(format!("minimum {} is above maximum {}", min, max));
+    }
+    Ok((min, max))
+}
+
+fn parse_price_band(value: &str) -> std::result::Result<PriceBand, String> {
+    let (min, max) = value
+        .split_once('-')
+        .ok_or_else(|| format!("expected MIN-MAX, got '{}'", value))?;
+    let bound = |bound: &str| -> std::result::Result<Option<f64>, String> {
+        let bound = bound.trim();
+        if bound.is_empty() {
+            return Ok(None);
+        }
+        bound.parse().map(Some).map_err(|e| format!("invalid price '{}': {}", bound, e))
+    };
+    let band = PriceBand { min: bound(min)?, max: bound(max)? };
+    if let (Some(min), Some(max)) = (band.min, band.max) {
+        if min > max {
+            return Err(format!("minimum {} is above maximum {}", min, max));
+        }
+    }
+    Ok(band)
+}
+
+fn parse_sweep_limit(value: &str) -> std::result::Result<(String, usize), String> {
+    let (category, pages) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected CATEGORY=PAGES, got '{}'", value))?;
+    let category = category.trim();
+    if !SWEEP_CATEGORIES.contains(&category) {
+        return Err(format!("unknown category '{}'; expected one of {}", category, SWEEP_CATEGORIES.join(", ")));
+    }
+    let pages = pages.trim().parse().map_err(|e| format!("invalid page count: {}", e))?;
+    Ok((category.to_string(), pages))
+}
+
+impl Command {
+    // Whether the command searches a league, so --league is worth validating first
+    pub fn uses_league(&self) -> bool {
+        #[cfg(feature = "tui")]
+        if matches!(self, Command::Tui(_)) {
+            return true;
+        }
+        matches!(
+            self,
+            Command::Collect(_) | Command::Search(_) | Command::Live(_) | Command::Currency(_)
+        )
+    }
+
+    // Whether the command can collect from several leagues at once
+    pub fn takes_several_leagues(&self) -> bool {
+        #[cfg(feature = "tui")]
+        if matches!(self, Command::Tui(_)) {
+            return true;
+        }
+        matches!(self, Command::Collect(_))
+    }
+}
+
+impl Cli {
+    // Fill in everything not given on the command line from the config file
+    pub fn apply_config(&mut self, config: ScraperConfig) {
+        let api = &mut self.api;
+        if api.leagues.is_empty() {
+            api.leagues.extend(config.league);
+        }
+        api.max_retries = api.max_retries.or(config.api.max_retries);
+        api.timeout_secs = api.timeout_secs.or(config.api.timeout_secs);
+        api.fetch_concurrency = api.fetch_concurrency.or(config.api.fetch_concurrency);
+        api.proxy = api.proxy.take().or(config.api.proxy);
+        api.user_agent = api.user_agent.take().or(config.api.user_agent);
+        let database = &mut self.database;
+        database.database_url = database.database_url.take().or(config.database_url);
+        database.db_pool_size = database.db_pool_size.or(config.database.pool_size);
+        database.db_busy_timeout_ms = database.db_busy_timeout_ms.or(config.database.busy_timeout_ms);
+        database.db_journal_mode = database.db_journal_mode.or(config.database.journal_mode.map(Into::into));
+        database.db_synchronous = database.db_synchronous.or(config.database.synchronous.map(Into::into));
+
+        let price = match &mut self.command {
+            Command::Collect(args) => args.apply_collect_config(config.collect),
+            #[cfg(feature = "tui")]
+            Command::Tui(args) => args.collect.apply_collect_config(config.collect),
+            Command::Search(args) => &mut args.price,
+            _ => return,
+        };
+        price.min_price = price.min_price.or(config.price.min);
+        price.max_price = price.max_price.or(config.price.max);
+        price.price_currency = price.price_currency.take().or(config.price.currency);
+    }
+}
+
+impl CollectArgs {
+    // Use the config file's stat ranges and sweep limits unless some were given, returning
+    // the price options for the rest of the config to fill in
+    fn apply_collect_config(&mut self, config: CollectConfig) -> &mut PriceArgs {
+        if self.stat_ranges.is_empty() {
+            self.stat_ranges = config.stat_ranges.unwrap_or_default();
+        }
+        self.sweep_pages = self.sweep_pages.or(config.sweep_pages);
+        self.config_plan = config.plan.map(Box::new);
+        for (category, pages) in config.sweep_limits {
+            if !self.sweep_limits.iter().any(|(given, _)| *given == category) {
+                self.sweep_limits.push((category, pages));
+            }
+        }
+        &mut self.price
+    }
+
+    // Page limits for --sweep, or None when collecting attribute ranges
+    pub fn sweep(&self) -> Option<SweepLimits> {
+        self.sweep.then(|| SweepLimits {
+            default_pages: self.sweep_pages.unwrap_or(DEFAULT_SWEEP_PAGES),
+            category_pages: self.sweep_limits.iter().cloned().collect(),
+        })
+    }
+}
+
+impl DatabaseArgs {
+    pub fn url(&self) -> String {
+        self.database_url.clone().unwrap_or_else(Database::default_url)
+    }
+
+    pub fn pool_settings(&self) -> PoolSettings {
+        let defaults = PoolSettings::default();
+        PoolSettings {
+            max_connections: self.db_pool_size.unwrap_or(defaults.max_connections),
+            busy_timeout: self.db_busy_timeout_ms.map_or(defaults.busy_timeout, Duration::from_millis),
+            journal_mode: self.db_journal_mode.map_or(defaults.journal_mode, Into::into),
+            synchronous: self.db_synchronous.map_or(defaults.synchronous, Into::into),
+        }
+    }
+}
+
+impl ApiArgs {
+    // The first league given; commands other than collect only search one
+    pub fn league(&self) -> &str {
+        self.leagues.first().map_or(DEFAULT_LEAGUE, String::as_str)
+    }
+
+    pub fn leagues(&self) -> Vec<String> {
+        if self.leagues.is_empty() {
+            vec![DEFAULT_LEAGUE.to_string()]
+        } else {
+            self.leagues.clone()
+        }
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES)
+    }
+
+    pub fn timeout_secs(&self) -> u64 {
+        self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS)
+    }
+
+    pub fn fetch_concurrency(&self) -> usize {
+        self.fetch_concurrency.unwrap_or(DEFAULT_FETCH_CONCURRENCY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Clap only reads doc comments, so a plain // comment leaves the help blank
+    fn undocumented(command: &clap::Command, path: &str, missing: &mut Vec<String>) {
+        for arg in command.get_arguments().filter(|arg| !["help", "version"].contains(&arg.get_id().as_str())) {
+            if arg.get_help().is_none() {
+                missing.push(format!("{} {}", path, arg.get_id()));
+            }
+        }
+        for subcommand in command.get_subcommands() {
+            let path = format!("{} {}", path, subcommand.get_name());
+            if subcommand.get_about().is_none() {
+                missing.push(path.clone());
+            }
+            undocumented(subcommand, &path, missing);
+        }
+    }
+
+    #[test]
+    fn test_every_option_has_help() {
+        use clap::CommandFactory;
+
+        let command = Cli::command();
+        let mut missing = Vec::new();
+        undocumented(&command, "rust-scraper", &mut missing);
+        assert!(missing.is_empty(), "no help text for {:?}", missing);
+        assert!(command.get_after_help().unwrap().to_string().contains("config file"));
+    }
+
+    #[test]
+    fn test_cli_overrides_config() {
+        let config = ScraperConfig::parse(r#"
+            league = "Dawn of the Hunt"
+            [price]
+            min = 1.0
+            max = 20.0
+            [collect]
+            stat_ranges = [[0, 100]]
+            [api]
+            max_retries = 8
+            [database]
+            pool_size = 2
+            journal_mode = "delete"
+        "#).unwrap();
+
+        let mut cli = Cli::parse_from([
+            "rust-scraper", "--league", "Standard", "collect", "-x", "5", "--stat-range", "10-20",
+            "--db-journal-mode", "truncate",
+        ]);
+        cli.apply_config(config);
+
+        assert_eq!(cli.api.league(), "Standard");
+        assert_eq!(cli.api.max_retries(), 8);
+        assert_eq!(cli.api.timeout_secs(), DEFAULT_TIMEOUT_SECS);
+        let Command::Collect(args) = &cli.command else { panic!("expected collect") };
+        assert_eq!(args.price.min_price, Some(1.0));
+        assert_eq!(args.price.max_price, Some(5.0));
+        assert_eq!(args.stat_ranges, vec![(10, 20)]);
+        let pool = cli.database.pool_settings();
+        assert_eq!(pool.max_connections, 2);
+        assert_eq!(pool.journal_mode, JournalMode::Truncate);
+        assert_eq!(pool.synchronous, Synchronous::Normal);
+    }
+
+    #[test]
+    fn test_repeated_league() {
+        let mut cli = Cli::parse_from([
+            "rust-scraper", "collect", "--league", "Standard", "--league", "Dawn of the Hunt",
+        ]);
+        cli.apply_config(ScraperConfig::parse(r#"league = "Hardcore""#).unwrap());
+
+        assert_eq!(cli.api.league(), "Standard");
+        assert_eq!(cli.api.leagues(), vec!["Standard", "Dawn of the Hunt"]);
+        assert!(Cli::try_parse_from(["rust-scraper", "collect", "--league", "Standard", "--all-leagues"]).is_err());
+    }
+
+    #[test]
+    fn test_sweep_limits() {
+        let mut cli = Cli::parse_from([
+            "rust-scraper", "collect", "--sweep", "--sweep-limit", "jewel=20", "--sweep-limit", "flask.life=1",
+        ]);
+        cli.apply_config(ScraperConfig::parse("[collect]\nsweep_pages = 3\n[collect.sweep_limits]\njewel = 8\n\"accessory.ring\" = 4").unwrap());
+
+        let Command::Collect(args) = &cli.command else { panic!("expected collect") };
+        let limits = args.sweep().unwrap();
+        assert_eq!(limits.pages("jewel"), 20);
+        assert_eq!(limits.pages("flask.life"), 1);
+        assert_eq!(limits.pages("accessory.ring"), 4);
+        assert_eq!(limits.max_results("weapon.bow"), 30);
+        assert!(parse_sweep_limit("jewels=5").is_err());
+    }
+
+    #[test]
+    fn test_parse_price_band() {
+        assert_eq!(parse_price_band("5-50"), Ok(PriceBand { min: Some(5.0), max: Some(50.0) }));
+        assert_eq!(parse_price_band("50-"), Ok(PriceBand { min: Some(50.0), max: None }));
+        assert_eq!(parse_price_band("-0.5"), Ok(PriceBand { min: None, max: Some(0.5) }));
+        assert!(parse_price_band("50-5").is_err());
+        assert!(parse_price_band("5").is_err());
+    }
+
+    #[test]
+    fn test_parse_stat_range() {
+        assert_eq!(parse_stat_range("51-100"), Ok((51, 100)));
+        assert!(parse_stat_range("100-51").is_err());
+        assert!(parse_stat_range("100").is_err());
+    }
+
+    #[test]
+    fn test_export_filter_min_value() {
+        assert_eq!(parse_currency_amount("1ex"), Ok((1.0, Some(Currency::Exalted))));
+        assert_eq!(parse_currency_amount("0.5 div"), Ok((0.5, Some(Currency::Divine))));
+        assert_eq!(parse_currency_amount("20"), Ok((20.0, None)));
+        assert_eq!(parse_currency_amount("1/4div"), Ok((0.25, Some(Currency::Divine))));
+        assert!(parse_currency_amount("ex").is_err());
+        assert!(parse_currency_amount("1foo").is_err());
+
+        let cli = Cli::parse_from(["rust-scraper", "export", "filter", "--min-value", "1ex"]);
+        let Command::Export(ExportArgs { target: Some(ExportTarget::Filter(args)), .. }) = &cli.command else {
+            panic!("expected export filter")
+        };
+        assert_eq!(args.min_value, (1.0, Some(Currency::Exalted)));
+        assert!(Cli::try_parse_from(["rust-scraper", "export", "--format", "csv", "filter", "--min-value", "1"]).is_err());
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30d"), Ok(Duration::from_secs(30 * 24 * 60 * 60)));
+        assert_eq!(parse_age("12h"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert!(parse_age("30").is_err());
+        assert!(parse_age("d").is_err());
+    }
+}
@@ -0,0 +1,227 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use crate::errors::{Result, ScraperError};
+use crate::analyzer::{CollectionPlan, SWEEP_CATEGORIES};
+use crate::models::Currency;
+use crate::storage::{JournalMode, Synchronous};
+
+pub const DEFAULT_CONFIG_PATH: &str = "scraper.toml";
+
+// Settings read from scraper.toml. Everything is optional; command line flags win over
+// the file, and the file wins over built-in defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScraperConfig {
+    pub league: Option<String>,
+    pub database_url: Option<String>,
+    pub price: PriceConfig,
+    pub collect: CollectConfig,
+    pub api: ApiConfig,
+    pub database: DatabaseConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PriceConfig {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub currency: Option<Currency>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CollectConfig {
+    // Attribute requirement ranges queried by `collect`, as [min, max] pairs
+    pub stat_ranges: Option<Vec<(u32, u32)>>,
+    // Result pages fetched per category by `collect --sweep`
+    pub sweep_pages: Option<usize>,
+    // Page limits for single categories, overriding sweep_pages
+    pub sweep_limits: HashMap<String, usize>,
+    // What to search instead of the attribute ranges on armour, as `collect --plan` reads it
+    pub plan: Option<CollectionPlan>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    pub max_retries: Option<u32>,
+    pub fetch_concurrency: Option<usize>,
+    pub timeout_secs: Option<u64>,
+    pub proxy: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub pool_size: Option<u32>,
+    pub busy_timeout_ms: Option<u64>,
+    pub journal_mode: Option<JournalMode>,
+    pub synchronous: Option<Synchronous>,
+}
+
+pub const CONFIG_TEMPLATE: &str = r#"# rust-poe2-scraper configuration
+# Every setting is optional. Command line flags override anything set here.
+
+# Trade league to search
+# league = "Standard"
+
+# Where collected items are stored; postgres:// URLs need the postgres feature
+# database_url = "sqlite:poe_items.db"
+
+[price]
+# Only fetch listings priced within these bounds
+# min = 1.0
+# max = 50.0
+# currency = "exalted"
+
+[collect]
+# Attribute requirement ranges queried per attribute, as [min, max] pairs
+# stat_ranges = [[0, 50], [51, 100], [101, 150], [151, 200]]
+# Result pages of 10 listings fetched per category by `collect --sweep`
+# sweep_pages = 5
+
+[collect.sweep_limits]
+# Pages for single trade categories, overriding sweep_pages
+# "jewel" = 20
+# "flask.life" = 2
+
+# A collection plan replaces the attribute ranges on armour: every combination of
+# category, attribute range, price band and sort order is searched
+# [collect.plan]
+# categories = ["armour.boots", "accessory.ring"]
+# attributes = ["Dexterity"]
+# attribute_ranges = [[0, 100], [101, 200]]
+# price_bands = [{ max = 5.0 }, { min = 5.0, max = 50.0 }]
+# sorts = ["price_asc", "indexed_desc"]
+# max_results = 50
+
+[api]
+# max_retries = 5
+# fetch_concurrency = 2
+# timeout_secs = 30
+# proxy = "socks5://127.0.0.1:1080"
+# user_agent = "my-tool/1.0 (contact: me@example.com)"
+
+[database]
+# Connections kept open to the database
+# pool_size = 5
+# SQLite only: how long a write waits for the lock held by another process, e.g. a
+# collector and an analyzer sharing the file
+# busy_timeout_ms = 10000
+# SQLite only: "wal" lets readers and a writer work at the same time; also "delete", "truncate"
+# journal_mode = "wal"
+# SQLite only: "off", "normal" or "full"
+# synchronous = "normal"
+"#;
+
+impl ScraperConfig {
+    pub fn parse(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content)
+            .map_err(|e| ScraperError::ValidationError(format!("Invalid config file: {}", e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let Some(ranges) = &self.collect.stat_ranges {
+            if let Some((min, max)) = ranges.iter().find(|(min, max)| min > max) {
+                return Err(ScraperError::ValidationError(format!(
+                    "Invalid config file: stat range [{}, {}] has min above max",
+                    min, max
+                )));
+            }
+        }
+        if let Some(plan) = &self.collect.plan {
+            plan.validate()?;
+        }
+        if let Some(category) = self.collect.sweep_limits.keys().find(|category| !SWEEP_CATEGORIES.contains(&category.as_str())) {
+            return Err(ScraperError::ValidationError(format!(
+                "Invalid config file: '{}' is not a sweep category",
+                category
+            )));
+        }
+        Ok(())
+    }
+
+    // Load the config at `path`. A missing file is only an error when it was asked for explicitly.
+    pub async fn load(path: &Path, required: bool) -> Result<Self> {
+        match tokio::fs::read_to_string(path).await {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Write the commented template, refusing to clobber an existing file unless forced
+    pub async fn write_template(path: &Path, force: bool) -> Result<()> {
+        if !force && tokio::fs::try_exists(path).await? {
+            return Err(ScraperError::ValidationError(format!(
+                "{} already exists; pass --force to overwrite it",
+                path.display()
+            )));
+        }
+
+        tokio::fs::write(path, CONFIG_TEMPLATE).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_parses_to_defaults() {
+        let config = ScraperConfig::parse(CONFIG_TEMPLATE).unwrap();
+        assert!(config.league.is_none());
+        assert!(config.collect.stat_ranges.is_none());
+        assert!(config.api.max_retries.is_none());
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let config = ScraperConfig::parse(r#"
+            league = "Dawn of the Hunt"
+
+            [price]
+            max = 20.0
+            currency = "divine"
+
+            [collect]
+            stat_ranges = [[0, 100], [101, 250]]
+            sweep_pages = 2
+
+            [collect.sweep_limits]
+            jewel = 20
+
+            [collect.plan]
+            categories = ["jewel"]
+            price_bands = [{ max = 5.0 }, { min = 5.0 }]
+
+            [api]
+            fetch_concurrency = 3
+
+            [database]
+            journal_mode = "delete"
+        "#).unwrap();
+
+        assert_eq!(config.league.as_deref(), Some("Dawn of the Hunt"));
+        assert_eq!(config.price.max, Some(20.0));
+        assert_eq!(config.price.currency, Some(Currency::Divine));
+        assert_eq!(config.collect.stat_ranges, Some(vec![(0, 100), (101, 250)]));
+        assert_eq!(config.collect.sweep_pages, Some(2));
+        assert_eq!(config.collect.sweep_limits.get("jewel"), Some(&20));
+        let plan = config.collect.plan.unwrap();
+        assert_eq!(plan.categories, vec!["jewel"]);
+        assert_eq!(plan.price_bands.len(), 2);
+        assert_eq!(plan.attribute_ranges, CollectionPlan::default().attribute_ranges);
+        assert_eq!(config.api.fetch_concurrency, Some(3));
+        assert_eq!(config.database.journal_mode, Some(JournalMode::Delete));
+
+        assert!(ScraperConfig::parse("leage = \"typo\"").is_err());
+        assert!(ScraperConfig::parse("[collect]\nstat_ranges = [[100, 50]]").is_err());
+        assert!(ScraperConfig::parse("[collect.sweep_limits]\njewels = 5").is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use crate::errors::{Result, ResultExt};
+use crate::models::{CleanedItem, ItemBaseType, ItemCategory, ItemRarity};
+use crate::models::cleaned_item::ExplicitMod;
+
+// Seed and output file of the affix database, written by `affixes build`
+pub const AFFIX_DATA_PATH: &str = "data/affixes.json";
+
+// Affixes of each slot a rare can have; magic items get one of each
+const RARE_AFFIXES_PER_SLOT: usize = 3;
+const MAGIC_AFFIXES_PER_SLOT: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AffixSlot {
+    Prefix,
+    Suffix,
+}
+
+impl AffixSlot {
+    // The trade API tiers explicit mods as "P1", "S3" and so on; other tiers, such as the
+    // "R" of rune mods, aren't affixes
+    pub fn from_tier(tier: &str) -> Option<Self> {
+        match tier.chars().next()? {
+            'P' => Some(AffixSlot::Prefix),
+            'S' => Some(AffixSlot::Suffix),
+            _ => None,
+        }
+    }
+}
+
+// One tier of an affix that can spawn on an item class
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Affix {
+    // Affix name, e.g. "Sturdy"
+    pub name: String,
+    pub tier: String,
+    pub slot: AffixSlot,
+    // Trade stat hashes of the affix, sorted and joined with '|'
+    pub stat: String,
+    // Lowest item level the tier spawns at
+    pub level: u32,
+    // Range of the first value, as far as it is known
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Affix {
+    fn from_mod(slot: AffixSlot, modifier: &ExplicitMod) -> Self {
+        let mut hashes: Vec<&str> = modifier.magnitudes.iter().map(|m| m.hash.as_str()).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        let value = modifier.magnitudes.first().and_then(|m| m.min.parse().ok()).unwrap_or_default();
+        Self {
+            name: modifier.name.clone(),
+            tier: modifier.tier.clone(),
+            slot,
+            stat: hashes.join("|"),
+            level: modifier.level,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn same_tier(&self, other: &Affix) -> bool {
+        self.name == other.name && self.tier == other.tier
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum AffixIssue {
+    // The affix isn't known to spawn on the item's class
+    NotInPool { affix: String, tier: String },
+    // The tier needs a higher item level than the item has
+    AboveItemLevel { affix: String, tier: String, level: u32 },
+    // More prefixes or suffixes than the item's rarity allows
+    TooManyAffixes { slot: AffixSlot, count: usize, allowed: usize },
+    // Added by crafting rather than rolled
+    Crafted { modifier: String },
+}
+
+// Which affix tiers can spawn on each item class
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AffixDatabase {
+    pools: HashMap<ItemCategory, Vec<Affix>>,
+}
+
+impl AffixDatabase {
+    pub async fn load_from_file(path: &str) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path).await.with_context(|| format!("reading {}", path))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub async fn save_to_file(&self, path: &str) -> Result<()> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        // Sorted, so rebuilding the file only shows what changed
+        let pools: BTreeMap<String, Vec<&Affix>> = self.pools
+            .iter()
+            .map(|(category, affixes)| {
+                let mut affixes: Vec<&Affix> = affixes.iter().collect();
+                affixes.sort_by(|a, b| (&a.name, a.level, &a.tier).cmp(&(&b.name, b.level, &b.tier)));
+                (category.to_string(), affixes)
+            })
+            .collect();
+        tokio::fs::write(path, serde_json::to_string_pretty(&serde_json::json!({ "pools": pools }))?).await?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pools.values().all(Vec::is_empty)
+    }
+
+    pub fn affixes(&self, category: ItemCategory) -> &[Affix] {
+        self.pools.get(&category).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // Affix tiers that can spawn on the base at the item level
+    pub fn possible_mods(&self, base: &ItemBaseType, ilvl: u32) -> Vec<&Affix> {
+        self.affixes(base.category)
+            .iter()
+            .filter(|affix| affix.level <= ilvl)
+            .collect()
+    }
+
+    // Add an affix tier, widening the level and value range of one already known
+    pub fn add(&mut self, category: ItemCategory, affix: Affix) {
+        let pool = self.pools.entry(category).or_default();
+        match pool.iter_mut().find(|known| known.same_tier(&affix)) {
+            Some(known) => {
+                known.level = known.level.min(affix.level);
+                known.min = known.min.min(affix.min);
+                known.max = known.max.max(affix.max);
+            }
+            None => pool.push(affix),
+        }
+    }
+
+    pub fn merge(&mut self, other: AffixDatabase) {
+        for (category, affixes) in other.pools {
+            for affix in affixes {
+                self.add(category, affix);
+            }
+        }
+    }
+
+    // Learn the affixes rolled on a collected item of the class. Uniques are skipped,
+    // their mods don't come from the pool.
+    pub fn observe(&mut self, category: ItemCategory, item: &CleanedItem) {
+        if item.rarity == ItemRarity::Unique {
+            return;
+        }
+        for modifier in &item.mod_info.explicit {
+            if let Some(slot) = AffixSlot::from_tier(&modifier.tier) {
+                self.add(category, Affix::from_mod(slot, modifier));
+            }
+        }
+    }
+
+    // Affixes and crafted mods an item of the class can't have rolled. Pool checks are
+    // skipped for classes the database knows nothing about.
+    pub fn check(&self, category: ItemCategory, item: &CleanedItem) -> Vec<AffixIssue> {
+        let mut issues = Vec::new();
+        if item.rarity == ItemRarity::Unique {
+            return issues;
+        }
+
+        let pool = self.affixes(category);
+        let mut counts: HashMap<AffixSlot, usize> = HashMap::new();
+        for modifier in &item.mod_info.explicit {
+            let Some(slot) = AffixSlot::from_tier(&modifier.tier) else {
+                continue;
+            };
+            *counts.entry(slot).or_default() += 1;
+            if pool.is_empty() {
+                continue;
+            }
+            match pool.iter().find(|affix| affix.name == modifier.name && affix.tier == modifier.tier) {
+                None => issues.push(AffixIssue::NotInPool {
+                    affix: modifier.name.clone(),
+                    tier: modifier.tier.clone(),
+                }),
+                Some(affix) if affix.level > item.item_level => issues.push(AffixIssue::AboveItemLevel {
+                    affix: affix.name.clone(),
+                    tier: affix.tier.clone(),
+                    level: affix.level,
+                }),
+                Some(_) => {}
+            }
+        }
+
+        let allowed = match item.rarity {
+            ItemRarity::Magic => MAGIC_AFFIXES_PER_SLOT,
+            _ => RARE_AFFIXES_PER_SLOT,
+        };
+        for slot in [AffixSlot::Prefix, AffixSlot::Suffix] {
+            let count = counts.get(&slot).copied().unwrap_or_default();
+            if count > allowed {
+                issues.push(AffixIssue::TooManyAffixes { slot, count, allowed });
+            }
+        }
+
+        issues.extend(item.mod_info.crafted.iter().map(|modifier| AffixIssue::Crafted {
+            modifier: modifier.magnitudes.first().map(|m| m.hash.clone()).unwrap_or_else(|| modifier.name.clone()),
+        }));
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::cleaned_item::ModInfo;
+
+    fn rare(item_level: u32, mods: &[(&str, &str, u32)]) -> CleanedItem {
+        let explicit = mods
+            .iter()
+            .map(|(name, tier, level)| {
+                serde_json::from_value(serde_json::json!({
+                    "name": name,
+                    "tier": tier,
+                    "level": level,
+                    "magnitudes": [{ "hash": "explicit.stat_1", "min": "10", "max": "10" }]
+                }))
+                .unwrap()
+            })
+            .collect();
+        CleanedItem {
+            id: String::new(),
+            price: None,
+            base_type: "Gold Ring".to_string(),
+            name: "Doom Loop".to_string(),
+            rarity: ItemRarity::Rare,
+            explicit_mods: Vec::new(),
+            item_level,
+            quality: None,
+            corrupted: false,
+            mirrored: false,
+            properties: Vec::new(),
+            requirements: Vec::new(),
+            mod_info: ModInfo { explicit, ..Default::default() },
+            mod_hashes: HashMap::new(),
+            gem: None,
+            jewel: None,
+            waystone: None,
+            sockets: None,
+        }
+    }
+
+    #[test]
+    fn test_pool_by_item_level_and_impossible_affixes() {
+        let mut database = AffixDatabase::default();
+        database.observe(ItemCategory::Accessory, &rare(82, &[("Sturdy", "P1", 75), ("of the Bear", "S2", 30)]));
+        database.observe(ItemCategory::Accessory, &rare(40, &[("of the Bear", "S2", 30)]));
+
+        let ring = ItemBaseType::new("Gold Ring".to_string(), ItemCategory::Accessory);
+        let names = |ilvl| -> Vec<String> {
+            database.possible_mods(&ring, ilvl).iter().map(|affix| affix.name.clone()).collect()
+        };
+        assert_eq!(names(50), vec!["of the Bear"]);
+        assert_eq!(names(80).len(), 2);
+
+        let item = rare(60, &[
+            ("Sturdy", "P1", 75),
+            ("Glinting", "P3", 10),
+            ("of the Bear", "S2", 30),
+            ("of the Bear", "S2", 30),
+            ("of the Bear", "S2", 30),
+            ("of the Bear", "S2", 30),
+        ]);
+        assert_eq!(database.check(ItemCategory::Accessory, &item), vec![
+            AffixIssue::AboveItemLevel { affix: "Sturdy".to_string(), tier: "P1".to_string(), level: 75 },
+            AffixIssue::NotInPool { affix: "Glinting".to_string(), tier: "P3".to_string() },
+            AffixIssue::TooManyAffixes { slot: AffixSlot::Suffix, count: 4, allowed: 3 },
+        ]);
+        assert!(database.check(ItemCategory::Weapon, &item).iter().all(|issue| matches!(issue, AffixIssue::TooManyAffixes { .. })));
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+use crate::errors::{Result, ResultExt};
+use crate::fetcher::TradeApiClient;
+use crate::models::{Currency, ItemPrice};
+
+pub const DEFAULT_RATES_PATH: &str = "data/currency_rates.json";
+pub const DEFAULT_BASE_CURRENCY: Currency = Currency::Exalted;
+
+// Currencies priced against the base when refreshing rates from the exchange
+pub const EXCHANGE_CURRENCIES: &[Currency] = &[
+    Currency::Divine,
+    Currency::Chaos,
+    Currency::Regal,
+    Currency::Alchemy,
+    Currency::Annulment,
+    Currency::Vaal,
+    Currency::Chance,
+    Currency::Transmutation,
+    Currency::Augmentation,
+    Currency::Mirror,
+];
+
+// How old the rates file may get before collection refreshes it
+pub const RATES_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Converts listing prices into one base currency so prices are comparable across items.
+// A rate is the number of base currency units one unit of the currency is worth.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurrencyConverter {
+    base: Currency,
+    rates: HashMap<Currency, f64>,
+    // Unix seconds of the last exchange refresh
+    #[serde(default)]
+    updated_at: u64,
+}
+
+impl Default for CurrencyConverter {
+    fn default() -> Self {
+        Self::new(DEFAULT_BASE_CURRENCY)
+    }
+}
+
+impl CurrencyConverter {
+    pub fn new(base: Currency) -> Self {
+        Self {
+            base,
+            rates: HashMap::new(),
+            updated_at: 0,
+        }
+    }
+
+    pub fn base(&self) -> &Currency {
+        &self.base
+    }
+
+    pub fn set_rate(&mut self, currency: Currency, rate: f64) {
+        self.rates.insert(currency, rate);
+    }
+
+    // Value of `amount` of `currency` in the base currency, if its rate is known
+    pub fn convert(&self, amount: f64, currency: &Currency) -> Option<f64> {
+        if *currency == self.base {
+            return Some(amount);
+        }
+        self.rates.get(currency).map(|rate| amount * rate)
+    }
+
+    // Fill in the price's normalized amount; unknown currencies are left unnormalized
+    pub fn normalize(&self, price: &mut ItemPrice) {
+        price.normalized_amount = self.convert(price.amount, &price.currency);
+    }
+
+    pub fn is_stale(&self, max_age: Duration) -> bool {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        now.saturating_sub(self.updated_at) > max_age.as_secs()
+    }
+
+    pub async fn load_from_file(path: &str) -> Result<Self> {
+        let content = tokio::fs::read_to_string(path).await.with_context(|| format!("reading {}", path))?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    pub async fn save_to_file(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        tokio::fs::write(path, json).await?;
+        Ok(())
+    }
+
+    // Price each currency against the base on the league's currency exchange
+    pub async fn update_from_exchange(&mut self, client: &mut TradeApiClient, currencies: &[Currency]) -> Result<()> {
+        for currency in currencies.iter().filter(|&currency| *currency != self.base) {
+            match client.exchange_rate(self.base.as_str(), currency.as_str()).await? {
+                Some(rate) => {
+                    self.rates.insert(currency.clone(), rate);
+                }
+                None => warn!(%currency, "no exchange offers, keeping previous rate"),
+            }
+        }
+
+        self.updated_at = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        Ok(())
+    }
+}
+
+// Load the rates file, refreshing it from the exchange when it is missing or older than a day.
+// A failed refresh falls back to whatever rates were on disk.
+pub async fn initialize_currency_converter(client: &mut TradeApiClient) -> Result<CurrencyConverter> {
+    let mut converter = CurrencyConverter::load_from_file(DEFAULT_RATES_PATH)
+        .await
+        .unwrap_or_default();
+
+    if converter.is_stale(RATES_MAX_AGE) {
+        match converter.update_from_exchange(client, EXCHANGE_CURRENCIES).await {
+            Ok(()) => {
+                converter.save_to_file(DEFAULT_RATES_PATH).await?;
+                info!(base = %converter.base(), rates = converter.rates.len(), "refreshed currency rates");
+            }
+            Err(e) => warn!(error = %e, "failed to refresh currency rates, using cached rates"),
+        }
+    }
+
+    Ok(converter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_to_base() {
+        let mut converter = CurrencyConverter::new(Currency::Exalted);
+        converter.set_rate(Currency::Divine, 200.0);
+
+        assert_eq!(converter.convert(3.0, &Currency::Exalted), Some(3.0));
+        assert_eq!(converter.convert(1.5, &Currency::Divine), Some(300.0));
+        assert_eq!(converter.convert(1.0, &Currency::Mirror), None);
+
+        let mut price = ItemPrice { amount: 2.0, currency: Currency::Divine, normalized_amount: None };
+        converter.normalize(&mut price);
+        assert_eq!(price.normalized_amount, Some(400.0));
+        assert!(converter.is_stale(RATES_MAX_AGE));
+    }
+}
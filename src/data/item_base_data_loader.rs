@@ -1,42 +1,106 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
 use reqwest::Client;
 use crate::models::{
     CoreAttribute,
-    StatRequirements,
     ItemBaseType,
     ItemCategory,
+    ItemClass,
 };
-use crate::errors::Result;
+use crate::errors::{Result, ResultExt};
+use crate::fetcher::{RateBudget, DATA_ENDPOINT, DEFAULT_USER_AGENT};
+use crate::metrics::Metrics;
 
+// The trade data items endpoint: every listable base type grouped by item class,
+// e.g. {"result":[{"id":"accessory","label":"Accessories","entries":[...]}]}
 #[derive(Debug, Deserialize)]
-struct TradeApiBase {
-    name: String,
-    category: String,
-    requirements: Option<BaseRequirements>,
-    // Add other fields as needed based on the API response
+struct TradeItemsResponse {
+    result: Vec<TradeItemGroup>,
 }
 
 #[derive(Debug, Deserialize)]
-struct BaseRequirements {
-    strength: Option<u32>,
-    dexterity: Option<u32>,
-    intelligence: Option<u32>,
-    level: Option<u32>,
+struct TradeItemGroup {
+    id: String,
+    label: String,
+    entries: Vec<TradeItemEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradeItemEntry {
+    #[serde(rename = "type")]
+    base_type: String,
+    // Only set on uniques, which are listed next to the base they drop as
+    name: Option<String>,
+}
+
+// item_bases.json: the bases along with when they were fetched, so a restarted process
+// knows whether the cache is due a refresh
+#[derive(Debug, Serialize, Deserialize)]
+struct BaseCacheFile<B> {
+    // Unix seconds of the API fetch the bases came from
+    fetched_at: u64,
+    bases: B,
+}
+
+// Cache files written before the fetch time was recorded hold the bare map
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StoredBases {
+    Cached(BaseCacheFile<HashMap<String, ItemBaseType>>),
+    Unversioned(HashMap<String, ItemBaseType>),
+}
+
+// Quality prefixes the trade site puts in front of a base's name
+const BASE_NAME_PREFIXES: &[&str] = &["superior", "advanced", "expert"];
+
+// Lowest normalized Levenshtein similarity a name may have to a base to count as it
+const MIN_FUZZY_SIMILARITY: f64 = 0.85;
+
+// Reduce a base type name to its comparable core: lowercase, without bracketed tags such
+// as "(Tier 15)" and without leading quality prefixes
+pub fn normalize_base_name(name: &str) -> String {
+    let mut stripped = String::with_capacity(name.len());
+    let mut depth = 0usize;
+    for c in name.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => stripped.extend(c.to_lowercase()),
+            _ => {}
+        }
+    }
+
+    let mut words = stripped.split_whitespace().peekable();
+    while words.next_if(|word| BASE_NAME_PREFIXES.contains(word)).is_some() {}
+    words.collect::<Vec<_>>().join(" ")
 }
 
 pub struct BaseDataLoader {
     client: Client,
+    rate_budget: RateBudget,
     base_cache: HashMap<String, ItemBaseType>,
-    last_update: std::time::SystemTime,
+    // Normalized name to the cached base it stands for
+    name_index: HashMap<String, String>,
+    last_update: SystemTime,
+}
+
+impl Default for BaseDataLoader {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BaseDataLoader {
+    // Nothing has been fetched yet, so a new loader always needs an update
     pub fn new() -> Self {
         Self {
             client: Client::new(),
+            rate_budget: RateBudget::global(),
             base_cache: HashMap::new(),
-            last_update: std::time::SystemTime::now(),
+            name_index: HashMap::new(),
+            last_update: UNIX_EPOCH,
         }
     }
 
@@ -44,96 +108,145 @@ impl BaseDataLoader {
         self.base_cache.values()
     }
 
-    // Load base items from a JSON file (for initial/fallback data)
+    // Load base items from a JSON file (for initial/fallback data), along with when they
+    // were fetched. Files without a fetch time count as never updated.
     pub async fn load_from_file(&mut self, path: &str) -> Result<()> {
-        let content = tokio::fs::read_to_string(path).await?;
-        let bases: HashMap<String, ItemBaseType> = serde_json::from_str(&content)?;
+        let content = tokio::fs::read_to_string(path).await.with_context(|| format!("reading {}", path))?;
+        let (bases, fetched_at) = match serde_json::from_str(&content)? {
+            StoredBases::Cached(file) => (file.bases, file.fetched_at),
+            StoredBases::Unversioned(bases) => (bases, 0),
+        };
         self.base_cache = bases;
+        self.index_names();
+        self.last_update = UNIX_EPOCH + Duration::from_secs(fetched_at);
         Ok(())
     }
 
     // Save current base items to a JSON file
     pub async fn save_to_file(&self, path: &str) -> Result<()> {
-        let json = serde_json::to_string_pretty(&self.base_cache)?;
+        let file = BaseCacheFile {
+            fetched_at: self.last_update.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            bases: &self.base_cache,
+        };
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let json = serde_json::to_string_pretty(&file)?;
         tokio::fs::write(path, json).await?;
         Ok(())
     }
 
+    // Load the base items snapshot compiled into the binary. It keeps the loader's fetch
+    // time, so an API refresh is still due afterwards.
+    #[cfg(feature = "bundled-bases")]
+    pub fn load_bundled(&mut self) -> Result<()> {
+        self.load_from_response(BUNDLED_ITEM_BASES)
+    }
+
     // Update base items from the trade API
     pub async fn update_from_api(&mut self, api_url: &str) -> Result<()> {
+        self.rate_budget.acquire(DATA_ENDPOINT).await;
         let response = self.client.get(api_url)
+            .header("User-Agent", DEFAULT_USER_AGENT)
             .send()
-            .await?
-            .json::<Vec<TradeApiBase>>()
+            .await;
+        Metrics::global().record_api_response(DATA_ENDPOINT, response.as_ref().ok().map(|response| response.status()));
+        let response = response?;
+        self.rate_budget.update_from_headers(DATA_ENDPOINT, response.headers());
+        let response = response
+            .error_for_status()?
+            .text()
             .await?;
 
-        for base in response {
-            if let Some(base_type) = self.convert_api_base(base) {
-                self.base_cache.insert(base_type.name.clone(), base_type);
-            }
-        }
-
-        self.last_update = std::time::SystemTime::now();
+        self.load_from_response(&response)?;
+        self.last_update = SystemTime::now();
         Ok(())
     }
 
-    // Convert API response to our internal ItemBaseType
-    fn convert_api_base(&self, api_base: TradeApiBase) -> Option<ItemBaseType> {
-        let category = self.determine_category(&api_base.category)?;
-        let mut base_type = ItemBaseType::new(api_base.name, category);
-
-        if let Some(reqs) = api_base.requirements {
-            // Add strength requirement if present
-            if let Some(str_req) = reqs.strength {
-                base_type.stat_requirements.add_requirement(
-                    CoreAttribute::Strength,
-                    str_req
-                );
-            }
-
-            // Add dexterity requirement if present
-            if let Some(dex_req) = reqs.dexterity {
-                base_type.stat_requirements.add_requirement(
-                    CoreAttribute::Dexterity,
-                    dex_req
-                );
-            }
-
-            // Add intelligence requirement if present
-            if let Some(int_req) = reqs.intelligence {
-                base_type.stat_requirements.add_requirement(
-                    CoreAttribute::Intelligence,
-                    int_req
-                );
-            }
-
-            // Set base level if available
-            if let Some(level) = reqs.level {
-                base_type.base_level = level;
+    // Add the base types of a data items response. The endpoint lists no requirements or
+    // levels, so those keep their defaults; uniques are skipped as they aren't bases.
+    fn load_from_response(&mut self, body: &str) -> Result<()> {
+        let response: TradeItemsResponse = serde_json::from_str(body)?;
+        for group in response.result {
+            // Labels are display text; fall back to the group ID when one isn't recognised
+            let category = self.determine_category(&group.label)
+                .filter(|category| *category != ItemCategory::Other)
+                .or_else(|| self.determine_category(&group.id))
+                .unwrap_or(ItemCategory::Other);
+            for entry in group.entries.into_iter().filter(|entry| entry.name.is_none()) {
+                self.base_cache.entry(entry.base_type.clone()).or_insert_with(|| {
+                    // The groups are whole categories; a class guessed from the name is
+                    // only kept when it belongs to the group
+                    let mut base = ItemBaseType::new(entry.base_type, category);
+                    base.class = ItemClass::from_base_name(&base.name).filter(|class| class.category() == category);
+                    base
+                });
             }
         }
+        self.index_names();
+        Ok(())
+    }
 
-        Some(base_type)
+    // When several bases normalize alike, e.g. "Maraketh Cuirass" and "Advanced Maraketh
+    // Cuirass", the shortest name stands for them
+    fn index_names(&mut self) {
+        self.name_index.clear();
+        for name in self.base_cache.keys() {
+            self.name_index
+                .entry(normalize_base_name(name))
+                .and_modify(|indexed| {
+                    if (name.len(), name) < (indexed.len(), indexed) {
+                        indexed.clone_from(name);
+                    }
+                })
+                .or_insert_with(|| name.clone());
+        }
     }
 
     // Map API category strings to our ItemCategory enum
     fn determine_category(&self, api_category: &str) -> Option<ItemCategory> {
         match api_category.to_lowercase().as_str() {
-            "weapons" => Some(ItemCategory::Weapon),
+            "weapons" | "weapon" => Some(ItemCategory::Weapon),
             "armour" | "armor" => Some(ItemCategory::Armour),
-            "accessories" => Some(ItemCategory::Accessory),
-            "flasks" => Some(ItemCategory::Flask),
-            "gems" => Some(ItemCategory::Gem),
+            "accessories" | "accessory" => Some(ItemCategory::Accessory),
+            "flasks" | "flask" => Some(ItemCategory::Flask),
+            "gems" | "gem" => Some(ItemCategory::Gem),
+            "jewels" | "jewel" => Some(ItemCategory::Jewel),
             "currency" => Some(ItemCategory::Currency),
-            "cards" => Some(ItemCategory::DivinationCard),
-            "maps" => Some(ItemCategory::Map),
+            "cards" | "card" => Some(ItemCategory::DivinationCard),
+            // PoE2 lists maps as waystones
+            "maps" | "map" | "waystones" => Some(ItemCategory::Map),
             _ => Some(ItemCategory::Other),
         }
     }
 
-    // Get a base type by name
+    // Get a base type by name. Names the cache doesn't hold exactly are normalized, then
+    // matched against the longest base they contain as whole words (magic items carry
+    // their affixes, e.g. "Sapphire Ring of the Bear"), then by edit distance.
     pub fn get_base(&self, name: &str) -> Option<&ItemBaseType> {
-        self.base_cache.get(name)
+        if let Some(base) = self.base_cache.get(name) {
+            return Some(base);
+        }
+
+        let normalized = normalize_base_name(name);
+        if let Some(base) = self.name_index.get(&normalized).and_then(|name| self.base_cache.get(name)) {
+            return Some(base);
+        }
+
+        let padded = format!(" {} ", normalized);
+        let contained = self.name_index
+            .iter()
+            .filter(|(candidate, _)| !candidate.is_empty() && padded.contains(&format!(" {} ", candidate)))
+            .max_by_key(|(candidate, _)| candidate.len());
+        let closest = || {
+            self.name_index
+                .iter()
+                .map(|(candidate, base)| (strsim::normalized_levenshtein(&normalized, candidate), base))
+                .filter(|(similarity, _)| *similarity >= MIN_FUZZY_SIMILARITY)
+                .max_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, base)| base)
+        };
+        contained.map(|(_, base)| base).or_else(closest).and_then(|name| self.base_cache.get(name))
     }
 
     // Get all bases matching certain criteria
@@ -146,7 +259,7 @@ impl BaseDataLoader {
     }
 
     // Check if the cache needs updating (e.g., if it's older than 24 hours)
-    pub fn needs_update(&self, update_interval: std::time::Duration) -> bool {
+    pub fn needs_update(&self, update_interval: Duration) -> bool {
         self.last_update.elapsed().unwrap_or_default() > update_interval
     }
 
@@ -172,21 +285,32 @@ impl BaseDataLoader {
     }
 }
 
+pub const BASE_ITEMS_URL: &str = "https://www.pathofexile.com/api/trade2/data/items";
+pub const BASE_ITEMS_PATH: &str = "data/item_bases.json";
+
+// A data items response recorded from the trade site, for runs without a cache or network
+#[cfg(feature = "bundled-bases")]
+const BUNDLED_ITEM_BASES: &str = include_str!("bundled/item_bases.json");
+
 pub async fn initialize_base_loader() -> Result<BaseDataLoader> {
     let mut loader = BaseDataLoader::new();
 
-    // Try to load initial data from file
-    if let Err(_) = loader.load_from_file("data/item_bases.json").await {
-        // If file doesn't exist or is invalid, update from API
-        loader.update_from_api("https://api.pathofexile.com/trade/data/items").await?;
-        // Save the fresh data
-        loader.save_to_file("data/item_bases.json").await?;
+    // Try to load initial data from file, falling back to the snapshot built into the binary
+    if loader.load_from_file(BASE_ITEMS_PATH).await.is_err() {
+        #[cfg(feature = "bundled-bases")]
+        loader.load_bundled()?;
     }
 
-    // Check if data needs updating
-    if loader.needs_update(std::time::Duration::from_secs(86400)) {  // 24 hours
-        loader.update_from_api("https://api.pathofexile.com/trade/data/items").await?;
-        loader.save_to_file("data/item_bases.json").await?;
+    // Fetch bases when there's no cache or it's older than a day. Fetched bases are added
+    // on top of what's loaded, which stays usable if the fetch fails.
+    if loader.needs_update(Duration::from_secs(86400)) {
+        match loader.update_from_api(BASE_ITEMS_URL).await {
+            Ok(()) => loader.save_to_file(BASE_ITEMS_PATH).await?,
+            Err(e) if !loader.base_cache.is_empty() => {
+                warn!(error = %e, "failed to refresh base items, using cached bases");
+            }
+            Err(e) => return Err(e),
+        }
     }
 
     Ok(loader)
@@ -209,4 +333,74 @@ mod tests {
         assert!(matches!(loader.determine_category("Armour"), Some(ItemCategory::Armour)));
         assert!(matches!(loader.determine_category("Unknown"), Some(ItemCategory::Other)));
     }
+
+    #[tokio::test]
+    async fn test_fetch_time_survives_restart() {
+        let path = std::env::temp_dir().join(format!("poe2-item-bases-test-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut loader = BaseDataLoader::new();
+        assert!(loader.needs_update(Duration::from_secs(86400)));
+        loader.load_from_response(include_str!("fixtures/trade_items.json")).unwrap();
+        loader.last_update = SystemTime::now() - Duration::from_secs(2 * 86400);
+        loader.save_to_file(path).await.unwrap();
+
+        let mut restarted = BaseDataLoader::new();
+        restarted.load_from_file(path).await.unwrap();
+        tokio::fs::write(path, serde_json::to_string(&restarted.base_cache).unwrap()).await.unwrap();
+        let mut unversioned = BaseDataLoader::new();
+        unversioned.load_from_file(path).await.unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(restarted.get_all_bases().count(), 9);
+        assert!(restarted.needs_update(Duration::from_secs(86400)));
+        assert!(!restarted.needs_update(Duration::from_secs(3 * 86400)));
+        assert_eq!(unversioned.get_all_bases().count(), 9);
+        assert_eq!(unversioned.last_update, UNIX_EPOCH);
+    }
+
+    #[test]
+    fn test_lookup_tolerates_prefixes_and_affixes() {
+        let mut loader = BaseDataLoader::new();
+        loader.load_from_response(r#"{"result": [
+            {"id": "armour", "label": "Armour", "entries": [{"type": "Maraketh Cuirass"}]},
+            {"id": "accessory", "label": "Accessories", "entries": [{"type": "Ring"}, {"type": "Sapphire Ring"}]}
+        ]}"#).unwrap();
+
+        assert_eq!(normalize_base_name("Superior Expert Maraketh Cuirass [Corrupted]"), "maraketh cuirass");
+        let found = |name: &str| loader.get_base(name).map(|base| base.name.as_str());
+        assert_eq!(found("Advanced Maraketh Cuirass"), Some("Maraketh Cuirass"));
+        assert_eq!(found("Sapphire Ring of the Bear"), Some("Sapphire Ring"));
+        assert_eq!(found("Maraketh Cuiras"), Some("Maraketh Cuirass"));
+        assert_eq!(found("Leather Belt"), None);
+    }
+
+    #[cfg(feature = "bundled-bases")]
+    #[test]
+    fn test_bundled_bases_load() {
+        let mut loader = BaseDataLoader::new();
+        loader.load_bundled().unwrap();
+
+        assert!(loader.get_all_bases().count() > 200);
+        assert_eq!(loader.get_base("Gold Ring").map(|base| base.category), Some(ItemCategory::Accessory));
+        assert_eq!(loader.get_base("Expert Warstaff").map(|base| base.category), Some(ItemCategory::Weapon));
+        assert!(loader.needs_update(Duration::from_secs(86400)));
+    }
+
+    #[test]
+    fn test_load_recorded_response() {
+        let mut loader = BaseDataLoader::new();
+        loader.load_from_response(include_str!("fixtures/trade_items.json")).unwrap();
+
+        // Uniques are dropped and the alternate gem listing folds into its base
+        assert_eq!(loader.get_all_bases().count(), 9);
+        assert!(loader.get_base("Andvarius").is_none());
+        let category = |name: &str| loader.get_base(name).map(|base| base.category);
+        assert_eq!(category("Gold Ring"), Some(ItemCategory::Accessory));
+        assert_eq!(category("Advanced Maraketh Cuirass"), Some(ItemCategory::Armour));
+        assert_eq!(category("Waystone (Tier 15)"), Some(ItemCategory::Map));
+        assert_eq!(category("Sapphire"), Some(ItemCategory::Jewel));
+        assert_eq!(category("Expert Warstaff"), Some(ItemCategory::Weapon));
+        assert_eq!(loader.get_base("Gold Ring").and_then(|base| base.class), Some(ItemClass::Ring));
+        assert_eq!(loader.get_base("Expert Warstaff").and_then(|base| base.class), Some(ItemClass::Quarterstaff));
+    }
 }
\ No newline at end of file
@@ -1 +1,5 @@
-pub mod item_base_data_loader;
\ No newline at end of file
+pub mod affix_data_loader;
+pub mod currency_converter;
+pub mod item_base_data_loader;
+pub mod stat_catalog_loader;
+pub mod unique_data_loader;
\ No newline at end of file
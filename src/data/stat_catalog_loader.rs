@@ -0,0 +1,290 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use reqwest::Client;
+use crate::errors::{Result, ResultExt};
+use crate::fetcher::{RateBudget, DATA_ENDPOINT, DEFAULT_USER_AGENT};
+use crate::metrics::Metrics;
+
+pub const TRADE_STATS_URL: &str = "https://www.pathofexile.com/api/trade2/data/stats";
+pub const STAT_CATALOG_PATH: &str = "data/trade_stats.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatEntry {
+    pub id: String,
+    pub text: String,
+    #[serde(rename = "type")]
+    pub stat_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradeStatsResponse {
+    result: Vec<TradeStatGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradeStatGroup {
+    entries: Vec<StatEntry>,
+}
+
+// trade_stats.json: the stats along with when they were fetched, so a restarted process
+// knows whether the cache is due a refresh
+#[derive(Debug, Serialize, Deserialize)]
+struct StatCacheFile<S> {
+    // Unix seconds of the API fetch the stats came from
+    fetched_at: u64,
+    stats: S,
+}
+
+// Cache files written before the fetch time was recorded hold the bare list
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StoredStats {
+    Cached(StatCacheFile<Vec<StatEntry>>),
+    Unversioned(Vec<StatEntry>),
+}
+
+// Loads the trade site's stat list so stat IDs can be looked up by their display text
+pub struct StatCatalogLoader {
+    client: Client,
+    rate_budget: RateBudget,
+    stats: HashMap<String, StatEntry>,
+    text_index: HashMap<String, Vec<String>>,
+    last_update: SystemTime,
+}
+
+// Reduce mod text to the catalogue's template form: numbers become '#', case is ignored
+pub fn normalize_stat_text(text: &str) -> String {
+    let mut normalized = String::with_capacity(text.len());
+    let mut in_number = false;
+
+    for c in text.trim().chars() {
+        if c.is_ascii_digit() || (in_number && c == '.') {
+            if !in_number {
+                normalized.push('#');
+                in_number = true;
+            }
+        } else {
+            in_number = false;
+            normalized.extend(c.to_lowercase());
+        }
+    }
+
+    normalized
+}
+
+impl Default for StatCatalogLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatCatalogLoader {
+    // Nothing has been fetched yet, so a new loader always needs an update
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+            rate_budget: RateBudget::global(),
+            stats: HashMap::new(),
+            text_index: HashMap::new(),
+            last_update: UNIX_EPOCH,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.stats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+
+    fn set_entries(&mut self, entries: Vec<StatEntry>) {
+        self.stats.clear();
+        self.text_index.clear();
+
+        for entry in entries {
+            self.text_index
+                .entry(normalize_stat_text(&entry.text))
+                .or_default()
+                .push(entry.id.clone());
+            self.stats.insert(entry.id.clone(), entry);
+        }
+    }
+
+    // Load a previously cached catalogue along with when it was fetched. Files without a
+    // fetch time count as never updated.
+    pub async fn load_from_file(&mut self, path: &str) -> Result<()> {
+        let content = tokio::fs::read_to_string(path).await.with_context(|| format!("reading {}", path))?;
+        let (entries, fetched_at) = match serde_json::from_str(&content)? {
+            StoredStats::Cached(file) => (file.stats, file.fetched_at),
+            StoredStats::Unversioned(entries) => (entries, 0),
+        };
+        self.set_entries(entries);
+        self.last_update = UNIX_EPOCH + Duration::from_secs(fetched_at);
+        Ok(())
+    }
+
+    // Cache the current catalogue to disk
+    pub async fn save_to_file(&self, path: &str) -> Result<()> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut entries: Vec<&StatEntry> = self.stats.values().collect();
+        entries.sort_by(|a, b| a.id.cmp(&b.id));
+        let file = StatCacheFile {
+            fetched_at: self.last_update.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            stats: entries,
+        };
+        let json = serde_json::to_string_pretty(&file)?;
+        tokio::fs::write(path, json).await?;
+        Ok(())
+    }
+
+    // Download the stat list from the trade data endpoint
+    pub async fn update_from_api(&mut self, api_url: &str) -> Result<()> {
+        self.rate_budget.acquire(DATA_ENDPOINT).await;
+        let response = self.client.get(api_url)
+            .header("User-Agent", DEFAULT_USER_AGENT)
+            .send()
+            .await;
+        Metrics::global().record_api_response(DATA_ENDPOINT, response.as_ref().ok().map(|response| response.status()));
+        let response = response?;
+        self.rate_budget.update_from_headers(DATA_ENDPOINT, response.headers());
+        let response = response
+            .error_for_status()?
+            .text()
+            .await?;
+
+        self.load_from_response(&response)?;
+        self.last_update = SystemTime::now();
+        Ok(())
+    }
+
+    fn load_from_response(&mut self, body: &str) -> Result<()> {
+        let response: TradeStatsResponse = serde_json::from_str(body)?;
+        let entries = response.result
+            .into_iter()
+            .flat_map(|group| group.entries)
+            .collect();
+        self.set_entries(entries);
+        Ok(())
+    }
+
+    pub fn get_stat(&self, id: &str) -> Option<&StatEntry> {
+        self.stats.get(id)
+    }
+
+    // All stats whose text matches, e.g. "+# to maximum Life" or "+54 to maximum Life"
+    pub fn find_by_text(&self, text: &str) -> Vec<&StatEntry> {
+        self.text_index
+            .get(&normalize_stat_text(text))
+            .map(|ids| ids.iter().filter_map(|id| self.stats.get(id)).collect())
+            .unwrap_or_default()
+    }
+
+    // The stat ID for a text of a given type ("explicit", "implicit", "pseudo", ...)
+    pub fn find_stat_id(&self, text: &str, stat_type: &str) -> Option<&str> {
+        self.find_by_text(text)
+            .into_iter()
+            .find(|entry| entry.stat_type.eq_ignore_ascii_case(stat_type))
+            .map(|entry| entry.id.as_str())
+    }
+
+    pub fn needs_update(&self, update_interval: Duration) -> bool {
+        self.last_update.elapsed().unwrap_or_default() > update_interval
+    }
+}
+
+pub async fn initialize_stat_catalog() -> Result<StatCatalogLoader> {
+    let mut loader = StatCatalogLoader::new();
+
+    // Use the cached catalogue when present, otherwise download a fresh one
+    if loader.load_from_file(STAT_CATALOG_PATH).await.is_err() || loader.is_empty() {
+        loader.update_from_api(TRADE_STATS_URL).await?;
+        loader.save_to_file(STAT_CATALOG_PATH).await?;
+    }
+
+    if loader.needs_update(Duration::from_secs(86400)) {  // 24 hours
+        loader.update_from_api(TRADE_STATS_URL).await?;
+        loader.save_to_file(STAT_CATALOG_PATH).await?;
+    }
+
+    Ok(loader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"{
+        "result": [
+            {
+                "id": "explicit",
+                "label": "Explicit",
+                "entries": [
+                    {"id": "explicit.stat_3299347043", "text": "+# to maximum Life", "type": "explicit"},
+                    {"id": "explicit.stat_4080418644", "text": "+# to Strength", "type": "explicit"}
+                ]
+            },
+            {
+                "id": "implicit",
+                "label": "Implicit",
+                "entries": [
+                    {"id": "implicit.stat_4080418644", "text": "+# to Strength", "type": "implicit"}
+                ]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_normalize_stat_text() {
+        assert_eq!(normalize_stat_text("+54 to maximum Life"), "+# to maximum life");
+        assert_eq!(normalize_stat_text("+# to maximum Life"), "+# to maximum life");
+        assert_eq!(normalize_stat_text("12.5% increased Attack Speed"), "#% increased attack speed");
+    }
+
+    #[test]
+    fn test_lookup_by_text() {
+        let mut loader = StatCatalogLoader::new();
+        loader.load_from_response(FIXTURE).unwrap();
+
+        assert_eq!(loader.len(), 3);
+        assert_eq!(
+            loader.find_stat_id("+# to maximum Life", "explicit"),
+            Some("explicit.stat_3299347043")
+        );
+        assert_eq!(loader.find_by_text("+17 to Strength").len(), 2);
+        assert_eq!(
+            loader.find_stat_id("+17 to Strength", "implicit"),
+            Some("implicit.stat_4080418644")
+        );
+        assert!(loader.find_stat_id("+# to Intelligence", "explicit").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stale_cache_needs_update() {
+        let path = std::env::temp_dir().join(format!("poe2-trade-stats-test-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut loader = StatCatalogLoader::new();
+        assert!(loader.needs_update(Duration::from_secs(86400)));
+        loader.load_from_response(FIXTURE).unwrap();
+        loader.last_update = SystemTime::now() - Duration::from_secs(2 * 86400);
+        loader.save_to_file(path).await.unwrap();
+
+        let mut restarted = StatCatalogLoader::new();
+        restarted.load_from_file(path).await.unwrap();
+        let entries: Vec<&StatEntry> = restarted.stats.values().collect();
+        tokio::fs::write(path, serde_json::to_string(&entries).unwrap()).await.unwrap();
+        let mut unversioned = StatCatalogLoader::new();
+        unversioned.load_from_file(path).await.unwrap();
+        let _ = std::fs::remove_file(path);
+
+        assert_eq!(restarted.len(), 3);
+        assert!(restarted.needs_update(Duration::from_secs(86400)));
+        assert!(!restarted.needs_update(Duration::from_secs(3 * 86400)));
+        assert_eq!(unversioned.len(), 3);
+        assert_eq!(unversioned.last_update, UNIX_EPOCH);
+    }
+}
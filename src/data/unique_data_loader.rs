@@ -0,0 +1,268 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use reqwest::Client;
+use tracing::warn;
+use crate::errors::{Result, ResultExt};
+use crate::fetcher::{RateBudget, DATA_ENDPOINT, DEFAULT_USER_AGENT};
+use crate::metrics::Metrics;
+use super::item_base_data_loader::BASE_ITEMS_URL;
+
+// Seed and cache file of the unique catalogue. Mod ranges aren't served by the trade site,
+// so they only come from this file; fetching adds the uniques it doesn't know yet.
+pub const UNIQUE_ITEMS_PATH: &str = "data/unique_items.json";
+
+// The range a unique's modifier can roll in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModRange {
+    // Trade stat ID, e.g. "explicit.stat_3299347043", as unique listings name their mods
+    pub stat: String,
+    // Display text, e.g. "+# to maximum Life"
+    #[serde(default)]
+    pub text: String,
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UniqueItem {
+    pub name: String,
+    pub base_type: String,
+    #[serde(default)]
+    pub mods: Vec<ModRange>,
+}
+
+// Known uniques by name
+#[derive(Debug, Clone, Default)]
+pub struct UniqueCatalogue {
+    uniques: HashMap<String, UniqueItem>,
+}
+
+impl UniqueCatalogue {
+    pub fn len(&self) -> usize {
+        self.uniques.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uniques.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&UniqueItem> {
+        self.uniques.get(name)
+    }
+
+    // The modifier's possible range on the unique, low end first
+    pub fn mod_range(&self, name: &str, stat: &str) -> Option<(f64, f64)> {
+        let range = self.get(name)?.mods.iter().find(|range| range.stat == stat)?;
+        Some((range.min.min(range.max), range.min.max(range.max)))
+    }
+
+    // Where a roll sits in the modifier's range: 0 for the worst possible roll, 1 for the
+    // best. None for unknown modifiers and ones that can only roll one value.
+    pub fn roll_position(&self, name: &str, stat: &str, value: f64) -> Option<f64> {
+        let (low, high) = self.mod_range(name, stat)?;
+        if high <= low {
+            return None;
+        }
+        Some(((value - low) / (high - low)).clamp(0.0, 1.0))
+    }
+
+    // Add a unique, keeping the mod ranges of one already known under its name
+    fn merge(&mut self, unique: UniqueItem) {
+        match self.uniques.get_mut(&unique.name) {
+            Some(known) => {
+                known.base_type = unique.base_type;
+                if known.mods.is_empty() {
+                    known.mods = unique.mods;
+                }
+            }
+            None => {
+                self.uniques.insert(unique.name.clone(), unique);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TradeItemsResponse {
+    result: Vec<TradeItemGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradeItemGroup {
+    entries: Vec<TradeItemEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TradeItemEntry {
+    #[serde(rename = "type")]
+    base_type: String,
+    name: Option<String>,
+    #[serde(default)]
+    flags: TradeItemFlags,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TradeItemFlags {
+    #[serde(default)]
+    unique: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UniqueCacheFile<U> {
+    // Unix seconds of the API fetch, 0 for a hand-written seed
+    fetched_at: u64,
+    uniques: U,
+}
+
+// A seed file may be just the list of uniques
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum StoredUniques {
+    Cached(UniqueCacheFile<Vec<UniqueItem>>),
+    Seed(Vec<UniqueItem>),
+}
+
+pub struct UniqueDataLoader {
+    client: Client,
+    rate_budget: RateBudget,
+    catalogue: UniqueCatalogue,
+    last_update: SystemTime,
+}
+
+impl Default for UniqueDataLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UniqueDataLoader {
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+            rate_budget: RateBudget::global(),
+            catalogue: UniqueCatalogue::default(),
+            last_update: UNIX_EPOCH,
+        }
+    }
+
+    pub fn catalogue(&self) -> &UniqueCatalogue {
+        &self.catalogue
+    }
+
+    pub async fn load_from_file(&mut self, path: &str) -> Result<()> {
+        let content = tokio::fs::read_to_string(path).await.with_context(|| format!("reading {}", path))?;
+        let (uniques, fetched_at) = match serde_json::from_str(&content)? {
+            StoredUniques::Cached(file) => (file.uniques, file.fetched_at),
+            StoredUniques::Seed(uniques) => (uniques, 0),
+        };
+        for unique in uniques {
+            self.catalogue.merge(unique);
+        }
+        self.last_update = UNIX_EPOCH + Duration::from_secs(fetched_at);
+        Ok(())
+    }
+
+    pub async fn save_to_file(&self, path: &str) -> Result<()> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut uniques: Vec<&UniqueItem> = self.catalogue.uniques.values().collect();
+        uniques.sort_by(|a, b| a.name.cmp(&b.name));
+        let file = UniqueCacheFile {
+            fetched_at: self.last_update.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            uniques,
+        };
+        tokio::fs::write(path, serde_json::to_string_pretty(&file)?).await?;
+        Ok(())
+    }
+
+    // Add the uniques listed by the trade data items endpoint, which names each unique
+    // and its base but not its mods
+    pub async fn update_from_api(&mut self, api_url: &str) -> Result<()> {
+        self.rate_budget.acquire(DATA_ENDPOINT).await;
+        let response = self.client.get(api_url)
+            .header("User-Agent", DEFAULT_USER_AGENT)
+            .send()
+            .await;
+        Metrics::global().record_api_response(DATA_ENDPOINT, response.as_ref().ok().map(|response| response.status()));
+        let response = response?;
+        self.rate_budget.update_from_headers(DATA_ENDPOINT, response.headers());
+        let response = response
+            .error_for_status()?
+            .text()
+            .await?;
+
+        self.load_from_response(&response)?;
+        self.last_update = SystemTime::now();
+        Ok(())
+    }
+
+    fn load_from_response(&mut self, body: &str) -> Result<()> {
+        let response: TradeItemsResponse = serde_json::from_str(body)?;
+        let uniques = response.result
+            .into_iter()
+            .flat_map(|group| group.entries)
+            .filter(|entry| entry.flags.unique);
+        for entry in uniques {
+            let Some(name) = entry.name else {
+                continue;
+            };
+            self.catalogue.merge(UniqueItem { name, base_type: entry.base_type, mods: Vec::new() });
+        }
+        Ok(())
+    }
+
+    pub fn needs_update(&self, update_interval: Duration) -> bool {
+        self.last_update.elapsed().unwrap_or_default() > update_interval
+    }
+}
+
+// Load the catalogue file and add any uniques the trade site lists that it lacks, at most
+// once a day. A failed fetch leaves whatever was loaded.
+pub async fn initialize_unique_catalogue() -> Result<UniqueDataLoader> {
+    let mut loader = UniqueDataLoader::new();
+    // A missing seed file just means every unique starts without mod ranges
+    let _ = loader.load_from_file(UNIQUE_ITEMS_PATH).await;
+
+    if loader.needs_update(Duration::from_secs(86400)) {
+        match loader.update_from_api(BASE_ITEMS_URL).await {
+            Ok(()) => loader.save_to_file(UNIQUE_ITEMS_PATH).await?,
+            Err(e) if !loader.catalogue.is_empty() => {
+                warn!(error = %e, "failed to refresh unique items, using the cached catalogue");
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(loader)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_catalogue_merges_seed_and_trade_data() {
+        let path = std::env::temp_dir().join(format!("poe2-uniques-test-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        tokio::fs::write(path, r##"[
+            {"name": "Andvarius", "base_type": "Gold Ring", "mods": [
+                {"stat": "explicit.stat_3917489142", "text": "#% increased Rarity of Items found", "min": 50, "max": 70}
+            ]}
+        ]"##).await.unwrap();
+
+        let mut loader = UniqueDataLoader::new();
+        loader.load_from_file(path).await.unwrap();
+        loader.load_from_response(include_str!("fixtures/trade_items.json")).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        let catalogue = loader.catalogue();
+        assert_eq!(catalogue.len(), 3);
+        assert_eq!(catalogue.get("Briskwrap").map(|u| u.base_type.as_str()), Some("Rogue Armour"));
+        assert_eq!(catalogue.roll_position("Andvarius", "explicit.stat_3917489142", 65.0), Some(0.75));
+        assert_eq!(catalogue.roll_position("Andvarius", "explicit.stat_3917489142", 80.0), Some(1.0));
+        assert_eq!(catalogue.roll_position("Briskwrap", "explicit.stat_3917489142", 65.0), None);
+    }
+}
@@ -1,108 +1,268 @@
-use std::fmt;
-use std::error::Error;
+use serde::Deserialize;
 use sqlx::migrate::MigrateError;
+use thiserror::Error;
+use crate::fetcher::RetryPolicy;
 
-#[derive(Debug)]
+// Error codes the PoE API reports in {"error": {"code": .., "message": ..}} payloads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TradeApiErrorCode {
+    NotFound,
+    InvalidQuery,
+    RateLimited,
+    InternalError,
+    UnexpectedContentType,
+    Forbidden,
+    Unavailable,
+    Unauthorized,
+    MethodNotAllowed,
+    UnprocessableEntity,
+    Other(u32),
+}
+
+impl From<u32> for TradeApiErrorCode {
+    fn from(code: u32) -> Self {
+        match code {
+            1 => TradeApiErrorCode::NotFound,
+            2 => TradeApiErrorCode::InvalidQuery,
+            3 => TradeApiErrorCode::RateLimited,
+            4 => TradeApiErrorCode::InternalError,
+            5 => TradeApiErrorCode::UnexpectedContentType,
+            6 => TradeApiErrorCode::Forbidden,
+            7 => TradeApiErrorCode::Unavailable,
+            8 => TradeApiErrorCode::Unauthorized,
+            9 => TradeApiErrorCode::MethodNotAllowed,
+            10 => TradeApiErrorCode::UnprocessableEntity,
+            other => TradeApiErrorCode::Other(other),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorPayload {
+    error: ApiErrorBody,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    code: u32,
+    message: String,
+}
+
+#[derive(Debug, Error)]
 pub enum ScraperError {
+    #[error("API Error: {0}")]
     ApiError(String),
+    #[error("Trade API Error ({code:?}): {message}")]
+    TradeApiError {
+        code: TradeApiErrorCode,
+        message: String,
+    },
+    #[error("Parse Error: {0}")]
     ParseError(String),
+    #[error("Validation Error: {0}")]
     ValidationError(String),
+    // The search endpoint refused a query for having too many stats or filters. It won't
+    // go through on a retry; split the query or drop filters.
+    #[error("Query Too Complex: {0}")]
+    QueryTooComplex(String),
+    #[error("Rate Limit Error: {0}")]
     RateLimitError(String),
+    #[error("Network Error: {0}")]
     NetworkError(String),
+    #[error("IO Error: {0}")]
     IoError(String),
+    #[error("Database Error: {0}")]
     DatabaseError(String),
+    #[error("Migration Error: {0}")]
     MigrationError(String),
+    #[error("Conversion Error: {0}")]
     ConversionError(String),
+    // Work stopped early because shutdown was requested
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
+    // Errors of the libraries underneath, kept whole as the source
+    #[error("Network Error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Parse Error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("IO Error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Database Error: {}", database_message(.0))]
+    Database(#[from] sqlx::Error),
+    #[error("Migration Error: {}", migration_message(.0))]
+    Migrate(#[from] MigrateError),
+    #[cfg(feature = "parquet")]
+    #[error("Parquet Error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[cfg(feature = "parquet")]
+    #[error("Arrow Error: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    // What was being done when an error happened, e.g. the URL requested or the listing
+    // converted, see ResultExt
+    #[error("{context}: {source}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<ScraperError>,
+    },
 }
 
-impl fmt::Display for ScraperError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl ScraperError {
+    // The structured error in an API response body, if the body is one. Searches rejected
+    // for their complexity come as an invalid query and are told apart by the message.
+    pub fn from_api_body(body: &str) -> Option<Self> {
+        let payload: ApiErrorPayload = serde_json::from_str(body).ok()?;
+        let code = payload.error.code.into();
+        if code == TradeApiErrorCode::InvalidQuery && payload.error.message.to_lowercase().contains("too complex") {
+            return Some(ScraperError::QueryTooComplex(payload.error.message));
+        }
+        Some(ScraperError::TradeApiError {
+            code,
+            message: payload.error.message,
+        })
+    }
+
+    pub fn trade_api_code(&self) -> Option<TradeApiErrorCode> {
+        match self.root() {
+            ScraperError::TradeApiError { code, .. } => Some(*code),
+            ScraperError::QueryTooComplex(_) => Some(TradeApiErrorCode::InvalidQuery),
+            _ => None,
+        }
+    }
+
+    pub fn is_query_too_complex(&self) -> bool {
+        matches!(self.root(), ScraperError::QueryTooComplex(_))
+    }
+
+    pub fn context(self, context: impl Into<String>) -> Self {
+        ScraperError::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    // The error underneath any context added to it
+    pub fn root(&self) -> &ScraperError {
         match self {
-            ScraperError::ApiError(msg) => write!(f, "API Error: {}", msg),
-            ScraperError::ParseError(msg) => write!(f, "Parse Error: {}", msg),
-            ScraperError::ValidationError(msg) => write!(f, "Validation Error: {}", msg),
-            ScraperError::RateLimitError(msg) => write!(f, "Rate Limit Error: {}", msg),
-            ScraperError::NetworkError(msg) => write!(f, "Network Error: {}", msg),
-            ScraperError::IoError(msg) => write!(f, "IO Error: {}", msg),
-            ScraperError::DatabaseError(msg) => write!(f, "Database Error: {}", msg),
-            ScraperError::MigrationError(msg) => write!(f, "Migration Error: {}", msg),
-            ScraperError::ConversionError(msg) => write!(f, "Conversion Error: {}", msg),
+            ScraperError::Context { source, .. } => source.root(),
+            other => other,
         }
     }
-}
 
-impl Error for ScraperError {}
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.root(), ScraperError::Cancelled(_))
+    }
 
-impl From<reqwest::Error> for ScraperError {
-    fn from(err: reqwest::Error) -> Self {
-        ScraperError::NetworkError(err.to_string())
+    // Whether trying again later could succeed: rate limits, timeouts, dropped
+    // connections and server-side failures, as opposed to bad queries or data
+    pub fn is_transient(&self) -> bool {
+        match self.root() {
+            ScraperError::RateLimitError(_) | ScraperError::NetworkError(_) => true,
+            ScraperError::TradeApiError { code, .. } => matches!(
+                code,
+                TradeApiErrorCode::RateLimited | TradeApiErrorCode::InternalError | TradeApiErrorCode::Unavailable
+            ),
+            ScraperError::Http(err) => {
+                RetryPolicy::is_retryable_error(err) || err.status().is_some_and(RetryPolicy::is_retryable_status)
+            }
+            ScraperError::Database(err) => matches!(err, sqlx::Error::PoolTimedOut | sqlx::Error::Io(_)),
+            _ => false,
+        }
     }
 }
 
-impl From<serde_json::Error> for ScraperError {
-    fn from(err: serde_json::Error) -> Self {
-        ScraperError::ParseError(err.to_string())
-    }
+// Attach what was being done to the error of a result, converting it to a ScraperError
+pub trait ResultExt<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T>;
 }
 
-impl From<std::io::Error> for ScraperError {
-    fn from(err: std::io::Error) -> Self {
-        ScraperError::IoError(err.to_string())
+impl<T, E: Into<ScraperError>> ResultExt<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.map_err(|err| err.into().context(context))
+    }
+
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|err| err.into().context(context()))
     }
 }
 
-impl From<sqlx::Error> for ScraperError {
-    fn from(err: sqlx::Error) -> Self {
-        match err {
-            sqlx::Error::Database(db_err) => {
-                // Handle specific database errors like constraint violations
-                ScraperError::DatabaseError(format!("Database error: {}", db_err))
-            }
-            sqlx::Error::RowNotFound => {
-                ScraperError::DatabaseError("Requested data not found".to_string())
-            }
-            sqlx::Error::ColumnNotFound(col_name) => {
-                ScraperError::DatabaseError(format!("Column not found: {}", col_name))
-            }
-            sqlx::Error::ColumnDecode { index, source } => {
-                ScraperError::DatabaseError(
-                    format!("Failed to decode column {}: {}", index, source)
-                )
-            }
-            sqlx::Error::Decode(desc) => {
-                ScraperError::DatabaseError(format!("Decode error: {}", desc))
-            }
-            sqlx::Error::PoolTimedOut => {
-                ScraperError::DatabaseError("Database connection pool timeout".to_string())
-            }
-            sqlx::Error::WorkerCrashed => {
-                ScraperError::DatabaseError("Database worker thread crashed".to_string())
-            }
-            _ => ScraperError::DatabaseError(format!("Other database error: {}", err)),
+fn database_message(err: &sqlx::Error) -> String {
+    match err {
+        // Handle specific database errors like constraint violations
+        sqlx::Error::Database(db_err) => format!("Database error: {}", db_err),
+        sqlx::Error::RowNotFound => "Requested data not found".to_string(),
+        sqlx::Error::ColumnNotFound(col_name) => format!("Column not found: {}", col_name),
+        sqlx::Error::ColumnDecode { index, source } => {
+            format!("Failed to decode column {}: {}", index, source)
         }
+        sqlx::Error::Decode(desc) => format!("Decode error: {}", desc),
+        sqlx::Error::PoolTimedOut => "Database connection pool timeout".to_string(),
+        sqlx::Error::WorkerCrashed => "Database worker thread crashed".to_string(),
+        _ => format!("Other database error: {}", err),
     }
 }
 
-impl From<MigrateError> for ScraperError {
-    fn from(err: MigrateError) -> Self {
-        match err {
-            MigrateError::Source(source_err) => {
-                // Handle errors that occurred during migration execution
-                ScraperError::MigrationError(format!("Migration source error: {}", source_err))
-            }
-            MigrateError::VersionMismatch(version) => {
-                // Handle version mismatch with the single version number provided
-                ScraperError::MigrationError(format!("Migration version mismatch at version {}", version))
-            }
-            MigrateError::Dirty(version) => {
-                // Handle cases where a migration failed and left the database in a "dirty" state
-                ScraperError::MigrationError(
-                    format!("Database left in dirty state at version {}", version)
-                )
-            }
-            _ => ScraperError::MigrationError(format!("Other migration error: {}", err)),
+fn migration_message(err: &MigrateError) -> String {
+    match err {
+        // Errors that occurred during migration execution
+        MigrateError::Source(source_err) => format!("Migration source error: {}", source_err),
+        MigrateError::VersionMismatch(version) => {
+            format!("Migration version mismatch at version {}", version)
         }
+        // A migration failed and left the database in a "dirty" state
+        MigrateError::Dirty(version) => format!("Database left in dirty state at version {}", version),
+        _ => format!("Other migration error: {}", err),
     }
 }
 
 pub type Result<T> = std::result::Result<T, ScraperError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_api_error_payload() {
+        let error = ScraperError::from_api_body(
+            r#"{"error":{"code":2,"message":"Invalid query"}}"#
+        ).unwrap();
+        assert_eq!(error.trade_api_code(), Some(TradeApiErrorCode::InvalidQuery));
+        assert_eq!(error.to_string(), "Trade API Error (InvalidQuery): Invalid query");
+
+        let error = ScraperError::from_api_body(
+            r#"{"error":{"code":42,"message":"Something new"}}"#
+        ).unwrap();
+        assert_eq!(error.trade_api_code(), Some(TradeApiErrorCode::Other(42)));
+
+        let error = ScraperError::from_api_body(
+            r#"{"error":{"code":2,"message":"Query is too complex."}}"#
+        ).unwrap();
+        assert!(error.is_query_too_complex() && !error.is_transient());
+        assert_eq!(error.trade_api_code(), Some(TradeApiErrorCode::InvalidQuery));
+
+        assert!(ScraperError::from_api_body(r#"{"result":[]}"#).is_none());
+        assert!(ScraperError::from_api_body("<html>maintenance</html>").is_none());
+    }
+
+    #[test]
+    fn test_context_keeps_source_and_transience() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let error = Err::<(), _>(io)
+            .with_context(|| "reading data/item_bases.json")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "reading data/item_bases.json: IO Error: no such file");
+        let source = std::error::Error::source(&error).unwrap();
+        assert!(std::error::Error::source(source).unwrap().is::<std::io::Error>());
+        assert!(!error.is_transient());
+
+        let rate_limited = ScraperError::from_api_body(r#"{"error":{"code":3,"message":"Rate limit exceeded"}}"#)
+            .unwrap()
+            .context("GET https://www.pathofexile.com/api/trade2/fetch/abc");
+        assert!(rate_limited.is_transient());
+        assert_eq!(rate_limited.trade_api_code(), Some(TradeApiErrorCode::RateLimited));
+        assert!(!ScraperError::ValidationError("bad league".to_string()).is_transient());
+    }
+}
@@ -0,0 +1,208 @@
+use reqwest::{Client, RequestBuilder};
+use serde::Deserialize;
+use tracing::debug;
+use std::time::{Duration, Instant};
+use crate::errors::{Result, ScraperError};
+use crate::metrics::Metrics;
+use super::rate_budget::{RateBudget, OAUTH_ENDPOINT};
+
+const DEFAULT_TOKEN_URL: &str = "https://www.pathofexile.com/oauth/token";
+
+// Refresh tokens this long before they actually expire
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone)]
+pub struct OAuthConfig {
+    pub client_id: String,
+    pub client_secret: String,
+    pub scope: String,
+    pub token_url: String,
+}
+
+impl OAuthConfig {
+    pub fn new(client_id: String, client_secret: String) -> Self {
+        Self {
+            client_id,
+            client_secret,
+            scope: "service:psapi".to_string(),
+            token_url: DEFAULT_TOKEN_URL.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessToken {
+    value: String,
+    expires_at: Option<Instant>,
+}
+
+impl AccessToken {
+    fn is_fresh(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => Instant::now() + TOKEN_REFRESH_MARGIN < expires_at,
+            None => true,
+        }
+    }
+}
+
+// How requests to the trade API are authenticated
+#[derive(Debug, Clone, Default)]
+pub enum Credentials {
+    #[default]
+    Anonymous,
+    Session(String),
+    OAuth {
+        config: OAuthConfig,
+        token: Option<AccessToken>,
+    },
+}
+
+impl Credentials {
+    pub fn session(sessid: String) -> Self {
+        Credentials::Session(sessid)
+    }
+
+    pub fn oauth(config: OAuthConfig) -> Self {
+        Credentials::OAuth { config, token: None }
+    }
+
+    // Attach the Cookie or Authorization header, fetching a new token first if needed. Token
+    // requests are counted against the rate budget like any other pathofexile.com request.
+    pub async fn authorize(&mut self, client: &Client, rate_budget: &RateBudget, request: RequestBuilder) -> Result<RequestBuilder> {
+        match self {
+            Credentials::Anonymous => Ok(request),
+            Credentials::Session(sessid) => {
+                Ok(request.header("Cookie", format!("POESESSID={}", sessid)))
+            }
+            Credentials::OAuth { config, token } => {
+                let needs_refresh = token.as_ref().is_none_or(|t| !t.is_fresh());
+                if needs_refresh {
+                    *token = Some(request_token(client, rate_budget, config).await?);
+                }
+
+                let access_token = token.as_ref()
+                    .map(|t| t.value.clone())
+                    .unwrap_or_default();
+                Ok(request.bearer_auth(access_token))
+            }
+        }
+    }
+
+    // The POESESSID in use, if authenticating with a session cookie
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            Credentials::Session(sessid) => Some(sessid),
+            _ => None,
+        }
+    }
+
+    // Forget the current token so the next request fetches a new one (e.g. after a 401)
+    pub fn invalidate(&mut self) {
+        if let Credentials::OAuth { token, .. } = self {
+            *token = None;
+        }
+    }
+}
+
+async fn request_token(client: &Client, rate_budget: &RateBudget, config: &OAuthConfig) -> Result<AccessToken> {
+    debug!(client_id = %config.client_id, "requesting OAuth access token");
+
+    rate_budget.acquire(OAUTH_ENDPOINT).await;
+    let response = client
+        .post(&config.token_url)
+        .form(&[
+            ("client_id", config.client_id.as_str()),
+            ("client_secret", config.client_secret.as_str()),
+            ("grant_type", "client_credentials"),
+            ("scope", config.scope.as_str()),
+        ])
+        .send()
+        .await;
+    Metrics::global().record_api_response(OAUTH_ENDPOINT, response.as_ref().ok().map(|response| response.status()));
+    let response = response?;
+    rate_budget.update_from_headers(OAUTH_ENDPOINT, response.headers());
+
+    let status = response.status();
+    let body = response.text().await?;
+
+    if !status.is_success() {
+        return Err(ScraperError::ApiError(format!(
+            "OAuth token request failed with status {}: {}",
+            status, body
+        )));
+    }
+
+    let token: TokenResponse = serde_json::from_str(&body)?;
+    Ok(AccessToken {
+        value: token.access_token,
+        expires_at: token.expires_in.map(|secs| Instant::now() + Duration::from_secs(secs)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_freshness() {
+        let fresh = AccessToken {
+            value: "abc".to_string(),
+            expires_at: Some(Instant::now() + Duration::from_secs(3600)),
+        };
+        let expiring = AccessToken {
+            value: "abc".to_string(),
+            expires_at: Some(Instant::now() + Duration::from_secs(10)),
+        };
+        let no_expiry = AccessToken {
+            value: "abc".to_string(),
+            expires_at: None,
+        };
+
+        assert!(fresh.is_fresh());
+        assert!(!expiring.is_fresh());
+        assert!(no_expiry.is_fresh());
+    }
+
+    #[tokio::test]
+    async fn test_session_cookie_is_attached() {
+        let client = Client::new();
+        let mut credentials = Credentials::session("secret".to_string());
+        let request = credentials
+            .authorize(&client, &RateBudget::new(), client.get("https://example.com"))
+            .await
+            .unwrap()
+            .build()
+            .unwrap();
+
+        assert_eq!(request.headers()["Cookie"], "POESESSID=secret");
+    }
+
+    #[test]
+    fn test_invalidate_clears_token() {
+        let mut credentials = Credentials::OAuth {
+            config: OAuthConfig::new("id".to_string(), "secret".to_string()),
+            token: Some(AccessToken { value: "abc".to_string(), expires_at: None }),
+        };
+        credentials.invalidate();
+        assert!(matches!(credentials, Credentials::OAuth { token: None, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_token_request_uses_rate_budget() {
+        let client = Client::new();
+        let budget = RateBudget::new();
+        let mut config = OAuthConfig::new("id".to_string(), "secret".to_string());
+        // Nothing listens on the discard port, so the request fails once it's been let through
+        config.token_url = "http://127.0.0.1:9/oauth/token".to_string();
+        let mut credentials = Credentials::oauth(config);
+
+        assert!(credentials.authorize(&client, &budget, client.get("https://example.com")).await.is_err());
+        assert_eq!(budget.requests_sent(), 1);
+    }
+}
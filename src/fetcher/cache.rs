@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use crate::errors::Result;
+
+pub const DEFAULT_CACHE_DIR: &str = "data/http_cache";
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    stored_at: u64,
+    body: String,
+}
+
+// On-disk cache of successful API response bodies, one JSON file per request hash.
+// Only meant to spare the API while iterating on the same queries during development.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Default for ResponseCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_CACHE_DIR, DEFAULT_CACHE_TTL)
+    }
+}
+
+impl ResponseCache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl,
+        }
+    }
+
+    // Hash of everything that identifies a request: method, URL and body
+    pub fn cache_key(method: &str, url: &str, body: &str) -> String {
+        let mut hasher = Sha256::new();
+        for part in [method, url, body] {
+            hasher.update(part.as_bytes());
+            hasher.update([0u8]);
+        }
+
+        hasher.finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    fn is_fresh(&self, stored_at: u64) -> bool {
+        unix_now().saturating_sub(stored_at) < self.ttl.as_secs()
+    }
+
+    // The cached body for a key, if present and not older than the TTL
+    pub async fn get(&self, key: &str) -> Option<String> {
+        let content = tokio::fs::read_to_string(self.path_for(key)).await.ok()?;
+        let cached: CachedResponse = serde_json::from_str(&content).ok()?;
+
+        if self.is_fresh(cached.stored_at) {
+            Some(cached.body)
+        } else {
+            None
+        }
+    }
+
+    pub async fn put(&self, key: &str, body: &str) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        let cached = CachedResponse {
+            stored_at: unix_now(),
+            body: body.to_string(),
+        };
+        tokio::fs::write(self.path_for(key), serde_json::to_string(&cached)?).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key_depends_on_whole_request() {
+        let key = ResponseCache::cache_key("POST", "https://example.com/search", "{}");
+
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, ResponseCache::cache_key("POST", "https://example.com/search", "{}"));
+        assert_ne!(key, ResponseCache::cache_key("GET", "https://example.com/search", "{}"));
+        assert_ne!(key, ResponseCache::cache_key("POST", "https://example.com/search", "{\"a\":1}"));
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_and_expiry() {
+        let dir = std::env::temp_dir().join(format!("poe2-cache-test-{}", std::process::id()));
+        let cache = ResponseCache::new(&dir, Duration::from_secs(60));
+        let key = ResponseCache::cache_key("GET", "https://example.com/fetch/a", "");
+
+        assert!(cache.get(&key).await.is_none());
+        cache.put(&key, "{\"result\":[]}").await.unwrap();
+        assert_eq!(cache.get(&key).await.as_deref(), Some("{\"result\":[]}"));
+
+        let expired = ResponseCache::new(&dir, Duration::ZERO);
+        assert!(expired.get(&key).await.is_none());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,164 @@
+use futures_util::StreamExt;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+use crate::errors::{Result, ScraperError};
+use crate::models::ItemResponse;
+use super::rate_budget::LIVE_ENDPOINT;
+use super::trade_api::TradeApiClient;
+
+// Messages the live search socket sends us
+#[derive(Debug, PartialEq)]
+pub enum LiveMessage {
+    Auth(bool),
+    NewItems(Vec<String>),
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLiveMessage {
+    auth: Option<bool>,
+    new: Option<Vec<String>>,
+}
+
+pub fn parse_live_message(text: &str) -> Result<LiveMessage> {
+    let raw: RawLiveMessage = serde_json::from_str(text)?;
+
+    Ok(match (raw.auth, raw.new) {
+        (_, Some(ids)) => LiveMessage::NewItems(ids),
+        (Some(auth), None) => LiveMessage::Auth(auth),
+        (None, None) => LiveMessage::Other,
+    })
+}
+
+// Watches a saved trade search over the trade2 websocket and yields newly listed items
+pub struct LiveSearchClient {
+    client: TradeApiClient,
+    search_id: String,
+    buffer_size: usize,
+}
+
+impl LiveSearchClient {
+    pub fn new(client: TradeApiClient, search_id: String) -> Self {
+        Self {
+            client,
+            search_id,
+            buffer_size: 100,
+        }
+    }
+
+    pub fn with_buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size.max(1);
+        self
+    }
+
+    fn socket_url(&self) -> String {
+        format!(
+            "wss://www.pathofexile.com/api/trade2/live/poe2/{}/{}",
+            self.client.league(),
+            self.search_id
+        )
+    }
+
+    // Connect to the live search and stream item details through the returned channel.
+    // The channel closes when the socket does or when the receiver is dropped.
+    pub async fn start(mut self) -> Result<mpsc::Receiver<Result<ItemResponse>>> {
+        let url = self.socket_url();
+        let mut request = url.as_str()
+            .into_client_request()
+            .map_err(|e| ScraperError::NetworkError(e.to_string()))?;
+
+        let headers = request.headers_mut();
+        headers.insert("Origin", "https://www.pathofexile.com".parse().unwrap());
+        let user_agent = self.client.user_agent()
+            .parse()
+            .map_err(|_| ScraperError::ValidationError("User-Agent is not a valid header value".to_string()))?;
+        headers.insert("User-Agent", user_agent);
+        if let Some(sessid) = self.client.session_id() {
+            let cookie = format!("POESESSID={}", sessid)
+                .parse()
+                .map_err(|_| ScraperError::ValidationError("POESESSID is not a valid header value".to_string()))?;
+            headers.insert("Cookie", cookie);
+        }
+
+        info!(%url, "connecting to live search");
+        self.client.rate_budget().acquire(LIVE_ENDPOINT).await;
+        let (mut socket, _) = connect_async(request)
+            .await
+            .map_err(|e| ScraperError::NetworkError(format!("Live search connection failed: {}", e)))?;
+
+        let (sender, receiver) = mpsc::channel(self.buffer_size);
+
+        tokio::spawn(async move {
+            while let Some(message) = socket.next().await {
+                let text = match message {
+                    Ok(Message::Text(text)) => text,
+                    Ok(Message::Close(_)) => break,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        let _ = sender.send(Err(ScraperError::NetworkError(e.to_string()))).await;
+                        break;
+                    }
+                };
+
+                match parse_live_message(&text) {
+                    Ok(LiveMessage::NewItems(ids)) => {
+                        info!(count = ids.len(), "live search reported new listings");
+                        match self.client.fetch_items_lenient(&ids).await {
+                            Ok(report) => {
+                                for item in report.items {
+                                    if sender.send(Ok(item)).await.is_err() {
+                                        return;
+                                    }
+                                }
+                                for failure in report.failures {
+                                    let error = ScraperError::ParseError(format!(
+                                        "Failed to parse live listing {}: {}",
+                                        failure.id.as_deref().unwrap_or("<unknown>"),
+                                        failure.error
+                                    ));
+                                    if sender.send(Err(error)).await.is_err() {
+                                        return;
+                                    }
+                                }
+                            }
+                            Err(e) => {
+                                if sender.send(Err(e)).await.is_err() {
+                                    return;
+                                }
+                            }
+                        }
+                    }
+                    Ok(LiveMessage::Auth(false)) => {
+                        let _ = sender.send(Err(ScraperError::ApiError(
+                            "Live search rejected authentication; a valid POESESSID is required".to_string()
+                        ))).await;
+                        break;
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!(error = %e, "ignoring unparseable live search message"),
+                }
+            }
+        });
+
+        Ok(receiver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_live_messages() {
+        assert_eq!(parse_live_message(r#"{"auth": true}"#).unwrap(), LiveMessage::Auth(true));
+        assert_eq!(
+            parse_live_message(r#"{"new": ["a1", "b2"]}"#).unwrap(),
+            LiveMessage::NewItems(vec!["a1".to_string(), "b2".to_string()])
+        );
+        assert_eq!(parse_live_message(r#"{"something": 1}"#).unwrap(), LiveMessage::Other);
+        assert!(parse_live_message("not json").is_err());
+    }
+}
@@ -1,9 +1,26 @@
 mod trade_api;
+mod rate_limit;
+mod rate_budget;
+mod retry;
+mod auth;
+mod query_builder;
+mod live_search;
+mod cache;
+mod transport;
 
 pub use trade_api::{
     TradeApiClient,
+    TradeApiClientBuilder,
+    DEFAULT_USER_AGENT,
+    MAX_FETCH_IDS,
+    MAX_QUERY_STATS,
+    MAX_QUERY_STAT_GROUPS,
     SearchRequest,
     SearchResponse,
+    SearchPages,
+    FetchReport,
+    ParseFailure,
+    League,
     TradeQuery,
     StatusFilter,
     StatFilter,
@@ -13,5 +30,39 @@ pub use trade_api::{
     TypeFilters,
     CategoryFilter,
     CategoryOption,
+    RangeFilter,
+    OptionFilter,
+    MiscFilters,
+    MiscFilterValues,
+    SocketFilters,
+    SocketFilterValues,
+    EquipmentFilters,
+    EquipmentFilterValues,
+    MapFilters,
+    MapFilterValues,
+    RequirementFilters,
+    RequirementFilterValues,
+    TradeFilters,
+    TradeFilterValues,
+    PriceFilter,
     TradeStatus,
-};
\ No newline at end of file
+};
+
+pub use retry::RetryPolicy;
+pub use rate_limit::{RateLimitRule, parse_rules};
+pub use rate_budget::{
+    RateBudget, DATA_ENDPOINT, EXCHANGE_ENDPOINT, FETCH_ENDPOINT, LIVE_ENDPOINT, OAUTH_ENDPOINT, SEARCH_ENDPOINT,
+};
+pub use auth::OAuthConfig;
+pub use query_builder::QueryBuilder;
+pub use live_search::{LiveSearchClient, LiveMessage};
+pub use cache::ResponseCache;
+pub use transport::{
+    HttpTransport,
+    HttpResponse,
+    ReqwestTransport,
+    RecordedTransport,
+    RecordedExchange,
+    RecordingTransport,
+    SessionRecorder,
+};
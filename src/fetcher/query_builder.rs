@@ -0,0 +1,360 @@
+use super::trade_api::{
+    SearchRequest, TradeQuery, TradeStatus, StatusFilter, StatFilter,
+    StatFilterValue, StatValue, QueryFilters, TypeFilters, CategoryFilter,
+    CategoryOption, RangeFilter, OptionFilter, MiscFilters, SocketFilters,
+    EquipmentFilters, MapFilters, RequirementFilters, TradeFilters, PriceFilter,
+};
+use tracing::warn;
+use crate::models::{CoreAttribute, Currency};
+
+// Fluent builder for trade search requests, e.g.
+// QueryBuilder::new().category("armour.chest").online().stat("explicit.stat_x", 50, None).build()
+#[derive(Debug)]
+pub struct QueryBuilder {
+    status: TradeStatus,
+    category: String,
+    stat_filters: Vec<StatFilterValue>,
+    extra_stat_groups: Vec<StatFilter>,
+    misc_filters: Option<MiscFilters>,
+    socket_filters: Option<SocketFilters>,
+    equipment_filters: Option<EquipmentFilters>,
+    req_filters: Option<RequirementFilters>,
+    map_filters: Option<MapFilters>,
+    trade_filters: Option<TradeFilters>,
+    sort: Option<serde_json::Value>,
+}
+
+fn range(min: Option<u32>, max: Option<u32>) -> Option<RangeFilter> {
+    Some(RangeFilter { min, max })
+}
+
+impl Default for QueryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl QueryBuilder {
+    pub fn new() -> Self {
+        Self {
+            status: TradeStatus::Online,
+            category: "any".to_string(),
+            stat_filters: Vec::new(),
+            extra_stat_groups: Vec::new(),
+            misc_filters: None,
+            socket_filters: None,
+            equipment_filters: None,
+            req_filters: None,
+            map_filters: None,
+            trade_filters: None,
+            sort: Some(serde_json::json!({
+                "price": "asc"
+            })),
+        }
+    }
+
+    pub fn status(mut self, status: TradeStatus) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn online(self) -> Self {
+        self.status(TradeStatus::Online)
+    }
+
+    pub fn any_status(self) -> Self {
+        self.status(TradeStatus::Any)
+    }
+
+    // Trade category id such as "armour", "armour.chest" or "jewel"
+    pub fn category(mut self, category: &str) -> Self {
+        self.category = category.to_string();
+        self
+    }
+
+    // Add a stat to the main "and" group with optional min/max bounds
+    pub fn stat(
+        mut self,
+        id: &str,
+        min: impl Into<Option<u32>>,
+        max: impl Into<Option<u32>>,
+    ) -> Self {
+        let (min, max) = (min.into(), max.into());
+        let value = if min.is_some() || max.is_some() {
+            Some(StatValue { min, max })
+        } else {
+            None
+        };
+
+        self.stat_filters.push(StatFilterValue {
+            id: id.to_string(),
+            value,
+            disabled: false,
+        });
+        self
+    }
+
+    // Add a separate stat group such as "not", "count" or "weight"
+    pub fn stat_group(mut self, group_type: &str, stat_ids: &[&str]) -> Self {
+        self.extra_stat_groups.push(StatFilter {
+            r#type: group_type.to_string(),
+            filters: stat_ids.iter()
+                .map(|id| StatFilterValue {
+                    id: id.to_string(),
+                    value: None,
+                    disabled: false,
+                })
+                .collect(),
+            disabled: false,
+        });
+        self
+    }
+
+    pub fn item_level(mut self, min: impl Into<Option<u32>>, max: impl Into<Option<u32>>) -> Self {
+        self.misc_filters.get_or_insert_with(Default::default).filters.ilvl = range(min.into(), max.into());
+        self
+    }
+
+    pub fn quality(mut self, min: impl Into<Option<u32>>, max: impl Into<Option<u32>>) -> Self {
+        self.misc_filters.get_or_insert_with(Default::default).filters.quality = range(min.into(), max.into());
+        self
+    }
+
+    pub fn corrupted(mut self, corrupted: bool) -> Self {
+        self.misc_filters.get_or_insert_with(Default::default).filters.corrupted = Some(OptionFilter::flag(corrupted));
+        self
+    }
+
+    pub fn identified(mut self, identified: bool) -> Self {
+        self.misc_filters.get_or_insert_with(Default::default).filters.identified = Some(OptionFilter::flag(identified));
+        self
+    }
+
+    pub fn sockets(mut self, min: impl Into<Option<u32>>, max: impl Into<Option<u32>>) -> Self {
+        self.socket_filters.get_or_insert_with(Default::default).filters.sockets = range(min.into(), max.into());
+        self
+    }
+
+    pub fn links(mut self, min: impl Into<Option<u32>>, max: impl Into<Option<u32>>) -> Self {
+        self.socket_filters.get_or_insert_with(Default::default).filters.links = range(min.into(), max.into());
+        self
+    }
+
+    // PoE2 rune and soul core sockets; `sockets` is the older socket filter
+    pub fn rune_sockets(mut self, min: impl Into<Option<u32>>, max: impl Into<Option<u32>>) -> Self {
+        self.equipment_filters.get_or_insert_with(Default::default).filters.rune_sockets = range(min.into(), max.into());
+        self
+    }
+
+    pub fn level_requirement(mut self, min: impl Into<Option<u32>>, max: impl Into<Option<u32>>) -> Self {
+        self.req_filters.get_or_insert_with(Default::default).filters.lvl = range(min.into(), max.into());
+        self
+    }
+
+    // Filter on the item's Str/Dex/Int requirement
+    pub fn attribute_requirement(
+        mut self,
+        attr: CoreAttribute,
+        min: impl Into<Option<u32>>,
+        max: impl Into<Option<u32>>,
+    ) -> Self {
+        let filters = &mut self.req_filters.get_or_insert_with(Default::default).filters;
+        let bound = range(min.into(), max.into());
+        match attr {
+            CoreAttribute::Strength => filters.strength = bound,
+            CoreAttribute::Dexterity => filters.dexterity = bound,
+            CoreAttribute::Intelligence => filters.intelligence = bound,
+        }
+        self
+    }
+
+    // Waystone tier, 1 to 16
+    pub fn waystone_tier(mut self, min: impl Into<Option<u32>>, max: impl Into<Option<u32>>) -> Self {
+        self.map_filters.get_or_insert_with(Default::default).filters.map_tier = range(min.into(), max.into());
+        self
+    }
+
+    // Only match listings priced within the bounds; unset bounds are left open
+    pub fn price_range(mut self, min: impl Into<Option<f64>>, max: impl Into<Option<f64>>) -> Self {
+        let (min, max) = (min.into(), max.into());
+        if min.is_none() && max.is_none() {
+            return self;
+        }
+
+        let price = self.price_filter();
+        price.min = min;
+        price.max = max;
+        self
+    }
+
+    // Currency the price bounds are expressed in, e.g. "exalted" or "divine"
+    pub fn price_currency(mut self, currency: &Currency) -> Self {
+        self.price_filter().option = Some(currency.to_string());
+        self
+    }
+
+    fn price_filter(&mut self) -> &mut PriceFilter {
+        self.trade_filters
+            .get_or_insert_with(Default::default)
+            .filters
+            .price
+            .get_or_insert_with(Default::default)
+    }
+
+    // Sort by a field ("price", "indexed", a stat id...) in "asc" or "desc" order
+    pub fn sort_by(mut self, field: &str, direction: &str) -> Self {
+        self.sort = Some(serde_json::json!({ field: direction }));
+        self
+    }
+
+    // Warns when the query goes past the trade site's stat limits, which it would refuse
+    // rather than run
+    pub fn build(self) -> SearchRequest {
+        let mut stats = vec![StatFilter {
+            r#type: "and".to_string(),
+            filters: self.stat_filters,
+            disabled: false,
+        }];
+        stats.extend(self.extra_stat_groups);
+
+        let request = SearchRequest {
+            query: TradeQuery {
+                status: StatusFilter {
+                    option: self.status.as_str().to_string(),
+                },
+                stats,
+                filters: QueryFilters {
+                    type_filters: TypeFilters {
+                        filters: CategoryFilter {
+                            category: CategoryOption {
+                                option: self.category,
+                            },
+                        },
+                    },
+                    misc_filters: self.misc_filters,
+                    socket_filters: self.socket_filters,
+                    equipment_filters: self.equipment_filters,
+                    req_filters: self.req_filters,
+                    map_filters: self.map_filters,
+                    trade_filters: self.trade_filters,
+                },
+            },
+            sort: self.sort,
+        };
+        for problem in request.complexity_problems() {
+            warn!(category = %request.query.filters.type_filters.filters.category.option, problem, "search query is likely too complex");
+        }
+        request
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::{MAX_QUERY_STATS, MAX_QUERY_STAT_GROUPS};
+
+    #[test]
+    fn test_builder_produces_expected_payload() {
+        let request = QueryBuilder::new()
+            .category("armour.chest")
+            .online()
+            .stat("explicit.stat_3299347043", 50, None)
+            .stat_group("not", &["explicit.stat_803737631"])
+            .build();
+
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["query"]["status"]["option"], "online");
+        assert_eq!(json["query"]["filters"]["type_filters"]["filters"]["category"]["option"], "armour.chest");
+        assert_eq!(json["query"]["stats"][0]["type"], "and");
+        assert_eq!(json["query"]["stats"][0]["filters"][0]["id"], "explicit.stat_3299347043");
+        assert_eq!(json["query"]["stats"][0]["filters"][0]["value"]["min"], 50);
+        assert!(json["query"]["stats"][0]["filters"][0]["value"]["max"].is_null());
+        assert_eq!(json["query"]["stats"][1]["type"], "not");
+        assert_eq!(json["sort"]["price"], "asc");
+    }
+
+    #[test]
+    fn test_defaults_and_sort() {
+        let json = serde_json::to_value(
+            QueryBuilder::new().any_status().sort_by("indexed", "desc").build()
+        ).unwrap();
+
+        assert_eq!(json["query"]["status"]["option"], "any");
+        assert_eq!(json["query"]["filters"]["type_filters"]["filters"]["category"]["option"], "any");
+        assert_eq!(json["sort"]["indexed"], "desc");
+        assert!(json["query"]["stats"][0]["filters"].as_array().unwrap().is_empty());
+        assert!(json["query"]["filters"].get("misc_filters").is_none());
+        assert!(json["query"]["filters"].get("socket_filters").is_none());
+        assert!(json["query"]["filters"].get("req_filters").is_none());
+        assert!(json["query"]["filters"].get("map_filters").is_none());
+        assert!(json["query"]["filters"].get("trade_filters").is_none());
+    }
+
+    #[test]
+    fn test_price_filters() {
+        let json = serde_json::to_value(
+            QueryBuilder::new()
+                .price_range(1.0, 20.0)
+                .price_currency(&Currency::Exalted)
+                .build()
+        ).unwrap();
+
+        let price = &json["query"]["filters"]["trade_filters"]["filters"]["price"];
+        assert_eq!(price["min"], 1.0);
+        assert_eq!(price["max"], 20.0);
+        assert_eq!(price["option"], "exalted");
+
+        let json = serde_json::to_value(QueryBuilder::new().price_range(None, 5.0).build()).unwrap();
+        let price = &json["query"]["filters"]["trade_filters"]["filters"]["price"];
+        assert!(price.get("min").is_none());
+        assert!(price.get("option").is_none());
+
+        let json = serde_json::to_value(QueryBuilder::new().price_range(None, None).build()).unwrap();
+        assert!(json["query"]["filters"].get("trade_filters").is_none());
+    }
+
+    #[test]
+    fn test_complexity_limits() {
+        let ids: Vec<String> = (0..=MAX_QUERY_STATS).map(|i| format!("explicit.stat_{}", i)).collect();
+        let request = ids.iter().fold(QueryBuilder::new(), |builder, id| builder.stat(id, None, None)).build();
+        assert_eq!(request.complexity_problems(), vec!["36 stat filters, more than the 35 the trade site accepts"]);
+
+        let request = (0..MAX_QUERY_STAT_GROUPS)
+            .fold(QueryBuilder::new(), |builder, _| builder.stat_group("not", &["explicit.stat_1"]))
+            .build();
+        assert_eq!(request.complexity_problems().len(), 1);
+        assert!(QueryBuilder::new().stat("explicit.stat_1", 10, None).build().complexity_problems().is_empty());
+    }
+
+    #[test]
+    fn test_misc_socket_and_requirement_filters() {
+        let json = serde_json::to_value(
+            QueryBuilder::new()
+                .item_level(75, None)
+                .quality(None, 20)
+                .corrupted(false)
+                .identified(true)
+                .sockets(2, None)
+                .rune_sockets(1, 2)
+                .attribute_requirement(CoreAttribute::Strength, 100, 150)
+                .level_requirement(None, 60)
+                .waystone_tier(15, None)
+                .build()
+        ).unwrap();
+
+        let filters = &json["query"]["filters"];
+        assert_eq!(filters["misc_filters"]["filters"]["ilvl"]["min"], 75);
+        assert!(filters["misc_filters"]["filters"]["ilvl"].get("max").is_none());
+        assert_eq!(filters["misc_filters"]["filters"]["quality"]["max"], 20);
+        assert_eq!(filters["misc_filters"]["filters"]["corrupted"]["option"], "false");
+        assert_eq!(filters["misc_filters"]["filters"]["identified"]["option"], "true");
+        assert_eq!(filters["socket_filters"]["filters"]["sockets"]["min"], 2);
+        assert!(filters["socket_filters"]["filters"].get("links").is_none());
+        assert_eq!(filters["equipment_filters"]["filters"]["rune_sockets"]["min"], 1);
+        assert_eq!(filters["equipment_filters"]["filters"]["rune_sockets"]["max"], 2);
+        assert_eq!(filters["req_filters"]["filters"]["str"]["min"], 100);
+        assert_eq!(filters["req_filters"]["filters"]["str"]["max"], 150);
+        assert_eq!(filters["req_filters"]["filters"]["lvl"]["max"], 60);
+        assert_eq!(filters["map_filters"]["filters"]["map_tier"]["min"], 15);
+    }
+}
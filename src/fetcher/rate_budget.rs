@@ -0,0 +1,110 @@
+use reqwest::header::HeaderMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tracing::debug;
+use super::rate_limit::{RateLimitRule, RateLimiter};
+use crate::metrics::Metrics;
+
+// Endpoint policies requests are counted against. The data endpoints (leagues, item bases,
+// stats and uniques) share one policy on pathofexile.com.
+pub const SEARCH_ENDPOINT: &str = "search";
+pub const FETCH_ENDPOINT: &str = "fetch";
+pub const EXCHANGE_ENDPOINT: &str = "exchange";
+pub const DATA_ENDPOINT: &str = "data";
+// OAuth token requests and live search websocket connections
+pub const OAUTH_ENDPOINT: &str = "oauth";
+pub const LIVE_ENDPOINT: &str = "live";
+
+// Request budget for pathofexile.com shared by every HTTP caller in the process. Clones
+// share one RateLimiter, so a trade client searching while a loader refreshes item bases
+// can't together go over the limits either one alone would respect.
+#[derive(Debug, Clone, Default)]
+pub struct RateBudget {
+    limiter: Arc<Mutex<RateLimiter>>,
+}
+
+impl RateBudget {
+    // A budget of its own, not shared with the rest of the process
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The budget every client and loader uses unless given another
+    pub fn global() -> Self {
+        static GLOBAL: OnceLock<RateBudget> = OnceLock::new();
+        GLOBAL.get_or_init(RateBudget::new).clone()
+    }
+
+    fn limiter(&self) -> std::sync::MutexGuard<'_, RateLimiter> {
+        // The limiter holds no invariants a panicking holder could break half-way
+        self.limiter.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn wait_time(&self, endpoint: &str) -> Duration {
+        self.limiter().wait_time(endpoint)
+    }
+
+    // Wait until the endpoint's policy allows a request and count it. The lock isn't held
+    // while sleeping, so waiting on one endpoint doesn't hold up the others; the wait is
+    // checked again afterwards as another caller may have taken the slot.
+    pub async fn acquire(&self, endpoint: &str) {
+        let mut waited = Duration::ZERO;
+        loop {
+            let (wait, policy) = {
+                let mut limiter = self.limiter();
+                let wait = limiter.wait_time(endpoint);
+                if wait.is_zero() {
+                    limiter.record(endpoint);
+                    if !waited.is_zero() {
+                        Metrics::global().record_rate_limit_wait(endpoint, waited);
+                    }
+                    return;
+                }
+                (wait, limiter.policy_name(endpoint).unwrap_or(endpoint).to_string())
+            };
+            debug!(?wait, policy, "rate budget delaying request");
+            tokio::time::sleep(wait).await;
+            waited += wait;
+        }
+    }
+
+    pub fn rules(&self, endpoint: &str) -> Vec<RateLimitRule> {
+        self.limiter().rules(endpoint).to_vec()
+    }
+
+    pub fn update_from_headers(&self, endpoint: &str, headers: &HeaderMap) {
+        self.limiter().update_from_headers(endpoint, headers);
+    }
+
+    // Requests let through across every holder of the budget
+    pub fn requests_sent(&self) -> u64 {
+        self.limiter().requests_sent()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[tokio::test]
+    async fn test_clones_share_one_budget() {
+        let budget = RateBudget::new();
+        let loader = budget.clone();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-rate-limit-rules", HeaderValue::from_static("Ip"));
+        headers.insert("x-rate-limit-ip", HeaderValue::from_static("2:10:60"));
+        headers.insert("x-rate-limit-ip-state", HeaderValue::from_static("1:10:0"));
+        budget.update_from_headers(DATA_ENDPOINT, &headers);
+
+        // The second hit of the window is the loader's, which leaves the client none
+        loader.acquire(DATA_ENDPOINT).await;
+        assert_eq!(budget.requests_sent(), 1);
+        assert!(budget.wait_time(DATA_ENDPOINT) > Duration::from_secs(9));
+        assert!(budget.wait_time(SEARCH_ENDPOINT).is_zero());
+        assert!(RateBudget::new().wait_time(DATA_ENDPOINT).is_zero());
+
+        assert!(Arc::ptr_eq(&RateBudget::global().limiter, &RateBudget::global().limiter));
+    }
+}
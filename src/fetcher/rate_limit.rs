@@ -0,0 +1,311 @@
+use reqwest::header::HeaderMap;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+// A single "hits:period:penalty" rule from an X-Rate-Limit-<Rule> header
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitRule {
+    pub max_hits: u32,
+    pub period: Duration,
+    pub penalty: Duration,
+}
+
+// A single "hits:period:restricted" entry from an X-Rate-Limit-<Rule>-State header
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitState {
+    pub hits: u32,
+    pub period: Duration,
+    pub restricted_for: Duration,
+}
+
+// Tracks the rules and our own request history for one endpoint
+#[derive(Debug, Default)]
+struct PolicyBucket {
+    policy_name: Option<String>,
+    rules: Vec<RateLimitRule>,
+    history: VecDeque<Instant>,
+    restricted_until: Option<Instant>,
+}
+
+impl PolicyBucket {
+    fn wait_time(&self, now: Instant) -> Duration {
+        let mut wait = self.restricted_until
+            .map(|until| until.saturating_duration_since(now))
+            .unwrap_or_default();
+
+        for rule in &self.rules {
+            if rule.max_hits == 0 {
+                continue;
+            }
+
+            // Requests still counting towards this rule's window, newest first
+            let in_window: Vec<&Instant> = self.history
+                .iter()
+                .rev()
+                .filter(|&&at| now.duration_since(at) < rule.period)
+                .collect();
+
+            if in_window.len() as u32 >= rule.max_hits {
+                // We have to wait until the oldest request that keeps us at the limit expires
+                let blocking = in_window[rule.max_hits as usize - 1];
+                let expires = *blocking + rule.period;
+                wait = wait.max(expires.saturating_duration_since(now));
+            }
+        }
+
+        wait
+    }
+
+    fn longest_period(&self) -> Duration {
+        self.rules.iter()
+            .map(|rule| rule.period)
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn prune(&mut self, now: Instant) {
+        let keep = self.longest_period();
+        while let Some(&oldest) = self.history.front() {
+            if now.duration_since(oldest) >= keep {
+                self.history.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+// Adaptive rate limiter driven by the trade API's X-Rate-Limit-* headers.
+// Each endpoint gets its own bucket since search and fetch use different policies.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: HashMap<String, PolicyBucket>,
+    // Requests let through over the limiter's lifetime, across all endpoints
+    requests_sent: u64,
+}
+
+impl RateLimiter {
+    pub fn requests_sent(&self) -> u64 {
+        self.requests_sent
+    }
+
+    // How long a request to this endpoint would have to wait right now
+    pub fn wait_time(&self, endpoint: &str) -> Duration {
+        self.buckets
+            .get(endpoint)
+            .map(|bucket| bucket.wait_time(Instant::now()))
+            .unwrap_or_default()
+    }
+
+    // The policy the server named for the endpoint, e.g. "trade-fetch-request-limit"
+    pub fn policy_name(&self, endpoint: &str) -> Option<&str> {
+        self.buckets.get(endpoint).and_then(|bucket| bucket.policy_name.as_deref())
+    }
+
+    // The rules the server last gave for the endpoint; none until it has answered once
+    pub fn rules(&self, endpoint: &str) -> &[RateLimitRule] {
+        self.buckets.get(endpoint).map(|bucket| bucket.rules.as_slice()).unwrap_or_default()
+    }
+
+    // Count a request sent now against the endpoint's bucket, without waiting
+    pub fn record(&mut self, endpoint: &str) {
+        let now = Instant::now();
+        let bucket = self.buckets.entry(endpoint.to_string()).or_default();
+        bucket.prune(now);
+        bucket.history.push_back(now);
+        self.requests_sent += 1;
+    }
+
+    // Update the endpoint's rules and state from a response's headers
+    pub fn update_from_headers(&mut self, endpoint: &str, headers: &HeaderMap) {
+        let now = Instant::now();
+        let bucket = self.buckets.entry(endpoint.to_string()).or_default();
+
+        if let Some(policy) = header_str(headers, "x-rate-limit-policy") {
+            bucket.policy_name = Some(policy.to_string());
+        }
+
+        if let Some(rule_names) = header_str(headers, "x-rate-limit-rules") {
+            let mut rules = Vec::new();
+
+            for rule_name in rule_names.split(',').map(str::trim).filter(|r| !r.is_empty()) {
+                let rule_header = format!("x-rate-limit-{}", rule_name.to_lowercase());
+                let state_header = format!("{}-state", rule_header);
+
+                let rule_set = header_str(headers, &rule_header)
+                    .map(parse_rules)
+                    .unwrap_or_default();
+                let states = header_str(headers, &state_header)
+                    .map(parse_states)
+                    .unwrap_or_default();
+
+                // States are reported in the same order as the rules they belong to
+                for (index, state) in states.iter().enumerate() {
+                    // An active restriction means we are already being penalised
+                    if !state.restricted_for.is_zero() {
+                        restrict(bucket, now + state.restricted_for);
+                    } else if let Some(rule) = rule_set.get(index) {
+                        // Over the limit without a reported restriction yet; assume the penalty applies
+                        if state.hits > rule.max_hits {
+                            restrict(bucket, now + rule.penalty);
+                        }
+                    }
+
+                    // The server may have seen requests we didn't make (another process,
+                    // a browser tab), so pad our history to match its view
+                    let local_hits = bucket.history
+                        .iter()
+                        .filter(|&&at| now.duration_since(at) < state.period)
+                        .count() as u32;
+                    for _ in local_hits..state.hits {
+                        bucket.history.push_back(now);
+                    }
+                }
+
+                rules.extend(rule_set);
+            }
+
+            if !rules.is_empty() {
+                bucket.rules = rules;
+            }
+        }
+
+        if let Some(retry_after) = parse_retry_after(headers) {
+            restrict(bucket, now + retry_after);
+        }
+
+        bucket.prune(now);
+    }
+}
+
+fn restrict(bucket: &mut PolicyBucket, until: Instant) {
+    bucket.restricted_until = Some(match bucket.restricted_until {
+        Some(existing) if existing > until => existing,
+        _ => until,
+    });
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+fn parse_triplets(value: &str) -> Vec<(u32, u64, u64)> {
+    value.split(',')
+        .filter_map(|part| {
+            let mut fields = part.trim().split(':').map(|f| f.trim().parse::<u64>());
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(Ok(a)), Some(Ok(b)), Some(Ok(c))) => Some((a as u32, b, c)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// Parse "8:10:60,15:60:300" into rules
+pub fn parse_rules(value: &str) -> Vec<RateLimitRule> {
+    parse_triplets(value)
+        .into_iter()
+        .map(|(max_hits, period, penalty)| RateLimitRule {
+            max_hits,
+            period: Duration::from_secs(period),
+            penalty: Duration::from_secs(penalty),
+        })
+        .collect()
+}
+
+// Parse "1:10:0,1:60:0" into rule states
+pub fn parse_states(value: &str) -> Vec<RateLimitState> {
+    parse_triplets(value)
+        .into_iter()
+        .map(|(hits, period, restricted)| RateLimitState {
+            hits,
+            period: Duration::from_secs(period),
+            restricted_for: Duration::from_secs(restricted),
+        })
+        .collect()
+}
+
+pub fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    header_str(headers, "retry-after")
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_str(value).unwrap());
+        }
+        map
+    }
+
+    #[test]
+    fn test_parse_rules_and_states() {
+        let rules = parse_rules("8:10:60,15:60:300");
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].max_hits, 8);
+        assert_eq!(rules[0].period, Duration::from_secs(10));
+        assert_eq!(rules[1].penalty, Duration::from_secs(300));
+
+        let states = parse_states("1:10:0,16:60:120");
+        assert_eq!(states[1].hits, 16);
+        assert_eq!(states[1].restricted_for, Duration::from_secs(120));
+
+        assert!(parse_rules("garbage").is_empty());
+    }
+
+    #[test]
+    fn test_unknown_endpoint_does_not_wait() {
+        let limiter = RateLimiter::default();
+        assert!(limiter.wait_time("search").is_zero());
+    }
+
+    #[test]
+    fn test_waits_when_state_reports_limit_reached() {
+        let mut limiter = RateLimiter::default();
+        limiter.update_from_headers("fetch", &headers(&[
+            ("x-rate-limit-policy", "trade-fetch-request-limit"),
+            ("x-rate-limit-rules", "Ip"),
+            ("x-rate-limit-ip", "2:10:60"),
+            ("x-rate-limit-ip-state", "2:10:0"),
+        ]));
+
+        let wait = limiter.wait_time("fetch");
+        assert!(wait > Duration::from_secs(9) && wait <= Duration::from_secs(10));
+        assert!(limiter.wait_time("search").is_zero());
+    }
+
+    #[test]
+    fn test_no_wait_below_limit() {
+        let mut limiter = RateLimiter::default();
+        limiter.update_from_headers("search", &headers(&[
+            ("x-rate-limit-rules", "Ip,Account"),
+            ("x-rate-limit-ip", "8:10:60"),
+            ("x-rate-limit-ip-state", "1:10:0"),
+            ("x-rate-limit-account", "3:5:60"),
+            ("x-rate-limit-account-state", "1:5:0"),
+        ]));
+
+        assert!(limiter.wait_time("search").is_zero());
+    }
+
+    #[test]
+    fn test_retry_after_and_restriction() {
+        let mut limiter = RateLimiter::default();
+        limiter.update_from_headers("search", &headers(&[("retry-after", "30")]));
+        assert!(limiter.wait_time("search") > Duration::from_secs(29));
+
+        let mut limiter = RateLimiter::default();
+        limiter.update_from_headers("search", &headers(&[
+            ("x-rate-limit-rules", "Ip"),
+            ("x-rate-limit-ip", "8:10:60"),
+            ("x-rate-limit-ip-state", "9:10:60"),
+        ]));
+        assert!(limiter.wait_time("search") > Duration::from_secs(59));
+    }
+}
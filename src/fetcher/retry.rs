@@ -0,0 +1,104 @@
+use reqwest::StatusCode;
+use std::time::Duration;
+
+// Controls how failed trade API requests are retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub backoff_base: Duration,
+    pub max_backoff: Duration,
+    pub jitter: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            backoff_base: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            jitter: Duration::from_millis(250),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Whether another attempt is allowed after `attempt` (0-based) failed
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt + 1 < self.max_attempts
+    }
+
+    // Exponential backoff for the given 0-based attempt, capped and with random jitter added
+    pub fn delay_for(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        let backoff = self.backoff_base
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff);
+
+        let jitter_ms = self.jitter.as_millis() as u64;
+        let jitter = if jitter_ms > 0 {
+            Duration::from_millis(rand::random::<u64>() % jitter_ms)
+        } else {
+            Duration::ZERO
+        };
+
+        backoff + jitter
+    }
+
+    pub fn is_retryable_status(status: StatusCode) -> bool {
+        status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+    }
+
+    pub fn is_retryable_error(err: &reqwest::Error) -> bool {
+        err.is_timeout() || err.is_connect() || err.is_request()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let policy = RetryPolicy {
+            max_attempts: 10,
+            backoff_base: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            jitter: Duration::ZERO,
+        };
+
+        assert_eq!(policy.delay_for(0), Duration::from_secs(1));
+        assert_eq!(policy.delay_for(1), Duration::from_secs(2));
+        assert_eq!(policy.delay_for(2), Duration::from_secs(4));
+        assert_eq!(policy.delay_for(3), Duration::from_secs(5));
+        assert_eq!(policy.delay_for(40), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_jitter_is_bounded() {
+        let policy = RetryPolicy {
+            backoff_base: Duration::from_millis(100),
+            jitter: Duration::from_millis(50),
+            ..RetryPolicy::default()
+        };
+        for _ in 0..20 {
+            let delay = policy.delay_for(0);
+            assert!(delay >= Duration::from_millis(100) && delay < Duration::from_millis(150));
+        }
+    }
+
+    #[test]
+    fn test_attempt_limits_and_statuses() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            ..RetryPolicy::default()
+        };
+        assert!(policy.should_retry(0));
+        assert!(policy.should_retry(1));
+        assert!(!policy.should_retry(2));
+
+        assert!(RetryPolicy::is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(RetryPolicy::is_retryable_status(StatusCode::BAD_GATEWAY));
+        assert!(!RetryPolicy::is_retryable_status(StatusCode::BAD_REQUEST));
+    }
+}
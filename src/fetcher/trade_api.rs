@@ -1,10 +1,35 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use crate::errors::Result;
+use crate::models::ItemResponse;
+use crate::errors::ScraperError;
+use super::rate_budget::{RateBudget, DATA_ENDPOINT, EXCHANGE_ENDPOINT, FETCH_ENDPOINT, SEARCH_ENDPOINT};
+use super::retry::RetryPolicy;
+use super::auth::{Credentials, OAuthConfig};
+use super::query_builder::QueryBuilder;
+use super::cache::ResponseCache;
+use super::transport::{HttpResponse, HttpTransport, RecordingTransport, ReqwestTransport, SessionRecorder};
+use crate::metrics::Metrics;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
-use crate::models::{Item, ItemResponse};
-use rand; // 0.8.4
-use crate::ScraperError;
+use tracing::{debug, info, trace, warn};
+use futures_util::stream::{self, FuturesUnordered, Stream, StreamExt};
+use tokio_util::sync::CancellationToken;
+
+// The fetch endpoint accepts at most this many IDs per request
+pub const MAX_FETCH_IDS: usize = 10;
+
+// Past these the search endpoint answers "Query is too complex". The site doesn't publish
+// its limits; these are where its own search form stops letting filters be added.
+pub const MAX_QUERY_STATS: usize = 35;
+pub const MAX_QUERY_STAT_GROUPS: usize = 10;
+
+// Fetch batches kept in flight at once unless configured otherwise
+const DEFAULT_MAX_IN_FLIGHT: usize = 2;
+
+// Identify ourselves to GGG rather than pretending to be a browser
+pub const DEFAULT_USER_AGENT: &str = concat!("rust-poe2-scraper/", env!("CARGO_PKG_VERSION"));
 
 #[derive(Debug, Serialize)]
 pub struct SearchRequest {
@@ -12,6 +37,27 @@ pub struct SearchRequest {
     pub sort: Option<serde_json::Value>,
 }
 
+impl SearchRequest {
+    // Ways the query goes past the known stat limits, so the search would be refused
+    // with ScraperError::QueryTooComplex
+    pub fn complexity_problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let groups = &self.query.stats;
+        let stats: usize = groups.iter().map(|group| group.filters.len()).sum();
+        if stats > MAX_QUERY_STATS {
+            problems.push(format!("{} stat filters, more than the {} the trade site accepts", stats, MAX_QUERY_STATS));
+        }
+        if groups.len() > MAX_QUERY_STAT_GROUPS {
+            problems.push(format!(
+                "{} stat groups, more than the {} the trade site accepts",
+                groups.len(),
+                MAX_QUERY_STAT_GROUPS
+            ));
+        }
+        problems
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SearchResponse {
     result: Vec<String>,
@@ -23,13 +69,314 @@ impl SearchResponse {
     pub fn get_result_ids(&self) -> &[String] {
         &self.result
     }
+
+    // Total number of listings matching the query, which can exceed the IDs returned
+    pub fn total(&self) -> u32 {
+        self.total
+    }
+
+    // ID of the search, as used in trade site URLs and by live search
+    pub fn search_id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct League {
+    pub id: String,
+    pub realm: Option<String>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LeaguesResponse {
+    result: Vec<League>,
+}
+
+// Wait for a slot in the rate budget unless shutdown is requested first. A free slot is
+// taken even after cancellation, so a batch under way can finish.
+async fn wait_for_budget(rate_budget: &RateBudget, cancellation: &CancellationToken, endpoint: &str) -> Result<()> {
+    tokio::select! {
+        biased;
+        _ = rate_budget.acquire(endpoint) => Ok(()),
+        _ = cancellation.cancelled() => {
+            Err(ScraperError::Cancelled(format!("waiting on the {} rate limit", endpoint)))
+        }
+    }
+}
+
+// Count a trade API response, or the failure to get one, in the process's metrics
+fn record_response(endpoint: &str, sent: Result<HttpResponse>) -> Result<HttpResponse> {
+    Metrics::global().record_api_response(endpoint, sent.as_ref().ok().map(|response| response.status));
+    sent
+}
+
+// Keep only PoE2 leagues; entries without a realm are assumed to belong to it
+fn poe2_leagues(response: LeaguesResponse) -> Vec<League> {
+    response.result
+        .into_iter()
+        .filter(|league| league.realm.as_deref().is_none_or(|realm| realm == "poe2"))
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeAmount {
+    amount: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeOffer {
+    // What the seller asks for, in the currency we offered
+    exchange: ExchangeAmount,
+    // What the seller gives, in the currency we want
+    item: ExchangeAmount,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeListing {
+    offers: Vec<ExchangeOffer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeResult {
+    listing: ExchangeListing,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExchangeResponse {
+    #[serde(default)]
+    result: HashMap<String, ExchangeResult>,
+}
+
+// Median of the offered ratios, as units of the offered currency per unit wanted.
+// The median keeps a few troll offers from skewing the rate.
+fn median_exchange_rate(response: &ExchangeResponse) -> Option<f64> {
+    let mut ratios: Vec<f64> = response.result
+        .values()
+        .flat_map(|result| &result.listing.offers)
+        .filter(|offer| offer.item.amount > 0.0)
+        .map(|offer| offer.exchange.amount / offer.item.amount)
+        .collect();
+    if ratios.is_empty() {
+        return None;
+    }
+
+    ratios.sort_by(|a, b| a.total_cmp(b));
+    Some(ratios[ratios.len() / 2])
+}
+
+// An item from a fetch response that could not be deserialized
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseFailure {
+    pub id: Option<String>,
+    pub error: String,
+}
+
+// Items parsed from a lenient fetch along with the ones that were skipped
+#[derive(Debug, Default)]
+pub struct FetchReport {
+    pub items: Vec<ItemResponse>,
+    pub failures: Vec<ParseFailure>,
+}
+
+impl FetchReport {
+    pub fn merge(&mut self, other: FetchReport) {
+        self.items.extend(other.items);
+        self.failures.extend(other.failures);
+    }
+
+    pub fn attempted(&self) -> usize {
+        self.items.len() + self.failures.len()
+    }
+}
+
+fn parse_fetched_item(raw_item: serde_json::Value) -> std::result::Result<ItemResponse, ParseFailure> {
+    let id = raw_item["id"].as_str().map(str::to_string);
+    serde_json::from_value(raw_item).map_err(|e| ParseFailure {
+        id,
+        error: e.to_string(),
+    })
+}
+
+// Append the listings in a fetch response body
+fn extend_fetched_items(items: &mut Vec<serde_json::Value>, body: &str) -> Result<()> {
+    let json_response: serde_json::Value = serde_json::from_str(body)?;
+    if let Some(result) = json_response["result"].as_array() {
+        items.extend(result.iter().cloned());
+    }
+    Ok(())
+}
+
+// Walks the result IDs of a search one fetch-sized page at a time
+#[derive(Debug)]
+pub struct SearchPages {
+    ids: Vec<String>,
+    page_size: usize,
+    position: usize,
+}
+
+impl SearchPages {
+    fn new(response: SearchResponse, page_size: usize) -> Self {
+        Self {
+            ids: response.result,
+            page_size: page_size.clamp(1, MAX_FETCH_IDS),
+            position: 0,
+        }
+    }
+
+    pub fn result_ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.ids.len() - self.position
+    }
+
+    // Fetch the next page of items, or None once every result ID has been fetched
+    pub async fn next_page(&mut self, client: &mut TradeApiClient) -> Option<Result<FetchReport>> {
+        if self.remaining() == 0 {
+            return None;
+        }
+
+        let end = (self.position + self.page_size).min(self.ids.len());
+        let page_ids = &self.ids[self.position..end];
+        self.position = end;
+
+        Some(client.fetch_items_lenient(page_ids).await)
+    }
+
+    // Yield the remaining items one at a time, fetching a page whenever the last one runs
+    // out, so only a single page is held in memory. The stream ends after the first error.
+    pub fn into_stream(self, client: &mut TradeApiClient) -> impl Stream<Item = Result<ItemResponse>> + '_ {
+        stream::unfold((self, client, VecDeque::new()), |(mut pages, client, mut buffered)| async move {
+            loop {
+                if let Some(item) = buffered.pop_front() {
+                    return Some((Ok(item), (pages, client, buffered)));
+                }
+                match pages.next_page(client).await? {
+                    Ok(report) => buffered.extend(report.items),
+                    Err(e) => {
+                        pages.position = pages.ids.len();
+                        return Some((Err(e), (pages, client, buffered)));
+                    }
+                }
+            }
+        })
+    }
+}
+
+// Configures the HTTP client underneath TradeApiClient: proxy, User-Agent and timeout
+#[derive(Debug)]
+pub struct TradeApiClientBuilder {
+    league: String,
+    user_agent: String,
+    proxy: Option<String>,
+    timeout: Option<Duration>,
+    rate_budget: RateBudget,
+    transport: Option<Arc<dyn HttpTransport>>,
+    recorder: Option<Arc<SessionRecorder>>,
+}
+
+impl TradeApiClientBuilder {
+    pub fn new(league: String) -> Self {
+        Self {
+            league,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            proxy: None,
+            timeout: None,
+            rate_budget: RateBudget::global(),
+            transport: None,
+            recorder: None,
+        }
+    }
+
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    // Route all requests through a proxy, e.g. "http://proxy:8080" or "socks5://127.0.0.1:1080"
+    pub fn proxy(mut self, url: &str) -> Self {
+        self.proxy = Some(url.to_string());
+        self
+    }
+
+    // Give up on any single request after this long
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    // Count requests against this budget instead of the process-wide one
+    pub fn rate_budget(mut self, rate_budget: RateBudget) -> Self {
+        self.rate_budget = rate_budget;
+        self
+    }
+
+    // Send trade requests through this transport instead of the network, e.g. a
+    // RecordedTransport in tests. Proxy and timeout settings don't apply to it.
+    pub fn transport(mut self, transport: Arc<dyn HttpTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    // Record every request and its response to the recorder's session directory
+    pub fn record(mut self, recorder: Arc<SessionRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn build(self) -> Result<TradeApiClient> {
+        let mut builder = Client::builder().user_agent(self.user_agent.as_str());
+
+        if let Some(url) = &self.proxy {
+            let proxy = reqwest::Proxy::all(url.as_str())
+                .map_err(|e| ScraperError::ValidationError(format!("Invalid proxy URL '{}': {}", url, e)))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(timeout) = self.timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        let client = builder.build()?;
+        let mut transport = self.transport
+            .unwrap_or_else(|| Arc::new(ReqwestTransport::new(client.clone())));
+        if let Some(recorder) = self.recorder {
+            transport = Arc::new(RecordingTransport::new(transport, recorder));
+        }
+
+        Ok(TradeApiClient {
+            client,
+            transport,
+            league: self.league,
+            user_agent: self.user_agent,
+            rate_budget: self.rate_budget,
+            api_calls: 0,
+            retry_policy: RetryPolicy::default(),
+            credentials: Credentials::default(),
+            max_in_flight: DEFAULT_MAX_IN_FLIGHT,
+            cache: None,
+            cancellation: CancellationToken::new(),
+        })
+    }
 }
 
 pub struct TradeApiClient {
+    // Builds requests and fetches OAuth tokens; the transport sends the requests
     client: Client,
+    transport: Arc<dyn HttpTransport>,
     league: String,
-    last_request: Instant,
-    rate_limit_delay: Duration,
+    user_agent: String,
+    // Shared with every other caller of pathofexile.com in the process
+    rate_budget: RateBudget,
+    // Requests this client sent, out of all those the budget let through
+    api_calls: u64,
+    retry_policy: RetryPolicy,
+    credentials: Credentials,
+    max_in_flight: usize,
+    cache: Option<ResponseCache>,
+    // Cut rate limit waits and retry backoffs short on shutdown
+    cancellation: CancellationToken,
 }
 
 #[derive(Debug, Serialize)]
@@ -49,6 +396,134 @@ pub struct TradeQuery {
 #[derive(Debug, Serialize)]
 pub struct QueryFilters {
     pub type_filters: TypeFilters,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub misc_filters: Option<MiscFilters>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub socket_filters: Option<SocketFilters>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub equipment_filters: Option<EquipmentFilters>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub req_filters: Option<RequirementFilters>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map_filters: Option<MapFilters>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trade_filters: Option<TradeFilters>,
+}
+
+// A numeric min/max bound; missing bounds are left out of the payload
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RangeFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<u32>,
+}
+
+// A yes/no style filter, serialized as {"option": "true"|"false"}
+#[derive(Debug, Clone, Serialize)]
+pub struct OptionFilter {
+    pub option: String,
+}
+
+impl OptionFilter {
+    pub fn flag(value: bool) -> Self {
+        Self {
+            option: value.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TradeFilters {
+    pub filters: TradeFilterValues,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TradeFilterValues {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<PriceFilter>,
+}
+
+// Listing price bounds; `option` is the currency the bounds are expressed in
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PriceFilter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub option: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MiscFilters {
+    pub filters: MiscFilterValues,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MiscFilterValues {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ilvl: Option<RangeFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<RangeFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub corrupted: Option<OptionFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identified: Option<OptionFilter>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SocketFilters {
+    pub filters: SocketFilterValues,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SocketFilterValues {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sockets: Option<RangeFilter>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<RangeFilter>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct EquipmentFilters {
+    pub filters: EquipmentFilterValues,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct EquipmentFilterValues {
+    // Sockets for runes and soul cores
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rune_sockets: Option<RangeFilter>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MapFilters {
+    pub filters: MapFilterValues,
+}
+
+// Waystone filters; the trade site still calls waystones maps
+#[derive(Debug, Default, Serialize)]
+pub struct MapFilterValues {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map_tier: Option<RangeFilter>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RequirementFilters {
+    pub filters: RequirementFilterValues,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct RequirementFilterValues {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lvl: Option<RangeFilter>,
+    #[serde(rename = "str", skip_serializing_if = "Option::is_none")]
+    pub strength: Option<RangeFilter>,
+    #[serde(rename = "dex", skip_serializing_if = "Option::is_none")]
+    pub dexterity: Option<RangeFilter>,
+    #[serde(rename = "int", skip_serializing_if = "Option::is_none")]
+    pub intelligence: Option<RangeFilter>,
 }
 
 #[derive(Debug, Serialize)]
@@ -92,7 +567,7 @@ pub struct StatusFilter {
 }
 
 impl TradeStatus {
-    fn as_str(&self) -> &'static str {
+    pub fn as_str(&self) -> &'static str {
         match self {
             TradeStatus::Online => "online",
             TradeStatus::OnlineLeague => "onlineleague",
@@ -103,137 +578,445 @@ impl TradeStatus {
 
 impl TradeApiClient {
     pub fn new(league: String) -> Self {
-        Self {
-            client: Client::new(),
-            league,
-            last_request: Instant::now(),
-            rate_limit_delay: Duration::from_millis(100),
+        // Without a proxy to parse this can only fail where reqwest's Client::new() would panic too
+        Self::builder(league)
+            .build()
+            .expect("failed to build the default HTTP client")
+    }
+
+    pub fn builder(league: String) -> TradeApiClientBuilder {
+        TradeApiClientBuilder::new(league)
+    }
+
+    pub fn league(&self) -> &str {
+        &self.league
+    }
+
+    // The request budget this client draws on, with the limits the server has reported
+    pub fn rate_budget(&self) -> &RateBudget {
+        &self.rate_budget
+    }
+
+    // Requests actually sent to the API so far; cache hits don't count
+    pub fn api_calls(&self) -> u64 {
+        self.api_calls
+    }
+
+    async fn acquire(&mut self, endpoint: &str) -> Result<()> {
+        wait_for_budget(&self.rate_budget, &self.cancellation, endpoint).await?;
+        self.api_calls += 1;
+        Ok(())
+    }
+
+    // Wait out a retry backoff unless shutdown is requested first
+    async fn backoff(&self, delay: Duration) -> Result<()> {
+        tokio::select! {
+            biased;
+            _ = self.cancellation.cancelled() => Err(ScraperError::Cancelled("waiting to retry a request".to_string())),
+            _ = tokio::time::sleep(delay) => Ok(()),
         }
     }
 
-    async fn process_raw_item(&self, raw_item: serde_json::Value) -> Result<ItemResponse> {
-        println!("Processing raw item structure:");
-        println!("{}", serde_json::to_string_pretty(&raw_item).unwrap_or_default());
-        
-        match serde_json::from_value::<ItemResponse>(raw_item.clone()) {
-            Ok(response) => {
-                println!("Successfully processed item:");
-                println!("  ID: {}", response.id);
-                println!("  Base Type: {}", response.item.base_type);
-                println!("  Type Line: {}", response.item.type_line);
-                println!("  Price: {} {}", response.listing.price.amount, response.listing.price.currency);
-                
-                Ok(response)
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.credentials.session_id()
+    }
+
+    // Authenticate requests with a logged-in POESESSID cookie
+    pub fn with_session(mut self, sessid: String) -> Self {
+        self.credentials = Credentials::session(sessid);
+        self
+    }
+
+    // Authenticate requests with an OAuth client-credentials token, refreshed when it expires
+    pub fn with_oauth(mut self, config: OAuthConfig) -> Self {
+        self.credentials = Credentials::oauth(config);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    // Serve repeated searches and fetches from an on-disk cache
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    // Stop waiting on rate limits and retries once `token` is cancelled, failing with
+    // ScraperError::Cancelled; requests already sent are still finished
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    // Neither read nor write the on-disk response cache
+    pub fn without_cache(mut self) -> Self {
+        self.cache = None;
+        self
+    }
+
+    // Number of fetch batches allowed in flight at once (at least 1)
+    pub fn with_concurrency(mut self, max_in_flight: usize) -> Self {
+        self.max_in_flight = max_in_flight.max(1);
+        self
+    }
+
+    // Currently active PoE2 trade leagues, including HC/SSF variants
+    #[tracing::instrument(skip_all)]
+    pub async fn list_leagues(&mut self) -> Result<Vec<League>> {
+        self.acquire(DATA_ENDPOINT).await?;
+
+        let request = self.client
+            .get("https://www.pathofexile.com/api/trade2/data/leagues")
+            .header("Accept", "*/*");
+        let request = self.credentials.authorize(&self.client, &self.rate_budget, request).await?.build()?;
+        let response = record_response(DATA_ENDPOINT, self.transport.send(request).await)?;
+
+        let status = response.status;
+        self.rate_budget.update_from_headers(DATA_ENDPOINT, &response.headers);
+        let body = response.body;
+
+        if !status.is_success() {
+            return Err(ScraperError::from_api_body(&body).unwrap_or_else(|| {
+                ScraperError::ApiError(format!(
+                    "League list request failed with status {}: {}",
+                    status, body
+                ))
+            }));
+        }
+
+        Ok(poe2_leagues(serde_json::from_str(&body)?))
+    }
+
+    // How many `have` the currency exchange currently asks for one `want`,
+    // or None when nobody is offering that pair
+    #[tracing::instrument(skip(self))]
+    pub async fn exchange_rate(&mut self, have: &str, want: &str) -> Result<Option<f64>> {
+        self.acquire(EXCHANGE_ENDPOINT).await?;
+
+        let url = format!("https://www.pathofexile.com/api/trade2/exchange/poe2/{}", self.league);
+        let payload = serde_json::json!({
+            "exchange": {
+                "status": { "option": "online" },
+                "have": [have],
+                "want": [want],
             }
-            Err(e) => {
-                println!("Failed to process item. Error: {}", e);
-                println!("Examining raw item fields:");
-                if let Some(obj) = raw_item.as_object() {
-                    for (key, value) in obj {
-                        println!("  {}: {:?}", key, value);
-                    }
-                }
-                Err(ScraperError::ParseError(format!(
-                    "Failed to parse item: {}. Raw data available in debug output.",
-                    e
-                )))
+        });
+        let request = self.client
+            .post(&url)
+            .header("Accept", "*/*")
+            .header("Content-Type", "application/json")
+            .json(&payload);
+        let request = self.credentials.authorize(&self.client, &self.rate_budget, request).await?.build()?;
+        let response = record_response(EXCHANGE_ENDPOINT, self.transport.send(request).await)?;
+
+        let status = response.status;
+        self.rate_budget.update_from_headers(EXCHANGE_ENDPOINT, &response.headers);
+        let body = response.body;
+
+        if !status.is_success() {
+            return Err(ScraperError::from_api_body(&body).unwrap_or_else(|| {
+                ScraperError::ApiError(format!(
+                    "Exchange request failed with status {}: {}",
+                    status, body
+                ))
+            }));
+        }
+
+        Ok(median_exchange_rate(&serde_json::from_str(&body)?))
+    }
+
+    // Check that the configured league is one the trade API knows about
+    pub async fn validate_league(&mut self) -> Result<()> {
+        let leagues = self.list_leagues().await?;
+
+        if leagues.iter().any(|league| league.id == self.league) {
+            Ok(())
+        } else {
+            let known: Vec<&str> = leagues.iter().map(|l| l.id.as_str()).collect();
+            Err(ScraperError::ValidationError(format!(
+                "Unknown league '{}'. Active leagues: {}",
+                self.league,
+                known.join(", ")
+            )))
+        }
+    }
+
+    pub async fn search_paginated(&mut self, query: SearchRequest, page_size: usize) -> Result<SearchPages> {
+        let response = self.search_items(query).await?;
+
+        let returned = response.get_result_ids().len();
+        if (returned as u32) < response.total() {
+            warn!(total = response.total(), returned,
+                "search matched more listings than the trade API returns; narrow the query to reach the rest");
+        }
+
+        Ok(SearchPages::new(response, page_size))
+    }
+
+    // Run a search and fetch every result ID it returned
+    pub async fn search_all(&mut self, query: SearchRequest) -> Result<FetchReport> {
+        let mut pages = self.search_paginated(query, MAX_FETCH_IDS).await?;
+        let mut report = FetchReport::default();
+
+        while let Some(page) = pages.next_page(self).await {
+            report.merge(page?);
+        }
+
+        Ok(report)
+    }
+
+    // Run a search and stream its results instead of collecting them all up front
+    pub async fn search_stream(&mut self, query: SearchRequest) -> Result<impl Stream<Item = Result<ItemResponse>> + '_> {
+        let pages = self.search_paginated(query, MAX_FETCH_IDS).await?;
+        Ok(pages.into_stream(self))
+    }
+
+    // Fetch and deserialize listings, failing on the first item that doesn't parse
+    pub async fn fetch_items(&mut self, ids: &[String]) -> Result<Vec<ItemResponse>> {
+        self.fetch_raw_items(ids).await?
+            .into_iter()
+            .map(|raw_item| parse_fetched_item(raw_item).map_err(|failure| {
+                ScraperError::ParseError(format!(
+                    "Failed to parse item {}: {}",
+                    failure.id.as_deref().unwrap_or("<unknown>"),
+                    failure.error
+                ))
+            }))
+            .collect()
+    }
+
+    // Like fetch_items, but items that don't parse are recorded in the report and skipped
+    pub async fn fetch_items_lenient(&mut self, ids: &[String]) -> Result<FetchReport> {
+        let mut report = FetchReport::default();
+
+        for raw_item in self.fetch_raw_items(ids).await? {
+            match parse_fetched_item(raw_item) {
+                Ok(item) => report.items.push(item),
+                Err(failure) => report.failures.push(failure),
             }
         }
+
+        if !report.failures.is_empty() {
+            Metrics::global().record_parse_failures("fetch", report.failures.len());
+            warn!(skipped = report.failures.len(), attempted = report.attempted(),
+                "skipped fetched items that failed to parse");
+        }
+
+        Ok(report)
     }
 
-    pub async fn fetch_items(&mut self, ids: &[String]) -> Result<Vec<serde_json::Value>> {
+    #[tracing::instrument(skip_all, fields(league = %self.league, ids = ids.len()))]
+    async fn fetch_raw_items(&mut self, ids: &[String]) -> Result<Vec<serde_json::Value>> {
         let mut all_items = Vec::new();
         
-        // Process IDs in batches of 10
-        for chunk in ids.chunks(10) {
-            // Increase the base delay and add some randomness to avoid synchronization
-            let delay = Duration::from_millis(500 + (rand::random::<u64>() % 100));
-            self.respect_rate_limit(delay).await;
-    
-            let ids_str = chunk.join(",");
-            let url = format!(
-                "https://www.pathofexile.com/api/trade2/fetch/{}",
-                ids_str
-            );
-    
-            println!("Fetching items from: {}", url);
-    
-            let response = self.client
-                .get(&url)
-                .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0")
-                .header("Accept", "*/*")
-                .header("Accept-Language", "en-US,en;q=0.5")
-                .header("Content-Type", "application/json")
-                .header("X-Requested-With", "XMLHttpRequest")
-                .header("Origin", "https://www.pathofexile.com")
-                .header("Referer", format!("https://www.pathofexile.com/trade2/search/poe2/{}", self.league))
-                .send()
-                .await?;
+        // Process IDs in batches of 10, re-queueing batches that fail transiently.
+        // Up to `max_in_flight` batches run at once. Each waits on the rate limiter inside its
+        // own future, so it is sent as soon as it gets a slot while the others are still waiting,
+        // and concurrency never pushes us past the server's limits.
+        let mut pending: VecDeque<(Vec<String>, u32)> = ids.chunks(MAX_FETCH_IDS)
+            .map(|chunk| (chunk.to_vec(), 0))
+            .collect();
+        let mut in_flight = FuturesUnordered::new();
+
+        while !pending.is_empty() || !in_flight.is_empty() {
+            while in_flight.len() < self.max_in_flight {
+                let Some((chunk, attempt)) = pending.pop_front() else {
+                    break;
+                };
+
+                let url = format!(
+                    "https://www.pathofexile.com/api/trade2/fetch/{}",
+                    chunk.join(",")
+                );
+                let cache_key = ResponseCache::cache_key("GET", &url, "");
+                if let Some(cache) = &self.cache {
+                    if let Some(body) = cache.get(&cache_key).await {
+                        debug!(%url, "using cached fetch response");
+                        extend_fetched_items(&mut all_items, &body)?;
+                        continue;
+                    }
+                }
+
+                let request = self.client
+                    .get(&url)
+                    .header("Accept", "*/*")
+                    .header("Accept-Language", "en-US,en;q=0.5")
+                    .header("Content-Type", "application/json")
+                    .header("X-Requested-With", "XMLHttpRequest")
+                    .header("Origin", "https://www.pathofexile.com")
+                    .header("Referer", format!("https://www.pathofexile.com/trade2/search/poe2/{}", self.league));
+                // Only waits when an OAuth token has to be fetched first
+                let request = self.credentials.authorize(&self.client, &self.rate_budget, request).await?.build()?;
+                let transport = Arc::clone(&self.transport);
+                let rate_budget = self.rate_budget.clone();
+                let cancellation = self.cancellation.clone();
+
+                in_flight.push(async move {
+                    wait_for_budget(&rate_budget, &cancellation, FETCH_ENDPOINT).await?;
+                    debug!(%url, attempt, "fetching items");
+                    let started = Instant::now();
+                    let sent = record_response(FETCH_ENDPOINT, transport.send(request).await);
+                    Ok::<_, ScraperError>((chunk, attempt, cache_key, started.elapsed(), sent))
+                });
+            }
+
+            let Some(fetched) = in_flight.next().await else {
+                continue;
+            };
+            let (chunk, attempt, cache_key, elapsed, sent) = fetched?;
+            self.api_calls += 1;
+
+            let (status, headers, response_text) = match sent {
+                Ok(response) => (response.status, response.headers, response.body),
+                Err(e) if e.is_transient() => {
+                    self.requeue_chunk(&mut pending, chunk, attempt, &e.to_string()).await?;
+                    continue;
+                }
+                Err(e) => return Err(e.context(format!("fetching listings {}", chunk.join(",")))),
+            };
     
-            let status = response.status();
-            println!("Fetch response status: {}", status);
-            
-            let response_text = response.text().await?;
-            println!("Fetch response body: {}", response_text);
+            debug!(%status, elapsed_ms = elapsed.as_millis() as u64, items = chunk.len(), "fetch request completed");
+            trace!(body = %response_text, "fetch response body");
+            self.rate_budget.update_from_headers(FETCH_ENDPOINT, &headers);
+            if status == reqwest::StatusCode::UNAUTHORIZED {
+                self.credentials.invalidate();
+            }
     
-            // If we hit rate limit, wait and retry
-            if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
-                println!("Rate limit hit, waiting 5 seconds before retry...");
-                tokio::time::sleep(Duration::from_secs(5)).await;
+            // Rate limits and server errors are retried later instead of dropping the batch
+            if RetryPolicy::is_retryable_status(status) {
+                self.requeue_chunk(&mut pending, chunk, attempt, status.as_str()).await?;
                 continue;
             }
     
-            if status.is_success() {
-                let json_response: serde_json::Value = serde_json::from_str(&response_text)?;
-                if let Some(items) = json_response["result"].as_array() {
-                    all_items.extend(items.to_vec());
-                }
+            if !status.is_success() {
+                let error = ScraperError::from_api_body(&response_text).unwrap_or_else(|| {
+                    ScraperError::ApiError(format!("Fetch failed with status {}", status))
+                });
+                return Err(error.context(format!("fetching listings {}", chunk.join(","))));
             }
-    
-            self.last_request = Instant::now();
+
+            extend_fetched_items(&mut all_items, &response_text)?;
+            self.store_in_cache(&cache_key, &response_text).await;
         }
     
         Ok(all_items)
     }
 
+    async fn store_in_cache(&self, key: &str, body: &str) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.put(key, body).await {
+                warn!(error = %e, "failed to write response cache");
+            }
+        }
+    }
+
+    async fn requeue_chunk(
+        &self,
+        pending: &mut VecDeque<(Vec<String>, u32)>,
+        chunk: Vec<String>,
+        attempt: u32,
+        reason: &str,
+    ) -> Result<()> {
+        if !self.retry_policy.should_retry(attempt) {
+            return Err(ScraperError::RateLimitError(format!(
+                "Giving up on fetch of {} items after {} attempts (last failure: {})",
+                chunk.len(), attempt + 1, reason
+            )));
+        }
+
+        let delay = self.retry_policy.delay_for(attempt);
+        warn!(reason, items = chunk.len(), ?delay, attempt = attempt + 1, max_attempts = self.retry_policy.max_attempts,
+            "fetch failed, re-queueing batch");
+        self.backoff(delay).await?;
+        pending.push_back((chunk, attempt + 1));
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(league = %self.league))]
     pub async fn search_items(&mut self, query: SearchRequest) -> Result<SearchResponse> {
-        let delay = Duration::from_millis(500 + (rand::random::<u64>() % 100));
-        self.respect_rate_limit(delay).await;
-        
         let url = format!(
             "https://www.pathofexile.com/api/trade2/search/poe2/{}",
             self.league
         );
 
-        println!("Sending search request to: {}", url);
-        println!("Query payload: {}", serde_json::to_string_pretty(&query).unwrap_or_default());
+        let payload = serde_json::to_string(&query)?;
+        let cache_key = ResponseCache::cache_key("POST", &url, &payload);
+        if let Some(cache) = &self.cache {
+            if let Some(body) = cache.get(&cache_key).await {
+                debug!(%url, "using cached search response");
+                return Ok(serde_json::from_str(&body)?);
+            }
+        }
 
-        let response = self.client
-            .post(&url)
-            .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:133.0) Gecko/20100101 Firefox/133.0")
-            .header("Accept", "*/*")
-            .header("Accept-Language", "en-US,en;q=0.5")
-            .header("Content-Type", "application/json")
-            .header("X-Requested-With", "XMLHttpRequest")
-            .header("Origin", "https://www.pathofexile.com")
-            .header("Referer", format!("https://www.pathofexile.com/trade2/search/poe2/{}", self.league))
-            .json(&query)
-            .send()
-            .await?;
-
-        println!("Search response status: {}", response.status());
-        
-        let response_text = response.text().await?;
-        println!("Search response body: {}", response_text);
+        debug!(%url, "sending search request");
+        trace!(payload = %payload, "search query payload");
+
+        let mut attempt = 0;
+        let (status, response_text) = loop {
+            self.acquire(SEARCH_ENDPOINT).await?;
+
+            let request = self.client
+                .post(&url)
+                .header("Accept", "*/*")
+                .header("Accept-Language", "en-US,en;q=0.5")
+                .header("Content-Type", "application/json")
+                .header("X-Requested-With", "XMLHttpRequest")
+                .header("Origin", "https://www.pathofexile.com")
+                .header("Referer", format!("https://www.pathofexile.com/trade2/search/poe2/{}", self.league))
+                .json(&query);
+            let started = Instant::now();
+            let request = self.credentials.authorize(&self.client, &self.rate_budget, request).await?.build()?;
+            let sent = record_response(SEARCH_ENDPOINT, self.transport.send(request).await);
+
+            let failure = match sent {
+                Ok(response) => {
+                    let status = response.status;
+                    debug!(%status, elapsed_ms = started.elapsed().as_millis() as u64, "search request completed");
+                    self.rate_budget.update_from_headers(SEARCH_ENDPOINT, &response.headers);
+                    if status == reqwest::StatusCode::UNAUTHORIZED {
+                        self.credentials.invalidate();
+                    }
+
+                    if !RetryPolicy::is_retryable_status(status) {
+                        break (status, response.body);
+                    }
+                    ScraperError::RateLimitError(format!("Search failed with status {}", status))
+                }
+                Err(e) => e,
+            };
+
+            if !failure.is_transient() || !self.retry_policy.should_retry(attempt) {
+                return Err(failure.context(format!("searching {}", url)));
+            }
+
+            let delay = self.retry_policy.delay_for(attempt);
+            warn!(error = %failure, ?delay, attempt = attempt + 1, max_attempts = self.retry_policy.max_attempts,
+                "search failed, retrying");
+            self.backoff(delay).await?;
+            attempt += 1;
+        };
+
+        trace!(body = %response_text, "search response body");
+
+        if let Some(error) = ScraperError::from_api_body(&response_text) {
+            return Err(error);
+        }
 
         match serde_json::from_str::<SearchResponse>(&response_text) {
             Ok(parsed) => {
-                self.last_request = Instant::now();
+                if status.is_success() {
+                    self.store_in_cache(&cache_key, &response_text).await;
+                }
                 Ok(parsed)
-            },
+            }
             Err(e) => {
-                eprintln!("Failed to parse search response: {}", e);
-                eprintln!("Response body was: {}", response_text);
                 Err(crate::errors::ScraperError::ParseError(format!(
                     "Failed to parse search response: {}. Response body: {}", 
                     e, response_text
@@ -241,103 +1024,295 @@ impl TradeApiClient {
             }
         }
     }
-    
-    async fn respect_rate_limit(&self, delay: Duration) {
-        let elapsed = self.last_request.elapsed();
-        if elapsed < delay {
-            tokio::time::sleep(delay - elapsed).await;
-        }
-    }
 
     pub fn build_basic_query(&self, status: TradeStatus) -> SearchRequest {
-        SearchRequest {
-            query: TradeQuery {
-                status: StatusFilter {
-                    option: status.as_str().to_string(),
-                },
-                stats: vec![StatFilter {
-                    r#type: "and".to_string(),
-                    filters: vec![],
-                    disabled: false,
-                }],
-                filters: QueryFilters {
-                    type_filters: TypeFilters {
-                        filters: CategoryFilter {
-                            category: CategoryOption {
-                                option: "any".to_string(),
-                            },
-                        },
-                    },
-                },
-            },
-            sort: Some(serde_json::json!({
-                "price": "asc"
-            })),
-        }
+        QueryBuilder::new()
+            .status(status)
+            .build()
     }
     
     pub fn build_jewel_query(&self, status: TradeStatus) -> SearchRequest {
-        SearchRequest {
-            query: TradeQuery {
-                status: StatusFilter {
-                    option: status.as_str().to_string(),
-                },
-                stats: vec![StatFilter {
-                    r#type: "and".to_string(),
-                    filters: vec![],
-                    disabled: false,
-                }],
-                filters: QueryFilters {
-                    type_filters: TypeFilters {
-                        filters: CategoryFilter {
-                            category: CategoryOption {
-                                option: "jewel".to_string(),
-                            },
-                        },
-                    },
-                },
-            },
-            sort: Some(serde_json::json!({
-                "price": "asc"
-            })),
-        }
+        QueryBuilder::new()
+            .status(status)
+            .category("jewel")
+            .build()
     }
 
     pub async fn fetch_items_with_stats(&mut self, query: SearchRequest) -> Result<Vec<ItemResponse>> {
-        println!("Starting items with stats fetch...");
-        
         let search_response = self.search_items(query).await?;
-        println!("Search returned {} results", search_response.result.len());
+        debug!(results = search_response.result.len(), "search returned results");
         
-        let raw_items = self.fetch_items(search_response.get_result_ids()).await?;
-        let total_items = raw_items.len();  // Store the length before processing
-        println!("Fetched {} raw items", total_items);
-        
-        let mut processed_items = Vec::new();
-        let mut failed_count = 0;
+        let report = self.fetch_items_lenient(search_response.get_result_ids()).await?;
+        info!(
+            attempted = report.attempted(),
+            processed = report.items.len(),
+            failed = report.failures.len(),
+            "fetched items with stats"
+        );
         
-        // Process each raw item using our diagnostic method
-        for raw_item in raw_items {
-            match self.process_raw_item(raw_item.clone()).await {
-                Ok(item) => {
-                    println!("Processed item: {} - {} {}", 
-                        item.id,
-                        item.item.base_type,
-                        item.listing.price.amount);
-                    processed_items.push(item);
-                },
-                Err(e) => {
-                    eprintln!("Failed to process item: {}", e);
-                    failed_count += 1;
-                }
-            }
+        Ok(report.items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::transport::{HttpResponse, HttpTransport, RecordedTransport};
+    use crate::errors::TradeApiErrorCode;
+    use reqwest::{Method, StatusCode};
+
+    #[test]
+    fn test_poe2_leagues_are_filtered() {
+        let response: LeaguesResponse = serde_json::from_str(r#"{
+            "result": [
+                {"id": "Standard", "realm": "poe2", "text": "Standard"},
+                {"id": "Hardcore", "realm": "poe2", "text": "Hardcore"},
+                {"id": "Settlers", "realm": "pc", "text": "Settlers"},
+                {"id": "Dawn of the Hunt", "text": "Dawn of the Hunt"}
+            ]
+        }"#).unwrap();
+
+        let ids: Vec<String> = poe2_leagues(response).into_iter().map(|l| l.id).collect();
+        assert_eq!(ids, vec!["Standard", "Hardcore", "Dawn of the Hunt"]);
+    }
+
+    #[test]
+    fn test_exchange_rate_is_median_offer() {
+        let offer = |ask: f64, give: f64| serde_json::json!({
+            "listing": { "offers": [{
+                "exchange": { "currency": "exalted", "amount": ask },
+                "item": { "currency": "divine", "amount": give, "stock": 10 }
+            }]}
+        });
+        let response: ExchangeResponse = serde_json::from_value(serde_json::json!({
+            "id": "abc",
+            "result": { "a": offer(400.0, 2.0), "b": offer(190.0, 1.0), "c": offer(1.0, 1.0) }
+        })).unwrap();
+
+        assert_eq!(median_exchange_rate(&response), Some(190.0));
+        assert_eq!(median_exchange_rate(&serde_json::from_str(r#"{"result": {}}"#).unwrap()), None);
+    }
+
+    #[test]
+    fn test_builder_rejects_bad_proxy() {
+        let client = TradeApiClient::builder("Standard".to_string())
+            .user_agent("my-tool/1.0 (contact: me@example.com)")
+            .timeout(Duration::from_secs(5))
+            .proxy("socks5://127.0.0.1:1080")
+            .build()
+            .unwrap();
+        assert_eq!(client.user_agent(), "my-tool/1.0 (contact: me@example.com)");
+
+        let result = TradeApiClient::builder("Standard".to_string())
+            .proxy("not a url")
+            .build();
+        assert!(matches!(result, Err(ScraperError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_parse_failure_keeps_item_id() {
+        let failure = parse_fetched_item(serde_json::json!({
+            "id": "abc123",
+            "listing": {}
+        })).unwrap_err();
+
+        assert_eq!(failure.id.as_deref(), Some("abc123"));
+        assert!(!failure.error.is_empty());
+
+        let failure = parse_fetched_item(serde_json::json!("not an item")).unwrap_err();
+        assert!(failure.id.is_none());
+    }
+
+    // A client answering from recorded responses, with its own budget and no backoff
+    fn recorded_client(transport: Arc<RecordedTransport>) -> TradeApiClient {
+        TradeApiClient::builder("Standard".to_string())
+            .rate_budget(RateBudget::new())
+            .transport(transport)
+            .build()
+            .unwrap()
+            .with_retry_policy(RetryPolicy {
+                max_attempts: 3,
+                backoff_base: Duration::ZERO,
+                max_backoff: Duration::ZERO,
+                jitter: Duration::ZERO,
+            })
+    }
+
+    fn recorded(status: StatusCode, body: &str) -> HttpResponse {
+        HttpResponse::new(status, body)
+    }
+
+    #[tokio::test]
+    async fn test_search_and_fetch_from_recorded_responses() {
+        let transport = Arc::new(RecordedTransport::new()
+            .respond(Method::POST, "/search/poe2/Standard", recorded(StatusCode::OK, include_str!("fixtures/search.json")))
+            .respond(Method::GET, "/fetch/", recorded(StatusCode::OK, include_str!("fixtures/fetch.json"))));
+        let mut client = recorded_client(transport.clone());
+
+        let report = client.search_all(client.build_basic_query(TradeStatus::Online)).await.unwrap();
+        assert_eq!(report.items.len(), 2);
+        assert!(report.failures.is_empty());
+        assert_eq!(report.items[0].item.base_type, "Chain Mail");
+        assert_eq!(client.api_calls(), 2);
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].starts_with("POST https://www.pathofexile.com/api/trade2/search/poe2/Standard"));
+        let ids: Vec<String> = report.items.iter().map(|item| item.id.clone()).collect();
+        assert!(requests[1].starts_with("GET ") && requests[1].ends_with(&ids.join(",")));
+    }
+
+    // Log output written to a buffer the test can read back
+    #[derive(Clone, Default)]
+    struct CapturedLog(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
         }
-    
-        println!("\nProcessing summary:");
-        println!("Total items attempted: {}", total_items);  // Use our stored count
-        println!("Successfully processed: {}", processed_items.len());
-        println!("Failed to process: {}", failed_count);
-        
-        Ok(processed_items)
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_pages_walk_every_returned_id() {
+        // The search matched more listings than it returned IDs for
+        let mut search: serde_json::Value = serde_json::from_str(include_str!("fixtures/search.json")).unwrap();
+        search["total"] = serde_json::json!(250);
+        let fetched: serde_json::Value = serde_json::from_str(include_str!("fixtures/fetch.json")).unwrap();
+        let ids: Vec<String> = fetched["result"].as_array().unwrap().iter().map(|item| item["id"].as_str().unwrap().to_string()).collect();
+        let page = |i: usize| recorded(StatusCode::OK, &serde_json::json!({ "result": [fetched["result"][i].clone()] }).to_string());
+        let transport = Arc::new(RecordedTransport::new()
+            .respond(Method::POST, "/search/", recorded(StatusCode::OK, &search.to_string()))
+            .respond(Method::GET, &format!("/fetch/{}", ids[0]), page(0))
+            .respond(Method::GET, &format!("/fetch/{}", ids[1]), page(1)));
+        let mut client = recorded_client(transport.clone());
+
+        let log = CapturedLog::default();
+        let writer = log.clone();
+        let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).with_ansi(false).finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut pages = client.search_paginated(client.build_basic_query(TradeStatus::Online), 1).await.unwrap();
+        assert_eq!(pages.result_ids(), ids.as_slice());
+        let mut fetched_ids = Vec::new();
+        while let Some(page) = pages.next_page(&mut client).await {
+            let page = page.unwrap();
+            assert_eq!(page.items.len(), 1);
+            fetched_ids.extend(page.items.into_iter().map(|item| item.id));
+            assert_eq!(pages.remaining(), ids.len() - fetched_ids.len());
+        }
+        assert_eq!(fetched_ids, ids);
+        assert_eq!(transport.requests().len(), 3);
+
+        let output = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        assert!(output.contains("WARN") && output.contains("search matched more listings than the trade API returns"));
+        assert!(output.contains("total=250") && output.contains("returned=2"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_requests_are_retried() {
+        let rate_limited = || recorded(StatusCode::TOO_MANY_REQUESTS, include_str!("fixtures/rate_limited.json"))
+            .with_header("retry-after", "0");
+        let transport = Arc::new(RecordedTransport::new()
+            .respond(Method::POST, "/search/", rate_limited())
+            .respond(Method::POST, "/search/", recorded(StatusCode::OK, include_str!("fixtures/search.json")))
+            .respond(Method::GET, "/fetch/", rate_limited())
+            .respond(Method::GET, "/fetch/", recorded(StatusCode::OK, include_str!("fixtures/fetch.json"))));
+        let mut client = recorded_client(transport.clone());
+
+        let report = client.search_all(client.build_basic_query(TradeStatus::Online)).await.unwrap();
+        assert_eq!(report.items.len(), 2);
+        assert_eq!(transport.requests().len(), 4);
+        assert_eq!(client.api_calls(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_error_payloads_fail_without_retrying() {
+        let transport = Arc::new(RecordedTransport::new()
+            .respond(Method::POST, "/search/", recorded(StatusCode::BAD_REQUEST, include_str!("fixtures/invalid_query.json"))));
+        let mut client = recorded_client(transport.clone());
+
+        let error = client.search_items(client.build_basic_query(TradeStatus::Online)).await.unwrap_err();
+        assert_eq!(error.trade_api_code(), Some(TradeApiErrorCode::InvalidQuery));
+        assert_eq!(transport.requests().len(), 1);
+
+        // Nothing recorded for the fetch endpoint, which must not be mistaken for a network blip
+        let error = client.fetch_items(&["abc123".to_string()]).await.unwrap_err();
+        assert!(!error.is_transient());
+        assert_eq!(transport.requests().len(), 2);
+    }
+
+    // Notes how long after the test started each request went out
+    #[derive(Debug)]
+    struct TimedTransport {
+        inner: RecordedTransport,
+        started: Instant,
+        sent_at: std::sync::Mutex<Vec<Duration>>,
     }
-}
\ No newline at end of file
+
+    impl HttpTransport for TimedTransport {
+        fn send(&self, request: reqwest::Request) -> futures_util::future::BoxFuture<'_, Result<HttpResponse>> {
+            self.sent_at.lock().unwrap().push(self.started.elapsed());
+            self.inner.send(request)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_batches_sent_as_their_rate_slots_free_up() {
+        let inner = (0..3).fold(RecordedTransport::new(), |transport, _| {
+            transport.respond(Method::GET, "/fetch/", recorded(StatusCode::OK, include_str!("fixtures/fetch.json")))
+        });
+        let transport = Arc::new(TimedTransport {
+            inner,
+            started: Instant::now(),
+            sent_at: Default::default(),
+        });
+        // One fetch a second
+        let budget = RateBudget::new();
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-rate-limit-rules", "ip".parse().unwrap());
+        headers.insert("x-rate-limit-ip", "1:1:0".parse().unwrap());
+        budget.update_from_headers(FETCH_ENDPOINT, &headers);
+        let mut client = TradeApiClient::builder("Standard".to_string())
+            .rate_budget(budget)
+            .transport(transport.clone())
+            .build()
+            .unwrap()
+            .with_concurrency(3);
+
+        let ids: Vec<String> = (0..3 * MAX_FETCH_IDS).map(|i| format!("id{}", i)).collect();
+        client.fetch_items(&ids).await.unwrap();
+
+        // The first batch goes out at once instead of after the others got their slots
+        let sent_at = transport.sent_at.lock().unwrap().clone();
+        assert_eq!(sent_at.len(), 3);
+        assert!(sent_at[0] < Duration::from_millis(500), "{:?}", sent_at);
+        assert!(sent_at[2] >= Duration::from_secs(2), "{:?}", sent_at);
+        assert_eq!(client.api_calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cancellation_cuts_retry_backoff_short() {
+        let transport = Arc::new(RecordedTransport::new()
+            .respond(Method::POST, "/search/", recorded(StatusCode::SERVICE_UNAVAILABLE, "")));
+        let token = CancellationToken::new();
+        let mut client = recorded_client(transport.clone())
+            .with_retry_policy(RetryPolicy {
+                max_attempts: 3,
+                backoff_base: Duration::from_secs(3600),
+                max_backoff: Duration::from_secs(3600),
+                jitter: Duration::ZERO,
+            })
+            .with_cancellation(token.clone());
+        token.cancel();
+
+        let error = client.search_items(client.build_basic_query(TradeStatus::Online)).await.unwrap_err();
+        assert!(error.is_cancelled());
+        assert!(!error.is_transient());
+        assert_eq!(transport.requests().len(), 1);
+    }
+}
@@ -0,0 +1,295 @@
+use futures_util::future::BoxFuture;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Method, Request, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::errors::{Result, ResultExt, ScraperError};
+
+// A response read in full, so the trade client never holds a connection open and
+// recorded responses can stand in for real ones
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn new(status: StatusCode, body: impl Into<String>) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: body.into(),
+        }
+    }
+
+    // Headers with names or values that aren't valid HTTP are left out
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+}
+
+// How the trade client sends its requests. ReqwestTransport goes to the network;
+// RecordedTransport answers from responses given to it up front, for offline tests and
+// replaying a recorded session; RecordingTransport writes down what another one sends.
+pub trait HttpTransport: fmt::Debug + Send + Sync {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<HttpResponse>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct ReqwestTransport {
+    client: Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl HttpTransport for ReqwestTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<HttpResponse>> {
+        Box::pin(async move {
+            let response = self.client.execute(request).await?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.text().await?;
+            Ok(HttpResponse { status, headers, body })
+        })
+    }
+}
+
+// One request and the response to it, as written to a session directory. Request headers
+// are left out so session cookies and tokens don't end up in recordings.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedExchange {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_body: Option<String>,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+}
+
+fn request_body(request: &Request) -> Option<String> {
+    let bytes = request.body()?.as_bytes()?;
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+// A directory exchanges are written to as numbered JSON files, in the order requests were
+// sent. One recorder is shared by every client of a run so they number in one sequence.
+#[derive(Debug)]
+pub struct SessionRecorder {
+    dir: PathBuf,
+    next: AtomicUsize,
+}
+
+impl SessionRecorder {
+    // Start recording into `dir`, which must not hold a session already
+    pub fn create(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).with_context(|| format!("creating session directory {}", dir.display()))?;
+        if session_files(&dir)?.next().is_some() {
+            return Err(ScraperError::ValidationError(format!(
+                "{} already holds a recorded session; record into a new directory",
+                dir.display()
+            )));
+        }
+        Ok(Self { dir, next: AtomicUsize::new(1) })
+    }
+
+    async fn write(&self, sequence: usize, exchange: &RecordedExchange) -> Result<()> {
+        let path = self.dir.join(format!("{:06}.json", sequence));
+        tokio::fs::write(&path, serde_json::to_vec_pretty(exchange)?)
+            .await
+            .with_context(|| format!("recording {}", path.display()))
+    }
+}
+
+// The exchange files of a session directory
+fn session_files(dir: &Path) -> Result<impl Iterator<Item = PathBuf>> {
+    let entries = std::fs::read_dir(dir).with_context(|| format!("reading session directory {}", dir.display()))?;
+    Ok(entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "json")))
+}
+
+// Sends through another transport and records every response it gets to a session
+#[derive(Debug)]
+pub struct RecordingTransport {
+    inner: Arc<dyn HttpTransport>,
+    recorder: Arc<SessionRecorder>,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Arc<dyn HttpTransport>, recorder: Arc<SessionRecorder>) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl HttpTransport for RecordingTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<HttpResponse>> {
+        // Numbered when sent, so concurrent fetches keep the order they went out in
+        let sequence = self.recorder.next.fetch_add(1, Ordering::Relaxed);
+        let method = request.method().to_string();
+        let url = request.url().to_string();
+        let request_body = request_body(&request);
+        Box::pin(async move {
+            let response = self.inner.send(request).await?;
+            let exchange = RecordedExchange {
+                method,
+                url,
+                request_body,
+                status: response.status.as_u16(),
+                headers: response
+                    .headers
+                    .iter()
+                    .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+                    .collect(),
+                body: response.body.clone(),
+            };
+            self.recorder.write(sequence, &exchange).await?;
+            Ok(response)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct RecordedRoute {
+    method: Method,
+    path: String,
+    // Only requests with this body match, when set
+    body: Option<String>,
+    response: HttpResponse,
+}
+
+// Answers each request with the first unused response recorded for its method and a path
+// the URL contains, in the order they were recorded. Requests without one fail with an
+// ApiError rather than reaching the network.
+#[derive(Debug, Default)]
+pub struct RecordedTransport {
+    routes: Mutex<VecDeque<RecordedRoute>>,
+    // "METHOD url" of every request sent, in order
+    requests: Mutex<Vec<String>>,
+}
+
+impl RecordedTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn respond(self, method: Method, path: &str, response: HttpResponse) -> Self {
+        self.routes.lock().unwrap().push_back(RecordedRoute {
+            method,
+            path: path.to_string(),
+            body: None,
+            response,
+        });
+        self
+    }
+
+    // Answer from a session directory written by RecordingTransport, each request with the
+    // next response recorded for its method, URL and body. Rate limit headers are dropped
+    // so a replay isn't paced like the recorded run.
+    pub fn from_session(dir: &Path) -> Result<Self> {
+        let mut files: Vec<PathBuf> = session_files(dir)?.collect();
+        if files.is_empty() {
+            return Err(ScraperError::ValidationError(format!("{} holds no recorded session", dir.display())));
+        }
+        files.sort();
+
+        let transport = Self::new();
+        for path in files {
+            let content = std::fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+            let exchange: RecordedExchange = serde_json::from_str(&content).with_context(|| format!("parsing {}", path.display()))?;
+            let method = Method::from_bytes(exchange.method.as_bytes())
+                .map_err(|_| ScraperError::ParseError(format!("{}: unknown method {}", path.display(), exchange.method)))?;
+            let status = StatusCode::from_u16(exchange.status)
+                .map_err(|_| ScraperError::ParseError(format!("{}: invalid status {}", path.display(), exchange.status)))?;
+
+            let mut response = HttpResponse::new(status, exchange.body);
+            for (name, value) in &exchange.headers {
+                if !name.starts_with("x-rate-limit-") && name != "retry-after" {
+                    response = response.with_header(name, value);
+                }
+            }
+            transport.routes.lock().unwrap().push_back(RecordedRoute {
+                method,
+                path: exchange.url,
+                body: exchange.request_body,
+                response,
+            });
+        }
+        Ok(transport)
+    }
+
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl HttpTransport for RecordedTransport {
+    fn send(&self, request: Request) -> BoxFuture<'_, Result<HttpResponse>> {
+        let url = request.url().to_string();
+        let body = request_body(&request);
+        self.requests.lock().unwrap().push(format!("{} {}", request.method(), url));
+
+        let mut routes = self.routes.lock().unwrap();
+        let position = routes.iter().position(|route| {
+            route.method == request.method()
+                && url.contains(&route.path)
+                && route.body.as_ref().is_none_or(|expected| Some(expected) == body.as_ref())
+        });
+        let response = match position.and_then(|index| routes.remove(index)) {
+            Some(route) => Ok(route.response),
+            None => Err(ScraperError::ApiError(format!(
+                "No recorded response for {} {}",
+                request.method(),
+                url
+            ))),
+        };
+        Box::pin(async move { response })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: Method, url: &str, body: &str) -> Request {
+        let client = Client::new();
+        client.request(method, url).body(body.to_string()).build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_recorded_session_replays() {
+        let dir = std::env::temp_dir().join(format!("poe2-session-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let live = Arc::new(RecordedTransport::new()
+            .respond(Method::POST, "/search/", HttpResponse::new(StatusCode::OK, "first").with_header("x-rate-limit-ip", "5:10:60"))
+            .respond(Method::POST, "/search/", HttpResponse::new(StatusCode::OK, "second")));
+        let recorder = Arc::new(SessionRecorder::create(&dir).unwrap());
+        let recording = RecordingTransport::new(live, recorder);
+        recording.send(request(Method::POST, "https://example.com/search/a", "{\"q\":1}")).await.unwrap();
+        recording.send(request(Method::POST, "https://example.com/search/a", "{\"q\":2}")).await.unwrap();
+        assert!(SessionRecorder::create(&dir).is_err());
+
+        // Answered by body, not by order, without the recorded rate limits
+        let replay = RecordedTransport::from_session(&dir).unwrap();
+        let second = replay.send(request(Method::POST, "https://example.com/search/a", "{\"q\":2}")).await.unwrap();
+        let first = replay.send(request(Method::POST, "https://example.com/search/a", "{\"q\":1}")).await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!((first.body.as_str(), second.body.as_str()), ("first", "second"));
+        assert!(first.headers.is_empty());
+        assert!(replay.send(request(Method::POST, "https://example.com/search/a", "{\"q\":1}")).await.is_err());
+    }
+}
@@ -0,0 +1,32 @@
+// Path of Exile 2 trade scraper: a trade API client, item models, stat analysis and
+// SQLite or Postgres storage. The `rust-scraper` binary is a thin CLI over this crate.
+//
+//     use rust_scraper::{QueryBuilder, TradeApiClient};
+//
+//     let mut client = TradeApiClient::new("Standard".to_string());
+//     let report = client.search_all(QueryBuilder::new().online().build()).await?;
+
+pub mod analyzer;
+pub mod data;
+pub mod errors;
+pub mod fetcher;
+pub mod metrics;
+pub mod models;
+pub mod notifier;
+pub mod storage;
+// REST API over the database, behind the `server` feature
+#[cfg(feature = "server")]
+pub mod server;
+// Terminal dashboard for collection runs, behind the `tui` feature
+#[cfg(feature = "tui")]
+pub mod tui;
+
+// Settings file handling for the CLI
+#[doc(hidden)]
+pub mod config;
+
+pub use analyzer::{StatAnalyzer, StatCollector, CollectionSummary};
+pub use errors::{Result, ResultExt, ScraperError};
+pub use fetcher::{LiveSearchClient, QueryBuilder, SearchRequest, TradeApiClient};
+pub use models::{Item, ItemResponse};
+pub use storage::Database;
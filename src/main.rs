@@ -1,206 +1,1563 @@
+mod cli;
+
 use clap::Parser;
-use tokio;
-use serde_json;
-
-use crate::{
-    analyzer::{ModifierAnalyzer, StatAnalyzer, StatCollector},
-    models::{Item, ItemCategory, ItemResponse},
-    errors::{ScraperError, Result},
-    data::item_base_data_loader::BaseDataLoader,
-    storage::Database,
+use futures_util::StreamExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn, error};
+use tracing_subscriber::EnvFilter;
+use tokio_util::sync::CancellationToken;
+
+use cli::{
+    Cli, Command, ApiArgs, DatabaseArgs, CollectArgs, SearchArgs, AnalyzeArgs, StackingArgs, HeatmapArgs, ReportFormatArg,
+    ExportArgs, ExportFormat, ExportTarget, FilterArgs, PriceCheckArgs, LiveArgs, DbCommand, MigrateCommand, SalesArgs, PruneArgs, ImportArgs, RefreshAggregatesArgs, BasesCommand, AffixesCommand, AffixBuildArgs, AffixShowArgs, CurrencyCommand, CoverageArgs, ConfigCommand,
+    TrendsCommand, TrendRecordArgs, TrendShowArgs,
+};
+use rust_scraper::{
+    analyzer::{
+        StatAnalyzer, ModifierAnalyzer, StatCollector, CollectionPlan, CollectionCheckpoint, CollectedDataWriter,
+        AnalyzerSnapshot, AttributeStackAnalyzer, TrendAnalyzer, AnalysisPipeline, ItemAnalyzer, UniqueAnalyzer, AffixAnalyzer, GemAnalyzer, JewelAnalyzer, WaystoneAnalyzer, CurrencyCoverage, LootFilterAnalyzer, ModValueHeatmap, price_check, write_report, write_charts, format_timestamp, DEFAULT_CHECKPOINT_PATH, DEFAULT_SEARCH_CACHE_PATH,
+    },
+    config::{ScraperConfig, DEFAULT_CONFIG_PATH},
+    models::{Item, ItemResponse, ItemText},
+    errors::{ScraperError, Result, ResultExt},
+    metrics::Metrics,
+    notifier::Notifier,
+    analyzer::CollectionSummary,
+    data::item_base_data_loader::{BaseDataLoader, initialize_base_loader, BASE_ITEMS_URL, BASE_ITEMS_PATH},
+    data::unique_data_loader::{UniqueDataLoader, UNIQUE_ITEMS_PATH},
+    data::affix_data_loader::{AffixDatabase, AFFIX_DATA_PATH},
+    data::stat_catalog_loader::{StatCatalogLoader, initialize_stat_catalog, STAT_CATALOG_PATH},
+    data::currency_converter::{
+        CurrencyConverter, initialize_currency_converter, DEFAULT_RATES_PATH, EXCHANGE_CURRENCIES,
+    },
+    storage::{
+        compression::{self, Compression}, Database, Storage, MigrationState, MigrationStatus, RetentionPolicy, collected_items_csv, export_dataset, import_listings, prune, refresh_aggregates, ITEM_PAGE_SIZE,
+    },
 };
-use crate::fetcher::{
+#[cfg(feature = "tui")]
+use cli::TuiArgs;
+#[cfg(feature = "tui")]
+use rust_scraper::tui::{Dashboard, ErrorLog};
+use rust_scraper::fetcher::{
     TradeApiClient,
-    SearchRequest,
-    TradeQuery,
-    StatusFilter,
-    StatFilter,
-    QueryFilters,
-    TypeFilters,
-    CategoryFilter,
-    CategoryOption,
+    RetryPolicy,
+    OAuthConfig,
+    QueryBuilder,
+    LiveSearchClient,
+    ResponseCache,
+    HttpTransport,
+    RecordedTransport,
+    SessionRecorder,
 };
 
-// These are the top-level modules
-mod analyzer;
-mod fetcher;
-mod models;
-mod errors;
-mod data;
-mod storage;
-
-#[derive(Parser, Debug)]
-#[clap(author, version, about)]
-struct Args {
-    #[clap(short, long, default_value = "Standard")]
-    league: String,
+// Where daemon mode writes one output file per run
+const RUNS_DIR: &str = "data/runs";
+
+// Fetched listings that may wait between the collector and the database
+const COLLECT_BUFFER: usize = 100;
 
-    #[clap(short = 'n', long)]
-    min_price: Option<f64>,
+// Logs go to stderr so stdout stays usable for reports; RUST_LOG overrides the flags
+fn init_tracing(cli: &Cli) {
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => "warn",
+        (false, 0) => "info",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,rust_scraper={}", level)));
 
-    #[clap(short = 'x', long)]
-    max_price: Option<f64>,
-    
-    #[clap(long)]
-    analyze_stats: bool,
+    // Log lines would be written over the dashboard, which shows warnings and errors itself
+    #[cfg(feature = "tui")]
+    if matches!(cli.command, Command::Tui(_)) {
+        use tracing_subscriber::layer::SubscriberExt;
+        use tracing_subscriber::util::SubscriberInitExt;
+        tracing_subscriber::registry().with(filter).with(ErrorLog::layer()).init();
+        return;
+    }
 
-    #[clap(long)]
-    collect_data: bool,
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
 }
 
-async fn initialize_base_loader() -> Result<BaseDataLoader> {
-    let mut loader = BaseDataLoader::new();
+fn build_client(args: &ApiArgs) -> Result<TradeApiClient> {
+    build_league_client(args, args.league())
+}
+
+// Every client of a run records into one session, numbered in one sequence
+fn session_recorder(dir: &Path) -> Result<Arc<SessionRecorder>> {
+    static RECORDER: OnceLock<Arc<SessionRecorder>> = OnceLock::new();
+    if let Some(recorder) = RECORDER.get() {
+        return Ok(recorder.clone());
+    }
+    let recorder = Arc::new(SessionRecorder::create(dir)?);
+    info!(dir = %dir.display(), "recording trade API requests");
+    Ok(RECORDER.get_or_init(|| recorder).clone())
+}
+
+// Every client of a run answers from the one replayed session, each response used once
+fn session_replay(dir: &Path) -> Result<Arc<dyn HttpTransport>> {
+    static REPLAY: OnceLock<Arc<RecordedTransport>> = OnceLock::new();
+    if let Some(replay) = REPLAY.get() {
+        return Ok(replay.clone());
+    }
+    let replay = Arc::new(RecordedTransport::from_session(dir)?);
+    info!(dir = %dir.display(), "replaying recorded trade API session");
+    Ok(REPLAY.get_or_init(|| replay).clone())
+}
+
+fn build_league_client(args: &ApiArgs, league: &str) -> Result<TradeApiClient> {
+    let mut builder = TradeApiClient::builder(league.to_string())
+        .timeout(Duration::from_secs(args.timeout_secs()));
+    if let Some(proxy) = &args.proxy {
+        builder = builder.proxy(proxy);
+    }
+    if let Some(user_agent) = &args.user_agent {
+        builder = builder.user_agent(user_agent);
+    }
+
+    if let Some(dir) = &args.record {
+        builder = builder.record(session_recorder(dir)?);
+    }
+    if let Some(dir) = &args.replay {
+        builder = builder.transport(session_replay(dir)?);
+    }
 
-    // Try to load initial data from file
-    if loader.load_from_file("data/item_bases.json").await.is_err() {
-        // If file doesn't exist or is invalid, update from API
-        loader.update_from_api("https://api.pathofexile.com/trade/data/items").await?;
-        // Save the fresh data
-        loader.save_to_file("data/item_bases.json").await?;
+    let mut client = builder.build()?
+        .with_retry_policy(RetryPolicy {
+            max_attempts: args.max_retries().max(1),
+            ..RetryPolicy::default()
+        })
+        .with_concurrency(args.fetch_concurrency());
+    // Cached responses would neither be recorded nor come from the replayed session
+    if !args.no_cache && args.record.is_none() && args.replay.is_none() {
+        client = client.with_cache(ResponseCache::default());
     }
 
-    // Check if data needs updating
-    if loader.needs_update(std::time::Duration::from_secs(86400)) {  // 24 hours
-        loader.update_from_api("https://api.pathofexile.com/trade/data/items").await?;
-        loader.save_to_file("data/item_bases.json").await?;
+    // Prefer OAuth when both are configured since tokens refresh themselves
+    Ok(match (&args.oauth_client_id, &args.oauth_client_secret, &args.poesessid) {
+        (Some(id), Some(secret), _) => {
+            client.with_oauth(OAuthConfig::new(id.clone(), secret.clone()))
+        }
+        (_, _, Some(sessid)) => client.with_session(sessid.clone()),
+        _ => client,
+    })
+}
+
+// Each league of a multi-league run gets its own output and checkpoint files, named after
+// the league; a single league keeps the paths it was given
+fn league_path(path: &Path, league: &str, leagues: usize) -> PathBuf {
+    if leagues <= 1 {
+        return path.to_path_buf();
     }
+    let slug: String = league
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, slug, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, slug),
+    };
+    path.with_file_name(name)
+}
 
-    Ok(loader)
+// One league's collector and where its listings and summary are written
+struct LeagueCollection {
+    league: String,
+    collector: StatCollector,
+    output: PathBuf,
+    // Whether the run carries on from a checkpoint, adding to an NDJSON output
+    resume: bool,
+    summary_json: Option<PathBuf>,
 }
 
-fn main() -> Result<()> {
-    tokio::runtime::Runtime::new()?.block_on(async {
-        let args = Args::parse();
-    
-        // Initialize database first
-        let db = Database::initialize().await?;
-        
-        if args.collect_data {
-            println!("Starting data collection...");
-            let client = TradeApiClient::new(args.league.clone());
-            let mut collector = StatCollector::new(client);
-            
-            println!("Collecting stat data...");
-            let items = collector.collect_stat_data().await?;
-            let total_items = items.len();
-            println!("Collected {} items from API", total_items);
-            
-            collector.save_collected_data(&items, "collected_data.json").await?;
-            println!("Saved items to collected_data.json");
-            
-            let mut successful_conversions = 0;
-            let mut successful_saves = 0;
-            
-            for (index, item_response) in items.into_iter().enumerate() {
-                println!("Processing item {}", index + 1);
-                
-                match Item::try_from(item_response) {
-                    Ok(item) => {
-                        successful_conversions += 1;
-                        println!("Successfully converted item: {} ({})", 
-                            item.name.as_deref().unwrap_or("unnamed"), 
-                            item.id);
-                        
-                        match db.store_collected_item(&item).await {
-                            Ok(_) => {
-                                successful_saves += 1;
-                                println!("Successfully stored item in database");
-                            }
-                            Err(e) => {
-                                eprintln!("Failed to store item in database: {}", e);
-                                eprintln!("Item details: {:?}", item);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to convert item {}: {}", index + 1, e);
-                    }
+// --plan, else --sweep, else the config file's plan or the built-in one narrowed to
+// --stat-range; the latter two are stratified by --price-band
+async fn collection_plan(args: &CollectArgs) -> Result<CollectionPlan> {
+    if let Some(path) = &args.plan {
+        return CollectionPlan::load(path).await.with_context(|| format!("reading collection plan {}", path.display()));
+    }
+    let mut plan = match args.sweep() {
+        Some(limits) => CollectionPlan::sweep(&limits),
+        None => {
+            let mut plan = args.config_plan.as_deref().cloned().unwrap_or_default();
+            if !args.stat_ranges.is_empty() {
+                plan.attribute_ranges = args.stat_ranges.clone();
+            }
+            plan
+        }
+    };
+    if !args.price_bands.is_empty() {
+        plan.price_bands = args.price_bands.clone();
+    }
+    if let Some(per_band) = args.per_band {
+        plan.max_results = Some(per_band);
+        plan.category_max_results.clear();
+    }
+    plan.validate()?;
+    Ok(plan)
+}
+
+fn build_collector(api: &ApiArgs, args: &CollectArgs, league: &str, plan: &CollectionPlan, catalog: Option<&StatCatalogLoader>) -> Result<StatCollector> {
+    let price = &args.price;
+    let mut client = build_league_client(api, league)?;
+    if args.dry_run {
+        // A dry run writes nothing, cached responses included
+        client = client.without_cache();
+    }
+    let mut collector = StatCollector::new(client)
+        .with_price_range(price.min_price, price.max_price, price.price_currency.clone())
+        .with_plan(plan.clone());
+    if args.sockets.is_set() {
+        collector = collector.with_rune_sockets(args.sockets.min_sockets, args.sockets.max_sockets);
+    }
+    if let Some(catalog) = catalog {
+        collector = collector.with_stat_catalog(catalog);
+    }
+    Ok(collector)
+}
+
+// A collector per league, all sharing the process-wide rate budget
+async fn build_collections(
+    api: &ApiArgs,
+    args: &CollectArgs,
+    resume: bool,
+    progress: bool,
+    shutdown: &CancellationToken,
+) -> Result<Vec<LeagueCollection>> {
+    let plan = collection_plan(args).await?;
+    let catalog = match initialize_stat_catalog().await {
+        Ok(catalog) => {
+            info!(stats = catalog.len(), "loaded trade stats catalogue");
+            Some(catalog)
+        }
+        Err(e) => {
+            warn!(error = %e, "failed to load stat catalogue, using built-in stat IDs");
+            None
+        }
+    };
+
+    let leagues = api.leagues();
+    let mut collections = Vec::with_capacity(leagues.len());
+    for league in &leagues {
+        let mut collector = build_collector(api, args, league, &plan, catalog.as_ref())?
+            .with_checkpoint(league_path(Path::new(DEFAULT_CHECKPOINT_PATH), league, leagues.len()), resume)
+            .with_progress(progress)
+            .with_cancellation(shutdown.clone());
+        // Repeated daemon polls mostly find the same listings, so only new ones are fetched
+        if args.daemon && !args.refetch_all {
+            collector = collector.with_search_cache(league_path(Path::new(DEFAULT_SEARCH_CACHE_PATH), league, leagues.len()));
+        }
+        collections.push(LeagueCollection {
+            league: league.clone(),
+            collector,
+            output: league_path(&args.output, league, leagues.len()),
+            resume,
+            summary_json: args.summary_json.as_deref().map(|path| league_path(path, league, leagues.len())),
+        });
+    }
+    Ok(collections)
+}
+
+// Open the configured database with its pool settings
+async fn connect_database(args: &DatabaseArgs) -> Result<Database> {
+    Database::connect_with(&args.url(), &args.pool_settings()).await
+}
+
+// Exchange rates for normalizing prices; without them prices are stored as listed only
+async fn load_currency_converter(client: &mut TradeApiClient) -> CurrencyConverter {
+    initialize_currency_converter(client).await.unwrap_or_else(|e| {
+        warn!(error = %e, "failed to load currency rates, prices won't be normalized");
+        CurrencyConverter::default()
+    })
+}
+
+// Running counts of collected listings converted and saved to the database
+#[derive(Debug, Default)]
+struct StoreCounts {
+    processed: usize,
+    converted: usize,
+    saved: usize,
+}
+
+// Convert and store one collected listing, returning it once stored
+async fn store_collected_item(
+    db: &Database,
+    converter: &CurrencyConverter,
+    item_response: ItemResponse,
+    counts: &mut StoreCounts,
+) -> Option<Item> {
+    counts.processed += 1;
+    match Item::try_from(item_response.clone()) {
+        Ok(mut item) => {
+            counts.converted += 1;
+            if let Some(price) = &mut item.price {
+                converter.normalize(price);
+            }
+            debug!(id = %item.id, name = item.name.as_deref().unwrap_or("unnamed"), "converted item");
+
+            match db.store_collected_item(&item, Some(&item_response)).await {
+                Ok(_) => {
+                    counts.saved += 1;
+                    return Some(item);
+                }
+                Err(e) => {
+                    warn!(id = %item.id, error = %e, "failed to store item in database");
+                    debug!(?item, "item that failed to store");
                 }
             }
-            
-            println!("Collection summary:");
-            println!("Total items processed: {}", total_items);
-            println!("Successful conversions: {}", successful_conversions);
-            println!("Successfully saved to DB: {}", successful_saves);
-        }
-
-        // Initialize the base loader
-        let mut base_loader = initialize_base_loader().await?;
-        println!("Base item cache statistics:");
-        println!("{}", serde_json::to_string_pretty(&base_loader.get_cache_stats())?);
-        
-        // Store base items in database while keeping file-based cache
-        for base_item in base_loader.get_all_bases() {
-            if let Err(e) = db.store_base_item(base_item).await {
-                eprintln!("Warning: Failed to store base item in database: {}", e);
+        }
+        Err(e) => {
+            Metrics::global().record_parse_failures("convert", 1);
+            warn!(index = counts.processed, error = %e, "failed to convert item");
+        }
+    }
+    None
+}
+
+// Collect one league, writing each listing to the output file and the database as it arrives
+// so a run of any length holds only a small buffer of listings in memory. `on_stored` sees
+// each stored listing along with how many fetched listings are still waiting to be.
+async fn run_collection(
+    collection: &mut LeagueCollection,
+    db: &Database,
+    converter: &CurrencyConverter,
+    on_stored: &impl Fn(&Item, usize),
+) -> Result<CollectionSummary> {
+    let started = Instant::now();
+    let collector = &mut collection.collector;
+    let output = collection.output.as_path();
+    let header = collector.dataset_header();
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(COLLECT_BUFFER);
+    let resume = collection.resume;
+    let store = async {
+        let mut writer = if resume {
+            CollectedDataWriter::append(output, &header).await?
+        } else {
+            CollectedDataWriter::create(output, &header).await?
+        };
+        let mut counts = StoreCounts::default();
+        while let Some(item) = receiver.recv().await {
+            writer.write(&item).await?;
+            if let Some(item) = store_collected_item(db, converter, item, &mut counts).await {
+                on_stored(&item, receiver.len());
+            }
+        }
+        writer.finish().await?;
+        Ok::<_, ScraperError>(counts)
+    };
+
+    let (collected, stored) = tokio::join!(collector.collect_into(sender), store);
+    let counts = stored?;
+    collected?;
+    if !collector.unchanged_ids().is_empty() {
+        let seen = db.mark_seen(collector.unchanged_ids()).await?;
+        info!(unchanged = collector.unchanged_ids().len(), seen, "marked unchanged listings as seen");
+    }
+    info!(path = %output.display(), items = counts.processed, "saved collected items");
+    info!(
+        league = collector.summary().league.as_deref(),
+        processed = counts.processed,
+        converted = counts.converted,
+        saved = counts.saved,
+        "league collection finished"
+    );
+
+    let mut summary = collector.summary().clone();
+    summary.db_inserts = counts.saved;
+    summary.set_duration(started.elapsed());
+    if let Some(path) = &collection.summary_json {
+        summary.save_json(path).await?;
+    }
+    Ok(summary)
+}
+
+// Collect every league at once as one collection run, returning a summary per league
+async fn run_collections(
+    collections: &mut [LeagueCollection],
+    db: &Database,
+    converter: &CurrencyConverter,
+    delist_after: u32,
+    on_stored: impl Fn(&Item, usize),
+) -> Result<Vec<CollectionSummary>> {
+    db.begin_collection_run().await?;
+    let runs = collections
+        .iter_mut()
+        .map(|collection| run_collection(collection, db, converter, &on_stored));
+    let summaries = futures_util::future::join_all(runs)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+    // Only after every league completed, so listings a failed or interrupted run never
+    // reached aren't marked
+    if summaries.iter().any(|summary| summary.interrupted) {
+        info!(leagues = summaries.len(), "collection interrupted, not marking delisted listings");
+        return Ok(summaries);
+    }
+    let delisted = db.mark_delisted(delist_after).await?;
+    info!(leagues = summaries.len(), delisted, "collection finished");
+    Ok(summaries)
+}
+
+// Cancelled on the first Ctrl-C, so a run finishes its current batch, flushes its output
+// and commits what it fetched; a second Ctrl-C exits at once
+fn shutdown_on_ctrl_c() -> CancellationToken {
+    let token = CancellationToken::new();
+    let shutdown = token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        info!("shutting down after the current batch; press Ctrl-C again to exit now");
+        shutdown.cancel();
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    token
+}
+
+fn print_summaries(summaries: &[CollectionSummary]) {
+    for summary in summaries {
+        println!("{}", summary);
+    }
+}
+
+// Delete all but the newest `keep` run outputs; names embed the run's timestamp so they sort by age
+async fn rotate_run_outputs(dir: &Path, keep: usize) -> Result<()> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    let mut outputs = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with("collected_data_") && name.ends_with(".json") {
+            outputs.push(entry.path());
+        }
+    }
+
+    outputs.sort();
+    let excess = outputs.len().saturating_sub(keep);
+    for path in &outputs[..excess] {
+        tokio::fs::remove_file(path).await?;
+    }
+    Ok(())
+}
+
+async fn run_daemon(api: &ApiArgs, args: &CollectArgs, db: &Database) -> Result<()> {
+    // One collector per league for every run so the rate limiter's budget carries over
+    // between runs. They always resume, so a run that fails part-way is finished by the next one.
+    let leagues = api.leagues();
+    if !args.resume {
+        for league in &leagues {
+            CollectionCheckpoint::new(league_path(Path::new(DEFAULT_CHECKPOINT_PATH), league, leagues.len())).clear().await?;
+        }
+    }
+    let shutdown = shutdown_on_ctrl_c();
+    let mut collections = build_collections(api, args, true, !args.no_progress, &shutdown).await?;
+    let interval = Duration::from_secs(args.interval_mins.max(1) * 60);
+    let runs_dir = Path::new(RUNS_DIR);
+    tokio::fs::create_dir_all(runs_dir).await?;
+
+    #[cfg(feature = "server")]
+    if let Some(addr) = args.metrics_bind {
+        tokio::spawn(async move {
+            if let Err(e) = rust_scraper::server::serve_metrics(addr).await {
+                error!(error = %e, "metrics endpoint stopped");
+            }
+        });
+    }
+
+    info!(interval_mins = args.interval_mins, "starting collection daemon");
+    loop {
+        let started = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let output = runs_dir.join(format!("collected_data_{}.json", started));
+        for collection in &mut collections {
+            collection.output = league_path(&output, &collection.league, leagues.len());
+        }
+        // Reloaded every run; it only goes to the exchange once the rates are a day old
+        let converter = load_currency_converter(&mut build_client(api)?).await;
+
+        match run_collections(&mut collections, db, &converter, args.delist_after, |_, _| {}).await {
+            Ok(summaries) => print_summaries(&summaries),
+            Err(e) => error!(error = %e, "collection run failed; it will resume on the next run"),
+        }
+        if shutdown.is_cancelled() {
+            // The checkpoint stays on disk so --resume can finish the run
+            info!("interrupted during a run, shutting down");
+            return Ok(());
+        }
+
+        if let Err(e) = rotate_run_outputs(runs_dir, args.keep_runs * collections.len()).await {
+            warn!(error = %e, "failed to rotate run outputs");
+        }
+
+        info!(next_run_in = ?interval, "waiting for next run");
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown.cancelled() => {
+                info!("interrupted, shutting down");
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Load the base item cache and mirror it into the database
+async fn sync_base_items(db: &Database, loader: &BaseDataLoader) {
+    for base_item in loader.get_all_bases() {
+        if let Err(e) = db.store_base_item(base_item).await {
+            warn!(error = %e, "failed to store base item in database");
+        }
+    }
+}
+
+// Check the plan's queries and estimate a run per league; nothing is fetched or written
+async fn dry_run(api: &ApiArgs, args: &CollectArgs) -> Result<()> {
+    let plan = collection_plan(args).await?;
+    // Only an already cached stat catalogue, since refreshing it would write one
+    let mut catalog = StatCatalogLoader::new();
+    let catalog = match catalog.load_from_file(STAT_CATALOG_PATH).await {
+        Ok(()) if !catalog.is_empty() => Some(catalog),
+        _ => {
+            warn!(path = STAT_CATALOG_PATH, "no cached stat catalogue, stat IDs won't be checked");
+            None
+        }
+    };
+
+    let mut problems = 0;
+    for league in api.leagues() {
+        let mut collector = build_collector(api, args, &league, &plan, catalog.as_ref())?;
+        let report = collector.dry_run(catalog.as_ref(), args.count_results).await?;
+        problems += report.problems();
+        println!("{}", report);
+    }
+    if problems > 0 {
+        return Err(ScraperError::ValidationError(format!("the collection plan's queries have {} problems", problems)));
+    }
+    Ok(())
+}
+
+async fn collect(api: &ApiArgs, args: &CollectArgs, database: &DatabaseArgs) -> Result<()> {
+    if args.dry_run {
+        return dry_run(api, args).await;
+    }
+    let db = connect_database(database).await?;
+    if args.daemon {
+        return run_daemon(api, args, &db).await;
+    }
+
+    info!(leagues = ?api.leagues(), "starting data collection");
+    let shutdown = shutdown_on_ctrl_c();
+    let mut collections = build_collections(api, args, args.resume, !args.no_progress, &shutdown).await?;
+    let converter = load_currency_converter(&mut build_client(api)?).await;
+    let summaries = run_collections(&mut collections, &db, &converter, args.delist_after, |_, _| {}).await?;
+    print_summaries(&summaries);
+    Ok(())
+}
+
+// Collect as `collect` does while a dashboard shows how the run is going
+#[cfg(feature = "tui")]
+async fn tui(api: &ApiArgs, args: &TuiArgs, database: &DatabaseArgs) -> Result<()> {
+    let collect = &args.collect;
+    if collect.daemon {
+        return Err(ScraperError::ValidationError("the dashboard runs a single collection; use collect --daemon to keep collecting".to_string()));
+    }
+    if collect.dry_run {
+        return Err(ScraperError::ValidationError("the dashboard shows a collection as it runs; use collect --dry-run to check a plan".to_string()));
+    }
+    let db = connect_database(database).await?;
+    // Progress bars would draw over the dashboard
+    let shutdown = CancellationToken::new();
+    let mut collections = build_collections(api, collect, collect.resume, false, &shutdown).await?;
+    let converter = load_currency_converter(&mut build_client(api)?).await;
+
+    let dashboard = Dashboard::new(args.criteria()).with_shutdown(shutdown);
+    let collection = run_collections(
+        &mut collections,
+        &db,
+        &converter,
+        collect.delist_after,
+        |item, queued| dashboard.record_item(item, queued),
+    );
+    match dashboard.run(collection).await? {
+        Some(summaries) => print_summaries(&summaries),
+        // Listings fetched so far are stored and the checkpoint lets --resume finish the run
+        None => info!("dashboard closed during the run; collect --resume continues it"),
+    }
+    Ok(())
+}
+
+async fn search(api: &ApiArgs, args: &SearchArgs, database: &DatabaseArgs) -> Result<()> {
+    let db = connect_database(database).await?;
+    let base_loader = initialize_base_loader().await?;
+    debug!(stats = %serde_json::to_string(&base_loader.get_cache_stats())?, "base item cache statistics");
+    sync_base_items(&db, &base_loader).await;
+
+    let mut query_builder = QueryBuilder::new()
+        .online()
+        .price_range(args.price.min_price, args.price.max_price);
+    if let Some(currency) = &args.price.price_currency {
+        query_builder = query_builder.price_currency(currency);
+    }
+    if args.sockets.is_set() {
+        query_builder = query_builder.rune_sockets(args.sockets.min_sockets, args.sockets.max_sockets);
+    }
+
+    let mut client = build_client(api)?;
+    let converter = load_currency_converter(&mut client).await;
+    let mut listings = Box::pin(client.search_stream(query_builder.build()).await?);
+
+    let mut saved = 0;
+    while let Some(item_response) = listings.next().await {
+        let item_response = item_response?;
+        match Item::try_from(item_response.clone()) {
+            Ok(mut item) => {
+                // Gems are stored whether or not the base list knows them
+                let base_type = base_loader.get_base(&item.item_type.base_type);
+                if base_type.is_none() && !item.is_gem() {
+                    continue;
+                }
+                if let Some(base_type) = base_type {
+                    item.stat_requirements = base_type.stat_requirements.clone();
+                }
+                if let Some(price) = &mut item.price {
+                    converter.normalize(price);
+                }
+
+                match db.store_collected_item(&item, Some(&item_response)).await {
+                    Ok(_) => saved += 1,
+                    Err(e) => warn!(error = %e, "failed to store processed item"),
+                }
             }
+            Err(e) => warn!(error = %e, "failed to process item"),
+        }
+    }
+
+    info!(saved, "search finished");
+    Ok(())
+}
+
+async fn analyze(args: &AnalyzeArgs, database: &DatabaseArgs) -> Result<()> {
+    let mod_kinds = args.mod_kinds();
+    let mut stat_analyzer = StatAnalyzer::new()
+        .with_mod_kinds(mod_kinds.clone())
+        .with_min_pair_support(args.min_pair_support)
+        .with_include_uniques(args.include_uniques);
+    let mut modifier_analyzer = ModifierAnalyzer::new(args.value_breakpoints.clone());
+    modifier_analyzer.set_mod_kinds(mod_kinds.clone());
+    modifier_analyzer.set_include_uniques(args.include_uniques);
+    let mut unique_analyzer = UniqueAnalyzer::new();
+    unique_analyzer.set_mod_kinds(mod_kinds);
+    let mut gem_analyzer = GemAnalyzer::new();
+    let mut jewel_analyzer = JewelAnalyzer::new();
+    let mut waystone_analyzer = WaystoneAnalyzer::new();
+    // Analysis runs offline, so only use rates already on disk
+    let mut currency_coverage = match CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await {
+        Ok(converter) => {
+            unique_analyzer.set_currency_converter(converter.clone());
+            gem_analyzer.set_currency_converter(converter.clone());
+            jewel_analyzer.set_currency_converter(converter.clone());
+            waystone_analyzer.set_currency_converter(converter.clone());
+            modifier_analyzer.set_currency_converter(converter.clone());
+            CurrencyCoverage::new(converter)
+        }
+        Err(e) => {
+            warn!(error = %e, "no currency rates on disk, modifier prices are not normalized");
+            CurrencyCoverage::new(CurrencyConverter::default())
+        }
+    };
+    let mut unique_loader = UniqueDataLoader::new();
+    match unique_loader.load_from_file(UNIQUE_ITEMS_PATH).await {
+        Ok(()) => unique_analyzer.set_catalogue(unique_loader.catalogue().clone()),
+        Err(e) => debug!(error = %e, "no unique catalogue on disk, roll positions are not reported"),
+    }
+    let mut affix_analyzer = AffixAnalyzer::new(load_cached_bases().await);
+    match AffixDatabase::load_from_file(AFFIX_DATA_PATH).await {
+        Ok(database) => affix_analyzer.set_database(database),
+        Err(e) => debug!(error = %e, "no affix database on disk, only affix counts and crafted mods are checked"),
+    }
+    if let Some(path) = &args.snapshot {
+        if let Some(snapshot) = AnalyzerSnapshot::load(path).await? {
+            info!(
+                path = %path.display(),
+                items = snapshot.stats.total_items(),
+                listings = snapshot.modifiers.listings(),
+                "continuing from analyzer snapshot"
+            );
+            stat_analyzer.restore(snapshot.stats);
+            modifier_analyzer.restore(snapshot.modifiers);
+        }
+    }
+    // Items are analyzed as they're read so the input never sits in memory whole
+    let mut pipeline = AnalysisPipeline::new()
+        .with_analyzer(&mut stat_analyzer)
+        .with_analyzer(&mut modifier_analyzer)
+        .with_analyzer(&mut unique_analyzer)
+        .with_analyzer(&mut affix_analyzer)
+        .with_analyzer(&mut gem_analyzer)
+        .with_analyzer(&mut jewel_analyzer)
+        .with_analyzer(&mut waystone_analyzer)
+        .with_analyzer(&mut currency_coverage);
+    if args.from_db {
+        let db = connect_database(database).await?;
+        let items = pipeline.run_storage(&db).await?;
+        info!(items, "analyzed stored listings");
+    } else {
+        let (header, items) = pipeline.run_with_header(&args.input)?;
+        info!(
+            items,
+            path = %args.input.display(),
+            version = header.version,
+            league = header.league.as_deref(),
+            collected_at = header.collected_at.map(format_timestamp),
+            "analyzed collected data"
+        );
+    }
+    let coverage = currency_coverage.coverage_report();
+    if coverage.converted_listings < coverage.priced_listings {
+        warn!(
+            converted = coverage.converted_listings,
+            priced = coverage.priced_listings,
+            "some listings are priced in currencies without a rate; see the report's currencies section"
+        );
+    }
+    if let Some(path) = &args.snapshot {
+        AnalyzerSnapshot::new(stat_analyzer.snapshot(), modifier_analyzer.snapshot()).save(path).await?;
+        info!(path = %path.display(), "saved analyzer snapshot");
+    }
+    if let Some(filter) = args.outlier_filter() {
+        let summary = modifier_analyzer.apply_outlier_filter(filter);
+        info!(below = summary.below, above = summary.above, excluded = summary.excluded, "checked listing prices for outliers");
+    }
+
+    let model = modifier_analyzer.price_model(args.price_feature.into(), args.ridge_lambda, args.min_occurrences);
+    if model.is_none() {
+        warn!(min_occurrences = args.min_occurrences, "not enough data to fit the modifier price model");
+    }
+    let clusters = args.clusters.and_then(|k| {
+        let report = modifier_analyzer.clusters(k, args.min_occurrences, args.cluster_seed);
+        if report.is_none() {
+            warn!(k, "not enough listings or modifiers to cluster");
+        }
+        report
+    });
+    if let Some(dir) = &args.charts {
+        let files = write_charts(dir, &modifier_analyzer, &stat_analyzer, args.chart_modifiers).await?;
+        info!(charts = files.len(), dir = %dir.display(), "wrote charts");
+    }
+
+    let report = serde_json::json!({
+        "attributes": stat_analyzer.generate_attribute_report(),
+        "modifiers": modifier_analyzer.generate_report(model.as_ref(), clusters.as_ref()),
+        "uniques": unique_analyzer.report(),
+        "affixes": affix_analyzer.report(),
+        "gems": gem_analyzer.report(),
+        "jewels": jewel_analyzer.report(),
+        "waystones": waystone_analyzer.report(),
+        "currencies": coverage,
+    });
+    if let Some(dir) = &args.out {
+        let files = write_report(&report, args.format.into(), dir).await?;
+        info!(files = files.len(), dir = %dir.display(), "exported stat analysis report");
+        return Ok(());
+    }
+
+    let report = serde_json::to_string_pretty(&report)?;
+    match &args.output {
+        Some(path) => {
+            tokio::fs::write(path, report).await?;
+            info!(path = %path.display(), "wrote stat analysis report");
+        }
+        None => println!("{}", report),
+    }
+    Ok(())
+}
+
+async fn stacking(args: &StackingArgs, database: &DatabaseArgs) -> Result<()> {
+    let mut analyzer = AttributeStackAnalyzer::new(args.attribute.into())
+        .with_min_listings(args.min_listings)
+        .with_top_bases(args.top);
+    match CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await {
+        Ok(converter) => analyzer.set_currency_converter(converter),
+        Err(e) => warn!(error = %e, "no currency rates on disk, prices are not normalized"),
+    }
+    let mut pipeline = AnalysisPipeline::new().with_analyzer(&mut analyzer);
+    if args.from_db {
+        let db = connect_database(database).await?;
+        pipeline.run_storage(&db).await?;
+    } else {
+        pipeline.run(&args.input)?;
+    }
+
+    let report = analyzer.stacking_report();
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let price = |price: Option<f64>| price.map_or("-".to_string(), |price| format!("{:.2}", price));
+    println!(
+        "{:?}: {} of {} listings grant it, median price {} (others {}), {} per point",
+        report.attribute,
+        report.stacking_listings,
+        report.listings,
+        price(report.median_price_stacking),
+        price(report.median_price_other),
+        price(report.price_per_point),
+    );
+
+    println!("\n{:<45} {:>8} {:>7} {:>7} {:>6} {:>7} {:>9}", "modifier", "listings", "with", "without", "lift", "slope", "median");
+    for modifier in &report.modifiers {
+        println!(
+            "{:<45} {:>8} {:>6.0}% {:>6.0}% {:>6} {:>7} {:>9}",
+            modifier.modifier,
+            modifier.listings,
+            modifier.share_stacking * 100.0,
+            modifier.share_other * 100.0,
+            modifier.lift.map_or("only".to_string(), |lift| format!("{:.2}", lift)),
+            modifier.value_fit.as_ref().map_or("-".to_string(), |fit| format!("{:+.2}", fit.slope)),
+            price(modifier.median_price),
+        );
+    }
+
+    println!("\n{:<35} {:>8} {:>6} {:>7} {:>9}", "base", "listings", "max", "median", "price");
+    for base in &report.bases {
+        println!(
+            "{:<35} {:>8} {:>6.0} {:>7.0} {:>9}",
+            base.base_type,
+            base.listings,
+            base.max_attribute,
+            base.median_attribute,
+            price(base.median_price),
+        );
+    }
+
+    println!("\n{:<9} {:>8} {:>9}", "total", "listings", "median");
+    for bracket in &report.price_brackets {
+        println!(
+            "{:<9} {:>8} {:>9.2}",
+            format!("{:.0}+", bracket.min_attribute),
+            bracket.listings,
+            bracket.median_price,
+        );
+    }
+    Ok(())
+}
+
+async fn record_trends(args: &TrendRecordArgs, database: &DatabaseArgs) -> Result<()> {
+    let mut trends = match &args.day {
+        Some(day) => TrendAnalyzer::new(day.clone()),
+        None => TrendAnalyzer::today(),
+    };
+    trends.set_mod_kinds(args.mod_kinds());
+    match CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await {
+        Ok(converter) => trends.set_currency_converter(converter),
+        Err(e) => warn!(error = %e, "no currency rates on disk, recording prices as listed"),
+    }
+
+    let items = AnalysisPipeline::new().with_analyzer(&mut trends).run(&args.input)?;
+    let aggregates = trends.aggregates();
+    let db = connect_database(database).await?;
+    db.store_price_aggregates(&aggregates).await?;
+    info!(
+        day = trends.day(),
+        items,
+        series = aggregates.len(),
+        unconverted = trends.unconverted_listings(),
+        "recorded price history"
+    );
+    Ok(())
+}
+
+async fn show_trends(args: &TrendShowArgs, database: &DatabaseArgs) -> Result<()> {
+    let (kind, subject) = args.subject();
+    let db = connect_database(database).await?;
+    if args.by_value {
+        return show_price_buckets(&db, subject).await;
+    }
+    let history = db.price_history(kind, subject).await?;
+    if history.is_empty() {
+        println!("No price history for {} '{}'", kind, subject);
+        return Ok(());
+    }
+
+    let change = |delta: Option<f64>| delta.map_or("-".to_string(), |d| format!("{:+.1}%", d * 100.0));
+    println!(
+        "{:<10} {:>10} {:>10} {:>10} {:>7} {:>8} {:>8} {:>8} {:>10} {:>10}  signal",
+        "day", "median", "p25", "p75", "volume", "listings", "d/d", "w/w", "supply d/d", "supply w/w"
+    );
+    for point in TrendAnalyzer::deltas(&history) {
+        let aggregate = &point.aggregate;
+        println!(
+            "{:<10} {:>10.2} {:>10.2} {:>10.2} {:>7} {:>8} {:>8} {:>8} {:>10} {:>10}  {}",
+            aggregate.day,
+            aggregate.median,
+            aggregate.p25,
+            aggregate.p75,
+            aggregate.volume,
+            aggregate.listings,
+            change(point.day_over_day),
+            change(point.week_over_week),
+            change(point.supply_day_over_day),
+            change(point.supply_week_over_week),
+            point.signal.map_or("", |signal| signal.as_str()),
+        );
+    }
+    Ok(())
+}
+
+async fn show_price_buckets(db: &Database, modifier: &str) -> Result<()> {
+    let buckets = db.modifier_price_buckets(modifier).await?;
+    if buckets.is_empty() {
+        println!("No price aggregates for modifier '{}'; run `db refresh-aggregates` first", modifier);
+        return Ok(());
+    }
+
+    println!("{:<10} {:>21} {:>10} {:>10} {:>10} {:>8}", "day", "values", "median", "p25", "p75", "listings");
+    for bucket in buckets {
+        println!(
+            "{:<10} {:>21} {:>10.2} {:>10.2} {:>10.2} {:>8}",
+            bucket.day,
+            format!("{:.1}-{:.1}", bucket.value_min, bucket.value_max),
+            bucket.median,
+            bucket.p25,
+            bucket.p75,
+            bucket.listings,
+        );
+    }
+    Ok(())
+}
+
+async fn heatmap(args: &HeatmapArgs, database: &DatabaseArgs) -> Result<()> {
+    let mut heatmap = ModValueHeatmap::new(args.modifiers.clone())
+        .with_class(args.class)
+        .with_top_modifiers(args.top)
+        .with_min_listings(args.min_listings);
+    match CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await {
+        Ok(converter) => heatmap.set_currency_converter(converter),
+        Err(e) => warn!(error = %e, "no currency rates on disk, prices are not normalized"),
+    }
+    let mut pipeline = AnalysisPipeline::new().with_analyzer(&mut heatmap);
+    if args.from_db {
+        let db = connect_database(database).await?;
+        pipeline.run_storage(&db).await?;
+    } else {
+        pipeline.run(&args.input)?;
+    }
+
+    let report = heatmap.heatmap_report();
+    let content = match args.format {
+        ReportFormatArg::Json => serde_json::to_string_pretty(&report)?,
+        ReportFormatArg::Csv => report.to_csv(),
+        ReportFormatArg::Html => report.to_html(),
+    };
+    match &args.output {
+        Some(path) => {
+            tokio::fs::write(path, content).await.with_context(|| format!("writing {}", path.display()))?;
+            info!(bases = report.rows.len(), columns = report.columns.len(), path = %path.display(), "wrote heatmap");
+        }
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+async fn export(args: &ExportArgs, database: &DatabaseArgs) -> Result<()> {
+    if let Some(ExportTarget::Filter(filter)) = &args.target {
+        return export_filter(filter, database).await;
+    }
+    let db = connect_database(database).await?;
+    if let Some(dir) = &args.dataset {
+        let compression = args.compress.map(Compression::from).unwrap_or_default();
+        let summary = export_dataset(&db, dir, args.format.into(), compression).await?;
+        info!(
+            listings = summary.listings,
+            modifiers = summary.modifiers,
+            item_modifiers = summary.item_modifiers,
+            days = summary.partitions,
+            dir = %dir.display(),
+            "exported dataset"
+        );
+        return Ok(());
+    }
+
+    let records = db.collected_items().await?;
+    let content = match args.format {
+        ExportFormat::Json => serde_json::to_string_pretty(&records)?,
+        ExportFormat::Jsonl => records
+            .iter()
+            .map(|record| serde_json::to_string(record).map(|line| line + "\n"))
+            .collect::<serde_json::Result<String>>()?,
+        ExportFormat::Csv => collected_items_csv(&records),
+        #[cfg(feature = "parquet")]
+        ExportFormat::Parquet => {
+            return Err(ScraperError::ValidationError(
+                "parquet is only written as a dataset; pass --dataset <DIR>".to_string(),
+            ))
+        }
+    };
+    match &args.output {
+        Some(path) => {
+            compression::write_file(path, content.as_bytes()).await?;
+            info!(items = records.len(), path = %path.display(), "exported collected items");
+        }
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+async fn export_filter(args: &FilterArgs, database: &DatabaseArgs) -> Result<()> {
+    let converter = match CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await {
+        Ok(converter) => converter,
+        Err(e) => {
+            warn!(error = %e, "no currency rates on disk, only prices in the base currency are used");
+            CurrencyConverter::default()
+        }
+    };
+    let (amount, currency) = &args.min_value;
+    let min_value = match currency {
+        Some(currency) => converter.convert(*amount, currency).ok_or_else(|| ScraperError::ValidationError(format!(
+            "no exchange rate for {} on disk; run `currency update` first",
+            currency
+        )))?,
+        None => *amount,
+    };
+
+    let mut analyzer = LootFilterAnalyzer::new(min_value)
+        .with_min_listings(args.min_listings)
+        .with_price_model(args.ridge_lambda, args.min_occurrences);
+    let base = converter.base().to_string();
+    analyzer.set_currency_converter(converter);
+    let mut pipeline = AnalysisPipeline::new().with_analyzer(&mut analyzer);
+    // Stored items only keep mod text, while filters match affix names, so the listings
+    // are read back as fetched
+    let listings = if args.from_db {
+        let db = connect_database(database).await?;
+        pipeline.run_raw_storage(&db).await?
+    } else {
+        pipeline.run(&args.input)?
+    };
+
+    let rules = analyzer.rules();
+    let filter = analyzer.render(&rules, &base);
+    match &args.output {
+        Some(path) => {
+            tokio::fs::write(path, filter).await.with_context(|| format!("writing {}", path.display()))?;
+            info!(
+                listings,
+                rules = rules.len(),
+                unconverted = analyzer.unconverted_listings(),
+                path = %path.display(),
+                "wrote loot filter"
+            );
+        }
+        None => print!("{}", filter),
+    }
+    Ok(())
+}
+
+async fn check_price(args: &PriceCheckArgs, database: &DatabaseArgs) -> Result<()> {
+    let content = tokio::fs::read_to_string(&args.input).await
+        .with_context(|| format!("reading {}", args.input.display()))?;
+    let item = if content.trim_start().starts_with('{') {
+        let response: ItemResponse = serde_json::from_str(&content)
+            .with_context(|| format!("parsing listing JSON in {}", args.input.display()))?;
+        Item::try_from(response)?
+    } else {
+        // Copied from the game client, where magic items carry their affixes in the base
+        let mut item = ItemText::parse(&content)?.to_item();
+        if let Some(base) = load_cached_bases().await.get_base(&item.item_type.base_type) {
+            item.item_type.classify(Some(base), &[]);
+            item.item_type.base_type = base.name.clone();
         }
+        item
+    };
+    let db = connect_database(database).await?;
+    let check = price_check(&db, &item, args.count, args.sold_within_hours).await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&check)?);
+        return Ok(());
+    }
+
+    println!("{} ({}), compared against {} listings", check.base_type, check.category, check.candidates);
+    for comparable in &check.comparables {
+        let listing = &comparable.listing;
+        let price = match (listing.price_amount, &listing.price_currency) {
+            (Some(amount), Some(currency)) => format!("{} {}", amount, currency),
+            _ => "unpriced".to_string(),
+        };
+        println!(
+            "  {:>5.1}%  {:<20} {}",
+            comparable.similarity * 100.0,
+            price,
+            listing.name.as_deref().unwrap_or(&listing.trade_id)
+        );
+    }
+    match check.median_price {
+        Some(median) => println!("Median of comparables: {:.2} (base currency)", median),
+        None => println!("No comparable listings with a normalized price"),
+    }
+    match check.median_sold_price {
+        Some(median) => println!(
+            "Median of {} {} sold within {}h: {:.2} (base currency)",
+            check.probable_sales.len(),
+            check.base_type,
+            args.sold_within_hours,
+            median
+        ),
+        None => println!("No {} sold within {}h with a normalized price", check.base_type, args.sold_within_hours),
+    }
+    Ok(())
+}
+
+// Convert every stored raw listing again, e.g. after the models learned to map more of it
+async fn reprocess(database: &DatabaseArgs) -> Result<()> {
+    let db = connect_database(database).await?;
+    let converter = CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await.unwrap_or_else(|e| {
+        warn!(error = %e, "no currency rates on disk, only exalted prices will be normalized");
+        CurrencyConverter::default()
+    });
 
-        let mut client = TradeApiClient::new(args.league);
-        let mut modifier_analyzer = ModifierAnalyzer::new(vec![
-            0.0, 10.0, 20.0, 30.0, 40.0, 50.0
-        ]);
-        let mut stat_analyzer = StatAnalyzer::new();
-
-        let query = SearchRequest {
-            query: TradeQuery {
-                status: StatusFilter {
-                    option: "online".to_string(),
-                },
-                stats: vec![StatFilter {
-                    r#type: "and".to_string(),
-                    filters: vec![],
-                    disabled: false,
-                }],
-                filters: QueryFilters {
-                    type_filters: TypeFilters {
-                        filters: CategoryFilter {
-                            category: CategoryOption {
-                                option: "any".to_string(),
-                            },
-                        },
-                    },
-                },
-            },
-            sort: Some(serde_json::json!({
-                "price": "asc"
-            })),
+    let (mut reprocessed, mut failed) = (0, 0);
+    let mut after_id = 0;
+    loop {
+        let page = db.raw_listings_after(after_id, ITEM_PAGE_SIZE).await?;
+        let Some(&(last_id, _)) = page.last() else {
+            break;
         };
+        after_id = last_id;
 
-        let search_response = client.search_items(query).await?;
-        let raw_items = client.fetch_items(search_response.get_result_ids()).await?;
-        
-        for raw_item in raw_items {
-            let conversion_result = serde_json::from_value::<ItemResponse>(raw_item)
-                .map_err(|e| ScraperError::ParseError(e.to_string()))
-                .and_then(|response| Item::try_from(response));
-        
-            match conversion_result {
+        for (id, raw_json) in page {
+            let converted = serde_json::from_str::<ItemResponse>(&raw_json)
+                .map_err(ScraperError::from)
+                .and_then(Item::try_from);
+            match converted {
                 Ok(mut item) => {
-                    if let Some(base_type) = base_loader.get_base(&item.item_type.base_type) {
-                        item.stat_requirements = base_type.stat_requirements.clone();
-                        
-                        if let Err(e) = db.store_collected_item(&item).await {
-                            eprintln!("Warning: Failed to store processed item: {}", e);
-                        }
+                    if let Some(price) = &mut item.price {
+                        converter.normalize(price);
                     }
+                    db.update_collected_item(&item).await?;
+                    reprocessed += 1;
                 }
                 Err(e) => {
-                    eprintln!("Warning: Failed to process item: {}", e);
-                    continue;
+                    warn!(id, error = %e, "failed to convert stored listing");
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    info!(reprocessed, failed, "reprocessed stored listings");
+    Ok(())
+}
+
+fn print_migrations(heading: &str, migrations: &[MigrationStatus]) {
+    if migrations.is_empty() {
+        println!("{}: none", heading);
+        return;
+    }
+    println!("{}:", heading);
+    for migration in migrations {
+        println!("  {:>14}  {:<40}  {}", migration.version, migration.description, migration.state);
+    }
+}
+
+async fn migrate(command: Option<&MigrateCommand>, database: &DatabaseArgs) -> Result<()> {
+    let db = Database::open_with(&database.url(), &database.pool_settings()).await?;
+    match command.unwrap_or(&MigrateCommand::Run { dry_run: false }) {
+        MigrateCommand::Status => {
+            let statuses = db.migration_status().await?;
+            print_migrations("Migrations", &statuses);
+            let changed = statuses.iter().filter(|s| s.state == MigrationState::ChecksumMismatch).count();
+            if changed > 0 {
+                warn!(changed, "applied migrations were edited afterwards; `db migrate run` will refuse to continue");
+            }
+        }
+        MigrateCommand::Run { dry_run: true } => {
+            let pending: Vec<_> = db
+                .migration_status()
+                .await?
+                .into_iter()
+                .filter(|s| s.state == MigrationState::Pending)
+                .collect();
+            print_migrations("Pending migrations", &pending);
+        }
+        MigrateCommand::Run { dry_run: false } => {
+            let applied = db.run_migrations().await?;
+            print_migrations("Applied migrations", &applied);
+            info!("database is up to date");
+        }
+        MigrateCommand::Revert { target } => {
+            let reverted = db.revert_migrations(*target).await?;
+            print_migrations("Reverted migrations", &reverted);
+        }
+        MigrateCommand::Fresh { force } => {
+            if !force {
+                return Err(ScraperError::ValidationError(
+                    "`db migrate fresh` deletes all stored data; pass --force to confirm".to_string(),
+                ));
+            }
+            let reverted = db.revert_migrations(Some(0)).await?;
+            let applied = db.run_migrations().await?;
+            info!(reverted = reverted.len(), applied = applied.len(), "recreated the database schema");
+            print_migrations("Applied migrations", &applied);
+        }
+    }
+    Ok(())
+}
+
+async fn refresh_price_aggregates(args: &RefreshAggregatesArgs, database: &DatabaseArgs) -> Result<()> {
+    let db = connect_database(database).await?;
+    let summary = refresh_aggregates(&db, args.value_buckets).await?;
+    println!(
+        "Aggregated {} priced listings into {} buckets across {} modifiers",
+        summary.listings, summary.buckets, summary.modifiers
+    );
+    Ok(())
+}
+
+async fn import(args: &ImportArgs, database: &DatabaseArgs) -> Result<()> {
+    let db = connect_database(database).await?;
+    let converter = CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await.unwrap_or_else(|e| {
+        warn!(error = %e, "no currency rates on disk, only exalted prices will be normalized");
+        CurrencyConverter::default()
+    });
+
+    let summary = import_listings(&db, &args.path, &converter).await?;
+    println!(
+        "Imported {} listings; kept {} newer stored copies, skipped {} without a raw listing, {} failed",
+        summary.imported, summary.kept_existing, summary.missing_raw, summary.failed
+    );
+    Ok(())
+}
+
+async fn prune_listings(args: &PruneArgs, database: &DatabaseArgs) -> Result<()> {
+    let mut converter = None;
+    if args.keep_aggregates {
+        match CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await {
+            Ok(rates) => converter = Some(rates),
+            Err(e) => warn!(error = %e, "no currency rates on disk, aggregating prices as listed"),
+        }
+    }
+    let policy = RetentionPolicy {
+        older_than: args.older_than,
+        keep_aggregates: args.keep_aggregates,
+        converter,
+        vacuum: !args.no_vacuum,
+    };
+
+    let db = connect_database(database).await?;
+    let summary = prune(&db, policy).await?;
+    println!(
+        "Deleted {} listings last seen before {}, kept {} daily aggregates",
+        summary.listings_deleted, summary.cutoff, summary.aggregates_added
+    );
+    Ok(())
+}
+
+async fn show_sales(args: &SalesArgs, database: &DatabaseArgs) -> Result<()> {
+    let db = connect_database(database).await?;
+    let sales = db.probable_sales(args.within_hours, args.base_type.as_deref()).await?;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&sales)?);
+        return Ok(());
+    }
+
+    println!("{} listings delisted within {}h of first being seen", sales.len(), args.within_hours);
+    for sale in &sales {
+        let price = match (sale.price_amount, &sale.price_currency) {
+            (Some(amount), Some(currency)) => format!("{} {}", amount, currency),
+            _ => "unpriced".to_string(),
+        };
+        println!(
+            "  {:>6.1}h  {:<20} {} ({})",
+            sale.hours_listed,
+            price,
+            sale.name.as_deref().unwrap_or(&sale.trade_id),
+            sale.base_type
+        );
+    }
+    Ok(())
+}
+
+async fn notify_listing(notifier: &mut Notifier, converter: &CurrencyConverter, listing: ItemResponse) {
+    let id = listing.id.clone();
+    let mut item = match Item::try_from(listing) {
+        Ok(item) => item,
+        Err(e) => {
+            warn!(%id, error = %e, "failed to convert live listing, not checking it for notification");
+            return;
+        }
+    };
+    if let Some(price) = &mut item.price {
+        converter.normalize(price);
+    }
+    if let Err(e) = notifier.notify(&item).await {
+        warn!(%id, error = %e, "failed to send listing notification");
+    }
+}
+
+async fn live(api: &ApiArgs, args: &LiveArgs) -> Result<()> {
+    let live = LiveSearchClient::new(build_client(api)?, args.search_id.clone());
+    let mut notifier = args.notify.webhook.as_ref().map(|url| {
+        let mut notifier = Notifier::new(url.clone(), args.notify.webhook_format.into(), args.notify.criteria());
+        notifier.set_min_interval(Duration::from_secs(args.notify.notify_interval_secs));
+        notifier
+    });
+    // Price limits are in exalted, so prices are normalized with the rates already on disk
+    let converter = match &notifier {
+        Some(_) => CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await.unwrap_or_else(|e| {
+            warn!(error = %e, "no currency rates on disk, only listings priced in exalted can meet --notify-max-price");
+            CurrencyConverter::default()
+        }),
+        None => CurrencyConverter::default(),
+    };
+    let mut listings = live.start().await?;
+
+    while let Some(listing) = listings.recv().await {
+        match listing {
+            Ok(item) => {
+                println!("New listing: {} - {} for {} {}",
+                    item.id,
+                    item.item.type_line,
+                    item.listing.price.amount,
+                    item.listing.price.currency);
+                if let Some(notifier) = &mut notifier {
+                    notify_listing(notifier, &converter, item).await;
                 }
             }
+            Err(e) => error!(error = %e, "live search error"),
+        }
+    }
+
+    info!("live search closed");
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+async fn serve(args: &cli::ServeArgs, database: &DatabaseArgs) -> Result<()> {
+    let db = connect_database(database).await?;
+    rust_scraper::server::serve(db, args.bind).await
+}
+
+async fn list_leagues(api: &ApiArgs) -> Result<()> {
+    let leagues = build_client(api)?.list_leagues().await?;
+    println!("Active PoE2 leagues:");
+    for league in leagues {
+        println!("  {}", league.id);
+    }
+    Ok(())
+}
+
+async fn update_bases(database: &DatabaseArgs) -> Result<()> {
+    let mut loader = BaseDataLoader::new();
+    loader.update_from_api(BASE_ITEMS_URL).await?;
+    loader.save_to_file(BASE_ITEMS_PATH).await?;
+
+    let db = connect_database(database).await?;
+    sync_base_items(&db, &loader).await;
+    info!(bases = loader.get_all_bases().count(), "updated base items");
+
+    // Keep the mod ranges of a seeded catalogue and add the uniques it lacks
+    let mut unique_loader = UniqueDataLoader::new();
+    let _ = unique_loader.load_from_file(UNIQUE_ITEMS_PATH).await;
+    unique_loader.update_from_api(BASE_ITEMS_URL).await?;
+    unique_loader.save_to_file(UNIQUE_ITEMS_PATH).await?;
+    info!(uniques = unique_loader.catalogue().len(), "updated unique items");
+    Ok(())
+}
+
+// Bases for commands that run offline: the cache file, else the snapshot built into the binary
+async fn load_cached_bases() -> BaseDataLoader {
+    let mut loader = BaseDataLoader::new();
+    if let Err(e) = loader.load_from_file(BASE_ITEMS_PATH).await {
+        debug!(error = %e, "no base item cache on disk");
+        #[cfg(feature = "bundled-bases")]
+        if let Err(e) = loader.load_bundled() {
+            warn!(error = %e, "failed to load the bundled base items");
+        }
+    }
+    loader
+}
+
+async fn build_affixes(args: &AffixBuildArgs) -> Result<()> {
+    let mut analyzer = AffixAnalyzer::new(load_cached_bases().await);
+    let items = AnalysisPipeline::new().with_analyzer(&mut analyzer).run(&args.input)?;
+
+    // Seeded affixes keep their levels unless a listing shows a lower one
+    let mut database = match AffixDatabase::load_from_file(AFFIX_DATA_PATH).await {
+        Ok(database) => database,
+        Err(e) => {
+            debug!(error = %e, "no affix database on disk, starting a new one");
+            AffixDatabase::default()
+        }
+    };
+    database.merge(analyzer.into_learned());
+    database.save_to_file(AFFIX_DATA_PATH).await?;
+    info!(items, path = AFFIX_DATA_PATH, "updated affix database");
+    Ok(())
+}
+
+async fn show_affixes(args: &AffixShowArgs) -> Result<()> {
+    let bases = load_cached_bases().await;
+    let Some(base) = bases.get_base(&args.base) else {
+        return Err(ScraperError::ValidationError(format!("unknown base type: {}", args.base)));
+    };
+    let database = AffixDatabase::load_from_file(AFFIX_DATA_PATH).await?;
+    let mut affixes = database.possible_mods(base, args.ilvl);
+    affixes.sort_by(|a, b| (a.slot, &a.name, a.level).cmp(&(b.slot, &b.name, b.level)));
+    println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+        "base": base.name,
+        "category": base.category,
+        "item_level": args.ilvl,
+        "affixes": affixes,
+    }))?);
+    Ok(())
+}
+
+// Read the config file and merge it under the command line flags. An explicit --config
+// must exist; the default scraper.toml is optional.
+async fn load_config(cli: &mut Cli) -> Result<()> {
+    let (path, required) = match &cli.config {
+        Some(path) => (path.clone(), true),
+        None => (DEFAULT_CONFIG_PATH.into(), false),
+    };
+    let config = ScraperConfig::load(&path, required).await?;
+    cli.apply_config(config);
+    Ok(())
+}
+
+async fn init_config(path: &Path, force: bool) -> Result<()> {
+    ScraperConfig::write_template(path, force).await?;
+    info!(path = %path.display(), "wrote config template");
+    Ok(())
+}
+
+async fn update_currency_rates(api: &ApiArgs) -> Result<()> {
+    let mut converter = CurrencyConverter::load_from_file(DEFAULT_RATES_PATH)
+        .await
+        .unwrap_or_default();
+    converter.update_from_exchange(&mut build_client(api)?, EXCHANGE_CURRENCIES).await?;
+    converter.save_to_file(DEFAULT_RATES_PATH).await?;
+
+    println!("Rates in {}:", converter.base());
+    for currency in EXCHANGE_CURRENCIES {
+        if let Some(rate) = converter.convert(1.0, currency) {
+            println!("  {:<22} {:.3}", currency.long_name(), rate);
+        }
+    }
+    Ok(())
+}
+
+async fn currency_coverage(args: &CoverageArgs, database: &DatabaseArgs) -> Result<()> {
+    let converter = CurrencyConverter::load_from_file(DEFAULT_RATES_PATH).await.unwrap_or_else(|e| {
+        warn!(error = %e, "no currency rates on disk, only prices in the base currency count as converted");
+        CurrencyConverter::default()
+    });
+    let mut coverage = CurrencyCoverage::new(converter);
+    let mut pipeline = AnalysisPipeline::new().with_analyzer(&mut coverage);
+    if args.from_db {
+        let db = connect_database(database).await?;
+        pipeline.run_storage(&db).await?;
+    } else {
+        pipeline.run(&args.input)?;
+    }
+
+    let report = coverage.coverage_report();
+    let Some(share) = report.coverage else {
+        println!("No priced listings");
+        return Ok(());
+    };
+    println!(
+        "{} of {} priced listings ({:.1}%) convert to {}; {} unpriced",
+        report.converted_listings,
+        report.priced_listings,
+        share * 100.0,
+        report.base,
+        report.unpriced_listings
+    );
+    for currency in &report.currencies {
+        println!(
+            "  {:<28} {:>7}  {}",
+            currency.currency.long_name(),
+            currency.listings,
+            if currency.converted { "" } else { "no rate" }
+        );
+    }
+    Ok(())
+}
+
+fn main() -> Result<()> {
+    let mut cli = Cli::parse();
+    init_tracing(&cli);
+
+    tokio::runtime::Runtime::new()?.block_on(async {
+        // `config init` must work even when the existing file doesn't parse
+        if !matches!(cli.command, Command::Config(_)) {
+            load_config(&mut cli).await?;
+        }
+
+        if cli.api.all_leagues {
+            cli.api.leagues = build_client(&cli.api)?
+                .list_leagues()
+                .await?
+                .into_iter()
+                .map(|league| league.id)
+                .collect();
+        }
+        if cli.api.leagues.len() > 1 && !cli.command.takes_several_leagues() {
+            return Err(ScraperError::ValidationError(
+                "only collect and tui take several leagues; give one --league".to_string(),
+            ));
         }
 
-        // Generate and save analysis reports
-        if args.analyze_stats {
-            let stat_report = stat_analyzer.generate_attribute_report();
-            
-            println!("Stat Analysis Report:");
-            println!("{}", serde_json::to_string_pretty(&stat_report)?);
+        // Leagues from --all-leagues came from the list they'd be checked against
+        if cli.command.uses_league() && !cli.api.skip_league_check && !cli.api.all_leagues {
+            for league in cli.api.leagues() {
+                match build_league_client(&cli.api, &league)?.validate_league().await {
+                    Ok(()) => {}
+                    Err(e @ ScraperError::ValidationError(_)) => return Err(e),
+                    Err(e) => warn!(league, error = %e, "could not verify league"),
+                }
+            }
         }
 
-        println!("Analysis complete!");
-        Ok(())
+        match &cli.command {
+            Command::Collect(args) => collect(&cli.api, args, &cli.database).await,
+            Command::Search(args) => search(&cli.api, args, &cli.database).await,
+            Command::Analyze(args) => analyze(args, &cli.database).await,
+            Command::Stacking(args) => stacking(args, &cli.database).await,
+            Command::Heatmap(args) => heatmap(args, &cli.database).await,
+            Command::Export(args) => export(args, &cli.database).await,
+            Command::PriceCheck(args) => check_price(args, &cli.database).await,
+            Command::Live(args) => live(&cli.api, args).await,
+            Command::Leagues => list_leagues(&cli.api).await,
+            #[cfg(feature = "tui")]
+            Command::Tui(args) => tui(&cli.api, args, &cli.database).await,
+            #[cfg(feature = "server")]
+            Command::Serve(args) => serve(args, &cli.database).await,
+            Command::Db(DbCommand::Migrate(args)) => migrate(args.command.as_ref(), &cli.database).await,
+            Command::Db(DbCommand::Sales(args)) => show_sales(args, &cli.database).await,
+            Command::Db(DbCommand::Reprocess) => reprocess(&cli.database).await,
+            Command::Db(DbCommand::Prune(args)) => prune_listings(args, &cli.database).await,
+            Command::Db(DbCommand::Import(args)) => import(args, &cli.database).await,
+            Command::Db(DbCommand::RefreshAggregates(args)) => refresh_price_aggregates(args, &cli.database).await,
+            Command::Bases(BasesCommand::Update) => update_bases(&cli.database).await,
+            Command::Affixes(AffixesCommand::Build(args)) => build_affixes(args).await,
+            Command::Affixes(AffixesCommand::Show(args)) => show_affixes(args).await,
+            Command::Currency(CurrencyCommand::Update) => update_currency_rates(&cli.api).await,
+            Command::Currency(CurrencyCommand::Coverage(args)) => currency_coverage(args, &cli.database).await,
+            Command::Config(ConfigCommand::Init { path, force }) => init_config(path, *force).await,
+            Command::Trends(TrendsCommand::Record(args)) => record_trends(args, &cli.database).await,
+            Command::Trends(TrendsCommand::Show(args)) => show_trends(args, &cli.database).await,
+        }
     })
-}
\ No newline at end of file
+}
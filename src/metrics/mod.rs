@@ -0,0 +1,128 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use reqwest::StatusCode;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+// Prometheus metrics for long-running collection, recorded wherever the work happens and
+// served as /metrics by `serve` and `collect --daemon --metrics-bind`
+pub struct Metrics {
+    registry: Registry,
+    api_requests: IntCounterVec,
+    rate_limit_waits: HistogramVec,
+    items_ingested: IntCounter,
+    parse_failures: IntCounterVec,
+    db_insert_seconds: Histogram,
+    items_analyzed: IntCounter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let api_requests = IntCounterVec::new(
+            Opts::new("poe2_api_requests_total", "Requests to pathofexile.com by endpoint policy and HTTP status"),
+            &["endpoint", "status"],
+        )
+        .unwrap();
+        let rate_limit_waits = HistogramVec::new(
+            HistogramOpts::new("poe2_rate_limit_wait_seconds", "Time requests were held back by the rate budget")
+                .buckets(vec![0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0]),
+            &["endpoint"],
+        )
+        .unwrap();
+        let items_ingested = IntCounter::new("poe2_items_ingested_total", "Listings stored in the database").unwrap();
+        let parse_failures = IntCounterVec::new(
+            Opts::new("poe2_parse_failures_total", "Listings that failed to parse (fetch) or convert to an item (convert)"),
+            &["stage"],
+        )
+        .unwrap();
+        let db_insert_seconds = Histogram::with_opts(
+            HistogramOpts::new("poe2_db_insert_seconds", "Time taken to store one listing")
+                .buckets(vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 1.0]),
+        )
+        .unwrap();
+        let items_analyzed = IntCounter::new("poe2_items_analyzed_total", "Items run through the analysis pipeline").unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(api_requests.clone())).unwrap();
+        registry.register(Box::new(rate_limit_waits.clone())).unwrap();
+        registry.register(Box::new(items_ingested.clone())).unwrap();
+        registry.register(Box::new(parse_failures.clone())).unwrap();
+        registry.register(Box::new(db_insert_seconds.clone())).unwrap();
+        registry.register(Box::new(items_analyzed.clone())).unwrap();
+
+        Self {
+            registry,
+            api_requests,
+            rate_limit_waits,
+            items_ingested,
+            parse_failures,
+            db_insert_seconds,
+            items_analyzed,
+        }
+    }
+
+    // The metrics the whole process records into
+    pub fn global() -> &'static Metrics {
+        static GLOBAL: OnceLock<Metrics> = OnceLock::new();
+        GLOBAL.get_or_init(Metrics::new)
+    }
+
+    // A response, or None when the request failed before one arrived
+    pub fn record_api_response(&self, endpoint: &str, status: Option<StatusCode>) {
+        let status = status.map(|status| status.as_u16().to_string());
+        self.api_requests
+            .with_label_values(&[endpoint, status.as_deref().unwrap_or("error")])
+            .inc();
+    }
+
+    pub fn record_rate_limit_wait(&self, endpoint: &str, waited: Duration) {
+        self.rate_limit_waits.with_label_values(&[endpoint]).observe(waited.as_secs_f64());
+    }
+
+    pub fn record_item_stored(&self, elapsed: Duration) {
+        self.items_ingested.inc();
+        self.db_insert_seconds.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_parse_failures(&self, stage: &str, count: usize) {
+        self.parse_failures.with_label_values(&[stage]).inc_by(count as u64);
+    }
+
+    pub fn record_item_analyzed(&self) {
+        self.items_analyzed.inc();
+    }
+
+    // Every metric in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        // Writing into a Vec only fails on metrics the registry itself rejected
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut buffer);
+        String::from_utf8_lossy(&buffer).into_owned()
+    }
+}
+
+// Content-Type of what Metrics::render returns
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recorded_metrics_render() {
+        let metrics = Metrics::new();
+        metrics.record_api_response("search", Some(StatusCode::OK));
+        metrics.record_api_response("search", Some(StatusCode::OK));
+        metrics.record_api_response("fetch", None);
+        metrics.record_item_stored(Duration::from_millis(3));
+        metrics.record_parse_failures("fetch", 2);
+
+        let text = metrics.render();
+        assert!(text.contains(r#"poe2_api_requests_total{endpoint="search",status="200"} 2"#));
+        assert!(text.contains(r#"poe2_api_requests_total{endpoint="fetch",status="error"} 1"#));
+        assert!(text.contains("poe2_items_ingested_total 1"));
+        assert!(text.contains("poe2_db_insert_seconds_count 1"));
+        assert!(text.contains(r#"poe2_parse_failures_total{stage="fetch"} 2"#));
+    }
+}
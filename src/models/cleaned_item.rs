@@ -1,18 +1,36 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use super::ItemResponse;
-use super::poe_item::{Magnitude, ModInfo as PoeModInfo};
-use crate::models::poe_item::ModBase;
+use super::{Item, ItemResponse, ItemSockets};
+use crate::models::poe_item::{Magnitude, ModBase, ModKind, Price};
+use crate::models::item_type::ItemRarity;
+use crate::models::gem::GemProperties;
+use crate::models::jewel::JewelProperties;
+use crate::models::waystone::WaystoneProperties;
+use crate::models::stats_requirements::attribute_requirements;
 use std::ops::Deref;
 use crate::analyzer::stat_analyzer::ModInfoLike;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CleanedItem {
+    // Listing information; absent for items that didn't come from a trade listing
+    #[serde(default)]
+    pub id: String,             // from the listing id
+    #[serde(default)]
+    pub price: Option<Price>,   // from listing.price
+
     // Core item information
     pub base_type: String,      // from baseType
-    pub name: String,           // from name
+    pub name: String,           // from name, or typeLine for items without one
+    #[serde(default)]
+    pub rarity: ItemRarity,     // from rarity
     pub explicit_mods: Vec<String>,  // from explicitMods
     pub item_level: u32,        // from ilvl
+    #[serde(default)]
+    pub quality: Option<u32>,   // from the Quality property
+    #[serde(default)]
+    pub corrupted: bool,        // from corrupted
+    #[serde(default)]
+    pub mirrored: bool,         // from duplicated
     
     // Item attributes
     pub properties: Vec<ItemProperty>,    // from properties
@@ -21,6 +39,22 @@ pub struct CleanedItem {
     // Mod information
     pub mod_info: ModInfo,      // structured mod data from extended.mods
     pub mod_hashes: HashMap<String, Vec<Vec<i32>>>,  // from extended.hashes
+
+    // Level, quality and kind of gems
+    #[serde(default)]
+    pub gem: Option<GemProperties>,
+
+    // Radius, limit and allocated passives of jewels
+    #[serde(default)]
+    pub jewel: Option<JewelProperties>,
+
+    // Tier and juicing bonuses of waystones
+    #[serde(default)]
+    pub waystone: Option<WaystoneProperties>,
+
+    // Socket count and the runes or soul cores in them
+    #[serde(default)]
+    pub sockets: Option<ItemSockets>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -37,9 +71,39 @@ pub struct ItemRequirement {
     pub display_mode: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct ModInfo {
     pub explicit: Vec<ExplicitMod>,  // Collection of explicit mods
+    #[serde(default)]
+    pub implicit: Vec<ExplicitMod>,
+    #[serde(default)]
+    pub enchant: Vec<ExplicitMod>,
+    #[serde(default)]
+    pub rune: Vec<ExplicitMod>,
+    #[serde(default)]
+    pub crafted: Vec<ExplicitMod>,
+}
+
+impl ModInfo {
+    pub fn of_kind(&self, kind: ModKind) -> &[ExplicitMod] {
+        match kind {
+            ModKind::Explicit => &self.explicit,
+            ModKind::Implicit => &self.implicit,
+            ModKind::Enchant => &self.enchant,
+            ModKind::Rune => &self.rune,
+            ModKind::Crafted => &self.crafted,
+        }
+    }
+
+    pub(crate) fn of_kind_mut(&mut self, kind: ModKind) -> &mut Vec<ExplicitMod> {
+        match kind {
+            ModKind::Explicit => &mut self.explicit,
+            ModKind::Implicit => &mut self.implicit,
+            ModKind::Enchant => &mut self.enchant,
+            ModKind::Rune => &mut self.rune,
+            ModKind::Crafted => &mut self.crafted,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -52,6 +116,12 @@ pub struct ExplicitMod {
     // pub tier: String,
 }
 
+impl ExplicitMod {
+    pub(crate) fn new(base: ModBase, level: u32) -> Self {
+        Self { base, level }
+    }
+}
+
 impl Deref for ExplicitMod {
     type Target = ModBase;
     
@@ -63,10 +133,20 @@ impl Deref for ExplicitMod {
 impl CleanedItem {
     pub fn from_response(response: &ItemResponse) -> Self {
         Self {
+            id: response.id.clone(),
+            price: Some(response.listing.price.clone()),
             base_type: response.item.base_type.clone(),
-            name: response.item.type_line.clone(),
+            name: if response.item.name.is_empty() {
+                response.item.type_line.clone()
+            } else {
+                response.item.name.clone()
+            },
+            rarity: response.item.item_rarity(),
             explicit_mods: response.item.explicit_mods.clone(),
             item_level: response.item.ilvl,
+            quality: response.item.quality(),
+            corrupted: response.item.corrupted,
+            mirrored: response.item.duplicated,
             
             // Map properties maintaining their structure
             properties: response.item.properties.iter()
@@ -86,43 +166,114 @@ impl CleanedItem {
                 })
                 .collect(),
             
-            // Map the explicit mods data
+            // Map the structured mods data of every kind
             mod_info: ModInfo {
-                explicit: response.item.extended.mods.explicit.iter()
-                    .map(|m| ExplicitMod {
-                        base: ModBase {
-                            name: m.name.clone(),
-                            tier: m.tier.clone(),
-                            magnitudes: m.magnitudes.clone(),
-                        },
-                        level: m.magnitudes.first()
-                            .map(|mag| mag.min.parse::<u32>().unwrap_or(0))
-                            .unwrap_or(0),
-                    })
-                    .collect(),
+                explicit: Self::map_mods(response, ModKind::Explicit),
+                implicit: Self::map_mods(response, ModKind::Implicit),
+                enchant: Self::map_mods(response, ModKind::Enchant),
+                rune: Self::map_mods(response, ModKind::Rune),
+                crafted: Self::map_mods(response, ModKind::Crafted),
             },
             
             // Map the hash data structure
-            mod_hashes: response.item.extended.hashes.explicit.iter()
+            mod_hashes: response.item.extended.hashes.all()
                 .map(|(k, v)| (k.clone(), vec![v.clone()]))
                 .collect(),
+            gem: GemProperties::from_listing(&response.item),
+            jewel: JewelProperties::from_listing(&response.item),
+            waystone: WaystoneProperties::from_listing(&response.item),
+            sockets: ItemSockets::from_listing(&response.item),
         }
     }
 
-    pub fn get_stat_requirements(&self) -> HashMap<String, u32> {
-        self.requirements.iter()
-            .filter(|req| {
-                matches!(req.name.as_str(),
-                    "[Strength|Str]" | "[Dexterity|Dex]" | "[Intelligence|Int]")
+    // Rebuild an item from what storage keeps of it. Mods are named by their template, e.g.
+    // "+# to maximum Life", since stored listings keep mod text rather than affix names,
+    // and keep the kind storage could recover for them.
+    pub fn from_item(item: &Item) -> Self {
+        let mut mod_info = ModInfo::default();
+        for modifier in &item.modifiers {
+            let mapped = ExplicitMod {
+                base: ModBase {
+                    name: modifier.template(),
+                    tier: modifier.tier.map(|tier| tier.to_string()).unwrap_or_default(),
+                    magnitudes: if modifier.rolls.is_empty() {
+                        modifier.values.iter()
+                            .map(|value| Magnitude {
+                                hash: String::new(),
+                                min: value.to_string(),
+                                max: value.to_string(),
+                            })
+                            .collect()
+                    } else {
+                        modifier.rolls.iter()
+                            .map(|roll| Magnitude {
+                                hash: String::new(),
+                                min: roll.min.to_string(),
+                                max: roll.max.to_string(),
+                            })
+                            .collect()
+                    },
+                    rolls: modifier.rolls.clone(),
+                },
+                level: 0,
+            };
+            mod_info.of_kind_mut(modifier.kind).push(mapped);
+        }
+
+        let mut requirements: Vec<ItemRequirement> = item.attribute_values.iter()
+            .map(|(attribute, value)| ItemRequirement {
+                name: attribute.requirement_name().to_string(),
+                values: vec![(value.to_string(), 0)],
+                display_mode: 0,
             })
-            .filter_map(|req| {
-                req.values.first().map(|(value, _)| {
-                    (req.name.clone(), value.parse::<u32>().unwrap_or(0))
-                })
+            .collect();
+        requirements.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self {
+            id: item.id.clone(),
+            price: item.price.as_ref().map(|price| Price {
+                amount: price.amount,
+                currency: price.currency.clone(),
+            }),
+            base_type: item.item_type.base_type.clone(),
+            name: item.name.clone().unwrap_or_else(|| item.item_type.base_type.clone()),
+            rarity: item.item_type.rarity,
+            explicit_mods: item.modifiers.iter()
+                .filter(|modifier| modifier.kind == ModKind::Explicit)
+                .map(|modifier| modifier.name.clone())
+                .collect(),
+            item_level: 0,
+            quality: item.quality,
+            corrupted: item.corrupted,
+            mirrored: item.mirrored,
+            properties: Vec::new(),
+            requirements,
+            mod_info,
+            mod_hashes: HashMap::new(),
+            gem: item.gem,
+            jewel: JewelProperties::from_item(item),
+            waystone: WaystoneProperties::from_item(item),
+            sockets: item.sockets.clone(),
+        }
+    }
+
+    fn map_mods(response: &ItemResponse, kind: ModKind) -> Vec<ExplicitMod> {
+        response.item.rolled_mods(kind).into_iter()
+            .map(|base| ExplicitMod {
+                level: base.magnitudes.first()
+                    .map(|mag| mag.min.parse::<u32>().unwrap_or(0))
+                    .unwrap_or(0),
+                base,
             })
             .collect()
     }
 
+    pub fn get_stat_requirements(&self) -> HashMap<String, u32> {
+        attribute_requirements(self.requirements.iter().filter_map(|req| {
+            req.values.first().map(|(value, _)| (req.name.as_str(), value.as_str()))
+        }))
+    }
+
     pub fn get_explicit_mods(&self) -> Vec<(&str, &str)> {
         self.mod_info.explicit.iter()
             .map(|m| (m.get_name(), m.get_tier()))
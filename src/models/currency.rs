@@ -0,0 +1,167 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt;
+use std::str::FromStr;
+
+// A currency listings are priced in. Currencies the scraper doesn't know keep the name
+// they were listed under, so nothing is lost when the game adds new ones.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Currency {
+    Exalted,
+    Divine,
+    Chaos,
+    Regal,
+    Alchemy,
+    Annulment,
+    Vaal,
+    Chance,
+    Transmutation,
+    Augmentation,
+    Mirror,
+    Unknown(String),
+}
+
+impl Currency {
+    pub const KNOWN: [Currency; 11] = [
+        Currency::Exalted,
+        Currency::Divine,
+        Currency::Chaos,
+        Currency::Regal,
+        Currency::Alchemy,
+        Currency::Annulment,
+        Currency::Vaal,
+        Currency::Chance,
+        Currency::Transmutation,
+        Currency::Augmentation,
+        Currency::Mirror,
+    ];
+
+    // The ID the trade API prices listings and exchange offers in, e.g. "alch"
+    pub fn as_str(&self) -> &str {
+        match self {
+            Currency::Exalted => "exalted",
+            Currency::Divine => "divine",
+            Currency::Chaos => "chaos",
+            Currency::Regal => "regal",
+            Currency::Alchemy => "alch",
+            Currency::Annulment => "annul",
+            Currency::Vaal => "vaal",
+            Currency::Chance => "chance",
+            Currency::Transmutation => "transmute",
+            Currency::Augmentation => "aug",
+            Currency::Mirror => "mirror",
+            Currency::Unknown(name) => name,
+        }
+    }
+
+    // The in-game item name, e.g. "Orb of Alchemy"
+    pub fn long_name(&self) -> &str {
+        match self {
+            Currency::Exalted => "Exalted Orb",
+            Currency::Divine => "Divine Orb",
+            Currency::Chaos => "Chaos Orb",
+            Currency::Regal => "Regal Orb",
+            Currency::Alchemy => "Orb of Alchemy",
+            Currency::Annulment => "Orb of Annulment",
+            Currency::Vaal => "Vaal Orb",
+            Currency::Chance => "Orb of Chance",
+            Currency::Transmutation => "Orb of Transmutation",
+            Currency::Augmentation => "Orb of Augmentation",
+            Currency::Mirror => "Mirror of Kalandra",
+            Currency::Unknown(name) => name,
+        }
+    }
+
+    // Parse a trade API ID, an in-game name or a common short form, ignoring case, plurals
+    // and whether words are split by spaces, hyphens or underscores. Anything else, such as
+    // a barter item, is kept as an Unknown currency under the name given.
+    pub fn from_name(name: &str) -> Self {
+        let lower = name.trim().to_lowercase().replace(['-', '_'], " ");
+        Currency::from_lowercase(&lower)
+            .or_else(|| lower.strip_suffix('s').and_then(Currency::from_lowercase))
+            .unwrap_or_else(|| Currency::Unknown(name.trim().to_string()))
+    }
+
+    fn from_lowercase(lower: &str) -> Option<Self> {
+        match lower {
+            "exa" | "ex" | "exalt" => Some(Currency::Exalted),
+            "div" => Some(Currency::Divine),
+            "alchemy" => Some(Currency::Alchemy),
+            "annulment" => Some(Currency::Annulment),
+            "transmutation" | "transmute" => Some(Currency::Transmutation),
+            "augmentation" | "augment" => Some(Currency::Augmentation),
+            "mirror of kalandra" => Some(Currency::Mirror),
+            _ => Currency::KNOWN
+                .into_iter()
+                .find(|currency| currency.as_str() == lower || currency.long_name().to_lowercase() == lower),
+        }
+    }
+
+    pub fn is_known(&self) -> bool {
+        !matches!(self, Currency::Unknown(_))
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = Infallible;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(Currency::from_name(name))
+    }
+}
+
+impl From<&str> for Currency {
+    fn from(name: &str) -> Self {
+        Currency::from_name(name)
+    }
+}
+
+impl From<String> for Currency {
+    fn from(name: String) -> Self {
+        Currency::from_name(&name)
+    }
+}
+
+// Written as the trade API ID, so stored listings and rate files keep the strings they
+// always held
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Currency::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_currency_names_and_unknown_fallback() {
+        assert_eq!(Currency::from_name("Orb of Alchemy"), Currency::Alchemy);
+        assert_eq!(Currency::from_name("div"), Currency::Divine);
+        assert_eq!(Currency::from_name("EXALTED"), Currency::Exalted);
+        assert_eq!(Currency::Alchemy.to_string(), "alch");
+        assert_eq!(Currency::Divine.long_name(), "Divine Orb");
+        assert_eq!(Currency::from_name("orb-of-alchemy"), Currency::Alchemy);
+        assert_eq!(Currency::from_name("Exalted Orbs"), Currency::Exalted);
+        assert_eq!(Currency::from_name("divines"), Currency::Divine);
+        assert_eq!(Currency::from_name("chaos"), Currency::Chaos);
+
+        let unknown: Currency = serde_json::from_str("\"greater-jewellers-orb\"").unwrap();
+        assert_eq!(unknown, Currency::Unknown("greater-jewellers-orb".to_string()));
+        assert!(!unknown.is_known());
+        assert_eq!(serde_json::to_string(&unknown).unwrap(), "\"greater-jewellers-orb\"");
+        assert_eq!(serde_json::to_string(&Currency::Annulment).unwrap(), "\"annul\"");
+    }
+}
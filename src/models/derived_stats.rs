@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use super::poe_item::Property;
+
+// Quality most weapons and armour can be raised to, used to compare items as if maxed
+pub const MAX_QUALITY: f64 = 20.0;
+
+// Damage, speed and defence numbers worked out from a weapon's or armour's properties.
+// Property values already include quality and local mods; the `_q20` fields project the
+// same item to 20% quality using the local percent mods among its explicits.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DerivedStats {
+    pub quality: f64,
+    pub attacks_per_second: Option<f64>,
+    pub critical_chance: Option<f64>,
+    pub physical_dps: Option<f64>,
+    pub elemental_dps: Option<f64>,
+    pub chaos_dps: Option<f64>,
+    pub total_dps: Option<f64>,
+    pub armour: Option<f64>,
+    pub evasion: Option<f64>,
+    pub energy_shield: Option<f64>,
+    pub physical_dps_q20: Option<f64>,
+    pub armour_q20: Option<f64>,
+    pub evasion_q20: Option<f64>,
+    pub energy_shield_q20: Option<f64>,
+}
+
+// Local percent increases found on the item's explicit mods
+#[derive(Debug, Default)]
+struct LocalIncreases {
+    physical: f64,
+    armour: f64,
+    evasion: f64,
+    energy_shield: f64,
+}
+
+impl LocalIncreases {
+    fn from_mods(explicit_mods: &[String]) -> Self {
+        let mut increases = Self::default();
+        for text in explicit_mods {
+            let Some((amount, stat)) = parse_increase(text) else {
+                continue;
+            };
+            match stat {
+                "Physical Damage" => increases.physical += amount,
+                "Armour" => increases.armour += amount,
+                "Evasion Rating" => increases.evasion += amount,
+                "Energy Shield" => increases.energy_shield += amount,
+                "Armour and Evasion" => {
+                    increases.armour += amount;
+                    increases.evasion += amount;
+                }
+                "Armour and Energy Shield" => {
+                    increases.armour += amount;
+                    increases.energy_shield += amount;
+                }
+                "Evasion and Energy Shield" => {
+                    increases.evasion += amount;
+                    increases.energy_shield += amount;
+                }
+                "Armour, Evasion and Energy Shield" => {
+                    increases.armour += amount;
+                    increases.evasion += amount;
+                    increases.energy_shield += amount;
+                }
+                _ => {}
+            }
+        }
+        increases
+    }
+}
+
+impl DerivedStats {
+    // None for items without damage or defence properties, e.g. jewellery
+    pub fn from_properties(properties: &[Property], explicit_mods: &[String]) -> Option<Self> {
+        let mut stats = Self::default();
+        let mut physical = None;
+        let mut elemental = None;
+        let mut chaos = None;
+
+        for property in properties {
+            let values = || property.values.iter().map(|(value, _)| parse_value(value));
+            let first = values().next().flatten();
+            match display_name(&property.name).as_str() {
+                "Quality" => stats.quality = first.unwrap_or(0.0),
+                "Physical Damage" => physical = first,
+                "Elemental Damage" | "Fire Damage" | "Cold Damage" | "Lightning Damage" => {
+                    // One value per element on the combined property
+                    let sum: f64 = values().flatten().sum();
+                    elemental = Some(elemental.unwrap_or(0.0) + sum);
+                }
+                "Chaos Damage" => chaos = first,
+                "Attacks per Second" => stats.attacks_per_second = first,
+                "Critical Hit Chance" | "Critical Strike Chance" => stats.critical_chance = first,
+                "Armour" => stats.armour = first,
+                "Evasion Rating" => stats.evasion = first,
+                "Energy Shield" => stats.energy_shield = first,
+                _ => {}
+            }
+        }
+
+        if let Some(aps) = stats.attacks_per_second {
+            stats.physical_dps = physical.map(|damage| damage * aps);
+            stats.elemental_dps = elemental.map(|damage| damage * aps);
+            stats.chaos_dps = chaos.map(|damage| damage * aps);
+            let parts = [stats.physical_dps, stats.elemental_dps, stats.chaos_dps];
+            if parts.iter().any(Option::is_some) {
+                stats.total_dps = Some(parts.iter().flatten().sum());
+            }
+        }
+
+        let increases = LocalIncreases::from_mods(explicit_mods);
+        let quality = stats.quality;
+        stats.physical_dps_q20 = stats.physical_dps.map(|dps| at_max_quality(dps, increases.physical, quality));
+        stats.armour_q20 = stats.armour.map(|value| at_max_quality(value, increases.armour, quality));
+        stats.evasion_q20 = stats.evasion.map(|value| at_max_quality(value, increases.evasion, quality));
+        stats.energy_shield_q20 = stats.energy_shield
+            .map(|value| at_max_quality(value, increases.energy_shield, quality));
+
+        let has_stats = stats.total_dps.is_some()
+            || stats.armour.is_some()
+            || stats.evasion.is_some()
+            || stats.energy_shield.is_some();
+        has_stats.then_some(stats)
+    }
+
+    // Flattened into the name -> value map kept on Item, skipping what the item doesn't have
+    pub fn to_stats(&self) -> HashMap<String, f64> {
+        let fields = [
+            ("quality", Some(self.quality)),
+            ("attacks_per_second", self.attacks_per_second),
+            ("critical_chance", self.critical_chance),
+            ("physical_dps", self.physical_dps),
+            ("elemental_dps", self.elemental_dps),
+            ("chaos_dps", self.chaos_dps),
+            ("total_dps", self.total_dps),
+            ("armour", self.armour),
+            ("evasion", self.evasion),
+            ("energy_shield", self.energy_shield),
+            ("physical_dps_q20", self.physical_dps_q20),
+            ("armour_q20", self.armour_q20),
+            ("evasion_q20", self.evasion_q20),
+            ("energy_shield_q20", self.energy_shield_q20),
+        ];
+        fields.into_iter()
+            .filter_map(|(name, value)| value.map(|value| (name.to_string(), value)))
+            .collect()
+    }
+}
+
+// Quality and local increases are additive, so the base value is the shown value divided
+// by the current multiplier
+fn at_max_quality(value: f64, increased: f64, quality: f64) -> f64 {
+    if quality >= MAX_QUALITY {
+        return value;
+    }
+    value * (100.0 + increased + MAX_QUALITY) / (100.0 + increased + quality)
+}
+
+// Property names mark up their keywords, e.g. "[Critical|Critical Hit] Chance" is shown
+// as "Critical Hit Chance"
+pub(crate) fn display_name(name: &str) -> String {
+    let mut display = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(start) = rest.find('[') {
+        display.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(']') else {
+            break;
+        };
+        let inner = &rest[start + 1..start + end];
+        display.push_str(inner.rsplit('|').next().unwrap_or(inner));
+        rest = &rest[start + end + 1..];
+    }
+    display.push_str(rest);
+    display
+}
+
+// "12-24" averages to 18, "+20%" and "1.50" read as numbers
+fn parse_value(value: &str) -> Option<f64> {
+    let value = value.trim().trim_start_matches('+').trim_end_matches('%');
+    match value.split_once('-') {
+        Some((min, max)) => {
+            let min: f64 = min.trim().parse().ok()?;
+            let max: f64 = max.trim().parse().ok()?;
+            Some((min + max) / 2.0)
+        }
+        None => value.parse().ok(),
+    }
+}
+
+// "54% increased Armour" -> (54.0, "Armour")
+fn parse_increase(text: &str) -> Option<(f64, &str)> {
+    let (amount, stat) = text.split_once("% increased ")?;
+    Some((amount.trim_start_matches('+').parse().ok()?, stat.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property(name: &str, values: &[&str]) -> Property {
+        Property {
+            name: name.to_string(),
+            values: values.iter().map(|value| (value.to_string(), 1)).collect(),
+            display_mode: 0,
+        }
+    }
+
+    #[test]
+    fn test_weapon_and_armour_stats() {
+        let weapon = [
+            property("[Quality]", &["+10%"]),
+            property("[Physical] Damage", &["20-40"]),
+            property("[Elemental Damage]", &["5-15", "1-9"]),
+            property("[Critical|Critical Hit] Chance", &["5.00%"]),
+            property("[Attacks per Second]", &["1.50"]),
+        ];
+        let mods = vec!["50% increased Physical Damage".to_string()];
+        let stats = DerivedStats::from_properties(&weapon, &mods).unwrap();
+        assert_eq!(stats.physical_dps, Some(45.0));
+        assert_eq!(stats.elemental_dps, Some(22.5));
+        assert_eq!(stats.total_dps, Some(67.5));
+        assert_eq!(stats.critical_chance, Some(5.0));
+        // 45 at 160% becomes 170%
+        assert!((stats.physical_dps_q20.unwrap() - 45.0 * 170.0 / 160.0).abs() < 1e-9);
+
+        let armour = [property("[Armour]", &["483"]), property("[Quality]", &["+20%"])];
+        let stats = DerivedStats::from_properties(&armour, &[]).unwrap();
+        assert_eq!(stats.armour_q20, Some(483.0));
+        assert_eq!(stats.to_stats().get("armour"), Some(&483.0));
+        assert!(!stats.to_stats().contains_key("total_dps"));
+
+        assert!(DerivedStats::from_properties(&[property("Ring", &[])], &[]).is_none());
+    }
+}
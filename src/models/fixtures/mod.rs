@@ -0,0 +1,145 @@
+// Listings and items for tests, so each test only states what it cares about
+use super::{CleanedItem, Currency, Item, ItemCategory, ItemModifier, ItemPrice, ItemRarity, ItemResponse, ItemType, ModKind};
+
+// A trade API listing: a rare, item level 80 base priced in exalted unless set otherwise
+pub(crate) struct ListingBuilder {
+    id: String,
+    base_type: String,
+    name: String,
+    rarity: String,
+    mods: Vec<String>,
+    affixes: Vec<serde_json::Value>,
+    properties: Vec<serde_json::Value>,
+    amount: f64,
+    currency: String,
+}
+
+impl ListingBuilder {
+    pub(crate) fn new(id: &str, base_type: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            base_type: base_type.to_string(),
+            name: String::new(),
+            rarity: "Rare".to_string(),
+            mods: Vec::new(),
+            affixes: Vec::new(),
+            properties: Vec::new(),
+            amount: 1.0,
+            currency: "exalted".to_string(),
+        }
+    }
+
+    pub(crate) fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    // Normal, Magic, Rare or Unique
+    pub(crate) fn with_rarity(mut self, rarity: &str) -> Self {
+        self.rarity = rarity.to_string();
+        self
+    }
+
+    // Explicit modifier text, e.g. "+80 to maximum Life"
+    pub(crate) fn with_mods(mut self, mods: &[&str]) -> Self {
+        self.mods.extend(mods.iter().map(|text| text.to_string()));
+        self
+    }
+
+    // An explicit affix in the extended data, with one magnitude of the stat hash
+    pub(crate) fn with_affix(mut self, name: &str, tier: &str, hash: &str, min: f64, max: f64) -> Self {
+        self.affixes.push(serde_json::json!({
+            "name": name,
+            "tier": tier,
+            "magnitudes": [{ "hash": hash, "min": min.to_string(), "max": max.to_string() }]
+        }));
+        self
+    }
+
+    // A property with one value, e.g. ("Monster Pack Size", "+12%")
+    pub(crate) fn with_property(mut self, name: &str, value: &str) -> Self {
+        self.properties.push(serde_json::json!({ "name": name, "values": [[value, 0]] }));
+        self
+    }
+
+    pub(crate) fn with_price(mut self, amount: f64) -> Self {
+        self.amount = amount;
+        self
+    }
+
+    pub(crate) fn with_currency(mut self, currency: &str) -> Self {
+        self.currency = currency.to_string();
+        self
+    }
+
+    pub(crate) fn json(&self) -> serde_json::Value {
+        let frame_type = match self.rarity.as_str() {
+            "Magic" => 1,
+            "Rare" => 2,
+            "Unique" => 3,
+            _ => 0,
+        };
+        serde_json::json!({
+            "id": self.id,
+            "item": {
+                "name": self.name,
+                "base_type": self.base_type,
+                "typeLine": self.base_type,
+                "explicitMods": self.mods,
+                "extended": { "mods": { "explicit": self.affixes }, "hashes": { "explicit": [] } },
+                "frameType": frame_type,
+                "requirements": [],
+                "properties": self.properties,
+                "rarity": self.rarity,
+                "ilvl": 80
+            },
+            "listing": {
+                "price": { "amount": self.amount, "currency": self.currency },
+                "account": { "name": "seller", "realm": "poe2" }
+            }
+        })
+    }
+
+    pub(crate) fn response(&self) -> ItemResponse {
+        serde_json::from_value(self.json()).unwrap()
+    }
+
+    pub(crate) fn cleaned(&self) -> CleanedItem {
+        CleanedItem::from_response(&self.response())
+    }
+}
+
+// A stored rare Iron Ring priced in exalted
+pub(crate) fn ring(id: &str, price: f64) -> Item {
+    let mut item = Item::new(
+        id.to_string(),
+        ItemType::new(ItemCategory::Accessory, "Iron Ring".to_string(), ItemRarity::Rare),
+    );
+    item.price = Some(ItemPrice { amount: price, currency: Currency::Exalted, normalized_amount: Some(price) });
+    item
+}
+
+// As ring, with a "+<life> to maximum Life" modifier
+pub(crate) fn life_ring(id: &str, price: f64, life: f64) -> Item {
+    let mut item = ring(id, price);
+    item.add_modifier(ItemModifier {
+        stat_hashes: vec!["explicit.stat_3299347043".to_string()],
+        ..explicit_modifier(&format!("+{} to maximum Life", life), &[life])
+    });
+    item
+}
+
+// An explicit modifier with no tier, rolls or requirements
+pub(crate) fn explicit_modifier(name: &str, values: &[f64]) -> ItemModifier {
+    ItemModifier {
+        name: name.to_string(),
+        tier: None,
+        values: values.to_vec(),
+        rolls: Vec::new(),
+        is_crafted: false,
+        kind: ModKind::Explicit,
+        stat_requirements: None,
+        attribute_scaling: None,
+        stat_hashes: Vec::new(),
+    }
+}
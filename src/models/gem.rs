@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use super::derived_stats::display_name;
+use super::poe_item::ItemData;
+
+// frameType the trade API gives skill and support gems
+pub const GEM_FRAME_TYPE: i32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GemKind {
+    Active,
+    Support,
+}
+
+impl GemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GemKind::Active => "active",
+            GemKind::Support => "support",
+        }
+    }
+}
+
+impl std::fmt::Display for GemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct GemProperties {
+    pub kind: GemKind,
+    pub level: u32,
+    pub quality: u32,
+}
+
+impl GemProperties {
+    // None for listings that aren't gems
+    pub fn from_listing(item: &ItemData) -> Option<Self> {
+        if item.frame_type != GEM_FRAME_TYPE {
+            return None;
+        }
+
+        let mut gem = Self {
+            kind: if item.support.unwrap_or(false) { GemKind::Support } else { GemKind::Active },
+            level: 1,
+            quality: 0,
+        };
+        for property in &item.properties {
+            let Some(value) = property.values.first().and_then(|(value, _)| leading_number(value)) else {
+                continue;
+            };
+            match display_name(&property.name).as_str() {
+                "Level" => gem.level = value,
+                "Quality" => gem.quality = value,
+                _ => {}
+            }
+        }
+        Some(gem)
+    }
+
+    // Flattened into the name -> value map kept on Item, the only place stored listings
+    // keep them
+    pub fn to_stats(&self) -> HashMap<String, f64> {
+        HashMap::from([
+            ("gem_level".to_string(), self.level as f64),
+            ("gem_quality".to_string(), self.quality as f64),
+            ("gem_support".to_string(), (self.kind == GemKind::Support) as u8 as f64),
+        ])
+    }
+
+    pub fn from_stats(stats: &HashMap<String, f64>) -> Option<Self> {
+        Some(Self {
+            kind: if stats.get("gem_support").copied().unwrap_or(0.0) > 0.0 { GemKind::Support } else { GemKind::Active },
+            level: *stats.get("gem_level")? as u32,
+            quality: stats.get("gem_quality").copied().unwrap_or(0.0) as u32,
+        })
+    }
+}
+
+// "20 (Max)" -> 20, "+15%" -> 15
+pub(crate) fn leading_number(value: &str) -> Option<u32> {
+    let digits: String = value.trim().trim_start_matches('+').chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gem_properties_from_listing() {
+        let item: ItemData = serde_json::from_value(serde_json::json!({
+            "base_type": "Controlled Destruction",
+            "typeLine": "Controlled Destruction",
+            "explicitMods": [],
+            "extended": { "mods": { "explicit": [] }, "hashes": { "explicit": [] } },
+            "frameType": 4,
+            "support": true,
+            "requirements": [],
+            "properties": [
+                { "name": "Support", "values": [], "display_mode": 0 },
+                { "name": "Level", "values": [["20 (Max)", 0]], "display_mode": 0 },
+                { "name": "[Quality]", "values": [["+13%", 1]], "display_mode": 0 }
+            ],
+            "rarity": "Gem",
+            "ilvl": 0
+        }))
+        .unwrap();
+
+        let gem = GemProperties::from_listing(&item).unwrap();
+        assert_eq!(gem, GemProperties { kind: GemKind::Support, level: 20, quality: 13 });
+        assert_eq!(GemProperties::from_stats(&gem.to_stats()), Some(gem));
+        assert_eq!(serde_json::to_value(&item).unwrap()["support"], true);
+    }
+}
@@ -1,29 +1,233 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use super::item_type::{ItemType, ItemRarity};
+use super::item_type::{ItemType, ItemRarity, ItemCategory};
 use super::stats_requirements::{
     CoreAttribute,
     StatRequirements,
     ModifierStatRequirements,
 };
-use super::poe_item::ItemResponse;
-use crate::ItemCategory;
+use super::poe_item::{ItemData, ItemResponse, ListingData, Magnitude, ModKind, StashLocation};
+use super::currency::Currency;
+use super::derived_stats::DerivedStats;
+use super::gem::GemProperties;
 use crate::errors::{ScraperError, Result};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemModifier {
     pub name: String,
     pub tier: Option<i32>,
+    // The rolled value of each magnitude
     pub values: Vec<f64>,
+    // What each magnitude rolled and the range it could have rolled in, when known
+    #[serde(default)]
+    pub rolls: Vec<ModifierRoll>,
     pub is_crafted: bool,
+    #[serde(default)]
+    pub kind: ModKind,
     pub stat_requirements: Option<ModifierStatRequirements>,
     pub attribute_scaling: Option<HashMap<CoreAttribute, f64>>,
+    // Trade API stat hashes behind the text, e.g. "explicit.stat_3299347043". Different
+    // stats can share a text, so these are what identify the modifier.
+    #[serde(default)]
+    pub stat_hashes: Vec<String>,
+}
+
+impl ItemModifier {
+    // The stat hashes sorted and joined with '|', empty when they aren't known
+    pub fn stat_key(&self) -> String {
+        let mut hashes: Vec<&str> = self.stat_hashes.iter().map(String::as_str).collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        hashes.join("|")
+    }
+
+    // The modifier's text with its rolled numbers blanked out, e.g. "+# to maximum Life",
+    // so the same modifier matches across items whatever it rolled
+    pub fn template(&self) -> String {
+        modifier_template(&self.name)
+    }
+
+    pub fn roll_quality(&self) -> Option<f64> {
+        average_roll_quality(&self.rolls)
+    }
+
+    // Whether `modifier` names this one, by its text, its template or a stat hash
+    pub fn matches(&self, modifier: &str) -> bool {
+        self.name == modifier
+            || self.template() == modifier
+            || self.stat_hashes.iter().any(|hash| hash == modifier)
+    }
+}
+
+// What one magnitude of a modifier rolled, within the range its tier allows
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModifierRoll {
+    pub current: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl ModifierRoll {
+    // How far up its range the roll landed, 0 for the worst roll and 100 for the best.
+    // Fixed values have no range to roll in.
+    pub fn quality(&self) -> Option<f64> {
+        let span = self.max - self.min;
+        if span.abs() < f64::EPSILON {
+            return None;
+        }
+        Some(((self.current - self.min) / span * 100.0).clamp(0.0, 100.0))
+    }
+
+    // The rolls behind a modifier's text. The text's numbers are the rolled values: taken
+    // in order when there is one per magnitude, otherwise the first unused number inside
+    // each range. Negative ranges match the unsigned numbers of texts like "10% reduced".
+    // Magnitudes whose bounds aren't numbers are skipped.
+    pub fn from_text(text: &str, magnitudes: &[Magnitude]) -> Vec<ModifierRoll> {
+        let numbers = modifier_numbers(text);
+        let mut used = vec![false; numbers.len()];
+        magnitudes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, magnitude)| {
+                let min: f64 = magnitude.min.parse().ok()?;
+                let max: f64 = magnitude.max.parse().unwrap_or(min);
+                let (low, high) = if min <= max { (min, max) } else { (max, min) };
+                let within = |value: f64| value >= low - f64::EPSILON && value <= high + f64::EPSILON;
+                let current = if numbers.len() == magnitudes.len() {
+                    let value = numbers[i];
+                    if !within(value) && within(-value) { -value } else { value }
+                } else {
+                    let found = numbers.iter().enumerate().find_map(|(j, &value)| {
+                        if used[j] {
+                            None
+                        } else if within(value) {
+                            Some((j, value))
+                        } else if within(-value) {
+                            Some((j, -value))
+                        } else {
+                            None
+                        }
+                    });
+                    match found {
+                        Some((j, value)) => {
+                            used[j] = true;
+                            value
+                        }
+                        None => min,
+                    }
+                };
+                Some(ModifierRoll { current, min, max })
+            })
+            .collect()
+    }
+}
+
+// Mean quality of the rolls that have a range to roll in
+pub fn average_roll_quality(rolls: &[ModifierRoll]) -> Option<f64> {
+    let qualities: Vec<f64> = rolls.iter().filter_map(ModifierRoll::quality).collect();
+    if qualities.is_empty() {
+        return None;
+    }
+    Some(qualities.iter().sum::<f64>() / qualities.len() as f64)
+}
+
+// Every number in a modifier line, e.g. "Adds 3 to 7.5 Fire Damage" -> [3, 7.5]
+pub(crate) fn modifier_numbers(text: &str) -> Vec<f64> {
+    let mut values = Vec::new();
+    let mut current = String::new();
+    let mut previous = ' ';
+    for c in text.chars().chain([' ']) {
+        if c.is_ascii_digit() || (c == '.' && !current.is_empty()) {
+            if current.is_empty() && previous == '-' {
+                current.push('-');
+            }
+            current.push(c);
+        } else if !current.is_empty() {
+            values.extend(current.trim_end_matches('.').parse::<f64>().ok());
+            current.clear();
+        }
+        previous = c;
+    }
+    values
+}
+
+// Each distinct hash once, in the order the magnitudes list them
+fn stat_hashes(magnitudes: &[Magnitude]) -> Vec<String> {
+    let mut hashes: Vec<String> = Vec::new();
+    for magnitude in magnitudes {
+        if !magnitude.hash.is_empty() && !hashes.contains(&magnitude.hash) {
+            hashes.push(magnitude.hash.clone());
+        }
+    }
+    hashes
+}
+
+pub fn modifier_template(text: &str) -> String {
+    let mut template = String::with_capacity(text.len());
+    let mut in_number = false;
+    for c in text.chars() {
+        let numeric = c.is_ascii_digit() || (in_number && c == '.');
+        if numeric && !in_number {
+            template.push('#');
+        } else if !numeric {
+            template.push(c);
+        }
+        in_number = numeric;
+    }
+    template
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemPrice {
     pub amount: f64,
-    pub currency: String,
+    pub currency: Currency,
+    // The price in the converter's base currency, when its rate is known
+    #[serde(default)]
+    pub normalized_amount: Option<f64>,
+}
+
+// Sockets of an item and the runes or soul cores in them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ItemSockets {
+    pub count: u32,
+    // Names of the socketed items, e.g. "Desert Rune"
+    #[serde(default)]
+    pub contents: Vec<String>,
+}
+
+impl ItemSockets {
+    // None for items without sockets
+    pub fn from_listing(item: &ItemData) -> Option<Self> {
+        if item.sockets.is_empty() {
+            return None;
+        }
+        Some(Self {
+            count: item.sockets.len() as u32,
+            contents: item.socketed_items.iter().map(|socketed| socketed.type_line.clone()).collect(),
+        })
+    }
+}
+
+// How to reach the seller of a listed item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListingContact {
+    pub whisper: Option<String>,
+    pub stash: Option<StashLocation>,
+    // When the trade site indexed the listing, as 'YYYY-MM-DD HH:MM:SS' like the other
+    // stored timestamps
+    pub indexed_at: Option<String>,
+}
+
+impl ListingContact {
+    // None when the listing carries none of it
+    pub fn from_listing(listing: &ListingData) -> Option<Self> {
+        let contact = Self {
+            whisper: listing.whisper.clone(),
+            stash: listing.stash.clone(),
+            indexed_at: listing.indexed.as_deref().and_then(|indexed| indexed.get(..19)).map(|indexed| indexed.replace('T', " ")),
+        };
+        (contact.whisper.is_some() || contact.stash.is_some() || contact.indexed_at.is_some()).then_some(contact)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,8 +239,25 @@ pub struct Item {
     pub price: Option<ItemPrice>,
     pub stats: HashMap<String, f64>,
     pub corrupted: bool,
+    #[serde(default)]
+    pub mirrored: bool,
+    #[serde(default)]
+    pub quality: Option<u32>,
+    // The trade league the listing was collected from
+    #[serde(default)]
+    pub league: Option<String>,
     pub stat_requirements: StatRequirements,
     pub attribute_values: HashMap<CoreAttribute, u32>,
+    // DPS and defences for weapons and armour; also flattened into `stats`
+    #[serde(default)]
+    pub derived_stats: Option<DerivedStats>,
+    // Level, quality and kind of gems; also flattened into `stats`
+    #[serde(default)]
+    pub gem: Option<GemProperties>,
+    #[serde(default)]
+    pub sockets: Option<ItemSockets>,
+    #[serde(default)]
+    pub contact: Option<ListingContact>,
 }
 
 impl Item {
@@ -49,8 +270,15 @@ impl Item {
             price: None,
             stats: HashMap::new(),
             corrupted: false,
+            mirrored: false,
+            quality: None,
+            league: None,
             stat_requirements: StatRequirements::new(),
             attribute_values: HashMap::new(),
+            derived_stats: None,
+            gem: None,
+            sockets: None,
+            contact: None,
         }
     }
 
@@ -63,8 +291,22 @@ impl Item {
         self.modifiers.push(modifier);
     }
 
-    pub fn set_price(&mut self, amount: f64, currency: String) {
-        self.price = Some(ItemPrice { amount, currency });
+    pub fn set_price(&mut self, amount: f64, currency: Currency) {
+        self.price = Some(ItemPrice { amount, currency, normalized_amount: None });
+    }
+
+    pub fn set_derived_stats(&mut self, derived: DerivedStats) {
+        self.stats.extend(derived.to_stats());
+        self.derived_stats = Some(derived);
+    }
+
+    pub fn set_gem(&mut self, gem: GemProperties) {
+        self.stats.extend(gem.to_stats());
+        self.gem = Some(gem);
+    }
+
+    pub fn is_gem(&self) -> bool {
+        self.item_type.category == ItemCategory::Gem
     }
 
     pub fn is_unique(&self) -> bool {
@@ -108,64 +350,88 @@ impl Item {
 impl TryFrom<ItemResponse> for Item {
     type Error = ScraperError;
 
+    // Total over what the trade site returns: mods without extended data keep the numbers
+    // of their text, unparseable magnitudes and requirement values are skipped. Only a
+    // listing naming no item at all fails.
     fn try_from(response: ItemResponse) -> Result<Self> {
-        let item_type = ItemType::new(
-            ItemCategory::Other,
-            response.item.base_type,
-            match response.item.rarity.as_str() {
-                "Unique" => ItemRarity::Unique,
-                "Rare" => ItemRarity::Rare,
-                "Magic" => ItemRarity::Magic,
-                _ => ItemRarity::Normal,
-            }
+        let base_type = if response.item.base_type.is_empty() {
+            response.item.type_line.clone()
+        } else {
+            response.item.base_type.clone()
+        };
+        if base_type.is_empty() {
+            return Err(ScraperError::ConversionError(
+                format!("Listing {} has no base type or type line", response.id)
+            ));
+        }
+
+        let gem = GemProperties::from_listing(&response.item);
+        let sockets = ItemSockets::from_listing(&response.item);
+        let quality = response.item.quality();
+        let contact = ListingContact::from_listing(&response.listing);
+        let mut item_type = ItemType::new(
+            if gem.is_some() { ItemCategory::Gem } else { ItemCategory::Other },
+            base_type,
+            response.item.item_rarity(),
         );
+        if gem.is_none() {
+            item_type.classify(None, &response.item.properties);
+        }
 
-        // Convert explicit mods with error handling
-        let modifiers = response.item.explicit_mods.iter()
-            .zip(response.item.extended.mods.explicit.iter())
-            .map(|(text, mod_info)| {
-                let values = mod_info.magnitudes.iter()
-                    .map(|m| m.min.parse::<f64>())
-                    .collect::<std::result::Result<Vec<_>, _>>()
-                    .map_err(|e| ScraperError::ConversionError(
-                        format!("Failed to parse modifier value: {}", e)
-                    ))?;
-
-                Ok(ItemModifier {
+        // Mods of every kind, by their text; the extended data at the same position, when
+        // there is any, adds the tier, roll ranges and stat hashes
+        let modifiers: Vec<ItemModifier> = ModKind::ALL.iter()
+            .flat_map(|&kind| {
+                let mods = response.item.extended.mods.of_kind(kind);
+                response.item.mod_texts(kind).iter()
+                    .enumerate()
+                    .map(move |(i, text)| (kind, text, mods.get(i)))
+            })
+            .map(|(kind, text, mod_info)| {
+                let rolls = mod_info
+                    .map(|mod_info| ModifierRoll::from_text(text, &mod_info.magnitudes))
+                    .unwrap_or_default();
+                let values = if rolls.is_empty() {
+                    modifier_numbers(text)
+                } else {
+                    rolls.iter().map(|roll| roll.current).collect()
+                };
+                ItemModifier {
                     name: text.clone(),
-                    tier: mod_info.tier.parse().ok(),
+                    tier: mod_info.and_then(|mod_info| mod_info.tier.trim_start_matches(['P', 'S']).parse().ok()),
                     values,
-                    is_crafted: false,
+                    rolls,
+                    is_crafted: kind == ModKind::Crafted,
+                    kind,
                     stat_requirements: None,
                     attribute_scaling: None,
-                })
+                    stat_hashes: mod_info.map(|mod_info| stat_hashes(&mod_info.magnitudes)).unwrap_or_default(),
+                }
             })
-            .collect::<Result<Vec<_>>>()?;
+            .collect();
 
-        // Process requirements with error handling
+        // Hybrid bases list more than one attribute; each becomes a requirement, in
+        // attribute order so the same base always gives the same requirements
         let mut attribute_values = HashMap::new();
-        let mut stat_requirements = StatRequirements::new();
-
         for req in &response.item.requirements {
-            if let Some(attr) = match req.name.as_str() {
-                "Str" | "Strength" => Some(CoreAttribute::Strength),
-                "Dex" | "Dexterity" => Some(CoreAttribute::Dexterity),
-                "Int" | "Intelligence" => Some(CoreAttribute::Intelligence),
-                _ => None
-            } {
-                if let Some((val_str, _)) = req.values.first() {
-                    let value = val_str.parse::<u32>()
-                        .map_err(|e| ScraperError::ConversionError(
-                            format!("Failed to parse attribute value: {}", e)
-                        ))?;
-                    
-                    attribute_values.insert(attr.clone(), value);
-                    stat_requirements.add_requirement(attr, value);
-                }
+            let value = req.values.first().and_then(|(value, _)| value.parse::<u32>().ok());
+            if let (Some(attribute), Some(value)) = (CoreAttribute::from_requirement_name(&req.name), value) {
+                attribute_values.insert(attribute, value);
+            }
+        }
+        let mut stat_requirements = StatRequirements::new();
+        for attribute in [CoreAttribute::Strength, CoreAttribute::Dexterity, CoreAttribute::Intelligence] {
+            if let Some(&value) = attribute_values.get(&attribute) {
+                stat_requirements.add_requirement(attribute, value);
             }
         }
 
-        Ok(Item {
+        let derived_stats = DerivedStats::from_properties(
+            &response.item.properties,
+            &response.item.explicit_mods,
+        );
+
+        let mut item = Item {
             id: response.id,
             item_type,
             name: Some(response.item.type_line),
@@ -173,12 +439,27 @@ impl TryFrom<ItemResponse> for Item {
             price: Some(ItemPrice {
                 amount: response.listing.price.amount,
                 currency: response.listing.price.currency,
+                normalized_amount: None,
             }),
             stats: HashMap::new(),
-            corrupted: false,
+            corrupted: response.item.corrupted,
+            mirrored: response.item.duplicated,
+            quality,
+            league: response.item.league,
             stat_requirements,
             attribute_values,
-        })
+            derived_stats: None,
+            gem: None,
+            sockets,
+            contact,
+        };
+        if let Some(derived) = derived_stats {
+            item.set_derived_stats(derived);
+        }
+        if let Some(gem) = gem {
+            item.set_gem(gem);
+        }
+        Ok(item)
     }
 }
 
@@ -201,7 +482,102 @@ mod tests {
         assert!(item.is_unique());
         assert_eq!(item.name, Some("Soul Taker".to_string()));
 
-        item.set_price(50.0, "chaos".to_string());
+        item.set_price(50.0, Currency::Chaos);
         assert!(item.price.is_some());
+
+        assert_eq!(modifier_template("+109 to maximum Life"), "+# to maximum Life");
+        assert_eq!(modifier_template("Adds 3 to 5.5 Fire Damage"), "Adds # to # Fire Damage");
+    }
+
+    #[test]
+    fn test_condition_read_from_listing() {
+        let response: ItemResponse = serde_json::from_value(serde_json::json!({
+            "id": "listing1",
+            "item": {
+                "base_type": "Expert Plate",
+                "explicitMods": [],
+                "extended": { "mods": { "explicit": [] }, "hashes": { "explicit": [] } },
+                "frameType": 2,
+                "requirements": [],
+                "properties": [{ "name": "[Quality]", "values": [["+20%", 1]], "display_mode": 0 }],
+                "rarity": "Rare",
+                "typeLine": "Expert Plate",
+                "ilvl": 80,
+                "corrupted": true,
+                "duplicated": true
+            },
+            "listing": {
+                "price": { "amount": 5.0, "currency": "exalted" },
+                "account": { "name": "seller", "realm": "poe2" }
+            }
+        }))
+        .unwrap();
+
+        let item = Item::try_from(response).unwrap();
+        assert!(item.corrupted);
+        assert!(item.mirrored);
+        assert_eq!(item.quality, Some(20));
+    }
+
+    #[test]
+    fn test_rolls_read_from_mod_text() {
+        let magnitude = |min: &str, max: &str| Magnitude { hash: String::new(), min: min.to_string(), max: max.to_string() };
+
+        let life = ModifierRoll::from_text("+45 to maximum Life", &[magnitude("40", "49")]);
+        assert_eq!(life, vec![ModifierRoll { current: 45.0, min: 40.0, max: 49.0 }]);
+        assert!((life[0].quality().unwrap() - 55.555).abs() < 0.01);
+
+        // Both bounds of a hybrid damage mod, and a reduction listed with negative ranges
+        let fire = ModifierRoll::from_text("Adds 4 to 9 Fire Damage", &[magnitude("3", "5"), magnitude("8", "10")]);
+        assert_eq!(fire.iter().map(|roll| roll.current).collect::<Vec<_>>(), vec![4.0, 9.0]);
+        assert_eq!(average_roll_quality(&fire), Some(50.0));
+        let reduced = ModifierRoll::from_text("15% reduced Attribute Requirements", &[magnitude("-20", "-15")]);
+        assert_eq!(reduced[0].current, -15.0);
+
+        // A fixed value has nothing to grade, and an unmatched text falls back to the minimum
+        assert_eq!(ModifierRoll::from_text("Has 1 Socket", &[magnitude("1", "1")])[0].quality(), None);
+        assert_eq!(ModifierRoll::from_text("Grants Level 5 Skill", &[magnitude("1", "2"), magnitude("x", "y")]).len(), 1);
+    }
+
+    #[test]
+    fn test_recorded_listings_convert_and_round_trip() {
+        let responses: Vec<ItemResponse> = serde_json::from_str(include_str!("fixtures/listings.json")).unwrap();
+
+        for response in &responses {
+            // Serializing keeps what was read, so stored raw listings convert the same again
+            let json = serde_json::to_value(response).unwrap();
+            let reread: ItemResponse = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&reread).unwrap(), json);
+
+            let item = Item::try_from(response.clone()).unwrap();
+            let stored = serde_json::to_value(&item).unwrap();
+            let restored: Item = serde_json::from_value(stored.clone()).unwrap();
+            assert_eq!(serde_json::to_value(&restored).unwrap(), stored);
+        }
+
+        // A hybrid base with numeric and null magnitudes
+        let armour = Item::try_from(responses[0].clone()).unwrap();
+        assert_eq!(armour.item_type.base_type, "Chain Mail");
+        assert_eq!(armour.stat_requirements.primary_attributes, vec![CoreAttribute::Strength, CoreAttribute::Intelligence]);
+        assert!(armour.stat_requirements.is_hybrid_requirement());
+        let values: Vec<Vec<f64>> = armour.modifiers.iter().map(|modifier| modifier.values.clone()).collect();
+        assert_eq!(values, vec![vec![86.0], vec![32.0], vec![12.0]]);
+        assert_eq!(armour.modifiers[0].tier, Some(3));
+        assert_eq!(armour.modifiers[0].rolls, vec![ModifierRoll { current: 86.0, min: 85.0, max: 99.0 }]);
+        assert!(armour.modifiers[2].rolls.is_empty());
+        assert_eq!(armour.modifiers[2].stat_hashes, vec!["explicit.stat_3321629045"]);
+
+        // No extended data and no requirements
+        let ring = Item::try_from(responses[1].clone()).unwrap();
+        let kinds: Vec<ModKind> = ring.modifiers.iter().map(|modifier| modifier.kind).collect();
+        assert_eq!(kinds, vec![ModKind::Explicit, ModKind::Implicit]);
+        assert_eq!(ring.modifiers[1].values, vec![25.0]);
+        assert!(ring.modifiers.iter().all(|modifier| modifier.tier.is_none() && modifier.stat_hashes.is_empty()));
+        assert!(ring.attribute_values.is_empty());
+
+        let mut unnamed = responses[1].clone();
+        unnamed.item.base_type.clear();
+        unnamed.item.type_line.clear();
+        assert!(matches!(Item::try_from(unnamed), Err(ScraperError::ConversionError(_))));
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+use super::cleaned_item::{CleanedItem, ExplicitMod, ItemProperty, ItemRequirement, ModInfo};
+use super::derived_stats::DerivedStats;
+use super::gem::{leading_number, GemKind, GemProperties};
+use super::jewel::{is_jewel_base, JewelProperties};
+use super::waystone::{is_waystone_base, WaystoneProperties};
+use super::item::{modifier_numbers, modifier_template, Item, ItemModifier, ItemSockets};
+use super::item_type::{ItemCategory, ItemClass, ItemRarity, ItemType};
+use super::poe_item::{Magnitude, ModBase, ModKind, Property};
+use super::stats_requirements::CoreAttribute;
+use crate::errors::{Result, ScraperError};
+
+// Line the game puts between the sections of a copied item
+const SECTION_SEPARATOR: &str = "--------";
+
+// One modifier line of a copied item
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextModifier {
+    pub kind: ModKind,
+    // The line without its kind tag or roll ranges, e.g. "+45 to maximum Life"
+    pub text: String,
+    pub values: Vec<f64>,
+    // Affix name and tier, e.g. "Hale" and "P8"; only the advanced copy (Ctrl+Alt+C)
+    // has them
+    pub affix: Option<String>,
+    pub tier: Option<String>,
+}
+
+// An item as the game client copies it with Ctrl+C, so drops that were never listed can
+// be priced like listings
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ItemText {
+    pub item_class: Option<String>,
+    pub rarity: ItemRarity,
+    // The unique's or rare's own name
+    pub name: Option<String>,
+    // Magic items keep their affixes here, e.g. "Sturdy Gold Ring of the Bear";
+    // BaseDataLoader::get_base finds the base in it
+    pub base_type: String,
+    pub item_level: u32,
+    pub quality: Option<u32>,
+    // Lines of the sections above the item level, e.g. ("Armour", "483")
+    pub properties: Vec<(String, String)>,
+    pub level_requirement: Option<u32>,
+    pub attribute_requirements: HashMap<CoreAttribute, u32>,
+    pub sockets: Option<u32>,
+    pub modifiers: Vec<TextModifier>,
+    pub gem: Option<GemProperties>,
+    pub corrupted: bool,
+    pub mirrored: bool,
+}
+
+impl ItemText {
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.replace('\r', "");
+        let sections: Vec<Vec<&str>> = text
+            .split(SECTION_SEPARATOR)
+            .map(|section| section.lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>())
+            .filter(|section| !section.is_empty())
+            .collect();
+        let Some((header, rest)) = sections.split_first() else {
+            return Err(ScraperError::ParseError("Item text is empty".to_string()));
+        };
+
+        let mut item = ItemText::default();
+        let mut rarity = None;
+        let mut names = Vec::new();
+        for line in header {
+            if let Some(class) = line.strip_prefix("Item Class:") {
+                item.item_class = Some(class.trim().to_string());
+            } else if let Some(value) = line.strip_prefix("Rarity:") {
+                rarity = Some(value.trim());
+            } else {
+                names.push(line.to_string());
+            }
+        }
+        let rarity = rarity.ok_or_else(|| {
+            ScraperError::ParseError("Item text has no Rarity line; copy the item in game with Ctrl+C".to_string())
+        })?;
+        item.rarity = ItemRarity::from_name(rarity).unwrap_or_default();
+        match <[String; 2]>::try_from(names) {
+            Ok([name, base_type]) => {
+                item.name = Some(name);
+                item.base_type = base_type;
+            }
+            Err(names) if names.len() == 1 => item.base_type = names[0].clone(),
+            Err(_) => return Err(ScraperError::ParseError("Item text has no name and base type lines".to_string())),
+        }
+
+        // Properties come before the item level, modifiers after it
+        let mut past_item_level = false;
+        for section in rest {
+            let first = section[0];
+            if first.starts_with("Requirements:") || first.starts_with("Requires") {
+                item.parse_requirements(section);
+            } else if let Some(level) = first.strip_prefix("Item Level:") {
+                item.item_level = leading_number(level).unwrap_or(0);
+                past_item_level = true;
+            } else if let Some(sockets) = first.strip_prefix("Sockets:") {
+                item.sockets = Some(sockets.split_whitespace().count() as u32);
+            } else if past_item_level {
+                item.parse_modifiers(section);
+            } else {
+                item.parse_properties(section);
+            }
+        }
+
+        if rarity == "Gem" {
+            let property = |name: &str| item.properties.iter().find(|(key, _)| key == name).and_then(|(_, value)| leading_number(value));
+            item.gem = Some(GemProperties {
+                kind: match &item.item_class {
+                    Some(class) if class.contains("Support") => GemKind::Support,
+                    _ => GemKind::Active,
+                },
+                level: property("Level").unwrap_or(1),
+                quality: item.quality.unwrap_or(0),
+            });
+        }
+        Ok(item)
+    }
+
+    fn parse_properties(&mut self, section: &[&str]) {
+        for line in section {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = strip_annotations(value);
+            if name == "Quality" {
+                self.quality = leading_number(&value);
+            }
+            self.properties.push((name.trim().to_string(), value));
+        }
+    }
+
+    // Either a "Requirements:" block of "Level: 65" lines or one "Requires: Level 65,
+    // 121 Str" line
+    fn parse_requirements(&mut self, section: &[&str]) {
+        let parts = section.iter().flat_map(|line| {
+            let line = line
+                .strip_prefix("Requirements:")
+                .or_else(|| line.strip_prefix("Requires:"))
+                .or_else(|| line.strip_prefix("Requires"))
+                .unwrap_or(line);
+            line.split(',')
+        });
+        for part in parts {
+            let part = strip_annotations(part);
+            let (name, value) = match part.split_once(':') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => match part.split_once(' ') {
+                    // "121 Str"
+                    Some((value, name)) if leading_number(value).is_some() => (name.trim(), value),
+                    // "Level 65"
+                    Some((name, value)) => (name.trim(), value.trim()),
+                    None => continue,
+                },
+            };
+            let Some(value) = leading_number(value) else {
+                continue;
+            };
+            if name == "Level" {
+                self.level_requirement = Some(value);
+            } else if let Some(attribute) = CoreAttribute::from_requirement_name(name) {
+                self.attribute_requirements.insert(attribute, value);
+            }
+        }
+    }
+
+    fn parse_modifiers(&mut self, section: &[&str]) {
+        let mut lines = Vec::new();
+        for &line in section {
+            match line {
+                "Corrupted" => self.corrupted = true,
+                "Mirrored" => self.mirrored = true,
+                "Unidentified" => {}
+                _ if line.starts_with("Note:") => {}
+                // Reminder text of the advanced copy
+                _ if line.starts_with('(') && line.ends_with(')') => {}
+                _ => lines.push(line),
+            }
+        }
+        // A unique's flavour text is the one section without numbers
+        let flavour = self.rarity == ItemRarity::Unique
+            && lines.iter().all(|line| !line.starts_with('{') && !line.chars().any(|c| c.is_ascii_digit()));
+        if flavour {
+            return;
+        }
+
+        // Header of the advanced copy, e.g. { Prefix Modifier "Hale" (Tier: 8) — Life }
+        let mut header: Option<(Option<ModKind>, Option<String>, Option<String>)> = None;
+        for line in lines {
+            if let Some(inner) = line.strip_prefix('{') {
+                header = Some(parse_header(inner.trim_end_matches('}')));
+                continue;
+            }
+
+            let (mut kind, text) = split_kind_tag(line);
+            let (header_kind, affix, tier) = header.clone().unwrap_or_default();
+            if kind == ModKind::Explicit {
+                kind = header_kind.unwrap_or_default();
+            }
+            let text = strip_roll_ranges(text);
+            self.modifiers.push(TextModifier {
+                kind,
+                values: modifier_numbers(&text),
+                text,
+                affix,
+                tier,
+            });
+        }
+    }
+
+    fn explicit_texts(&self) -> Vec<String> {
+        self.modifiers
+            .iter()
+            .filter(|modifier| modifier.kind == ModKind::Explicit)
+            .map(|modifier| modifier.text.clone())
+            .collect()
+    }
+
+    // Unlisted, so without an ID or price
+    pub fn to_item(&self) -> Item {
+        let category = if self.gem.is_some() { ItemCategory::Gem } else { ItemCategory::Other };
+        let mut item_type = ItemType::new(category, self.base_type.clone(), self.rarity);
+        if self.gem.is_none() {
+            item_type.class = self.item_class.as_deref().and_then(ItemClass::from_name);
+            item_type.classify(None, &[]);
+        }
+        let mut item = Item::new(String::new(), item_type)
+            .with_name(self.name.clone().unwrap_or_else(|| self.base_type.clone()));
+        for modifier in &self.modifiers {
+            item.add_modifier(ItemModifier {
+                name: modifier.text.clone(),
+                tier: modifier.tier.as_deref().and_then(|tier| tier.trim_start_matches(['P', 'S']).parse().ok()),
+                values: modifier.values.clone(),
+                rolls: Vec::new(),
+                is_crafted: modifier.kind == ModKind::Crafted,
+                kind: modifier.kind,
+                stat_requirements: None,
+                attribute_scaling: None,
+                stat_hashes: Vec::new(),
+            });
+        }
+        for (attribute, &value) in &self.attribute_requirements {
+            item.attribute_values.insert(attribute.clone(), value);
+            item.stat_requirements.add_requirement(attribute.clone(), value);
+        }
+        item.corrupted = self.corrupted;
+        item.mirrored = self.mirrored;
+        item.quality = self.quality;
+        item.sockets = self.sockets.map(|count| ItemSockets { count, contents: Vec::new() });
+
+        let properties: Vec<Property> = self.properties
+            .iter()
+            .map(|(name, value)| Property {
+                name: name.clone(),
+                values: vec![(value.clone(), 0)],
+                display_mode: 0,
+            })
+            .collect();
+        if let Some(derived) = DerivedStats::from_properties(&properties, &self.explicit_texts()) {
+            item.set_derived_stats(derived);
+        }
+        if let Some(gem) = self.gem {
+            item.set_gem(gem);
+        }
+        item
+    }
+
+    pub fn to_cleaned_item(&self) -> CleanedItem {
+        let mut mod_info = ModInfo::default();
+        for modifier in &self.modifiers {
+            let base = ModBase {
+                name: modifier.affix.clone().unwrap_or_else(|| modifier_template(&modifier.text)),
+                tier: modifier.tier.clone().unwrap_or_default(),
+                magnitudes: modifier.values
+                    .iter()
+                    .map(|value| Magnitude {
+                        hash: String::new(),
+                        min: value.to_string(),
+                        max: value.to_string(),
+                    })
+                    .collect(),
+                rolls: Vec::new(),
+            };
+            mod_info.of_kind_mut(modifier.kind).push(ExplicitMod::new(base, 0));
+        }
+
+        let mut requirements: Vec<ItemRequirement> = self.level_requirement
+            .map(|level| ItemRequirement {
+                name: "Level".to_string(),
+                values: vec![(level.to_string(), 0)],
+                display_mode: 0,
+            })
+            .into_iter()
+            .collect();
+        let mut attributes: Vec<(&CoreAttribute, &u32)> = self.attribute_requirements.iter().collect();
+        attributes.sort_by_key(|(attribute, _)| attribute.requirement_name());
+        requirements.extend(attributes.into_iter().map(|(attribute, value)| ItemRequirement {
+            name: attribute.requirement_name().to_string(),
+            values: vec![(value.to_string(), 0)],
+            display_mode: 0,
+        }));
+
+        CleanedItem {
+            id: String::new(),
+            price: None,
+            base_type: self.base_type.clone(),
+            name: self.name.clone().unwrap_or_else(|| self.base_type.clone()),
+            rarity: self.rarity,
+            explicit_mods: self.explicit_texts(),
+            item_level: self.item_level,
+            quality: self.quality,
+            corrupted: self.corrupted,
+            mirrored: self.mirrored,
+            properties: self.properties
+                .iter()
+                .map(|(name, value)| ItemProperty {
+                    name: name.clone(),
+                    values: vec![(value.clone(), 0)],
+                    display_mode: 0,
+                })
+                .collect(),
+            requirements,
+            mod_info,
+            mod_hashes: HashMap::new(),
+            gem: self.gem,
+            jewel: is_jewel_base(&self.base_type).then(|| {
+                JewelProperties::from_mods(self.rarity, self.modifiers.iter().map(|modifier| &modifier.text))
+                    .with_properties(self.properties.iter().map(|(name, value)| (name.as_str(), value.as_str())))
+            }),
+            waystone: is_waystone_base(&self.base_type).then(|| {
+                WaystoneProperties::from_base(&self.base_type)
+                    .with_properties(self.properties.iter().map(|(name, value)| (name.as_str(), value.as_str())))
+            }),
+            sockets: self.sockets.map(|count| ItemSockets { count, contents: Vec::new() }),
+        }
+    }
+}
+
+// "+20% (augmented)" -> "+20%"
+fn strip_annotations(value: &str) -> String {
+    value.replace("(augmented)", "").replace("(unmet)", "").trim().to_string()
+}
+
+// The kind tag the game appends to non-explicit modifiers, e.g. "(implicit)"
+fn split_kind_tag(line: &str) -> (ModKind, &str) {
+    let Some((text, tag)) = line.strip_suffix(')').and_then(|line| line.rsplit_once(" (")) else {
+        return (ModKind::Explicit, line);
+    };
+    let kind = match tag {
+        "implicit" => ModKind::Implicit,
+        "enchant" => ModKind::Enchant,
+        "rune" => ModKind::Rune,
+        "crafted" => ModKind::Crafted,
+        "fractured" | "desecrated" => ModKind::Explicit,
+        _ => return (ModKind::Explicit, line),
+    };
+    (kind, text)
+}
+
+// Kind, affix name and tier from the inside of an advanced copy header
+fn parse_header(header: &str) -> (Option<ModKind>, Option<String>, Option<String>) {
+    let kind = if header.contains("Implicit") {
+        Some(ModKind::Implicit)
+    } else if header.contains("Crafted") {
+        Some(ModKind::Crafted)
+    } else {
+        None
+    };
+    let affix = header.split('"').nth(1).map(str::to_string);
+    let slot = if header.contains("Prefix") {
+        "P"
+    } else if header.contains("Suffix") {
+        "S"
+    } else {
+        ""
+    };
+    let tier = header
+        .split_once("Tier:")
+        .and_then(|(_, rest)| leading_number(rest))
+        .map(|tier| format!("{}{}", slot, tier));
+    (kind, affix, tier)
+}
+
+// The advanced copy shows each roll's range after it, e.g. "+45(40-49) to maximum Life"
+fn strip_roll_ranges(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let after_number = text.chars().last().is_some_and(|last| last.is_ascii_digit());
+        if c == '(' && after_number {
+            let range: String = chars.clone().take_while(|&c| c != ')').collect();
+            if range.chars().all(|c| c.is_ascii_digit() || matches!(c, '.' | '-' | '+' | ' ')) {
+                for _ in 0..=range.chars().count() {
+                    chars.next();
+                }
+                continue;
+            }
+        }
+        text.push(c);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RARE_RING: &str = "Item Class: Rings
+Rarity: Rare
+Doom Loop
+Gold Ring
+--------
+Quality: +10% (augmented)
+--------
+Requires: Level 45, 52 (augmented) Str
+--------
+Item Level: 82
+--------
++12% to Cold Resistance (implicit)
+--------
+{ Prefix Modifier \"Hale\" (Tier: 8) — Life }
++45(40-49) to maximum Life
+Adds 3 to 7 Physical Damage to Attacks
+-10% to Chaos Resistance
+--------
+Corrupted
+--------
+Note: ~price 5 exalted
+";
+
+    #[test]
+    fn test_copied_item_parsed() {
+        let parsed = ItemText::parse(&RARE_RING.replace('\n', "\r\n")).unwrap();
+        assert_eq!(parsed.name.as_deref(), Some("Doom Loop"));
+        assert_eq!(parsed.base_type, "Gold Ring");
+        assert_eq!(parsed.rarity, ItemRarity::Rare);
+        assert_eq!((parsed.item_level, parsed.quality, parsed.level_requirement), (82, Some(10), Some(45)));
+        assert_eq!(parsed.attribute_requirements[&CoreAttribute::Strength], 52);
+        assert!(parsed.corrupted && !parsed.mirrored);
+
+        let mods: Vec<(ModKind, &str, Vec<f64>)> = parsed.modifiers
+            .iter()
+            .map(|m| (m.kind, m.text.as_str(), m.values.clone()))
+            .collect();
+        assert_eq!(mods, vec![
+            (ModKind::Implicit, "+12% to Cold Resistance", vec![12.0]),
+            (ModKind::Explicit, "+45 to maximum Life", vec![45.0]),
+            (ModKind::Explicit, "Adds 3 to 7 Physical Damage to Attacks", vec![3.0, 7.0]),
+            (ModKind::Explicit, "-10% to Chaos Resistance", vec![-10.0]),
+        ]);
+        assert_eq!(parsed.modifiers[1].affix.as_deref(), Some("Hale"));
+        assert_eq!(parsed.modifiers[1].tier.as_deref(), Some("P8"));
+
+        let cleaned = parsed.to_cleaned_item();
+        assert_eq!(cleaned.mod_info.implicit.len(), 1);
+        assert_eq!(cleaned.mod_info.explicit[0].name, "Hale");
+        assert_eq!(cleaned.explicit_mods.len(), 3);
+        let item = parsed.to_item();
+        assert_eq!(item.modifiers.len(), 4);
+        assert_eq!((item.item_type.category, item.item_type.class), (ItemCategory::Accessory, Some(ItemClass::Ring)));
+        assert!(item.corrupted);
+
+        assert!(ItemText::parse("Gold Ring").is_err());
+    }
+}
@@ -1,32 +1,286 @@
 use serde::{Deserialize, Serialize};
+use super::derived_stats::display_name;
+use super::jewel::is_jewel_base;
+use super::waystone::is_waystone_base;
+use super::poe_item::Property;
+use super::stats_requirements::ItemBaseType;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ItemCategory {
     Weapon,
     Armour,
     Accessory,
     Flask,
     Gem,
+    Jewel,
     Currency,
     DivinationCard,
     Map,
     Other,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+// The item class within a category, e.g. a Helmet within Armour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ItemClass {
+    Claw,
+    Dagger,
+    OneHandSword,
+    OneHandAxe,
+    OneHandMace,
+    Sceptre,
+    Spear,
+    Flail,
+    TwoHandSword,
+    TwoHandAxe,
+    TwoHandMace,
+    Quarterstaff,
+    Bow,
+    Crossbow,
+    Wand,
+    Staff,
+    Talisman,
+    Helmet,
+    BodyArmour,
+    Gloves,
+    Boots,
+    Shield,
+    Buckler,
+    Focus,
+    Quiver,
+    Ring,
+    Amulet,
+    Belt,
+    Flask,
+    Charm,
+}
+
+impl ItemClass {
+    pub const ALL: [ItemClass; 30] = [
+        ItemClass::Claw,
+        ItemClass::Dagger,
+        ItemClass::OneHandSword,
+        ItemClass::OneHandAxe,
+        ItemClass::OneHandMace,
+        ItemClass::Sceptre,
+        ItemClass::Spear,
+        ItemClass::Flail,
+        ItemClass::TwoHandSword,
+        ItemClass::TwoHandAxe,
+        ItemClass::TwoHandMace,
+        ItemClass::Quarterstaff,
+        ItemClass::Bow,
+        ItemClass::Crossbow,
+        ItemClass::Wand,
+        ItemClass::Staff,
+        ItemClass::Talisman,
+        ItemClass::Helmet,
+        ItemClass::BodyArmour,
+        ItemClass::Gloves,
+        ItemClass::Boots,
+        ItemClass::Shield,
+        ItemClass::Buckler,
+        ItemClass::Focus,
+        ItemClass::Quiver,
+        ItemClass::Ring,
+        ItemClass::Amulet,
+        ItemClass::Belt,
+        ItemClass::Flask,
+        ItemClass::Charm,
+    ];
+
+    // The class as the game names it, e.g. on a weapon's first property or in the
+    // "Item Class:" line of a copied item
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemClass::Claw => "Claw",
+            ItemClass::Dagger => "Dagger",
+            ItemClass::OneHandSword => "One Hand Sword",
+            ItemClass::OneHandAxe => "One Hand Axe",
+            ItemClass::OneHandMace => "One Hand Mace",
+            ItemClass::Sceptre => "Sceptre",
+            ItemClass::Spear => "Spear",
+            ItemClass::Flail => "Flail",
+            ItemClass::TwoHandSword => "Two Hand Sword",
+            ItemClass::TwoHandAxe => "Two Hand Axe",
+            ItemClass::TwoHandMace => "Two Hand Mace",
+            ItemClass::Quarterstaff => "Quarterstaff",
+            ItemClass::Bow => "Bow",
+            ItemClass::Crossbow => "Crossbow",
+            ItemClass::Wand => "Wand",
+            ItemClass::Staff => "Staff",
+            ItemClass::Talisman => "Talisman",
+            ItemClass::Helmet => "Helmet",
+            ItemClass::BodyArmour => "Body Armour",
+            ItemClass::Gloves => "Gloves",
+            ItemClass::Boots => "Boots",
+            ItemClass::Shield => "Shield",
+            ItemClass::Buckler => "Buckler",
+            ItemClass::Focus => "Focus",
+            ItemClass::Quiver => "Quiver",
+            ItemClass::Ring => "Ring",
+            ItemClass::Amulet => "Amulet",
+            ItemClass::Belt => "Belt",
+            ItemClass::Flask => "Flask",
+            ItemClass::Charm => "Charm",
+        }
+    }
+
+    pub fn category(&self) -> ItemCategory {
+        match self {
+            ItemClass::Helmet
+            | ItemClass::BodyArmour
+            | ItemClass::Gloves
+            | ItemClass::Boots
+            | ItemClass::Shield
+            | ItemClass::Buckler
+            | ItemClass::Focus
+            | ItemClass::Quiver => ItemCategory::Armour,
+            ItemClass::Ring | ItemClass::Amulet | ItemClass::Belt => ItemCategory::Accessory,
+            ItemClass::Flask | ItemClass::Charm => ItemCategory::Flask,
+            _ => ItemCategory::Weapon,
+        }
+    }
+
+    // Parse a class name, singular or plural, e.g. "Quarterstaff", "[Body Armour]",
+    // "Rings" or "Life Flasks"
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = display_name(name).trim().to_lowercase();
+        let singular = match name.as_str() {
+            "foci" => "focus",
+            "staves" => "staff",
+            "quarterstaves" => "quarterstaff",
+            "gloves" | "boots" => name.as_str(),
+            _ => name.strip_suffix('s').unwrap_or(&name),
+        };
+        let singular = match singular {
+            "life flask" | "mana flask" => "flask",
+            "warstaff" => "quarterstaff",
+            other => other,
+        };
+        ItemClass::ALL.into_iter().find(|class| class.as_str().to_lowercase() == singular)
+    }
+
+    // Guess the class from a base type's name, e.g. "Rusted Greathelm" -> Helmet. Weapon
+    // bases don't name their hands, so only classes a name can't mix up are guessed.
+    pub fn from_base_name(base_type: &str) -> Option<Self> {
+        const KEYWORDS: &[(&str, ItemClass)] = &[
+            ("helm", ItemClass::Helmet),
+            ("hood", ItemClass::Helmet),
+            ("cap", ItemClass::Helmet),
+            ("crown", ItemClass::Helmet),
+            ("circlet", ItemClass::Helmet),
+            ("mask", ItemClass::Helmet),
+            ("tiara", ItemClass::Helmet),
+            ("cowl", ItemClass::Helmet),
+            ("gloves", ItemClass::Gloves),
+            ("gauntlets", ItemClass::Gloves),
+            ("mitts", ItemClass::Gloves),
+            ("bracers", ItemClass::Gloves),
+            ("wraps", ItemClass::Gloves),
+            ("boots", ItemClass::Boots),
+            ("greaves", ItemClass::Boots),
+            ("shoes", ItemClass::Boots),
+            ("sandals", ItemClass::Boots),
+            ("slippers", ItemClass::Boots),
+            ("sabatons", ItemClass::Boots),
+            ("shield", ItemClass::Shield),
+            ("buckler", ItemClass::Buckler),
+            ("focus", ItemClass::Focus),
+            ("quiver", ItemClass::Quiver),
+            ("ring", ItemClass::Ring),
+            ("amulet", ItemClass::Amulet),
+            ("belt", ItemClass::Belt),
+            ("sash", ItemClass::Belt),
+            ("flask", ItemClass::Flask),
+            ("charm", ItemClass::Charm),
+            ("crossbow", ItemClass::Crossbow),
+            ("bow", ItemClass::Bow),
+            ("quarterstaff", ItemClass::Quarterstaff),
+            ("warstaff", ItemClass::Quarterstaff),
+            ("staff", ItemClass::Staff),
+            ("wand", ItemClass::Wand),
+            ("sceptre", ItemClass::Sceptre),
+            ("spear", ItemClass::Spear),
+            ("flail", ItemClass::Flail),
+            ("claw", ItemClass::Claw),
+            ("dagger", ItemClass::Dagger),
+            ("talisman", ItemClass::Talisman),
+        ];
+        // Magic items carry their suffix after the base, e.g. "Leather Boots of the Bear"
+        base_type.split_whitespace().rev().find_map(|word| {
+            let word = word.to_lowercase();
+            KEYWORDS.iter().find(|(keyword, _)| word.ends_with(keyword)).map(|&(_, class)| class)
+        })
+    }
+}
+
+impl std::fmt::Display for ItemClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ItemRarity {
+    #[default]
     Normal,
     Magic,
     Rare,
     Unique,
 }
 
+impl ItemRarity {
+    // The rarity as the trade API or copied item text names it, in any case. None for
+    // names that aren't a rarity, such as "Currency", and for an empty name.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "normal" => Some(ItemRarity::Normal),
+            "magic" => Some(ItemRarity::Magic),
+            "rare" => Some(ItemRarity::Rare),
+            "unique" => Some(ItemRarity::Unique),
+            _ => None,
+        }
+    }
+
+    // The rarity a listing's frame is drawn for: 0 to 3 are normal to unique, and 9 and 10
+    // are the foil frames of uniques. Gems, currency and the other frames carry no rarity.
+    pub fn from_frame_type(frame_type: i32) -> Option<Self> {
+        match frame_type {
+            0 => Some(ItemRarity::Normal),
+            1 => Some(ItemRarity::Magic),
+            2 => Some(ItemRarity::Rare),
+            3 | 9 | 10 => Some(ItemRarity::Unique),
+            _ => None,
+        }
+    }
+
+    // A listing's rarity from its rarity name and frame type, which are sometimes missing
+    // or disagree. A recognized name wins, since the frame is only how the item is drawn;
+    // without one the frame decides, and items with neither, like currency, are Normal.
+    pub fn resolve(rarity: &str, frame_type: i32) -> Self {
+        Self::from_name(rarity)
+            .or_else(|| Self::from_frame_type(frame_type))
+            .unwrap_or_default()
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ItemRarity::Normal => "Normal",
+            ItemRarity::Magic => "Magic",
+            ItemRarity::Rare => "Rare",
+            ItemRarity::Unique => "Unique",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ItemType {
     pub category: ItemCategory,
     pub base_type: String,
     pub rarity: ItemRarity,
     pub required_level: Option<u32>,
+    #[serde(default)]
+    pub class: Option<ItemClass>,
 }
 
 impl ItemType {
@@ -36,6 +290,7 @@ impl ItemType {
             base_type,
             rarity,
             required_level: None,
+            class: None,
         }
     }
 
@@ -44,6 +299,27 @@ impl ItemType {
         self
     }
 
+    // Fill in the class and category the type doesn't know yet. The listing's first
+    // property names the class of weapons and some armour; otherwise the catalogue base
+    // decides, and failing that the base type's name.
+    pub fn classify(&mut self, base: Option<&ItemBaseType>, properties: &[Property]) {
+        if self.class.is_none() {
+            self.class = properties.first()
+                .and_then(|property| ItemClass::from_name(&property.name))
+                .or_else(|| base.and_then(|base| base.class))
+                .or_else(|| ItemClass::from_base_name(&self.base_type));
+        }
+        if self.category == ItemCategory::Other {
+            self.category = match (self.class, base) {
+                (Some(class), _) => class.category(),
+                (None, Some(base)) => base.category,
+                (None, None) if is_jewel_base(&self.base_type) => ItemCategory::Jewel,
+                (None, None) if is_waystone_base(&self.base_type) => ItemCategory::Map,
+                (None, None) => ItemCategory::Other,
+            };
+        }
+    }
+
     pub fn is_equipment(&self) -> bool {
         matches!(self.category, 
             ItemCategory::Weapon | 
@@ -53,6 +329,24 @@ impl ItemType {
     }
 }
 
+impl ItemCategory {
+    // Parse a category as its Display impl writes it; anything unknown is Other
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "Weapon" => ItemCategory::Weapon,
+            "Armour" => ItemCategory::Armour,
+            "Accessory" => ItemCategory::Accessory,
+            "Flask" => ItemCategory::Flask,
+            "Gem" => ItemCategory::Gem,
+            "Jewel" => ItemCategory::Jewel,
+            "Currency" => ItemCategory::Currency,
+            "DivinationCard" => ItemCategory::DivinationCard,
+            "Map" => ItemCategory::Map,
+            _ => ItemCategory::Other,
+        }
+    }
+}
+
 impl std::fmt::Display for ItemCategory {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -61,6 +355,7 @@ impl std::fmt::Display for ItemCategory {
             ItemCategory::Accessory => write!(f, "Accessory"),
             ItemCategory::Flask => write!(f, "Flask"),
             ItemCategory::Gem => write!(f, "Gem"),
+            ItemCategory::Jewel => write!(f, "Jewel"),
             ItemCategory::Currency => write!(f, "Currency"),
             ItemCategory::DivinationCard => write!(f, "DivinationCard"),
             ItemCategory::Map => write!(f, "Map"),
@@ -87,4 +382,44 @@ mod tests {
         assert_eq!(item.required_level, Some(68));
         assert!(item.is_equipment());
     }
+
+    #[test]
+    fn test_rarity_resolved_from_name_and_frame_type() {
+        assert_eq!(ItemRarity::resolve("Rare", 2), ItemRarity::Rare);
+        // The name wins when the two disagree
+        assert_eq!(ItemRarity::resolve("Magic", 2), ItemRarity::Magic);
+        assert_eq!(ItemRarity::resolve(" unique ", 0), ItemRarity::Unique);
+        // Without a recognized name the frame decides
+        assert_eq!(ItemRarity::resolve("", 3), ItemRarity::Unique);
+        assert_eq!(ItemRarity::resolve("Currency", 9), ItemRarity::Unique);
+        assert_eq!(ItemRarity::resolve("", 1), ItemRarity::Magic);
+        // Neither, as on gems and currency
+        assert_eq!(ItemRarity::resolve("", 5), ItemRarity::Normal);
+        assert_eq!(ItemRarity::resolve("Gem", 4), ItemRarity::Normal);
+    }
+
+    #[test]
+    fn test_classify_from_property_catalogue_and_name() {
+        let property = |name: &str| Property { name: name.to_string(), values: Vec::new(), display_mode: 0 };
+        let classify = |base_type: &str, base: Option<&ItemBaseType>, properties: &[Property]| {
+            let mut item_type = ItemType::new(ItemCategory::Other, base_type.to_string(), ItemRarity::Rare);
+            item_type.classify(base, properties);
+            (item_type.category, item_type.class)
+        };
+
+        assert_eq!(classify("Ashen Staff", None, &[property("[Quarterstaff]")]), (ItemCategory::Weapon, Some(ItemClass::Quarterstaff)));
+        assert_eq!(classify("Rusted Greathelm", None, &[property("Armour")]), (ItemCategory::Armour, Some(ItemClass::Helmet)));
+        assert_eq!(classify("Gold Ring", None, &[]), (ItemCategory::Accessory, Some(ItemClass::Ring)));
+        assert_eq!(classify("Sturdy Leather Boots of the Bear", None, &[]).1, Some(ItemClass::Boots));
+
+        let mut vest = ItemBaseType::new("Garment".to_string(), ItemCategory::Armour);
+        assert_eq!(classify("Garment", Some(&vest), &[]), (ItemCategory::Armour, None));
+        vest.class = Some(ItemClass::BodyArmour);
+        assert_eq!(classify("Garment", Some(&vest), &[]).1, Some(ItemClass::BodyArmour));
+        assert_eq!(classify("Sapphire", None, &[]), (ItemCategory::Jewel, None));
+
+        assert_eq!(ItemClass::from_name("Body Armours"), Some(ItemClass::BodyArmour));
+        assert_eq!(ItemClass::from_name("Life Flasks"), Some(ItemClass::Flask));
+        assert_eq!(ItemClass::from_name("Foci"), Some(ItemClass::Focus));
+    }
 }
\ No newline at end of file
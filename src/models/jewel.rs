@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use super::derived_stats::display_name;
+use super::gem::leading_number;
+use super::item::Item;
+use super::item_type::ItemRarity;
+use super::poe_item::{ItemData, ModKind};
+
+// Jewel base types. Time-Lost and Timeless jewels affect the passives within a radius of
+// their socket; the others only add their own mods.
+pub const JEWEL_BASES: &[&str] = &[
+    "Ruby",
+    "Emerald",
+    "Sapphire",
+    "Diamond",
+    "Time-Lost Ruby",
+    "Time-Lost Emerald",
+    "Time-Lost Sapphire",
+    "Time-Lost Diamond",
+    "Timeless Jewel",
+];
+
+pub fn is_jewel_base(base_type: &str) -> bool {
+    JEWEL_BASES.contains(&base_type)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JewelRadius {
+    Small,
+    Medium,
+    Large,
+    VeryLarge,
+}
+
+impl JewelRadius {
+    // As the Radius property shows it, e.g. "Very Large"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim() {
+            "Small" => Some(JewelRadius::Small),
+            "Medium" => Some(JewelRadius::Medium),
+            "Large" => Some(JewelRadius::Large),
+            "Very Large" => Some(JewelRadius::VeryLarge),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JewelRadius::Small => "Small",
+            JewelRadius::Medium => "Medium",
+            JewelRadius::Large => "Large",
+            JewelRadius::VeryLarge => "Very Large",
+        }
+    }
+}
+
+impl std::fmt::Display for JewelRadius {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JewelProperties {
+    // For jewels that affect the passives around their socket
+    pub radius: Option<JewelRadius>,
+    // How many of the jewel can be socketed at once, from "Limited to"
+    pub limit: Option<u32>,
+    // Passives the jewel allocates, from its "Allocates ..." mods, e.g. on Megalomaniac
+    pub allocated_passives: Vec<String>,
+    pub unique: bool,
+}
+
+impl JewelProperties {
+    // None for listings that aren't jewels
+    pub fn from_listing(item: &ItemData) -> Option<Self> {
+        if !is_jewel_base(&item.base_type) {
+            return None;
+        }
+
+        let texts = [ModKind::Explicit, ModKind::Implicit, ModKind::Enchant]
+            .into_iter()
+            .flat_map(|kind| item.mod_texts(kind));
+        let properties = item.properties
+            .iter()
+            .filter_map(|property| property.values.first().map(|(value, _)| (property.name.as_str(), value.as_str())));
+        Some(Self::from_mods(item.item_rarity(), texts).with_properties(properties))
+    }
+
+    // From what storage keeps of a jewel. Stored items have no properties, so the radius
+    // and limit are unknown.
+    pub fn from_item(item: &Item) -> Option<Self> {
+        if !is_jewel_base(&item.item_type.base_type) {
+            return None;
+        }
+        Some(Self::from_mods(item.item_type.rarity, item.modifiers.iter().map(|modifier| &modifier.name)))
+    }
+
+    pub(crate) fn from_mods<'a>(rarity: ItemRarity, texts: impl Iterator<Item = &'a String>) -> Self {
+        Self {
+            radius: None,
+            limit: None,
+            allocated_passives: texts
+                .filter_map(|text| display_name(text).strip_prefix("Allocates ").map(|passive| passive.trim().to_string()))
+                .collect(),
+            unique: rarity == ItemRarity::Unique,
+        }
+    }
+
+    // Read the radius and limit from property names and values, e.g. ("Radius", "Large")
+    pub(crate) fn with_properties<'a>(mut self, properties: impl Iterator<Item = (&'a str, &'a str)>) -> Self {
+        for (name, value) in properties {
+            match display_name(name).as_str() {
+                "Radius" => self.radius = JewelRadius::from_name(value),
+                "Limited to" => self.limit = leading_number(value),
+                _ => {}
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jewel_properties_from_listing() {
+        let item: ItemData = serde_json::from_value(serde_json::json!({
+            "base_type": "Time-Lost Diamond",
+            "typeLine": "Time-Lost Diamond",
+            "name": "Megalomaniac",
+            "explicitMods": [
+                "Allocates [Notable|Heavy Buffer]",
+                "Allocates Unwavering Stance",
+                "10% increased Damage"
+            ],
+            "extended": { "mods": { "explicit": [] }, "hashes": { "explicit": [] } },
+            "frameType": 3,
+            "requirements": [],
+            "properties": [
+                { "name": "Radius", "values": [["Large", 0]], "display_mode": 0 },
+                { "name": "Limited to", "values": [["1", 0]], "display_mode": 0 }
+            ],
+            "rarity": "Unique",
+            "ilvl": 84
+        }))
+        .unwrap();
+
+        let jewel = JewelProperties::from_listing(&item).unwrap();
+        assert_eq!(jewel, JewelProperties {
+            radius: Some(JewelRadius::Large),
+            limit: Some(1),
+            allocated_passives: vec!["Heavy Buffer".to_string(), "Unwavering Stance".to_string()],
+            unique: true,
+        });
+        assert!(!is_jewel_base("Sapphire Ring"));
+    }
+}
@@ -4,13 +4,17 @@ pub mod stats;
 pub mod stats_requirements;
 pub mod poe_item;
 pub mod cleaned_item;
+pub mod derived_stats;
+pub mod gem;
+pub mod jewel;
+pub mod waystone;
+pub mod currency;
+pub mod item_text;
+#[cfg(test)]
+pub(crate) mod fixtures;
 pub use cleaned_item::*;
 
 // Re-export the modules to make them accessible
-pub use item_type::*;
-pub use item::*;
-pub use stats::*;
-pub use stats_requirements::*;
 pub use poe_item::{
     ItemResponse,
     ItemData,
@@ -22,19 +26,42 @@ pub use poe_item::{
     HashData,
     Requirement,
     Property,
+    Socket,
+    SocketedItem,
+    StashLocation,
     Price,
+    parse_price_amount,
     Account,
+    ModKind,
 };
 
+pub use currency::Currency;
+
+pub use derived_stats::DerivedStats;
+
+pub use gem::{GemKind, GemProperties, GEM_FRAME_TYPE};
+
+pub use jewel::{is_jewel_base, JewelProperties, JewelRadius, JEWEL_BASES};
+
+pub use waystone::{is_waystone_base, WaystoneProperties};
+
+pub use item_text::{ItemText, TextModifier};
+
 pub use item::{
     Item,
     ItemModifier,
     ItemPrice,
+    ItemSockets,
+    ListingContact,
+    ModifierRoll,
+    average_roll_quality,
+    modifier_template,
 };
 
 pub use item_type::{
     ItemType,
     ItemCategory,
+    ItemClass,
     ItemRarity,
 };
 
@@ -42,6 +69,12 @@ pub use stats::{
     ModifierStats,
     StatisticalMeasures,
     ValueRange,
+    PricePercentiles,
+    CorruptionSplit,
+    RollQualityBucket,
+    ROLL_QUALITY_BREAKS,
+    LinearFit,
+    percentile,
 };
 
 pub use stats_requirements::{
@@ -50,4 +83,5 @@ pub use stats_requirements::{
     ModifierStatRequirements,
     ItemBaseType,
     ItemBaseDatabase,
+    attribute_requirements,
 };
@@ -1,12 +1,91 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::Deref;
+use super::currency::Currency;
+use super::derived_stats::display_name;
+use super::gem::leading_number;
+use super::item::{average_roll_quality, ModifierRoll};
+use super::item_type::ItemRarity;
+use super::stats_requirements::attribute_requirements;
+
+// Where a modifier on an item comes from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModKind {
+    #[default]
+    Explicit,
+    Implicit,
+    Enchant,
+    Rune,
+    Crafted,
+}
+
+impl ModKind {
+    pub const ALL: [ModKind; 5] = [
+        ModKind::Explicit,
+        ModKind::Implicit,
+        ModKind::Enchant,
+        ModKind::Rune,
+        ModKind::Crafted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModKind::Explicit => "explicit",
+            ModKind::Implicit => "implicit",
+            ModKind::Enchant => "enchant",
+            ModKind::Rune => "rune",
+            ModKind::Crafted => "crafted",
+        }
+    }
+
+    // Name a modifier is tracked under in the analyzers. Explicit mods keep their bare
+    // name; the others are prefixed so e.g. an implicit and an explicit of the same name
+    // stay apart.
+    pub fn stat_key(&self, name: &str) -> String {
+        match self {
+            ModKind::Explicit => name.to_string(),
+            kind => format!("{}: {}", kind, name),
+        }
+    }
+
+    // Trade stat hashes carry the kind of mod they belong to, e.g. "rune.stat_1234"
+    pub fn from_stat_hash(hash: &str) -> Option<Self> {
+        let (prefix, _) = hash.split_once('.')?;
+        ModKind::ALL.into_iter().find(|kind| kind.as_str() == prefix)
+    }
+}
+
+impl fmt::Display for ModKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ModBase {
     pub name: String,
     pub tier: String,
     pub magnitudes: Vec<Magnitude>,
+    // What the magnitudes rolled. The trade API only lists their ranges, so this is
+    // filled in from the mod's text where the text is at hand.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rolls: Vec<ModifierRoll>,
+}
+
+impl ModBase {
+    // The first magnitude's rolled value, or its minimum when the roll isn't known
+    pub fn rolled_value(&self) -> Option<f64> {
+        match self.rolls.first() {
+            Some(roll) => Some(roll.current),
+            None => self.magnitudes.first().and_then(|magnitude| magnitude.min.parse().ok()),
+        }
+    }
+
+    pub fn roll_quality(&self) -> Option<f64> {
+        average_roll_quality(&self.rolls)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -27,60 +106,197 @@ pub struct ItemResponse {
     pub id: String,
     pub item: ItemData,
     pub listing: ListingData,
+    // Listings on the market this one stands for: the listings its collection query matched
+    // over those fetched. Set by StatCollector; analyzers weight estimates by it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampling_weight: Option<f64>,
+    // Fields the models don't map, kept so the listing serializes back to what was fetched
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ItemData {
+    // The unique's or rare's own name; empty for normal and magic items
+    #[serde(default)]
+    pub name: String,
+    #[serde(alias = "baseType")]
     pub base_type: String,
-    #[serde(rename = "explicitMods")]
+    // Currency, gems and other items without mods leave out the mod lists, requirements
+    // and properties they don't have
+    #[serde(rename = "explicitMods", default)]
     pub explicit_mods: Vec<String>,
+    #[serde(rename = "implicitMods", default)]
+    pub implicit_mods: Vec<String>,
+    #[serde(rename = "enchantMods", default)]
+    pub enchant_mods: Vec<String>,
+    #[serde(rename = "runeMods", default)]
+    pub rune_mods: Vec<String>,
+    #[serde(rename = "craftedMods", default)]
+    pub crafted_mods: Vec<String>,
+    // Only listings the trade site was asked to extend carry it
+    #[serde(default)]
     pub extended: ExtendedData,
     #[serde(rename = "frameType")]
     pub frame_type: i32,
+    #[serde(default)]
     pub requirements: Vec<Requirement>,
+    #[serde(default)]
     pub properties: Vec<Property>,
+    // Absent on currency, which reads as Normal
+    #[serde(default)]
     pub rarity: String,
     #[serde(rename = "typeLine")]
     pub type_line: String,
+    #[serde(default)]
     pub ilvl: u32,
+    // The league the item is listed in, e.g. "Standard"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub league: Option<String>,
+    // Set on gems: whether it's a support gem rather than a skill
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub support: Option<bool>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub corrupted: bool,
+    // Mirrored items, which can't be modified further
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub duplicated: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sockets: Vec<Socket>,
+    // Runes and soul cores in the sockets; their effects are listed under runeMods
+    #[serde(rename = "socketedItems", default, skip_serializing_if = "Vec::is_empty")]
+    pub socketed_items: Vec<SocketedItem>,
+    // Unmapped fields such as influences and the seller's note
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct ExtendedData {
+    #[serde(default)]
     pub mods: ModData,
+    #[serde(default)]
     pub hashes: HashData,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct ModData {
+    #[serde(default)]
     pub explicit: Vec<ModInfo>,
+    #[serde(default)]
+    pub implicit: Vec<ModInfo>,
+    #[serde(default)]
+    pub enchant: Vec<ModInfo>,
+    #[serde(default)]
+    pub rune: Vec<ModInfo>,
+    #[serde(default)]
+    pub crafted: Vec<ModInfo>,
+}
+
+impl ModData {
+    pub fn of_kind(&self, kind: ModKind) -> &[ModInfo] {
+        match kind {
+            ModKind::Explicit => &self.explicit,
+            ModKind::Implicit => &self.implicit,
+            ModKind::Enchant => &self.enchant,
+            ModKind::Rune => &self.rune,
+            ModKind::Crafted => &self.crafted,
+        }
+    }
+
+    // Every mod of the given kinds, tagged with its kind
+    pub fn iter_kinds<'a>(&'a self, kinds: &'a [ModKind]) -> impl Iterator<Item = (ModKind, &'a ModInfo)> + 'a {
+        kinds.iter().flat_map(move |&kind| self.of_kind(kind).iter().map(move |m| (kind, m)))
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Magnitude {
     pub hash: String,
+    #[serde(default, deserialize_with = "magnitude_bound")]
     pub min: String,
+    #[serde(default, deserialize_with = "magnitude_bound")]
     pub max: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+// Magnitude bounds come as strings or as bare numbers depending on the payload, and are
+// null for mods without a range; they're kept as text either way
+fn magnitude_bound<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Bound {
+        Number(serde_json::Number),
+        Text(String),
+    }
+    Ok(match Option::<Bound>::deserialize(deserializer)? {
+        Some(Bound::Number(number)) => number.to_string(),
+        Some(Bound::Text(text)) => text,
+        None => String::new(),
+    })
+}
+
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct HashData {
+    #[serde(default, deserialize_with = "hash_entries")]
     pub explicit: Vec<(String, Vec<i32>)>,
+    #[serde(default, deserialize_with = "hash_entries")]
+    pub implicit: Vec<(String, Vec<i32>)>,
+    #[serde(default, deserialize_with = "hash_entries")]
+    pub enchant: Vec<(String, Vec<i32>)>,
+    #[serde(default, deserialize_with = "hash_entries")]
+    pub rune: Vec<(String, Vec<i32>)>,
+    #[serde(default, deserialize_with = "hash_entries")]
+    pub crafted: Vec<(String, Vec<i32>)>,
+}
+
+// Stats the trade site couldn't tie to a mod come with null instead of mod indices
+fn hash_entries<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(String, Vec<i32>)>, D::Error> {
+    let entries = Vec::<(String, Option<Vec<i32>>)>::deserialize(deserializer)?;
+    Ok(entries.into_iter().map(|(hash, indices)| (hash, indices.unwrap_or_default())).collect())
+}
+
+impl HashData {
+    pub fn all(&self) -> impl Iterator<Item = &(String, Vec<i32>)> {
+        self.explicit.iter()
+            .chain(&self.implicit)
+            .chain(&self.enchant)
+            .chain(&self.rune)
+            .chain(&self.crafted)
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Requirement {
     pub name: String,
     pub values: Vec<(String, i32)>,
+    #[serde(default, alias = "displayMode")]
     pub display_mode: i32,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Socket {
+    #[serde(default)]
+    pub group: u32,
+    // e.g. "rune"
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+}
+
+// Kept whole so the listing serializes back as fetched; only the name is read
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SocketedItem {
+    #[serde(rename = "typeLine")]
+    pub type_line: String,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Property {
     pub name: String,
     #[serde(default)]
     pub values: Vec<(String, i32)>,
-    #[serde(default)]
+    #[serde(default, alias = "displayMode")]
     pub display_mode: i32,
 }
 
@@ -88,12 +304,65 @@ pub struct Property {
 pub struct ListingData {
     pub price: Price,
     pub account: Account,
+    // Message to paste in game to ask the seller for the item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub whisper: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stash: Option<StashLocation>,
+    // When the trade site indexed the listing, e.g. "2025-01-01T12:00:00Z"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexed: Option<String>,
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
+}
+
+// The stash tab a listed item is in and its cell there
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StashLocation {
+    #[serde(default)]
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Price {
+    #[serde(deserialize_with = "price_amount")]
     pub amount: f64,
-    pub currency: String,
+    // Barter items and currencies added after this was written are kept under their listed
+    // name as Currency::Unknown, to be left out of converted prices rather than the listing
+    pub currency: Currency,
+}
+
+// Parse a listed amount the way sellers write them: "3", "0.5", "1,5" or a fraction such as
+// "1/2", which the trade site uses for bulk ratios
+pub fn parse_price_amount(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let amount = match text.split_once('/') {
+        Some((numerator, denominator)) => {
+            let denominator: f64 = denominator.trim().parse().ok()?;
+            (denominator != 0.0).then_some(())?;
+            numerator.trim().parse::<f64>().ok()? / denominator
+        }
+        None => text.replace(',', ".").parse().ok()?,
+    };
+    (amount.is_finite() && amount >= 0.0).then_some(amount)
+}
+
+// Amounts are usually bare numbers, but listings relayed from notes or other tools give
+// them as text, fractions included
+fn price_amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Number(f64),
+        Text(String),
+    }
+    match Amount::deserialize(deserializer)? {
+        Amount::Number(amount) => Ok(amount),
+        Amount::Text(text) => parse_price_amount(&text)
+            .ok_or_else(|| serde::de::Error::custom(format!("unreadable price amount '{}'", text))),
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -118,16 +387,72 @@ impl Deref for ExplicitMod {
     }
 }
 
+impl ItemData {
+    // Rarity from the rarity name and frame type; see ItemRarity::resolve
+    pub fn item_rarity(&self) -> ItemRarity {
+        let rarity = ItemRarity::resolve(&self.rarity, self.frame_type);
+        if let (Some(named), Some(framed)) = (ItemRarity::from_name(&self.rarity), ItemRarity::from_frame_type(self.frame_type)) {
+            if named != framed {
+                tracing::debug!(
+                    base_type = %self.base_type,
+                    rarity = %self.rarity,
+                    frame_type = self.frame_type,
+                    "listing rarity disagrees with its frame type, using the rarity"
+                );
+            }
+        }
+        rarity
+    }
+
+    // Display text of the item's mods of one kind, in the same order as extended.mods
+    pub fn mod_texts(&self, kind: ModKind) -> &[String] {
+        match kind {
+            ModKind::Explicit => &self.explicit_mods,
+            ModKind::Implicit => &self.implicit_mods,
+            ModKind::Enchant => &self.enchant_mods,
+            ModKind::Rune => &self.rune_mods,
+            ModKind::Crafted => &self.crafted_mods,
+        }
+    }
+
+    // The item's mods of one kind with what each rolled, read from the mod's text
+    pub fn rolled_mods(&self, kind: ModKind) -> Vec<ModBase> {
+        let texts = self.mod_texts(kind);
+        self.extended.mods.of_kind(kind)
+            .iter()
+            .enumerate()
+            .map(|(i, mod_info)| {
+                let mut base = mod_info.base.clone();
+                if let Some(text) = texts.get(i) {
+                    base.rolls = ModifierRoll::from_text(text, &base.magnitudes);
+                }
+                base
+            })
+            .collect()
+    }
+
+    // The Quality property, e.g. "+20%" -> 20; None for items without one
+    pub fn quality(&self) -> Option<u32> {
+        self.properties
+            .iter()
+            .find(|property| display_name(&property.name) == "Quality")
+            .and_then(|property| property.values.first())
+            .and_then(|(value, _)| leading_number(value))
+    }
+}
+
 impl ItemResponse {
 
     pub fn debug_print(&self) {
-        println!("Processing ItemResponse:");
-        println!("  ID: {}", self.id);
-        println!("  Base Type: {}", self.item.base_type);
-        println!("  Type Line: {}", self.item.type_line);
-        println!("  Properties count: {}", self.item.properties.len());
-        println!("  Requirements count: {}", self.item.requirements.len());
-        println!("  Explicit mods count: {}", self.item.explicit_mods.len());
+        tracing::debug!(
+            id = %self.id,
+            base_type = %self.item.base_type,
+            type_line = %self.item.type_line,
+            properties = self.item.properties.len(),
+            requirements = self.item.requirements.len(),
+            explicit_mods = self.item.explicit_mods.len(),
+            "processing item response"
+        );
     }
 
     pub fn get_stat_values(&self) -> HashMap<String, i32> {
@@ -142,15 +467,9 @@ impl ItemResponse {
     }
 
     pub fn get_stat_requirements(&self) -> HashMap<String, u32> {
-        self.item.requirements
-            .iter()
-            .filter(|req| req.name == "Strength" || req.name == "Dexterity" || req.name == "Intelligence")
-            .filter_map(|req| {
-                req.values.first().map(|(value, _)| {
-                    (req.name.clone(), value.parse::<u32>().unwrap_or(0))
-                })
-            })
-            .collect()
+        attribute_requirements(self.item.requirements.iter().filter_map(|req| {
+            req.values.first().map(|(value, _)| (req.name.as_str(), value.as_str()))
+        }))
     }
 
     pub fn get_explicit_mod_values(&self) -> Vec<(String, f64)> {
@@ -163,4 +482,39 @@ impl ItemResponse {
             })
             .collect()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{GemKind, GemProperties, ItemRarity};
+
+    #[test]
+    fn test_items_without_mods_or_requirements_parse() {
+        let responses: Vec<ItemResponse> = serde_json::from_str(include_str!("fixtures/listings.json")).unwrap();
+        let (currency, flask, gem) = (&responses[2].item, &responses[3].item, &responses[4].item);
+
+        // Currency has no rarity, item level or requirements
+        assert_eq!(currency.base_type, "Divine Orb");
+        assert_eq!(currency.item_rarity(), ItemRarity::Normal);
+        assert!(currency.requirements.is_empty());
+        assert_eq!(currency.extra["stackSize"], 3);
+
+        assert_eq!(flask.requirements.len(), 1);
+        assert_eq!(flask.extended.mods.explicit[0].magnitudes[0].max, "0.2");
+
+        // Gems have no mod lists at all
+        assert!(gem.explicit_mods.is_empty() && gem.extended.mods.explicit.is_empty());
+        assert_eq!(
+            GemProperties::from_listing(gem),
+            Some(GemProperties { kind: GemKind::Active, level: 17, quality: 20 })
+        );
+        assert_eq!(gem.extra["icon"], "https://web.poecdn.com/gen/image/Fireball.png");
+
+        // Amounts given as text, bulk fractions and barter items still make a price
+        let price: Price = serde_json::from_str(r#"{ "amount": "1/2", "currency": "Divine Orbs" }"#).unwrap();
+        assert_eq!((price.amount, price.currency), (0.5, Currency::Divine));
+        let barter: Price = serde_json::from_str(r#"{ "amount": "1,5", "currency": "Ancient Rib" }"#).unwrap();
+        assert_eq!((barter.amount, barter.currency), (1.5, Currency::Unknown("Ancient Rib".to_string())));
+        assert!(serde_json::from_str::<Price>(r#"{ "amount": "2/0", "currency": "exalted" }"#).is_err());
+    }
+}
@@ -1,11 +1,160 @@
-use std::collections::HashMap;
 use serde::{Serialize, Deserialize};
+use super::currency::Currency;
+
+// Buckets a modifier's values fall into when no breakpoints are given
+pub const DEFAULT_VALUE_BUCKETS: usize = 5;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValueRange {
     pub min: f64,
     pub max: f64,
     pub count: u32,
+    // Price distribution of the listings whose value falls in this range
+    #[serde(default)]
+    pub prices: Option<PricePercentiles>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PricePercentiles {
+    pub p10: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p90: f64,
+}
+
+impl PricePercentiles {
+    // None for an empty slice; the input doesn't need to be sorted
+    pub fn from_prices(prices: &[f64]) -> Option<Self> {
+        if prices.is_empty() {
+            return None;
+        }
+
+        let mut sorted = prices.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        Some(Self {
+            p10: percentile(&sorted, 0.10),
+            p25: percentile(&sorted, 0.25),
+            p50: percentile(&sorted, 0.50),
+            p75: percentile(&sorted, 0.75),
+            p90: percentile(&sorted, 0.90),
+        })
+    }
+}
+
+// Prices of clean and corrupted listings kept apart, as a corruption can make an item worth
+// far more or far less than a clean one
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CorruptionSplit {
+    pub clean_listings: usize,
+    pub corrupted_listings: usize,
+    pub clean: Option<PricePercentiles>,
+    pub corrupted: Option<PricePercentiles>,
+}
+
+impl CorruptionSplit {
+    // From (corrupted, price) pairs
+    pub fn from_prices(prices: impl IntoIterator<Item = (bool, f64)>) -> Self {
+        let mut clean = Vec::new();
+        let mut corrupted = Vec::new();
+        for (is_corrupted, price) in prices {
+            if is_corrupted {
+                corrupted.push(price);
+            } else {
+                clean.push(price);
+            }
+        }
+        Self {
+            clean_listings: clean.len(),
+            corrupted_listings: corrupted.len(),
+            clean: PricePercentiles::from_prices(&clean),
+            corrupted: PricePercentiles::from_prices(&corrupted),
+        }
+    }
+}
+
+// Roll quality percentages that split listings for prices_by_roll_quality; near-perfect
+// rolls get a bucket of their own as they're the ones that sell at a premium
+pub const ROLL_QUALITY_BREAKS: [f64; 3] = [50.0, 80.0, 95.0];
+
+// Prices of the listings whose modifiers rolled, on average, within a quality range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RollQualityBucket {
+    pub min_quality: f64,
+    pub max_quality: f64,
+    pub listings: usize,
+    pub prices: Option<PricePercentiles>,
+}
+
+impl RollQualityBucket {
+    // From (roll quality, price) pairs, one bucket per range between ROLL_QUALITY_BREAKS
+    // and 0..=100. The last bucket includes 100.
+    pub fn from_prices(prices: impl IntoIterator<Item = (f64, f64)>) -> Vec<Self> {
+        let bounds: Vec<f64> = std::iter::once(0.0)
+            .chain(ROLL_QUALITY_BREAKS)
+            .chain(std::iter::once(100.0))
+            .collect();
+        let mut buckets: Vec<Vec<f64>> = vec![Vec::new(); bounds.len() - 1];
+        for (quality, price) in prices {
+            let index = bounds[1..bounds.len() - 1].iter().filter(|&&bound| quality >= bound).count();
+            buckets[index].push(price);
+        }
+        bounds
+            .windows(2)
+            .zip(buckets)
+            .map(|(pair, prices)| Self {
+                min_quality: pair[0],
+                max_quality: pair[1],
+                listings: prices.len(),
+                prices: PricePercentiles::from_prices(&prices),
+            })
+            .collect()
+    }
+}
+
+// Linearly interpolated percentile of sorted, non-empty data; `p` is in 0..=1
+pub fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+// Least-squares line through (x, y) points
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinearFit {
+    pub slope: f64,
+    pub intercept: f64,
+    // Share of the variance in y the line explains, from 0 to 1
+    pub r_squared: f64,
+    pub observations: usize,
+}
+
+impl LinearFit {
+    // None for fewer than two points or when every x is the same
+    pub fn fit(points: &[(f64, f64)]) -> Option<Self> {
+        if points.len() < 2 {
+            return None;
+        }
+        let n = points.len() as f64;
+        let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+        let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+        let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+        let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+        let syy: f64 = points.iter().map(|p| (p.1 - mean_y).powi(2)).sum();
+        if sxx == 0.0 {
+            return None;
+        }
+
+        let slope = sxy / sxx;
+        Some(Self {
+            slope,
+            intercept: mean_y - slope * mean_x,
+            // A flat y is fit perfectly by a flat line
+            r_squared: if syy == 0.0 { 1.0 } else { sxy * sxy / (sxx * syy) },
+            observations: points.len(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,7 +172,15 @@ pub struct ModifierStats {
     pub total_occurrences: u32,
     pub value_ranges: Vec<ValueRange>,
     pub price_points: Vec<(f64, f64)>, // (value, price) pairs
+    // (amount, currency) of each price point as listed, before normalization
+    #[serde(default)]
+    pub raw_prices: Vec<(f64, Currency)>,
     pub measures: StatisticalMeasures,
+    // Mean roll quality of the occurrences whose roll range is known, in percent
+    #[serde(default)]
+    pub average_roll_quality: Option<f64>,
+    #[serde(default)]
+    pub rolls_measured: u32,
 }
 
 impl ModifierStats {
@@ -33,6 +190,7 @@ impl ModifierStats {
             total_occurrences: 0,
             value_ranges: Vec::new(),
             price_points: Vec::new(),
+            raw_prices: Vec::new(),
             measures: StatisticalMeasures {
                 mean: 0.0,
                 median: 0.0,
@@ -40,6 +198,8 @@ impl ModifierStats {
                 min: 0.0,
                 max: 0.0,
             },
+            average_roll_quality: None,
+            rolls_measured: 0,
         }
     }
 
@@ -49,6 +209,12 @@ impl ModifierStats {
         self.update_measures();
     }
 
+    pub fn add_roll_quality(&mut self, quality: f64) {
+        let total = self.average_roll_quality.unwrap_or(0.0) * self.rolls_measured as f64 + quality;
+        self.rolls_measured += 1;
+        self.average_roll_quality = Some(total / self.rolls_measured as f64);
+    }
+
     fn update_measures(&mut self) {
         if self.price_points.is_empty() {
             return;
@@ -63,7 +229,7 @@ impl ModifierStats {
         let mut sorted = values.clone();
         sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
         let mid = sorted.len() / 2;
-        self.measures.median = if sorted.len() % 2 == 0 {
+        self.measures.median = if sorted.len().is_multiple_of(2) {
             (sorted[mid - 1] + sorted[mid]) / 2.0
         } else {
             sorted[mid]
@@ -78,12 +244,61 @@ impl ModifierStats {
             .sum::<f64>() / values.len() as f64;
         self.measures.std_dev = variance.sqrt();
     }
+
+    // Rebuild value_ranges from the price points. Consecutive breakpoints bound each range;
+    // without breakpoints the observed values are split into equal-width ranges. The last
+    // range includes its upper bound.
+    pub fn update_value_ranges(&mut self, breakpoints: &[f64]) {
+        let bounds: Vec<f64> = if breakpoints.len() >= 2 {
+            breakpoints.to_vec()
+        } else if self.measures.max > self.measures.min {
+            let width = (self.measures.max - self.measures.min) / DEFAULT_VALUE_BUCKETS as f64;
+            (0..=DEFAULT_VALUE_BUCKETS)
+                .map(|i| self.measures.min + width * i as f64)
+                .collect()
+        } else {
+            vec![self.measures.min, self.measures.max]
+        };
+
+        let last = bounds.len() - 2;
+        self.value_ranges = bounds
+            .windows(2)
+            .enumerate()
+            .map(|(i, pair)| {
+                let (min, max) = (pair[0], pair[1]);
+                let prices: Vec<f64> = self.price_points
+                    .iter()
+                    .filter(|(value, _)| *value >= min && (*value < max || (i == last && *value <= max)))
+                    .map(|(_, price)| *price)
+                    .collect();
+                ValueRange {
+                    min,
+                    max,
+                    count: prices.len() as u32,
+                    prices: PricePercentiles::from_prices(&prices),
+                }
+            })
+            .collect();
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_linear_fit() {
+        let fit = LinearFit::fit(&[(100.0, 10.0), (150.0, 15.0), (200.0, 20.0)]).unwrap();
+        assert!((fit.slope - 0.1).abs() < 1e-9);
+        assert!(fit.intercept.abs() < 1e-9);
+        assert!((fit.r_squared - 1.0).abs() < 1e-9);
+
+        let noisy = LinearFit::fit(&[(0.0, 1.0), (1.0, 3.0), (2.0, 2.0), (3.0, 4.0)]).unwrap();
+        assert!((noisy.slope - 0.8).abs() < 1e-9);
+        assert!((noisy.r_squared - 0.64).abs() < 1e-9);
+        assert!(LinearFit::fit(&[(5.0, 1.0), (5.0, 2.0)]).is_none());
+    }
+
     #[test]
     fn test_modifier_stats_calculations() {
         let mut stats = ModifierStats::new("test_mod".to_string());
@@ -97,4 +312,25 @@ mod tests {
         assert_eq!(stats.measures.min, 10.0);
         assert_eq!(stats.measures.max, 30.0);
     }
+
+    #[test]
+    fn test_value_range_percentiles() {
+        let mut stats = ModifierStats::new("test_mod".to_string());
+        for (value, price) in [(5.0, 1.0), (8.0, 3.0), (12.0, 10.0), (20.0, 30.0)] {
+            stats.add_data_point(value, price);
+        }
+
+        stats.update_value_ranges(&[0.0, 10.0, 20.0]);
+        assert_eq!(stats.value_ranges[0].count, 2);
+        assert_eq!(stats.value_ranges[1].count, 2);
+        let prices = stats.value_ranges[0].prices.as_ref().unwrap();
+        assert_eq!(prices.p50, 2.0);
+        assert!((prices.p10 - 1.2).abs() < 1e-9);
+
+        // Without breakpoints the observed 5..20 span is split evenly
+        stats.update_value_ranges(&[]);
+        assert_eq!(stats.value_ranges.len(), DEFAULT_VALUE_BUCKETS);
+        assert_eq!(stats.value_ranges[0].min, 5.0);
+        assert_eq!(stats.value_ranges.iter().map(|r| r.count).sum::<u32>(), 4);
+    }
 }
\ No newline at end of file
@@ -9,12 +9,54 @@ pub enum CoreAttribute {
     Intelligence,
 }
 
+impl CoreAttribute {
+    // Requirement names come as "Strength", "Str" or, from the trade API, "[Strength|Str]"
+    pub fn from_requirement_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+        let name = name.strip_prefix('[').and_then(|name| name.strip_suffix(']')).unwrap_or(name);
+        name.split('|').find_map(|part| match part.trim() {
+            "Strength" | "Str" => Some(CoreAttribute::Strength),
+            "Dexterity" | "Dex" => Some(CoreAttribute::Dexterity),
+            "Intelligence" | "Int" => Some(CoreAttribute::Intelligence),
+            _ => None,
+        })
+    }
+
+    // The requirement name as the trade API lists it
+    pub fn requirement_name(&self) -> &'static str {
+        match self {
+            CoreAttribute::Strength => "[Strength|Str]",
+            CoreAttribute::Dexterity => "[Dexterity|Dex]",
+            CoreAttribute::Intelligence => "[Intelligence|Int]",
+        }
+    }
+}
+
+// Attribute requirements among (name, value) pairs however their names are spelled, keyed
+// by the trade API's name so every spelling lands under one key. Level and other
+// requirements, and values that aren't numbers, are skipped.
+pub fn attribute_requirements<'a>(requirements: impl IntoIterator<Item = (&'a str, &'a str)>) -> HashMap<String, u32> {
+    requirements
+        .into_iter()
+        .filter_map(|(name, value)| {
+            let attribute = CoreAttribute::from_requirement_name(name)?;
+            Some((attribute.requirement_name().to_string(), value.parse().ok()?))
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatRequirements {
     pub primary_attributes: Vec<CoreAttribute>,
     pub attribute_thresholds: HashMap<CoreAttribute, u32>,
 }
 
+impl Default for StatRequirements {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StatRequirements {
     pub fn new() -> Self {
         Self {
@@ -53,6 +95,9 @@ impl StatRequirements {
 pub struct ItemBaseType {
     pub name: String,
     pub category: super::item_type::ItemCategory,
+    // The class within the category, when it's known
+    #[serde(default)]
+    pub class: Option<super::item_type::ItemClass>,
     pub stat_requirements: StatRequirements,
     pub implicit_modifiers: Vec<String>,
     pub base_level: u32,
@@ -65,6 +110,7 @@ impl ItemBaseType {
         Self {
             name,
             category,
+            class: None,
             stat_requirements: StatRequirements::new(),
             implicit_modifiers: Vec::new(),
             base_level: 1,
@@ -92,6 +138,12 @@ pub struct ItemBaseDatabase {
     bases: HashMap<String, ItemBaseType>,
 }
 
+impl Default for ItemBaseDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ItemBaseDatabase {
     pub fn new() -> Self {
         Self {
@@ -168,4 +220,19 @@ mod tests {
         assert!(reqs.is_hybrid_requirement());
         assert!(!reqs.is_pure_requirement());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_requirement_spellings_resolve_alike() {
+        for name in ["Strength", "Str", "[Strength|Str]", " [Strength|Str] "] {
+            assert_eq!(CoreAttribute::from_requirement_name(name), Some(CoreAttribute::Strength));
+        }
+        assert_eq!(CoreAttribute::from_requirement_name("[Intelligence|Int]"), Some(CoreAttribute::Intelligence));
+        assert_eq!(CoreAttribute::from_requirement_name("Level"), None);
+
+        let requirements = attribute_requirements([("Level", "65"), ("Dex", "40"), ("[Strength|Str]", "121"), ("Int", "n/a")]);
+        assert_eq!(requirements, HashMap::from([
+            ("[Strength|Str]".to_string(), 121),
+            ("[Dexterity|Dex]".to_string(), 40),
+        ]));
+    }
+}
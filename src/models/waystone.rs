@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+use super::derived_stats::display_name;
+use super::gem::leading_number;
+use super::item::Item;
+use super::poe_item::ItemData;
+
+// Waystone bases are named after their tier, e.g. "Waystone (Tier 15)"
+pub fn is_waystone_base(base_type: &str) -> bool {
+    base_type.starts_with("Waystone")
+}
+
+// "Waystone (Tier 15)" -> 15
+pub fn tier_from_base(base_type: &str) -> Option<u32> {
+    let (_, rest) = base_type.split_once("(Tier ")?;
+    leading_number(rest)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WaystoneProperties {
+    pub tier: Option<u32>,
+    // Percentage bonuses the waystone's mods add to the map, which is what players juice for
+    pub item_rarity: Option<u32>,
+    pub pack_size: Option<u32>,
+    pub drop_chance: Option<u32>,
+}
+
+impl WaystoneProperties {
+    // None for listings that aren't waystones
+    pub fn from_listing(item: &ItemData) -> Option<Self> {
+        if !is_waystone_base(&item.base_type) {
+            return None;
+        }
+
+        let properties = item.properties
+            .iter()
+            .filter_map(|property| property.values.first().map(|(value, _)| (property.name.as_str(), value.as_str())));
+        Some(Self::from_base(&item.base_type).with_properties(properties))
+    }
+
+    // From what storage keeps of a waystone. Stored items have no properties, so only the
+    // tier in the base name is known.
+    pub fn from_item(item: &Item) -> Option<Self> {
+        is_waystone_base(&item.item_type.base_type).then(|| Self::from_base(&item.item_type.base_type))
+    }
+
+    pub(crate) fn from_base(base_type: &str) -> Self {
+        Self {
+            tier: tier_from_base(base_type),
+            ..Self::default()
+        }
+    }
+
+    // Read the tier and bonuses from property names and values, e.g. ("Waystone Tier", "15")
+    pub(crate) fn with_properties<'a>(mut self, properties: impl Iterator<Item = (&'a str, &'a str)>) -> Self {
+        for (name, value) in properties {
+            match display_name(name).as_str() {
+                "Waystone Tier" => self.tier = leading_number(value).or(self.tier),
+                "Item Rarity" => self.item_rarity = leading_number(value),
+                "Monster Pack Size" | "Pack Size" => self.pack_size = leading_number(value),
+                "Waystone Drop Chance" => self.drop_chance = leading_number(value),
+                _ => {}
+            }
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waystone_properties_from_listing() {
+        let item: ItemData = serde_json::from_value(serde_json::json!({
+            "base_type": "Waystone (Tier 15)",
+            "typeLine": "Waystone (Tier 15)",
+            "explicitMods": ["Monsters deal 25% of Damage as Extra Fire"],
+            "extended": { "mods": { "explicit": [] }, "hashes": { "explicit": [] } },
+            "frameType": 2,
+            "requirements": [],
+            "properties": [
+                { "name": "Waystone Tier", "values": [["15", 0]], "display_mode": 0 },
+                { "name": "Item Rarity", "values": [["+42%", 1]], "display_mode": 0 },
+                { "name": "Monster Pack Size", "values": [["+12%", 1]], "display_mode": 0 },
+                { "name": "Waystone Drop Chance", "values": [["+160%", 1]], "display_mode": 0 }
+            ],
+            "rarity": "Rare",
+            "ilvl": 79
+        }))
+        .unwrap();
+
+        assert_eq!(WaystoneProperties::from_listing(&item).unwrap(), WaystoneProperties {
+            tier: Some(15),
+            item_rarity: Some(42),
+            pack_size: Some(12),
+            drop_chance: Some(160),
+        });
+        assert_eq!(tier_from_base("Waystone (Tier 3)"), Some(3));
+        assert!(!is_waystone_base("Sapphire"));
+    }
+}
@@ -0,0 +1,289 @@
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+use crate::errors::{Result, ScraperError};
+use crate::fetcher::{HttpTransport, ReqwestTransport};
+use crate::models::Item;
+
+// Discord allows a webhook about 30 messages a minute
+pub const DEFAULT_MIN_INTERVAL: Duration = Duration::from_secs(2);
+
+// Trade IDs remembered for deduplication; the oldest are forgotten past this
+const SEEN_CAPACITY: usize = 10_000;
+
+// Discord cuts messages off at 2000 characters
+const DISCORD_MESSAGE_LIMIT: usize = 2000;
+
+// How the webhook expects its payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WebhookFormat {
+    // {"content": "<message>"} for a Discord channel webhook
+    #[default]
+    Discord,
+    // The listing as a JSON object, for anything else
+    Generic,
+}
+
+// A modifier a listing must have, by text, template (e.g. "+# to maximum Life") or stat
+// hash, optionally rolled at least `min_value`. Written "MOD" or "MOD>=VALUE".
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModCriterion {
+    pub modifier: String,
+    pub min_value: Option<f64>,
+}
+
+impl FromStr for ModCriterion {
+    type Err = ScraperError;
+
+    fn from_str(text: &str) -> Result<Self> {
+        let (modifier, min_value) = match text.rsplit_once(">=") {
+            Some((modifier, value)) => {
+                let value = value.trim().parse().map_err(|_| {
+                    ScraperError::ValidationError(format!("'{}' is not a number in modifier criterion '{}'", value.trim(), text))
+                })?;
+                (modifier.trim(), Some(value))
+            }
+            None => (text.trim(), None),
+        };
+        if modifier.is_empty() {
+            return Err(ScraperError::ValidationError(format!("modifier criterion '{}' names no modifier", text)));
+        }
+        Ok(Self { modifier: modifier.to_string(), min_value })
+    }
+}
+
+// What a listing must have to be notified about: every one of the modifiers, and a price
+// of at most `max_price` in the converter's base currency (exalted) when one is set
+#[derive(Debug, Clone, Default)]
+pub struct NotifyCriteria {
+    pub modifiers: Vec<ModCriterion>,
+    pub max_price: Option<f64>,
+}
+
+impl NotifyCriteria {
+    // Listings with a price limit need a normalized price to be compared against it
+    pub fn matches(&self, item: &Item) -> bool {
+        let price_ok = self.max_price.is_none_or(|max| {
+            item.price.as_ref().and_then(|price| price.normalized_amount).is_some_and(|price| price <= max)
+        });
+        price_ok
+            && self.modifiers.iter().all(|criterion| {
+                item.modifiers.iter().any(|m| {
+                    m.matches(&criterion.modifier)
+                        && criterion.min_value.is_none_or(|min| m.values.first().is_some_and(|&value| value >= min))
+                })
+            })
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GenericPayload<'a> {
+    id: &'a str,
+    name: Option<&'a str>,
+    base_type: &'a str,
+    price: Option<f64>,
+    currency: Option<&'a str>,
+    normalized_price: Option<f64>,
+    modifiers: Vec<&'a str>,
+    whisper: Option<&'a str>,
+}
+
+// Posts listings matching the criteria to a webhook, each trade ID at most once and no
+// more often than `min_interval`
+#[derive(Debug)]
+pub struct Notifier {
+    client: Client,
+    transport: Arc<dyn HttpTransport>,
+    url: String,
+    format: WebhookFormat,
+    criteria: NotifyCriteria,
+    min_interval: Duration,
+    last_sent: Option<Instant>,
+    seen: HashSet<String>,
+    seen_order: VecDeque<String>,
+}
+
+impl Notifier {
+    pub fn new(url: String, format: WebhookFormat, criteria: NotifyCriteria) -> Self {
+        let client = Client::new();
+        Self {
+            transport: Arc::new(ReqwestTransport::new(client.clone())),
+            client,
+            url,
+            format,
+            criteria,
+            min_interval: DEFAULT_MIN_INTERVAL,
+            last_sent: None,
+            seen: HashSet::new(),
+            seen_order: VecDeque::new(),
+        }
+    }
+
+    pub fn set_min_interval(&mut self, min_interval: Duration) {
+        self.min_interval = min_interval;
+    }
+
+    // Send through this transport instead of the network, e.g. a RecordedTransport in tests
+    pub fn set_transport(&mut self, transport: Arc<dyn HttpTransport>) {
+        self.transport = transport;
+    }
+
+    // Post the listing if it matches and hasn't been posted before, returning whether it was
+    pub async fn notify(&mut self, item: &Item) -> Result<bool> {
+        if !self.criteria.matches(item) || self.seen.contains(&item.id) {
+            return Ok(false);
+        }
+
+        let payload = self.payload(item)?;
+        // One retry when the webhook itself says to slow down
+        for attempt in 0..2 {
+            if let Some(last_sent) = self.last_sent {
+                tokio::time::sleep_until(last_sent + self.min_interval).await;
+            }
+            let request = self.client
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .body(payload.clone())
+                .build()?;
+            let response = self.transport.send(request).await?;
+            self.last_sent = Some(Instant::now());
+
+            if response.status == StatusCode::TOO_MANY_REQUESTS && attempt == 0 {
+                let retry_after = retry_after(&response.headers).unwrap_or(self.min_interval);
+                warn!(?retry_after, "webhook is rate limiting us, retrying");
+                tokio::time::sleep(retry_after).await;
+                continue;
+            }
+            if !response.status.is_success() {
+                return Err(ScraperError::ApiError(format!(
+                    "Webhook rejected notification with status {}: {}",
+                    response.status, response.body
+                )));
+            }
+            break;
+        }
+
+        info!(id = %item.id, "sent listing notification");
+        self.remember(&item.id);
+        Ok(true)
+    }
+
+    fn remember(&mut self, id: &str) {
+        self.seen.insert(id.to_string());
+        self.seen_order.push_back(id.to_string());
+        if self.seen_order.len() > SEEN_CAPACITY {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+
+    fn payload(&self, item: &Item) -> Result<String> {
+        let whisper = item.contact.as_ref().and_then(|contact| contact.whisper.as_deref());
+        let payload = match self.format {
+            WebhookFormat::Discord => serde_json::json!({ "content": discord_message(item, whisper) }).to_string(),
+            WebhookFormat::Generic => serde_json::to_string(&GenericPayload {
+                id: &item.id,
+                name: item.name.as_deref(),
+                base_type: &item.item_type.base_type,
+                price: item.price.as_ref().map(|price| price.amount),
+                currency: item.price.as_ref().map(|price| price.currency.as_str()),
+                normalized_price: item.price.as_ref().and_then(|price| price.normalized_amount),
+                modifiers: item.modifiers.iter().map(|m| m.name.as_str()).collect(),
+                whisper,
+            })?,
+        };
+        debug!(%payload, "webhook payload");
+        Ok(payload)
+    }
+}
+
+// Seconds from a Retry-After header; Discord's can be fractional
+fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    let seconds: f64 = headers.get("retry-after")?.to_str().ok()?.trim().parse().ok()?;
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+// The listing's name, price and modifiers, then the whisper in a code block so it copies
+// cleanly, cut to Discord's message limit
+fn discord_message(item: &Item, whisper: Option<&str>) -> String {
+    let mut message = match &item.name {
+        Some(name) => format!("**{}** {}", name, item.item_type.base_type),
+        None => format!("**{}**", item.item_type.base_type),
+    };
+    if let Some(price) = &item.price {
+        message.push_str(&format!(" for {} {}", price.amount, price.currency));
+    }
+    for modifier in &item.modifiers {
+        message.push_str("\n- ");
+        message.push_str(&modifier.name);
+    }
+    if let Some(whisper) = whisper {
+        message.push_str(&format!("\n```\n{}\n```", whisper));
+    }
+    if message.chars().count() > DISCORD_MESSAGE_LIMIT {
+        message = message.chars().take(DISCORD_MESSAGE_LIMIT - 1).collect();
+        message.push('…');
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetcher::{HttpResponse, RecordedTransport};
+    use crate::models::fixtures;
+    use crate::models::ListingContact;
+    use reqwest::Method;
+
+    fn ring(id: &str, price: f64, life: f64) -> Item {
+        let mut item = fixtures::life_ring(id, price, life);
+        item.contact = Some(ListingContact {
+            whisper: Some(format!("@Seller Hi, I would like to buy your Iron Ring listed for {} exalted", price)),
+            stash: None,
+            indexed_at: None,
+        });
+        item
+    }
+
+    #[test]
+    fn test_criteria_parse_and_match() {
+        let criterion: ModCriterion = "+# to maximum Life >= 70".parse().unwrap();
+        assert_eq!(criterion, ModCriterion { modifier: "+# to maximum Life".to_string(), min_value: Some(70.0) });
+        assert!("+# to maximum Life>=lots".parse::<ModCriterion>().is_err());
+
+        let criteria = NotifyCriteria { modifiers: vec![criterion], max_price: Some(5.0) };
+        assert!(criteria.matches(&ring("a", 4.0, 80.0)));
+        assert!(!criteria.matches(&ring("b", 4.0, 60.0)));
+        assert!(!criteria.matches(&ring("c", 9.0, 80.0)));
+    }
+
+    #[tokio::test]
+    async fn test_matches_are_posted_once_with_whisper() {
+        let transport = Arc::new(RecordedTransport::new()
+            .respond(Method::POST, "/webhook", HttpResponse::new(StatusCode::TOO_MANY_REQUESTS, "").with_header("retry-after", "0"))
+            .respond(Method::POST, "/webhook", HttpResponse::new(StatusCode::NO_CONTENT, ""))
+            .respond(Method::POST, "/webhook", HttpResponse::new(StatusCode::NO_CONTENT, "")));
+        let criteria = NotifyCriteria { modifiers: vec!["+# to maximum Life".parse().unwrap()], max_price: Some(5.0) };
+        let mut notifier = Notifier::new("https://example.com/webhook".to_string(), WebhookFormat::Discord, criteria);
+        notifier.set_transport(transport.clone());
+        notifier.set_min_interval(Duration::ZERO);
+
+        assert!(notifier.notify(&ring("cheap", 3.0, 80.0)).await.unwrap());
+        assert!(!notifier.notify(&ring("cheap", 3.0, 80.0)).await.unwrap());
+        assert!(!notifier.notify(&ring("dear", 9.0, 80.0)).await.unwrap());
+        assert!(notifier.notify(&ring("another", 2.0, 50.0)).await.unwrap());
+        // The rate-limited attempt and its retry, then the second listing
+        assert_eq!(transport.requests().len(), 3);
+
+        let message = discord_message(&ring("cheap", 3.0, 80.0), Some("@Seller Hi"));
+        assert!(message.starts_with("**Iron Ring** for 3 "));
+        assert!(message.contains("- +80 to maximum Life"));
+        assert!(message.ends_with("```\n@Seller Hi\n```"));
+    }
+}
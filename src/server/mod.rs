@@ -0,0 +1,212 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures_util::{StreamExt, TryStreamExt};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::{error, info};
+use crate::analyzer::StatAnalyzer;
+use crate::errors::{Result, ScraperError};
+use crate::metrics::{Metrics, METRICS_CONTENT_TYPE};
+use crate::models::{CleanedItem, Item, ItemCategory};
+use crate::storage::{Database, Storage};
+
+// Items returned by /items unless the query asks for fewer
+pub const DEFAULT_ITEM_LIMIT: usize = 100;
+// Most items /items returns however many are asked for
+pub const MAX_ITEM_LIMIT: usize = 1000;
+
+// REST API over a scraper database, so other tools and web frontends can read collected
+// listings and analysis without opening the database themselves:
+//
+//     GET /items?mod=...&min_value=...&max_price=...&category=...&limit=...
+//     GET /mods/{name}/stats
+//     GET /report/attributes
+//     GET /metrics
+pub fn router(db: Database) -> Router {
+    Router::new()
+        .route("/items", get(items))
+        .route("/mods/:name/stats", get(modifier_stats))
+        .route("/report/attributes", get(attribute_report))
+        .with_state(Arc::new(db))
+        .merge(metrics_router())
+}
+
+// Just /metrics, for processes like the collection daemon that serve nothing else
+pub fn metrics_router() -> Router {
+    Router::new().route("/metrics", get(metrics))
+}
+
+// Serve the API on `addr` until the process is stopped
+pub async fn serve(db: Database, addr: SocketAddr) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(addr = %listener.local_addr()?, "serving REST API");
+    axum::serve(listener, router(db)).await?;
+    Ok(())
+}
+
+// Serve only /metrics on `addr` until the process is stopped
+pub async fn serve_metrics(addr: SocketAddr) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!(addr = %listener.local_addr()?, "serving metrics");
+    axum::serve(listener, metrics_router()).await?;
+    Ok(())
+}
+
+// A ScraperError as a JSON response: bad parameters are the client's fault, anything else
+// is ours and only logged in full
+struct ApiError(ScraperError);
+
+impl From<ScraperError> for ApiError {
+    fn from(error: ScraperError) -> Self {
+        Self(error)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match self.0.root() {
+            ScraperError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        if status.is_server_error() {
+            error!(error = %self.0, "REST API request failed");
+        }
+        (status, Json(serde_json::json!({ "error": self.0.to_string() }))).into_response()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ItemQuery {
+    // Modifier text, template (e.g. "+# to maximum Life") or stat hash
+    #[serde(rename = "mod")]
+    modifier: Option<String>,
+    // Least first rolled value of `mod`
+    min_value: Option<f64>,
+    // Most normalized price; unpriced listings are left out when set
+    max_price: Option<f64>,
+    category: Option<String>,
+    limit: Option<usize>,
+}
+
+impl ItemQuery {
+    fn matches(&self, item: &Item) -> bool {
+        let price_ok = self.max_price.is_none_or(|max| {
+            item.price.as_ref().and_then(|price| price.normalized_amount).is_some_and(|price| price <= max)
+        });
+        let category_ok = self.category.as_deref().is_none_or(|category| {
+            item.item_type.category == ItemCategory::from_name(category)
+        });
+        price_ok && category_ok
+    }
+}
+
+async fn items(State(db): State<Arc<Database>>, Query(query): Query<ItemQuery>) -> std::result::Result<Json<Vec<Item>>, ApiError> {
+    if query.min_value.is_some() && query.modifier.is_none() {
+        return Err(ScraperError::ValidationError("min_value needs a mod to apply to".to_string()).into());
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_ITEM_LIMIT).min(MAX_ITEM_LIMIT);
+
+    let items: Vec<Item> = match &query.modifier {
+        Some(modifier) => db
+            .items_with_modifier(modifier, query.min_value)
+            .await?
+            .into_iter()
+            .filter(|item| query.matches(item))
+            .take(limit)
+            .collect(),
+        None => db
+            .iter_items()
+            .try_filter(|item| std::future::ready(query.matches(item)))
+            .take(limit)
+            .try_collect()
+            .await?,
+    };
+    Ok(Json(items))
+}
+
+// A modifier's price distribution and its price buckets as of the last `db
+// refresh-aggregates`; 404 when no priced listing has it
+async fn modifier_stats(State(db): State<Arc<Database>>, Path(name): Path<String>) -> std::result::Result<Response, ApiError> {
+    let Some(distribution) = db.price_distribution(&name).await? else {
+        let body = serde_json::json!({ "error": format!("no priced listings with modifier '{}'", name) });
+        return Ok((StatusCode::NOT_FOUND, Json(body)).into_response());
+    };
+    let buckets = db.modifier_price_buckets(&name).await?;
+    Ok(Json(serde_json::json!({
+        "distribution": distribution,
+        "price_buckets": buckets,
+    }))
+    .into_response())
+}
+
+async fn metrics() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)], Metrics::global().render())
+}
+
+// The attribute report of `analyze`, over every stored listing
+async fn attribute_report(State(db): State<Arc<Database>>) -> std::result::Result<Json<serde_json::Value>, ApiError> {
+    let mut analyzer = StatAnalyzer::new();
+    let mut items = std::pin::pin!(db.iter_items());
+    while let Some(item) = items.try_next().await? {
+        analyzer.process_cleaned_item(&CleanedItem::from_item(&item));
+    }
+    Ok(Json(analyzer.generate_attribute_report()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::life_ring;
+    use crate::models::ItemBaseType;
+    use crate::storage::{JournalMode, PoolSettings};
+
+    #[tokio::test]
+    async fn test_endpoints_serve_stored_listings() {
+        let path = std::env::temp_dir().join(format!("poe2-server-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let settings = PoolSettings { journal_mode: JournalMode::Delete, ..PoolSettings::default() };
+        let db = Database::connect_with(&format!("sqlite:{}", path.display()), &settings).await.unwrap();
+        db.store_base_item(&ItemBaseType::new("Iron Ring".to_string(), ItemCategory::Accessory)).await.unwrap();
+        db.store_collected_item(&life_ring("cheap", 2.0, 40.0), None).await.unwrap();
+        db.store_collected_item(&life_ring("dear", 9.0, 80.0), None).await.unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router(db)).await });
+        let client = reqwest::Client::new();
+        let get = |url: String| {
+            let request = client.get(url);
+            async move { request.send().await.unwrap() }
+        };
+
+        let response = get(format!("{}/items?mod=%2B%23%20to%20maximum%20Life&max_price=5", base)).await;
+        let items: Vec<Item> = response.json().await.unwrap();
+        assert_eq!(items.iter().map(|item| item.id.as_str()).collect::<Vec<_>>(), vec!["cheap"]);
+
+        let response = get(format!("{}/items?min_value=10", base)).await;
+        assert_eq!(response.status().as_u16(), 400);
+
+        let response = get(format!("{}/mods/explicit.stat_3299347043/stats", base)).await;
+        let stats: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(stats["distribution"]["listings"], 2);
+        assert_eq!(stats["distribution"]["max"], 9.0);
+
+        let response = get(format!("{}/mods/Unknown/stats", base)).await;
+        assert_eq!(response.status().as_u16(), 404);
+
+        let response = get(format!("{}/report/attributes", base)).await;
+        assert!(response.status().is_success());
+        assert!(response.json::<serde_json::Value>().await.unwrap().is_object());
+
+        let response = get(format!("{}/metrics", base)).await;
+        let text = response.text().await.unwrap();
+        assert!(text.contains("poe2_items_ingested_total"));
+        assert!(text.contains("poe2_db_insert_seconds_bucket"));
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::{BTreeMap, HashMap};
+use serde::Serialize;
+use tracing::info;
+use crate::errors::Result;
+use crate::models::{percentile, ModKind};
+use super::database::{Storage, ITEM_PAGE_SIZE};
+
+// Value ranges each modifier is split into when none is given
+pub const DEFAULT_VALUE_BUCKETS: usize = 5;
+
+// One day's prices of the listings with a modifier whose first value fell in one range
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModifierPriceBucket {
+    pub modifier: String,
+    pub day: String,
+    pub bucket: u32,
+    pub value_min: f64,
+    pub value_max: f64,
+    pub listings: u32,
+    pub median: f64,
+    pub p25: f64,
+    pub p75: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AggregateRefreshSummary {
+    // Stored listings with a normalized price
+    pub listings: usize,
+    pub modifiers: usize,
+    pub buckets: usize,
+}
+
+// A modifier's sightings: day last seen, first value and normalized price
+type Observations = Vec<(String, f64, f64)>;
+
+// Rebuild the per-modifier price buckets from every stored listing. Each modifier's
+// observed values are split into `value_buckets` even ranges, so bucket 0 of a modifier
+// covers the same values on every day. Listings count towards the day they were last seen.
+pub async fn refresh_aggregates(storage: &impl Storage, value_buckets: usize) -> Result<AggregateRefreshSummary> {
+    let mut observations: HashMap<String, Observations> = HashMap::new();
+    let mut summary = AggregateRefreshSummary::default();
+    let mut after_id = 0;
+    loop {
+        let page = storage.items_after(after_id, ITEM_PAGE_SIZE, None).await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        for stored in &page {
+            let Some(price) = stored.item.price.as_ref().and_then(|price| price.normalized_amount) else {
+                continue;
+            };
+            summary.listings += 1;
+            let day = stored.last_seen_at.get(..10).unwrap_or(&stored.last_seen_at);
+            for modifier in &stored.item.modifiers {
+                // Named the way trend history names them
+                let name = if modifier.is_crafted {
+                    ModKind::Crafted.stat_key(&modifier.template())
+                } else {
+                    modifier.template()
+                };
+                let value = modifier.values.first().copied().unwrap_or_default();
+                observations.entry(name).or_default().push((day.to_string(), value, price));
+            }
+        }
+    }
+
+    summary.modifiers = observations.len();
+    let buckets: Vec<ModifierPriceBucket> = observations
+        .into_iter()
+        .flat_map(|(modifier, observations)| bucket_prices(modifier, observations, value_buckets.max(1)))
+        .collect();
+    summary.buckets = buckets.len();
+    storage.replace_modifier_price_buckets(&buckets).await?;
+
+    info!(listings = summary.listings, modifiers = summary.modifiers, buckets = summary.buckets, "refreshed price aggregates");
+    Ok(summary)
+}
+
+fn bucket_prices(modifier: String, observations: Observations, value_buckets: usize) -> Vec<ModifierPriceBucket> {
+    let low = observations.iter().map(|(_, value, _)| *value).fold(f64::INFINITY, f64::min);
+    let high = observations.iter().map(|(_, value, _)| *value).fold(f64::NEG_INFINITY, f64::max);
+    // A modifier that always rolls the same value gets a single bucket
+    let (value_buckets, width) = if high > low {
+        (value_buckets, (high - low) / value_buckets as f64)
+    } else {
+        (1, 0.0)
+    };
+
+    let mut prices: BTreeMap<(String, usize), Vec<f64>> = BTreeMap::new();
+    for (day, value, price) in observations {
+        let bucket = if width > 0.0 {
+            (((value - low) / width) as usize).min(value_buckets - 1)
+        } else {
+            0
+        };
+        prices.entry((day, bucket)).or_default().push(price);
+    }
+
+    prices
+        .into_iter()
+        .map(|((day, bucket), mut prices)| {
+            prices.sort_by(|a, b| a.total_cmp(b));
+            ModifierPriceBucket {
+                modifier: modifier.clone(),
+                day,
+                bucket: bucket as u32,
+                value_min: low + width * bucket as f64,
+                value_max: if bucket + 1 == value_buckets { high } else { low + width * (bucket + 1) as f64 },
+                listings: prices.len() as u32,
+                median: percentile(&prices, 0.5),
+                p25: percentile(&prices, 0.25),
+                p75: percentile(&prices, 0.75),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,141 @@
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use async_compression::tokio::write::{GzipEncoder, ZstdEncoder};
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+use crate::errors::Result;
+
+// Compression of a data file, told by its extension: .gz for gzip, .zst for zstd
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+pub type FileWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+impl Compression {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+
+    // `path` with this compression's extension added, e.g. part-0.jsonl.gz
+    pub fn with_extension(&self, path: PathBuf) -> PathBuf {
+        match self.extension() {
+            Some(extension) => {
+                let mut name = path.into_os_string();
+                name.push(".");
+                name.push(extension);
+                name.into()
+            }
+            None => path,
+        }
+    }
+
+    fn encoder(&self, file: tokio::fs::File) -> FileWriter {
+        let file = BufWriter::new(file);
+        match self {
+            Compression::None => Box::new(file),
+            Compression::Gzip => Box::new(GzipEncoder::new(file)),
+            Compression::Zstd => Box::new(ZstdEncoder::new(file)),
+        }
+    }
+
+    // Decompressing reader over `inner`. Concatenated gzip members and zstd frames are read
+    // as one stream, so appending to a compressed file works.
+    pub fn reader<'a>(&self, inner: impl Read + 'a) -> Result<Box<dyn BufRead + 'a>> {
+        Ok(match self {
+            Compression::None => Box::new(BufReader::new(inner)),
+            Compression::Gzip => Box::new(BufReader::new(flate2::read::MultiGzDecoder::new(inner))),
+            Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::new(inner)?)),
+        })
+    }
+}
+
+// Extension of the content under any compression, e.g. "ndjson" for data.ndjson.zst
+pub fn content_extension(path: &Path) -> Option<&str> {
+    let content = match Compression::from_path(path) {
+        Compression::None => path,
+        _ => Path::new(path.file_stem()?),
+    };
+    content.extension()?.to_str()
+}
+
+// Start a file compressed as its extension says. It has to be shut down once written for
+// the compressed stream to be complete.
+pub async fn create_file(path: &Path) -> Result<FileWriter> {
+    Ok(Compression::from_path(path).encoder(tokio::fs::File::create(path).await?))
+}
+
+// Add to a file compressed as its extension says; compressed data goes in a stream of its own
+pub async fn append_file(path: &Path) -> Result<FileWriter> {
+    let file = tokio::fs::OpenOptions::new().append(true).create(true).open(path).await?;
+    Ok(Compression::from_path(path).encoder(file))
+}
+
+pub async fn write_file(path: &Path, content: &[u8]) -> Result<()> {
+    let mut file = create_file(path).await?;
+    file.write_all(content).await?;
+    file.shutdown().await?;
+    Ok(())
+}
+
+// Open a file for reading, decompressing as its extension says
+pub fn open_file(path: &Path) -> Result<Box<dyn BufRead>> {
+    Compression::from_path(path).reader(std::fs::File::open(path)?)
+}
+
+// A whole file's contents, decompressed as its extension says
+pub async fn read_file(path: &Path) -> Result<Vec<u8>> {
+    let content = tokio::fs::read(path).await?;
+    match Compression::from_path(path) {
+        Compression::None => Ok(content),
+        compression => {
+            let mut decompressed = Vec::new();
+            compression.reader(content.as_slice())?.read_to_end(&mut decompressed)?;
+            Ok(decompressed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip_by_extension() {
+        for name in ["data.jsonl", "data.jsonl.gz", "data.jsonl.zst"] {
+            let path = std::env::temp_dir().join(format!("poe2-compression-test-{}-{}", std::process::id(), name));
+            write_file(&path, b"first\n").await.unwrap();
+            let mut appended = append_file(&path).await.unwrap();
+            appended.write_all(b"second\n").await.unwrap();
+            appended.shutdown().await.unwrap();
+
+            assert_eq!(read_file(&path).await.unwrap(), b"first\nsecond\n", "{}", name);
+            let lines: Vec<String> = open_file(&path).unwrap().lines().map(|line| line.unwrap()).collect();
+            assert_eq!(lines, ["first", "second"]);
+            if name != "data.jsonl" {
+                assert_ne!(std::fs::read(&path).unwrap(), b"first\nsecond\n");
+            }
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        assert_eq!(content_extension(Path::new("runs/data.ndjson.zst")), Some("ndjson"));
+        assert_eq!(content_extension(Path::new("data.json")), Some("json"));
+        assert_eq!(content_extension(Path::new("data.gz")), None);
+        assert_eq!(Compression::Gzip.with_extension(PathBuf::from("part-0.csv")), PathBuf::from("part-0.csv.gz"));
+    }
+}
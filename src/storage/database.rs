@@ -1,254 +1,672 @@
-use sqlx::{sqlite::SqlitePool, migrate::MigrateDatabase, Transaction, Sqlite};
 use crate::models::{
-    Item, 
-    ItemModifier, 
-    ItemBaseType,
-    ItemCategory,
-    StatRequirements,
-    CoreAttribute
+    percentile, CoreAttribute, Currency, GemProperties, Item, ItemBaseType, ItemCategory, ItemClass, ItemModifier, ItemPrice, ItemResponse,
+    ItemRarity, ItemType, ListingContact, ModKind, StashLocation, StatRequirements,
 };
-use crate::errors::Result;
+use crate::analyzer::{DailyAggregate, TrendSubject};
+use crate::errors::{Result, ScraperError};
+use crate::metrics::Metrics;
+use futures_util::{Stream, TryStreamExt};
 use std::collections::HashMap;
-use crate::ScraperError;
+use serde::Serialize;
+use super::pool::PoolSettings;
+use super::aggregates::ModifierPriceBucket;
+use super::migrations::{migration_statuses, previous_version, MigrationState, MigrationStatus};
+#[cfg(feature = "postgres")]
+use super::postgres::PostgresStorage;
+#[cfg(feature = "sqlite")]
+use super::sqlite::SqliteStorage;
 
 const DEFAULT_DATABASE_URL: &str = "sqlite:poe_items.db";
 
-pub struct Database {
-    pool: SqlitePool,
+// Listings fetched per query when streaming stored items
+pub const ITEM_PAGE_SIZE: u32 = 500;
+
+// A stored listing, flattened for export
+#[derive(Debug, Serialize)]
+pub struct CollectedItemRecord {
+    pub trade_id: String,
+    pub base_type: String,
+    pub name: Option<String>,
+    pub price_amount: Option<f64>,
+    pub price_currency: Option<Currency>,
+    pub price_normalized: Option<f64>,
+    pub corrupted: bool,
+    pub league: Option<String>,
+    pub stats: HashMap<String, f64>,
+    // First and most recent time a search returned the listing
+    pub collected_at: String,
+    pub last_seen_at: String,
+    pub delisted_at: Option<String>,
+    // The listing as the trade API returned it; too bulky for the plain export, so only
+    // the dataset export writes it
+    #[serde(skip)]
+    pub raw_json: Option<String>,
 }
 
-impl Database {
-    pub async fn initialize() -> Result<Self> {
-        let database_url = std::env::var("DATABASE_URL")
-            .unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_string());
-        
-        if !sqlx::Sqlite::database_exists(&database_url).await? {
-            println!("Creating new database at {}", database_url);
-            sqlx::Sqlite::create_database(&database_url).await?;
+// When a listing was seen, as carried over by an import
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListingTimes {
+    pub collected_at: String,
+    pub last_seen_at: String,
+    pub delisted_at: Option<String>,
+}
+
+// A row of the modifiers table
+#[derive(Debug, Clone, Serialize)]
+pub struct ModifierRecord {
+    pub id: i64,
+    pub name: String,
+    // Sorted stat hashes joined with '|', empty when unknown
+    pub stat_key: String,
+    pub tier: Option<i64>,
+    pub modifier_values: Vec<f64>,
+    pub is_crafted: bool,
+    pub created_at: String,
+}
+
+// A modifier rolled on a listing, keyed by trade ID rather than row ID so it still joins
+// once loaded somewhere else
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemModifierRecord {
+    pub trade_id: String,
+    pub modifier_id: i64,
+    pub modifier_values: Vec<f64>,
+    // When the listing was first collected, which the dataset export partitions by
+    pub collected_at: String,
+}
+
+// A stored listing with its modifiers, as compared against when pricing an item
+#[derive(Debug, Clone, Serialize)]
+pub struct StoredListing {
+    pub trade_id: String,
+    pub base_type: String,
+    pub name: Option<String>,
+    pub price_amount: Option<f64>,
+    pub price_currency: Option<Currency>,
+    pub price_normalized: Option<f64>,
+    // Modifier text and rolled values
+    pub modifiers: Vec<(String, Vec<f64>)>,
+}
+
+// A listing that disappeared soon after it was first seen, so was probably sold at its
+// asking price
+#[derive(Debug, Clone, Serialize)]
+pub struct ProbableSale {
+    pub trade_id: String,
+    pub base_type: String,
+    pub name: Option<String>,
+    pub price_amount: Option<f64>,
+    pub price_currency: Option<Currency>,
+    pub price_normalized: Option<f64>,
+    pub first_seen_at: String,
+    pub last_seen_at: String,
+    pub delisted_at: String,
+    // From first to last sighting, so a lower bound on how long it was listed
+    pub hours_listed: f64,
+}
+
+// Spread of the normalized prices of the stored listings with one modifier
+#[derive(Debug, Clone, Serialize)]
+pub struct PriceDistribution {
+    pub modifier: String,
+    pub listings: usize,
+    pub min: f64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub max: f64,
+}
+
+// A listing's seller contact as the collected_items columns it's stored in
+pub(crate) struct ContactColumns<'a> {
+    pub whisper: Option<&'a str>,
+    pub stash_name: Option<&'a str>,
+    pub stash_x: Option<i64>,
+    pub stash_y: Option<i64>,
+    pub indexed_at: Option<&'a str>,
+}
+
+impl<'a> ContactColumns<'a> {
+    pub fn of(item: &'a Item) -> Self {
+        let contact = item.contact.as_ref();
+        let stash = contact.and_then(|contact| contact.stash.as_ref());
+        Self {
+            whisper: contact.and_then(|contact| contact.whisper.as_deref()),
+            stash_name: stash.map(|stash| stash.name.as_str()),
+            stash_x: stash.map(|stash| stash.x as i64),
+            stash_y: stash.map(|stash| stash.y as i64),
+            indexed_at: contact.and_then(|contact| contact.indexed_at.as_deref()),
         }
-        
-        let pool = SqlitePool::connect(&database_url).await?;
-        
-        println!("Running database migrations...");
-        sqlx::migrate!("./migrations")
-            .run(&pool)
-            .await?;
-        
-        Ok(Self { pool })
-    }
-
-    pub async fn store_base_item(&self, base_item: &ItemBaseType) -> Result<i64> {
-        let mut tx = self.pool.begin().await?;
-        
-        // First check if base item exists
-        let existing_row = sqlx::query!(
-            "SELECT id FROM base_items WHERE name = ?",
-            base_item.name
-        )
-        .fetch_optional(&mut *tx)
-        .await?;
-
-        // Prepare all our data before using it in queries
-        let stat_requirements_json = serde_json::to_string(&base_item.stat_requirements)?;
-        let implicit_mods_json = serde_json::to_string(&base_item.implicit_modifiers)?;
-        let tags_json = serde_json::to_string(&base_item.tags)?;
-        let category_str = base_item.category.to_string();
-        let base_level = base_item.base_level as i64;
-
-        // Handle existing or insert new base item
-        let id = if let Some(row) = existing_row {
-            // Update existing base item
-            sqlx::query!(
-                r#"
-                UPDATE base_items SET
-                    category = ?,
-                    stat_requirements = ?,
-                    implicit_modifiers = ?,
-                    base_level = ?,
-                    tags = ?,
-                    updated_at = datetime('now')
-                WHERE id = ?
-                "#,
-                category_str,
-                stat_requirements_json,
-                implicit_mods_json,
-                base_level,
-                tags_json,
-                row.id
-            )
-            .execute(&mut *tx)
-            .await?;
-            
-            row.id.expect("Database returned null ID")
-        } else {
-            // Insert new base item
-            let result = sqlx::query!(
-                r#"
-                INSERT INTO base_items (
-                    name, category, stat_requirements,
-                    implicit_modifiers, base_level, tags,
-                    created_at, updated_at
-                ) VALUES (?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
-                "#,
-                base_item.name,
-                category_str,
-                stat_requirements_json,
-                implicit_mods_json,
-                base_level,
-                tags_json
-            )
-            .execute(&mut *tx)
-            .await?;
-            
-            result.last_insert_rowid()
-        };
-
-        tx.commit().await?;
-        Ok(id)
     }
+}
 
-    async fn ensure_modifier(&self, modifier: &ItemModifier, tx: &mut Transaction<'_, Sqlite>) -> Result<i64> {
-        let existing_row = sqlx::query!(
-            "SELECT id FROM modifiers WHERE name = ?",
-            modifier.name
-        )
-        .fetch_optional(&mut **tx)
-        .await?;
-
-        match existing_row {
-            Some(row) => Ok(row.id.expect("Database returned null ID")),
-            None => {
-                // Prepare all data before using in query
-                let values_json = serde_json::to_string(&modifier.values)?;
-                let stat_requirements_json = modifier.stat_requirements
-                    .as_ref()
-                    .map(|sr| serde_json::to_string(sr))
-                    .transpose()?;
-                let attribute_scaling_json = modifier.attribute_scaling
-                    .as_ref()
-                    .map(|scaling| serde_json::to_string(scaling))
-                    .transpose()?;
-                let tier = modifier.tier.map(|t| t as i64);
-
-                let result = sqlx::query!(
-                    r#"
-                    INSERT INTO modifiers (
-                        name, tier, modifier_values,
-                        is_crafted, stat_requirements,
-                        attribute_scaling, created_at
-                    ) VALUES (?, ?, ?, ?, ?, ?, datetime('now'))
-                    "#,
-                    modifier.name,
-                    tier,
-                    values_json,
-                    modifier.is_crafted,
-                    stat_requirements_json,
-                    attribute_scaling_json
-                )
-                .execute(&mut **tx)
-                .await?;
-
-                Ok(result.last_insert_rowid())
-            }
-        }
+// One row of a stored item joined with at most one of its modifiers, as both backends
+// read them before folding them into items
+pub(crate) struct ItemRow {
+    pub id: i64,
+    pub trade_id: String,
+    pub base_type: String,
+    pub category: String,
+    pub item_class: Option<String>,
+    pub name: Option<String>,
+    pub rarity: Option<String>,
+    pub price_amount: Option<f64>,
+    pub price_currency: Option<String>,
+    pub price_normalized: Option<f64>,
+    pub stats: String,
+    pub corrupted: bool,
+    pub mirrored: bool,
+    pub quality: Option<i64>,
+    pub league: Option<String>,
+    pub stat_requirements: String,
+    pub attribute_values: String,
+    pub sockets: Option<String>,
+    pub whisper: Option<String>,
+    pub stash_name: Option<String>,
+    pub stash_x: Option<i64>,
+    pub stash_y: Option<i64>,
+    pub indexed_at: Option<String>,
+    pub collected_at: String,
+    pub last_seen_at: String,
+    pub modifier: Option<String>,
+    pub stat_key: Option<String>,
+    pub tier: Option<i64>,
+    pub is_crafted: Option<bool>,
+    pub modifier_values: Option<String>,
+    pub modifier_rolls: Option<String>,
+}
+
+// A stored listing rebuilt as an item, with when it was seen
+#[derive(Debug, Clone)]
+pub struct StoredItem {
+    pub id: i64,
+    pub collected_at: String,
+    pub last_seen_at: String,
+    pub item: Item,
+}
+
+// A modifier's rolls as stored with a listing, NULL when their ranges aren't known
+pub(crate) fn rolls_json(modifier: &ItemModifier) -> Result<Option<String>> {
+    if modifier.rolls.is_empty() {
+        return Ok(None);
     }
+    Ok(Some(serde_json::to_string(&modifier.rolls)?))
+}
 
-    pub async fn store_collected_item(&self, item: &Item) -> Result<i64> {
-        println!("Attempting to store item in database: {} ({})", 
-            item.name.as_deref().unwrap_or("unnamed"), 
-            item.id);
-            
-        let mut tx = self.pool.begin().await?;
-        
-        // First, ensure we have the base item
-        let base_item_id = match sqlx::query!(
-            "SELECT id FROM base_items WHERE name = ?",
-            item.item_type.base_type
-        )
-        .fetch_optional(&mut *tx)
-        .await? {
-            Some(row) => {
-                println!("Found existing base item with id: {:?}", row.id);
-                row.id.expect("Database returned null ID")
+// Fold rows ordered by item ID into items. Listings stored before rarity was recorded come
+// back as Normal.
+pub(crate) fn fold_item_rows(rows: Vec<ItemRow>) -> Result<Vec<StoredItem>> {
+    let mut items: Vec<StoredItem> = Vec::new();
+    for row in rows {
+        if items.last().map(|stored| stored.id) != Some(row.id) {
+            let rarity = row.rarity.as_deref().and_then(ItemRarity::from_name).unwrap_or_default();
+            let mut item_type = ItemType::new(ItemCategory::from_name(&row.category), row.base_type, rarity);
+            item_type.class = row.item_class.as_deref().and_then(ItemClass::from_name);
+            let mut item = Item::new(row.trade_id, item_type);
+            item.name = row.name;
+            item.price = match (row.price_amount, row.price_currency) {
+                (Some(amount), Some(currency)) => Some(ItemPrice {
+                    amount,
+                    currency: Currency::from(currency),
+                    normalized_amount: row.price_normalized,
+                }),
+                _ => None,
+            };
+            item.stats = serde_json::from_str(&row.stats)?;
+            if item.is_gem() {
+                item.gem = GemProperties::from_stats(&item.stats);
             }
-            None => {
-                println!("Base item not found, this might cause an error due to foreign key constraint");
-                return Err(ScraperError::DatabaseError(
-                    format!("Base item not found: {}", item.item_type.base_type)
-                ));
+            item.corrupted = row.corrupted;
+            item.mirrored = row.mirrored;
+            item.quality = row.quality.map(|quality| quality as u32);
+            item.league = row.league;
+            item.stat_requirements = serde_json::from_str::<StatRequirements>(&row.stat_requirements)?;
+            item.attribute_values = serde_json::from_str::<HashMap<CoreAttribute, u32>>(&row.attribute_values)?;
+            item.sockets = row.sockets.as_deref().map(serde_json::from_str).transpose()?;
+            let stash = match (row.stash_x, row.stash_y) {
+                (Some(x), Some(y)) => Some(StashLocation {
+                    name: row.stash_name.unwrap_or_default(),
+                    x: x as u32,
+                    y: y as u32,
+                }),
+                _ => None,
+            };
+            if row.whisper.is_some() || stash.is_some() || row.indexed_at.is_some() {
+                item.contact = Some(ListingContact { whisper: row.whisper, stash, indexed_at: row.indexed_at });
             }
-        };
-        
-        // Prepare all our JSON strings and values before the query
-        let stats_json = serde_json::to_string(&item.stats)?;
-        let stat_requirements_json = serde_json::to_string(&item.stat_requirements)?;
-        let attribute_values_json = serde_json::to_string(&item.attribute_values)?;
-        
-        // Extract price information into owned values that will live long enough
-        let price_amount = item.price.as_ref().map(|p| p.amount);
-        let price_currency = item.price.as_ref().map(|p| p.currency.clone());
-        
-        println!("Inserting item into collected_items table...");
-        
-        // Insert collected item
-        let result = sqlx::query!(
-            r#"
-            INSERT INTO collected_items (
-                trade_id, base_item_id, name,
-                price_amount, price_currency,
-                stats, corrupted, stat_requirements,
-                attribute_values, collected_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))
-            "#,
-            item.id,
-            base_item_id,
-            item.name,
-            price_amount,
-            price_currency,
-            stats_json,
-            item.corrupted,
-            stat_requirements_json,
-            attribute_values_json
-        )
-        .execute(&mut *tx)
-        .await?;
-        
-        let item_id = result.last_insert_rowid();
-        println!("Successfully inserted item with ID: {}", item_id);
-        
-        // Store item modifiers
-        for modifier in &item.modifiers {
-            let modifier_id = self.ensure_modifier(modifier, &mut tx).await?;
-            let values_json = serde_json::to_string(&modifier.values)?;
-            
-            sqlx::query!(
-                r#"
-                INSERT INTO item_modifiers (
-                    item_id, modifier_id, modifier_values
-                ) VALUES (?, ?, ?)
-                "#,
-                item_id,
-                modifier_id,
-                values_json
-            )
-            .execute(&mut *tx)
-            .await?;
+            items.push(StoredItem {
+                id: row.id,
+                collected_at: row.collected_at,
+                last_seen_at: row.last_seen_at,
+                item,
+            });
+        }
+
+        if let (Some(name), Some(values), Some(stored)) = (row.modifier, row.modifier_values, items.last_mut()) {
+            let is_crafted = row.is_crafted.unwrap_or(false);
+            let stat_hashes: Vec<String> = row.stat_key
+                .map(|key| key.split('|').filter(|hash| !hash.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default();
+            // The kind isn't stored; crafted mods are flagged and the stat hashes tell the rest
+            let kind = if is_crafted {
+                ModKind::Crafted
+            } else {
+                stat_hashes.first().and_then(|hash| ModKind::from_stat_hash(hash)).unwrap_or_default()
+            };
+            stored.item.modifiers.push(ItemModifier {
+                name,
+                tier: row.tier.map(|tier| tier as i32),
+                values: serde_json::from_str(&values)?,
+                rolls: row.modifier_rolls.as_deref().map(serde_json::from_str).transpose()?.unwrap_or_default(),
+                is_crafted,
+                kind,
+                stat_requirements: None,
+                attribute_scaling: None,
+                stat_hashes,
+            });
         }
-        
-        tx.commit().await?;
-        println!("Successfully committed transaction for item");
-        
-        Ok(item_id)
     }
+    Ok(items)
+}
+
+// Everything the scraper keeps in a database. Each backend has its own migration set
+// under migrations/<backend>, kept in step so both hold the same tables.
+#[allow(async_fn_in_trait)]
+pub trait Storage {
+    // Insert or update a base type by name, returning its row ID
+    async fn store_base_item(&self, base_item: &ItemBaseType) -> Result<i64>;
+
+    // Insert a listing, or update its price, stats and last_seen_at when its trade ID is
+    // already stored. `raw` is the listing as fetched, kept for `db reprocess`. Fails when
+    // the listing's base type hasn't been stored yet, except for gems, whose base is added.
+    async fn store_collected_item(&self, item: &Item, raw: Option<&ItemResponse>) -> Result<i64>;
+
+    // Upsert a listing exported from another database, keeping its own timestamps. The
+    // listing's data only replaces a stored copy that was last seen earlier; either way the
+    // earliest collection time is kept. False when the stored copy was newer.
+    async fn import_collected_item(&self, item: &Item, raw: Option<&ItemResponse>, times: &ListingTimes) -> Result<bool>;
+
+    // Rewrite the converted columns and modifiers of an already stored listing, leaving
+    // when it was seen alone. False when no listing has the item's trade ID.
+    async fn update_collected_item(&self, item: &Item) -> Result<bool>;
+
+    // Up to `limit` stored raw listings with an ID above `after_id`, in ID order
+    async fn raw_listings_after(&self, after_id: i64, limit: u32) -> Result<Vec<(i64, String)>>;
+
+    // Note the start of a collection run, returning its ID. Listings are marked delisted by
+    // how many runs have missed them.
+    async fn begin_collection_run(&self) -> Result<i64>;
+
+    // Mark listings that none of the latest `missed_runs` runs saw as delisted, returning
+    // how many were marked. Nothing is marked until that many runs have been recorded.
+    async fn mark_delisted(&self, missed_runs: u32) -> Result<u64>;
+
+    // Note listings as seen now without fetching them again, for search results unchanged
+    // since the previous search. Returns how many stored listings were updated.
+    async fn mark_seen(&self, trade_ids: &[String]) -> Result<u64>;
+
+    // Delisted listings that were listed for at most `within_hours`, optionally of one base
+    // type, most recently delisted first
+    async fn probable_sales(&self, within_hours: f64, base_type: Option<&str>) -> Result<Vec<ProbableSale>>;
 
-    pub async fn base_item_exists(&self, name: &str) -> Result<bool> {
-        let result = sqlx::query!(
-            "SELECT COUNT(*) as count FROM base_items WHERE name = ?",
-            name
-        )
-        .fetch_one(&self.pool)
-        .await?;
+    // Up to `limit` stored items with an ID above `after_id`, in ID order. With
+    // `last_seen_before`, only listings last seen before that time.
+    async fn items_after(&self, after_id: i64, limit: u32, last_seen_before: Option<&str>) -> Result<Vec<StoredItem>>;
+
+    // Every stored item, fetched a page at a time so the whole table never sits in memory
+    fn iter_items(&self) -> impl Stream<Item = Result<Item>> + '_ {
+        futures_util::stream::try_unfold(Some(0), move |after_id| async move {
+            let Some(after_id) = after_id else {
+                return Ok::<_, ScraperError>(None);
+            };
+            let page = self.items_after(after_id, ITEM_PAGE_SIZE, None).await?;
+            let next = match page.last() {
+                Some(stored) if page.len() == ITEM_PAGE_SIZE as usize => Some(stored.id),
+                _ => None,
+            };
+            let items: Vec<Result<Item>> = page.into_iter().map(|stored| Ok(stored.item)).collect();
+            Ok(Some((futures_util::stream::iter(items), next)))
+        })
+        .try_flatten()
+    }
+
+    async fn items_by_category(&self, category: &ItemCategory) -> Result<Vec<Item>> {
+        self.iter_items()
+            .try_filter(|item| std::future::ready(&item.item_type.category == category))
+            .try_collect()
+            .await
+    }
+
+    // Items with the modifier, matched by its text, its template (e.g. "+# to maximum
+    // Life") or a stat hash, whose first rolled value is at least `min_value`
+    async fn items_with_modifier(&self, modifier: &str, min_value: Option<f64>) -> Result<Vec<Item>> {
+        self.iter_items()
+            .try_filter(|item| {
+                let matches = item.modifiers.iter().any(|m| {
+                    m.matches(modifier)
+                        && min_value.is_none_or(|min| m.values.first().is_some_and(|&value| value >= min))
+                });
+                std::future::ready(matches)
+            })
+            .try_collect()
+            .await
+    }
+
+    // None when no listing with the modifier has a normalized price
+    async fn price_distribution(&self, modifier: &str) -> Result<Option<PriceDistribution>> {
+        let mut prices: Vec<f64> = self.items_with_modifier(modifier, None)
+            .await?
+            .iter()
+            .filter_map(|item| item.price.as_ref().and_then(|price| price.normalized_amount))
+            .collect();
+        if prices.is_empty() {
+            return Ok(None);
+        }
+        prices.sort_by(|a, b| a.total_cmp(b));
+
+        Ok(Some(PriceDistribution {
+            modifier: modifier.to_string(),
+            listings: prices.len(),
+            min: prices[0],
+            p25: percentile(&prices, 0.25),
+            median: percentile(&prices, 0.5),
+            p75: percentile(&prices, 0.75),
+            max: prices[prices.len() - 1],
+        }))
+    }
+
+    // Delete listings last seen before `cutoff` along with modifiers no listing has any more,
+    // returning how many listings were deleted
+    async fn delete_listings_seen_before(&self, cutoff: &str) -> Result<u64>;
+
+    // Add price aggregates, keeping any already recorded for the same day and subject.
+    // Returns how many were added.
+    async fn add_price_aggregates(&self, aggregates: &[DailyAggregate]) -> Result<u64>;
+
+    // Give the space freed by deletions back to the filesystem
+    async fn vacuum(&self) -> Result<()>;
+
+    // Every collected listing with its base type, oldest first
+    async fn collected_items(&self) -> Result<Vec<CollectedItemRecord>>;
+
+    // Every stored modifier, in ID order
+    async fn modifier_records(&self) -> Result<Vec<ModifierRecord>>;
+
+    // Every listing's modifiers, in listing order
+    async fn item_modifier_records(&self) -> Result<Vec<ItemModifierRecord>>;
+
+    async fn base_item_exists(&self, name: &str) -> Result<bool>;
+
+    // Record a day's price aggregates, replacing any already stored for the same day
+    async fn store_price_aggregates(&self, aggregates: &[DailyAggregate]) -> Result<()>;
+
+    // Daily aggregates of one modifier or base type, oldest day first
+    async fn price_history(&self, kind: TrendSubject, subject: &str) -> Result<Vec<DailyAggregate>>;
+
+    // Replace every stored modifier price bucket with these
+    async fn replace_modifier_price_buckets(&self, buckets: &[ModifierPriceBucket]) -> Result<()>;
+
+    // A modifier's price buckets as of the last refresh, by day and then value range
+    async fn modifier_price_buckets(&self, modifier: &str) -> Result<Vec<ModifierPriceBucket>>;
+
+    // Category of a known base type, as stored by store_base_item
+    async fn base_category(&self, base_type: &str) -> Result<Option<String>>;
+
+    // Every collected listing whose base type is in `category`, with its modifiers
+    async fn listings_in_category(&self, category: &str) -> Result<Vec<StoredListing>>;
+}
+
+// The backend picked by the database URL: postgres:// or postgresql:// for Postgres,
+// anything else for SQLite
+pub enum Database {
+    #[cfg(feature = "sqlite")]
+    Sqlite(SqliteStorage),
+    #[cfg(feature = "postgres")]
+    Postgres(PostgresStorage),
+}
+
+fn is_postgres_url(database_url: &str) -> bool {
+    database_url.starts_with("postgres://") || database_url.starts_with("postgresql://")
+}
+
+// Runs the same call on whichever backend is connected
+macro_rules! dispatch {
+    ($db:expr, $storage:ident => $call:expr) => {
+        match $db {
+            #[cfg(feature = "sqlite")]
+            Database::Sqlite($storage) => $call,
+            #[cfg(feature = "postgres")]
+            Database::Postgres($storage) => $call,
+        }
+    };
+}
+
+impl Database {
+    // DATABASE_URL from the environment, falling back to the bundled SQLite file
+    pub fn default_url() -> String {
+        std::env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_string())
+    }
+
+    // Connect with the default pool settings and bring the schema up to date
+    pub async fn connect(database_url: &str) -> Result<Self> {
+        Self::connect_with(database_url, &PoolSettings::default()).await
+    }
 
-        Ok(result.count > 0)
+    pub async fn connect_with(database_url: &str, settings: &PoolSettings) -> Result<Self> {
+        let db = Self::open_with(database_url, settings).await?;
+        dispatch!(&db, storage => storage.run_migrations().await)?;
+        Ok(db)
     }
-}
\ No newline at end of file
+
+    // Open the database without applying migrations, for `db migrate` to inspect it first
+    pub async fn open_with(database_url: &str, settings: &PoolSettings) -> Result<Self> {
+        if is_postgres_url(database_url) {
+            #[cfg(feature = "postgres")]
+            return Ok(Self::Postgres(PostgresStorage::open(database_url, settings).await?));
+            #[cfg(not(feature = "postgres"))]
+            return Err(ScraperError::DatabaseError(
+                "built without Postgres support; rebuild with --features postgres".to_string(),
+            ));
+        }
+
+        #[cfg(feature = "sqlite")]
+        return Ok(Self::Sqlite(SqliteStorage::open(database_url, settings).await?));
+        #[cfg(not(feature = "sqlite"))]
+        return Err(ScraperError::DatabaseError(
+            "built without SQLite support; rebuild with --features sqlite".to_string(),
+        ));
+    }
+
+    // Every migration this build knows or the database has applied, with its state
+    pub async fn migration_status(&self) -> Result<Vec<MigrationStatus>> {
+        dispatch!(self, storage => {
+            let applied = storage.applied_migrations().await?;
+            Ok(migration_statuses(storage.migrator(), &applied))
+        })
+    }
+
+    // Apply the pending migrations, returning them
+    pub async fn run_migrations(&self) -> Result<Vec<MigrationStatus>> {
+        let mut pending: Vec<_> = self
+            .migration_status()
+            .await?
+            .into_iter()
+            .filter(|status| status.state == MigrationState::Pending)
+            .collect();
+        dispatch!(self, storage => storage.run_migrations().await)?;
+        for status in &mut pending {
+            status.state = MigrationState::Applied;
+        }
+        Ok(pending)
+    }
+
+    // Revert the applied migrations newer than `target`, newest first, returning them.
+    // Without a target only the newest one is reverted.
+    pub async fn revert_migrations(&self, target: Option<i64>) -> Result<Vec<MigrationStatus>> {
+        let statuses = self.migration_status().await?;
+        let target = target.unwrap_or_else(|| previous_version(&statuses));
+        let mut reverted: Vec<_> = statuses
+            .into_iter()
+            .filter(|status| status.state != MigrationState::Pending && status.version > target)
+            .collect();
+        if let Some(status) = reverted.iter().find(|status| !status.reversible) {
+            return Err(ScraperError::MigrationError(format!(
+                "migration {} ({}) has no down script and can't be reverted",
+                status.version, status.description
+            )));
+        }
+
+        dispatch!(self, storage => storage.revert_migrations(target).await)?;
+        for status in &mut reverted {
+            status.state = MigrationState::Pending;
+        }
+        reverted.reverse();
+        Ok(reverted)
+    }
+}
+
+impl Storage for Database {
+    async fn store_base_item(&self, base_item: &ItemBaseType) -> Result<i64> {
+        dispatch!(self, storage => storage.store_base_item(base_item).await)
+    }
+
+    async fn store_collected_item(&self, item: &Item, raw: Option<&ItemResponse>) -> Result<i64> {
+        let started = std::time::Instant::now();
+        let id = dispatch!(self, storage => storage.store_collected_item(item, raw).await)?;
+        Metrics::global().record_item_stored(started.elapsed());
+        Ok(id)
+    }
+
+    async fn import_collected_item(&self, item: &Item, raw: Option<&ItemResponse>, times: &ListingTimes) -> Result<bool> {
+        dispatch!(self, storage => storage.import_collected_item(item, raw, times).await)
+    }
+
+    async fn update_collected_item(&self, item: &Item) -> Result<bool> {
+        dispatch!(self, storage => storage.update_collected_item(item).await)
+    }
+
+    async fn raw_listings_after(&self, after_id: i64, limit: u32) -> Result<Vec<(i64, String)>> {
+        dispatch!(self, storage => storage.raw_listings_after(after_id, limit).await)
+    }
+
+    async fn begin_collection_run(&self) -> Result<i64> {
+        dispatch!(self, storage => storage.begin_collection_run().await)
+    }
+
+    async fn mark_delisted(&self, missed_runs: u32) -> Result<u64> {
+        dispatch!(self, storage => storage.mark_delisted(missed_runs).await)
+    }
+
+    async fn mark_seen(&self, trade_ids: &[String]) -> Result<u64> {
+        dispatch!(self, storage => storage.mark_seen(trade_ids).await)
+    }
+
+    async fn probable_sales(&self, within_hours: f64, base_type: Option<&str>) -> Result<Vec<ProbableSale>> {
+        dispatch!(self, storage => storage.probable_sales(within_hours, base_type).await)
+    }
+
+    async fn items_after(&self, after_id: i64, limit: u32, last_seen_before: Option<&str>) -> Result<Vec<StoredItem>> {
+        dispatch!(self, storage => storage.items_after(after_id, limit, last_seen_before).await)
+    }
+
+    async fn delete_listings_seen_before(&self, cutoff: &str) -> Result<u64> {
+        dispatch!(self, storage => storage.delete_listings_seen_before(cutoff).await)
+    }
+
+    async fn add_price_aggregates(&self, aggregates: &[DailyAggregate]) -> Result<u64> {
+        dispatch!(self, storage => storage.add_price_aggregates(aggregates).await)
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        dispatch!(self, storage => storage.vacuum().await)
+    }
+
+    async fn collected_items(&self) -> Result<Vec<CollectedItemRecord>> {
+        dispatch!(self, storage => storage.collected_items().await)
+    }
+
+    async fn modifier_records(&self) -> Result<Vec<ModifierRecord>> {
+        dispatch!(self, storage => storage.modifier_records().await)
+    }
+
+    async fn item_modifier_records(&self) -> Result<Vec<ItemModifierRecord>> {
+        dispatch!(self, storage => storage.item_modifier_records().await)
+    }
+
+    async fn base_item_exists(&self, name: &str) -> Result<bool> {
+        dispatch!(self, storage => storage.base_item_exists(name).await)
+    }
+
+    async fn store_price_aggregates(&self, aggregates: &[DailyAggregate]) -> Result<()> {
+        dispatch!(self, storage => storage.store_price_aggregates(aggregates).await)
+    }
+
+    async fn price_history(&self, kind: TrendSubject, subject: &str) -> Result<Vec<DailyAggregate>> {
+        dispatch!(self, storage => storage.price_history(kind, subject).await)
+    }
+
+    async fn replace_modifier_price_buckets(&self, buckets: &[ModifierPriceBucket]) -> Result<()> {
+        dispatch!(self, storage => storage.replace_modifier_price_buckets(buckets).await)
+    }
+
+    async fn modifier_price_buckets(&self, modifier: &str) -> Result<Vec<ModifierPriceBucket>> {
+        dispatch!(self, storage => storage.modifier_price_buckets(modifier).await)
+    }
+
+    async fn base_category(&self, base_type: &str) -> Result<Option<String>> {
+        dispatch!(self, storage => storage.base_category(base_type).await)
+    }
+
+    async fn listings_in_category(&self, category: &str) -> Result<Vec<StoredListing>> {
+        dispatch!(self, storage => storage.listings_in_category(category).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_chosen_by_url() {
+        assert!(is_postgres_url("postgres://scraper@localhost/poe"));
+        assert!(is_postgres_url("postgresql://localhost/poe"));
+        assert!(!is_postgres_url("sqlite:poe_items.db"));
+        assert!(!is_postgres_url(DEFAULT_DATABASE_URL));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_migrations_revert_and_reapply() {
+        let path = std::env::temp_dir().join(format!("poe2-migrate-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let settings = PoolSettings { journal_mode: super::super::JournalMode::Delete, ..PoolSettings::default() };
+        let url = format!("sqlite:{}", path.display());
+        let db = Database::open_with(&url, &settings).await.unwrap();
+
+        let applied = db.run_migrations().await.unwrap();
+        let newest = applied.last().unwrap().version;
+        let reverted = db.revert_migrations(None).await.unwrap();
+        let after_revert = db.migration_status().await.unwrap();
+        let reverted_all = db.revert_migrations(Some(0)).await.unwrap();
+        let reapplied = db.run_migrations().await.unwrap();
+
+        // As if the newest migration file had been edited after it was applied
+        let pool = sqlx::SqlitePool::connect(&url).await.unwrap();
+        sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = ?")
+            .bind(newest)
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+        let tampered = db.migration_status().await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(applied.iter().all(|status| status.reversible));
+        assert_eq!(reverted.iter().map(|status| status.version).collect::<Vec<_>>(), vec![newest]);
+        assert_eq!(after_revert.last().unwrap().state, MigrationState::Pending);
+        assert_eq!(reverted_all.len(), applied.len() - 1);
+        assert_eq!(reapplied.len(), applied.len());
+        assert_eq!(tampered.last().unwrap().state, MigrationState::ChecksumMismatch);
+        assert!(tampered.iter().rev().skip(1).all(|status| status.state == MigrationState::Applied));
+    }
+}
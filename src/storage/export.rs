@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use serde::Serialize;
+use serde_json::Value;
+use crate::errors::Result;
+use crate::models::Currency;
+use super::compression::{self, Compression};
+use super::database::{CollectedItemRecord, ItemModifierRecord, ModifierRecord, Storage};
+#[cfg(feature = "parquet")]
+use super::parquet_dataset;
+
+const CSV_HEADER: &str = "trade_id,base_type,name,price_amount,price_currency,price_normalized,corrupted,league,collected_at,last_seen_at,delisted_at";
+
+// Quote a field when it contains a delimiter, quote or line break
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// One row per listing; stats are left out since they don't flatten into fixed columns
+pub fn collected_items_csv(records: &[CollectedItemRecord]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    csv.push('\n');
+
+    for record in records {
+        let row = [
+            csv_field(&record.trade_id),
+            csv_field(&record.base_type),
+            csv_field(record.name.as_deref().unwrap_or("")),
+            record.price_amount.map(|amount| amount.to_string()).unwrap_or_default(),
+            csv_field(record.price_currency.as_ref().map_or("", Currency::as_str)),
+            record.price_normalized.map(|amount| amount.to_string()).unwrap_or_default(),
+            record.corrupted.to_string(),
+            csv_field(record.league.as_deref().unwrap_or("")),
+            csv_field(&record.collected_at),
+            csv_field(&record.last_seen_at),
+            csv_field(record.delisted_at.as_deref().unwrap_or("")),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+// File formats the dataset export writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetFormat {
+    Csv,
+    Jsonl,
+    // Typed columns, compressed inside the file rather than as a whole
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl DatasetFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            DatasetFormat::Csv => "csv",
+            DatasetFormat::Jsonl => "jsonl",
+            #[cfg(feature = "parquet")]
+            DatasetFormat::Parquet => "parquet",
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DatasetSummary {
+    pub listings: usize,
+    pub modifiers: usize,
+    pub item_modifiers: usize,
+    pub partitions: usize,
+}
+
+// One table's rows as CSV cells, JSON objects or a Parquet record batch
+trait DatasetRow: Sized {
+    const CSV_HEADER: &'static str;
+    fn csv_cells(&self) -> Result<Vec<String>>;
+    fn json(&self) -> Result<Value>;
+    #[cfg(feature = "parquet")]
+    fn record_batch(rows: &[&Self]) -> Result<arrow_array::RecordBatch>;
+}
+
+fn json_cell<T: Serialize>(value: &T) -> Result<String> {
+    Ok(csv_field(&serde_json::to_string(value)?))
+}
+
+impl DatasetRow for CollectedItemRecord {
+    const CSV_HEADER: &'static str = "trade_id,base_type,name,price_amount,price_currency,price_normalized,corrupted,league,stats,collected_at,last_seen_at,delisted_at,raw_json";
+
+    fn csv_cells(&self) -> Result<Vec<String>> {
+        Ok(vec![
+            csv_field(&self.trade_id),
+            csv_field(&self.base_type),
+            csv_field(self.name.as_deref().unwrap_or("")),
+            self.price_amount.map(|amount| amount.to_string()).unwrap_or_default(),
+            csv_field(self.price_currency.as_ref().map_or("", Currency::as_str)),
+            self.price_normalized.map(|amount| amount.to_string()).unwrap_or_default(),
+            self.corrupted.to_string(),
+            csv_field(self.league.as_deref().unwrap_or("")),
+            json_cell(&self.stats)?,
+            csv_field(&self.collected_at),
+            csv_field(&self.last_seen_at),
+            csv_field(self.delisted_at.as_deref().unwrap_or("")),
+            csv_field(self.raw_json.as_deref().unwrap_or("")),
+        ])
+    }
+
+    // The raw listing is nested as an object rather than a string
+    fn json(&self) -> Result<Value> {
+        let mut row = serde_json::to_value(self)?;
+        let raw = self.raw_json.as_deref().map(serde_json::from_str).transpose()?;
+        row["raw_json"] = raw.unwrap_or(Value::Null);
+        Ok(row)
+    }
+
+    #[cfg(feature = "parquet")]
+    fn record_batch(rows: &[&Self]) -> Result<arrow_array::RecordBatch> {
+        parquet_dataset::collected_items_batch(rows)
+    }
+}
+
+impl DatasetRow for ModifierRecord {
+    const CSV_HEADER: &'static str = "id,name,stat_key,tier,modifier_values,is_crafted,created_at";
+
+    fn csv_cells(&self) -> Result<Vec<String>> {
+        Ok(vec![
+            self.id.to_string(),
+            csv_field(&self.name),
+            csv_field(&self.stat_key),
+            self.tier.map(|tier| tier.to_string()).unwrap_or_default(),
+            json_cell(&self.modifier_values)?,
+            self.is_crafted.to_string(),
+            csv_field(&self.created_at),
+        ])
+    }
+
+    fn json(&self) -> Result<Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    #[cfg(feature = "parquet")]
+    fn record_batch(rows: &[&Self]) -> Result<arrow_array::RecordBatch> {
+        parquet_dataset::modifiers_batch(rows)
+    }
+}
+
+impl DatasetRow for ItemModifierRecord {
+    const CSV_HEADER: &'static str = "trade_id,modifier_id,modifier_values,collected_at";
+
+    fn csv_cells(&self) -> Result<Vec<String>> {
+        Ok(vec![
+            csv_field(&self.trade_id),
+            self.modifier_id.to_string(),
+            json_cell(&self.modifier_values)?,
+            csv_field(&self.collected_at),
+        ])
+    }
+
+    fn json(&self) -> Result<Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    #[cfg(feature = "parquet")]
+    fn record_batch(rows: &[&Self]) -> Result<arrow_array::RecordBatch> {
+        parquet_dataset::item_modifiers_batch(rows)
+    }
+}
+
+fn render_rows<T: DatasetRow>(rows: &[&T], format: DatasetFormat) -> Result<String> {
+    let mut content = String::new();
+    if format == DatasetFormat::Csv {
+        content.push_str(T::CSV_HEADER);
+        content.push('\n');
+    }
+    for row in rows {
+        if format == DatasetFormat::Csv {
+            content.push_str(&row.csv_cells()?.join(","));
+        } else {
+            content.push_str(&serde_json::to_string(&row.json()?)?);
+        }
+        content.push('\n');
+    }
+    Ok(content)
+}
+
+// Write one table file named `<stem>.<ext>` under `dir`. CSV and JSON lines files are
+// compressed whole and get the compression's extension; Parquet compresses its columns.
+async fn write_rows<T: DatasetRow>(dir: &Path, stem: &str, rows: &[&T], format: DatasetFormat, compression: Compression) -> Result<()> {
+    let path = dir.join(format!("{}.{}", stem, format.extension()));
+    #[cfg(feature = "parquet")]
+    if format == DatasetFormat::Parquet {
+        return compression::write_file(&path, &parquet_dataset::write_batch(&T::record_batch(rows)?, compression)?).await;
+    }
+    compression::write_file(&compression.with_extension(path), render_rows(rows, format)?.as_bytes()).await
+}
+
+// Write rows into one file per day they were collected, as `<table>/day=<YYYY-MM-DD>/part-0.<ext>`.
+// The table's directory is replaced so days removed since the last export don't linger.
+async fn write_partitioned<'a, T: DatasetRow + 'a>(
+    dir: &Path,
+    table: &str,
+    rows: impl IntoIterator<Item = (&'a str, &'a T)>,
+    format: DatasetFormat,
+    compression: Compression,
+) -> Result<usize> {
+    let mut days: BTreeMap<&str, Vec<&T>> = BTreeMap::new();
+    for (collected_at, row) in rows {
+        days.entry(collected_at.get(..10).unwrap_or(collected_at)).or_default().push(row);
+    }
+
+    let table_dir = dir.join(table);
+    if tokio::fs::try_exists(&table_dir).await? {
+        tokio::fs::remove_dir_all(&table_dir).await?;
+    }
+    for (day, rows) in &days {
+        let partition = table_dir.join(format!("day={}", day));
+        tokio::fs::create_dir_all(&partition).await?;
+        write_rows(&partition, "part-0", rows, format, compression).await?;
+    }
+    Ok(days.len())
+}
+
+// Dump the listing tables under `dir` in the Hive-style layout pandas and pyarrow load as
+// one dataset: modifiers.<ext> plus collected_items/ and item_modifiers/ partitioned by
+// the day each listing was first collected. CSV and JSON lines files get the compression's
+// extension; Parquet files keep theirs and are compressed inside.
+pub async fn export_dataset(
+    storage: &impl Storage,
+    dir: &Path,
+    format: DatasetFormat,
+    compression: Compression,
+) -> Result<DatasetSummary> {
+    let listings = storage.collected_items().await?;
+    let modifiers = storage.modifier_records().await?;
+    let item_modifiers = storage.item_modifier_records().await?;
+
+    tokio::fs::create_dir_all(dir).await?;
+    let modifier_rows: Vec<&ModifierRecord> = modifiers.iter().collect();
+    write_rows(dir, "modifiers", &modifier_rows, format, compression).await?;
+
+    let partitions = write_partitioned(
+        dir,
+        "collected_items",
+        listings.iter().map(|record| (record.collected_at.as_str(), record)),
+        format,
+        compression,
+    )
+    .await?;
+    write_partitioned(
+        dir,
+        "item_modifiers",
+        item_modifiers.iter().map(|record| (record.collected_at.as_str(), record)),
+        format,
+        compression,
+    )
+    .await?;
+
+    Ok(DatasetSummary {
+        listings: listings.len(),
+        modifiers: modifiers.len(),
+        item_modifiers: item_modifiers.len(),
+        partitions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_csv_escapes_fields() {
+        let records = vec![CollectedItemRecord {
+            trade_id: "abc".to_string(),
+            base_type: "Expert Plate, Heavy".to_string(),
+            name: Some("Dread \"Shell\"".to_string()),
+            price_amount: Some(2.5),
+            price_currency: Some(Currency::Exalted),
+            price_normalized: Some(2.5),
+            corrupted: false,
+            league: Some("Standard".to_string()),
+            stats: HashMap::new(),
+            collected_at: "2025-01-01 00:00:00".to_string(),
+            last_seen_at: "2025-01-02 00:00:00".to_string(),
+            delisted_at: None,
+            raw_json: None,
+        }];
+
+        let csv = collected_items_csv(&records);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            "abc,\"Expert Plate, Heavy\",\"Dread \"\"Shell\"\"\",2.5,exalted,2.5,false,Standard,2025-01-01 00:00:00,2025-01-02 00:00:00,"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use crate::data::currency_converter::CurrencyConverter;
+use crate::errors::{Result, ResultExt, ScraperError};
+use crate::models::{Item, ItemResponse};
+use super::compression;
+use super::database::{ListingTimes, Storage};
+#[cfg(feature = "parquet")]
+use super::parquet_dataset;
+
+// A collected_items row of an exported dataset. Only the raw listing and its timestamps
+// are read back; everything else is converted again from the raw listing.
+#[derive(Debug, Deserialize)]
+pub(super) struct ExportedListing {
+    pub(super) trade_id: String,
+    pub(super) collected_at: String,
+    pub(super) last_seen_at: String,
+    #[serde(default)]
+    pub(super) delisted_at: Option<String>,
+    #[serde(default)]
+    pub(super) raw_json: Option<ItemResponse>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportSummary {
+    // Listings written, whether new or replacing an older copy
+    pub imported: usize,
+    // Listings already stored with a more recent sighting
+    pub kept_existing: usize,
+    // Rows without a raw listing to rebuild the item from, e.g. from a flat export
+    pub missing_raw: usize,
+    pub failed: usize,
+}
+
+// JSON lines, compressed or not, and Parquet when built with it
+fn is_listing_file(path: &Path) -> bool {
+    let extension = compression::content_extension(path);
+    extension == Some("jsonl") || (cfg!(feature = "parquet") && extension == Some("parquet"))
+}
+
+// The files to read: the file itself, or every collected_items partition of a dataset
+// directory written by export_dataset
+async fn listing_files(path: &Path) -> Result<Vec<PathBuf>> {
+    #[cfg(not(feature = "parquet"))]
+    if compression::content_extension(path) == Some("parquet") {
+        return Err(ScraperError::ValidationError(
+            "Parquet import needs the parquet feature; export the dataset as jsonl instead".to_string(),
+        ));
+    }
+    if !tokio::fs::metadata(path).await?.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    let mut partitions = tokio::fs::read_dir(path.join("collected_items")).await?;
+    while let Some(partition) = partitions.next_entry().await? {
+        if !partition.file_type().await?.is_dir() {
+            continue;
+        }
+        let mut parts = tokio::fs::read_dir(partition.path()).await?;
+        while let Some(part) = parts.next_entry().await? {
+            if is_listing_file(&part.path()) {
+                files.push(part.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// The listings of one JSON lines file
+async fn json_listings(file: &Path) -> Result<Vec<ExportedListing>> {
+    let content = String::from_utf8(compression::read_file(file).await?)
+        .map_err(|e| ScraperError::ParseError(format!("{}: {}", file.display(), e)))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| serde_json::from_str(line).with_context(|| format!("{} line {}", file.display(), index + 1)))
+        .collect()
+}
+
+async fn import_listing(storage: &impl Storage, listing: ExportedListing, converter: &CurrencyConverter, summary: &mut ImportSummary) {
+    let Some(raw) = listing.raw_json else {
+        summary.missing_raw += 1;
+        return;
+    };
+
+    let mut item = match Item::try_from(raw.clone()) {
+        Ok(item) => item,
+        Err(e) => {
+            warn!(trade_id = %listing.trade_id, error = %e, "failed to convert imported listing");
+            summary.failed += 1;
+            return;
+        }
+    };
+    if let Some(price) = &mut item.price {
+        converter.normalize(price);
+    }
+
+    let times = ListingTimes {
+        collected_at: listing.collected_at,
+        last_seen_at: listing.last_seen_at,
+        delisted_at: listing.delisted_at,
+    };
+    match storage.import_collected_item(&item, Some(&raw), &times).await {
+        Ok(true) => summary.imported += 1,
+        Ok(false) => summary.kept_existing += 1,
+        Err(e) => {
+            warn!(trade_id = %listing.trade_id, error = %e, "failed to store imported listing");
+            summary.failed += 1;
+        }
+    }
+}
+
+// Load listings exported from another database, as JSON lines or Parquet. Each one is
+// converted from its raw listing the way a collection run converts it and upserted by
+// trade ID, keeping the timestamps it was exported with.
+pub async fn import_listings(storage: &impl Storage, path: &Path, converter: &CurrencyConverter) -> Result<ImportSummary> {
+    let mut summary = ImportSummary::default();
+    for file in listing_files(path).await? {
+        debug!(file = %file.display(), "importing listings");
+        let listings = match compression::content_extension(&file) {
+            #[cfg(feature = "parquet")]
+            Some("parquet") => parquet_dataset::read_listings(&file).with_context(|| file.display().to_string())?,
+            _ => json_listings(&file).await?,
+        };
+        for listing in listings {
+            import_listing(storage, listing, converter, &mut summary).await;
+        }
+    }
+
+    info!(
+        imported = summary.imported,
+        kept_existing = summary.kept_existing,
+        missing_raw = summary.missing_raw,
+        failed = summary.failed,
+        "imported listings"
+    );
+    Ok(summary)
+}
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use serde::Serialize;
+use sqlx::migrate::{AppliedMigration, Migrator};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationState {
+    Applied,
+    Pending,
+    // Applied, but the migration file has been edited since, so `run` will refuse to continue
+    ChecksumMismatch,
+    // Recorded in the database but unknown to this build, e.g. applied by a newer version
+    Missing,
+}
+
+impl std::fmt::Display for MigrationState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            MigrationState::Applied => "applied",
+            MigrationState::Pending => "pending",
+            MigrationState::ChecksumMismatch => "checksum mismatch",
+            MigrationState::Missing => "missing",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub state: MigrationState,
+    // Whether the migration has a down script, so `db migrate revert` can undo it
+    pub reversible: bool,
+}
+
+// Compare the migrations built into the binary with those the database records as applied,
+// ordered by version
+pub(crate) fn migration_statuses(migrator: &Migrator, applied: &[AppliedMigration]) -> Vec<MigrationStatus> {
+    let mut applied: HashMap<i64, &AppliedMigration> = applied.iter().map(|m| (m.version, m)).collect();
+    let mut statuses: Vec<MigrationStatus> = migrator
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| {
+            let state = match applied.remove(&migration.version) {
+                None => MigrationState::Pending,
+                Some(recorded) if recorded.checksum != migration.checksum => MigrationState::ChecksumMismatch,
+                Some(_) => MigrationState::Applied,
+            };
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                state,
+                reversible: migration.migration_type.is_reversible(),
+            }
+        })
+        .collect();
+
+    statuses.extend(applied.into_keys().map(|version| MigrationStatus {
+        version,
+        description: String::new(),
+        state: MigrationState::Missing,
+        reversible: false,
+    }));
+    statuses.sort_by_key(|status| status.version);
+    statuses
+}
+
+// The version `db migrate revert` goes back to when none is given: the one before the
+// newest applied migration, or 0 to revert everything
+pub(crate) fn previous_version(statuses: &[MigrationStatus]) -> i64 {
+    let mut applied = statuses
+        .iter()
+        .filter(|status| status.state != MigrationState::Pending)
+        .map(|status| status.version)
+        .rev();
+    applied.next();
+    applied.next().unwrap_or(0)
+}
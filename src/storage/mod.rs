@@ -1,2 +1,33 @@
+mod aggregates;
+pub mod compression;
 mod database;
-pub use database::Database;
\ No newline at end of file
+mod export;
+mod import;
+mod migrations;
+#[cfg(feature = "parquet")]
+mod parquet_dataset;
+mod pool;
+mod retention;
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+#[cfg(not(any(feature = "sqlite", feature = "postgres")))]
+compile_error!("enable at least one storage backend: the `sqlite` or `postgres` feature");
+
+pub use database::{
+    Database, Storage, CollectedItemRecord, ItemModifierRecord, ListingTimes, ModifierRecord, PriceDistribution, ProbableSale, StoredItem,
+    StoredListing, ITEM_PAGE_SIZE,
+};
+pub use pool::{PoolSettings, JournalMode, Synchronous, DEFAULT_POOL_SIZE, DEFAULT_BUSY_TIMEOUT};
+pub use aggregates::{refresh_aggregates, AggregateRefreshSummary, ModifierPriceBucket, DEFAULT_VALUE_BUCKETS};
+pub use import::{import_listings, ImportSummary};
+pub use migrations::{MigrationState, MigrationStatus};
+pub use retention::{prune, PruneSummary, RetentionPolicy};
+pub use export::{collected_items_csv, export_dataset, DatasetFormat, DatasetSummary};
+pub(crate) use export::csv_field;
+#[cfg(feature = "postgres")]
+pub use postgres::PostgresStorage;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStorage;
@@ -0,0 +1,219 @@
+use std::path::Path;
+use std::sync::Arc;
+use arrow_array::builder::{Float64Builder, ListBuilder, MapBuilder, StringBuilder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampSecondType;
+use arrow_array::{Array, ArrayRef, BooleanArray, Float64Array, Int64Array, PrimitiveArray, RecordBatch, StringArray, TimestampSecondArray};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression as ParquetCompression, GzipLevel};
+use parquet::file::properties::WriterProperties;
+use crate::analyzer::{format_timestamp, parse_timestamp};
+use crate::errors::{Result, ScraperError};
+use crate::models::Currency;
+use super::compression::Compression;
+use super::database::{CollectedItemRecord, ItemModifierRecord, ModifierRecord};
+use super::import::ExportedListing;
+
+// Stored timestamps become UTC timestamps in seconds, which pandas and pyarrow read as datetimes
+fn timestamp_type() -> DataType {
+    DataType::Timestamp(TimeUnit::Second, Some("UTC".into()))
+}
+
+fn values_type() -> DataType {
+    DataType::List(Arc::new(Field::new_list_field(DataType::Float64, true)))
+}
+
+fn collected_items_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("trade_id", DataType::Utf8, false),
+        Field::new("base_type", DataType::Utf8, false),
+        Field::new("name", DataType::Utf8, true),
+        Field::new("price_amount", DataType::Float64, true),
+        Field::new("price_currency", DataType::Utf8, true),
+        Field::new("price_normalized", DataType::Float64, true),
+        Field::new("corrupted", DataType::Boolean, false),
+        Field::new("league", DataType::Utf8, true),
+        Field::new_map(
+            "stats",
+            "entries",
+            Field::new("keys", DataType::Utf8, false),
+            Field::new("values", DataType::Float64, true),
+            false,
+            false,
+        ),
+        Field::new("collected_at", timestamp_type(), false),
+        Field::new("last_seen_at", timestamp_type(), false),
+        Field::new("delisted_at", timestamp_type(), true),
+        // The listing as the trade API returned it, as JSON text
+        Field::new("raw_json", DataType::Utf8, true),
+    ])
+}
+
+fn modifiers_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("id", DataType::Int64, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("stat_key", DataType::Utf8, false),
+        Field::new("tier", DataType::Int64, true),
+        Field::new("modifier_values", values_type(), false),
+        Field::new("is_crafted", DataType::Boolean, false),
+        Field::new("created_at", timestamp_type(), false),
+    ])
+}
+
+fn item_modifiers_schema() -> Schema {
+    Schema::new(vec![
+        Field::new("trade_id", DataType::Utf8, false),
+        Field::new("modifier_id", DataType::Int64, false),
+        Field::new("modifier_values", values_type(), false),
+        Field::new("collected_at", timestamp_type(), false),
+    ])
+}
+
+fn timestamps<'a>(values: impl Iterator<Item = Option<&'a str>>) -> Result<ArrayRef> {
+    let secs = values
+        .map(|value| {
+            value
+                .map(|value| {
+                    parse_timestamp(value)
+                        .and_then(|secs| i64::try_from(secs).ok())
+                        .ok_or_else(|| ScraperError::ParseError(format!("invalid timestamp '{}'", value)))
+                })
+                .transpose()
+        })
+        .collect::<Result<Vec<Option<i64>>>>()?;
+    Ok(Arc::new(TimestampSecondArray::from(secs).with_timezone("UTC")))
+}
+
+fn value_lists<'a>(values: impl Iterator<Item = &'a Vec<f64>>) -> ArrayRef {
+    let mut builder = ListBuilder::new(Float64Builder::new());
+    for values in values {
+        builder.values().append_slice(values);
+        builder.append(true);
+    }
+    Arc::new(builder.finish())
+}
+
+pub(super) fn collected_items_batch(rows: &[&CollectedItemRecord]) -> Result<RecordBatch> {
+    let mut stats = MapBuilder::new(None, StringBuilder::new(), Float64Builder::new());
+    for row in rows {
+        // Sorted so the same listings always write the same file
+        let mut entries: Vec<(&String, &f64)> = row.stats.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in entries {
+            stats.keys().append_value(key);
+            stats.values().append_value(*value);
+        }
+        stats.append(true)?;
+    }
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|row| &row.trade_id))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|row| &row.base_type))),
+        Arc::new(StringArray::from_iter(rows.iter().map(|row| row.name.as_deref()))),
+        Arc::new(Float64Array::from_iter(rows.iter().map(|row| row.price_amount))),
+        Arc::new(StringArray::from_iter(rows.iter().map(|row| row.price_currency.as_ref().map(Currency::as_str)))),
+        Arc::new(Float64Array::from_iter(rows.iter().map(|row| row.price_normalized))),
+        Arc::new(BooleanArray::from_iter(rows.iter().map(|row| Some(row.corrupted)))),
+        Arc::new(StringArray::from_iter(rows.iter().map(|row| row.league.as_deref()))),
+        Arc::new(stats.finish()),
+        timestamps(rows.iter().map(|row| Some(row.collected_at.as_str())))?,
+        timestamps(rows.iter().map(|row| Some(row.last_seen_at.as_str())))?,
+        timestamps(rows.iter().map(|row| row.delisted_at.as_deref()))?,
+        Arc::new(StringArray::from_iter(rows.iter().map(|row| row.raw_json.as_deref()))),
+    ];
+    Ok(RecordBatch::try_new(Arc::new(collected_items_schema()), columns)?)
+}
+
+pub(super) fn modifiers_batch(rows: &[&ModifierRecord]) -> Result<RecordBatch> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|row| row.id))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|row| &row.name))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|row| &row.stat_key))),
+        Arc::new(Int64Array::from_iter(rows.iter().map(|row| row.tier))),
+        value_lists(rows.iter().map(|row| &row.modifier_values)),
+        Arc::new(BooleanArray::from_iter(rows.iter().map(|row| Some(row.is_crafted)))),
+        timestamps(rows.iter().map(|row| Some(row.created_at.as_str())))?,
+    ];
+    Ok(RecordBatch::try_new(Arc::new(modifiers_schema()), columns)?)
+}
+
+pub(super) fn item_modifiers_batch(rows: &[&ItemModifierRecord]) -> Result<RecordBatch> {
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|row| &row.trade_id))),
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|row| row.modifier_id))),
+        value_lists(rows.iter().map(|row| &row.modifier_values)),
+        timestamps(rows.iter().map(|row| Some(row.collected_at.as_str())))?,
+    ];
+    Ok(RecordBatch::try_new(Arc::new(item_modifiers_schema()), columns)?)
+}
+
+// A whole Parquet file holding the batch. Parquet compresses its column chunks itself, so
+// the file keeps its .parquet name; only gzip is built in.
+pub(super) fn write_batch(batch: &RecordBatch, compression: Compression) -> Result<Vec<u8>> {
+    let codec = match compression {
+        Compression::None => ParquetCompression::UNCOMPRESSED,
+        Compression::Gzip => ParquetCompression::GZIP(GzipLevel::default()),
+        Compression::Zstd => {
+            return Err(ScraperError::ValidationError(
+                "Parquet exports can be gzip compressed but not zstd".to_string(),
+            ))
+        }
+    };
+    let properties = WriterProperties::builder().set_compression(codec).build();
+    let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), Some(properties))?;
+    writer.write(batch)?;
+    Ok(writer.into_inner()?)
+}
+
+fn text_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a StringArray> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_string_opt::<i32>())
+        .ok_or_else(|| ScraperError::ParseError(format!("no text column '{}'", name)))
+}
+
+fn timestamp_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a PrimitiveArray<TimestampSecondType>> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_primitive_opt::<TimestampSecondType>())
+        .ok_or_else(|| ScraperError::ParseError(format!("no timestamp column '{}'", name)))
+}
+
+// The stored form of a row's timestamp, None where it's null
+fn timestamp_at(column: &PrimitiveArray<TimestampSecondType>, row: usize) -> Result<Option<String>> {
+    if column.is_null(row) {
+        return Ok(None);
+    }
+    let secs = u64::try_from(column.value(row))
+        .map_err(|_| ScraperError::ParseError(format!("timestamp {} is before 1970", column.value(row))))?;
+    Ok(Some(format_timestamp(secs)))
+}
+
+// The collected_items rows of a Parquet file written by export_dataset. As with JSON lines,
+// only the raw listing and its timestamps are read.
+pub(super) fn read_listings(path: &Path) -> Result<Vec<ExportedListing>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(std::fs::File::open(path)?)?.build()?;
+    let mut listings = Vec::new();
+    for batch in reader {
+        let batch = batch?;
+        let trade_ids = text_column(&batch, "trade_id")?;
+        let collected_at = timestamp_column(&batch, "collected_at")?;
+        let last_seen_at = timestamp_column(&batch, "last_seen_at")?;
+        let delisted_at = timestamp_column(&batch, "delisted_at")?;
+        let raw_json = text_column(&batch, "raw_json")?;
+        for row in 0..batch.num_rows() {
+            let missing = |name: &str| ScraperError::ParseError(format!("row {} has no {}", row, name));
+            listings.push(ExportedListing {
+                trade_id: trade_ids.value(row).to_string(),
+                collected_at: timestamp_at(collected_at, row)?.ok_or_else(|| missing("collected_at"))?,
+                last_seen_at: timestamp_at(last_seen_at, row)?.ok_or_else(|| missing("last_seen_at"))?,
+                delisted_at: timestamp_at(delisted_at, row)?,
+                raw_json: raw_json.is_valid(row).then(|| serde_json::from_str(raw_json.value(row))).transpose()?,
+            });
+        }
+    }
+    Ok(listings)
+}
@@ -0,0 +1,48 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+pub const DEFAULT_POOL_SIZE: u32 = 5;
+pub const DEFAULT_BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+// SQLite journal mode. WAL lets the analyzer read while the collector writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    #[default]
+    Wal,
+}
+
+// How often SQLite waits for writes to reach the disk. Normal is safe under WAL; a
+// power cut can lose the last transactions but not corrupt the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Synchronous {
+    Off,
+    #[default]
+    Normal,
+    Full,
+}
+
+// How the connection pool is set up. Postgres only uses the pool size; the rest are
+// SQLite pragmas.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolSettings {
+    pub max_connections: u32,
+    // How long a connection waits for another one's write lock before failing with SQLITE_BUSY
+    pub busy_timeout: Duration,
+    pub journal_mode: JournalMode,
+    pub synchronous: Synchronous,
+}
+
+impl Default for PoolSettings {
+    fn default() -> Self {
+        Self {
+            max_connections: DEFAULT_POOL_SIZE,
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            journal_mode: JournalMode::default(),
+            synchronous: Synchronous::default(),
+        }
+    }
+}
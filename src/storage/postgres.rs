@@ -0,0 +1,969 @@
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::migrate::{AppliedMigration, Migrate, MigrateDatabase, Migrator};
+use sqlx::{Postgres, Row, Transaction};
+use crate::models::{Currency, Item, ItemCategory, ItemModifier, ItemBaseType, ItemResponse, ItemType};
+use crate::analyzer::{DailyAggregate, TrendSubject};
+use crate::errors::{Result, ScraperError};
+use tracing::{debug, info, trace};
+use super::pool::PoolSettings;
+use super::aggregates::ModifierPriceBucket;
+use super::database::{fold_item_rows, rolls_json, CollectedItemRecord, ContactColumns, ItemModifierRecord, ItemRow, ListingTimes, ModifierRecord, ProbableSale, Storage, StoredItem, StoredListing};
+
+// Current UTC time in the same text format SQLite's datetime('now') produces
+const NOW: &str = "to_char(now() AT TIME ZONE 'UTC', 'YYYY-MM-DD HH24:MI:SS')";
+
+// The query! macros only check against one database at build time, so this backend's
+// queries are checked at runtime instead
+pub struct PostgresStorage {
+    pool: PgPool,
+}
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+impl PostgresStorage {
+    // Open the database and bring the schema up to date
+    pub async fn connect(database_url: &str, settings: &PoolSettings) -> Result<Self> {
+        let storage = Self::open(database_url, settings).await?;
+        info!("running database migrations");
+        storage.run_migrations().await?;
+        Ok(storage)
+    }
+
+    // Open the database, creating it if needed, without touching its schema. Only the pool
+    // size applies; the other settings are SQLite pragmas.
+    pub async fn open(database_url: &str, settings: &PoolSettings) -> Result<Self> {
+        if !Postgres::database_exists(database_url).await? {
+            info!(%database_url, "creating new database");
+            Postgres::create_database(database_url).await?;
+        }
+
+        let pool = PgPoolOptions::new()
+            .max_connections(settings.max_connections)
+            .connect(database_url)
+            .await?;
+        Ok(Self { pool })
+    }
+
+    pub(crate) fn migrator(&self) -> &'static Migrator {
+        &MIGRATOR
+    }
+
+    pub(crate) async fn applied_migrations(&self) -> Result<Vec<AppliedMigration>> {
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        Ok(conn.list_applied_migrations().await?)
+    }
+
+    pub(crate) async fn run_migrations(&self) -> Result<()> {
+        Ok(MIGRATOR.run(&self.pool).await?)
+    }
+
+    // Revert applied migrations newer than `target`, newest first
+    pub(crate) async fn revert_migrations(&self, target: i64) -> Result<()> {
+        Ok(MIGRATOR.undo(&self.pool, target).await?)
+    }
+
+    // The modifier's row, found by text and stat hashes. A modifier without hashes matches
+    // any row with its text; one with hashes claims a row stored before hashes were known.
+    async fn ensure_modifier(&self, modifier: &ItemModifier, tx: &mut Transaction<'_, Postgres>) -> Result<i64> {
+        let stat_key = modifier.stat_key();
+        let existing_row = sqlx::query(
+            r#"
+            SELECT id, stat_key FROM modifiers
+            WHERE name = $1 AND (stat_key = $2 OR stat_key = '' OR $2 = '')
+            ORDER BY stat_key = $2 DESC, stat_key = '' DESC, id
+            LIMIT 1
+            "#
+        )
+        .bind(&modifier.name)
+        .bind(&stat_key)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        let id: i64 = match existing_row {
+            Some(row) => {
+                let id: i64 = row.try_get("id")?;
+                let stored_key: String = row.try_get("stat_key")?;
+                if stored_key == stat_key || stat_key.is_empty() {
+                    return Ok(id);
+                }
+                sqlx::query("UPDATE modifiers SET stat_key = $1 WHERE id = $2")
+                    .bind(&stat_key)
+                    .bind(id)
+                    .execute(&mut **tx)
+                    .await?;
+                id
+            }
+            None => {
+                let values_json = serde_json::to_string(&modifier.values)?;
+                let stat_requirements_json = modifier.stat_requirements
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?;
+                let attribute_scaling_json = modifier.attribute_scaling
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?;
+                let tier = modifier.tier.map(|t| t as i64);
+
+                let row = sqlx::query(&format!(
+                    r#"
+                    INSERT INTO modifiers (
+                        name, stat_key, tier, modifier_values,
+                        is_crafted, stat_requirements,
+                        attribute_scaling, created_at
+                    ) VALUES ($1, $2, $3, $4, $5, $6, $7, {NOW})
+                    RETURNING id
+                    "#
+                ))
+                .bind(&modifier.name)
+                .bind(&stat_key)
+                .bind(tier)
+                .bind(values_json)
+                .bind(modifier.is_crafted)
+                .bind(stat_requirements_json)
+                .bind(attribute_scaling_json)
+                .fetch_one(&mut **tx)
+                .await?;
+                row.try_get("id")?
+            }
+        };
+
+        for hash in &modifier.stat_hashes {
+            sqlx::query("INSERT INTO modifier_stat_hashes (modifier_id, stat_hash) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(id)
+                .bind(hash)
+                .execute(&mut **tx)
+                .await?;
+        }
+        Ok(id)
+    }
+}
+
+impl PostgresStorage {
+    async fn upsert_base_item(&self, base_item: &ItemBaseType, tx: &mut Transaction<'_, Postgres>) -> Result<i64> {
+        let stat_requirements_json = serde_json::to_string(&base_item.stat_requirements)?;
+        let implicit_mods_json = serde_json::to_string(&base_item.implicit_modifiers)?;
+        let tags_json = serde_json::to_string(&base_item.tags)?;
+        let category_str = base_item.category.to_string();
+        let base_level = base_item.base_level as i64;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO base_items (
+                name, category, item_class, stat_requirements,
+                implicit_modifiers, base_level, tags,
+                created_at, updated_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, {NOW}, {NOW})
+            ON CONFLICT (name) DO UPDATE SET
+                category = excluded.category,
+                item_class = COALESCE(excluded.item_class, base_items.item_class),
+                stat_requirements = excluded.stat_requirements,
+                implicit_modifiers = excluded.implicit_modifiers,
+                base_level = excluded.base_level,
+                tags = excluded.tags,
+                updated_at = excluded.updated_at
+            RETURNING id
+            "#
+        ))
+        .bind(&base_item.name)
+        .bind(category_str)
+        .bind(base_item.class.map(|class| class.as_str()))
+        .bind(stat_requirements_json)
+        .bind(implicit_mods_json)
+        .bind(base_level)
+        .bind(tags_json)
+        .fetch_one(&mut **tx)
+        .await?;
+
+        Ok(row.try_get("id")?)
+    }
+
+    // Row ID of a listing's stored base type. A base the listing was classified beyond
+    // Other gets its category and class filled in where the stored base doesn't know them,
+    // or is added when the base list doesn't hold it, as it often doesn't for gems. Other
+    // listings are only stored against known bases, so a missing one fails the listing's
+    // transaction.
+    async fn base_item_id(&self, item_type: &ItemType, tx: &mut Transaction<'_, Postgres>) -> Result<i64> {
+        let base_type = &item_type.base_type;
+        let id: Option<i64> = sqlx::query_scalar("SELECT id FROM base_items WHERE name = $1")
+            .bind(base_type)
+            .fetch_optional(&mut **tx)
+            .await?;
+        if item_type.category == ItemCategory::Other {
+            return id.ok_or_else(|| ScraperError::DatabaseError(format!("Base item not found: {}", base_type)));
+        }
+
+        let Some(id) = id else {
+            let mut base = ItemBaseType::new(base_type.clone(), item_type.category);
+            base.class = item_type.class;
+            return self.upsert_base_item(&base, tx).await;
+        };
+        sqlx::query(
+            r#"
+            UPDATE base_items SET
+                category = CASE WHEN category = 'Other' THEN $1 ELSE category END,
+                item_class = COALESCE(item_class, $2)
+            WHERE id = $3
+            "#,
+        )
+        .bind(item_type.category.to_string())
+        .bind(item_type.class.map(|class| class.as_str()))
+        .bind(id)
+        .execute(&mut **tx)
+        .await?;
+        Ok(id)
+    }
+
+    async fn replace_modifiers(&self, item_id: i64, modifiers: &[ItemModifier], tx: &mut Transaction<'_, Postgres>) -> Result<()> {
+        sqlx::query("DELETE FROM item_modifiers WHERE item_id = $1")
+            .bind(item_id)
+            .execute(&mut **tx)
+            .await?;
+
+        for modifier in modifiers {
+            let modifier_id = self.ensure_modifier(modifier, tx).await?;
+            let values_json = serde_json::to_string(&modifier.values)?;
+
+            sqlx::query("INSERT INTO item_modifiers (item_id, modifier_id, modifier_values, modifier_rolls) VALUES ($1, $2, $3, $4)")
+                .bind(item_id)
+                .bind(modifier_id)
+                .bind(values_json)
+                .bind(rolls_json(modifier)?)
+                .execute(&mut **tx)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl Storage for PostgresStorage {
+    async fn store_base_item(&self, base_item: &ItemBaseType) -> Result<i64> {
+        let mut tx = self.pool.begin().await?;
+        let id = self.upsert_base_item(base_item, &mut tx).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn store_collected_item(&self, item: &Item, raw: Option<&ItemResponse>) -> Result<i64> {
+        debug!(id = %item.id, name = item.name.as_deref().unwrap_or("unnamed"), "storing item");
+
+        // The base lookup, listing, modifiers and their links all share this one transaction
+        let mut tx = self.pool.begin().await?;
+
+        let base_item_id = self.base_item_id(&item.item_type, &mut tx).await?;
+
+        let stats_json = serde_json::to_string(&item.stats)?;
+        let stat_requirements_json = serde_json::to_string(&item.stat_requirements)?;
+        let attribute_values_json = serde_json::to_string(&item.attribute_values)?;
+        let sockets_json = item.sockets.as_ref().map(serde_json::to_string).transpose()?;
+        let contact = ContactColumns::of(item);
+        let raw_json = raw.map(serde_json::to_string).transpose()?;
+
+        let row = sqlx::query(&format!(
+            r#"
+            INSERT INTO collected_items (
+                trade_id, base_item_id, name,
+                price_amount, price_currency, price_normalized,
+                stats, corrupted, mirrored, quality, league, stat_requirements,
+                attribute_values, sockets, whisper, stash_name, stash_x, stash_y, indexed_at,
+                raw_json, rarity, collected_at, last_seen_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, {NOW}, {NOW})
+            ON CONFLICT (trade_id) DO UPDATE SET
+                name = excluded.name,
+                price_amount = excluded.price_amount,
+                price_currency = excluded.price_currency,
+                price_normalized = excluded.price_normalized,
+                stats = excluded.stats,
+                corrupted = excluded.corrupted,
+                mirrored = excluded.mirrored,
+                quality = excluded.quality,
+                league = COALESCE(excluded.league, collected_items.league),
+                stat_requirements = excluded.stat_requirements,
+                attribute_values = excluded.attribute_values,
+                sockets = excluded.sockets,
+                -- A sighting without seller details keeps the ones already stored
+                whisper = COALESCE(excluded.whisper, collected_items.whisper),
+                stash_name = COALESCE(excluded.stash_name, collected_items.stash_name),
+                stash_x = COALESCE(excluded.stash_x, collected_items.stash_x),
+                stash_y = COALESCE(excluded.stash_y, collected_items.stash_y),
+                indexed_at = COALESCE(excluded.indexed_at, collected_items.indexed_at),
+                raw_json = COALESCE(excluded.raw_json, collected_items.raw_json),
+                rarity = excluded.rarity,
+                last_seen_at = excluded.last_seen_at,
+                delisted_at = NULL
+            RETURNING id
+            "#
+        ))
+        .bind(&item.id)
+        .bind(base_item_id)
+        .bind(&item.name)
+        .bind(item.price.as_ref().map(|p| p.amount))
+        .bind(item.price.as_ref().map(|p| p.currency.to_string()))
+        .bind(item.price.as_ref().and_then(|p| p.normalized_amount))
+        .bind(stats_json)
+        .bind(item.corrupted)
+        .bind(item.mirrored)
+        .bind(item.quality.map(|quality| quality as i64))
+        .bind(&item.league)
+        .bind(stat_requirements_json)
+        .bind(attribute_values_json)
+        .bind(sockets_json)
+        .bind(contact.whisper)
+        .bind(contact.stash_name)
+        .bind(contact.stash_x)
+        .bind(contact.stash_y)
+        .bind(contact.indexed_at)
+        .bind(raw_json)
+        .bind(item.item_type.rarity.as_str())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let item_id: i64 = row.try_get("id")?;
+        trace!(item_id, "upserted collected item");
+
+        self.replace_modifiers(item_id, &item.modifiers, &mut tx).await?;
+
+        tx.commit().await?;
+
+        Ok(item_id)
+    }
+
+    async fn import_collected_item(&self, item: &Item, raw: Option<&ItemResponse>, times: &ListingTimes) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let raw_json = raw.map(serde_json::to_string).transpose()?;
+        let newer_stored = sqlx::query("SELECT id FROM collected_items WHERE trade_id = $1 AND last_seen_at >= $2")
+            .bind(&item.id)
+            .bind(&times.last_seen_at)
+            .fetch_optional(&mut *tx)
+            .await?;
+        if let Some(row) = newer_stored {
+            let id: i64 = row.try_get("id")?;
+            sqlx::query(
+                r#"
+                UPDATE collected_items SET
+                    collected_at = LEAST(collected_at, $1),
+                    raw_json = COALESCE(raw_json, $2)
+                WHERE id = $3
+                "#
+            )
+            .bind(&times.collected_at)
+            .bind(raw_json)
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(false);
+        }
+
+        let base_item_id = self.base_item_id(&item.item_type, &mut tx).await?;
+
+        let stats_json = serde_json::to_string(&item.stats)?;
+        let stat_requirements_json = serde_json::to_string(&item.stat_requirements)?;
+        let attribute_values_json = serde_json::to_string(&item.attribute_values)?;
+        let sockets_json = item.sockets.as_ref().map(serde_json::to_string).transpose()?;
+        let contact = ContactColumns::of(item);
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO collected_items (
+                trade_id, base_item_id, name,
+                price_amount, price_currency, price_normalized,
+                stats, corrupted, mirrored, quality, league, stat_requirements,
+                attribute_values, sockets, whisper, stash_name, stash_x, stash_y, indexed_at,
+                raw_json, rarity, collected_at, last_seen_at, delisted_at
+            ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24)
+            ON CONFLICT (trade_id) DO UPDATE SET
+                name = excluded.name,
+                price_amount = excluded.price_amount,
+                price_currency = excluded.price_currency,
+                price_normalized = excluded.price_normalized,
+                stats = excluded.stats,
+                corrupted = excluded.corrupted,
+                mirrored = excluded.mirrored,
+                quality = excluded.quality,
+                league = COALESCE(excluded.league, collected_items.league),
+                stat_requirements = excluded.stat_requirements,
+                attribute_values = excluded.attribute_values,
+                sockets = excluded.sockets,
+                -- A sighting without seller details keeps the ones already stored
+                whisper = COALESCE(excluded.whisper, collected_items.whisper),
+                stash_name = COALESCE(excluded.stash_name, collected_items.stash_name),
+                stash_x = COALESCE(excluded.stash_x, collected_items.stash_x),
+                stash_y = COALESCE(excluded.stash_y, collected_items.stash_y),
+                indexed_at = COALESCE(excluded.indexed_at, collected_items.indexed_at),
+                raw_json = COALESCE(excluded.raw_json, collected_items.raw_json),
+                rarity = excluded.rarity,
+                collected_at = LEAST(collected_items.collected_at, excluded.collected_at),
+                last_seen_at = excluded.last_seen_at,
+                delisted_at = excluded.delisted_at
+            RETURNING id
+            "#
+        )
+        .bind(&item.id)
+        .bind(base_item_id)
+        .bind(&item.name)
+        .bind(item.price.as_ref().map(|p| p.amount))
+        .bind(item.price.as_ref().map(|p| p.currency.to_string()))
+        .bind(item.price.as_ref().and_then(|p| p.normalized_amount))
+        .bind(stats_json)
+        .bind(item.corrupted)
+        .bind(item.mirrored)
+        .bind(item.quality.map(|quality| quality as i64))
+        .bind(&item.league)
+        .bind(stat_requirements_json)
+        .bind(attribute_values_json)
+        .bind(sockets_json)
+        .bind(contact.whisper)
+        .bind(contact.stash_name)
+        .bind(contact.stash_x)
+        .bind(contact.stash_y)
+        .bind(contact.indexed_at)
+        .bind(raw_json)
+        .bind(item.item_type.rarity.as_str())
+        .bind(&times.collected_at)
+        .bind(&times.last_seen_at)
+        .bind(&times.delisted_at)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let item_id: i64 = row.try_get("id")?;
+        self.replace_modifiers(item_id, &item.modifiers, &mut tx).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn update_collected_item(&self, item: &Item) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let contact = ContactColumns::of(item);
+
+        let row = sqlx::query(
+            r#"
+            UPDATE collected_items SET
+                name = $1,
+                price_amount = $2,
+                price_currency = $3,
+                price_normalized = $4,
+                stats = $5,
+                corrupted = $6,
+                mirrored = $7,
+                quality = $8,
+                league = COALESCE($9, collected_items.league),
+                stat_requirements = $10,
+                attribute_values = $11,
+                sockets = $12,
+                whisper = $13,
+                stash_name = $14,
+                stash_x = $15,
+                stash_y = $16,
+                indexed_at = $17,
+                rarity = $18
+            WHERE trade_id = $19
+            RETURNING id
+            "#
+        )
+        .bind(&item.name)
+        .bind(item.price.as_ref().map(|p| p.amount))
+        .bind(item.price.as_ref().map(|p| p.currency.to_string()))
+        .bind(item.price.as_ref().and_then(|p| p.normalized_amount))
+        .bind(serde_json::to_string(&item.stats)?)
+        .bind(item.corrupted)
+        .bind(item.mirrored)
+        .bind(item.quality.map(|quality| quality as i64))
+        .bind(&item.league)
+        .bind(serde_json::to_string(&item.stat_requirements)?)
+        .bind(serde_json::to_string(&item.attribute_values)?)
+        .bind(item.sockets.as_ref().map(serde_json::to_string).transpose()?)
+        .bind(contact.whisper)
+        .bind(contact.stash_name)
+        .bind(contact.stash_x)
+        .bind(contact.stash_y)
+        .bind(contact.indexed_at)
+        .bind(item.item_type.rarity.as_str())
+        .bind(&item.id)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(false);
+        };
+        self.replace_modifiers(row.try_get("id")?, &item.modifiers, &mut tx).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn raw_listings_after(&self, after_id: i64, limit: u32) -> Result<Vec<(i64, String)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, raw_json
+            FROM collected_items
+            WHERE id > $1 AND raw_json IS NOT NULL
+            ORDER BY id
+            LIMIT $2
+            "#
+        )
+        .bind(after_id)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok((row.try_get("id")?, row.try_get("raw_json")?)))
+            .collect()
+    }
+
+    async fn begin_collection_run(&self) -> Result<i64> {
+        let id = sqlx::query_scalar(&format!("INSERT INTO collection_runs (started_at) VALUES ({NOW}) RETURNING id"))
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(id)
+    }
+
+    async fn mark_delisted(&self, missed_runs: u32) -> Result<u64> {
+        if missed_runs == 0 {
+            return Ok(0);
+        }
+
+        let result = sqlx::query(&format!(
+            r#"
+            UPDATE collected_items SET delisted_at = {NOW}
+            WHERE delisted_at IS NULL AND last_seen_at < (
+                SELECT started_at FROM collection_runs ORDER BY id DESC LIMIT 1 OFFSET $1
+            )
+            "#
+        ))
+        .bind(missed_runs as i64 - 1)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn mark_seen(&self, trade_ids: &[String]) -> Result<u64> {
+        let result = sqlx::query(&format!(
+            "UPDATE collected_items SET last_seen_at = {NOW}, delisted_at = NULL WHERE trade_id = ANY($1)"
+        ))
+        .bind(trade_ids)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn probable_sales(&self, within_hours: f64, base_type: Option<&str>) -> Result<Vec<ProbableSale>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM (
+                SELECT c.id, c.trade_id, b.name AS base_type, c.name, c.price_amount,
+                       c.price_currency, c.price_normalized, c.collected_at, c.last_seen_at,
+                       c.delisted_at,
+                       EXTRACT(EPOCH FROM (c.last_seen_at::timestamp - c.collected_at::timestamp))::float8
+                           / 3600.0 AS hours_listed
+                FROM collected_items c
+                JOIN base_items b ON b.id = c.base_item_id
+                WHERE c.delisted_at IS NOT NULL AND ($2::text IS NULL OR b.name = $2)
+            ) sales
+            WHERE hours_listed <= $1
+            ORDER BY delisted_at DESC, id
+            "#
+        )
+        .bind(within_hours)
+        .bind(base_type)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(ProbableSale {
+                trade_id: row.try_get("trade_id")?,
+                base_type: row.try_get("base_type")?,
+                name: row.try_get("name")?,
+                price_amount: row.try_get("price_amount")?,
+                price_currency: row.try_get::<Option<String>, _>("price_currency")?.map(Currency::from),
+                price_normalized: row.try_get("price_normalized")?,
+                first_seen_at: row.try_get("collected_at")?,
+                last_seen_at: row.try_get("last_seen_at")?,
+                delisted_at: row.try_get("delisted_at")?,
+                hours_listed: row.try_get("hours_listed")?,
+            }))
+            .collect()
+    }
+
+    async fn items_after(&self, after_id: i64, limit: u32, last_seen_before: Option<&str>) -> Result<Vec<StoredItem>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.id, c.trade_id, b.name AS base_type, b.category, b.item_class,
+                   c.name, c.rarity, c.price_amount, c.price_currency, c.price_normalized,
+                   c.stats, c.corrupted, c.mirrored, c.quality, c.league, c.stat_requirements, c.attribute_values,
+                   c.sockets, c.whisper, c.stash_name, c.stash_x, c.stash_y, c.indexed_at,
+                   c.collected_at, c.last_seen_at,
+                   m.name AS modifier, m.stat_key, m.tier, m.is_crafted, im.modifier_values, im.modifier_rolls
+            FROM (
+                SELECT * FROM collected_items
+                WHERE id > $1 AND ($3::text IS NULL OR last_seen_at < $3)
+                ORDER BY id LIMIT $2
+            ) c
+            JOIN base_items b ON b.id = c.base_item_id
+            LEFT JOIN item_modifiers im ON im.item_id = c.id
+            LEFT JOIN modifiers m ON m.id = im.modifier_id
+            ORDER BY c.id
+            "#
+        )
+        .bind(after_id)
+        .bind(limit as i64)
+        .bind(last_seen_before)
+        .fetch_all(&self.pool)
+        .await?;
+
+        fold_item_rows(rows.into_iter()
+            .map(|row| Ok(ItemRow {
+                id: row.try_get("id")?,
+                trade_id: row.try_get("trade_id")?,
+                base_type: row.try_get("base_type")?,
+                category: row.try_get("category")?,
+                item_class: row.try_get("item_class")?,
+                name: row.try_get("name")?,
+                rarity: row.try_get("rarity")?,
+                price_amount: row.try_get("price_amount")?,
+                price_currency: row.try_get("price_currency")?,
+                price_normalized: row.try_get("price_normalized")?,
+                stats: row.try_get("stats")?,
+                corrupted: row.try_get("corrupted")?,
+                mirrored: row.try_get("mirrored")?,
+                quality: row.try_get("quality")?,
+                league: row.try_get("league")?,
+                stat_requirements: row.try_get("stat_requirements")?,
+                attribute_values: row.try_get("attribute_values")?,
+                sockets: row.try_get("sockets")?,
+                whisper: row.try_get("whisper")?,
+                stash_name: row.try_get("stash_name")?,
+                stash_x: row.try_get("stash_x")?,
+                stash_y: row.try_get("stash_y")?,
+                indexed_at: row.try_get("indexed_at")?,
+                collected_at: row.try_get("collected_at")?,
+                last_seen_at: row.try_get("last_seen_at")?,
+                modifier: row.try_get("modifier")?,
+                stat_key: row.try_get("stat_key")?,
+                tier: row.try_get("tier")?,
+                is_crafted: row.try_get("is_crafted")?,
+                modifier_values: row.try_get("modifier_values")?,
+                modifier_rolls: row.try_get("modifier_rolls")?,
+            }))
+            .collect::<Result<Vec<_>>>()?)
+    }
+
+    async fn delete_listings_seen_before(&self, cutoff: &str) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM item_modifiers WHERE item_id IN (SELECT id FROM collected_items WHERE last_seen_at < $1)")
+            .bind(cutoff)
+            .execute(&mut *tx)
+            .await?;
+        let deleted = sqlx::query("DELETE FROM collected_items WHERE last_seen_at < $1")
+            .bind(cutoff)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        sqlx::query("DELETE FROM modifier_stat_hashes WHERE modifier_id NOT IN (SELECT modifier_id FROM item_modifiers)")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM modifiers WHERE id NOT IN (SELECT modifier_id FROM item_modifiers)")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn add_price_aggregates(&self, aggregates: &[DailyAggregate]) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut added = 0;
+        for aggregate in aggregates {
+            added += sqlx::query(
+                r#"
+                INSERT INTO price_history (day, subject_kind, subject, median, p25, p75, volume, listings)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (day, subject_kind, subject) DO NOTHING
+                "#
+            )
+            .bind(&aggregate.day)
+            .bind(aggregate.kind.as_str())
+            .bind(&aggregate.subject)
+            .bind(aggregate.median)
+            .bind(aggregate.p25)
+            .bind(aggregate.p75)
+            .bind(aggregate.volume as i64)
+            .bind(aggregate.listings as i64)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    // Plain VACUUM only marks space reusable; FULL rewrites the tables so the disk shrinks
+    async fn vacuum(&self) -> Result<()> {
+        sqlx::query("VACUUM FULL").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn collected_items(&self) -> Result<Vec<CollectedItemRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.trade_id, b.name AS base_type, c.name, c.price_amount,
+                   c.price_currency, c.price_normalized, c.corrupted, c.league, c.stats, c.collected_at,
+                   c.last_seen_at, c.delisted_at, c.raw_json
+            FROM collected_items c
+            JOIN base_items b ON b.id = c.base_item_id
+            ORDER BY c.collected_at, c.id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(CollectedItemRecord {
+                trade_id: row.try_get("trade_id")?,
+                base_type: row.try_get("base_type")?,
+                name: row.try_get("name")?,
+                price_amount: row.try_get("price_amount")?,
+                price_currency: row.try_get::<Option<String>, _>("price_currency")?.map(Currency::from),
+                price_normalized: row.try_get("price_normalized")?,
+                corrupted: row.try_get("corrupted")?,
+                league: row.try_get("league")?,
+                stats: serde_json::from_str(row.try_get("stats")?)?,
+                collected_at: row.try_get("collected_at")?,
+                last_seen_at: row.try_get("last_seen_at")?,
+                delisted_at: row.try_get("delisted_at")?,
+                raw_json: row.try_get("raw_json")?,
+            }))
+            .collect()
+    }
+
+    async fn modifier_records(&self) -> Result<Vec<ModifierRecord>> {
+        let rows = sqlx::query("SELECT id, name, stat_key, tier, modifier_values, is_crafted, created_at FROM modifiers ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(ModifierRecord {
+                id: row.try_get("id")?,
+                name: row.try_get("name")?,
+                stat_key: row.try_get("stat_key")?,
+                tier: row.try_get("tier")?,
+                modifier_values: serde_json::from_str(row.try_get("modifier_values")?)?,
+                is_crafted: row.try_get("is_crafted")?,
+                created_at: row.try_get("created_at")?,
+            }))
+            .collect()
+    }
+
+    async fn item_modifier_records(&self) -> Result<Vec<ItemModifierRecord>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.trade_id, im.modifier_id, im.modifier_values, c.collected_at
+            FROM item_modifiers im
+            JOIN collected_items c ON c.id = im.item_id
+            ORDER BY c.id, im.modifier_id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(ItemModifierRecord {
+                trade_id: row.try_get("trade_id")?,
+                modifier_id: row.try_get("modifier_id")?,
+                modifier_values: serde_json::from_str(row.try_get("modifier_values")?)?,
+                collected_at: row.try_get("collected_at")?,
+            }))
+            .collect()
+    }
+
+    async fn base_item_exists(&self, name: &str) -> Result<bool> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM base_items WHERE name = $1")
+            .bind(name)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    async fn store_price_aggregates(&self, aggregates: &[DailyAggregate]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for aggregate in aggregates {
+            sqlx::query(
+                r#"
+                INSERT INTO price_history (day, subject_kind, subject, median, p25, p75, volume, listings)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (day, subject_kind, subject) DO UPDATE SET
+                    median = excluded.median,
+                    p25 = excluded.p25,
+                    p75 = excluded.p75,
+                    volume = excluded.volume,
+                    listings = excluded.listings
+                "#
+            )
+            .bind(&aggregate.day)
+            .bind(aggregate.kind.as_str())
+            .bind(&aggregate.subject)
+            .bind(aggregate.median)
+            .bind(aggregate.p25)
+            .bind(aggregate.p75)
+            .bind(aggregate.volume as i64)
+            .bind(aggregate.listings as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn price_history(&self, kind: TrendSubject, subject: &str) -> Result<Vec<DailyAggregate>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT day, subject_kind, subject, median, p25, p75, volume, listings
+            FROM price_history
+            WHERE subject_kind = $1 AND subject = $2
+            ORDER BY day
+            "#
+        )
+        .bind(kind.as_str())
+        .bind(subject)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(DailyAggregate {
+                day: row.try_get("day")?,
+                kind: TrendSubject::parse(row.try_get("subject_kind")?)?,
+                subject: row.try_get("subject")?,
+                median: row.try_get("median")?,
+                p25: row.try_get("p25")?,
+                p75: row.try_get("p75")?,
+                volume: row.try_get::<i64, _>("volume")? as u32,
+                listings: row.try_get::<i64, _>("listings")? as u32,
+            }))
+            .collect()
+    }
+
+    async fn replace_modifier_price_buckets(&self, buckets: &[ModifierPriceBucket]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM modifier_price_buckets")
+            .execute(&mut *tx)
+            .await?;
+
+        for bucket in buckets {
+            sqlx::query(&format!(
+                r#"
+                INSERT INTO modifier_price_buckets (
+                    modifier, day, bucket, value_min, value_max, listings, median, p25, p75, refreshed_at
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, {NOW})
+                "#
+            ))
+            .bind(&bucket.modifier)
+            .bind(&bucket.day)
+            .bind(bucket.bucket as i64)
+            .bind(bucket.value_min)
+            .bind(bucket.value_max)
+            .bind(bucket.listings as i64)
+            .bind(bucket.median)
+            .bind(bucket.p25)
+            .bind(bucket.p75)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn modifier_price_buckets(&self, modifier: &str) -> Result<Vec<ModifierPriceBucket>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT modifier, day, bucket, value_min, value_max, listings, median, p25, p75
+            FROM modifier_price_buckets
+            WHERE modifier = $1
+            ORDER BY day, bucket
+            "#
+        )
+        .bind(modifier)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(ModifierPriceBucket {
+                modifier: row.try_get("modifier")?,
+                day: row.try_get("day")?,
+                bucket: row.try_get::<i64, _>("bucket")? as u32,
+                value_min: row.try_get("value_min")?,
+                value_max: row.try_get("value_max")?,
+                listings: row.try_get::<i64, _>("listings")? as u32,
+                median: row.try_get("median")?,
+                p25: row.try_get("p25")?,
+                p75: row.try_get("p75")?,
+            }))
+            .collect()
+    }
+
+    async fn base_category(&self, base_type: &str) -> Result<Option<String>> {
+        let category = sqlx::query_scalar("SELECT category FROM base_items WHERE name = $1")
+            .bind(base_type)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(category)
+    }
+
+    async fn listings_in_category(&self, category: &str) -> Result<Vec<StoredListing>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.id, c.trade_id, b.name AS base_type, c.name, c.price_amount,
+                   c.price_currency, c.price_normalized,
+                   m.name AS modifier, im.modifier_values
+            FROM collected_items c
+            JOIN base_items b ON b.id = c.base_item_id
+            LEFT JOIN item_modifiers im ON im.item_id = c.id
+            LEFT JOIN modifiers m ON m.id = im.modifier_id
+            WHERE b.category = $1
+            ORDER BY c.id
+            "#
+        )
+        .bind(category)
+        .fetch_all(&self.pool)
+        .await?;
+
+        // One row per modifier, so fold consecutive rows of the same listing together
+        let mut listings: Vec<StoredListing> = Vec::new();
+        let mut current_id = None;
+        for row in rows {
+            let id: i64 = row.try_get("id")?;
+            if current_id != Some(id) {
+                current_id = Some(id);
+                listings.push(StoredListing {
+                    trade_id: row.try_get("trade_id")?,
+                    base_type: row.try_get("base_type")?,
+                    name: row.try_get("name")?,
+                    price_amount: row.try_get("price_amount")?,
+                    price_currency: row.try_get::<Option<String>, _>("price_currency")?.map(Currency::from),
+                    price_normalized: row.try_get("price_normalized")?,
+                    modifiers: Vec::new(),
+                });
+            }
+            let modifier: Option<String> = row.try_get("modifier")?;
+            let values: Option<&str> = row.try_get("modifier_values")?;
+            if let (Some(modifier), Some(values), Some(listing)) = (modifier, values, listings.last_mut()) {
+                listing.modifiers.push((modifier, serde_json::from_str(values)?));
+            }
+        }
+
+        Ok(listings)
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use tracing::{debug, info};
+use crate::analyzer::{format_timestamp, TrendAnalyzer};
+use crate::data::currency_converter::CurrencyConverter;
+use crate::errors::Result;
+use crate::models::{CleanedItem, ModKind};
+use super::database::{Storage, ITEM_PAGE_SIZE};
+
+// What a prune removed and kept
+#[derive(Debug, Clone, Serialize)]
+pub struct PruneSummary {
+    pub cutoff: String,
+    pub listings_deleted: u64,
+    pub aggregates_added: u64,
+    pub vacuumed: bool,
+}
+
+// How a prune treats old listings
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    // Listings not seen for this long are deleted
+    pub older_than: Duration,
+    // Roll the deleted listings up into daily per-modifier and per-base price history first
+    pub keep_aggregates: bool,
+    // Prices are converted before they are aggregated, as `trends record` does
+    pub converter: Option<CurrencyConverter>,
+    pub vacuum: bool,
+}
+
+// Delete listings not seen within the policy's age, optionally keeping their prices as
+// daily aggregates. Each listing counts towards the day it was last seen, which is when
+// its stored price was current. Days that already have price history keep it.
+pub async fn prune(storage: &impl Storage, policy: RetentionPolicy) -> Result<PruneSummary> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let cutoff = format_timestamp(now.saturating_sub(policy.older_than).as_secs());
+
+    let aggregates_added = if policy.keep_aggregates {
+        roll_up(storage, &cutoff, policy.converter).await?
+    } else {
+        0
+    };
+
+    let listings_deleted = storage.delete_listings_seen_before(&cutoff).await?;
+    let vacuumed = policy.vacuum && listings_deleted > 0;
+    if vacuumed {
+        debug!("vacuuming database");
+        storage.vacuum().await?;
+    }
+
+    info!(%cutoff, listings_deleted, aggregates_added, vacuumed, "pruned old listings");
+    Ok(PruneSummary { cutoff, listings_deleted, aggregates_added, vacuumed })
+}
+
+async fn roll_up(storage: &impl Storage, cutoff: &str, converter: Option<CurrencyConverter>) -> Result<u64> {
+    let mut days: BTreeMap<String, TrendAnalyzer> = BTreeMap::new();
+    let mut after_id = 0;
+    loop {
+        let page = storage.items_after(after_id, ITEM_PAGE_SIZE, Some(cutoff)).await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        after_id = last.id;
+
+        for stored in &page {
+            let day = stored.last_seen_at.get(..10).unwrap_or(&stored.last_seen_at);
+            let trends = days.entry(day.to_string()).or_insert_with(|| {
+                let mut trends = TrendAnalyzer::new(day);
+                trends.set_mod_kinds(vec![ModKind::Explicit, ModKind::Crafted]);
+                if let Some(converter) = &converter {
+                    trends.set_currency_converter(converter.clone());
+                }
+                trends
+            });
+            trends.process_cleaned_item(&CleanedItem::from_item(&stored.item));
+        }
+    }
+
+    let aggregates: Vec<_> = days.values().flat_map(TrendAnalyzer::aggregates).collect();
+    storage.add_price_aggregates(&aggregates).await
+}
@@ -0,0 +1,1595 @@
+use std::str::FromStr;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::migrate::{AppliedMigration, Migrate, MigrateDatabase, Migrator};
+use sqlx::{Transaction, Sqlite};
+use crate::models::{Currency, Item, ItemCategory, ItemModifier, ItemBaseType, ItemResponse, ItemType};
+use crate::analyzer::{DailyAggregate, TrendSubject};
+use crate::errors::{Result, ScraperError};
+use tracing::{debug, info, trace};
+use super::pool::{JournalMode, PoolSettings, Synchronous};
+use super::aggregates::ModifierPriceBucket;
+use super::database::{fold_item_rows, rolls_json, CollectedItemRecord, ContactColumns, ItemModifierRecord, ItemRow, ListingTimes, ModifierRecord, ProbableSale, Storage, StoredItem, StoredListing};
+
+pub struct SqliteStorage {
+    pool: SqlitePool,
+}
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+impl SqliteStorage {
+    // Open the database and bring the schema up to date
+    pub async fn connect(database_url: &str, settings: &PoolSettings) -> Result<Self> {
+        let storage = Self::open(database_url, settings).await?;
+        info!("running database migrations");
+        storage.run_migrations().await?;
+        Ok(storage)
+    }
+
+    // Open the database, creating the file if needed, without touching its schema
+    pub async fn open(database_url: &str, settings: &PoolSettings) -> Result<Self> {
+        if !sqlx::Sqlite::database_exists(database_url).await? {
+            info!(%database_url, "creating new database");
+            sqlx::Sqlite::create_database(database_url).await?;
+        }
+
+        let journal_mode = match settings.journal_mode {
+            JournalMode::Delete => SqliteJournalMode::Delete,
+            JournalMode::Truncate => SqliteJournalMode::Truncate,
+            JournalMode::Wal => SqliteJournalMode::Wal,
+        };
+        let synchronous = match settings.synchronous {
+            Synchronous::Off => SqliteSynchronous::Off,
+            Synchronous::Normal => SqliteSynchronous::Normal,
+            Synchronous::Full => SqliteSynchronous::Full,
+        };
+        let options = SqliteConnectOptions::from_str(database_url)?
+            .journal_mode(journal_mode)
+            .synchronous(synchronous)
+            .busy_timeout(settings.busy_timeout);
+        debug!(?settings, "opening connection pool");
+        let pool = SqlitePoolOptions::new()
+            .max_connections(settings.max_connections)
+            .connect_with(options)
+            .await?;
+        Ok(Self { pool })
+    }
+
+    pub(crate) fn migrator(&self) -> &'static Migrator {
+        &MIGRATOR
+    }
+
+    pub(crate) async fn applied_migrations(&self) -> Result<Vec<AppliedMigration>> {
+        let mut conn = self.pool.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        Ok(conn.list_applied_migrations().await?)
+    }
+
+    pub(crate) async fn run_migrations(&self) -> Result<()> {
+        Ok(MIGRATOR.run(&self.pool).await?)
+    }
+
+    // Revert applied migrations newer than `target`, newest first
+    pub(crate) async fn revert_migrations(&self, target: i64) -> Result<()> {
+        Ok(MIGRATOR.undo(&self.pool, target).await?)
+    }
+
+    // The modifier's row, found by text and stat hashes. A modifier without hashes matches
+    // any row with its text; one with hashes claims a row stored before hashes were known.
+    async fn ensure_modifier(&self, modifier: &ItemModifier, tx: &mut Transaction<'_, Sqlite>) -> Result<i64> {
+        let stat_key = modifier.stat_key();
+        let existing_row = sqlx::query!(
+            r#"
+            SELECT id AS "id!", stat_key FROM modifiers
+            WHERE name = ? AND (stat_key = ? OR stat_key = '' OR ? = '')
+            ORDER BY stat_key = ? DESC, stat_key = '' DESC, id
+            LIMIT 1
+            "#,
+            modifier.name,
+            stat_key,
+            stat_key,
+            stat_key
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        let id = match existing_row {
+            Some(row) if row.stat_key == stat_key || stat_key.is_empty() => return Ok(row.id),
+            Some(row) => {
+                sqlx::query!("UPDATE modifiers SET stat_key = ? WHERE id = ?", stat_key, row.id)
+                    .execute(&mut **tx)
+                    .await?;
+                row.id
+            }
+            None => {
+                // Prepare all data before using in query
+                let values_json = serde_json::to_string(&modifier.values)?;
+                let stat_requirements_json = modifier.stat_requirements
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?;
+                let attribute_scaling_json = modifier.attribute_scaling
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?;
+                let tier = modifier.tier.map(|t| t as i64);
+
+                let result = sqlx::query!(
+                    r#"
+                    INSERT INTO modifiers (
+                        name, stat_key, tier, modifier_values,
+                        is_crafted, stat_requirements,
+                        attribute_scaling, created_at
+                    ) VALUES (?, ?, ?, ?, ?, ?, ?, datetime('now'))
+                    "#,
+                    modifier.name,
+                    stat_key,
+                    tier,
+                    values_json,
+                    modifier.is_crafted,
+                    stat_requirements_json,
+                    attribute_scaling_json
+                )
+                .execute(&mut **tx)
+                .await?;
+
+                result.last_insert_rowid()
+            }
+        };
+
+        for hash in &modifier.stat_hashes {
+            sqlx::query!(
+                "INSERT INTO modifier_stat_hashes (modifier_id, stat_hash) VALUES (?, ?) ON CONFLICT DO NOTHING",
+                id,
+                hash
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Ok(id)
+    }
+}
+
+impl SqliteStorage {
+    async fn upsert_base_item(&self, base_item: &ItemBaseType, tx: &mut Transaction<'_, Sqlite>) -> Result<i64> {
+        // First check if base item exists
+        let existing_row = sqlx::query!(
+            "SELECT id FROM base_items WHERE name = ?",
+            base_item.name
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        // Prepare all our data before using it in queries
+        let stat_requirements_json = serde_json::to_string(&base_item.stat_requirements)?;
+        let implicit_mods_json = serde_json::to_string(&base_item.implicit_modifiers)?;
+        let tags_json = serde_json::to_string(&base_item.tags)?;
+        let category_str = base_item.category.to_string();
+        let class_str = base_item.class.map(|class| class.as_str());
+        let base_level = base_item.base_level as i64;
+
+        // Handle existing or insert new base item
+        let id = if let Some(row) = existing_row {
+            // Update existing base item
+            sqlx::query!(
+                r#"
+                UPDATE base_items SET
+                    category = ?,
+                    item_class = COALESCE(?, item_class),
+                    stat_requirements = ?,
+                    implicit_modifiers = ?,
+                    base_level = ?,
+                    tags = ?,
+                    updated_at = datetime('now')
+                WHERE id = ?
+                "#,
+                category_str,
+                class_str,
+                stat_requirements_json,
+                implicit_mods_json,
+                base_level,
+                tags_json,
+                row.id
+            )
+            .execute(&mut **tx)
+            .await?;
+            
+            row.id.expect("Database returned null ID")
+        } else {
+            // Insert new base item
+            let result = sqlx::query!(
+                r#"
+                INSERT INTO base_items (
+                    name, category, item_class, stat_requirements,
+                    implicit_modifiers, base_level, tags,
+                    created_at, updated_at
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
+                "#,
+                base_item.name,
+                category_str,
+                class_str,
+                stat_requirements_json,
+                implicit_mods_json,
+                base_level,
+                tags_json
+            )
+            .execute(&mut **tx)
+            .await?;
+            
+            result.last_insert_rowid()
+        };
+
+        Ok(id)
+    }
+
+    // Row ID of a listing's stored base type. A base the listing was classified beyond
+    // Other gets its category and class filled in where the stored base doesn't know them,
+    // or is added when the base list doesn't hold it, as it often doesn't for gems. Other
+    // listings are only stored against known bases, so a missing one fails the listing's
+    // transaction.
+    async fn base_item_id(&self, item_type: &ItemType, tx: &mut Transaction<'_, Sqlite>) -> Result<i64> {
+        let base_type = &item_type.base_type;
+        let id = sqlx::query!("SELECT id FROM base_items WHERE name = ?", base_type)
+            .fetch_optional(&mut **tx)
+            .await?
+            .and_then(|row| row.id);
+        if item_type.category == ItemCategory::Other {
+            return id.ok_or_else(|| ScraperError::DatabaseError(format!("Base item not found: {}", base_type)));
+        }
+
+        let Some(id) = id else {
+            let mut base = ItemBaseType::new(base_type.clone(), item_type.category);
+            base.class = item_type.class;
+            return self.upsert_base_item(&base, tx).await;
+        };
+        let category_str = item_type.category.to_string();
+        let class_str = item_type.class.map(|class| class.as_str());
+        sqlx::query!(
+            r#"
+            UPDATE base_items SET
+                category = CASE WHEN category = 'Other' THEN ? ELSE category END,
+                item_class = COALESCE(item_class, ?)
+            WHERE id = ?
+            "#,
+            category_str,
+            class_str,
+            id
+        )
+        .execute(&mut **tx)
+        .await?;
+        Ok(id)
+    }
+
+    async fn replace_modifiers(&self, item_id: i64, modifiers: &[ItemModifier], tx: &mut Transaction<'_, Sqlite>) -> Result<()> {
+        sqlx::query!("DELETE FROM item_modifiers WHERE item_id = ?", item_id)
+            .execute(&mut **tx)
+            .await?;
+
+        for modifier in modifiers {
+            let modifier_id = self.ensure_modifier(modifier, tx).await?;
+            let values_json = serde_json::to_string(&modifier.values)?;
+            let rolls_json = rolls_json(modifier)?;
+            
+            sqlx::query!(
+                r#"
+                INSERT INTO item_modifiers (
+                    item_id, modifier_id, modifier_values, modifier_rolls
+                ) VALUES (?, ?, ?, ?)
+                "#,
+                item_id,
+                modifier_id,
+                values_json,
+                rolls_json
+            )
+            .execute(&mut **tx)
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    async fn store_base_item(&self, base_item: &ItemBaseType) -> Result<i64> {
+        let mut tx = self.pool.begin().await?;
+        let id = self.upsert_base_item(base_item, &mut tx).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn store_collected_item(&self, item: &Item, raw: Option<&ItemResponse>) -> Result<i64> {
+        debug!(id = %item.id, name = item.name.as_deref().unwrap_or("unnamed"), "storing item");
+            
+        let mut tx = self.pool.begin().await?;
+        
+        // The base lookup, listing, modifiers and their links all share this one transaction
+        let base_item_id = self.base_item_id(&item.item_type, &mut tx).await?;
+        
+        // Prepare all our JSON strings and values before the query
+        let stats_json = serde_json::to_string(&item.stats)?;
+        let stat_requirements_json = serde_json::to_string(&item.stat_requirements)?;
+        let attribute_values_json = serde_json::to_string(&item.attribute_values)?;
+        let sockets_json = item.sockets.as_ref().map(serde_json::to_string).transpose()?;
+        let quality = item.quality.map(|quality| quality as i64);
+        let contact = ContactColumns::of(item);
+        let rarity = item.item_type.rarity.as_str();
+        
+        // Extract price information into owned values that will live long enough
+        let price_amount = item.price.as_ref().map(|p| p.amount);
+        let price_currency = item.price.as_ref().map(|p| p.currency.to_string());
+        let price_normalized = item.price.as_ref().and_then(|p| p.normalized_amount);
+        let raw_json = raw.map(serde_json::to_string).transpose()?;
+        
+        // Insert the listing, or refresh it when its trade ID was already collected
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO collected_items (
+                trade_id, base_item_id, name,
+                price_amount, price_currency, price_normalized,
+                stats, corrupted, mirrored, quality, league, stat_requirements,
+                attribute_values, sockets, whisper, stash_name, stash_x, stash_y, indexed_at,
+                raw_json, rarity, collected_at, last_seen_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'), datetime('now'))
+            ON CONFLICT (trade_id) DO UPDATE SET
+                name = excluded.name,
+                price_amount = excluded.price_amount,
+                price_currency = excluded.price_currency,
+                price_normalized = excluded.price_normalized,
+                stats = excluded.stats,
+                corrupted = excluded.corrupted,
+                mirrored = excluded.mirrored,
+                quality = excluded.quality,
+                league = COALESCE(excluded.league, league),
+                stat_requirements = excluded.stat_requirements,
+                attribute_values = excluded.attribute_values,
+                sockets = excluded.sockets,
+                -- A sighting without seller details keeps the ones already stored
+                whisper = COALESCE(excluded.whisper, whisper),
+                stash_name = COALESCE(excluded.stash_name, stash_name),
+                stash_x = COALESCE(excluded.stash_x, stash_x),
+                stash_y = COALESCE(excluded.stash_y, stash_y),
+                indexed_at = COALESCE(excluded.indexed_at, indexed_at),
+                raw_json = COALESCE(excluded.raw_json, raw_json),
+                rarity = excluded.rarity,
+                last_seen_at = excluded.last_seen_at,
+                delisted_at = NULL
+            RETURNING id AS "id!"
+            "#,
+            item.id,
+            base_item_id,
+            item.name,
+            price_amount,
+            price_currency,
+            price_normalized,
+            stats_json,
+            item.corrupted,
+            item.mirrored,
+            quality,
+            item.league,
+            stat_requirements_json,
+            attribute_values_json,
+            sockets_json,
+            contact.whisper,
+            contact.stash_name,
+            contact.stash_x,
+            contact.stash_y,
+            contact.indexed_at,
+            raw_json,
+            rarity
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        
+        let item_id = row.id;
+        trace!(item_id, "upserted collected item");
+
+        // A listing seen again may have been re-rolled, so its modifiers are replaced
+        self.replace_modifiers(item_id, &item.modifiers, &mut tx).await?;
+        
+        tx.commit().await?;
+        
+        Ok(item_id)
+    }
+
+    async fn import_collected_item(&self, item: &Item, raw: Option<&ItemResponse>, times: &ListingTimes) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let raw_json = raw.map(serde_json::to_string).transpose()?;
+        let newer_stored = sqlx::query!(
+            "SELECT id AS \"id!\" FROM collected_items WHERE trade_id = ? AND last_seen_at >= ?",
+            item.id,
+            times.last_seen_at
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(row) = newer_stored {
+            sqlx::query!(
+                r#"
+                UPDATE collected_items SET
+                    collected_at = min(collected_at, ?),
+                    raw_json = COALESCE(raw_json, ?)
+                WHERE id = ?
+                "#,
+                times.collected_at,
+                raw_json,
+                row.id
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+            return Ok(false);
+        }
+
+        let base_item_id = self.base_item_id(&item.item_type, &mut tx).await?;
+
+        let stats_json = serde_json::to_string(&item.stats)?;
+        let stat_requirements_json = serde_json::to_string(&item.stat_requirements)?;
+        let attribute_values_json = serde_json::to_string(&item.attribute_values)?;
+        let sockets_json = item.sockets.as_ref().map(serde_json::to_string).transpose()?;
+        let quality = item.quality.map(|quality| quality as i64);
+        let contact = ContactColumns::of(item);
+        let rarity = item.item_type.rarity.as_str();
+        let price_amount = item.price.as_ref().map(|p| p.amount);
+        let price_currency = item.price.as_ref().map(|p| p.currency.to_string());
+        let price_normalized = item.price.as_ref().and_then(|p| p.normalized_amount);
+
+        let row = sqlx::query!(
+            r#"
+            INSERT INTO collected_items (
+                trade_id, base_item_id, name,
+                price_amount, price_currency, price_normalized,
+                stats, corrupted, mirrored, quality, league, stat_requirements,
+                attribute_values, sockets, whisper, stash_name, stash_x, stash_y, indexed_at,
+                raw_json, rarity, collected_at, last_seen_at, delisted_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (trade_id) DO UPDATE SET
+                name = excluded.name,
+                price_amount = excluded.price_amount,
+                price_currency = excluded.price_currency,
+                price_normalized = excluded.price_normalized,
+                stats = excluded.stats,
+                corrupted = excluded.corrupted,
+                mirrored = excluded.mirrored,
+                quality = excluded.quality,
+                league = COALESCE(excluded.league, league),
+                stat_requirements = excluded.stat_requirements,
+                attribute_values = excluded.attribute_values,
+                sockets = excluded.sockets,
+                -- A sighting without seller details keeps the ones already stored
+                whisper = COALESCE(excluded.whisper, whisper),
+                stash_name = COALESCE(excluded.stash_name, stash_name),
+                stash_x = COALESCE(excluded.stash_x, stash_x),
+                stash_y = COALESCE(excluded.stash_y, stash_y),
+                indexed_at = COALESCE(excluded.indexed_at, indexed_at),
+                raw_json = COALESCE(excluded.raw_json, raw_json),
+                rarity = excluded.rarity,
+                collected_at = min(collected_at, excluded.collected_at),
+                last_seen_at = excluded.last_seen_at,
+                delisted_at = excluded.delisted_at
+            RETURNING id AS "id!"
+            "#,
+            item.id,
+            base_item_id,
+            item.name,
+            price_amount,
+            price_currency,
+            price_normalized,
+            stats_json,
+            item.corrupted,
+            item.mirrored,
+            quality,
+            item.league,
+            stat_requirements_json,
+            attribute_values_json,
+            sockets_json,
+            contact.whisper,
+            contact.stash_name,
+            contact.stash_x,
+            contact.stash_y,
+            contact.indexed_at,
+            raw_json,
+            rarity,
+            times.collected_at,
+            times.last_seen_at,
+            times.delisted_at
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        self.replace_modifiers(row.id, &item.modifiers, &mut tx).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn update_collected_item(&self, item: &Item) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        let stats_json = serde_json::to_string(&item.stats)?;
+        let stat_requirements_json = serde_json::to_string(&item.stat_requirements)?;
+        let attribute_values_json = serde_json::to_string(&item.attribute_values)?;
+        let sockets_json = item.sockets.as_ref().map(serde_json::to_string).transpose()?;
+        let quality = item.quality.map(|quality| quality as i64);
+        let contact = ContactColumns::of(item);
+        let rarity = item.item_type.rarity.as_str();
+        let price_amount = item.price.as_ref().map(|p| p.amount);
+        let price_currency = item.price.as_ref().map(|p| p.currency.to_string());
+        let price_normalized = item.price.as_ref().and_then(|p| p.normalized_amount);
+
+        let row = sqlx::query!(
+            r#"
+            UPDATE collected_items SET
+                name = ?,
+                price_amount = ?,
+                price_currency = ?,
+                price_normalized = ?,
+                stats = ?,
+                corrupted = ?,
+                mirrored = ?,
+                quality = ?,
+                league = COALESCE(?, league),
+                stat_requirements = ?,
+                attribute_values = ?,
+                sockets = ?,
+                whisper = ?,
+                stash_name = ?,
+                stash_x = ?,
+                stash_y = ?,
+                indexed_at = ?,
+                rarity = ?
+            WHERE trade_id = ?
+            RETURNING id AS "id!"
+            "#,
+            item.name,
+            price_amount,
+            price_currency,
+            price_normalized,
+            stats_json,
+            item.corrupted,
+            item.mirrored,
+            quality,
+            item.league,
+            stat_requirements_json,
+            attribute_values_json,
+            sockets_json,
+            contact.whisper,
+            contact.stash_name,
+            contact.stash_x,
+            contact.stash_y,
+            contact.indexed_at,
+            rarity,
+            item.id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(false);
+        };
+        self.replace_modifiers(row.id, &item.modifiers, &mut tx).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn raw_listings_after(&self, after_id: i64, limit: u32) -> Result<Vec<(i64, String)>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id AS "id!", raw_json AS "raw_json!"
+            FROM collected_items
+            WHERE id > ? AND raw_json IS NOT NULL
+            ORDER BY id
+            LIMIT ?
+            "#,
+            after_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|row| (row.id, row.raw_json)).collect())
+    }
+
+    async fn begin_collection_run(&self) -> Result<i64> {
+        let result = sqlx::query!("INSERT INTO collection_runs (started_at) VALUES (datetime('now'))")
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    async fn mark_delisted(&self, missed_runs: u32) -> Result<u64> {
+        if missed_runs == 0 {
+            return Ok(0);
+        }
+
+        // Seen before the oldest of the missed runs started
+        let offset = missed_runs as i64 - 1;
+        let result = sqlx::query!(
+            r#"
+            UPDATE collected_items SET delisted_at = datetime('now')
+            WHERE delisted_at IS NULL AND last_seen_at < (
+                SELECT started_at FROM collection_runs ORDER BY id DESC LIMIT 1 OFFSET ?
+            )
+            "#,
+            offset
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn mark_seen(&self, trade_ids: &[String]) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let mut updated = 0;
+        for trade_id in trade_ids {
+            let result = sqlx::query!(
+                "UPDATE collected_items SET last_seen_at = datetime('now'), delisted_at = NULL WHERE trade_id = ?",
+                trade_id
+            )
+            .execute(&mut *tx)
+            .await?;
+            updated += result.rows_affected();
+        }
+        tx.commit().await?;
+
+        Ok(updated)
+    }
+
+    async fn probable_sales(&self, within_hours: f64, base_type: Option<&str>) -> Result<Vec<ProbableSale>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT c.trade_id, b.name AS base_type, c.name, c.price_amount,
+                   c.price_currency, c.price_normalized, c.collected_at, c.last_seen_at,
+                   c.delisted_at AS "delisted_at!",
+                   (unixepoch(c.last_seen_at) - unixepoch(c.collected_at)) / 3600.0 AS "hours_listed!: f64"
+            FROM collected_items c
+            JOIN base_items b ON b.id = c.base_item_id
+            WHERE c.delisted_at IS NOT NULL
+              AND (unixepoch(c.last_seen_at) - unixepoch(c.collected_at)) / 3600.0 <= ?
+              AND (? IS NULL OR b.name = ?)
+            ORDER BY c.delisted_at DESC, c.id
+            "#,
+            within_hours,
+            base_type,
+            base_type
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter()
+            .map(|row| ProbableSale {
+                trade_id: row.trade_id,
+                base_type: row.base_type,
+                name: row.name,
+                price_amount: row.price_amount,
+                price_currency: row.price_currency.map(Currency::from),
+                price_normalized: row.price_normalized,
+                first_seen_at: row.collected_at,
+                last_seen_at: row.last_seen_at,
+                delisted_at: row.delisted_at,
+                hours_listed: row.hours_listed,
+            })
+            .collect())
+    }
+
+    async fn items_after(&self, after_id: i64, limit: u32, last_seen_before: Option<&str>) -> Result<Vec<StoredItem>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT c.id AS "id!", c.trade_id AS "trade_id!", b.name AS base_type, b.category, b.item_class,
+                   c.name, c.rarity, c.price_amount, c.price_currency, c.price_normalized,
+                   c.stats AS "stats!", c.corrupted AS "corrupted!: bool",
+                   c.mirrored AS "mirrored!: bool", c.quality, c.league,
+                   c.stat_requirements AS "stat_requirements!", c.attribute_values AS "attribute_values!",
+                   c.sockets, c.whisper, c.stash_name, c.stash_x, c.stash_y, c.indexed_at,
+                   c.collected_at AS "collected_at!", c.last_seen_at AS "last_seen_at!",
+                   m.name AS "modifier?", m.stat_key AS "stat_key?", m.tier AS "tier?",
+                   m.is_crafted AS "is_crafted?: bool",
+                   im.modifier_values AS "modifier_values?", im.modifier_rolls AS "modifier_rolls?"
+            FROM (
+                SELECT * FROM collected_items
+                WHERE id > ? AND (? IS NULL OR last_seen_at < ?)
+                ORDER BY id LIMIT ?
+            ) c
+            JOIN base_items b ON b.id = c.base_item_id
+            LEFT JOIN item_modifiers im ON im.item_id = c.id
+            LEFT JOIN modifiers m ON m.id = im.modifier_id
+            ORDER BY c.id
+            "#,
+            after_id,
+            last_seen_before,
+            last_seen_before,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        fold_item_rows(rows.into_iter()
+            .map(|row| ItemRow {
+                id: row.id,
+                trade_id: row.trade_id,
+                base_type: row.base_type,
+                category: row.category,
+                item_class: row.item_class,
+                name: row.name,
+                rarity: row.rarity,
+                price_amount: row.price_amount,
+                price_currency: row.price_currency,
+                price_normalized: row.price_normalized,
+                stats: row.stats,
+                corrupted: row.corrupted,
+                mirrored: row.mirrored,
+                quality: row.quality,
+                league: row.league,
+                stat_requirements: row.stat_requirements,
+                attribute_values: row.attribute_values,
+                sockets: row.sockets,
+                whisper: row.whisper,
+                stash_name: row.stash_name,
+                stash_x: row.stash_x,
+                stash_y: row.stash_y,
+                indexed_at: row.indexed_at,
+                collected_at: row.collected_at,
+                last_seen_at: row.last_seen_at,
+                modifier: row.modifier,
+                stat_key: row.stat_key,
+                tier: row.tier,
+                is_crafted: row.is_crafted,
+                modifier_values: row.modifier_values,
+                modifier_rolls: row.modifier_rolls,
+            })
+            .collect())
+    }
+
+    async fn delete_listings_seen_before(&self, cutoff: &str) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            r#"
+            DELETE FROM item_modifiers WHERE item_id IN (
+                SELECT id FROM collected_items WHERE last_seen_at < ?
+            )
+            "#,
+            cutoff
+        )
+        .execute(&mut *tx)
+        .await?;
+        let deleted = sqlx::query!("DELETE FROM collected_items WHERE last_seen_at < ?", cutoff)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        sqlx::query!("DELETE FROM modifier_stat_hashes WHERE modifier_id NOT IN (SELECT modifier_id FROM item_modifiers)")
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM modifiers WHERE id NOT IN (SELECT modifier_id FROM item_modifiers)")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
+    async fn add_price_aggregates(&self, aggregates: &[DailyAggregate]) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut added = 0;
+        for aggregate in aggregates {
+            let kind = aggregate.kind.as_str();
+            added += sqlx::query!(
+                r#"
+                INSERT INTO price_history (day, subject_kind, subject, median, p25, p75, volume, listings)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (day, subject_kind, subject) DO NOTHING
+                "#,
+                aggregate.day,
+                kind,
+                aggregate.subject,
+                aggregate.median,
+                aggregate.p25,
+                aggregate.p75,
+                aggregate.volume,
+                aggregate.listings
+            )
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        tx.commit().await?;
+        Ok(added)
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        sqlx::query!("VACUUM").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn collected_items(&self) -> Result<Vec<CollectedItemRecord>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT c.trade_id, b.name AS base_type, c.name, c.price_amount,
+                   c.price_currency, c.price_normalized, c.corrupted, c.league, c.stats, c.collected_at,
+                   c.last_seen_at, c.delisted_at, c.raw_json
+            FROM collected_items c
+            JOIN base_items b ON b.id = c.base_item_id
+            ORDER BY c.collected_at, c.id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(CollectedItemRecord {
+                trade_id: row.trade_id,
+                base_type: row.base_type,
+                name: row.name,
+                price_amount: row.price_amount,
+                price_currency: row.price_currency.map(Currency::from),
+                price_normalized: row.price_normalized,
+                corrupted: row.corrupted,
+                league: row.league,
+                stats: serde_json::from_str(&row.stats)?,
+                collected_at: row.collected_at,
+                last_seen_at: row.last_seen_at,
+                delisted_at: row.delisted_at,
+                raw_json: row.raw_json,
+            }))
+            .collect()
+    }
+
+    async fn modifier_records(&self) -> Result<Vec<ModifierRecord>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id AS "id!", name, stat_key, tier, modifier_values, is_crafted, created_at
+            FROM modifiers
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(ModifierRecord {
+                id: row.id,
+                name: row.name,
+                stat_key: row.stat_key,
+                tier: row.tier,
+                modifier_values: serde_json::from_str(&row.modifier_values)?,
+                is_crafted: row.is_crafted,
+                created_at: row.created_at,
+            }))
+            .collect()
+    }
+
+    async fn item_modifier_records(&self) -> Result<Vec<ItemModifierRecord>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT c.trade_id, im.modifier_id, im.modifier_values, c.collected_at
+            FROM item_modifiers im
+            JOIN collected_items c ON c.id = im.item_id
+            ORDER BY c.id, im.modifier_id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(ItemModifierRecord {
+                trade_id: row.trade_id,
+                modifier_id: row.modifier_id,
+                modifier_values: serde_json::from_str(&row.modifier_values)?,
+                collected_at: row.collected_at,
+            }))
+            .collect()
+    }
+
+    async fn base_item_exists(&self, name: &str) -> Result<bool> {
+        let result = sqlx::query!(
+            "SELECT COUNT(*) as count FROM base_items WHERE name = ?",
+            name
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result.count > 0)
+    }
+
+    async fn store_price_aggregates(&self, aggregates: &[DailyAggregate]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for aggregate in aggregates {
+            let kind = aggregate.kind.as_str();
+            sqlx::query!(
+                r#"
+                INSERT INTO price_history (day, subject_kind, subject, median, p25, p75, volume, listings)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (day, subject_kind, subject) DO UPDATE SET
+                    median = excluded.median,
+                    p25 = excluded.p25,
+                    p75 = excluded.p75,
+                    volume = excluded.volume,
+                    listings = excluded.listings
+                "#,
+                aggregate.day,
+                kind,
+                aggregate.subject,
+                aggregate.median,
+                aggregate.p25,
+                aggregate.p75,
+                aggregate.volume,
+                aggregate.listings
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn price_history(&self, kind: TrendSubject, subject: &str) -> Result<Vec<DailyAggregate>> {
+        let kind_name = kind.as_str();
+        let rows = sqlx::query!(
+            r#"
+            SELECT day, subject_kind, subject, median, p25, p75, volume, listings
+            FROM price_history
+            WHERE subject_kind = ? AND subject = ?
+            ORDER BY day
+            "#,
+            kind_name,
+            subject
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(DailyAggregate {
+                day: row.day,
+                kind: TrendSubject::parse(&row.subject_kind)?,
+                subject: row.subject,
+                median: row.median,
+                p25: row.p25,
+                p75: row.p75,
+                volume: row.volume as u32,
+                listings: row.listings as u32,
+            }))
+            .collect()
+    }
+
+    async fn replace_modifier_price_buckets(&self, buckets: &[ModifierPriceBucket]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!("DELETE FROM modifier_price_buckets")
+            .execute(&mut *tx)
+            .await?;
+
+        for bucket in buckets {
+            sqlx::query!(
+                r#"
+                INSERT INTO modifier_price_buckets (
+                    modifier, day, bucket, value_min, value_max, listings, median, p25, p75, refreshed_at
+                )
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, datetime('now'))
+                "#,
+                bucket.modifier,
+                bucket.day,
+                bucket.bucket,
+                bucket.value_min,
+                bucket.value_max,
+                bucket.listings,
+                bucket.median,
+                bucket.p25,
+                bucket.p75
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn modifier_price_buckets(&self, modifier: &str) -> Result<Vec<ModifierPriceBucket>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT modifier, day, bucket, value_min, value_max, listings, median, p25, p75
+            FROM modifier_price_buckets
+            WHERE modifier = ?
+            ORDER BY day, bucket
+            "#,
+            modifier
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter()
+            .map(|row| ModifierPriceBucket {
+                modifier: row.modifier,
+                day: row.day,
+                bucket: row.bucket as u32,
+                value_min: row.value_min,
+                value_max: row.value_max,
+                listings: row.listings as u32,
+                median: row.median,
+                p25: row.p25,
+                p75: row.p75,
+            })
+            .collect())
+    }
+
+    async fn base_category(&self, base_type: &str) -> Result<Option<String>> {
+        let row = sqlx::query!(
+            "SELECT category FROM base_items WHERE name = ?",
+            base_type
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.category))
+    }
+
+    async fn listings_in_category(&self, category: &str) -> Result<Vec<StoredListing>> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT c.id, c.trade_id, b.name AS base_type, c.name, c.price_amount,
+                   c.price_currency, c.price_normalized,
+                   m.name AS "modifier?", im.modifier_values AS "modifier_values?"
+            FROM collected_items c
+            JOIN base_items b ON b.id = c.base_item_id
+            LEFT JOIN item_modifiers im ON im.item_id = c.id
+            LEFT JOIN modifiers m ON m.id = im.modifier_id
+            WHERE b.category = ?
+            ORDER BY c.id
+            "#,
+            category
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        // One row per modifier, so fold consecutive rows of the same listing together
+        let mut listings: Vec<StoredListing> = Vec::new();
+        let mut current_id = None;
+        for row in rows {
+            if current_id != Some(row.id) {
+                current_id = Some(row.id);
+                listings.push(StoredListing {
+                    trade_id: row.trade_id,
+                    base_type: row.base_type,
+                    name: row.name,
+                    price_amount: row.price_amount,
+                    price_currency: row.price_currency.map(Currency::from),
+                    price_normalized: row.price_normalized,
+                    modifiers: Vec::new(),
+                });
+            }
+            if let (Some(modifier), Some(values), Some(listing)) =
+                (row.modifier, row.modifier_values, listings.last_mut())
+            {
+                listing.modifiers.push((modifier, serde_json::from_str(&values)?));
+            }
+        }
+
+        Ok(listings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{ring, ListingBuilder};
+    use crate::models::{ItemCategory, ItemClass, ItemModifier, ItemRarity, ItemType, ModKind, ModifierRoll};
+
+    async fn test_storage(name: &str) -> (SqliteStorage, std::path::PathBuf) {
+        let path = std::env::temp_dir().join(format!("poe2-{}-test-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        // Rollback journal so removing the file cleans up after the test, unlike WAL's -wal and -shm files
+        let settings = PoolSettings { journal_mode: JournalMode::Delete, ..PoolSettings::default() };
+        let storage = SqliteStorage::connect(&format!("sqlite:{}", path.display()), &settings).await.unwrap();
+        storage.store_base_item(&ItemBaseType::new("Iron Ring".to_string(), ItemCategory::Accessory)).await.unwrap();
+        (storage, path)
+    }
+
+    #[tokio::test]
+    async fn test_repeat_listing_updates_row() {
+        let (storage, path) = test_storage("upsert").await;
+        let first_id = storage.store_collected_item(&ring("listing1", 5.0), None).await.unwrap();
+        let second_id = storage.store_collected_item(&ring("listing1", 3.0), None).await.unwrap();
+
+        let records = storage.collected_items().await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(first_id, second_id);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].price_amount, Some(3.0));
+        assert!(!records[0].last_seen_at.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missed_listings_become_sales() {
+        let (storage, path) = test_storage("lifecycle").await;
+        storage.store_collected_item(&ring("sold", 5.0), None).await.unwrap();
+        storage.store_collected_item(&ring("still_up", 8.0), None).await.unwrap();
+        for (trade_id, last_seen) in [("sold", "2025-01-01 02:00:00"), ("still_up", "2025-01-03 00:00:00")] {
+            sqlx::query!(
+                "UPDATE collected_items SET collected_at = '2025-01-01 00:00:00', last_seen_at = ? WHERE trade_id = ?",
+                last_seen,
+                trade_id
+            )
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+        }
+        let run = storage.begin_collection_run().await.unwrap();
+        sqlx::query!("UPDATE collection_runs SET started_at = '2025-01-02 00:00:00' WHERE id = ?", run)
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+
+        assert_eq!(storage.mark_delisted(2).await.unwrap(), 0);
+        assert_eq!(storage.mark_delisted(1).await.unwrap(), 1);
+        let sales = storage.probable_sales(24.0, Some("Iron Ring")).await.unwrap();
+        assert_eq!(sales.len(), 1);
+        assert_eq!((sales[0].trade_id.as_str(), sales[0].hours_listed), ("sold", 2.0));
+        assert!(storage.probable_sales(1.0, None).await.unwrap().is_empty());
+
+        // Seen again, so it wasn't sold after all
+        storage.store_collected_item(&ring("sold", 5.0), None).await.unwrap();
+        let sales = storage.probable_sales(24.0, None).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(sales.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_listing_marked_seen() {
+        let (storage, path) = test_storage("seen").await;
+        storage.store_collected_item(&ring("unchanged", 5.0), None).await.unwrap();
+        sqlx::query!("UPDATE collected_items SET last_seen_at = '2025-01-01 00:00:00'")
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+        let run = storage.begin_collection_run().await.unwrap();
+        sqlx::query!("UPDATE collection_runs SET started_at = '2025-01-02 00:00:00' WHERE id = ?", run)
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+
+        let seen = storage.mark_seen(&["unchanged".to_string(), "never_stored".to_string()]).await.unwrap();
+        let delisted = storage.mark_delisted(1).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!((seen, delisted), (1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_gem_stored_without_known_base() {
+        let (storage, path) = test_storage("gem").await;
+        let mut gem = Item::new(
+            "gem1".to_string(),
+            ItemType::new(ItemCategory::Gem, "Controlled Destruction".to_string(), ItemRarity::Normal),
+        );
+        gem.set_gem(crate::models::GemProperties { kind: crate::models::GemKind::Support, level: 20, quality: 13 });
+        storage.store_collected_item(&gem, None).await.unwrap();
+
+        let gems = storage.items_by_category(&ItemCategory::Gem).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(gems.len(), 1);
+        assert_eq!(gems[0].gem, gem.gem);
+    }
+
+    #[tokio::test]
+    async fn test_classified_listing_fills_in_its_base() {
+        let (storage, path) = test_storage("classified").await;
+        storage.store_base_item(&ItemBaseType::new("Rusted Greathelm".to_string(), ItemCategory::Other)).await.unwrap();
+        for (id, base_type) in [("helm", "Rusted Greathelm"), ("boots", "Leather Boots")] {
+            let mut item_type = ItemType::new(ItemCategory::Other, base_type.to_string(), ItemRarity::Rare);
+            item_type.classify(None, &[]);
+            storage.store_collected_item(&Item::new(id.to_string(), item_type), None).await.unwrap();
+        }
+
+        let armour = storage.items_by_category(&ItemCategory::Armour).await.unwrap();
+        let category = storage.base_category("Rusted Greathelm").await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        let classes: Vec<Option<ItemClass>> = armour.iter().map(|item| item.item_type.class).collect();
+        assert_eq!(classes, vec![Some(ItemClass::Helmet), Some(ItemClass::Boots)]);
+        assert_eq!(category.as_deref(), Some("Armour"));
+    }
+
+    #[tokio::test]
+    async fn test_sockets_and_rune_mods_stored() {
+        let (storage, path) = test_storage("sockets").await;
+        let mut item = ring("socketed", 3.0);
+        item.sockets = Some(crate::models::ItemSockets { count: 2, contents: vec!["Desert Rune".to_string()] });
+        item.corrupted = true;
+        item.mirrored = true;
+        item.quality = Some(20);
+        item.add_modifier(ItemModifier {
+            name: "+12% to Fire Resistance".to_string(),
+            tier: None,
+            values: vec![12.0],
+            rolls: vec![ModifierRoll { current: 12.0, min: 10.0, max: 15.0 }],
+            is_crafted: false,
+            kind: ModKind::Rune,
+            stat_requirements: None,
+            attribute_scaling: None,
+            stat_hashes: vec!["rune.stat_3372524247".to_string()],
+        });
+        storage.store_collected_item(&item, None).await.unwrap();
+
+        let stored = storage.items_by_category(&ItemCategory::Accessory).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(stored[0].sockets, item.sockets);
+        assert_eq!(stored[0].modifiers[0].kind, ModKind::Rune);
+        assert_eq!(stored[0].modifiers[0].roll_quality(), Some(40.0));
+        assert!(stored[0].corrupted && stored[0].mirrored);
+        assert_eq!(stored[0].quality, Some(20));
+    }
+
+    #[tokio::test]
+    async fn test_rarity_read_back() {
+        let (storage, path) = test_storage("rarity").await;
+        let mut unique = ring("unique", 50.0);
+        unique.item_type.rarity = ItemRarity::Unique;
+        unique.name = Some("Ming's Heart".to_string());
+        storage.store_collected_item(&unique, None).await.unwrap();
+        let stored = storage.items_after(0, 10, None).await.unwrap().remove(0).item;
+
+        // A later update replaces the stored rarity
+        unique.item_type.rarity = ItemRarity::Rare;
+        storage.update_collected_item(&unique).await.unwrap();
+        let updated = storage.items_after(0, 10, None).await.unwrap().remove(0).item;
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(stored.item_type.rarity, ItemRarity::Unique);
+        assert_eq!(updated.item_type.rarity, ItemRarity::Rare);
+    }
+
+    #[tokio::test]
+    async fn test_query_stored_items() {
+        let (storage, path) = test_storage("query").await;
+        for (id, life, price) in [("a", 40.0, 2.0), ("b", 90.0, 10.0), ("c", 95.0, 14.0)] {
+            let mut item = ring(id, price);
+            item.add_modifier(ItemModifier {
+                name: format!("+{} to maximum Life", life),
+                tier: Some(2),
+                values: vec![life],
+                rolls: Vec::new(),
+                is_crafted: false,
+                kind: Default::default(),
+                stat_requirements: None,
+                attribute_scaling: None,
+                stat_hashes: Vec::new(),
+            });
+            storage.store_collected_item(&item, None).await.unwrap();
+        }
+        storage.store_collected_item(&ring("bare", 1.0), None).await.unwrap();
+
+        let high_life = storage.items_with_modifier("+# to maximum Life", Some(90.0)).await.unwrap();
+        let distribution = storage.price_distribution("+# to maximum Life").await.unwrap().unwrap();
+        let rings = storage.items_by_category(&ItemCategory::Accessory).await.unwrap();
+        let mut pipeline_stats = crate::analyzer::ModifierAnalyzer::new(Vec::new());
+        let analyzed = crate::analyzer::AnalysisPipeline::new()
+            .with_analyzer(&mut pipeline_stats)
+            .run_storage(&storage)
+            .await
+            .unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let ids: Vec<&str> = high_life.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "c"]);
+        assert_eq!((distribution.listings, distribution.median, distribution.max), (3, 10.0, 14.0));
+        assert_eq!(rings.len(), 4);
+        assert_eq!(analyzed, 4);
+        assert_eq!(pipeline_stats.get_stats("+# to maximum Life").unwrap().total_occurrences, 3);
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_daily_aggregates() {
+        let (storage, path) = test_storage("prune").await;
+        for (id, price) in [("old1", 4.0), ("old2", 6.0), ("recent", 9.0)] {
+            let mut item = ring(id, price);
+            item.add_modifier(ItemModifier {
+                name: "+50 to maximum Life".to_string(),
+                tier: Some(1),
+                values: vec![50.0],
+                rolls: Vec::new(),
+                is_crafted: false,
+                kind: Default::default(),
+                stat_requirements: None,
+                attribute_scaling: None,
+                stat_hashes: Vec::new(),
+            });
+            storage.store_collected_item(&item, None).await.unwrap();
+        }
+        sqlx::query!("UPDATE collected_items SET last_seen_at = '2020-03-01 12:00:00' WHERE trade_id LIKE 'old%'")
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+
+        let policy = crate::storage::RetentionPolicy {
+            older_than: std::time::Duration::from_secs(30 * 24 * 60 * 60),
+            keep_aggregates: true,
+            converter: None,
+            vacuum: true,
+        };
+        let summary = crate::storage::prune(&storage, policy).await.unwrap();
+        let history = storage.price_history(TrendSubject::Modifier, "+# to maximum Life").await.unwrap();
+        let remaining = storage.collected_items().await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // The modifier, the base type and the ring category
+        assert_eq!((summary.listings_deleted, summary.aggregates_added), (2, 3));
+        assert!(summary.vacuumed);
+        assert_eq!(history.len(), 1);
+        assert_eq!((history[0].day.as_str(), history[0].median, history[0].volume, history[0].listings), ("2020-03-01", 5.0, 2, 2));
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].trade_id, "recent");
+    }
+
+    #[tokio::test]
+    async fn test_refresh_aggregates_buckets_by_value() {
+        let (storage, path) = test_storage("aggregates").await;
+        for (id, life, price) in [("a", 40.0, 2.0), ("b", 50.0, 4.0), ("c", 90.0, 10.0), ("d", 100.0, 14.0)] {
+            let mut item = ring(id, price);
+            item.add_modifier(ItemModifier {
+                name: format!("+{} to maximum Life", life),
+                tier: Some(2),
+                values: vec![life],
+                rolls: Vec::new(),
+                is_crafted: false,
+                kind: Default::default(),
+                stat_requirements: None,
+                attribute_scaling: None,
+                stat_hashes: Vec::new(),
+            });
+            storage.store_collected_item(&item, None).await.unwrap();
+        }
+        sqlx::query!("UPDATE collected_items SET last_seen_at = '2020-03-01 12:00:00' WHERE trade_id = 'a'")
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+
+        let summary = crate::storage::refresh_aggregates(&storage, 2).await.unwrap();
+        let buckets = storage.modifier_price_buckets("+# to maximum Life").await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!((summary.listings, summary.modifiers, summary.buckets), (4, 1, 3));
+        let rows: Vec<(&str, u32, f64, u32, f64)> = buckets
+            .iter()
+            .map(|b| (b.day.as_str(), b.bucket, b.value_min, b.listings, b.median))
+            .collect();
+        assert_eq!(rows[0], ("2020-03-01", 0, 40.0, 1, 2.0));
+        assert_eq!((rows[1].1, rows[1].3, rows[1].4), (0, 1, 4.0));
+        assert_eq!((rows[2].1, rows[2].2, rows[2].3, rows[2].4), (1, 70.0, 2, 12.0));
+        assert_eq!(buckets[2].value_max, 100.0);
+    }
+
+    #[tokio::test]
+    async fn test_dataset_partitioned_by_collection_day() {
+        let (storage, path) = test_storage("dataset").await;
+        for id in ["first", "second"] {
+            let mut item = ring(id, 2.0);
+            item.add_modifier(ItemModifier {
+                name: "+30 to maximum Life".to_string(),
+                tier: Some(3),
+                values: vec![30.0],
+                rolls: Vec::new(),
+                is_crafted: false,
+                kind: Default::default(),
+                stat_requirements: None,
+                attribute_scaling: None,
+                stat_hashes: Vec::new(),
+            });
+            storage.store_collected_item(&item, None).await.unwrap();
+        }
+        sqlx::query!("UPDATE collected_items SET collected_at = '2025-01-01 08:00:00' WHERE trade_id = 'first'")
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("poe2-dataset-test-{}", std::process::id()));
+        let summary = crate::storage::export_dataset(&storage, &dir, crate::storage::DatasetFormat::Jsonl, Default::default())
+            .await
+            .unwrap();
+        let first_day = std::fs::read_to_string(dir.join("collected_items/day=2025-01-01/part-0.jsonl")).unwrap();
+        let modifiers = std::fs::read_to_string(dir.join("modifiers.jsonl")).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!((summary.listings, summary.modifiers, summary.item_modifiers, summary.partitions), (2, 1, 2, 2));
+        let row: serde_json::Value = serde_json::from_str(first_day.trim()).unwrap();
+        assert_eq!(row["trade_id"], "first");
+        assert_eq!(modifiers.lines().count(), 1);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_parquet_dataset_typed_and_partitioned() {
+        use arrow_array::cast::AsArray;
+        use arrow_array::types::{Float64Type, TimestampSecondType};
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let (storage, path) = test_storage("parquet").await;
+        storage.store_collected_item(&ring("first", 2.0), None).await.unwrap();
+        storage.store_collected_item(&ring("second", 3.0), None).await.unwrap();
+        sqlx::query!("UPDATE collected_items SET collected_at = '2025-01-01 08:00:00' WHERE trade_id = 'first'")
+            .execute(&storage.pool)
+            .await
+            .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("poe2-parquet-test-{}", std::process::id()));
+        let summary = crate::storage::export_dataset(&storage, &dir, crate::storage::DatasetFormat::Parquet, crate::storage::compression::Compression::Gzip)
+            .await
+            .unwrap();
+        let file = std::fs::File::open(dir.join("collected_items/day=2025-01-01/part-0.parquet")).unwrap();
+        let batches: Vec<_> = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        let modifiers_written = dir.join("modifiers.parquet").exists();
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(summary.partitions, 2);
+        assert!(modifiers_written);
+        let batch = &batches[0];
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(batch.column_by_name("trade_id").unwrap().as_string::<i32>().value(0), "first");
+        let price = batch.column_by_name("price_amount").unwrap().as_primitive::<Float64Type>();
+        assert_eq!(price.value(0), 2.0);
+        let collected_at = batch.column_by_name("collected_at").unwrap().as_primitive::<TimestampSecondType>();
+        assert_eq!(crate::analyzer::format_timestamp(collected_at.value(0) as u64), "2025-01-01 08:00:00");
+    }
+
+    #[cfg(feature = "parquet")]
+    #[tokio::test]
+    async fn test_parquet_round_trip() {
+        let (storage, path) = test_storage("parquet-export").await;
+        for (id, amount) in [("first", 5.0), ("second", 7.5)] {
+            let raw = ListingBuilder::new(id, "Iron Ring").with_price(amount).response();
+            storage.store_collected_item(&Item::try_from(raw.clone()).unwrap(), Some(&raw)).await.unwrap();
+        }
+        sqlx::query!(
+            "UPDATE collected_items SET collected_at = '2025-01-01 08:00:00', delisted_at = '2025-01-03 09:30:00' WHERE trade_id = 'first'"
+        )
+        .execute(&storage.pool)
+        .await
+        .unwrap();
+
+        let dir = std::env::temp_dir().join(format!("poe2-parquet-round-trip-{}", std::process::id()));
+        crate::storage::export_dataset(&storage, &dir, crate::storage::DatasetFormat::Parquet, Default::default())
+            .await
+            .unwrap();
+        let (imported, imported_path) = test_storage("parquet-import").await;
+        let converter = crate::data::currency_converter::CurrencyConverter::default();
+        let summary = crate::storage::import_listings(&imported, &dir, &converter).await.unwrap();
+        // A second import finds every listing already stored as recently
+        let again = crate::storage::import_listings(&imported, &dir, &converter).await.unwrap();
+        let exported = storage.collected_items().await.unwrap();
+        let records = imported.collected_items().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&imported_path);
+
+        assert_eq!((summary.imported, summary.missing_raw, summary.failed), (2, 0, 0));
+        assert_eq!((again.imported, again.kept_existing), (0, 2));
+        let fields = |records: &[CollectedItemRecord]| -> Vec<_> {
+            records
+                .iter()
+                .map(|record| (
+                    record.trade_id.clone(),
+                    record.price_amount,
+                    record.price_currency.clone(),
+                    record.collected_at.clone(),
+                    record.last_seen_at.clone(),
+                    record.delisted_at.clone(),
+                    record.raw_json.is_some(),
+                ))
+                .collect()
+        };
+        assert_eq!(fields(&records), fields(&exported));
+    }
+
+    #[tokio::test]
+    async fn test_import_keeps_newest_copy() {
+        let (storage, path) = test_storage("import").await;
+        storage.store_collected_item(&ring("live", 5.0), None).await.unwrap();
+        let old = ListingTimes {
+            collected_at: "2020-01-01 00:00:00".to_string(),
+            last_seen_at: "2020-01-02 00:00:00".to_string(),
+            delisted_at: Some("2020-01-03 00:00:00".to_string()),
+        };
+
+        let replaced_live = storage.import_collected_item(&ring("live", 1.0), None, &old).await.unwrap();
+        let added = storage.import_collected_item(&ring("imported", 2.0), None, &old).await.unwrap();
+        let records = storage.collected_items().await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(!replaced_live);
+        assert!(added);
+        let live = records.iter().find(|record| record.trade_id == "live").unwrap();
+        assert_eq!(live.price_amount, Some(5.0));
+        assert_eq!(live.collected_at, old.collected_at);
+        assert!(live.delisted_at.is_none());
+        let imported = records.iter().find(|record| record.trade_id == "imported").unwrap();
+        assert_eq!((&imported.last_seen_at, &imported.delisted_at), (&old.last_seen_at, &old.delisted_at));
+    }
+
+    #[tokio::test]
+    async fn test_modifiers_keyed_by_stat_hash() {
+        let (storage, path) = test_storage("stat-hash").await;
+        for (id, hashes) in [("unhashed", vec![]), ("first", vec!["explicit.stat_1"]), ("second", vec!["explicit.stat_2"])] {
+            let mut item = ring(id, 1.0);
+            item.add_modifier(ItemModifier {
+                name: "Adds 1 to 2 Fire Damage".to_string(),
+                tier: None,
+                values: vec![1.0, 2.0],
+                rolls: Vec::new(),
+                is_crafted: false,
+                kind: Default::default(),
+                stat_requirements: None,
+                attribute_scaling: None,
+                stat_hashes: hashes.into_iter().map(str::to_string).collect(),
+            });
+            storage.store_collected_item(&item, None).await.unwrap();
+        }
+
+        let modifiers = storage.modifier_records().await.unwrap();
+        let second_stat = storage.items_with_modifier("explicit.stat_2", None).await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        // The first hashed listing claims the row stored without hashes; the second is a different stat
+        let keys: Vec<&str> = modifiers.iter().map(|modifier| modifier.stat_key.as_str()).collect();
+        assert_eq!(keys, vec!["explicit.stat_1", "explicit.stat_2"]);
+        let ids: Vec<&str> = second_stat.iter().map(|item| item.id.as_str()).collect();
+        assert_eq!(ids, vec!["second"]);
+    }
+
+    #[tokio::test]
+    async fn test_raw_listing_kept_for_reprocessing() {
+        let (storage, path) = test_storage("raw").await;
+        let raw: ItemResponse = serde_json::from_value(serde_json::json!({
+            "id": "listing1",
+            "item": {
+                "base_type": "Iron Ring",
+                "explicitMods": [],
+                "extended": { "mods": { "explicit": [] }, "hashes": { "explicit": [] } },
+                "frameType": 2,
+                "requirements": [],
+                "properties": [],
+                "rarity": "Rare",
+                "typeLine": "Iron Ring",
+                "ilvl": 80,
+                "sockets": [{ "group": 0, "type": "rune" }]
+            },
+            "listing": {
+                "price": { "amount": 5.0, "currency": "exalted" },
+                "account": { "name": "seller", "realm": "poe2" },
+                "whisper": "@seller hi",
+                "stash": { "name": "~price 5 exalted", "x": 3, "y": 7 },
+                "indexed": "2025-01-01T12:30:00Z"
+            }
+        }))
+        .unwrap();
+        storage.store_collected_item(&ring("listing1", 5.0), Some(&raw)).await.unwrap();
+        // Seen again without a payload; the stored one stays
+        storage.store_collected_item(&ring("listing1", 5.0), None).await.unwrap();
+
+        let stored = storage.raw_listings_after(0, 10).await.unwrap();
+        assert_eq!(stored.len(), 1);
+        let reparsed: ItemResponse = serde_json::from_str(&stored[0].1).unwrap();
+        assert_eq!(reparsed.item.sockets[0].kind.as_deref(), Some("rune"));
+        assert_eq!(reparsed.listing.whisper.as_deref(), Some("@seller hi"));
+
+        // Reprocessing the raw listing stores its seller contact, which a sighting without
+        // one leaves in place
+        assert!(storage.update_collected_item(&Item::try_from(reparsed).unwrap()).await.unwrap());
+        storage.store_collected_item(&ring("listing1", 5.0), None).await.unwrap();
+        let contact = storage.items_after(0, 10, None).await.unwrap().remove(0).item.contact.unwrap();
+        assert_eq!(contact.whisper.as_deref(), Some("@seller hi"));
+        assert_eq!(contact.stash, Some(crate::models::StashLocation { name: "~price 5 exalted".to_string(), x: 3, y: 7 }));
+        assert_eq!(contact.indexed_at.as_deref(), Some("2025-01-01 12:30:00"));
+
+        let before = storage.collected_items().await.unwrap();
+        assert!(storage.update_collected_item(&ring("listing1", 7.0)).await.unwrap());
+        assert!(!storage.update_collected_item(&ring("unknown", 7.0)).await.unwrap());
+        let after = storage.collected_items().await.unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(after[0].price_amount, Some(7.0));
+        assert_eq!(after[0].last_seen_at, before[0].last_seen_at);
+    }
+}
@@ -0,0 +1,386 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::{Event as TracingEvent, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tokio_util::sync::CancellationToken;
+use crate::errors::Result;
+use crate::fetcher::RateBudget;
+use crate::models::Item;
+use crate::notifier::NotifyCriteria;
+
+// How often the dashboard redraws
+const REFRESH_INTERVAL: Duration = Duration::from_millis(250);
+
+// Entries kept in each of the dashboard's lists
+const RECENT_ERRORS: usize = 8;
+const RECENT_LISTINGS: usize = 10;
+const TOP_MODIFIERS: usize = 10;
+
+// Warnings and errors logged while the dashboard is up, which would otherwise be written
+// over it. ErrorLog::layer() collects them for the dashboard to show.
+#[derive(Debug, Default)]
+pub struct ErrorLog {
+    entries: Mutex<VecDeque<String>>,
+}
+
+impl ErrorLog {
+    pub fn global() -> &'static ErrorLog {
+        static GLOBAL: OnceLock<ErrorLog> = OnceLock::new();
+        GLOBAL.get_or_init(ErrorLog::default)
+    }
+
+    // A tracing layer sending warnings and errors to the global log
+    pub fn layer() -> ErrorLayer {
+        ErrorLayer
+    }
+
+    fn entries(&self) -> MutexGuard<'_, VecDeque<String>> {
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn push(&self, entry: String) {
+        let mut entries = self.entries();
+        entries.push_back(entry);
+        if entries.len() > RECENT_ERRORS {
+            entries.pop_front();
+        }
+    }
+
+    // Newest first
+    pub fn recent(&self) -> Vec<String> {
+        self.entries().iter().rev().cloned().collect()
+    }
+}
+
+pub struct ErrorLayer;
+
+impl<S: Subscriber> Layer<S> for ErrorLayer {
+    fn on_event(&self, event: &TracingEvent<'_>, _ctx: Context<'_, S>) {
+        let level = *event.metadata().level();
+        if level > Level::WARN {
+            return;
+        }
+        let mut message = EventMessage::default();
+        event.record(&mut message);
+        ErrorLog::global().push(format!("{} {}{}", level, message.message, message.fields));
+    }
+}
+
+// An event's message followed by its other fields as " key=value"
+#[derive(Default)]
+struct EventMessage {
+    message: String,
+    fields: String,
+}
+
+impl Visit for EventMessage {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            let _ = write!(self.fields, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            let _ = write!(self.fields, " {}={}", field.name(), value);
+        }
+    }
+}
+
+// A stored listing as the dashboard lists it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListingLine {
+    pub name: String,
+    pub price: String,
+    pub modifiers: String,
+}
+
+impl ListingLine {
+    fn of(item: &Item) -> Self {
+        let name = match &item.name {
+            Some(name) => format!("{} {}", name, item.item_type.base_type),
+            None => item.item_type.base_type.clone(),
+        };
+        let price = item.price.as_ref()
+            .map(|price| format!("{} {}", price.amount, price.currency))
+            .unwrap_or_default();
+        let modifiers = item.modifiers.iter().map(|m| m.name.as_str()).collect::<Vec<_>>().join(", ");
+        Self { name, price, modifiers }
+    }
+}
+
+// What the dashboard shows about a collection run
+#[derive(Debug)]
+pub struct DashboardState {
+    started: Instant,
+    items: u64,
+    queued: usize,
+    modifier_counts: HashMap<String, u64>,
+    listings: VecDeque<ListingLine>,
+    criteria: NotifyCriteria,
+    finished: Option<String>,
+}
+
+impl DashboardState {
+    // Listings matching `criteria` are the interesting ones; without any, every listing is
+    pub fn new(criteria: NotifyCriteria) -> Self {
+        Self {
+            started: Instant::now(),
+            items: 0,
+            queued: 0,
+            modifier_counts: HashMap::new(),
+            listings: VecDeque::new(),
+            criteria,
+            finished: None,
+        }
+    }
+
+    // A listing was stored, with `queued` more fetched listings waiting to be
+    pub fn record_item(&mut self, item: &Item, queued: usize) {
+        self.items += 1;
+        self.queued = queued;
+        for modifier in &item.modifiers {
+            *self.modifier_counts.entry(modifier.template()).or_default() += 1;
+        }
+        if self.criteria.matches(item) {
+            self.listings.push_front(ListingLine::of(item));
+            self.listings.truncate(RECENT_LISTINGS);
+        }
+    }
+
+    pub fn finish(&mut self, outcome: String) {
+        self.finished = Some(outcome);
+    }
+
+    pub fn items(&self) -> u64 {
+        self.items
+    }
+
+    pub fn items_per_minute(&self) -> f64 {
+        let minutes = self.started.elapsed().as_secs_f64() / 60.0;
+        if minutes > 0.0 {
+            self.items as f64 / minutes
+        } else {
+            0.0
+        }
+    }
+
+    // Modifier templates seen on the most listings, most first
+    pub fn top_modifiers(&self) -> Vec<(&str, u64)> {
+        let mut counts: Vec<(&str, u64)> = self.modifier_counts
+            .iter()
+            .map(|(template, &count)| (template.as_str(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        counts.truncate(TOP_MODIFIERS);
+        counts
+    }
+
+    // Newest first
+    pub fn listings(&self) -> impl Iterator<Item = &ListingLine> {
+        self.listings.iter()
+    }
+}
+
+// The dashboard's state, shared between the collection feeding it and the loop drawing it
+#[derive(Debug, Clone)]
+pub struct Dashboard {
+    state: Arc<Mutex<DashboardState>>,
+    // Cancelled by the first quit during the run so the run can wind down
+    shutdown: Option<CancellationToken>,
+}
+
+impl Dashboard {
+    pub fn new(criteria: NotifyCriteria) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(DashboardState::new(criteria))),
+            shutdown: None,
+        }
+    }
+
+    // Quitting during the run cancels `token` and waits for the run to finish its current
+    // batch and save; quitting again leaves at once
+    pub fn with_shutdown(mut self, token: CancellationToken) -> Self {
+        self.shutdown = Some(token);
+        self
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, DashboardState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn record_item(&self, item: &Item, queued: usize) {
+        self.state().record_item(item, queued);
+    }
+
+    // Draw the dashboard while `work` runs, until it's done and the user presses q, or
+    // until they press q or Ctrl-C before, which drops `work` unfinished (with a shutdown
+    // token, only the second time). Returns what `work` did, or None when it was cut short.
+    pub async fn run<T>(&self, work: impl Future<Output = Result<T>>) -> Result<Option<T>> {
+        let mut terminal = ratatui::init();
+        let outcome = self.draw_while(&mut terminal, work).await;
+        ratatui::restore();
+        outcome
+    }
+
+    async fn draw_while<T>(&self, terminal: &mut DefaultTerminal, work: impl Future<Output = Result<T>>) -> Result<Option<T>> {
+        let mut work = std::pin::pin!(work);
+        let mut result = None;
+        let mut ticks = tokio::time::interval(REFRESH_INTERVAL);
+
+        loop {
+            if result.is_none() {
+                tokio::select! {
+                    done = &mut work => {
+                        let outcome = match &done {
+                            Ok(_) => "collection finished".to_string(),
+                            Err(e) => format!("collection failed: {}", e),
+                        };
+                        // A run stopped by quitting closes the dashboard as soon as it's saved
+                        if self.shutdown.as_ref().is_some_and(CancellationToken::is_cancelled) {
+                            return done.map(Some);
+                        }
+                        self.state().finish(format!("{}, press q to exit", outcome));
+                        result = Some(done);
+                    }
+                    _ = ticks.tick() => {}
+                }
+            } else {
+                ticks.tick().await;
+            }
+
+            terminal.draw(|frame| render(frame, &self.state(), RateBudget::global().requests_sent(), &ErrorLog::global().recent()))?;
+            if quit_requested()? {
+                match &self.shutdown {
+                    Some(token) if result.is_none() && !token.is_cancelled() => {
+                        token.cancel();
+                        self.state().finish("stopping after the current batch, q again to quit now".to_string());
+                    }
+                    _ => return result.transpose(),
+                }
+            }
+        }
+    }
+}
+
+// Drain pending terminal events, reporting whether one asked to quit
+fn quit_requested() -> Result<bool> {
+    while event::poll(Duration::ZERO)? {
+        if let Event::Key(key) = event::read()? {
+            let ctrl_c = key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+            if key.kind == KeyEventKind::Press && (matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) || ctrl_c) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+pub fn render(frame: &mut Frame, state: &DashboardState, requests: u64, errors: &[String]) {
+    let [counters, middle, listings] = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(8), Constraint::Length(RECENT_LISTINGS as u16 + 3)])
+        .areas(frame.area());
+    let [modifiers, recent_errors] = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+        .areas(middle);
+
+    render_counters(frame, counters, state, requests);
+
+    let top: Vec<Row> = state.top_modifiers()
+        .into_iter()
+        .map(|(template, count)| Row::new([count.to_string(), template.to_string()]))
+        .collect();
+    frame.render_widget(
+        Table::new(top, [Constraint::Length(7), Constraint::Fill(1)])
+            .block(Block::default().borders(Borders::ALL).title(" Top modifiers ")),
+        modifiers,
+    );
+
+    let errors: Vec<ListItem> = errors.iter().map(|error| ListItem::new(error.as_str())).collect();
+    frame.render_widget(
+        List::new(errors)
+            .style(Style::default().fg(Color::Yellow))
+            .block(Block::default().borders(Borders::ALL).title(" Recent errors ")),
+        recent_errors,
+    );
+
+    let rows: Vec<Row> = state.listings()
+        .map(|listing| Row::new([listing.name.clone(), listing.price.clone(), listing.modifiers.clone()]))
+        .collect();
+    frame.render_widget(
+        Table::new(rows, [Constraint::Percentage(30), Constraint::Length(14), Constraint::Fill(1)])
+            .header(Row::new(["Item", "Price", "Modifiers"]).style(Style::default().add_modifier(Modifier::BOLD)))
+            .block(Block::default().borders(Borders::ALL).title(" Latest interesting listings ")),
+        listings,
+    );
+}
+
+fn render_counters(frame: &mut Frame, area: Rect, state: &DashboardState, requests: u64) {
+    let elapsed = state.started.elapsed().as_secs();
+    let status = state.finished.clone().unwrap_or_else(|| "collecting, q to stop".to_string());
+    let line = Line::from(vec![
+        Span::raw(format!("Requests {}  ", requests)),
+        Span::raw(format!("Items {}  ", state.items)),
+        Span::raw(format!("Items/min {:.1}  ", state.items_per_minute())),
+        Span::raw(format!("Queue {}  ", state.queued)),
+        Span::raw(format!("Elapsed {:02}:{:02}:{:02}  ", elapsed / 3600, elapsed / 60 % 60, elapsed % 60)),
+        Span::styled(status, Style::default().add_modifier(Modifier::BOLD)),
+    ]);
+    frame.render_widget(
+        Paragraph::new(line).block(Block::default().borders(Borders::ALL).title(" Collection ")),
+        area,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::fixtures::{explicit_modifier, ring};
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    fn ring_with(id: &str, price: f64, modifiers: &[&str]) -> Item {
+        let mut item = ring(id, price);
+        for name in modifiers {
+            item.add_modifier(explicit_modifier(name, &[]));
+        }
+        item
+    }
+
+    #[test]
+    fn test_dashboard_counts_and_renders_listings() {
+        let criteria = NotifyCriteria { modifiers: Vec::new(), max_price: Some(5.0) };
+        let mut state = DashboardState::new(criteria);
+        state.record_item(&ring_with("a", 3.0, &["+80 to maximum Life", "+20% to Fire Resistance"]), 4);
+        state.record_item(&ring_with("b", 9.0, &["+55 to maximum Life"]), 2);
+
+        assert_eq!(state.items(), 2);
+        assert_eq!(state.top_modifiers(), vec![("+# to maximum Life", 2), ("+#% to Fire Resistance", 1)]);
+        assert_eq!(state.listings().count(), 1);
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 30)).unwrap();
+        terminal.draw(|frame| render(frame, &state, 17, &["WARN search failed".to_string()])).unwrap();
+        let screen: String = terminal.backend().buffer().content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("Requests 17"));
+        assert!(screen.contains("Queue 2"));
+        assert!(screen.contains("+# to maximum Life"));
+        assert!(screen.contains("WARN search failed"));
+        assert!(screen.contains("3 exalted"));
+    }
+}